@@ -1,6 +1,7 @@
-use anyhow::Result;
-use clap::Subcommand;
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::trusted_network::TrustedNetwork;
 
 use super::BooleanOption;
 
@@ -10,6 +11,39 @@ pub enum AutoConnect {
     Get,
     /// Change auto-connect setting
     Set { policy: BooleanOption },
+    /// Manage trusted networks. When enabled, the tunnel is disconnected on trusted networks and
+    /// connected on all other networks. Networks are identified by the MAC address of the
+    /// gateway, or by the Wi-Fi SSID on Linux
+    #[clap(subcommand)]
+    TrustedNetworks(TrustedNetworks),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrustedNetworks {
+    /// Display the trusted networks and the current network
+    Get,
+    /// Enable or disable connecting and disconnecting based on the current network
+    Set { policy: BooleanOption },
+    /// Trust a network
+    Add(NetworkArgs),
+    /// Stop trusting a network
+    Remove(NetworkArgs),
+    /// Remove all trusted networks
+    Clear,
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+pub struct NetworkArgs {
+    /// Wi-Fi network name
+    #[arg(long)]
+    ssid: Option<String>,
+    /// MAC address of the gateway
+    #[arg(long)]
+    gateway_mac: Option<String>,
+    /// The network that the device is currently connected to
+    #[arg(long)]
+    current: bool,
 }
 
 impl AutoConnect {
@@ -17,6 +51,7 @@ impl AutoConnect {
         match self {
             AutoConnect::Get => Self::get().await,
             AutoConnect::Set { policy } => Self::set(policy).await,
+            AutoConnect::TrustedNetworks(cmd) => cmd.handle().await,
         }
     }
 
@@ -34,3 +69,62 @@ impl AutoConnect {
         Ok(())
     }
 }
+
+impl TrustedNetworks {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut settings = rpc.get_settings().await?.trusted_networks;
+        match self {
+            TrustedNetworks::Get => {
+                println!(
+                    "Trusted networks: {}",
+                    BooleanOption::from(settings.enabled)
+                );
+                for network in &settings.networks {
+                    println!("\t{network}");
+                }
+                let current = rpc.get_current_network().await?;
+                if let Some(ssid) = current.ssid {
+                    println!("Current SSID: {ssid}");
+                }
+                if let Some(mac) = current.gateway_mac {
+                    println!("Current gateway MAC: {mac}");
+                }
+                return Ok(());
+            }
+            TrustedNetworks::Set { policy } => settings.enabled = *policy,
+            TrustedNetworks::Add(args) => {
+                let network = args.into_network(&mut rpc).await?;
+                if !settings.add(network) {
+                    println!("The network is already trusted");
+                    return Ok(());
+                }
+            }
+            TrustedNetworks::Remove(args) => {
+                let network = args.into_network(&mut rpc).await?;
+                if !settings.remove(&network) {
+                    return Err(anyhow!("The network is not trusted"));
+                }
+            }
+            TrustedNetworks::Clear => settings.networks.clear(),
+        }
+        rpc.set_trusted_network_settings(settings).await?;
+        println!("Updated trusted networks");
+        Ok(())
+    }
+}
+
+impl NetworkArgs {
+    async fn into_network(self, rpc: &mut MullvadProxyClient) -> Result<TrustedNetwork> {
+        if let Some(ssid) = self.ssid {
+            return Ok(TrustedNetwork::Ssid(ssid));
+        }
+        if let Some(mac) = self.gateway_mac {
+            return Ok(TrustedNetwork::GatewayMac(mac));
+        }
+        rpc.get_current_network()
+            .await?
+            .identity()
+            .ok_or(anyhow!("The current network could not be identified"))
+    }
+}
@@ -1,14 +1,80 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_relay_selector::{
+    matcher::RelayExplanation, GetRelay, RelaySelector, RuntimeParameters, SelectorConfig,
+    WireguardConfig,
+};
 use mullvad_types::{
     constraints::Constraint,
     relay_constraints::{RelayConstraints, RelaySettings},
+    relay_list::RelayList,
+    settings::Settings,
 };
+use std::path::{Path, PathBuf};
+use talpid_types::firewall::{FirewallInspectionRequest, FirewallPolicyKind};
+
+use super::BooleanOption;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum DebugCommands {
     /// Block all internet connection by setting an invalid relay constraint.
     BlockConnection,
+
+    /// Simulate relay selection offline, without connecting to the daemon, and explain why
+    /// relays were not selected.
+    RelayQuery {
+        /// Path to a relay list, such as the cached `relays.json`
+        #[arg(long)]
+        relays: PathBuf,
+        /// Path to a settings file, such as `settings.json`
+        #[arg(long)]
+        settings: PathBuf,
+        /// The retry attempt to simulate
+        #[arg(long, default_value_t = 0)]
+        retry_attempt: usize,
+        /// Assume that IPv6 is available
+        #[arg(long)]
+        ipv6: bool,
+    },
+
+    /// Print the firewall ruleset that the daemon installs for a policy, without applying it
+    /// (Linux only). Policies other than the current one use the current settings, as well as
+    /// the current tunnel if there is one
+    Firewall {
+        /// The policy to render
+        #[arg(long, value_enum, default_value_t = FirewallPolicy::Current)]
+        policy: FirewallPolicy,
+        /// Override the local network sharing setting of the rendered policy
+        #[arg(long, value_parser = BooleanOption::custom_parser("allow", "block"))]
+        allow_lan: Option<BooleanOption>,
+        /// Print the ruleset that is currently installed instead
+        #[arg(long, conflicts_with = "diff")]
+        live: bool,
+        /// Print the differences between the rendered and the installed ruleset. Fails if there
+        /// are any
+        #[arg(long)]
+        diff: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum FirewallPolicy {
+    Current,
+    Blocked,
+    Connecting,
+    Connected,
+}
+
+impl From<FirewallPolicy> for FirewallPolicyKind {
+    fn from(policy: FirewallPolicy) -> Self {
+        match policy {
+            FirewallPolicy::Current => FirewallPolicyKind::Current,
+            FirewallPolicy::Blocked => FirewallPolicyKind::Blocked,
+            FirewallPolicy::Connecting => FirewallPolicyKind::Connecting,
+            FirewallPolicy::Connected => FirewallPolicyKind::Connected,
+        }
+    }
 }
 
 impl DebugCommands {
@@ -41,6 +107,245 @@ impl DebugCommands {
                 eprintln!("WARNING: ENTERED BLOCKED MODE");
                 Ok(())
             }
+            DebugCommands::RelayQuery {
+                relays,
+                settings,
+                retry_attempt,
+                ipv6,
+            } => relay_query(&relays, &settings, retry_attempt, ipv6),
+            DebugCommands::Firewall {
+                policy,
+                allow_lan,
+                live,
+                diff,
+            } => {
+                let request = FirewallInspectionRequest {
+                    policy: policy.into(),
+                    allow_lan: allow_lan.map(|allow_lan| *allow_lan),
+                };
+                inspect_firewall(request, live, diff).await
+            }
+        }
+    }
+}
+
+async fn inspect_firewall(
+    request: FirewallInspectionRequest,
+    live: bool,
+    diff: bool,
+) -> Result<()> {
+    let mut rpc = MullvadProxyClient::new().await?;
+    let inspection = rpc.inspect_firewall(request).await?;
+
+    if live {
+        match inspection.live_ruleset {
+            Some(ruleset) => print!("{ruleset}"),
+            None => println!("No ruleset is installed"),
+        }
+        return Ok(());
+    }
+
+    match &inspection.policy {
+        Some(policy) => println!("# Policy: {policy}"),
+        None => println!("# Policy: none"),
+    }
+    let expected = inspection.expected_ruleset.unwrap_or_default();
+    if !diff {
+        print!("{expected}");
+        return Ok(());
+    }
+
+    let live = inspection.live_ruleset.unwrap_or_default();
+    let mut differs = false;
+    for line in diff_lines(&expected, &live) {
+        differs |= !matches!(line, DiffLine::Same(_));
+        println!("{line}");
+    }
+    if differs {
+        return Err(anyhow!(
+            "The installed ruleset differs from the expected one"
+        ));
+    }
+    println!("# The installed ruleset matches the expected one");
+    Ok(())
+}
+
+enum DiffLine<'a> {
+    Same(&'a str),
+    Expected(&'a str),
+    Live(&'a str),
+}
+
+impl std::fmt::Display for DiffLine<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffLine::Same(line) => write!(f, " {line}"),
+            DiffLine::Expected(line) => write!(f, "-{line}"),
+            DiffLine::Live(line) => write!(f, "+{line}"),
+        }
+    }
+}
+
+/// Compute a line diff between `expected` and `live` using their longest common subsequence.
+/// Lines only in `expected` are prefixed by `-`, and lines only in `live` by `+`.
+fn diff_lines<'a>(expected: &'a str, live: &'a str) -> Vec<DiffLine<'a>> {
+    let expected: Vec<_> = expected.lines().collect();
+    let live: Vec<_> = live.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of expected[i..] and live[j..]
+    let mut lcs = vec![vec![0usize; live.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..live.len()).rev() {
+            lcs[i][j] = if expected[i] == live[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() && j < live.len() {
+        if expected[i] == live[j] {
+            lines.push(DiffLine::Same(expected[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Expected(expected[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Live(live[j]));
+            j += 1;
+        }
+    }
+    lines.extend(expected[i..].iter().map(|line| DiffLine::Expected(line)));
+    lines.extend(live[j..].iter().map(|line| DiffLine::Live(line)));
+    lines
+}
+
+fn relay_query(
+    relays_path: &Path,
+    settings_path: &Path,
+    retry_attempt: usize,
+    ipv6: bool,
+) -> Result<()> {
+    let relay_list: RelayList = read_json(relays_path).context("Failed to read relay list")?;
+    let settings: Settings = read_json(settings_path).context("Failed to read settings")?;
+    let relay_selector =
+        RelaySelector::from_list(SelectorConfig::from_settings(&settings), relay_list);
+    let runtime_params = RuntimeParameters { ipv6 };
+
+    match relay_selector.get_relay(retry_attempt, runtime_params.clone()) {
+        Ok(GetRelay::Wireguard {
+            endpoint,
+            obfuscator,
+            inner,
+        }) => {
+            match inner {
+                WireguardConfig::Singlehop { exit } => println!("Exit relay: {}", exit.hostname),
+                WireguardConfig::Multihop { exit, entry } => {
+                    println!("Exit relay: {}", exit.hostname);
+                    println!("Entry relay: {}", entry.hostname);
+                }
+            }
+            if let Some(obfuscator) = obfuscator {
+                println!("Obfuscator: {}", obfuscator.relay.hostname);
+            }
+            println!("Endpoint: {}", endpoint.peer.endpoint);
+        }
+        Ok(GetRelay::OpenVpn {
+            endpoint,
+            exit,
+            bridge,
+        }) => {
+            println!("Exit relay: {}", exit.hostname);
+            if let Some(bridge) = bridge.as_ref().and_then(|bridge| bridge.relay()) {
+                println!("Bridge: {}", bridge.hostname);
+            }
+            println!("Endpoint: {endpoint}");
+        }
+        Ok(GetRelay::Custom(endpoint)) => {
+            println!("Custom endpoint: {endpoint}");
+            return Ok(());
         }
+        Err(error) => println!("No relay selected: {error}"),
+    }
+
+    let Some(explanation) =
+        relay_selector.explain_relay_selection(retry_attempt, runtime_params)?
+    else {
+        return Ok(());
+    };
+    println!();
+    print_explanation("Exit", &explanation.exit);
+    if let Some(entry) = &explanation.entry {
+        println!();
+        print_explanation("Entry", entry);
+    }
+    Ok(())
+}
+
+fn print_explanation(role: &str, explanations: &[RelayExplanation]) {
+    let (matching, rejected): (Vec<_>, Vec<_>) = explanations
+        .iter()
+        .partition(|explanation| explanation.rejection.is_none());
+    println!(
+        "{role} relay candidates ({} of {} relays):",
+        matching.len(),
+        explanations.len()
+    );
+    for explanation in &matching {
+        println!("\t{}", explanation.hostname);
+    }
+
+    println!("Rejected {} relays:", rejected.len());
+    let rejected_by_reason = rejected
+        .iter()
+        .filter_map(|explanation| Some((explanation.rejection?, &explanation.hostname)))
+        .into_group_map();
+    for (rejection, hostnames) in rejected_by_reason
+        .into_iter()
+        .sorted_by_key(|(_, hostnames)| std::cmp::Reverse(hostnames.len()))
+    {
+        println!("\t{} relays {rejection}:", hostnames.len());
+        for hostname in hostnames {
+            println!("\t\t{hostname}");
+        }
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::diff_lines;
+
+    fn render_diff(expected: &str, live: &str) -> Vec<String> {
+        diff_lines(expected, live)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(render_diff("a\nb\n", "a\nb\n"), [" a", " b"]);
+        assert!(render_diff("", "").is_empty());
+        assert_eq!(render_diff("a\nb\n", ""), ["-a", "-b"]);
+        assert_eq!(render_diff("", "a\n"), ["+a"]);
+        assert_eq!(
+            render_diff("a\nb\nc\nd\n", "a\nx\nc\nd\ny\n"),
+            [" a", "-b", "+x", " c", " d", "+y"]
+        );
+        assert_eq!(
+            render_diff("a\nb\nc\n", "c\na\nb\n"),
+            ["+c", " a", " b", "-c"]
+        );
     }
 }
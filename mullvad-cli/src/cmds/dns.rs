@@ -1,8 +1,11 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
-use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+use mullvad_types::settings::{
+    CustomDnsOptions, DefaultDnsOptions, DnsBlocklistSource, DnsOptions, DnsState,
+};
 use std::net::IpAddr;
+use talpid_types::net::dns::EncryptedDnsResolver;
 
 #[derive(Subcommand, Debug)]
 pub enum Dns {
@@ -14,6 +17,78 @@ pub enum Dns {
         #[clap(subcommand)]
         cmd: DnsSet,
     },
+
+    /// Manage DNS-over-HTTPS and DNS-over-TLS resolvers. When any are set, they replace the
+    /// custom DNS servers
+    #[clap(subcommand)]
+    Encrypted(EncryptedDns),
+
+    /// Manage custom blocklists. Queries for blocked domains are refused, whether or not custom
+    /// DNS is used
+    #[clap(subcommand)]
+    Blocklist(DnsBlocklist),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DnsBlocklist {
+    /// Add a blocklist in hosts file format, or with one domain per line
+    Add {
+        /// Path to a local file, or an HTTP(S) URL. Local files must be located in the
+        /// `dns-blocklists` directory in the settings directory of the daemon, such as
+        /// /etc/mullvad-vpn/dns-blocklists
+        source: String,
+    },
+
+    /// Remove a blocklist
+    Remove {
+        /// Path or URL of the blocklist
+        source: String,
+    },
+
+    /// Remove all blocklists
+    Clear,
+
+    /// Manage domains that are never blocked. This also applies to their subdomains
+    #[clap(subcommand)]
+    Allow(AllowedDomain),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AllowedDomain {
+    /// Never block a domain
+    Add { domain: String },
+
+    /// Stop allowing a domain
+    Remove { domain: String },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum EncryptedDns {
+    /// Add an encrypted resolver and enable custom DNS
+    Add {
+        /// URL of the resolver, e.g. "https://dns.quad9.net/dns-query" or "tls://1.1.1.1"
+        url: String,
+
+        /// IP address of the resolver. Required unless the URL contains an IP address
+        #[arg(long = "address")]
+        addresses: Vec<IpAddr>,
+
+        /// Base64 encoded SHA-256 digest of a SubjectPublicKeyInfo in the certificate chain of the
+        /// resolver. If given, only certificate chains containing a pinned public key are
+        /// accepted. A pinned intermediate or root certificate must have issued the certificate
+        /// of the resolver
+        #[arg(long = "spki-pin")]
+        spki_pins: Vec<String>,
+    },
+
+    /// Remove an encrypted resolver
+    Remove {
+        /// URL of the resolver
+        url: String,
+    },
+
+    /// Remove all encrypted resolvers
+    Clear,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -82,6 +157,22 @@ impl Dns {
             Dns::Set {
                 cmd: DnsSet::Custom { servers },
             } => Self::set_custom(servers).await,
+            Dns::Encrypted(EncryptedDns::Add {
+                url,
+                addresses,
+                spki_pins,
+            }) => Self::add_encrypted(url, addresses, spki_pins).await,
+            Dns::Encrypted(EncryptedDns::Remove { url }) => Self::remove_encrypted(url).await,
+            Dns::Encrypted(EncryptedDns::Clear) => Self::clear_encrypted().await,
+            Dns::Blocklist(DnsBlocklist::Add { source }) => Self::add_blocklist(source).await,
+            Dns::Blocklist(DnsBlocklist::Remove { source }) => Self::remove_blocklist(source).await,
+            Dns::Blocklist(DnsBlocklist::Clear) => Self::clear_blocklists().await,
+            Dns::Blocklist(DnsBlocklist::Allow(AllowedDomain::Add { domain })) => {
+                Self::add_allowed_domain(domain).await
+            }
+            Dns::Blocklist(DnsBlocklist::Allow(AllowedDomain::Remove { domain })) => {
+                Self::remove_allowed_domain(domain).await
+            }
         }
     }
 
@@ -105,6 +196,12 @@ impl Dns {
                     options.default_options.block_social_media
                 );
             }
+            DnsState::Custom if !options.custom_options.encrypted_resolvers.is_empty() => {
+                println!("Custom DNS: yes\nEncrypted resolvers:");
+                for resolver in &options.custom_options.encrypted_resolvers {
+                    println!("{resolver}");
+                }
+            }
             DnsState::Custom => {
                 println!("Custom DNS: yes\nServers:");
                 for server in &options.custom_options.addresses {
@@ -113,6 +210,19 @@ impl Dns {
             }
         }
 
+        if options.blocklists.is_enabled() {
+            println!("Blocklists:");
+            for source in &options.blocklists.sources {
+                println!("{source}");
+            }
+        }
+        if !options.blocklists.allowed_domains.is_empty() {
+            println!("Allowed domains:");
+            for domain in &options.blocklists.allowed_domains {
+                println!("{domain}");
+            }
+        }
+
         Ok(())
     }
 
@@ -148,11 +258,145 @@ impl Dns {
         let settings = rpc.get_settings().await?;
         rpc.set_dns_options(DnsOptions {
             state: DnsState::Custom,
-            custom_options: CustomDnsOptions { addresses: servers },
+            custom_options: CustomDnsOptions {
+                addresses: servers,
+                encrypted_resolvers: vec![],
+            },
             ..settings.tunnel_options.dns_options
         })
         .await?;
         println!("Updated DNS settings");
         Ok(())
     }
+
+    async fn add_encrypted(
+        url: String,
+        addresses: Vec<IpAddr>,
+        spki_pins: Vec<String>,
+    ) -> Result<()> {
+        let mut resolver = EncryptedDnsResolver::from_url(&url)?;
+        for address in addresses {
+            if !resolver.addresses.contains(&address) {
+                resolver.addresses.push(address);
+            }
+        }
+        resolver.spki_pins = spki_pins;
+        resolver.validate()?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let resolvers = &mut options.custom_options.encrypted_resolvers;
+        // Replace any resolver with the same URL
+        resolvers.retain(|existing| existing.url() != resolver.url());
+        resolvers.push(resolver);
+        options.state = DnsState::Custom;
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn remove_encrypted(url: String) -> Result<()> {
+        let url = EncryptedDnsResolver::from_url(&url)?.url();
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let resolvers = &mut options.custom_options.encrypted_resolvers;
+        let count = resolvers.len();
+        resolvers.retain(|resolver| resolver.url() != url);
+        if resolvers.len() == count {
+            return Err(anyhow!("No encrypted resolver with the URL {url} is set"));
+        }
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn clear_encrypted() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        options.custom_options.encrypted_resolvers.clear();
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn add_blocklist(source: String) -> Result<()> {
+        let source = parse_blocklist_source(&source)?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        if options.blocklists.sources.contains(&source) {
+            return Err(anyhow!("The blocklist {source} has already been added"));
+        }
+        options.blocklists.sources.push(source);
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn remove_blocklist(source: String) -> Result<()> {
+        let source = parse_blocklist_source(&source)?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let sources = &mut options.blocklists.sources;
+        let count = sources.len();
+        sources.retain(|existing| *existing != source);
+        if sources.len() == count {
+            return Err(anyhow!("No blocklist {source} has been added"));
+        }
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn clear_blocklists() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        options.blocklists.sources.clear();
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn add_allowed_domain(domain: String) -> Result<()> {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let allowed_domains = &mut options.blocklists.allowed_domains;
+        if !allowed_domains.contains(&domain) {
+            allowed_domains.push(domain);
+            rpc.set_dns_options(options).await?;
+        }
+        println!("Updated DNS settings");
+        Ok(())
+    }
+
+    async fn remove_allowed_domain(domain: String) -> Result<()> {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc.get_settings().await?.tunnel_options.dns_options;
+        let allowed_domains = &mut options.blocklists.allowed_domains;
+        let count = allowed_domains.len();
+        allowed_domains.retain(|existing| *existing != domain);
+        if allowed_domains.len() == count {
+            return Err(anyhow!("The domain {domain} is not allowed"));
+        }
+        rpc.set_dns_options(options).await?;
+        println!("Updated DNS settings");
+        Ok(())
+    }
+}
+
+/// Parse a blocklist URL or path. Relative paths are resolved against the current directory,
+/// since the daemon does not share it.
+fn parse_blocklist_source(source: &str) -> Result<DnsBlocklistSource> {
+    match source.parse() {
+        Ok(source) => Ok(source),
+        Err(error) => std::path::absolute(source)
+            .map(DnsBlocklistSource::File)
+            .map_err(|_| error.into()),
+    }
 }
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use clap::Args;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::connection_history::{ConnectionHistoryEntry, EndCause};
+
+use crate::format;
+
+#[derive(Args, Debug)]
+pub struct History {
+    /// Format output as JSON
+    #[arg(long, short = 'j')]
+    json: bool,
+}
+
+impl History {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let history = rpc.get_connection_history().await?;
+
+        if self.json {
+            let json =
+                serde_json::to_string(&history).context("Failed to format output as JSON")?;
+            println!("{json}");
+            return Ok(());
+        }
+
+        if history.is_empty() {
+            println!("No connections have been recorded");
+        }
+        for entry in &history {
+            print_entry(entry);
+        }
+        Ok(())
+    }
+}
+
+fn print_entry(entry: &ConnectionHistoryEntry) {
+    let mut line = format!("{} - ", format_time(entry.start));
+    match entry.end {
+        Some(end) => line.push_str(&format_time(end)),
+        None => line.push_str("now"),
+    }
+    line.push_str(&format!(
+        ": {} to {}",
+        entry.tunnel_type,
+        entry.relay.as_deref().unwrap_or("unknown relay")
+    ));
+    if let Some(entry_relay) = &entry.entry_relay {
+        line.push_str(&format!(" via {entry_relay}"));
+    }
+    if let Some(obfuscation) = &entry.obfuscation {
+        line.push_str(&format!(" using {obfuscation}"));
+    }
+    line.push_str(&format!(
+        ", sent {}, received {}",
+        format::format_bytes(entry.tx_bytes),
+        format::format_bytes(entry.rx_bytes)
+    ));
+    if !entry.connected {
+        line.push_str(", never connected");
+    }
+    match &entry.end_cause {
+        Some(EndCause::Disconnected) => line.push_str(", disconnected"),
+        Some(EndCause::Reconnected) => line.push_str(", reconnected"),
+        Some(EndCause::Error(cause)) => line.push_str(&format!(", error: {cause}")),
+        None => line.push_str(", ongoing"),
+    }
+    println!("{line}");
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
@@ -1,6 +1,8 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Subcommand;
+use ipnetwork::IpNetwork;
 use mullvad_management_interface::MullvadProxyClient;
+use talpid_types::net::LanAccess;
 
 use super::BooleanOption;
 
@@ -14,6 +16,53 @@ pub enum Lan {
         #[arg(value_parser = BooleanOption::custom_parser("allow", "block"))]
         policy: BooleanOption,
     },
+
+    /// Manage which private networks are treated as LAN. If none are added, all private
+    /// networks are
+    #[clap(subcommand)]
+    Network(LanNetwork),
+
+    /// Manage which local ports LAN peers may connect to. If none are added, all ports are
+    /// reachable
+    #[clap(subcommand)]
+    Port(LanPort),
+
+    /// Manage networks that are always reachable outside the tunnel, regardless of the local
+    /// network sharing setting. Traffic to these networks is allowed even in blocked states and
+    /// in lockdown mode
+    #[clap(subcommand)]
+    AllowedNetwork(AllowedNetwork),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LanNetwork {
+    /// Treat a private network in CIDR notation as LAN
+    Add { network: IpNetwork },
+    /// Stop treating a network as LAN
+    Remove { network: IpNetwork },
+    /// Treat all private networks as LAN
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LanPort {
+    /// Allow LAN peers to connect to a local TCP and UDP port
+    Add { port: u16 },
+    /// Stop allowing LAN peers to connect to a port
+    Remove { port: u16 },
+    /// Allow LAN peers to connect to any port
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AllowedNetwork {
+    /// Always allow traffic to and from a network in CIDR notation. The prefix must be at least
+    /// /8 for IPv4 and /16 for IPv6
+    Add { network: IpNetwork },
+    /// Stop always allowing a network
+    Remove { network: IpNetwork },
+    /// Remove all allowed networks
+    Clear,
 }
 
 impl Lan {
@@ -21,6 +70,9 @@ impl Lan {
         match self {
             Lan::Get => Self::get().await,
             Lan::Set { policy } => Self::set(policy).await,
+            Lan::Network(cmd) => cmd.handle().await,
+            Lan::Port(cmd) => cmd.handle().await,
+            Lan::AllowedNetwork(cmd) => cmd.handle().await,
         }
     }
 
@@ -33,9 +85,146 @@ impl Lan {
 
     async fn get() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
-        let allow_lan =
-            BooleanOption::with_labels(rpc.get_settings().await?.allow_lan, "allow", "block");
+        let settings = rpc.get_settings().await?;
+        let allow_lan = BooleanOption::with_labels(settings.allow_lan, "allow", "block");
         println!("Local network sharing setting: {allow_lan}");
+
+        let lan_access = settings.lan_access;
+        if lan_access.networks.is_empty() {
+            println!("LAN networks: all private networks");
+        } else {
+            println!("LAN networks:");
+            for network in &lan_access.networks {
+                println!("{network}");
+            }
+        }
+        if lan_access.inbound_ports.is_empty() {
+            println!("Inbound LAN ports: any");
+        } else {
+            println!("Inbound LAN ports:");
+            for port in &lan_access.inbound_ports {
+                println!("{port}");
+            }
+        }
+        println!("Allowed networks:");
+        for network in &lan_access.allowed_networks {
+            println!("{network}");
+        }
+        Ok(())
+    }
+}
+
+impl LanNetwork {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut lan_access = rpc.get_settings().await?.lan_access;
+
+        match self {
+            LanNetwork::Add { network } => {
+                let network = normalize(network);
+                if !LanAccess::is_lan_network(&network) {
+                    return Err(anyhow!("{network} is not a private network"));
+                }
+                if lan_access.networks.contains(&network) {
+                    return Err(anyhow!("{network} is already treated as LAN"));
+                }
+                lan_access.networks.push(network);
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Treating {network} as LAN");
+            }
+            LanNetwork::Remove { network } => {
+                let network = normalize(network);
+                if !remove(&mut lan_access.networks, &network) {
+                    return Err(anyhow!("{network} is not treated as LAN"));
+                }
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Stopped treating {network} as LAN");
+            }
+            LanNetwork::Clear => {
+                lan_access.networks.clear();
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Treating all private networks as LAN");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LanPort {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut lan_access = rpc.get_settings().await?.lan_access;
+
+        match self {
+            LanPort::Add { port } => {
+                if lan_access.inbound_ports.contains(&port) {
+                    return Err(anyhow!("Port {port} is already allowed"));
+                }
+                lan_access.inbound_ports.push(port);
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Allowing LAN peers to connect to port {port}");
+            }
+            LanPort::Remove { port } => {
+                if !remove(&mut lan_access.inbound_ports, &port) {
+                    return Err(anyhow!("Port {port} is not allowed"));
+                }
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Stopped allowing LAN peers to connect to port {port}");
+            }
+            LanPort::Clear => {
+                lan_access.inbound_ports.clear();
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Allowing LAN peers to connect to any port");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AllowedNetwork {
+    async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut lan_access = rpc.get_settings().await?.lan_access;
+
+        match self {
+            AllowedNetwork::Add { network } => {
+                let network = normalize(network);
+                if !LanAccess::is_allowed_network(&network) {
+                    return Err(anyhow!("{network} is too broad to be allowed"));
+                }
+                if lan_access.allowed_networks.contains(&network) {
+                    return Err(anyhow!("{network} is already allowed"));
+                }
+                lan_access.allowed_networks.push(network);
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Always allowing {network}");
+            }
+            AllowedNetwork::Remove { network } => {
+                let network = normalize(network);
+                if !remove(&mut lan_access.allowed_networks, &network) {
+                    return Err(anyhow!("{network} is not allowed"));
+                }
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Stopped always allowing {network}");
+            }
+            AllowedNetwork::Clear => {
+                lan_access.allowed_networks.clear();
+                rpc.set_lan_access(&lan_access).await?;
+                println!("Removed all allowed networks");
+            }
+        }
         Ok(())
     }
 }
+
+/// Clear the host bits of `network`, matching how the daemon stores it.
+fn normalize(network: IpNetwork) -> IpNetwork {
+    IpNetwork::new(network.network(), network.prefix()).unwrap()
+}
+
+/// Remove `item` from `items`. Returns whether it was present.
+fn remove<T: PartialEq>(items: &mut Vec<T>, item: &T) -> bool {
+    let len = items.len();
+    items.retain(|existing| existing != item);
+    items.len() != len
+}
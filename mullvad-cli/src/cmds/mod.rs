@@ -9,10 +9,12 @@ pub mod bridge;
 pub mod custom_list;
 pub mod debug;
 pub mod dns;
+pub mod history;
 pub mod lan;
 pub mod lockdown;
 pub mod obfuscation;
 pub mod patch;
+pub mod profile;
 pub mod proxies;
 pub mod relay;
 pub mod relay_constraints;
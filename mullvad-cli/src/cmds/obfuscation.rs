@@ -1,12 +1,14 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
     relay_constraints::{
-        ObfuscationSettings, SelectedObfuscation, ShadowsocksSettings, Udp2TcpObfuscationSettings,
+        GenericObfuscationSettings, ObfuscationSettings, SelectedObfuscation, ShadowsocksSettings,
+        Udp2TcpObfuscationSettings,
     },
 };
+use talpid_types::net::TransportProtocol;
 
 #[derive(Subcommand, Debug)]
 pub enum Obfuscation {
@@ -16,6 +18,20 @@ pub enum Obfuscation {
     /// Set obfuscation settings
     #[clap(subcommand)]
     Set(SetCommands),
+
+    /// Show or forget the obfuscation methods that last worked on each network. When obfuscation
+    /// is set to auto, the method learned for the current network is tried first. Methods are
+    /// forgotten if they have not worked for a week
+    #[clap(subcommand)]
+    Learned(LearnedCommands),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum LearnedCommands {
+    /// Display the learned obfuscation method of each network
+    Get,
+    /// Forget all learned obfuscation methods
+    Reset,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -36,6 +52,26 @@ pub enum SetCommands {
         #[arg(long, short = 'p')]
         port: Constraint<u16>,
     },
+
+    /// Configure obfuscation using any protocol supported by the app, identified by name. Its
+    /// server must listen on the WireGuard address of the relay.
+    Generic {
+        /// Name of the protocol
+        #[arg(long)]
+        protocol: String,
+
+        /// Port of the obfuscation server
+        #[arg(long, short = 'p')]
+        port: u16,
+
+        /// Transport protocol used by the obfuscation protocol
+        #[arg(long, default_value_t = TransportProtocol::Udp)]
+        transport: TransportProtocol,
+
+        /// Protocol-specific parameter, as NAME=VALUE. May be given multiple times.
+        #[arg(long = "parameter", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+    },
 }
 
 impl Obfuscation {
@@ -50,12 +86,39 @@ impl Obfuscation {
                 );
                 println!("udp2tcp settings: {}", obfuscation_settings.udp2tcp);
                 println!("Shadowsocks settings: {}", obfuscation_settings.shadowsocks);
+                println!("Generic settings: {}", obfuscation_settings.generic);
                 Ok(())
             }
             Obfuscation::Set(subcmd) => Self::set(subcmd).await,
+            Obfuscation::Learned(subcmd) => Self::learned(subcmd).await,
         }
     }
 
+    async fn learned(subcmd: LearnedCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match subcmd {
+            LearnedCommands::Get => {
+                let entries = rpc.get_learned_obfuscation().await?;
+                if entries.is_empty() {
+                    println!("No obfuscation methods have been learned");
+                }
+                for entry in entries {
+                    println!(
+                        "{}: {}, last worked at {}",
+                        entry.network,
+                        entry.method,
+                        entry.last_success.with_timezone(&chrono::Local),
+                    );
+                }
+            }
+            LearnedCommands::Reset => {
+                rpc.reset_learned_obfuscation().await?;
+                println!("Learned obfuscation methods forgotten");
+            }
+        }
+        Ok(())
+    }
+
     async fn set(subcmd: SetCommands) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let current_settings = rpc.get_settings().await?.obfuscation_settings;
@@ -82,6 +145,23 @@ impl Obfuscation {
                 })
                 .await?;
             }
+            SetCommands::Generic {
+                protocol,
+                port,
+                transport,
+                parameters,
+            } => {
+                rpc.set_obfuscation_settings(ObfuscationSettings {
+                    generic: GenericObfuscationSettings {
+                        protocol,
+                        port,
+                        transport,
+                        parameters: parameters.into_iter().collect(),
+                    },
+                    ..current_settings
+                })
+                .await?;
+            }
         }
 
         println!("Updated obfuscation settings");
@@ -89,3 +169,10 @@ impl Obfuscation {
         Ok(())
     }
 }
+
+fn parse_parameter(parameter: &str) -> Result<(String, String)> {
+    let (name, value) = parameter
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected NAME=VALUE, got '{parameter}'"))?;
+    Ok((name.to_owned(), value.to_owned()))
+}
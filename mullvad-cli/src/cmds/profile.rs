@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveTime, Weekday};
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::{
+    profile::{Profile, ProfileContents, ProfileTrigger},
+    relay_constraints::RelaySettingsFormatter,
+    settings::DnsState,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommand {
+    /// Save the current settings as a profile, replacing any existing profile with the same name.
+    /// If no settings are selected, relay, DNS and obfuscation settings are all saved
+    Save {
+        /// A name for the profile
+        name: String,
+        /// Save the relay settings
+        #[arg(long)]
+        relay: bool,
+        /// Save the DNS settings
+        #[arg(long)]
+        dns: bool,
+        /// Save the obfuscation settings
+        #[arg(long)]
+        obfuscation: bool,
+    },
+
+    /// Show all profiles
+    List,
+
+    /// Delete a profile
+    Delete {
+        /// A profile
+        name: String,
+    },
+
+    /// Apply the settings stored in a profile
+    Apply {
+        /// A profile
+        name: String,
+    },
+
+    /// Manage when a profile is applied automatically
+    #[clap(subcommand)]
+    Trigger(TriggerCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TriggerCommand {
+    /// Apply a profile automatically
+    #[clap(subcommand)]
+    Add(AddTrigger),
+
+    /// Remove a trigger from a profile
+    Remove {
+        /// A profile
+        name: String,
+        /// The trigger number, as shown by 'mullvad profile list'
+        index: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AddTrigger {
+    /// Apply the profile during a time interval. If the end time is earlier than the start time,
+    /// the interval extends past midnight
+    Schedule {
+        /// A profile
+        name: String,
+        /// Start time (local time, HH:MM)
+        #[arg(long, value_parser = parse_time)]
+        start: NaiveTime,
+        /// End time (local time, HH:MM)
+        #[arg(long, value_parser = parse_time)]
+        end: NaiveTime,
+        /// Comma-separated days of the week, e.g. 'mon,tue'. If omitted, every day is included
+        #[arg(long, value_delimiter = ',', value_parser = parse_weekday)]
+        days: Vec<Weekday>,
+    },
+
+    /// Apply the profile while the default gateway has a specific MAC address
+    Network {
+        /// A profile
+        name: String,
+        /// MAC address of the gateway
+        gateway_mac: String,
+    },
+}
+
+impl ProfileCommand {
+    pub async fn handle(self) -> Result<()> {
+        match self {
+            ProfileCommand::Save {
+                name,
+                relay,
+                dns,
+                obfuscation,
+            } => Self::save(name, relay, dns, obfuscation).await,
+            ProfileCommand::List => Self::list().await,
+            ProfileCommand::Delete { name } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.delete_profile(name).await?;
+                println!("Deleted profile");
+                Ok(())
+            }
+            ProfileCommand::Apply { name } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.apply_profile(name).await?;
+                println!("Applied profile");
+                Ok(())
+            }
+            ProfileCommand::Trigger(TriggerCommand::Add(AddTrigger::Schedule {
+                name,
+                start,
+                end,
+                days,
+            })) => Self::add_trigger(name, ProfileTrigger::Schedule { days, start, end }).await,
+            ProfileCommand::Trigger(TriggerCommand::Add(AddTrigger::Network {
+                name,
+                gateway_mac,
+            })) => Self::add_trigger(name, ProfileTrigger::Network { gateway_mac }).await,
+            ProfileCommand::Trigger(TriggerCommand::Remove { name, index }) => {
+                Self::remove_trigger(name, index).await
+            }
+        }
+    }
+
+    async fn save(name: String, relay: bool, dns: bool, obfuscation: bool) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+
+        let all = !(relay || dns || obfuscation);
+        let contents =
+            ProfileContents::capture(&settings, relay || all, dns || all, obfuscation || all);
+        // Keep the triggers of an existing profile
+        let triggers = settings
+            .profiles
+            .get(&name)
+            .map(|profile| profile.triggers.clone())
+            .unwrap_or_default();
+
+        rpc.set_profile(Profile {
+            name,
+            contents,
+            triggers,
+        })
+        .await?;
+        println!("Saved profile");
+        Ok(())
+    }
+
+    async fn list() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let settings = rpc.get_settings().await?;
+
+        for profile in settings.profiles.profiles() {
+            let active = settings.profiles.active() == Some(profile.name.as_str());
+            println!(
+                "{}{}",
+                profile.name,
+                if active { " (applied by trigger)" } else { "" }
+            );
+
+            let contents = &profile.contents;
+            if let Some(relay_settings) = &contents.relay_settings {
+                let formatter = RelaySettingsFormatter {
+                    settings: relay_settings,
+                    custom_lists: &settings.custom_lists,
+                };
+                println!("\tRelay settings: {formatter}");
+            }
+            if let Some(dns_options) = &contents.dns_options {
+                let state = match dns_options.state {
+                    DnsState::Default => "default",
+                    DnsState::Custom => "custom",
+                };
+                println!("\tDNS: {state}");
+            }
+            if let Some(obfuscation) = &contents.obfuscation_settings {
+                println!("\tObfuscation: {}", obfuscation.selected_obfuscation);
+            }
+            for (index, trigger) in profile.triggers.iter().enumerate() {
+                println!("\tTrigger {}: {trigger}", index + 1);
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_trigger(name: String, trigger: ProfileTrigger) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut profile = find_profile(&mut rpc, &name).await?;
+        profile.triggers.push(trigger);
+        rpc.set_profile(profile).await?;
+        println!("Added trigger");
+        Ok(())
+    }
+
+    async fn remove_trigger(name: String, index: usize) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut profile = find_profile(&mut rpc, &name).await?;
+        if index == 0 || index > profile.triggers.len() {
+            return Err(anyhow!("Profile '{name}' has no trigger {index}"));
+        }
+        profile.triggers.remove(index - 1);
+        rpc.set_profile(profile).await?;
+        println!("Removed trigger");
+        Ok(())
+    }
+}
+
+async fn find_profile(rpc: &mut MullvadProxyClient, name: &str) -> Result<Profile> {
+    rpc.get_settings()
+        .await?
+        .profiles
+        .get(name)
+        .cloned()
+        .ok_or(anyhow!("Profile '{name}' not found"))
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| anyhow!("Expected a time such as 08:30"))
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday> {
+    s.parse()
+        .map_err(|_| anyhow!("Expected a day of the week, e.g. 'mon'"))
+}
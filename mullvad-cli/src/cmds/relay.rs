@@ -1,14 +1,15 @@
 use anyhow::{anyhow, bail, Context, Result};
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use itertools::Itertools;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::{Constraint, Match},
-    location::CountryCode,
+    location::{Coordinates, CountryCode},
     relay_constraints::{
         GeographicLocationConstraint, LocationConstraint, LocationConstraintFormatter,
-        OpenVpnConstraints, Ownership, Provider, Providers, RelayConstraints, RelayOverride,
-        RelaySettings, TransportPort, WireguardConstraints,
+        NearestLocationConstraint, OpenVpnConstraints, Ownership, Provider, Providers,
+        RelayConstraints, RelayExclusion, RelayExclusionsFormatter, RelayOverride,
+        RelaySelectionStrategy, RelaySettings, TransportPort, WireguardConstraints,
     },
     relay_list::{RelayEndpointData, RelayListCountry},
     ConnectionConfig, CustomTunnelEndpoint,
@@ -19,7 +20,9 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 use talpid_types::net::{
-    all_of_the_internet, openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
+    all_of_the_internet,
+    obfuscation::{ObfuscatorConfig, SHADOWSOCKS_OBFUSCATION_CIPHERS},
+    openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
 };
 
 use super::{relay_constraints::LocationArgs, BooleanOption};
@@ -43,6 +46,14 @@ pub enum Relay {
     /// Override options for individual relays/servers
     #[clap(subcommand)]
     Override(OverrideCommands),
+
+    /// Exclude relays from ever being selected, regardless of other constraints
+    #[clap(subcommand)]
+    Exclude(ExcludeCommands),
+
+    /// Show or forget relays that recently failed to connect. Such relays are avoided for a while
+    #[clap(subcommand)]
+    Health(HealthCommands),
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -78,6 +89,22 @@ pub enum SetCommands {
         custom_list_name: String,
     },
 
+    /// Select the relays closest to the location of this device, or to a
+    /// given coordinate.
+    Nearest {
+        /// Also select relays within this distance, in kilometers
+        #[arg(long, short = 'r', default_value_t = NearestLocationConstraint::DEFAULT_RADIUS)]
+        radius: u32,
+
+        /// Latitude to measure distances from, instead of the location of this device
+        #[arg(long, requires = "longitude", allow_negative_numbers = true)]
+        latitude: Option<f64>,
+
+        /// Longitude to measure distances from, instead of the location of this device
+        #[arg(long, requires = "latitude", allow_negative_numbers = true)]
+        longitude: Option<f64>,
+    },
+
     /// Set hosting provider(s) to select relays from. The 'list'
     /// command shows the available relays and their providers.
     Provider {
@@ -99,6 +126,11 @@ pub enum SetCommands {
     /// Set tunnel protocol to use: 'wireguard', or 'openvpn'.
     TunnelProtocol { protocol: TunnelType },
 
+    /// Set how to pick a relay among the relays matching the constraints.
+    /// 'random' picks a random relay. 'fastest' prefers relays with a low
+    /// measured latency.
+    Strategy { strategy: RelaySelectionStrategy },
+
     /// Set a custom VPN relay to use
     #[clap(subcommand)]
     Custom(SetCustomCommands),
@@ -194,24 +226,89 @@ pub enum SetCustomCommands {
 
     /// Use a custom WireGuard relay
     #[clap(arg_required_else_help = true)]
-    Wireguard {
-        /// Hostname or IP
-        host: String,
-        /// Remote port
-        port: u16,
-        /// Base64 encoded public key of remote peer
-        #[arg(value_parser = wireguard::PublicKey::from_base64)]
-        peer_pubkey: wireguard::PublicKey,
-        /// IP addresses of local tunnel interface
-        #[arg(required = true, num_args = 1..)]
-        tunnel_ip: Vec<IpAddr>,
-        /// IPv4 gateway address
-        #[arg(long)]
-        v4_gateway: Ipv4Addr,
-        /// IPv6 gateway address
-        #[arg(long)]
-        v6_gateway: Option<Ipv6Addr>,
-    },
+    Wireguard(CustomWireguardArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CustomWireguardArgs {
+    /// Hostname or IP
+    host: String,
+    /// Remote port
+    port: u16,
+    /// Base64 encoded public key of remote peer
+    #[arg(value_parser = wireguard::PublicKey::from_base64)]
+    peer_pubkey: wireguard::PublicKey,
+    /// IP addresses of local tunnel interface
+    #[arg(required = true, num_args = 1..)]
+    tunnel_ip: Vec<IpAddr>,
+    /// IPv4 gateway address
+    #[arg(long)]
+    v4_gateway: Ipv4Addr,
+    /// IPv6 gateway address
+    #[arg(long)]
+    v6_gateway: Option<Ipv6Addr>,
+    /// Base64 encoded public key of an exit peer. If set, traffic is tunneled through the remote
+    /// peer to the exit peer
+    #[arg(long, requires = "exit_endpoint", value_parser = wireguard::PublicKey::from_base64)]
+    exit_pubkey: Option<wireguard::PublicKey>,
+    /// IP address and port of the exit peer, as reachable from the remote peer
+    #[arg(long, requires = "exit_pubkey")]
+    exit_endpoint: Option<SocketAddr>,
+    /// Negotiate quantum-resistant keys with the peers, if quantum resistance is enabled. This
+    /// requires that the peers run the ephemeral peer service
+    #[arg(long)]
+    quantum_resistant: bool,
+    /// Obfuscate traffic to the remote peer. The obfuscation server must forward traffic to the
+    /// remote peer
+    #[arg(long, requires = "obfuscation_endpoint")]
+    obfuscation: Option<CustomObfuscation>,
+    /// IP address and port of the obfuscation server
+    #[arg(long, requires = "obfuscation")]
+    obfuscation_endpoint: Option<SocketAddr>,
+    /// Cipher used by the Shadowsocks server. Defaults to the cipher used by Mullvad relays
+    #[arg(long, value_parser = SHADOWSOCKS_OBFUSCATION_CIPHERS)]
+    shadowsocks_cipher: Option<String>,
+    /// Password used by the Shadowsocks server. Defaults to the password used by Mullvad relays
+    #[arg(long)]
+    shadowsocks_password: Option<String>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CustomObfuscation {
+    Udp2tcp,
+    Shadowsocks,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExcludeCommands {
+    /// Display the excluded relays
+    Get,
+    /// Exclude a relay, custom list or provider
+    #[clap(subcommand)]
+    Add(ExclusionCommands),
+    /// Stop excluding a relay, custom list or provider
+    #[clap(subcommand)]
+    Remove(ExclusionCommands),
+    /// Stop excluding any relays
+    Clear,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExclusionCommands {
+    /// A single relay, identified by its hostname
+    Hostname { hostname: String },
+    /// All relays in a custom list
+    CustomList { custom_list_name: String },
+    /// All relays hosted by a provider
+    Provider { provider: Provider },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HealthCommands {
+    /// Display the relays that recently failed to connect
+    Get,
+    /// Forget all connection failures
+    Clear,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -266,6 +363,8 @@ impl Relay {
             Relay::Update => Self::update().await,
             Relay::Set(subcmd) => Self::set(subcmd).await,
             Relay::Override(subcmd) => Self::r#override(subcmd).await,
+            Relay::Exclude(subcmd) => Self::exclude(subcmd).await,
+            Relay::Health(subcmd) => Self::health(subcmd).await,
         }
     }
 
@@ -297,6 +396,14 @@ impl Relay {
 
                 print_option!("Provider(s)", constraints.providers,);
                 print_option!("Ownership", constraints.ownership,);
+                print_option!(
+                    "Excluded",
+                    RelayExclusionsFormatter {
+                        exclusions: &constraints.exclusions,
+                        custom_lists: &settings.custom_lists,
+                    },
+                );
+                print_option!("Selection strategy", settings.relay_selection_strategy,);
 
                 println!("OpenVPN constraints");
 
@@ -422,10 +529,24 @@ impl Relay {
             SetCommands::CustomList { custom_list_name } => {
                 Self::set_custom_list(custom_list_name).await
             }
+            SetCommands::Nearest {
+                radius,
+                latitude,
+                longitude,
+            } => {
+                let origin = latitude
+                    .zip(longitude)
+                    .map(|(latitude, longitude)| Coordinates {
+                        latitude,
+                        longitude,
+                    });
+                Self::set_nearest(origin, radius).await
+            }
             SetCommands::Provider { providers } => Self::set_providers(providers).await,
             SetCommands::Ownership { ownership } => Self::set_ownership(ownership).await,
             SetCommands::Tunnel(subcmd) => Self::set_tunnel(subcmd).await,
             SetCommands::TunnelProtocol { protocol } => Self::set_tunnel_protocol(protocol).await,
+            SetCommands::Strategy { strategy } => Self::set_strategy(strategy).await,
         }
     }
 
@@ -458,24 +579,7 @@ impl Relay {
             } => {
                 Self::read_custom_openvpn_relay(host, port, username, password, transport_protocol)
             }
-            SetCustomCommands::Wireguard {
-                host,
-                port,
-                peer_pubkey,
-                tunnel_ip,
-                v4_gateway,
-                v6_gateway,
-            } => {
-                Self::read_custom_wireguard_relay(
-                    host,
-                    port,
-                    peer_pubkey,
-                    tunnel_ip,
-                    v4_gateway,
-                    v6_gateway,
-                )
-                .await?
-            }
+            SetCustomCommands::Wireguard(args) => Self::read_custom_wireguard_relay(args).await?,
         };
         let mut rpc = MullvadProxyClient::new().await?;
         rpc.set_relay_settings(RelaySettings::CustomTunnelEndpoint(custom_endpoint))
@@ -501,17 +605,35 @@ impl Relay {
                 username,
                 password,
             }),
+            psk: None,
+            exit_psk: None,
+            quantum_resistant: false,
+            obfuscation: None,
         }
     }
 
     async fn read_custom_wireguard_relay(
-        host: String,
-        port: u16,
-        peer_pubkey: wireguard::PublicKey,
-        tunnel_ip: Vec<IpAddr>,
-        ipv4_gateway: Ipv4Addr,
-        ipv6_gateway: Option<Ipv6Addr>,
+        args: CustomWireguardArgs,
     ) -> Result<CustomTunnelEndpoint> {
+        if args.obfuscation != Some(CustomObfuscation::Shadowsocks)
+            && (args.shadowsocks_cipher.is_some() || args.shadowsocks_password.is_some())
+        {
+            bail!("Shadowsocks options require --obfuscation shadowsocks");
+        }
+        let obfuscation =
+            args.obfuscation
+                .zip(args.obfuscation_endpoint)
+                .map(|(obfuscation, endpoint)| match obfuscation {
+                    CustomObfuscation::Udp2tcp => ObfuscatorConfig::Udp2Tcp { endpoint },
+                    CustomObfuscation::Shadowsocks => ObfuscatorConfig::Shadowsocks {
+                        endpoint,
+                        cipher: args.shadowsocks_cipher,
+                        password: args.shadowsocks_password,
+                        // Set to the WireGuard endpoint by the daemon
+                        target: None,
+                    },
+                });
+
         println!("Reading private key from standard input");
 
         let private_key_str = tokio::task::spawn_blocking(|| {
@@ -529,27 +651,47 @@ impl Relay {
         let private_key =
             wireguard::PrivateKey::from_base64(&private_key_str).context("Invalid private key")?;
 
+        let exit_peer = args
+            .exit_pubkey
+            .zip(args.exit_endpoint)
+            .map(|(public_key, endpoint)| wireguard::PeerConfig {
+                public_key,
+                allowed_ips: all_of_the_internet(),
+                endpoint,
+                psk: None,
+                constant_packet_size: false,
+            });
+        // The entry peer should only route traffic to the exit peer
+        let entry_allowed_ips = match &exit_peer {
+            Some(exit_peer) => vec![exit_peer.endpoint.ip().into()],
+            None => all_of_the_internet(),
+        };
+
         Ok(CustomTunnelEndpoint {
-            host,
+            host: args.host,
             config: ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
                 tunnel: wireguard::TunnelConfig {
                     private_key,
-                    addresses: tunnel_ip,
+                    addresses: args.tunnel_ip,
                 },
                 peer: wireguard::PeerConfig {
-                    public_key: peer_pubkey,
-                    allowed_ips: all_of_the_internet(),
-                    endpoint: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+                    public_key: args.peer_pubkey,
+                    allowed_ips: entry_allowed_ips,
+                    endpoint: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), args.port),
                     psk: None,
                     constant_packet_size: false,
                 },
-                exit_peer: None,
-                ipv4_gateway,
-                ipv6_gateway,
+                exit_peer,
+                ipv4_gateway: args.v4_gateway,
+                ipv6_gateway: args.v6_gateway,
                 // NOTE: Ignored in gRPC
                 #[cfg(target_os = "linux")]
                 fwmark: None,
             }),
+            psk: None,
+            exit_psk: None,
+            quantum_resistant: args.quantum_resistant,
+            obfuscation,
         })
     }
 
@@ -596,6 +738,26 @@ impl Relay {
         .await
     }
 
+    async fn set_nearest(origin: Option<Coordinates>, radius: u32) -> Result<()> {
+        if let Some(origin) = origin {
+            if !origin.is_valid() {
+                bail!(
+                    "Invalid coordinate: {}, {}",
+                    origin.latitude,
+                    origin.longitude
+                );
+            }
+        }
+        Self::update_constraints(|constraints| {
+            constraints.location =
+                Constraint::Only(LocationConstraint::Nearest(NearestLocationConstraint {
+                    origin,
+                    radius,
+                }));
+        })
+        .await
+    }
+
     async fn set_providers(providers: Vec<String>) -> Result<()> {
         let providers = if providers[0].eq_ignore_ascii_case("any") {
             Constraint::Any
@@ -719,6 +881,103 @@ impl Relay {
         .await
     }
 
+    async fn set_strategy(strategy: RelaySelectionStrategy) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_relay_selection_strategy(strategy).await?;
+        println!("Updated relay selection strategy");
+        Ok(())
+    }
+
+    async fn exclude(subcmd: ExcludeCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match subcmd {
+            ExcludeCommands::Get => {
+                let settings = rpc.get_settings().await?;
+                let RelaySettings::Normal(constraints) = settings.relay_settings else {
+                    bail!("Relay exclusions are not used with a custom relay");
+                };
+                println!(
+                    "Excluded: {}",
+                    RelayExclusionsFormatter {
+                        exclusions: &constraints.exclusions,
+                        custom_lists: &settings.custom_lists,
+                    }
+                );
+            }
+            ExcludeCommands::Add(exclusion) => {
+                let exclusion = Self::resolve_exclusion(&mut rpc, exclusion).await?;
+                rpc.add_relay_exclusion(exclusion).await?;
+                println!("Relay exclusions updated");
+            }
+            ExcludeCommands::Remove(exclusion) => {
+                let exclusion = Self::resolve_exclusion(&mut rpc, exclusion).await?;
+                rpc.remove_relay_exclusion(exclusion).await?;
+                println!("Relay exclusions updated");
+            }
+            ExcludeCommands::Clear => {
+                rpc.clear_relay_exclusions().await?;
+                println!("Relay exclusions cleared");
+            }
+        }
+        Ok(())
+    }
+
+    async fn health(subcmd: HealthCommands) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        match subcmd {
+            HealthCommands::Get => {
+                let failures = rpc.get_relay_failures().await?;
+                if failures.is_empty() {
+                    println!("No relays have failed to connect recently");
+                }
+                for failure in failures {
+                    println!(
+                        "{}: {} failure(s), last at {}, avoided until {}",
+                        failure.hostname,
+                        failure.failures,
+                        failure.last_failure.with_timezone(&chrono::Local),
+                        failure.penalized_until.with_timezone(&chrono::Local),
+                    );
+                }
+            }
+            HealthCommands::Clear => {
+                rpc.clear_relay_failures().await?;
+                println!("Relay connection failures cleared");
+            }
+        }
+        Ok(())
+    }
+
+    async fn resolve_exclusion(
+        rpc: &mut MullvadProxyClient,
+        exclusion: ExclusionCommands,
+    ) -> Result<RelayExclusion> {
+        if let RelaySettings::CustomTunnelEndpoint(_) = rpc.get_settings().await?.relay_settings {
+            bail!("Relay exclusions are not used with a custom relay");
+        }
+        let exclusion = match exclusion {
+            ExclusionCommands::Hostname { hostname } => {
+                let relay_list = rpc.get_relay_locations().await?;
+                let relay = relay_list
+                    .relays()
+                    .find(|relay| relay.hostname.eq_ignore_ascii_case(&hostname));
+                match relay {
+                    Some(relay) => RelayExclusion::Hostname(relay.hostname.clone()),
+                    None => {
+                        eprintln!("Warning: Excluding an unrecognized server");
+                        RelayExclusion::Hostname(hostname)
+                    }
+                }
+            }
+            ExclusionCommands::CustomList { custom_list_name } => {
+                let list = super::custom_list::find_list_by_name(rpc, &custom_list_name).await?;
+                RelayExclusion::CustomList(list.id)
+            }
+            ExclusionCommands::Provider { provider } => RelayExclusion::Provider(provider),
+        };
+        Ok(exclusion)
+    }
+
     async fn update_override(
         hostname: &str,
         update_fn: impl FnOnce(&mut RelayOverride),
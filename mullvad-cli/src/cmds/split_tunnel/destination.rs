@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use clap::Subcommand;
+use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::split_tunnel_destination::{SplitTunnelDestination, SplitTunnelDestinations};
+
+#[derive(Subcommand, Debug)]
+pub enum Destination {
+    /// Reach an IP address, a network in CIDR notation or a domain outside the tunnel.
+    /// All addresses of a domain are excluded. `*.example.com` excludes all subdomains of
+    /// example.com
+    Add { destination: SplitTunnelDestination },
+    /// Stop excluding a destination
+    Remove { destination: SplitTunnelDestination },
+    /// Stop excluding all destinations
+    Clear,
+}
+
+impl Destination {
+    pub async fn handle(self) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut destinations = rpc.get_settings().await?.split_tunnel.destinations;
+
+        match self {
+            Destination::Add { destination } => {
+                if !destinations.add(destination.clone()) {
+                    return Err(anyhow!("{destination} is already excluded"));
+                }
+                rpc.set_split_tunnel_destinations(&destinations).await?;
+                println!("Excluding {destination} from the tunnel");
+            }
+            Destination::Remove { destination } => {
+                if !destinations.remove(&destination) {
+                    return Err(anyhow!("{destination} is not excluded"));
+                }
+                rpc.set_split_tunnel_destinations(&destinations).await?;
+                println!("Stopped excluding {destination} from the tunnel");
+            }
+            Destination::Clear => {
+                rpc.set_split_tunnel_destinations(&SplitTunnelDestinations::default())
+                    .await?;
+                println!("Stopped excluding all destinations");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Print the excluded destinations, as part of `split-tunnel get`.
+pub fn print_destinations(destinations: &SplitTunnelDestinations) {
+    println!("Excluded destinations:");
+    for network in &destinations.networks {
+        println!("{network}");
+    }
+    for domain in &destinations.domains {
+        println!("{domain}");
+    }
+}
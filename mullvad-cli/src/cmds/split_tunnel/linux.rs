@@ -1,11 +1,31 @@
 use anyhow::Result;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+use mullvad_types::settings::SplitTunnelMode;
+use std::path::PathBuf;
+
+use super::{
+    super::BooleanOption,
+    destination::{print_destinations, Destination},
+};
 
 /// Manage split tunneling. To launch applications outside the tunnel, use the program
 /// 'mullvad-exclude' instead of this command
 #[derive(Subcommand, Debug)]
 pub enum SplitTunnel {
+    /// Display the split tunnel status and apps
+    Get,
+    /// Enable or disable excluding the applications added using 'app'
+    Set { policy: BooleanOption },
+    /// Manage applications to always exclude from the tunnel, including after restarts
+    #[clap(subcommand)]
+    App(App),
+    /// Manage IP networks and domains to always reach outside the tunnel
+    #[clap(subcommand)]
+    Destination(Destination),
+    /// Set whether the selected processes and applications bypass the tunnel ('exclude'), or are
+    /// the only ones that use it ('include')
+    Mode { mode: Mode },
     /// List all processes that are excluded from the tunnel
     List,
     /// Add a PID to exclude from the tunnel
@@ -16,9 +36,72 @@ pub enum SplitTunnel {
     Clear,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Exclude,
+    Include,
+}
+
+impl From<Mode> for SplitTunnelMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Exclude => SplitTunnelMode::Exclude,
+            Mode::Include => SplitTunnelMode::Include,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum App {
+    /// Exclude all processes running the given executable
+    Add { path: PathBuf },
+    /// Stop excluding processes running the given executable
+    Remove { path: PathBuf },
+    /// Stop excluding all applications
+    Clear,
+}
+
 impl SplitTunnel {
     pub async fn handle(self) -> Result<()> {
         match self {
+            SplitTunnel::Get => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                let settings = rpc.get_settings().await?.split_tunnel;
+
+                let enable_exclusions = BooleanOption::from(settings.enable_exclusions);
+
+                println!("Split tunneling state: {enable_exclusions}");
+                println!("Split tunneling mode: {}", format_mode(settings.mode));
+                if settings.effective_mode() != settings.mode {
+                    println!("The mode has no effect until split tunneling is enabled and apps are added");
+                }
+
+                println!("Excluded applications:");
+                for path in &settings.apps {
+                    println!("{}", path.display());
+                }
+
+                print_destinations(&settings.destinations);
+
+                Ok(())
+            }
+            SplitTunnel::Set { policy } => {
+                let mut rpc = MullvadProxyClient::new().await?;
+                rpc.set_split_tunnel_state(*policy).await?;
+                println!("Split tunnel policy: {policy}");
+                Ok(())
+            }
+            SplitTunnel::App(subcmd) => Self::app(subcmd).await,
+            SplitTunnel::Destination(subcmd) => subcmd.handle().await,
+            SplitTunnel::Mode { mode } => {
+                let mode = SplitTunnelMode::from(mode);
+                MullvadProxyClient::new()
+                    .await?
+                    .set_split_tunnel_mode(mode)
+                    .await?;
+                println!("Split tunneling mode: {}", format_mode(mode));
+                Ok(())
+            }
             SplitTunnel::List => {
                 let pids = MullvadProxyClient::new()
                     .await?
@@ -58,4 +141,40 @@ impl SplitTunnel {
             }
         }
     }
+
+    async fn app(subcmd: App) -> Result<()> {
+        match subcmd {
+            App::Add { path } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .add_split_tunnel_app(path)
+                    .await?;
+                println!("Added path to excluded apps list");
+                Ok(())
+            }
+            App::Remove { path } => {
+                MullvadProxyClient::new()
+                    .await?
+                    .remove_split_tunnel_app(path)
+                    .await?;
+                println!("Stopped excluding app from tunnel");
+                Ok(())
+            }
+            App::Clear => {
+                MullvadProxyClient::new()
+                    .await?
+                    .clear_split_tunnel_apps()
+                    .await?;
+                println!("Stopped excluding all apps");
+                Ok(())
+            }
+        }
+    }
+}
+
+fn format_mode(mode: SplitTunnelMode) -> &'static str {
+    match mode {
+        SplitTunnelMode::Exclude => "exclude (selected apps bypass the tunnel)",
+        SplitTunnelMode::Include => "include (only selected apps use the tunnel)",
+    }
 }
@@ -4,7 +4,10 @@ use std::path::PathBuf;
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
-use super::super::BooleanOption;
+use super::{
+    super::BooleanOption,
+    destination::{print_destinations, Destination},
+};
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -18,6 +21,9 @@ pub enum SplitTunnel {
     /// Manage applications to exclude from the tunnel
     #[clap(subcommand)]
     App(App),
+    /// Manage IP networks and domains to always reach outside the tunnel
+    #[clap(subcommand)]
+    Destination(Destination),
 }
 
 #[derive(Subcommand, Debug)]
@@ -43,6 +49,8 @@ impl SplitTunnel {
                     println!("{}", path.display());
                 }
 
+                print_destinations(&settings.destinations);
+
                 Ok(())
             }
             SplitTunnel::Set { policy } => {
@@ -52,6 +60,7 @@ impl SplitTunnel {
                 Ok(())
             }
             SplitTunnel::App(subcmd) => Self::app(subcmd).await,
+            SplitTunnel::Destination(subcmd) => subcmd.handle().await,
         }
     }
 
@@ -1,3 +1,5 @@
+mod destination;
+
 #[cfg(target_os = "linux")]
 #[path = "linux.rs"]
 mod imp;
@@ -7,7 +7,10 @@ use std::{
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 
-use super::super::BooleanOption;
+use super::{
+    super::BooleanOption,
+    destination::{print_destinations, Destination},
+};
 
 /// Set options for applications to exclude from the tunnel.
 #[derive(Subcommand, Debug)]
@@ -27,6 +30,9 @@ pub enum SplitTunnel {
     /// Manage applications to exclude from the tunnel
     #[clap(subcommand)]
     App(App),
+    /// Manage IP networks and domains to always reach outside the tunnel
+    #[clap(subcommand)]
+    Destination(Destination),
 }
 
 #[derive(Subcommand, Debug)]
@@ -52,6 +58,8 @@ impl SplitTunnel {
                     println!("{}", path.display());
                 }
 
+                print_destinations(&settings.destinations);
+
                 if list_processes {
                     let processes = rpc.get_excluded_processes().await?;
                     for process in &processes {
@@ -76,6 +84,7 @@ impl SplitTunnel {
                 Ok(())
             }
             SplitTunnel::App(subcmd) => Self::app(subcmd).await,
+            SplitTunnel::Destination(subcmd) => subcmd.handle().await,
         }
     }
 
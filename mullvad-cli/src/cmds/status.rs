@@ -1,10 +1,11 @@
 use anyhow::{Context, Result};
 use clap::{Args, Subcommand};
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use mullvad_management_interface::{client::DaemonEvent, MullvadProxyClient};
 use mullvad_types::{device::DeviceState, states::TunnelState};
 use serde::Serialize;
-use std::fmt::Debug;
+use std::{fmt::Debug, pin::Pin};
+use talpid_types::tunnel::TunnelStats;
 
 use crate::format;
 
@@ -27,6 +28,10 @@ pub struct StatusArgs {
     /// Format output as JSON
     #[arg(long, short = 'j', conflicts_with_all = ["verbose", "debug"])]
     json: bool,
+
+    /// Continuously print traffic statistics of the current tunnel
+    #[arg(long, conflicts_with = "json")]
+    stats: bool,
 }
 
 impl Status {
@@ -36,38 +41,60 @@ impl Status {
         mut previous_tunnel_state: TunnelState,
     ) -> Result<()> {
         let mut event_stream = rpc.events_listen().await?;
-        while let Some(event) = event_stream.next().await {
-            match event? {
-                DaemonEvent::TunnelState(new_state) => {
-                    if args.debug {
-                        println!("New tunnel state: {new_state:#?}");
-                    } else if args.json {
-                        let json = serde_json::to_string(&new_state)
-                            .context("Failed to format output as JSON")?;
-                        println!("{json}");
-                    } else {
-                        format::print_state(&new_state, Some(&previous_tunnel_state), args.verbose);
-                        previous_tunnel_state = new_state;
+        let mut stats_rpc = MullvadProxyClient::new().await?;
+        let mut stats_stream: Pin<Box<dyn Stream<Item = _>>> = if args.stats {
+            Box::pin(stats_rpc.tunnel_stats_listen().await?)
+        } else {
+            Box::pin(futures::stream::pending())
+        };
+        loop {
+            tokio::select! {
+                event = event_stream.next() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+                    match event? {
+                        DaemonEvent::TunnelState(new_state) => {
+                            if args.debug {
+                                println!("New tunnel state: {new_state:#?}");
+                            } else if args.json {
+                                let json = serde_json::to_string(&new_state)
+                                    .context("Failed to format output as JSON")?;
+                                println!("{json}");
+                            } else {
+                                format::print_state(
+                                    &new_state,
+                                    Some(&previous_tunnel_state),
+                                    args.verbose,
+                                );
+                                previous_tunnel_state = new_state;
+                            }
+                        }
+                        DaemonEvent::Settings(settings) => {
+                            print_debug_or_json(&args, "New settings", &settings)?;
+                        }
+                        DaemonEvent::RelayList(relay_list) => {
+                            print_debug_or_json(&args, "New relay list", &relay_list)?;
+                        }
+                        DaemonEvent::AppVersionInfo(app_version_info) => {
+                            print_debug_or_json(
+                                &args,
+                                "New app version info",
+                                &app_version_info,
+                            )?;
+                        }
+                        DaemonEvent::Device(device) => {
+                            print_debug_or_json(&args, "Device event", &device)?;
+                        }
+                        DaemonEvent::RemoveDevice(device) => {
+                            print_debug_or_json(&args, "Remove device event", &device)?;
+                        }
+                        DaemonEvent::NewAccessMethod(access_method) => {
+                            print_debug_or_json(&args, "New access method", &access_method)?;
+                        }
                     }
                 }
-                DaemonEvent::Settings(settings) => {
-                    print_debug_or_json(&args, "New settings", &settings)?;
-                }
-                DaemonEvent::RelayList(relay_list) => {
-                    print_debug_or_json(&args, "New relay list", &relay_list)?;
-                }
-                DaemonEvent::AppVersionInfo(app_version_info) => {
-                    print_debug_or_json(&args, "New app version info", &app_version_info)?;
-                }
-                DaemonEvent::Device(device) => {
-                    print_debug_or_json(&args, "Device event", &device)?;
-                }
-                DaemonEvent::RemoveDevice(device) => {
-                    print_debug_or_json(&args, "Remove device event", &device)?;
-                }
-                DaemonEvent::NewAccessMethod(access_method) => {
-                    print_debug_or_json(&args, "New access method", &access_method)?;
-                }
+                Some(stats) = stats_stream.next() => print_stats(&args, &stats?),
             }
         }
         Ok(())
@@ -92,10 +119,36 @@ pub async fn handle(cmd: Option<Status>, args: StatusArgs) -> Result<()> {
 
     if cmd == Some(Status::Listen) {
         Status::listen(rpc, args, state).await?;
+    } else if args.stats {
+        let mut stats_stream = rpc.tunnel_stats_listen().await?;
+        while let Some(stats) = stats_stream.next().await {
+            print_stats(&args, &stats?);
+        }
     }
     Ok(())
 }
 
+fn print_stats(args: &StatusArgs, stats: &TunnelStats) {
+    if args.debug {
+        println!("Tunnel stats: {stats:#?}");
+        return;
+    }
+    let mut line = format!(
+        "Sent: {} ({}/s), received: {} ({}/s)",
+        format::format_bytes(stats.tx_bytes),
+        format::format_bytes(stats.tx_rate),
+        format::format_bytes(stats.rx_bytes),
+        format::format_bytes(stats.rx_rate),
+    );
+    if let Some(age) = stats.last_handshake_age {
+        line.push_str(&format!(", last handshake: {}s ago", age.as_secs()));
+    }
+    if let Some(latency) = stats.latency {
+        line.push_str(&format!(", latency: {} ms", latency.as_millis()));
+    }
+    println!("{line}");
+}
+
 fn print_account_logged_out(state: &TunnelState, device: &DeviceState) {
     match state {
         TunnelState::Connecting { .. } | TunnelState::Connected { .. } | TunnelState::Error(_) => {
@@ -1,10 +1,17 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
     wireguard::{QuantumResistantState, RotationInterval, DEFAULT_ROTATION_INTERVAL},
 };
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fs::{File, OpenOptions},
+    io::{read_to_string, stdin, BufReader, Write},
+    net::Ipv4Addr,
+};
 
 use super::BooleanOption;
 use crate::print_option;
@@ -17,6 +24,28 @@ pub enum Tunnel {
     /// Set tunnel options
     #[clap(subcommand)]
     Set(TunnelOptions),
+
+    /// Use the peer in a wg-quick configuration file as a custom WireGuard relay. DNS servers and
+    /// MTU in the file are ignored unless --apply-dns-and-mtu is given
+    Import {
+        /// Path to the configuration file, or "-" to read from standard input
+        file: String,
+        /// In-tunnel IPv4 address of the peer. If omitted, a DNS server routed through the tunnel
+        /// is assumed to be the peer, or else the first host address of the interface's network
+        #[arg(long)]
+        gateway: Option<Ipv4Addr>,
+        /// Replace the DNS and MTU settings with those in the file. They remain in effect if
+        /// another relay is selected later
+        #[arg(long)]
+        apply_dns_and_mtu: bool,
+    },
+
+    /// Export the current WireGuard tunnel as a wg-quick configuration file. The private key is
+    /// replaced by a placeholder
+    Export {
+        /// Path to write the configuration to, or "-" to write to standard output
+        file: String,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -68,6 +97,62 @@ impl Tunnel {
         match self {
             Tunnel::Get => Self::get().await,
             Tunnel::Set(options) => Self::set(options).await,
+            Tunnel::Import {
+                file,
+                gateway,
+                apply_dns_and_mtu,
+            } => Self::import(file, gateway, apply_dns_and_mtu).await,
+            Tunnel::Export { file } => Self::export(file).await,
+        }
+    }
+
+    async fn import(
+        source: String,
+        gateway: Option<Ipv4Addr>,
+        apply_dns_and_mtu: bool,
+    ) -> Result<()> {
+        let config = tokio::task::spawn_blocking(move || match source.as_str() {
+            "-" => read_to_string(BufReader::new(stdin())).context("Failed to read from stdin"),
+            _ => read_to_string(File::open(&source)?)
+                .context(format!("Failed to read from path: {source}")),
+        })
+        .await
+        .unwrap()?;
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.import_wireguard_config(config, gateway, apply_dns_and_mtu)
+            .await
+            .context("Failed to import WireGuard configuration")?;
+        println!("Custom WireGuard relay set");
+        Ok(())
+    }
+
+    async fn export(dest: String) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let config = rpc
+            .export_wireguard_config()
+            .await
+            .context("Failed to export WireGuard configuration")?;
+        eprintln!("The private key is not exported. Replace the placeholder before use");
+
+        match dest.as_str() {
+            "-" => {
+                print!("{config}");
+                Ok(())
+            }
+            _ => tokio::task::spawn_blocking(move || {
+                let mut options = OpenOptions::new();
+                options.write(true).create(true).truncate(true);
+                // The private key is meant to be filled in
+                #[cfg(unix)]
+                options.mode(0o600);
+                options
+                    .open(&dest)
+                    .and_then(|mut file| file.write_all(config.as_bytes()))
+                    .context(format!("Failed to write to path {dest}"))
+            })
+            .await
+            .unwrap(),
         }
     }
 
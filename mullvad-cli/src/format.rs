@@ -7,7 +7,7 @@ use mullvad_types::{
 };
 use talpid_types::{
     net::{Endpoint, TunnelEndpoint},
-    tunnel::{ActionAfterDisconnect, ErrorState},
+    tunnel::{ActionAfterDisconnect, ErrorState, NoMatchingRelayReason, ParameterGenerationError},
 };
 
 #[macro_export]
@@ -210,6 +210,23 @@ fn print_connection_info(
     }
 }
 
+/// Format a number of bytes using decimal units, e.g. `1.5 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    // Values that would be rounded up to 1000.0 are shown using the next unit
+    while value >= 999.95 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
 pub fn format_location(location: &GeoIpLocation) -> String {
     let mut formatted_location = location.country.to_string();
     if let Some(city) = &location.city {
@@ -312,10 +329,48 @@ fn print_error_state(error_state: &ErrorState) {
                 get_auth_failed_message(AuthFailed::from(auth_failed.as_str()))
             );
         }
+        cause @ talpid_types::tunnel::ErrorStateCause::TunnelParameterError(
+            ParameterGenerationError::NoMatchingRelay(Some(reason)),
+        ) => {
+            println!("Blocked: {cause}");
+            println!("{}", get_no_matching_relay_hint(*reason));
+        }
         cause => println!("Blocked: {cause}"),
     }
 }
 
+const fn get_no_matching_relay_hint(reason: NoMatchingRelayReason) -> &'static str {
+    match reason {
+        NoMatchingRelayReason::TunnelProtocol => {
+            "Select another tunnel protocol using 'mullvad relay set tunnel-protocol'."
+        }
+        NoMatchingRelayReason::Inactive => {
+            "The matching relays are currently unavailable. Select another location using 'mullvad relay set location'."
+        }
+        NoMatchingRelayReason::Excluded => {
+            "Review the excluded relays using 'mullvad relay exclude get'."
+        }
+        NoMatchingRelayReason::Location => {
+            "Select another location using 'mullvad relay set location'."
+        }
+        NoMatchingRelayReason::EntryLocation => {
+            "Select another entry location using 'mullvad relay set tunnel wireguard entry-location'."
+        }
+        NoMatchingRelayReason::Ownership => {
+            "Change the ownership constraint using 'mullvad relay set ownership'."
+        }
+        NoMatchingRelayReason::Providers => {
+            "Select other providers using 'mullvad relay set provider'."
+        }
+        NoMatchingRelayReason::Daita => {
+            "Select a location with DAITA-enabled relays, or allow multihop using 'mullvad tunnel set wireguard --daita-direct-only off'."
+        }
+        NoMatchingRelayReason::Obfuscation => {
+            "Change the obfuscation settings using 'mullvad obfuscation set'."
+        }
+    }
+}
+
 const fn get_auth_failed_message(auth_failed: AuthFailed) -> &'static str {
     const INVALID_ACCOUNT_MSG: &str = "You've logged in with an account number that is not valid. Please log out and try another one.";
     const EXPIRED_ACCOUNT_MSG: &str = "You have no more VPN time left on this account. Please log in on our website to buy more credit.";
@@ -329,3 +384,20 @@ const fn get_auth_failed_message(auth_failed: AuthFailed) -> &'static str {
         AuthFailed::Unknown => UNKNOWN_MSG,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::format_bytes;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1000), "1.0 kB");
+        assert_eq!(format_bytes(1_500), "1.5 kB");
+        assert_eq!(format_bytes(999_949), "999.9 kB");
+        assert_eq!(format_bytes(999_950), "1.0 MB");
+        assert_eq!(format_bytes(2_345_678_901), "2.3 GB");
+        assert_eq!(format_bytes(u64::MAX), "18446744.1 TB");
+    }
+}
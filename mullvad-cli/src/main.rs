@@ -112,6 +112,9 @@ enum Cli {
     #[clap(subcommand)]
     Tunnel(tunnel::Tunnel),
 
+    /// Show recent tunnel sessions
+    History(history::History),
+
     /// Show information about the current Mullvad version
     /// and available versions
     Version,
@@ -135,6 +138,10 @@ enum Cli {
     #[clap(subcommand)]
     CustomList(custom_list::CustomList),
 
+    /// Manage settings profiles, which can be applied manually or when a trigger matches
+    #[clap(subcommand)]
+    Profile(profile::ProfileCommand),
+
     /// Apply a JSON patch generated by 'export-settings'
     #[clap(arg_required_else_help = true)]
     ImportSettings {
@@ -170,9 +177,11 @@ async fn main() -> Result<()> {
         Cli::FactoryReset => reset::handle().await,
         Cli::Relay(cmd) => cmd.handle().await,
         Cli::Tunnel(cmd) => cmd.handle().await,
+        Cli::History(cmd) => cmd.handle().await,
         Cli::SplitTunnel(cmd) => cmd.handle().await,
         Cli::Status { cmd, args } => status::handle(cmd, args).await,
         Cli::CustomList(cmd) => cmd.handle().await,
+        Cli::Profile(cmd) => cmd.handle().await,
         Cli::ImportSettings { file } => patch::import(file).await,
         Cli::ExportSettings { file } => patch::export(file).await,
 
@@ -1,5 +1,5 @@
 use clap::{Args, Parser};
-use std::sync::LazyLock;
+use std::{net::SocketAddr, sync::LazyLock};
 
 static ENV_DESC: LazyLock<String> = LazyLock::new(|| {
     format!(
@@ -35,6 +35,10 @@ struct Cli {
     /// Don't log timestamps when logging to stdout, useful when running as a systemd service
     #[arg(long)]
     disable_stdout_timestamps: bool,
+    /// Serve metrics in the OpenMetrics text format on this address, e.g. 127.0.0.1:9090.
+    /// Disabled by default
+    #[arg(long)]
+    metrics_address: Option<SocketAddr>,
 
     #[command(flatten)]
     command: CommandFlags,
@@ -69,6 +73,7 @@ pub struct Config {
     pub log_level: log::LevelFilter,
     pub log_to_file: bool,
     pub log_stdout_timestamps: bool,
+    pub metrics_address: Option<SocketAddr>,
 
     pub command: Command,
 }
@@ -136,6 +141,7 @@ fn create_config() -> Config {
         log_level,
         log_to_file: !app.disable_log_to_file,
         log_stdout_timestamps: !app.disable_stdout_timestamps,
+        metrics_address: app.metrics_address,
         command: app.command.into(),
     }
 }
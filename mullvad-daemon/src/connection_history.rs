@@ -0,0 +1,437 @@
+//! Records a bounded history of tunnel sessions on disk.
+//!
+//! The history is kept by a separate task, which also follows the tunnel statistics, so that
+//! neither tracking the statistics nor writing the history file blocks the daemon. Statistics that
+//! have been published are always recorded before the next tunnel state, so they cannot end up in
+//! the wrong session.
+
+use crate::metrics::error_cause_name;
+use chrono::Utc;
+use futures::channel::oneshot;
+use mullvad_types::{
+    connection_history::{ConnectionHistoryEntry, EndCause},
+    states::TunnelState,
+};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+use talpid_types::{
+    tunnel::{ActionAfterDisconnect, TunnelStats},
+    ErrorExt,
+};
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+    sync::{mpsc, watch},
+};
+
+static CONNECTION_HISTORY_FILE: &str = "connection-history.json";
+
+/// Maximum number of finished sessions to keep. The oldest sessions are removed first.
+const MAX_ENTRIES: usize = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize connection history")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write connection history file")]
+    Write(#[source] io::Error),
+
+    #[error("The connection history task is not running")]
+    TaskStopped,
+}
+
+enum Command {
+    TunnelState(TunnelState),
+    Entries(oneshot::Sender<Vec<ConnectionHistoryEntry>>),
+    Clear(oneshot::Sender<Result<(), Error>>),
+}
+
+/// Handle used to record tunnel states and to read or clear the connection history.
+#[derive(Clone)]
+pub struct ConnectionHistoryHandle {
+    tx: mpsc::UnboundedSender<Command>,
+}
+
+impl ConnectionHistoryHandle {
+    /// Start, update, or end the current session based on the new tunnel state.
+    pub fn on_tunnel_state(&self, state: &TunnelState) {
+        let _ = self.tx.send(Command::TunnelState(state.clone()));
+    }
+
+    /// Return all sessions, oldest first. This includes the ongoing session, if any.
+    pub async fn entries(&self) -> Vec<ConnectionHistoryEntry> {
+        let (tx, rx) = oneshot::channel();
+        if self.tx.send(Command::Entries(tx)).is_err() {
+            return vec![];
+        }
+        rx.await.unwrap_or_default()
+    }
+
+    /// Remove all recorded sessions.
+    pub async fn clear(&self) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Command::Clear(tx))
+            .map_err(|_| Error::TaskStopped)?;
+        rx.await.map_err(|_| Error::TaskStopped)?
+    }
+}
+
+/// Load the connection history from `settings_dir` and spawn a task which keeps it up to date.
+pub(crate) async fn spawn(
+    settings_dir: &Path,
+    tunnel_stats: watch::Receiver<Option<TunnelStats>>,
+) -> ConnectionHistoryHandle {
+    let path = settings_dir.join(CONNECTION_HISTORY_FILE);
+    let entries = match fs::read_to_string(&path).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to parse connection history")
+            );
+            VecDeque::new()
+        }),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => VecDeque::new(),
+        Err(error) => {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to read connection history")
+            );
+            VecDeque::new()
+        }
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(run(ConnectionHistory::new(entries), path, tunnel_stats, rx));
+    ConnectionHistoryHandle { tx }
+}
+
+async fn run(
+    mut history: ConnectionHistory,
+    path: PathBuf,
+    mut tunnel_stats: watch::Receiver<Option<TunnelStats>>,
+    mut rx: mpsc::UnboundedReceiver<Command>,
+) {
+    let mut stats_open = true;
+    loop {
+        tokio::select! {
+            // Statistics are published before the tunnel state changes, so handle them first
+            biased;
+
+            changed = tunnel_stats.changed(), if stats_open => {
+                if changed.is_err() {
+                    stats_open = false;
+                    continue;
+                }
+                if let Some(stats) = *tunnel_stats.borrow_and_update() {
+                    history.on_tunnel_stats(stats);
+                }
+            }
+            command = rx.recv() => match command {
+                Some(Command::TunnelState(state)) => {
+                    if history.on_tunnel_state(&state) {
+                        if let Err(error) = save(&path, &history.entries).await {
+                            log::error!(
+                                "{}",
+                                error.display_chain_with_msg("Failed to save connection history")
+                            );
+                        }
+                    }
+                }
+                Some(Command::Entries(tx)) => {
+                    let _ = tx.send(history.all_entries());
+                }
+                Some(Command::Clear(tx)) => {
+                    history.clear();
+                    let _ = tx.send(save(&path, &history.entries).await);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+async fn save(path: &Path, entries: &VecDeque<ConnectionHistoryEntry>) -> Result<(), Error> {
+    let buffer = serde_json::to_string_pretty(entries).map_err(Error::Serialize)?;
+    let mut file = mullvad_fs::AtomicFile::new(path)
+        .await
+        .map_err(Error::Write)?;
+    file.write_all(buffer.as_bytes())
+        .await
+        .map_err(Error::Write)?;
+    file.finalize().await.map_err(Error::Write)
+}
+
+struct ConnectionHistory {
+    /// Finished sessions, oldest first.
+    entries: VecDeque<ConnectionHistoryEntry>,
+    current: Option<ConnectionHistoryEntry>,
+    /// Most recent traffic statistics of the current session. The statistics are reset when the
+    /// tunnel goes down, so the last known value is kept.
+    last_stats: Option<TunnelStats>,
+}
+
+impl ConnectionHistory {
+    fn new(entries: VecDeque<ConnectionHistoryEntry>) -> Self {
+        ConnectionHistory {
+            entries,
+            current: None,
+            last_stats: None,
+        }
+    }
+
+    fn all_entries(&self) -> Vec<ConnectionHistoryEntry> {
+        self.entries
+            .iter()
+            .chain(self.current.as_ref())
+            .cloned()
+            .collect()
+    }
+
+    fn on_tunnel_stats(&mut self, stats: TunnelStats) {
+        if self.current.is_some() {
+            self.last_stats = Some(stats);
+        }
+    }
+
+    /// Update the sessions based on the new tunnel state. Returns whether a session ended, in
+    /// which case the finished sessions should be saved.
+    fn on_tunnel_state(&mut self, state: &TunnelState) -> bool {
+        match state {
+            TunnelState::Connecting {
+                endpoint, location, ..
+            } => {
+                let location = location.as_ref();
+                let relay = location.and_then(|location| location.hostname.clone());
+                let entry_relay = location.and_then(|location| {
+                    location
+                        .entry_hostname
+                        .clone()
+                        .or_else(|| location.bridge_hostname.clone())
+                });
+                let obfuscation = endpoint
+                    .obfuscation
+                    .as_ref()
+                    .map(|obfuscation| obfuscation.obfuscation_type);
+
+                // Retries of an attempt that has not connected yet belong to the same session
+                if let Some(current) = self.current.as_mut().filter(|current| !current.connected) {
+                    current.tunnel_type = endpoint.tunnel_type;
+                    current.relay = relay;
+                    current.entry_relay = entry_relay;
+                    current.obfuscation = obfuscation;
+                    return false;
+                }
+
+                let ended = self.end_session(EndCause::Reconnected);
+                self.current = Some(ConnectionHistoryEntry {
+                    start: Utc::now(),
+                    end: None,
+                    tunnel_type: endpoint.tunnel_type,
+                    relay,
+                    entry_relay,
+                    obfuscation,
+                    connected: false,
+                    tx_bytes: 0,
+                    rx_bytes: 0,
+                    end_cause: None,
+                });
+                ended
+            }
+            TunnelState::Connected { location, .. } => {
+                if let Some(current) = &mut self.current {
+                    current.connected = true;
+                    if let Some(location) = location {
+                        current.relay = location.hostname.clone().or(current.relay.take());
+                        current.entry_relay = location
+                            .entry_hostname
+                            .clone()
+                            .or_else(|| location.bridge_hostname.clone())
+                            .or(current.entry_relay.take());
+                    }
+                }
+                false
+            }
+            TunnelState::Disconnecting(ActionAfterDisconnect::Reconnect) => {
+                self.end_session(EndCause::Reconnected)
+            }
+            TunnelState::Disconnecting(_) | TunnelState::Disconnected { .. } => {
+                self.end_session(EndCause::Disconnected)
+            }
+            TunnelState::Error(error_state) => {
+                self.end_session(EndCause::Error(error_cause_name(error_state.cause())))
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.current = None;
+        self.last_stats = None;
+    }
+
+    /// End the current session, if any. Returns whether there was one.
+    fn end_session(&mut self, cause: EndCause) -> bool {
+        let Some(mut entry) = self.current.take() else {
+            return false;
+        };
+        entry.end = Some(Utc::now());
+        entry.end_cause = Some(cause);
+        if let Some(stats) = self.last_stats.take() {
+            entry.tx_bytes = stats.tx_bytes;
+            entry.rx_bytes = stats.rx_bytes;
+        }
+
+        self.entries.push_back(entry);
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mullvad_types::location::GeoIpLocation;
+    use talpid_types::{
+        net::{Endpoint, TransportProtocol, TunnelEndpoint, TunnelType},
+        tunnel::{ErrorState, ErrorStateCause},
+    };
+
+    fn endpoint() -> TunnelEndpoint {
+        TunnelEndpoint {
+            endpoint: Endpoint::new(
+                std::net::Ipv4Addr::new(192, 0, 2, 1),
+                51820,
+                TransportProtocol::Udp,
+            ),
+            tunnel_type: TunnelType::Wireguard,
+            quantum_resistant: false,
+            proxy: None,
+            obfuscation: None,
+            entry_endpoint: None,
+            tunnel_interface: None,
+            #[cfg(daita)]
+            daita: false,
+        }
+    }
+
+    fn location(hostname: &str) -> GeoIpLocation {
+        GeoIpLocation {
+            ipv4: None,
+            ipv6: None,
+            country: "Sweden".to_owned(),
+            city: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            mullvad_exit_ip: true,
+            hostname: Some(hostname.to_owned()),
+            bridge_hostname: None,
+            entry_hostname: None,
+            obfuscator_hostname: None,
+        }
+    }
+
+    fn connecting(hostname: &str) -> TunnelState {
+        TunnelState::Connecting {
+            endpoint: endpoint(),
+            location: Some(location(hostname)),
+            feature_indicators: Default::default(),
+        }
+    }
+
+    fn connected(hostname: &str) -> TunnelState {
+        TunnelState::Connected {
+            endpoint: endpoint(),
+            location: Some(location(hostname)),
+            feature_indicators: Default::default(),
+        }
+    }
+
+    fn stats(tx_bytes: u64, rx_bytes: u64) -> TunnelStats {
+        TunnelStats {
+            tx_bytes,
+            rx_bytes,
+            tx_rate: 0,
+            rx_rate: 0,
+            last_handshake_age: None,
+            latency: None,
+        }
+    }
+
+    /// Retries while connecting are part of the same session.
+    #[test]
+    fn test_retries_are_coalesced() {
+        let mut history = ConnectionHistory::new(VecDeque::new());
+
+        assert!(!history.on_tunnel_state(&connecting("se-got-wg-001")));
+        assert!(!history.on_tunnel_state(&connecting("se-got-wg-002")));
+        assert!(!history.on_tunnel_state(&connected("se-got-wg-002")));
+
+        let entries = history.all_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].relay.as_deref(), Some("se-got-wg-002"));
+        assert!(entries[0].connected);
+        assert_eq!(entries[0].end_cause, None);
+
+        // Connecting again after having connected starts a new session
+        assert!(history.on_tunnel_state(&connecting("se-got-wg-003")));
+        let entries = history.all_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].end_cause, Some(EndCause::Reconnected));
+        assert_eq!(entries[1].relay.as_deref(), Some("se-got-wg-003"));
+    }
+
+    /// The last statistics of a session are recorded when it ends, and are not carried over to
+    /// the next session.
+    #[test]
+    fn test_session_stats() {
+        let mut history = ConnectionHistory::new(VecDeque::new());
+
+        history.on_tunnel_stats(stats(1, 1));
+        history.on_tunnel_state(&connecting("se-got-wg-001"));
+        history.on_tunnel_state(&connected("se-got-wg-001"));
+        history.on_tunnel_stats(stats(100, 200));
+        history.on_tunnel_stats(stats(300, 400));
+        assert!(
+            history.on_tunnel_state(&TunnelState::Disconnecting(ActionAfterDisconnect::Nothing))
+        );
+
+        history.on_tunnel_state(&connecting("se-got-wg-001"));
+        assert!(history.on_tunnel_state(&TunnelState::Error(ErrorState::new(
+            ErrorStateCause::IsOffline,
+            None
+        ))));
+
+        let entries = history.all_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].tx_bytes, entries[0].rx_bytes), (300, 400));
+        assert_eq!(entries[0].end_cause, Some(EndCause::Disconnected));
+        assert_eq!((entries[1].tx_bytes, entries[1].rx_bytes), (0, 0));
+        assert!(!entries[1].connected);
+        assert!(matches!(entries[1].end_cause, Some(EndCause::Error(_))));
+    }
+
+    /// Only the most recent sessions are kept.
+    #[test]
+    fn test_max_entries() {
+        let mut history = ConnectionHistory::new(VecDeque::new());
+
+        for i in 0..MAX_ENTRIES + 5 {
+            history.on_tunnel_state(&connecting(&format!("relay-{i}")));
+            history.on_tunnel_state(&TunnelState::Disconnecting(
+                ActionAfterDisconnect::Reconnect,
+            ));
+        }
+
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.entries[0].relay.as_deref(), Some("relay-5"));
+
+        history.clear();
+        assert!(history.all_entries().is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+//! Keeps track of the network the device is connected to, and reacts to changes in it.
+
+use crate::{Daemon, DaemonEventSender};
+#[cfg(target_os = "macos")]
+use futures::StreamExt;
+use mullvad_types::{profile::TriggerContext, trusted_network::CurrentNetwork};
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+};
+use talpid_core::mpsc::Sender;
+#[cfg(target_os = "linux")]
+use talpid_dbus::network_manager::NetworkManager;
+use talpid_routing::RouteManagerHandle;
+
+/// How often the current network is checked. This also determines how quickly schedule triggers
+/// of profiles take effect.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Address used to find the default route outside the tunnel.
+#[cfg(target_os = "linux")]
+const PUBLIC_INTERNET_ADDRESS_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(193, 138, 218, 78));
+
+/// Spawn a task which periodically sends the [`CurrentNetwork`] to the daemon. On macOS, it is
+/// also sent whenever the default route changes.
+pub(crate) fn spawn_monitor(
+    route_manager: RouteManagerHandle,
+    event_tx: DaemonEventSender<CurrentNetwork>,
+) {
+    tokio::spawn(async move {
+        #[cfg(target_os = "macos")]
+        let mut route_events = match route_manager.default_route_listener().await {
+            Ok(listener) => Some(Box::pin(listener)),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    talpid_types::ErrorExt::display_chain_with_msg(
+                        &error,
+                        "Failed to listen for default route changes"
+                    )
+                );
+                None
+            }
+        };
+
+        let mut ssid_reader = SsidReader::default();
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            #[cfg(target_os = "macos")]
+            match route_events.as_mut() {
+                Some(events) => {
+                    tokio::select! {
+                        _ = interval.tick() => (),
+                        event = events.next() => {
+                            if event.is_none() {
+                                route_events = None;
+                            }
+                        }
+                    }
+                }
+                None => {
+                    interval.tick().await;
+                }
+            }
+            #[cfg(not(target_os = "macos"))]
+            interval.tick().await;
+
+            let network = CurrentNetwork {
+                gateway_mac: gateway_mac(&route_manager).await,
+                ssid: ssid_reader.ssid().await,
+            };
+            if event_tx.send(network).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+async fn gateway_mac(route_manager: &RouteManagerHandle) -> Option<String> {
+    let (v4_gateway, v6_gateway) = route_manager.get_default_gateway().await.ok()?;
+    v4_gateway
+        .or(v6_gateway)
+        .map(|gateway| gateway.mac_address.to_string())
+}
+
+#[cfg(target_os = "linux")]
+async fn gateway_mac(route_manager: &RouteManagerHandle) -> Option<String> {
+    // The tunnel mark selects the route that is used outside the tunnel
+    let route = route_manager
+        .get_destination_route(
+            PUBLIC_INTERNET_ADDRESS_V4,
+            Some(mullvad_types::TUNNEL_FWMARK),
+        )
+        .await
+        .ok()??;
+    let node = route.get_node();
+    let gateway = node.get_address()?;
+    let device = node.get_device()?;
+
+    let arp_table = tokio::fs::read_to_string("/proc/net/arp").await.ok()?;
+    find_arp_entry(&arp_table, gateway, device)
+}
+
+/// Find the MAC address of `address` on `device` in the contents of `/proc/net/arp`.
+#[cfg(target_os = "linux")]
+fn find_arp_entry(arp_table: &str, address: IpAddr, device: &str) -> Option<String> {
+    // Columns: IP address, HW type, flags, HW address, mask, device
+    const ATF_COM: u32 = 0x2;
+
+    arp_table.lines().skip(1).find_map(|line| {
+        let columns: Vec<_> = line.split_whitespace().collect();
+        let [ip, _, flags, mac, _, entry_device] = columns[..] else {
+            return None;
+        };
+        let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+        let complete = flags & ATF_COM != 0;
+        (complete && ip.parse() == Ok(address) && entry_device == device).then(|| mac.to_owned())
+    })
+}
+
+#[cfg(target_os = "windows")]
+async fn gateway_mac(_route_manager: &RouteManagerHandle) -> Option<String> {
+    use talpid_windows::net::{inet_sockaddr_from_socketaddr, AddressFamily};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{GetIpNetEntry2, MIB_IPNET_ROW2};
+
+    let route =
+        tokio::task::spawn_blocking(|| talpid_routing::get_best_default_route(AddressFamily::Ipv4))
+            .await
+            .ok()?
+            .ok()??;
+
+    // SAFETY: All-zero bytes is a valid value for MIB_IPNET_ROW2
+    let mut row: MIB_IPNET_ROW2 = unsafe { std::mem::zeroed() };
+    row.Address = inet_sockaddr_from_socketaddr(route.gateway);
+    row.InterfaceLuid = route.iface;
+    // SAFETY: `row` is a valid MIB_IPNET_ROW2 with the address and interface of the neighbor set
+    if unsafe { GetIpNetEntry2(&mut row) } != 0 {
+        return None;
+    }
+
+    let len = usize::try_from(row.PhysicalAddressLength).ok()?;
+    let mac = row
+        .PhysicalAddress
+        .get(..len)
+        .filter(|mac| !mac.is_empty())?;
+    Some(
+        mac.iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+async fn gateway_mac(_route_manager: &RouteManagerHandle) -> Option<String> {
+    None
+}
+
+/// Obtains the SSID of the primary connection, reusing the same NetworkManager connection for
+/// every check.
+#[derive(Default)]
+struct SsidReader {
+    #[cfg(target_os = "linux")]
+    network_manager: Option<Arc<NetworkManager>>,
+}
+
+impl SsidReader {
+    #[cfg(target_os = "linux")]
+    async fn ssid(&mut self) -> Option<String> {
+        let network_manager = match &self.network_manager {
+            Some(network_manager) => network_manager.clone(),
+            None => {
+                let network_manager = tokio::task::spawn_blocking(NetworkManager::new)
+                    .await
+                    .ok()?
+                    .inspect_err(|error| {
+                        log::trace!("Failed to connect to NetworkManager: {error}")
+                    })
+                    .ok()?;
+                self.network_manager
+                    .insert(Arc::new(network_manager))
+                    .clone()
+            }
+        };
+        tokio::task::spawn_blocking(move || {
+            network_manager
+                .get_primary_connection_ssid()
+                .inspect_err(|error| log::trace!("Failed to obtain SSID: {error}"))
+                .ok()
+                .flatten()
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn ssid(&mut self) -> Option<String> {
+        None
+    }
+}
+
+impl Daemon {
+    pub(crate) async fn handle_current_network(&mut self, network: CurrentNetwork) {
+        if network != self.current_network {
+            log::debug!("Current network: {network:?}");
+            #[cfg(not(target_os = "android"))]
+            self.latency_monitor.network_changed();
+        }
+
+        let context = TriggerContext {
+            time: chrono::Local::now().naive_local(),
+            gateway_mac: network.gateway_mac.clone(),
+        };
+        self.handle_profile_triggers(context).await;
+
+        self.parameters_generator
+            .set_current_network(network.identity())
+            .await;
+        self.current_network = network;
+        self.apply_trusted_networks().await;
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_arp_entry() {
+        let arp_table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         aa:bb:cc:00:11:22     *        wlan0
+192.168.1.2      0x1         0x0         00:00:00:00:00:00     *        wlan0
+10.0.0.1         0x1         0x2         aa:bb:cc:33:44:55     *        eth0
+";
+        let gateway = |ip: &str| ip.parse().unwrap();
+
+        assert_eq!(
+            find_arp_entry(arp_table, gateway("192.168.1.1"), "wlan0").as_deref(),
+            Some("aa:bb:cc:00:11:22")
+        );
+        // Incomplete entries have no MAC address
+        assert_eq!(
+            find_arp_entry(arp_table, gateway("192.168.1.2"), "wlan0"),
+            None
+        );
+        assert_eq!(
+            find_arp_entry(arp_table, gateway("10.0.0.1"), "wlan0"),
+            None
+        );
+    }
+}
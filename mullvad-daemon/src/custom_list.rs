@@ -32,7 +32,11 @@ impl Daemon {
             .try_update(|settings| {
                 // NOTE: Not using swap remove because it would make user output slightly
                 // more confusing and the cost is so small.
-                settings.custom_lists.remove(&id)
+                settings.custom_lists.remove(&id)?;
+                if let RelaySettings::Normal(constraints) = &mut settings.relay_settings {
+                    constraints.exclusions.custom_lists.remove(&id);
+                }
+                Ok::<_, mullvad_types::custom_list::Error>(())
             })
             .await
             .map_err(Error::SettingsError);
@@ -118,6 +122,12 @@ impl Daemon {
             need_to_reconnect |= custom_list_id.map(|id| &id == list_id).unwrap_or(true);
         }
 
+        // Excluded custom lists apply to the entry, exit and bridge alike
+        let excluded_lists = &relay_settings.exclusions.custom_lists;
+        need_to_reconnect |= custom_list_id
+            .map(|id| excluded_lists.contains(&id))
+            .unwrap_or(!excluded_lists.is_empty());
+
         if let Some(endpoint) = self.tunnel_state.endpoint() {
             match endpoint.tunnel_type {
                 TunnelType::Wireguard => {
@@ -1,5 +1,12 @@
 use mullvad_types::settings::{DnsOptions, DnsState};
 use std::net::{IpAddr, Ipv4Addr};
+#[cfg(not(target_os = "android"))]
+use std::sync::Arc;
+#[cfg(not(target_os = "android"))]
+use talpid_core::dns::{
+    filter::DnsFilter,
+    forwarder::{ExcludedDomains, ForwarderConfig},
+};
 use talpid_core::{dns::DnsConfig, firewall::is_local_address};
 
 /// When we want to block certain contents with the help of DNS server side,
@@ -61,10 +68,37 @@ pub fn addresses_from_options(options: &DnsOptions) -> DnsConfig {
     }
 }
 
+/// Return the DNS config to use, including the local forwarder settings
+pub fn config_from_options(
+    options: &DnsOptions,
+    #[cfg(not(target_os = "android"))] filter: Option<Arc<DnsFilter>>,
+    #[cfg(not(target_os = "android"))] excluded_domains: Option<ExcludedDomains>,
+) -> DnsConfig {
+    let config = addresses_from_options(options);
+    #[cfg(not(target_os = "android"))]
+    let config = config.with_forwarder(ForwarderConfig {
+        encrypted_resolvers: encrypted_resolvers(options),
+        filter,
+        excluded_domains,
+    });
+    config
+}
+
+/// Return the encrypted resolvers that DNS queries should be forwarded to
+#[cfg(not(target_os = "android"))]
+fn encrypted_resolvers(options: &DnsOptions) -> Vec<talpid_types::net::dns::EncryptedDnsResolver> {
+    match options.state {
+        DnsState::Default => vec![],
+        DnsState::Custom => options.custom_options.encrypted_resolvers.clone(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::dns::addresses_from_options;
-    use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+    use mullvad_types::settings::{
+        CustomDnsOptions, DefaultDnsOptions, DnsBlocklists, DnsOptions, DnsState,
+    };
     use talpid_core::dns::DnsConfig;
 
     #[test]
@@ -73,6 +107,7 @@ mod test {
             state: DnsState::Default,
             custom_options: CustomDnsOptions::default(),
             default_options: DefaultDnsOptions::default(),
+            blocklists: DnsBlocklists::default(),
         };
 
         assert_eq!(addresses_from_options(&public_cfg), DnsConfig::default());
@@ -87,6 +122,7 @@ mod test {
                 block_ads: true,
                 ..DefaultDnsOptions::default()
             },
+            blocklists: DnsBlocklists::default(),
         };
 
         assert_eq!(
@@ -104,8 +140,10 @@ mod test {
             state: DnsState::Custom,
             custom_options: CustomDnsOptions {
                 addresses: vec![public_ip, private_ip],
+                ..Default::default()
             },
             default_options: DefaultDnsOptions::default(),
+            blocklists: DnsBlocklists::default(),
         };
 
         assert_eq!(
@@ -113,4 +151,38 @@ mod test {
             DnsConfig::from_addresses(&[public_ip], &[private_ip],)
         );
     }
+
+    // Encrypted resolvers are passed on to the forwarder
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn test_encrypted_dns() {
+        use talpid_core::dns::forwarder::ForwarderConfig;
+        use talpid_types::net::dns::EncryptedDnsResolver;
+
+        let public_ip = "1.2.3.4".parse().unwrap();
+        let resolver = EncryptedDnsResolver::from_url("tls://1.1.1.1").unwrap();
+        let cfg = DnsOptions {
+            state: DnsState::Custom,
+            custom_options: CustomDnsOptions {
+                addresses: vec![public_ip],
+                encrypted_resolvers: vec![resolver.clone()],
+            },
+            default_options: DefaultDnsOptions::default(),
+            blocklists: DnsBlocklists::default(),
+        };
+
+        let config = crate::dns::config_from_options(&cfg, None, None);
+        assert_eq!(
+            config.forwarder(),
+            &ForwarderConfig {
+                encrypted_resolvers: vec![resolver],
+                filter: None,
+                excluded_domains: None,
+            }
+        );
+        assert_eq!(
+            config,
+            DnsConfig::from_addresses(&[public_ip], &[]).with_forwarder(config.forwarder().clone())
+        );
+    }
 }
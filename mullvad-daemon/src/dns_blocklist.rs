@@ -0,0 +1,221 @@
+//! Loads custom DNS blocklists from files and URLs and passes the resulting filter on to the
+//! daemon, which forwards it to the local DNS resolver. Blocklists are reloaded periodically,
+//! since they are usually updated upstream.
+//!
+//! Since any user may change the settings, local blocklist files are only read if they are located
+//! in the blocklist directory, which only privileged users can write to.
+use crate::DaemonEventSender;
+use mullvad_types::settings::{DnsBlocklistSource, DnsBlocklists};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use talpid_core::{dns::filter::DnsFilter, mpsc::Sender};
+use tokio::sync::mpsc;
+
+/// How often to reload blocklists.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to wait before retrying if a blocklist could not be loaded.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Timeout for downloading a blocklist.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximum size of a single blocklist.
+const MAX_BLOCKLIST_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error("Failed to read blocklist file")]
+    Read(#[source] std::io::Error),
+    #[error("Blocklist file is not located in {}", .0.display())]
+    OutsideBlocklistDir(PathBuf),
+    #[error("Failed to download blocklist")]
+    Download(#[source] reqwest::Error),
+    #[error("Blocklist exceeds the maximum size of {MAX_BLOCKLIST_SIZE} bytes")]
+    TooLarge,
+    #[error("Blocklist is not valid UTF-8")]
+    InvalidEncoding,
+}
+
+/// A filter built from `blocklists`, or `None` if no blocklists are used.
+pub(crate) struct DnsFilterUpdate {
+    pub blocklists: DnsBlocklists,
+    pub filter: Option<Arc<DnsFilter>>,
+}
+
+/// Handle used to update the blocklists that should be loaded.
+#[derive(Clone)]
+pub struct DnsBlocklistHandle {
+    tx: mpsc::UnboundedSender<DnsBlocklists>,
+}
+
+impl DnsBlocklistHandle {
+    /// Load `blocklists`, unless they are already loaded.
+    pub fn update(&self, blocklists: &DnsBlocklists) {
+        let _ = self.tx.send(blocklists.clone());
+    }
+}
+
+/// Spawn a task which loads `blocklists` and sends a new filter to the daemon whenever the
+/// blocklists or their contents change. Blocklist files outside of `blocklist_dir` are not read.
+pub(crate) fn spawn(
+    event_tx: DaemonEventSender<DnsFilterUpdate>,
+    blocklist_dir: PathBuf,
+    blocklists: DnsBlocklists,
+) -> DnsBlocklistHandle {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(run_loader(event_tx, blocklist_dir, blocklists, rx));
+    DnsBlocklistHandle { tx }
+}
+
+async fn run_loader(
+    event_tx: DaemonEventSender<DnsFilterUpdate>,
+    blocklist_dir: PathBuf,
+    mut blocklists: DnsBlocklists,
+    mut rx: mpsc::UnboundedReceiver<DnsBlocklists>,
+) {
+    let client = match reqwest::Client::builder().timeout(DOWNLOAD_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(error) => {
+            log::error!("Failed to create HTTP client for DNS blocklists: {error}");
+            return;
+        }
+    };
+    if let Err(error) = tokio::fs::create_dir_all(&blocklist_dir).await {
+        log::warn!(
+            "Failed to create DNS blocklist directory {}: {error}",
+            blocklist_dir.display()
+        );
+    }
+
+    // Contents of each blocklist from the last successful load. These are kept if loading fails,
+    // e.g. because the tunnel is blocking traffic.
+    let mut contents: HashMap<DnsBlocklistSource, Arc<str>> = HashMap::new();
+    let mut refresh = true;
+
+    loop {
+        contents.retain(|source, _| blocklists.sources.contains(source));
+
+        let mut failed = false;
+        for source in &blocklists.sources {
+            if !refresh && contents.contains_key(source) {
+                continue;
+            }
+            match load(&client, &blocklist_dir, source).await {
+                Ok(list) => {
+                    contents.insert(source.clone(), list);
+                }
+                Err(error) => {
+                    log::warn!("Failed to load DNS blocklist {source}: {error}");
+                    failed = true;
+                }
+            }
+        }
+
+        let filter = if blocklists.is_enabled() {
+            let lists: Vec<_> = contents.values().cloned().collect();
+            let allowed_domains = blocklists.allowed_domains.clone();
+            let filter = tokio::task::spawn_blocking(move || {
+                let mut filter = DnsFilter::new(allowed_domains);
+                for list in lists {
+                    filter.add_list(&list);
+                }
+                filter
+            })
+            .await
+            .expect("DNS blocklist parser panicked");
+            log::debug!("Loaded {} domains from DNS blocklists", filter.len());
+            Some(Arc::new(filter))
+        } else {
+            None
+        };
+
+        let update = DnsFilterUpdate {
+            blocklists: blocklists.clone(),
+            filter,
+        };
+        if event_tx.send(update).is_err() {
+            break;
+        }
+
+        let interval = if failed {
+            RETRY_INTERVAL
+        } else {
+            REFRESH_INTERVAL
+        };
+        match wait_for_update(&mut blocklists, &mut rx, interval).await {
+            Some(timed_out) => refresh = timed_out,
+            None => break,
+        }
+    }
+}
+
+/// Wait until the blocklists have changed or should be reloaded. Returns whether the timer
+/// expired, or `None` if the handle has been dropped.
+async fn wait_for_update(
+    blocklists: &mut DnsBlocklists,
+    rx: &mut mpsc::UnboundedReceiver<DnsBlocklists>,
+    interval: Duration,
+) -> Option<bool> {
+    let refresh_timer = talpid_time::sleep(interval);
+    tokio::pin!(refresh_timer);
+
+    loop {
+        tokio::select! {
+            _ = &mut refresh_timer, if blocklists.is_enabled() => return Some(true),
+            update = rx.recv() => match update {
+                // Updates are sent on every settings change
+                Some(update) if update == *blocklists => (),
+                Some(update) => {
+                    *blocklists = update;
+                    return Some(false);
+                }
+                None => return None,
+            }
+        }
+    }
+}
+
+async fn load(
+    client: &reqwest::Client,
+    blocklist_dir: &Path,
+    source: &DnsBlocklistSource,
+) -> Result<Arc<str>, Error> {
+    let bytes = match source {
+        DnsBlocklistSource::File(path) => {
+            // Resolve symlinks so that they cannot point outside of the blocklist directory
+            let path = tokio::fs::canonicalize(path).await.map_err(Error::Read)?;
+            let dir = tokio::fs::canonicalize(blocklist_dir)
+                .await
+                .map_err(Error::Read)?;
+            if !path.starts_with(&dir) {
+                return Err(Error::OutsideBlocklistDir(dir));
+            }
+            let metadata = tokio::fs::metadata(&path).await.map_err(Error::Read)?;
+            if metadata.len() > MAX_BLOCKLIST_SIZE as u64 {
+                return Err(Error::TooLarge);
+            }
+            tokio::fs::read(&path).await.map_err(Error::Read)?
+        }
+        DnsBlocklistSource::Url(url) => {
+            let mut response = client
+                .get(url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(Error::Download)?;
+            let mut bytes = vec![];
+            while let Some(chunk) = response.chunk().await.map_err(Error::Download)? {
+                if bytes.len() + chunk.len() > MAX_BLOCKLIST_SIZE {
+                    return Err(Error::TooLarge);
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            bytes
+        }
+    };
+    String::from_utf8(bytes)
+        .map(Arc::from)
+        .map_err(|_| Error::InvalidEncoding)
+}
@@ -1,5 +1,6 @@
 use mullvad_daemon::settings::{self, SettingsPersister};
 use talpid_core::firewall::{self, Firewall, FirewallPolicy};
+use talpid_types::net::LanAccess;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -15,15 +16,16 @@ pub enum Error {
 
 pub async fn initialize_firewall() -> Result<(), Error> {
     let mut firewall = Firewall::new(mullvad_types::TUNNEL_FWMARK)?;
-    let allow_lan = get_allow_lan().await.unwrap_or_else(|err| {
+    let (allow_lan, lan_access) = get_lan_settings().await.unwrap_or_else(|err| {
         log::info!(
             "Not allowing LAN traffic due to failing to read settings: {}",
             err
         );
-        false
+        (false, LanAccess::default())
     });
     let policy = FirewallPolicy::Blocked {
         allow_lan,
+        lan_access,
         allowed_endpoint: None,
     };
     log::info!("Applying firewall policy {policy}");
@@ -31,8 +33,8 @@ pub async fn initialize_firewall() -> Result<(), Error> {
     Ok(())
 }
 
-async fn get_allow_lan() -> Result<bool, Error> {
+async fn get_lan_settings() -> Result<(bool, LanAccess), Error> {
     let path = mullvad_paths::settings_dir()?;
     let settings = SettingsPersister::load(&path).await;
-    Ok(settings.allow_lan)
+    Ok((settings.allow_lan, settings.lan_access.clone()))
 }
@@ -0,0 +1,178 @@
+//! Remembers which obfuscation method last resulted in a working tunnel on each network, so that
+//! the WireGuard retry order can start from that method the next time the device connects from
+//! the same network. Learned methods expire after a while, so that a network on which a more
+//! obfuscated method was once needed eventually tries the methods earlier in the order again.
+
+use chrono::{DateTime, Duration, Utc};
+use mullvad_types::{
+    learned_obfuscation::{LearnedObfuscation, ObfuscationMethod},
+    trusted_network::TrustedNetwork,
+};
+use std::path::{Path, PathBuf};
+use talpid_types::ErrorExt;
+use tokio::{
+    fs,
+    io::{self, AsyncWriteExt},
+};
+
+static LEARNED_OBFUSCATION_FILE: &str = "learned-obfuscation.json";
+
+/// Maximum number of networks to remember. The least recently successful network is forgotten
+/// first.
+const MAX_NETWORKS: usize = 50;
+
+/// Number of days after its last success that a learned method is forgotten.
+const LEARNED_METHOD_EXPIRY_DAYS: i64 = 7;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to serialize learned obfuscation methods")]
+    Serialize(#[source] serde_json::Error),
+
+    #[error("Unable to write learned obfuscation file")]
+    Write(#[source] io::Error),
+}
+
+pub struct LearnedObfuscationStore {
+    path: PathBuf,
+    /// Learned methods, least recently successful first.
+    entries: Vec<LearnedObfuscation>,
+}
+
+impl LearnedObfuscationStore {
+    pub async fn new(cache_dir: &Path) -> LearnedObfuscationStore {
+        let path = cache_dir.join(LEARNED_OBFUSCATION_FILE);
+        let entries = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to parse learned obfuscation methods")
+                );
+                Vec::new()
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => {
+                log::warn!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read learned obfuscation methods")
+                );
+                Vec::new()
+            }
+        };
+        LearnedObfuscationStore { path, entries }
+    }
+
+    /// Return the method that last worked on `network`, unless it has expired.
+    pub fn get(&self, network: &TrustedNetwork) -> Option<ObfuscationMethod> {
+        self.unexpired_entries(Utc::now())
+            .find(|entry| entry.network == *network)
+            .map(|entry| entry.method)
+    }
+
+    /// Return the unexpired learned methods of all networks, least recently successful first.
+    pub fn entries(&self) -> Vec<LearnedObfuscation> {
+        self.unexpired_entries(Utc::now()).cloned().collect()
+    }
+
+    fn unexpired_entries(&self, now: DateTime<Utc>) -> impl Iterator<Item = &LearnedObfuscation> {
+        self.entries
+            .iter()
+            .filter(move |entry| is_unexpired(entry, now))
+    }
+
+    /// Register that a tunnel was established on `network` using `method`. Repeated successes
+    /// with the same method on the same network only update the time of the last success once a
+    /// day, so that the file is not written every time a tunnel is established.
+    pub async fn record_success(&mut self, network: TrustedNetwork, method: ObfuscationMethod) {
+        let now = Utc::now();
+        if let Some(last) = self.entries.last() {
+            if last.network == network
+                && last.method == method
+                && now - last.last_success < Duration::days(1)
+            {
+                return;
+            }
+        }
+        if self.get(&network) != Some(method) {
+            log::info!("Learned obfuscation method for {network}: {method}");
+        }
+
+        self.entries
+            .retain(|entry| entry.network != network && is_unexpired(entry, now));
+        self.entries.push(LearnedObfuscation {
+            network,
+            method,
+            last_success: now,
+        });
+        if self.entries.len() > MAX_NETWORKS {
+            self.entries.drain(..self.entries.len() - MAX_NETWORKS);
+        }
+
+        if let Err(error) = self.save().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to save learned obfuscation methods")
+            );
+        }
+    }
+
+    /// Forget the learned methods of all networks.
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        self.entries.clear();
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<(), Error> {
+        let buffer = serde_json::to_string_pretty(&self.entries).map_err(Error::Serialize)?;
+        let mut file = mullvad_fs::AtomicFile::new(&self.path)
+            .await
+            .map_err(Error::Write)?;
+        file.write_all(buffer.as_bytes())
+            .await
+            .map_err(Error::Write)?;
+        file.finalize().await.map_err(Error::Write)
+    }
+}
+
+fn is_unexpired(entry: &LearnedObfuscation, now: DateTime<Utc>) -> bool {
+    now - entry.last_success < Duration::days(LEARNED_METHOD_EXPIRY_DAYS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn method(port: u16) -> ObfuscationMethod {
+        ObfuscationMethod {
+            obfuscation: None,
+            port: Some(port),
+            ip_version: None,
+        }
+    }
+
+    #[test]
+    fn test_expired_methods_are_ignored() {
+        let now = Utc::now();
+        let network = TrustedNetwork::GatewayMac("AA:BB:CC:00:11:22".to_owned());
+        let other_network = TrustedNetwork::Ssid("Home".to_owned());
+        let store = LearnedObfuscationStore {
+            path: PathBuf::new(),
+            entries: vec![
+                LearnedObfuscation {
+                    network: network.clone(),
+                    method: method(443),
+                    last_success: now - Duration::days(LEARNED_METHOD_EXPIRY_DAYS),
+                },
+                LearnedObfuscation {
+                    network: other_network.clone(),
+                    method: method(53),
+                    last_success: now,
+                },
+            ],
+        };
+
+        assert_eq!(store.get(&network), None);
+        assert_eq!(store.get(&other_network), Some(method(53)));
+        assert_eq!(store.entries().len(), 1);
+    }
+}
@@ -8,27 +8,40 @@ mod api;
 mod api_address_updater;
 #[cfg(not(target_os = "android"))]
 mod cleanup;
+mod connection_history;
+mod current_network;
 mod custom_list;
 pub mod device;
 mod dns;
+#[cfg(not(target_os = "android"))]
+mod dns_blocklist;
 pub mod exception_logging;
 mod geoip;
 mod leak_checker;
+mod learned_obfuscation;
 pub mod logging;
 #[cfg(target_os = "macos")]
 mod macos;
 pub mod management_interface;
+mod metrics;
 mod migrations;
+mod profile;
+#[cfg(not(target_os = "android"))]
+mod relay_latency;
 mod relay_list;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
 pub mod runtime;
 pub mod settings;
 pub mod shutdown;
+#[cfg(not(target_os = "android"))]
+mod split_tunnel_destinations;
 mod target_state;
+mod trusted_network;
 mod tunnel;
 pub mod version;
 mod version_check;
+mod wg_quick;
 
 use crate::target_state::PersistentTargetState;
 use api::AccessMethodEvent;
@@ -45,8 +58,8 @@ use mullvad_api::ApiEndpoint;
 use mullvad_relay_selector::{RelaySelector, SelectorConfig};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-use mullvad_types::settings::SplitApp;
+#[cfg(target_os = "linux")]
+use mullvad_types::settings::SplitTunnelMode;
 #[cfg(daita)]
 use mullvad_types::wireguard::DaitaSettings;
 use mullvad_types::{
@@ -56,24 +69,26 @@ use mullvad_types::{
     custom_list::CustomList,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
     features::{compute_feature_indicators, FeatureIndicator, FeatureIndicators},
-    location::{GeoIpLocation, LocationEventData},
+    location::{Coordinates, GeoIpLocation, LocationEventData},
     relay_constraints::{
-        BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, BridgeType, ExclusionsUnsupported, ObfuscationSettings,
+        RelayExclusion, RelayExclusions, RelayOverride, RelaySelectionStrategy, RelaySettings,
     },
+    relay_health::RelayFailure,
     relay_list::RelayList,
-    settings::{DnsOptions, Settings},
+    settings::{DnsOptions, Settings, SplitApp},
     states::{Secured, TargetState, TargetStateStrict, TunnelState},
     version::{AppVersion, AppVersionInfo},
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
 use relay_list::{RelayListUpdater, RelayListUpdaterHandle, RELAYS_FILENAME};
 use settings::SettingsPersister;
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-use std::collections::HashSet;
 #[cfg(target_os = "android")]
 use std::os::unix::io::RawFd;
 use std::{
+    collections::HashSet,
     marker::PhantomData,
+    net::{Ipv4Addr, SocketAddr},
     path::PathBuf,
     pin::Pin,
     sync::{Arc, Weak},
@@ -87,14 +102,18 @@ use talpid_core::{
 use talpid_routing::RouteManagerHandle;
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(target_os = "linux")]
+use talpid_types::firewall::{FirewallInspection, FirewallInspectionRequest};
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::LanAccess;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
     net::{IpVersion, TunnelType},
-    tunnel::{ErrorStateCause, TunnelStateTransition},
+    tunnel::{ErrorStateCause, TunnelStateTransition, TunnelStats},
     ErrorExt,
 };
-use tokio::io;
+use tokio::{io, sync::watch};
 
 #[cfg(target_os = "android")]
 use talpid_core::connectivity_listener::ConnectivityListener;
@@ -162,6 +181,10 @@ pub enum Error {
     #[error("Split tunneling error")]
     SplitTunnelError(#[source] split_tunnel::Error),
 
+    #[cfg(target_os = "linux")]
+    #[error("Failed to inspect the firewall")]
+    InspectFirewall(#[source] talpid_core::firewall::Error),
+
     #[error("An account is already set")]
     AlreadyLoggedIn,
 
@@ -200,6 +223,12 @@ pub enum Error {
     #[error("No custom bridge has been specified")]
     NoCustomProxySaved,
 
+    #[error("Invalid WireGuard configuration")]
+    WgQuickConfig(#[source] mullvad_types::wg_quick::Error),
+
+    #[error("Not connected to a WireGuard tunnel")]
+    NoWireguardTunnel,
+
     #[cfg(target_os = "macos")]
     #[error("Failed to set exclusion group")]
     GroupIdError(#[source] io::Error),
@@ -256,6 +285,10 @@ pub enum DaemonCommand {
     SetRelaySettings(ResponseTx<(), settings::Error>, RelaySettings),
     /// Set the allow LAN setting.
     SetAllowLan(ResponseTx<(), settings::Error>, bool),
+    /// Set which LAN networks and inbound ports are allowed, and which networks are always
+    /// allowed.
+    #[cfg(not(target_os = "android"))]
+    SetLanAccess(ResponseTx<(), settings::Error>, LanAccess),
     /// Set the beta program setting.
     SetShowBetaReleases(ResponseTx<(), settings::Error>, bool),
     /// Set the block_when_disconnected setting.
@@ -269,6 +302,24 @@ pub enum DaemonCommand {
     SetBridgeSettings(ResponseTx<(), Error>, BridgeSettings),
     /// Set proxy state
     SetBridgeState(ResponseTx<(), settings::Error>, BridgeState),
+    /// Set the strategy used to pick a relay among all matching relays
+    SetRelaySelectionStrategy(ResponseTx<(), settings::Error>, RelaySelectionStrategy),
+    /// Never select relays matching the given exclusion
+    AddRelayExclusion(ResponseTx<(), settings::Error>, RelayExclusion),
+    /// Stop excluding relays matching the given exclusion
+    RemoveRelayExclusion(ResponseTx<(), settings::Error>, RelayExclusion),
+    /// Stop excluding any relays
+    ClearRelayExclusions(ResponseTx<(), settings::Error>),
+    /// Return the relays that recently failed to connect
+    GetRelayFailures(oneshot::Sender<Vec<RelayFailure>>),
+    /// Forget all relay connection failures
+    ClearRelayFailures(oneshot::Sender<()>),
+    /// Return the obfuscation methods that have been learned to work, per network
+    GetLearnedObfuscation(
+        oneshot::Sender<Vec<mullvad_types::learned_obfuscation::LearnedObfuscation>>,
+    ),
+    /// Forget all learned obfuscation methods
+    ResetLearnedObfuscation(ResponseTx<(), learned_obfuscation::Error>),
     /// Set if IPv6 should be enabled in the tunnel
     SetEnableIpv6(ResponseTx<(), settings::Error>, bool),
     /// Set whether to enable PQ PSK exchange in the tunnel
@@ -307,6 +358,29 @@ pub enum DaemonCommand {
     UpdateCustomList(ResponseTx<(), Error>, CustomList),
     /// Remove all custom lists
     ClearCustomLists(ResponseTx<(), Error>),
+    /// Add a settings profile, or replace an existing one with the same name
+    SetProfile(ResponseTx<(), Error>, mullvad_types::profile::Profile),
+    /// Delete a settings profile
+    DeleteProfile(ResponseTx<(), Error>, String),
+    /// Apply the settings stored in a profile
+    ApplyProfile(ResponseTx<(), Error>, String),
+    /// Update the trusted networks
+    SetTrustedNetworkSettings(
+        ResponseTx<(), settings::Error>,
+        mullvad_types::trusted_network::TrustedNetworkSettings,
+    ),
+    /// Return the identifiers of the network that the device is connected to
+    GetCurrentNetwork(oneshot::Sender<mullvad_types::trusted_network::CurrentNetwork>),
+    /// Return recorded tunnel sessions
+    GetConnectionHistory(
+        oneshot::Sender<Vec<mullvad_types::connection_history::ConnectionHistoryEntry>>,
+    ),
+    /// Return a receiver of traffic statistics for the current tunnel
+    GetTunnelStatsListener(oneshot::Sender<watch::Receiver<Option<TunnelStats>>>),
+    /// Use a wg-quick configuration as a custom tunnel endpoint
+    ImportWireguardConfig(ResponseTx<(), Error>, String, Option<Ipv4Addr>, bool),
+    /// Export the current WireGuard tunnel as a wg-quick configuration
+    ExportWireguardConfig(ResponseTx<String, Error>),
     /// Add API access methods
     AddApiAccessMethod(
         ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -352,17 +426,22 @@ pub enum DaemonCommand {
     /// Clear list of processes excluded from the tunnel
     #[cfg(target_os = "linux")]
     ClearSplitTunnelProcesses(ResponseTx<(), split_tunnel::Error>),
+    /// Set whether split tunneled processes bypass the tunnel or are the only ones using it
+    #[cfg(target_os = "linux")]
+    SetSplitTunnelMode(ResponseTx<(), settings::Error>, SplitTunnelMode),
+    /// Set destinations whose traffic is excluded from the tunnel
+    #[cfg(not(target_os = "android"))]
+    SetSplitTunnelDestinations(
+        ResponseTx<(), settings::Error>,
+        mullvad_types::split_tunnel_destination::SplitTunnelDestinations,
+    ),
     /// Exclude traffic of an application from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     AddSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Remove application from list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     RemoveSplitTunnelApp(ResponseTx<(), Error>, SplitApp),
     /// Clear list of apps to exclude from the tunnel
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ClearSplitTunnelApps(ResponseTx<(), Error>),
     /// Enable or disable split tunneling
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     SetSplitTunnelState(ResponseTx<(), Error>, bool),
     /// Returns all processes currently being excluded from the tunnel
     #[cfg(windows)]
@@ -391,6 +470,12 @@ pub enum DaemonCommand {
     ExportJsonSettings(ResponseTx<String, settings::patch::Error>),
     /// Request the current feature indicators.
     GetFeatureIndicators(oneshot::Sender<FeatureIndicators>),
+    /// Render a firewall policy without applying it, along with the installed ruleset.
+    #[cfg(target_os = "linux")]
+    InspectFirewall(
+        ResponseTx<FirewallInspection, Error>,
+        FirewallInspectionRequest,
+    ),
 }
 
 /// All events that can happen in the daemon. Sent from various threads and exposed interfaces.
@@ -418,13 +503,19 @@ pub(crate) enum InternalDaemonEvent {
     /// A generic event for when any settings change.
     SettingsChanged,
     /// The split tunnel paths or state were updated.
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     ExcludedPathsEvent(ExcludedPathsUpdate, oneshot::Sender<Result<(), Error>>),
     /// A network leak was detected.
     LeakDetected(LeakInfo),
+    /// The current network was checked. Sent periodically, and when the default route changes.
+    CurrentNetwork(mullvad_types::trusted_network::CurrentNetwork),
+    /// Custom DNS blocklists were loaded.
+    #[cfg(not(target_os = "android"))]
+    DnsFilter(dns_blocklist::DnsFilterUpdate),
+    /// The excluded domains or the resolvers used for them changed.
+    #[cfg(not(target_os = "android"))]
+    ExcludedDomains(split_tunnel_destinations::ExcludedDomainsUpdate),
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 pub(crate) enum ExcludedPathsUpdate {
     SetState(bool),
     SetPaths(HashSet<SplitApp>),
@@ -463,6 +554,26 @@ impl From<(AccessMethodEvent, oneshot::Sender<()>)> for InternalDaemonEvent {
     }
 }
 
+impl From<mullvad_types::trusted_network::CurrentNetwork> for InternalDaemonEvent {
+    fn from(network: mullvad_types::trusted_network::CurrentNetwork) -> Self {
+        InternalDaemonEvent::CurrentNetwork(network)
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl From<dns_blocklist::DnsFilterUpdate> for InternalDaemonEvent {
+    fn from(update: dns_blocklist::DnsFilterUpdate) -> Self {
+        InternalDaemonEvent::DnsFilter(update)
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl From<split_tunnel_destinations::ExcludedDomainsUpdate> for InternalDaemonEvent {
+    fn from(update: split_tunnel_destinations::ExcludedDomainsUpdate) -> Self {
+        InternalDaemonEvent::ExcludedDomains(update)
+    }
+}
+
 pub struct DaemonCommandChannel {
     sender: DaemonCommandSender,
     receiver: mpsc::UnboundedReceiver<InternalDaemonEvent>,
@@ -574,6 +685,8 @@ pub struct Daemon {
     target_state: PersistentTargetState,
     #[cfg(target_os = "linux")]
     exclude_pids: split_tunnel::PidManager,
+    #[cfg(target_os = "linux")]
+    excluded_apps: split_tunnel::ExcludedApps,
     rx: mpsc::UnboundedReceiver<InternalDaemonEvent>,
     tx: DaemonEventSender,
     reconnection_job: Option<AbortHandle>,
@@ -596,6 +709,24 @@ pub struct Daemon {
     volume_update_tx: mpsc::UnboundedSender<()>,
     location_handler: GeoIpHandler,
     leak_checker: LeakChecker,
+    current_network: mullvad_types::trusted_network::CurrentNetwork,
+    /// Whether [Daemon::current_network] was trusted when trusted networks were last evaluated.
+    network_trusted: Option<bool>,
+    metrics: metrics::Metrics,
+    connection_history: connection_history::ConnectionHistoryHandle,
+    /// Filter built from the custom DNS blocklists, once they have been loaded.
+    #[cfg(not(target_os = "android"))]
+    dns_filter: Option<Arc<talpid_core::dns::filter::DnsFilter>>,
+    /// Directory that custom DNS blocklist files must be located in.
+    #[cfg(not(target_os = "android"))]
+    dns_blocklist_dir: PathBuf,
+    /// Excluded domains whose addresses the local DNS forwarder should report.
+    #[cfg(not(target_os = "android"))]
+    excluded_domains: Option<talpid_core::dns::forwarder::ExcludedDomains>,
+    #[cfg(not(target_os = "android"))]
+    split_tunnel_destinations: split_tunnel_destinations::SplitTunnelDestinationsHandle,
+    #[cfg(not(target_os = "android"))]
+    latency_monitor: relay_latency::LatencyMonitorHandle,
 }
 pub struct DaemonConfig {
     pub log_dir: Option<PathBuf>,
@@ -604,6 +735,8 @@ pub struct DaemonConfig {
     pub cache_dir: PathBuf,
     pub rpc_socket_path: PathBuf,
     pub endpoint: ApiEndpoint,
+    /// Address to serve OpenMetrics on, if any.
+    pub metrics_address: Option<SocketAddr>,
     #[cfg(target_os = "android")]
     pub android_context: AndroidContext,
 }
@@ -679,6 +812,18 @@ impl Daemon {
                 .set_config(SelectorConfig::from_settings(settings));
         });
 
+        // Measure relay latencies whenever the constraints or the selection strategy change.
+        // Relays that have been measured recently are not measured again.
+        #[cfg(not(target_os = "android"))]
+        let latency_monitor = {
+            // The tunnel state machine starts out disconnected
+            let latency_monitor =
+                relay_latency::spawn(relay_selector.clone(), !settings.block_when_disconnected);
+            let handle = latency_monitor.clone();
+            settings.register_change_listener(move |_settings| handle.update());
+            latency_monitor
+        };
+
         let (access_mode_handler, access_mode_provider) = api::AccessModeSelector::spawn(
             config.cache_dir.clone(),
             relay_selector.clone(),
@@ -760,10 +905,28 @@ impl Daemon {
             vec![]
         };
 
+        // The cgroup must exist before the firewall is configured, since it is matched on there
+        #[cfg(target_os = "linux")]
+        let exclude_pids = split_tunnel::PidManager::new().map_err(Error::InitSplitTunneling)?;
+        #[cfg(target_os = "linux")]
+        let excluded_apps = {
+            let paths = if settings.split_tunnel.enable_exclusions {
+                split_app_paths(&settings.split_tunnel.apps)
+            } else {
+                HashSet::new()
+            };
+            split_tunnel::ExcludedApps::spawn(exclude_pids.clone(), paths)
+        };
+
+        #[cfg(not(target_os = "android"))]
+        let excluded_destinations =
+            split_tunnel_destinations::active_destinations(&settings.split_tunnel);
+
         let parameters_generator = tunnel::ParametersGenerator::new(
             account_manager.clone(),
             relay_selector.clone(),
             settings.tunnel_options.clone(),
+            learned_obfuscation::LearnedObfuscationStore::new(&config.cache_dir).await,
         );
 
         let param_gen = parameters_generator.clone();
@@ -802,8 +965,16 @@ impl Daemon {
             tunnel_state_machine::InitialTunnelState {
                 allow_lan: settings.allow_lan,
                 #[cfg(not(target_os = "android"))]
+                lan_access: settings.lan_access.clone(),
+                #[cfg(not(target_os = "android"))]
                 block_when_disconnected: settings.block_when_disconnected,
-                dns_config: dns::addresses_from_options(&settings.tunnel_options.dns_options),
+                dns_config: dns::config_from_options(
+                    &settings.tunnel_options.dns_options,
+                    #[cfg(not(target_os = "android"))]
+                    None,
+                    #[cfg(not(target_os = "android"))]
+                    None,
+                ),
                 allowed_endpoint: access_mode_handler
                     .get_current()
                     .await
@@ -812,6 +983,10 @@ impl Daemon {
                 reset_firewall: *target_state != TargetState::Secured,
                 #[cfg(any(windows, target_os = "android", target_os = "macos"))]
                 exclude_paths,
+                #[cfg(target_os = "linux")]
+                split_tunnel_mode: settings.split_tunnel.effective_mode(),
+                #[cfg(not(target_os = "android"))]
+                excluded_destinations: excluded_destinations.networks.clone(),
             },
             parameters_generator.clone(),
             config.log_dir,
@@ -836,6 +1011,40 @@ impl Daemon {
 
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
+        // Resolve excluded domains and keep the tunnel state machine up to date
+        #[cfg(not(target_os = "android"))]
+        let split_tunnel_destinations = {
+            let initial_networks = excluded_destinations.networks.clone();
+            let destinations_handle = split_tunnel_destinations::spawn(
+                Arc::downgrade(tunnel_state_machine_handle.command_tx()),
+                internal_event_tx.to_specialized_sender(),
+                excluded_destinations,
+                initial_networks,
+            );
+            let listener_handle = destinations_handle.clone();
+            settings.register_change_listener(move |settings| {
+                listener_handle.update(&settings.split_tunnel);
+            });
+            destinations_handle
+        };
+
+        // Load custom DNS blocklists and keep them up to date
+        #[cfg(not(target_os = "android"))]
+        let dns_blocklist_dir = config
+            .settings_dir
+            .join(mullvad_types::settings::DNS_BLOCKLIST_DIR);
+        #[cfg(not(target_os = "android"))]
+        {
+            let blocklist_handle = dns_blocklist::spawn(
+                internal_event_tx.to_specialized_sender(),
+                dns_blocklist_dir.clone(),
+                settings.tunnel_options.dns_options.blocklists.clone(),
+            );
+            settings.register_change_listener(move |settings| {
+                blocklist_handle.update(&settings.tunnel_options.dns_options.blocklists);
+            });
+        }
+
         let relay_list_listener = management_interface.notifier().clone();
         let on_relay_list_update = move |relay_list: &RelayList| {
             relay_list_listener.notify_relay_list(relay_list.clone());
@@ -870,6 +1079,11 @@ impl Daemon {
             internal_event_tx.clone().to_specialized_sender(),
         );
 
+        current_network::spawn_monitor(
+            route_manager.clone(),
+            internal_event_tx.to_specialized_sender(),
+        );
+
         let leak_checker = {
             let mut leak_checker = LeakChecker::new(route_manager);
             let internal_event_tx = internal_event_tx.clone();
@@ -881,6 +1095,29 @@ impl Daemon {
             leak_checker
         };
 
+        let connection_history = connection_history::spawn(
+            &config.settings_dir,
+            tunnel_state_machine_handle.tunnel_stats(),
+        )
+        .await;
+
+        let metrics = metrics::Metrics::new();
+        if let Some(address) = config.metrics_address {
+            if let Err(error) = metrics
+                .spawn_server(
+                    address,
+                    relay_selector.clone(),
+                    tunnel_state_machine_handle.tunnel_stats(),
+                )
+                .await
+            {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to start metrics server")
+                );
+            }
+        }
+
         let daemon = Daemon {
             tunnel_state: TunnelState::Disconnected {
                 location: None,
@@ -889,7 +1126,9 @@ impl Daemon {
             },
             target_state,
             #[cfg(target_os = "linux")]
-            exclude_pids: split_tunnel::PidManager::new().map_err(Error::InitSplitTunneling)?,
+            exclude_pids,
+            #[cfg(target_os = "linux")]
+            excluded_apps,
             rx: internal_event_rx,
             tx: internal_event_tx,
             reconnection_job: None,
@@ -912,6 +1151,20 @@ impl Daemon {
             volume_update_tx,
             location_handler,
             leak_checker,
+            current_network: Default::default(),
+            network_trusted: None,
+            metrics,
+            connection_history,
+            #[cfg(not(target_os = "android"))]
+            dns_filter: None,
+            #[cfg(not(target_os = "android"))]
+            dns_blocklist_dir,
+            #[cfg(not(target_os = "android"))]
+            excluded_domains: None,
+            #[cfg(not(target_os = "android"))]
+            split_tunnel_destinations,
+            #[cfg(not(target_os = "android"))]
+            latency_monitor,
         };
 
         api_availability.unsuspend();
@@ -1033,12 +1286,16 @@ impl Daemon {
             SettingsChanged => {
                 self.update_feature_indicators_on_settings_changed();
             }
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ExcludedPathsEvent(update, tx) => self.handle_new_excluded_paths(update, tx).await,
             LeakDetected(leak_info) => {
                 log::warn!("Network leak detected! Please contact Mullvad support.");
                 log::warn!("{leak_info:?}")
             }
+            CurrentNetwork(network) => self.handle_current_network(network).await,
+            #[cfg(not(target_os = "android"))]
+            DnsFilter(update) => self.handle_dns_filter(update),
+            #[cfg(not(target_os = "android"))]
+            ExcludedDomains(update) => self.handle_excluded_domains(update),
         }
         should_stop
     }
@@ -1049,10 +1306,22 @@ impl Daemon {
     ) {
         self.leak_checker
             .on_tunnel_state_transition(tunnel_state_transition.clone());
+        self.metrics
+            .on_tunnel_state_transition(&tunnel_state_transition);
 
         self.reset_rpc_sockets_on_tunnel_state_transition(&tunnel_state_transition);
         self.device_checker
             .handle_state_transition(&tunnel_state_transition);
+        #[cfg(not(target_os = "android"))]
+        if let TunnelStateTransition::Disconnected { .. } = tunnel_state_transition {
+            self.split_tunnel_destinations.tunnel_disconnected();
+        }
+        // Relays are pinged directly, which is only possible while traffic is not blocked
+        #[cfg(not(target_os = "android"))]
+        self.latency_monitor.set_can_probe(matches!(
+            tunnel_state_transition,
+            TunnelStateTransition::Disconnected { locked_down: false }
+        ));
 
         let tunnel_state = match tunnel_state_transition {
             #[cfg(not(target_os = "android"))]
@@ -1075,6 +1344,7 @@ impl Daemon {
                 }
             }
             TunnelStateTransition::Connected(endpoint) => {
+                self.parameters_generator.report_connected().await;
                 let feature_indicators = compute_feature_indicators(
                     self.settings.settings(),
                     &endpoint,
@@ -1092,6 +1362,8 @@ impl Daemon {
             TunnelStateTransition::Error(error_state) => TunnelState::Error(error_state),
         };
 
+        self.connection_history.on_tunnel_state(&tunnel_state);
+
         if !tunnel_state.is_connected() {
             // Cancel reconnects except when entering the connected state.
             // Exempt the latter because a reconnect scheduled while connecting should not be
@@ -1201,7 +1473,14 @@ impl Daemon {
                 ref mut location,
                 #[cfg(not(target_os = "android"))]
                     locked_down: _,
-            } => *location = Some(fetched_location),
+            } => {
+                // Remember where the device is, so that relays nearby can be selected
+                if !fetched_location.mullvad_exit_ip {
+                    self.relay_selector
+                        .set_device_location(Some(Coordinates::from(&fetched_location)));
+                }
+                *location = Some(fetched_location)
+            }
             TunnelState::Connected {
                 ref mut location, ..
             } => {
@@ -1323,6 +1602,8 @@ impl Daemon {
             ClearAccountHistory(tx) => self.on_clear_account_history(tx).await,
             SetRelaySettings(tx, update) => self.on_set_relay_settings(tx, update).await,
             SetAllowLan(tx, allow_lan) => self.on_set_allow_lan(tx, allow_lan).await,
+            #[cfg(not(target_os = "android"))]
+            SetLanAccess(tx, lan_access) => self.on_set_lan_access(tx, lan_access).await,
             SetShowBetaReleases(tx, enabled) => self.on_set_show_beta_releases(tx, enabled).await,
             #[cfg(not(target_os = "android"))]
             SetBlockWhenDisconnected(tx, block_when_disconnected) => {
@@ -1335,6 +1616,18 @@ impl Daemon {
                 self.on_set_bridge_settings(tx, bridge_settings).await
             }
             SetBridgeState(tx, bridge_state) => self.on_set_bridge_state(tx, bridge_state).await,
+            SetRelaySelectionStrategy(tx, strategy) => {
+                self.on_set_relay_selection_strategy(tx, strategy).await
+            }
+            AddRelayExclusion(tx, exclusion) => self.on_add_relay_exclusion(tx, exclusion).await,
+            RemoveRelayExclusion(tx, exclusion) => {
+                self.on_remove_relay_exclusion(tx, exclusion).await
+            }
+            ClearRelayExclusions(tx) => self.on_clear_relay_exclusions(tx).await,
+            GetRelayFailures(tx) => self.on_get_relay_failures(tx),
+            ClearRelayFailures(tx) => self.on_clear_relay_failures(tx),
+            GetLearnedObfuscation(tx) => self.on_get_learned_obfuscation(tx).await,
+            ResetLearnedObfuscation(tx) => self.on_reset_learned_obfuscation(tx).await,
             SetEnableIpv6(tx, enable_ipv6) => self.on_set_enable_ipv6(tx, enable_ipv6).await,
             SetQuantumResistantTunnel(tx, quantum_resistant_state) => {
                 self.on_set_quantum_resistant_tunnel(tx, quantum_resistant_state)
@@ -1367,6 +1660,21 @@ impl Daemon {
             DeleteCustomList(tx, id) => self.on_delete_custom_list(tx, id).await,
             UpdateCustomList(tx, update) => self.on_update_custom_list(tx, update).await,
             ClearCustomLists(tx) => self.on_clear_custom_lists(tx).await,
+            SetProfile(tx, profile) => self.on_set_profile(tx, profile).await,
+            DeleteProfile(tx, name) => self.on_delete_profile(tx, name).await,
+            ApplyProfile(tx, name) => self.on_apply_profile(tx, name).await,
+            SetTrustedNetworkSettings(tx, trusted_networks) => {
+                self.on_set_trusted_network_settings(tx, trusted_networks)
+                    .await
+            }
+            GetCurrentNetwork(tx) => self.on_get_current_network(tx),
+            GetTunnelStatsListener(tx) => self.on_get_tunnel_stats_listener(tx),
+            GetConnectionHistory(tx) => self.on_get_connection_history(tx),
+            ImportWireguardConfig(tx, config, ipv4_gateway, apply_dns_and_mtu) => {
+                self.on_import_wireguard_config(tx, config, ipv4_gateway, apply_dns_and_mtu)
+                    .await
+            }
+            ExportWireguardConfig(tx) => self.on_export_wireguard_config(tx).await,
             GetVersionInfo(tx) => self.on_get_version_info(tx),
             AddApiAccessMethod(tx, name, enabled, access_method) => {
                 self.on_add_access_method(tx, name, enabled, access_method)
@@ -1391,13 +1699,16 @@ impl Daemon {
             RemoveSplitTunnelProcess(tx, pid) => self.on_remove_split_tunnel_process(tx, pid),
             #[cfg(target_os = "linux")]
             ClearSplitTunnelProcesses(tx) => self.on_clear_split_tunnel_processes(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+            #[cfg(target_os = "linux")]
+            SetSplitTunnelMode(tx, mode) => self.on_set_split_tunnel_mode(tx, mode).await,
+            #[cfg(not(target_os = "android"))]
+            SetSplitTunnelDestinations(tx, destinations) => {
+                self.on_set_split_tunnel_destinations(tx, destinations)
+                    .await
+            }
             AddSplitTunnelApp(tx, app) => self.on_add_split_tunnel_app(tx, app),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             RemoveSplitTunnelApp(tx, path) => self.on_remove_split_tunnel_app(tx, path),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             ClearSplitTunnelApps(tx) => self.on_clear_split_tunnel_apps(tx),
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
             SetSplitTunnelState(tx, enabled) => self.on_set_split_tunnel_state(tx, enabled),
             #[cfg(windows)]
             GetSplitTunnelProcesses(tx) => self.on_get_split_tunnel_processes(tx),
@@ -1418,6 +1729,8 @@ impl Daemon {
             ApplyJsonSettings(tx, blob) => self.on_apply_json_settings(tx, blob).await,
             ExportJsonSettings(tx) => self.on_export_json_settings(tx),
             GetFeatureIndicators(tx) => self.on_get_feature_indicators(tx),
+            #[cfg(target_os = "linux")]
+            InspectFirewall(tx, request) => self.on_inspect_firewall(tx, request),
         }
     }
 
@@ -1493,6 +1806,7 @@ impl Daemon {
         #[cfg(target_os = "android")]
         match event {
             AccessMethodEvent::New { setting, .. } => {
+                self.metrics.on_access_method_change(&setting.name);
                 // On android mullvad-api invokes protect on a socket to send requests
                 // outside the tunnel
                 let notifier = self.management_interface.notifier().clone();
@@ -1517,6 +1831,7 @@ impl Daemon {
                 });
             }
             AccessMethodEvent::New { setting, endpoint } => {
+                self.metrics.on_access_method_change(&setting.name);
                 // Update the firewall to exempt a new API endpoint.
                 let (completion_tx, completion_rx) = oneshot::channel();
                 self.send_tunnel_command(TunnelCommand::AllowEndpoint(endpoint, completion_tx));
@@ -1576,7 +1891,6 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn handle_new_excluded_paths(
         &mut self,
         update: ExcludedPathsUpdate,
@@ -1619,6 +1933,15 @@ impl Daemon {
                 .await
                 .map_err(Error::SettingsError),
         };
+        // Whether the inverse mode applies depends on the state and the selected apps
+        #[cfg(target_os = "linux")]
+        if let Ok(true) = save_result {
+            let (mode_tx, _mode_rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                self.settings.split_tunnel.effective_mode(),
+                mode_tx,
+            ));
+        }
         let _ = tx.send(save_result.map(|_| ()));
     }
 
@@ -1910,6 +2233,22 @@ impl Daemon {
             last_error = Some("Failed to clear account history");
         }
 
+        if let Err(error) = self.connection_history.clear().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to clear connection history")
+            );
+            last_error = Some("Failed to clear connection history");
+        }
+
+        if let Err(error) = self.parameters_generator.clear_learned_obfuscation().await {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to clear learned obfuscation methods")
+            );
+            last_error = Some("Failed to clear learned obfuscation methods");
+        }
+
         if let Err(e) = self.settings.reset().await {
             log::error!("Failed to reset settings: {}", e);
             last_error = Some("Failed to reset settings");
@@ -1969,6 +2308,57 @@ impl Daemon {
         Self::oneshot_send(tx, result, "clear_split_tunnel_processes response");
     }
 
+    #[cfg(target_os = "linux")]
+    async fn on_set_split_tunnel_mode(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        mode: SplitTunnelMode,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.split_tunnel.mode = mode)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                        self.settings.split_tunnel.effective_mode(),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_split_tunnel_mode response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_split_tunnel_mode response");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_split_tunnel_destinations(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        destinations: mullvad_types::split_tunnel_destination::SplitTunnelDestinations,
+    ) {
+        // The tunnel state machine is updated by a settings listener once domains are resolved
+        let result = self
+            .settings
+            .update(move |settings| settings.split_tunnel.destinations = destinations)
+            .await
+            .map(|_| ());
+        if let Err(error) = &result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to save settings")
+            );
+        }
+        Self::oneshot_send(tx, result, "set_split_tunnel_destinations response");
+    }
+
     /// Update the split app paths in both the settings and tunnel
     #[cfg(any(windows, target_os = "android"))]
     fn set_split_tunnel_paths(
@@ -2040,6 +2430,29 @@ impl Daemon {
         }
     }
 
+    /// Update the split app paths in both the settings and the app monitor
+    #[cfg(target_os = "linux")]
+    fn set_split_tunnel_paths(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        _response_msg: &'static str,
+        settings: Settings,
+        update: ExcludedPathsUpdate,
+    ) {
+        let paths = match update {
+            ExcludedPathsUpdate::SetPaths(ref paths) if settings.split_tunnel.enable_exclusions => {
+                split_app_paths(paths)
+            }
+            ExcludedPathsUpdate::SetState(true) => split_app_paths(&settings.split_tunnel.apps),
+            _ => HashSet::new(),
+        };
+        self.excluded_apps.set_paths(paths);
+
+        let _ = self
+            .tx
+            .send(InternalDaemonEvent::ExcludedPathsEvent(update, tx));
+    }
+
     /// Update the split app paths in both the settings and tunnel
     #[cfg(target_os = "macos")]
     fn set_split_tunnel_paths(
@@ -2090,7 +2503,6 @@ impl Daemon {
         });
     }
 
-    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "android"))]
     fn on_add_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: SplitApp) {
         let settings = self.settings.to_settings();
 
@@ -2108,7 +2520,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_remove_split_tunnel_app(&mut self, tx: ResponseTx<(), Error>, app: impl Into<SplitApp>) {
         let settings = self.settings.to_settings();
 
@@ -2126,7 +2537,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_clear_split_tunnel_apps(&mut self, tx: ResponseTx<(), Error>) {
         let settings = self.settings.to_settings();
         let new_list = HashSet::new();
@@ -2138,7 +2548,6 @@ impl Daemon {
         );
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     fn on_set_split_tunnel_state(&mut self, tx: ResponseTx<(), Error>, state: bool) {
         let settings = self.settings.to_settings();
         self.set_split_tunnel_paths(
@@ -2219,6 +2628,37 @@ impl Daemon {
         }
     }
 
+    #[cfg(not(target_os = "android"))]
+    async fn on_set_lan_access(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        lan_access: LanAccess,
+    ) {
+        let lan_access_copy = lan_access.clone();
+        match self
+            .settings
+            .update(move |settings| settings.lan_access = lan_access_copy)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::LanAccess(
+                        lan_access,
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), "set_lan_access response");
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_lan_access response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_lan_access response");
+            }
+        }
+    }
+
     async fn on_set_show_beta_releases(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2416,6 +2856,123 @@ impl Daemon {
         Self::oneshot_send(tx, result, "on_set_bridge_state response");
     }
 
+    async fn on_set_relay_selection_strategy(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        strategy: RelaySelectionStrategy,
+    ) {
+        // The new strategy takes effect the next time a relay is selected, so there is no need to
+        // reconnect.
+        let result = match self
+            .settings
+            .update(move |settings| settings.relay_selection_strategy = strategy)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to set relay selection strategy")
+                );
+                Err(error)
+            }
+        };
+        Self::oneshot_send(tx, result, "on_set_relay_selection_strategy response");
+    }
+
+    async fn on_add_relay_exclusion(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        exclusion: RelayExclusion,
+    ) {
+        let result = self
+            .update_relay_exclusions(|exclusions| {
+                exclusions.insert(exclusion);
+            })
+            .await;
+        Self::oneshot_send(tx, result, "on_add_relay_exclusion response");
+    }
+
+    async fn on_remove_relay_exclusion(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        exclusion: RelayExclusion,
+    ) {
+        let result = self
+            .update_relay_exclusions(|exclusions| {
+                exclusions.remove(&exclusion);
+            })
+            .await;
+        Self::oneshot_send(tx, result, "on_remove_relay_exclusion response");
+    }
+
+    async fn on_clear_relay_exclusions(&mut self, tx: ResponseTx<(), settings::Error>) {
+        let result = self
+            .update_relay_exclusions(|exclusions| *exclusions = RelayExclusions::default())
+            .await;
+        Self::oneshot_send(tx, result, "on_clear_relay_exclusions response");
+    }
+
+    fn on_get_relay_failures(&mut self, tx: oneshot::Sender<Vec<RelayFailure>>) {
+        Self::oneshot_send(tx, self.relay_selector.relay_failures(), "relay failures");
+    }
+
+    fn on_clear_relay_failures(&mut self, tx: oneshot::Sender<()>) {
+        self.relay_selector.clear_relay_failures();
+        Self::oneshot_send(tx, (), "on_clear_relay_failures response");
+    }
+
+    async fn on_get_learned_obfuscation(
+        &self,
+        tx: oneshot::Sender<Vec<mullvad_types::learned_obfuscation::LearnedObfuscation>>,
+    ) {
+        Self::oneshot_send(
+            tx,
+            self.parameters_generator.learned_obfuscation().await,
+            "get_learned_obfuscation response",
+        );
+    }
+
+    async fn on_reset_learned_obfuscation(&self, tx: ResponseTx<(), learned_obfuscation::Error>) {
+        let result = self.parameters_generator.clear_learned_obfuscation().await;
+        Self::oneshot_send(tx, result, "reset_learned_obfuscation response");
+    }
+
+    /// Apply `update_fn` to the relay exclusions, and reconnect if they changed. Exclusions only
+    /// apply to relays from the relay list, so an error is returned if a custom tunnel endpoint is
+    /// in use.
+    async fn update_relay_exclusions(
+        &mut self,
+        update_fn: impl FnOnce(&mut RelayExclusions),
+    ) -> Result<(), settings::Error> {
+        let result = self
+            .settings
+            .try_update(move |settings| match &mut settings.relay_settings {
+                RelaySettings::Normal(constraints) => {
+                    update_fn(&mut constraints.exclusions);
+                    Ok(())
+                }
+                RelaySettings::CustomTunnelEndpoint(_) => Err(ExclusionsUnsupported),
+            })
+            .await;
+        match result {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    log::info!("Initiating tunnel restart because the relay exclusions changed");
+                    self.reconnect_tunnel();
+                }
+                Ok(())
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to update relay exclusions")
+                );
+                Err(error)
+            }
+        }
+    }
+
     async fn on_set_enable_ipv6(&mut self, tx: ResponseTx<(), settings::Error>, enable_ipv6: bool) {
         match self
             .settings
@@ -2563,18 +3120,24 @@ impl Daemon {
         tx: ResponseTx<(), settings::Error>,
         dns_options: DnsOptions,
     ) {
+        #[cfg(not(target_os = "android"))]
+        let blocklist_dir = self.dns_blocklist_dir.clone();
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.dns_options = dns_options)
+            .try_update(move |settings| {
+                // Any user may change the settings, so only allow files that privileged users
+                // have placed in the blocklist directory
+                #[cfg(not(target_os = "android"))]
+                dns_options.blocklists.check_files(&blocklist_dir)?;
+                settings.tunnel_options.dns_options = dns_options;
+                Ok::<_, mullvad_types::settings::BlocklistFileNotAllowed>(())
+            })
             .await
         {
             Ok(settings_changed) => {
                 if settings_changed {
-                    let settings = self.settings.settings();
-                    let resolvers =
-                        dns::addresses_from_options(&settings.tunnel_options.dns_options);
                     self.send_tunnel_command(TunnelCommand::Dns(
-                        resolvers,
+                        self.dns_config(),
                         oneshot_map(tx, |tx, ()| {
                             Self::oneshot_send(tx, Ok(()), "set_dns_options response");
                         }),
@@ -2590,6 +3153,49 @@ impl Daemon {
         }
     }
 
+    /// Return the DNS config to use for the current settings.
+    fn dns_config(&self) -> talpid_core::dns::DnsConfig {
+        dns::config_from_options(
+            &self.settings.tunnel_options.dns_options,
+            #[cfg(not(target_os = "android"))]
+            self.dns_filter.clone(),
+            #[cfg(not(target_os = "android"))]
+            self.excluded_domains.clone(),
+        )
+    }
+
+    /// Use a filter built from the custom DNS blocklists, unless the blocklists have changed since.
+    #[cfg(not(target_os = "android"))]
+    fn handle_dns_filter(&mut self, update: dns_blocklist::DnsFilterUpdate) {
+        if update.blocklists != self.settings.tunnel_options.dns_options.blocklists
+            || update.filter == self.dns_filter
+        {
+            return;
+        }
+        self.dns_filter = update.filter;
+        let (tx, _rx) = oneshot::channel();
+        self.send_tunnel_command(TunnelCommand::Dns(self.dns_config(), tx));
+    }
+
+    /// Have the local DNS forwarder report the addresses of excluded domains, unless the excluded
+    /// domains have changed since.
+    #[cfg(not(target_os = "android"))]
+    fn handle_excluded_domains(
+        &mut self,
+        update: split_tunnel_destinations::ExcludedDomainsUpdate,
+    ) {
+        let destinations =
+            split_tunnel_destinations::active_destinations(&self.settings.split_tunnel);
+        if update.domains != destinations.domains
+            || update.excluded_domains == self.excluded_domains
+        {
+            return;
+        }
+        self.excluded_domains = update.excluded_domains;
+        let (tx, _rx) = oneshot::channel();
+        self.send_tunnel_command(TunnelCommand::Dns(self.dns_config(), tx));
+    }
+
     async fn on_set_relay_override(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2740,6 +3346,94 @@ impl Daemon {
         Self::oneshot_send(tx, result, "clear_custom_lists response");
     }
 
+    async fn on_set_profile(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        profile: mullvad_types::profile::Profile,
+    ) {
+        let result = self.set_profile(profile).await;
+        Self::oneshot_send(tx, result, "set_profile response");
+    }
+
+    async fn on_delete_profile(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let result = self.delete_profile(name).await;
+        Self::oneshot_send(tx, result, "delete_profile response");
+    }
+
+    async fn on_apply_profile(&mut self, tx: ResponseTx<(), Error>, name: String) {
+        let result = self.apply_profile(name).await;
+        Self::oneshot_send(tx, result, "apply_profile response");
+    }
+
+    async fn on_set_trusted_network_settings(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        trusted_networks: mullvad_types::trusted_network::TrustedNetworkSettings,
+    ) {
+        let result = self.set_trusted_network_settings(trusted_networks).await;
+        if let Err(error) = &result {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Unable to save settings")
+            );
+        }
+        Self::oneshot_send(tx, result, "set_trusted_network_settings response");
+    }
+
+    fn on_get_current_network(
+        &self,
+        tx: oneshot::Sender<mullvad_types::trusted_network::CurrentNetwork>,
+    ) {
+        Self::oneshot_send(
+            tx,
+            self.current_network.clone(),
+            "get_current_network response",
+        );
+    }
+
+    fn on_get_connection_history(
+        &self,
+        tx: oneshot::Sender<Vec<mullvad_types::connection_history::ConnectionHistoryEntry>>,
+    ) {
+        let connection_history = self.connection_history.clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(
+                tx,
+                connection_history.entries().await,
+                "get_connection_history response",
+            );
+        });
+    }
+
+    fn on_get_tunnel_stats_listener(
+        &self,
+        tx: oneshot::Sender<watch::Receiver<Option<TunnelStats>>>,
+    ) {
+        Self::oneshot_send(
+            tx,
+            self.tunnel_state_machine_handle.tunnel_stats(),
+            "get_tunnel_stats_listener response",
+        );
+    }
+
+    async fn on_import_wireguard_config(
+        &mut self,
+        tx: ResponseTx<(), Error>,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        apply_dns_and_mtu: bool,
+    ) {
+        let result = self
+            .import_wireguard_config(config, ipv4_gateway, apply_dns_and_mtu)
+            .await;
+        Self::oneshot_send(tx, result, "import_wireguard_config response");
+    }
+
+    async fn on_export_wireguard_config(&self, tx: ResponseTx<String, Error>) {
+        let result = self.export_wireguard_config().await;
+        Self::oneshot_send(tx, result, "export_wireguard_config response");
+    }
+
     async fn on_add_access_method(
         &mut self,
         tx: ResponseTx<mullvad_types::access_method::Id, Error>,
@@ -2920,6 +3614,15 @@ impl Daemon {
             let (tx, _rx) = oneshot::channel();
             self.send_tunnel_command(TunnelCommand::SetExcludedApps(tx, vec![]));
         }
+        #[cfg(target_os = "linux")]
+        {
+            self.excluded_apps.set_paths(HashSet::new());
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::SplitTunnelMode(
+                SplitTunnelMode::default(),
+                tx,
+            ));
+        }
 
         #[cfg(not(target_os = "android"))]
         {
@@ -2934,8 +3637,7 @@ impl Daemon {
         self.send_tunnel_command(TunnelCommand::AllowLan(self.settings.allow_lan, tx));
 
         let (tx, _rx) = oneshot::channel();
-        let dns = dns::addresses_from_options(&self.settings.tunnel_options.dns_options);
-        self.send_tunnel_command(TunnelCommand::Dns(dns, tx));
+        self.send_tunnel_command(TunnelCommand::Dns(self.dns_config(), tx));
 
         self.version_updater_handle
             .set_show_beta_releases(self.settings.show_beta_releases)
@@ -3079,6 +3781,27 @@ impl Daemon {
         Self::oneshot_send(tx, feature_indicators, "get_feature_indicators response");
     }
 
+    #[cfg(target_os = "linux")]
+    fn on_inspect_firewall(
+        &self,
+        tx: ResponseTx<FirewallInspection, Error>,
+        request: FirewallInspectionRequest,
+    ) {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.send_tunnel_command(TunnelCommand::InspectFirewall(request, result_tx));
+
+        tokio::spawn(async move {
+            match result_rx.await {
+                Ok(result) => Self::oneshot_send(
+                    tx,
+                    result.map_err(Error::InspectFirewall),
+                    "inspect_firewall response",
+                ),
+                Err(_) => log::error!("The tunnel failed to return a result"),
+            }
+        });
+    }
+
     /// Set the target state of the client. If it changed trigger the operations needed to
     /// progress towards that state.
     /// Returns a bool representing whether a state change was initiated.
@@ -3150,6 +3873,15 @@ impl DaemonShutdownHandle {
     }
 }
 
+/// Return the executable paths of the given excluded applications.
+#[cfg(target_os = "linux")]
+fn split_app_paths(apps: &HashSet<SplitApp>) -> HashSet<std::path::PathBuf> {
+    apps.iter()
+        .cloned()
+        .map(|app| std::path::PathBuf::from(app.to_tunnel_command_repr()))
+        .collect()
+}
+
 /// Consume a oneshot sender of `T1` and return a sender that takes a different type `T2`.
 /// `forwarder` should map `T1` back to `T2` and send the result back to the original receiver.
 fn oneshot_map<T1: Send + 'static, T2: Send + 'static>(
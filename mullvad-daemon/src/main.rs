@@ -220,6 +220,7 @@ async fn create_daemon(log_dir: Option<PathBuf>) -> Result<Daemon, String> {
             cache_dir,
             rpc_socket_path,
             endpoint: mullvad_api::ApiEndpoint::from_env_vars(),
+            metrics_address: cli::get_config().metrics_address,
         },
         DaemonCommandChannel::new(),
     )
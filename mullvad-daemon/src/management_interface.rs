@@ -1,4 +1,6 @@
-use crate::{account_history, device, version_check, DaemonCommand, DaemonCommandSender};
+use crate::{
+    account_history, device, learned_obfuscation, version_check, DaemonCommand, DaemonCommandSender,
+};
 use futures::{
     channel::{mpsc, oneshot},
     StreamExt,
@@ -11,7 +13,8 @@ use mullvad_management_interface::{
 use mullvad_types::{
     account::AccountNumber,
     relay_constraints::{
-        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, ObfuscationSettings, RelayExclusion, RelayOverride,
+        RelaySelectionStrategy, RelaySettings,
     },
     relay_list::RelayList,
     settings::{DnsOptions, Settings},
@@ -54,6 +57,7 @@ const USED_VOUCHER_MESSAGE: &str = "This voucher code has already been used";
 impl ManagementService for ManagementServiceImpl {
     type GetSplitTunnelProcessesStream = UnboundedReceiverStream<Result<i32, Status>>;
     type EventsListenStream = EventsListenerReceiver;
+    type TunnelStatsListenStream = UnboundedReceiverStream<Result<types::TunnelStats, Status>>;
 
     // Control and get the tunnel state
     //
@@ -92,6 +96,55 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(types::TunnelState::from(state)))
     }
 
+    async fn get_connection_history(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::ConnectionHistory> {
+        log::debug!("get_connection_history");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetConnectionHistory(tx))?;
+        self.wait_for_result(rx).await.map(|entries| {
+            Response::new(types::ConnectionHistory {
+                entries: entries
+                    .into_iter()
+                    .map(types::ConnectionHistoryEntry::from)
+                    .collect(),
+            })
+        })
+    }
+
+    async fn tunnel_stats_listen(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<Self::TunnelStatsListenStream> {
+        log::debug!("tunnel_stats_listen");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetTunnelStatsListener(tx))?;
+        let mut stats_rx = self.wait_for_result(rx).await?;
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    result = stats_rx.changed() => {
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    _ = tx.closed() => break,
+                }
+                let stats = *stats_rx.borrow_and_update();
+                if let Some(stats) = stats {
+                    if tx.send(Ok(types::TunnelStats::from(stats))).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+
     // Control the daemon and receive events
     //
 
@@ -235,6 +288,105 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_relay_selection_strategy(
+        &self,
+        request: Request<types::RelaySelectionStrategy>,
+    ) -> ServiceResult<()> {
+        let strategy = RelaySelectionStrategy::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+
+        log::debug!("set_relay_selection_strategy({:?})", strategy);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetRelaySelectionStrategy(tx, strategy))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn add_relay_exclusion(
+        &self,
+        request: Request<types::RelayExclusion>,
+    ) -> ServiceResult<()> {
+        let exclusion =
+            RelayExclusion::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+
+        log::debug!("add_relay_exclusion({:?})", exclusion);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::AddRelayExclusion(tx, exclusion))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn remove_relay_exclusion(
+        &self,
+        request: Request<types::RelayExclusion>,
+    ) -> ServiceResult<()> {
+        let exclusion =
+            RelayExclusion::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+
+        log::debug!("remove_relay_exclusion({:?})", exclusion);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::RemoveRelayExclusion(tx, exclusion))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn clear_relay_exclusions(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_relay_exclusions");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ClearRelayExclusions(tx))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn get_relay_failures(&self, _: Request<()>) -> ServiceResult<types::RelayFailureList> {
+        log::debug!("get_relay_failures");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetRelayFailures(tx))?;
+        self.wait_for_result(rx).await.map(|failures| {
+            Response::new(types::RelayFailureList {
+                failures: failures
+                    .into_iter()
+                    .map(types::RelayFailure::from)
+                    .collect(),
+            })
+        })
+    }
+
+    async fn clear_relay_failures(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("clear_relay_failures");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ClearRelayFailures(tx))?;
+        self.wait_for_result(rx).await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_learned_obfuscation(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::LearnedObfuscationList> {
+        log::debug!("get_learned_obfuscation");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetLearnedObfuscation(tx))?;
+        self.wait_for_result(rx).await.map(|entries| {
+            Response::new(types::LearnedObfuscationList {
+                entries: entries
+                    .into_iter()
+                    .map(types::LearnedObfuscation::from)
+                    .collect(),
+            })
+        })
+    }
+
+    async fn reset_learned_obfuscation(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("reset_learned_obfuscation");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ResetLearnedObfuscation(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_learned_obfuscation_error)
+    }
+
     // Settings
     //
 
@@ -264,6 +416,24 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    #[cfg(not(target_os = "android"))]
+    async fn set_lan_access(&self, request: Request<types::LanAccess>) -> ServiceResult<()> {
+        let lan_access = talpid_types::net::LanAccess::try_from(request.into_inner())?;
+        log::debug!("set_lan_access({:?})", lan_access);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetLanAccess(tx, lan_access))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    #[cfg(target_os = "android")]
+    async fn set_lan_access(&self, _: Request<types::LanAccess>) -> ServiceResult<()> {
+        log::debug!("set_lan_access");
+        Err(Status::unimplemented(
+            "Restricting LAN access is not supported on Android",
+        ))
+    }
+
     async fn set_show_beta_releases(&self, request: Request<bool>) -> ServiceResult<()> {
         let enabled = request.into_inner();
         log::debug!("set_show_beta_releases({})", enabled);
@@ -304,6 +474,66 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_trusted_network_settings(
+        &self,
+        request: Request<types::TrustedNetworkSettings>,
+    ) -> ServiceResult<()> {
+        log::debug!("set_trusted_network_settings");
+        let trusted_networks =
+            mullvad_types::trusted_network::TrustedNetworkSettings::try_from(request.into_inner())?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetTrustedNetworkSettings(
+            tx,
+            trusted_networks,
+        ))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn import_wireguard_config(
+        &self,
+        request: Request<types::WireguardConfigImport>,
+    ) -> ServiceResult<()> {
+        log::debug!("import_wireguard_config");
+        let request = request.into_inner();
+        let ipv4_gateway = request
+            .ipv4_gateway
+            .map(|gateway| gateway.parse())
+            .transpose()
+            .map_err(|_| Status::invalid_argument("invalid IPv4 gateway"))?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ImportWireguardConfig(
+            tx,
+            request.config,
+            ipv4_gateway,
+            request.apply_dns_and_mtu,
+        ))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn export_wireguard_config(&self, _: Request<()>) -> ServiceResult<String> {
+        log::debug!("export_wireguard_config");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportWireguardConfig(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn get_current_network(&self, _: Request<()>) -> ServiceResult<types::CurrentNetwork> {
+        log::debug!("get_current_network");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetCurrentNetwork(tx))?;
+        self.wait_for_result(rx)
+            .await
+            .map(types::CurrentNetwork::from)
+            .map(Response::new)
+    }
+
     async fn set_openvpn_mssfix(&self, request: Request<u32>) -> ServiceResult<()> {
         let mssfix = request.into_inner();
         let mssfix = if mssfix != 0 {
@@ -680,6 +910,39 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    // Settings profiles
+
+    async fn set_profile(&self, request: Request<types::Profile>) -> ServiceResult<()> {
+        log::debug!("set_profile");
+        let profile = mullvad_types::profile::Profile::try_from(request.into_inner())?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetProfile(tx, profile))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn delete_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        log::debug!("delete_profile");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::DeleteProfile(tx, request.into_inner()))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn apply_profile(&self, request: Request<String>) -> ServiceResult<()> {
+        log::debug!("apply_profile");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ApplyProfile(tx, request.into_inner()))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     // Access Methods
 
     async fn add_api_access_method(
@@ -884,7 +1147,51 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+    #[cfg(target_os = "linux")]
+    async fn set_split_tunnel_mode(
+        &self,
+        request: Request<types::SplitTunnelMode>,
+    ) -> ServiceResult<()> {
+        use mullvad_types::settings::SplitTunnelMode;
+
+        let mode = SplitTunnelMode::try_from(request.into_inner())?;
+        log::debug!("set_split_tunnel_mode({:?})", mode);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSplitTunnelMode(tx, mode))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+    #[cfg(not(target_os = "linux"))]
+    async fn set_split_tunnel_mode(&self, _: Request<types::SplitTunnelMode>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Split tunnel modes are only supported on Linux",
+        ))
+    }
+
+    #[cfg(not(target_os = "android"))]
+    async fn set_split_tunnel_destinations(
+        &self,
+        request: Request<types::SplitTunnelDestinations>,
+    ) -> ServiceResult<()> {
+        use mullvad_types::split_tunnel_destination::SplitTunnelDestinations;
+
+        let destinations = SplitTunnelDestinations::try_from(request.into_inner())?;
+        log::debug!("set_split_tunnel_destinations({:?})", destinations);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetSplitTunnelDestinations(tx, destinations))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+    #[cfg(target_os = "android")]
+    async fn set_split_tunnel_destinations(
+        &self,
+        _: Request<types::SplitTunnelDestinations>,
+    ) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "Split tunneling by destination is not supported on Android",
+        ))
+    }
+
     async fn add_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("add_split_tunnel_app");
@@ -902,7 +1209,6 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn remove_split_tunnel_app(&self, request: Request<String>) -> ServiceResult<()> {
         use mullvad_types::settings::SplitApp;
         log::debug!("remove_split_tunnel_app");
@@ -919,7 +1225,6 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn clear_split_tunnel_apps(&self, _: Request<()>) -> ServiceResult<()> {
         log::debug!("clear_split_tunnel_apps");
         let (tx, rx) = oneshot::channel();
@@ -934,7 +1239,6 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     async fn set_split_tunnel_state(&self, request: Request<bool>) -> ServiceResult<()> {
         log::debug!("set_split_tunnel_state");
         let enabled = request.into_inner();
@@ -1095,6 +1399,32 @@ impl ManagementService for ManagementServiceImpl {
 
         Ok(Response::new(feature_indicators))
     }
+
+    #[cfg(target_os = "linux")]
+    async fn inspect_firewall(
+        &self,
+        request: Request<types::FirewallInspectionRequest>,
+    ) -> ServiceResult<types::FirewallInspection> {
+        let request =
+            talpid_types::firewall::FirewallInspectionRequest::try_from(request.into_inner())?;
+        log::debug!("inspect_firewall({:?})", request);
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::InspectFirewall(tx, request))?;
+        let inspection = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(types::FirewallInspection::from(inspection)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn inspect_firewall(
+        &self,
+        _: Request<types::FirewallInspectionRequest>,
+    ) -> ServiceResult<types::FirewallInspection> {
+        log::debug!("inspect_firewall");
+        Err(Status::unimplemented(
+            "Inspecting the firewall is only supported on Linux",
+        ))
+    }
 }
 
 impl ManagementServiceImpl {
@@ -1216,8 +1546,8 @@ impl ManagementInterfaceEventBroadcaster {
     pub(crate) fn notify_settings(&self, settings: Settings) {
         log::debug!("Broadcasting new settings");
         self.notify(types::DaemonEvent {
-            event: Some(daemon_event::Event::Settings(types::Settings::from(
-                &settings,
+            event: Some(daemon_event::Event::Settings(Box::new(
+                types::Settings::from(&settings),
             ))),
         })
     }
@@ -1304,6 +1634,10 @@ fn map_daemon_error(error: crate::Error) -> Status {
             Status::unauthenticated(error.to_string())
         }
         DaemonError::VersionCheckError(error) => map_version_check_error(error),
+        DaemonError::WgQuickConfig(_) => Status::invalid_argument(error.display_chain()),
+        DaemonError::NoWireguardTunnel => Status::failed_precondition(error.to_string()),
+        #[cfg(target_os = "linux")]
+        DaemonError::InspectFirewall(_) => Status::unknown(error.display_chain()),
         error => Status::unknown(error.to_string()),
     }
 }
@@ -1373,6 +1707,16 @@ fn map_account_history_error(error: account_history::Error) -> Status {
     }
 }
 
+/// Converts an instance of [`crate::learned_obfuscation::Error`] into a tonic status.
+fn map_learned_obfuscation_error(error: learned_obfuscation::Error) -> Status {
+    match error {
+        learned_obfuscation::Error::Write(..) => {
+            Status::new(Code::FailedPrecondition, error.to_string())
+        }
+        learned_obfuscation::Error::Serialize(..) => Status::new(Code::Internal, error.to_string()),
+    }
+}
+
 fn map_version_check_error(error: version_check::Error) -> Status {
     match error {
         version_check::Error::Download(..)
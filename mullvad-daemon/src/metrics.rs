@@ -0,0 +1,409 @@
+//! Exports metrics about the daemon in the OpenMetrics text format over HTTP.
+//!
+//! The endpoint is disabled by default. It is enabled by passing `--metrics-address` to the
+//! daemon, after which `GET /metrics` on that address returns the current metrics.
+
+use mullvad_relay_selector::RelaySelector;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use talpid_types::{
+    tunnel::{TunnelStateTransition, TunnelStats},
+    ErrorExt,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+/// Requests with headers larger than this are rejected.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Connections that do not send a complete request within this time are closed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before accepting connections again after failing to accept one.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+const STATES: [&str; 5] = [
+    "disconnected",
+    "connecting",
+    "connected",
+    "disconnecting",
+    "error",
+];
+
+/// Collects metrics from daemon events. Cloning returns a handle to the same metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<Mutex<MetricsState>>,
+}
+
+struct MetricsState {
+    state: &'static str,
+    state_since: Instant,
+    /// Time spent in each state, excluding the time spent in the current state.
+    time_in_state: BTreeMap<&'static str, Duration>,
+    transitions: BTreeMap<&'static str, u64>,
+    reconnects: u64,
+    error_causes: BTreeMap<String, u64>,
+    access_method_changes: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::with_start(Instant::now())
+    }
+
+    fn with_start(start: Instant) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MetricsState {
+                state: "disconnected",
+                state_since: start,
+                time_in_state: BTreeMap::new(),
+                transitions: BTreeMap::new(),
+                reconnects: 0,
+                error_causes: BTreeMap::new(),
+                access_method_changes: BTreeMap::new(),
+            })),
+        }
+    }
+
+    pub fn on_tunnel_state_transition(&self, transition: &TunnelStateTransition) {
+        self.on_tunnel_state_transition_at(transition, Instant::now());
+    }
+
+    fn on_tunnel_state_transition_at(&self, transition: &TunnelStateTransition, now: Instant) {
+        let new_state = match transition {
+            TunnelStateTransition::Disconnected { .. } => "disconnected",
+            TunnelStateTransition::Connecting(_) => "connecting",
+            TunnelStateTransition::Connected(_) => "connected",
+            TunnelStateTransition::Disconnecting(_) => "disconnecting",
+            TunnelStateTransition::Error(_) => "error",
+        };
+
+        let mut state = self.inner.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.state_since);
+        *state.time_in_state.entry(state.state).or_default() += elapsed;
+
+        // Any attempt to connect that does not start from the disconnected state is a reconnect,
+        // whether it is caused by a failure, a retry, or a changed setting.
+        if new_state == "connecting" && state.state != "disconnected" {
+            state.reconnects += 1;
+        }
+        if let TunnelStateTransition::Error(error_state) = transition {
+            let cause = error_cause_name(error_state.cause());
+            *state.error_causes.entry(cause).or_default() += 1;
+        }
+        *state.transitions.entry(new_state).or_default() += 1;
+        state.state = new_state;
+        state.state_since = now;
+    }
+
+    /// Record that the API access method changed to the one named `name`.
+    pub fn on_access_method_change(&self, name: &str) {
+        let mut state = self.inner.lock().unwrap();
+        *state
+            .access_method_changes
+            .entry(name.to_owned())
+            .or_default() += 1;
+    }
+
+    /// Start serving metrics on `address`.
+    pub async fn spawn_server(
+        &self,
+        address: SocketAddr,
+        relay_selector: RelaySelector,
+        tunnel_stats: watch::Receiver<Option<TunnelStats>>,
+    ) -> io::Result<()> {
+        let listener = TcpListener::bind(address).await?;
+        if !address.ip().is_loopback() {
+            log::warn!("Metrics are exposed on a non-loopback address: {address}");
+        }
+        log::info!("Serving metrics on http://{address}/metrics");
+
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(error) => {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to accept metrics connection")
+                        );
+                        tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                        continue;
+                    }
+                };
+                let metrics = metrics.clone();
+                let relay_selector = relay_selector.clone();
+                let tunnel_stats = tunnel_stats.clone();
+                tokio::spawn(async move {
+                    let result = tokio::time::timeout(
+                        REQUEST_TIMEOUT,
+                        metrics.serve(stream, &relay_selector, &tunnel_stats),
+                    )
+                    .await;
+                    match result {
+                        Ok(Ok(())) => (),
+                        Ok(Err(error)) => log::debug!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to serve metrics request")
+                        ),
+                        Err(_) => log::debug!("Metrics request timed out"),
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    async fn serve(
+        &self,
+        mut stream: TcpStream,
+        relay_selector: &RelaySelector,
+        tunnel_stats: &watch::Receiver<Option<TunnelStats>>,
+    ) -> io::Result<()> {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let n = stream.read(&mut buffer).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            request.extend_from_slice(&buffer[..n]);
+            if request.len() > MAX_REQUEST_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Request is too large",
+                ));
+            }
+        }
+
+        let request_line = String::from_utf8_lossy(&request);
+        let mut parts = request_line.split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => {
+                let relay_list_updated = relay_selector.last_updated();
+                let stats = *tunnel_stats.borrow();
+                let body = self.render(Instant::now(), relay_list_updated, stats);
+                http_response("200 OK", CONTENT_TYPE, &body)
+            }
+            (Some("GET"), _) => http_response("404 Not Found", "text/plain", "Not found\n"),
+            _ => http_response(
+                "405 Method Not Allowed",
+                "text/plain",
+                "Method not allowed\n",
+            ),
+        };
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    fn render(
+        &self,
+        now: Instant,
+        relay_list_updated: SystemTime,
+        tunnel_stats: Option<TunnelStats>,
+    ) -> String {
+        let state = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        family(
+            &mut out,
+            "mullvad_tunnel_state",
+            "gauge",
+            "Whether the tunnel is in the given state",
+        );
+        for name in STATES {
+            let value = u8::from(name == state.state);
+            let _ = writeln!(out, "mullvad_tunnel_state{{state=\"{name}\"}} {value}");
+        }
+
+        family(
+            &mut out,
+            "mullvad_tunnel_state_transitions",
+            "counter",
+            "Number of times the tunnel entered the given state",
+        );
+        for name in STATES {
+            let value = state.transitions.get(name).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "mullvad_tunnel_state_transitions_total{{state=\"{name}\"}} {value}"
+            );
+        }
+
+        family(
+            &mut out,
+            "mullvad_tunnel_state_seconds",
+            "counter",
+            "Time spent in the given tunnel state",
+        );
+        for name in STATES {
+            let mut value = state.time_in_state.get(name).copied().unwrap_or_default();
+            if name == state.state {
+                value += now.saturating_duration_since(state.state_since);
+            }
+            let _ = writeln!(
+                out,
+                "mullvad_tunnel_state_seconds_total{{state=\"{name}\"}} {:.3}",
+                value.as_secs_f64()
+            );
+        }
+
+        family(
+            &mut out,
+            "mullvad_tunnel_reconnects",
+            "counter",
+            "Number of times the daemon started connecting without first being disconnected",
+        );
+        let _ = writeln!(out, "mullvad_tunnel_reconnects_total {}", state.reconnects);
+
+        family(
+            &mut out,
+            "mullvad_tunnel_errors",
+            "counter",
+            "Number of times the tunnel entered the error state, by cause",
+        );
+        for (cause, value) in &state.error_causes {
+            let _ = writeln!(
+                out,
+                "mullvad_tunnel_errors_total{{cause=\"{}\"}} {value}",
+                escape_label(cause)
+            );
+        }
+
+        family(
+            &mut out,
+            "mullvad_relay_list_age_seconds",
+            "gauge",
+            "Time since the relay list was last updated",
+        );
+        let age = SystemTime::now()
+            .duration_since(relay_list_updated)
+            .unwrap_or_default();
+        let _ = writeln!(out, "mullvad_relay_list_age_seconds {}", age.as_secs());
+
+        family(
+            &mut out,
+            "mullvad_api_access_method_changes",
+            "counter",
+            "Number of times the given API access method was selected",
+        );
+        for (name, value) in &state.access_method_changes {
+            let _ = writeln!(
+                out,
+                "mullvad_api_access_method_changes_total{{access_method=\"{}\"}} {value}",
+                escape_label(name)
+            );
+        }
+
+        family(
+            &mut out,
+            "mullvad_wireguard_sent_bytes",
+            "counter",
+            "Bytes sent through the current WireGuard tunnel",
+        );
+        family(
+            &mut out,
+            "mullvad_wireguard_received_bytes",
+            "counter",
+            "Bytes received through the current WireGuard tunnel",
+        );
+        if let Some(stats) = tunnel_stats {
+            let _ = writeln!(out, "mullvad_wireguard_sent_bytes_total {}", stats.tx_bytes);
+            let _ = writeln!(
+                out,
+                "mullvad_wireguard_received_bytes_total {}",
+                stats.rx_bytes
+            );
+        }
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {name} {metric_type}");
+    let _ = writeln!(out, "# HELP {name} {help}");
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Return the name of an error state cause, e.g. `start_tunnel_error`.
+pub(crate) fn error_cause_name(cause: &talpid_types::tunnel::ErrorStateCause) -> String {
+    serde_json::to_value(cause)
+        .ok()
+        .and_then(|value| value.get("reason")?.as_str().map(str::to_owned))
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::tunnel::{ActionAfterDisconnect, ErrorState, ErrorStateCause};
+
+    #[test]
+    fn test_state_accounting() {
+        let start = Instant::now();
+        let metrics = Metrics::with_start(start);
+
+        let transitions = [
+            TunnelStateTransition::Disconnecting(ActionAfterDisconnect::Nothing),
+            TunnelStateTransition::Error(ErrorState::new(ErrorStateCause::IsOffline, None)),
+            TunnelStateTransition::Disconnecting(ActionAfterDisconnect::Reconnect),
+        ];
+        for (i, transition) in transitions.iter().enumerate() {
+            metrics.on_tunnel_state_transition_at(
+                transition,
+                start + Duration::from_secs(i as u64 + 1),
+            );
+        }
+        metrics.on_access_method_change("My \"proxy\"");
+
+        let rendered = metrics.render(
+            start + Duration::from_secs(10),
+            SystemTime::now(),
+            Some(TunnelStats {
+                tx_bytes: 10,
+                rx_bytes: 20,
+                ..Default::default()
+            }),
+        );
+
+        assert!(rendered.contains("mullvad_tunnel_state{state=\"disconnecting\"} 1\n"));
+        assert!(rendered
+            .contains("mullvad_tunnel_state_transitions_total{state=\"disconnecting\"} 2\n"));
+        assert!(
+            rendered.contains("mullvad_tunnel_state_seconds_total{state=\"disconnected\"} 1.000\n")
+        );
+        assert!(rendered
+            .contains("mullvad_tunnel_state_seconds_total{state=\"disconnecting\"} 8.000\n"));
+        assert!(rendered.contains("mullvad_tunnel_errors_total{cause=\"is_offline\"} 1\n"));
+        assert!(rendered.contains(
+            "mullvad_api_access_method_changes_total{access_method=\"My \\\"proxy\\\"\"} 1\n"
+        ));
+        assert!(rendered.contains("mullvad_wireguard_received_bytes_total 20\n"));
+        assert!(rendered.ends_with("# EOF\n"));
+    }
+}
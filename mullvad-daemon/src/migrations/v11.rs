@@ -0,0 +1,147 @@
+use super::{Error, Result};
+use mullvad_types::settings::SettingsVersion;
+
+/// Relay exclusions were added to the relay constraints. They make it possible to never select
+/// certain hostnames, custom lists or providers. This migration adds an empty set of exclusions to
+/// the normal relay settings, which means that no relays are excluded.
+///
+/// LAN access settings were also added. They restrict which LAN networks and inbound ports are
+/// allowed, and which other networks are always allowed. This migration adds empty LAN access
+/// settings, which means that all LAN networks and ports are allowed when `allow_lan` is set, like
+/// before.
+pub fn migrate(settings: &mut serde_json::Value) -> Result<()> {
+    if !version_matches(settings) {
+        return Ok(());
+    }
+
+    log::info!("Migrating settings format to V12");
+
+    if let Some(normal) = relay_settings(settings) {
+        add_relay_exclusions(normal)?;
+    }
+    add_lan_access(settings)?;
+
+    settings["settings_version"] = serde_json::json!(SettingsVersion::V12);
+
+    Ok(())
+}
+
+fn version_matches(settings: &serde_json::Value) -> bool {
+    settings
+        .get("settings_version")
+        .map(|version| version == SettingsVersion::V11 as u64)
+        .unwrap_or(false)
+}
+
+fn relay_settings(settings: &mut serde_json::Value) -> Option<&mut serde_json::Value> {
+    settings.get_mut("relay_settings")?.get_mut("normal")
+}
+
+fn add_relay_exclusions(normal: &mut serde_json::Value) -> Result<()> {
+    let normal = normal
+        .as_object_mut()
+        .ok_or(Error::InvalidSettingsContent)?;
+    normal.entry("exclusions").or_insert(serde_json::json!({
+        "hostnames": [],
+        "custom_lists": [],
+        "providers": [],
+    }));
+    Ok(())
+}
+
+fn add_lan_access(settings: &mut serde_json::Value) -> Result<()> {
+    let settings = settings
+        .as_object_mut()
+        .ok_or(Error::InvalidSettingsContent)?;
+    settings.entry("lan_access").or_insert(serde_json::json!({
+        "networks": [],
+        "inbound_ports": [],
+        "allowed_networks": [],
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{migrate, version_matches};
+    use serde_json::json;
+
+    /// Empty relay exclusions are added to the normal relay settings, and empty LAN access settings
+    /// are added
+    #[test]
+    fn test_v11_to_v12_migration() {
+        let mut old_settings = json!({
+            "relay_settings": {
+                "normal": {
+                    "location": {
+                        "only": {
+                            "location": {
+                                "country": "se"
+                            }
+                        }
+                    },
+                    "tunnel_protocol": "wireguard"
+                }
+            },
+            "settings_version": 11
+        });
+        assert!(version_matches(&old_settings));
+
+        migrate(&mut old_settings).unwrap();
+        let new_settings = json!({
+            "relay_settings": {
+                "normal": {
+                    "location": {
+                        "only": {
+                            "location": {
+                                "country": "se"
+                            }
+                        }
+                    },
+                    "tunnel_protocol": "wireguard",
+                    "exclusions": {
+                        "hostnames": [],
+                        "custom_lists": [],
+                        "providers": []
+                    }
+                }
+            },
+            "lan_access": {
+                "networks": [],
+                "inbound_ports": [],
+                "allowed_networks": []
+            },
+            "settings_version": 12
+        });
+        assert_eq!(&old_settings, &new_settings);
+    }
+
+    /// Custom tunnel endpoints are left untouched
+    #[test]
+    fn test_v11_to_v12_migration_custom_endpoint() {
+        let mut old_settings = json!({
+            "relay_settings": {
+                "custom_tunnel_endpoint": {
+                    "host": "example.com"
+                }
+            },
+            "settings_version": 11
+        });
+
+        migrate(&mut old_settings).unwrap();
+        let new_settings = json!({
+            "relay_settings": {
+                "custom_tunnel_endpoint": {
+                    "host": "example.com"
+                }
+            },
+            "lan_access": {
+                "networks": [],
+                "inbound_ports": [],
+                "allowed_networks": []
+            },
+            "settings_version": 12
+        });
+        assert_eq!(&old_settings, &new_settings);
+    }
+}
@@ -0,0 +1,103 @@
+//! Applying settings profiles, manually or when their triggers match.
+
+use crate::{settings, Daemon, Error};
+use mullvad_types::{
+    profile::{self, Profile, TriggerContext},
+    settings::Settings,
+};
+use talpid_core::tunnel_state_machine::TunnelCommand;
+use talpid_types::ErrorExt;
+use tokio::sync::oneshot;
+
+impl Daemon {
+    /// Add a profile, or replace an existing profile with the same name.
+    pub async fn set_profile(&mut self, profile: Profile) -> Result<(), Error> {
+        // Blocklist files are restricted in the same way as when setting the DNS options
+        #[cfg(not(target_os = "android"))]
+        if let Some(dns_options) = &profile.contents.dns_options {
+            dns_options
+                .blocklists
+                .check_files(&self.dns_blocklist_dir)
+                .map_err(|error| {
+                    Error::SettingsError(settings::Error::UpdateFailed(Box::new(error)))
+                })?;
+        }
+        self.settings
+            .try_update(|settings| settings.profiles.set(profile))
+            .await
+            .map_err(Error::SettingsError)?;
+        Ok(())
+    }
+
+    /// Remove a profile. If it is currently applied by a trigger, the settings it replaced are
+    /// restored.
+    pub async fn delete_profile(&mut self, name: String) -> Result<(), Error> {
+        self.update_profile_settings(|settings| profile::delete_profile(settings, &name))
+            .await
+    }
+
+    /// Apply the settings stored in a profile.
+    pub async fn apply_profile(&mut self, name: String) -> Result<(), Error> {
+        log::info!("Applying profile \"{name}\"");
+        self.update_profile_settings(|settings| profile::apply_profile(settings, &name))
+            .await
+    }
+
+    /// Apply or restore profiles whose triggers changed state.
+    pub(crate) async fn handle_profile_triggers(&mut self, context: TriggerContext) {
+        // Triggers are evaluated periodically, so leave the settings alone unless they will change
+        if !self.settings.profiles.trigger_changed(&context) {
+            return;
+        }
+        let mut activated = None;
+        let result = self
+            .update_profile_settings(|settings| {
+                activated = profile::evaluate_triggers(settings, &context);
+                Ok::<_, profile::Error>(())
+            })
+            .await;
+        match result {
+            Ok(()) => {
+                if let Some(name) = activated {
+                    log::info!("Applied profile \"{name}\" because it was triggered");
+                }
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to apply triggered profile")
+                );
+            }
+        }
+    }
+
+    /// Update the settings, and then apply any changes that a profile may contain.
+    async fn update_profile_settings(
+        &mut self,
+        update_fn: impl FnOnce(&mut Settings) -> Result<(), profile::Error>,
+    ) -> Result<(), Error> {
+        let old_dns_options = self.settings.tunnel_options.dns_options.clone();
+        let old_relay_settings = self.settings.relay_settings.clone();
+        let old_obfuscation_settings = self.settings.obfuscation_settings.clone();
+        let changed = self
+            .settings
+            .try_update(update_fn)
+            .await
+            .map_err(Error::SettingsError)?;
+        if !changed {
+            return Ok(());
+        }
+
+        if old_dns_options != self.settings.tunnel_options.dns_options {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::Dns(self.dns_config(), tx));
+        }
+        if old_relay_settings != self.settings.relay_settings
+            || old_obfuscation_settings != self.settings.obfuscation_settings
+        {
+            log::info!("Initiating tunnel restart because a profile changed the relay settings");
+            self.reconnect_tunnel();
+        }
+        Ok(())
+    }
+}
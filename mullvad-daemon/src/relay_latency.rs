@@ -0,0 +1,171 @@
+//! Measures the round-trip time to relays using ICMP echo requests. The measurements are used by
+//! the relay selector when the fastest relay selection strategy is in use.
+//!
+//! Relays are only probed while the tunnel is disconnected and traffic is not blocked, since the
+//! echo requests would otherwise be sent through the tunnel or dropped. Measurements are
+//! forgotten when the device moves to another network, since they depend on the network path.
+use futures::{stream, StreamExt};
+use mullvad_relay_selector::{latency::LatencyProber, RelaySelector};
+use mullvad_types::relay_list::Relay;
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+use talpid_types::ErrorExt;
+use tokio::{runtime::Handle, sync::mpsc};
+
+/// How often to check whether any latency measurements need to be refreshed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long to wait for a single echo reply.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of echo requests sent to each relay. The lowest round-trip time is used.
+const PINGS_PER_RELAY: u16 = 3;
+/// Maximum number of relays to probe concurrently.
+const MAX_CONCURRENT_PROBES: usize = 32;
+
+enum Command {
+    Update,
+    NetworkChanged,
+}
+
+/// Handle used to request that latencies are measured immediately.
+#[derive(Clone)]
+pub struct LatencyMonitorHandle {
+    tx: mpsc::UnboundedSender<Command>,
+    can_probe: Arc<AtomicBool>,
+}
+
+impl LatencyMonitorHandle {
+    /// Measure the latency to any relays matching the current constraints that have not been
+    /// measured recently.
+    pub fn update(&self) {
+        let _ = self.tx.send(Command::Update);
+    }
+
+    /// Set whether relays can be probed, i.e. whether the tunnel is disconnected without blocking
+    /// traffic. Must be called on every tunnel state transition.
+    pub fn set_can_probe(&self, can_probe: bool) {
+        // This also stops any ongoing measurement
+        self.can_probe.store(can_probe, Ordering::SeqCst);
+        let _ = self.tx.send(Command::Update);
+    }
+
+    /// Forget all measurements, and measure the latencies again once relays can be probed.
+    pub fn network_changed(&self) {
+        let _ = self.tx.send(Command::NetworkChanged);
+    }
+}
+
+/// Spawn a task which periodically measures the latency to relays using `relay_selector`.
+/// `can_probe` is whether relays can initially be probed, see
+/// [`LatencyMonitorHandle::set_can_probe`].
+pub fn spawn(relay_selector: RelaySelector, can_probe: bool) -> LatencyMonitorHandle {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let can_probe = Arc::new(AtomicBool::new(can_probe));
+    tokio::spawn(run_latency_monitor(relay_selector, can_probe.clone(), rx));
+    LatencyMonitorHandle { tx, can_probe }
+}
+
+async fn run_latency_monitor(
+    relay_selector: RelaySelector,
+    can_probe: Arc<AtomicBool>,
+    mut rx: mpsc::UnboundedReceiver<Command>,
+) {
+    loop {
+        if can_probe.load(Ordering::SeqCst) {
+            let relay_selector = relay_selector.clone();
+            let prober = IcmpProber {
+                runtime: Handle::current(),
+                can_probe: can_probe.clone(),
+            };
+            // The relay selector is synchronous, so the measurement must not block the runtime
+            let result =
+                tokio::task::spawn_blocking(move || relay_selector.update_latencies(&prober)).await;
+            if let Err(error) = result {
+                log::error!("Failed to measure relay latencies: {error}");
+            }
+        }
+
+        tokio::select! {
+            _ = talpid_time::sleep(PROBE_INTERVAL) => (),
+            command = rx.recv() => match command {
+                Some(Command::Update) => (),
+                Some(Command::NetworkChanged) => {
+                    log::debug!("Forgetting relay latencies since the network changed");
+                    relay_selector.clear_latencies();
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// A [`LatencyProber`] which pings the IPv4 address of each relay.
+struct IcmpProber {
+    runtime: Handle,
+    can_probe: Arc<AtomicBool>,
+}
+
+impl LatencyProber for IcmpProber {
+    fn probe(&self, relays: &[Relay]) -> Vec<(String, Duration)> {
+        let measurements = self.runtime.block_on(probe_relays(relays, &self.can_probe));
+        // Some echo requests may have been sent through the tunnel
+        if !self.can_probe.load(Ordering::SeqCst) {
+            log::debug!("Discarding relay latencies since the tunnel state changed");
+            return vec![];
+        }
+        measurements
+    }
+}
+
+async fn probe_relays(relays: &[Relay], can_probe: &AtomicBool) -> Vec<(String, Duration)> {
+    let client = match Client::new(&Config::builder().kind(surge_ping::ICMP::V4).build()) {
+        Ok(client) => client,
+        Err(error) => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to create ICMP socket")
+            );
+            return vec![];
+        }
+    };
+
+    stream::iter(relays.iter().enumerate())
+        .map(|(identifier, relay)| {
+            let client = &client;
+            async move {
+                if !can_probe.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let addr = IpAddr::V4(relay.ipv4_addr_in);
+                let latency = ping(client, addr, PingIdentifier(identifier as u16)).await?;
+                Some((relay.hostname.clone(), latency))
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_PROBES)
+        .filter_map(|measurement| async move { measurement })
+        .collect()
+        .await
+}
+
+/// Return the lowest round-trip time to `addr`, or `None` if no echo replies were received.
+async fn ping(client: &Client, addr: IpAddr, identifier: PingIdentifier) -> Option<Duration> {
+    let mut pinger = client.pinger(addr, identifier).await;
+    pinger.timeout(PING_TIMEOUT);
+
+    let mut lowest_latency: Option<Duration> = None;
+    for sequence in 0..PINGS_PER_RELAY {
+        match pinger.ping(PingSequence(sequence), &[0; 16]).await {
+            Ok((_packet, latency)) => {
+                lowest_latency = Some(lowest_latency.map_or(latency, |lowest| lowest.min(latency)));
+            }
+            Err(error) => log::trace!("Failed to ping {addr}: {error}"),
+        }
+    }
+    lowest_latency
+}
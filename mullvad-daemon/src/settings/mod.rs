@@ -57,6 +57,43 @@ impl From<Error> for mullvad_management_interface::Status {
                 let custom_list_err = *err.downcast::<CustomListError>().unwrap();
                 handle_custom_list_error(custom_list_err)
             }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::profile::Error>()
+                    .is_some() =>
+            {
+                let profile_err = *err.downcast::<mullvad_types::profile::Error>().unwrap();
+                match profile_err {
+                    mullvad_types::profile::Error::EmptyName
+                    | mullvad_types::profile::Error::UnsupportedTrigger(_) => {
+                        Status::new(Code::InvalidArgument, profile_err.to_string())
+                    }
+                    mullvad_types::profile::Error::ProfileNotFound => {
+                        Status::new(Code::NotFound, profile_err.to_string())
+                    }
+                }
+            }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::trusted_network::Error>()
+                    .is_some() =>
+            {
+                Status::new(Code::InvalidArgument, err.to_string())
+            }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::relay_constraints::ExclusionsUnsupported>()
+                    .is_some() =>
+            {
+                Status::new(Code::FailedPrecondition, err.to_string())
+            }
+            Error::UpdateFailed(err)
+                if err
+                    .downcast_ref::<mullvad_types::settings::BlocklistFileNotAllowed>()
+                    .is_some() =>
+            {
+                Status::new(Code::InvalidArgument, err.to_string())
+            }
             Error::SerializeError(..) | Error::ParseError(..) | Error::UpdateFailed(..) => {
                 Status::new(Code::Internal, error.to_string())
             }
@@ -196,7 +233,14 @@ impl SettingsPersister {
         let settings_bytes = fs::read(path)
             .await
             .map_err(|error| Error::ReadError(display.as_ref().display().to_string(), error))?;
-        let settings = Self::load_from_bytes(&settings_bytes)?;
+        let mut settings = Self::load_from_bytes(&settings_bytes)?;
+        settings.lan_access.allowed_networks.retain(|network| {
+            let allowed = talpid_types::net::LanAccess::is_allowed_network(network);
+            if !allowed {
+                log::warn!("Removing allowed network {network} since it is too broad");
+            }
+            allowed
+        });
         Ok(settings)
     }
 
@@ -0,0 +1,417 @@
+//! Resolves the destinations that are excluded from the tunnel and passes the resulting networks
+//! on to the tunnel state machine.
+//!
+//! On Linux, domains are resolved using the resolvers of the physical network, which are read
+//! whenever the tunnel is disconnected, since the system DNS is not overridden then. Only queries
+//! for excluded domains, which are marked with the firewall mark, may reach these resolvers
+//! outside the tunnel. Listed domains are resolved again when their records expire.
+//!
+//! The daemon is also asked to have the local DNS forwarder report the addresses that applications
+//! receive for excluded domains. This is how wildcard domains are excluded, and how address changes
+//! are picked up between lookups. On other platforms, this is the only way that addresses of
+//! excluded domains are learned.
+use crate::DaemonEventSender;
+use futures::{
+    channel::{mpsc::UnboundedSender, oneshot},
+    StreamExt,
+};
+use ipnetwork::IpNetwork;
+use mullvad_types::{
+    settings::SplitTunnelSettings, split_tunnel_destination::SplitTunnelDestinations,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::IpAddr,
+    sync::Weak,
+    time::{Duration, Instant},
+};
+#[cfg(target_os = "linux")]
+use talpid_core::dns::forwarder::{create_excluded_resolver, ExcludedResolver};
+use talpid_core::{
+    dns::forwarder::{ExcludedAddressesSender, ExcludedDomains},
+    mpsc::Sender,
+    tunnel_state_machine::TunnelCommand,
+};
+use tokio::sync::mpsc;
+
+/// Minimum time between lookups of excluded domains, regardless of the TTL of their records.
+const MIN_RESOLVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Maximum time between lookups of excluded domains. Also used to retry failed lookups.
+const MAX_RESOLVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Minimum time that addresses reported by the local DNS forwarder remain excluded, so that
+/// connections to them are not moved into the tunnel as soon as their records expire.
+const MIN_OBSERVED_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Maximum number of addresses reported by the local DNS forwarder. The addresses that expire first
+/// are removed beyond this.
+const MAX_OBSERVED_ADDRESSES: usize = 1024;
+
+/// Configuration of the local DNS forwarder for the excluded domains in `domains`.
+pub(crate) struct ExcludedDomainsUpdate {
+    pub domains: Vec<String>,
+    pub excluded_domains: Option<ExcludedDomains>,
+}
+
+enum Command {
+    Update(SplitTunnelDestinations),
+    TunnelDisconnected,
+}
+
+/// Handle used to update the destinations that are excluded from the tunnel.
+#[derive(Clone)]
+pub struct SplitTunnelDestinationsHandle {
+    tx: mpsc::UnboundedSender<Command>,
+}
+
+impl SplitTunnelDestinationsHandle {
+    /// Update the excluded destinations using `settings`.
+    pub fn update(&self, settings: &SplitTunnelSettings) {
+        let _ = self.tx.send(Command::Update(active_destinations(settings)));
+    }
+
+    /// Read the resolvers of the physical network again. Must be called whenever the tunnel has
+    /// been disconnected.
+    pub fn tunnel_disconnected(&self) {
+        let _ = self.tx.send(Command::TunnelDisconnected);
+    }
+}
+
+/// Return the destinations that should currently be excluded. Like excluded apps, these only
+/// apply while split tunneling is enabled.
+pub fn active_destinations(settings: &SplitTunnelSettings) -> SplitTunnelDestinations {
+    if settings.enable_exclusions {
+        settings.destinations.clone()
+    } else {
+        SplitTunnelDestinations::default()
+    }
+}
+
+/// Spawn a task which resolves excluded domains and sends all excluded networks to the tunnel
+/// state machine whenever they change. `initial_networks` are the networks that the tunnel state
+/// machine was initialized with. The tunnel must be disconnected when this is called.
+pub(crate) fn spawn(
+    command_tx: Weak<UnboundedSender<TunnelCommand>>,
+    event_tx: DaemonEventSender<ExcludedDomainsUpdate>,
+    destinations: SplitTunnelDestinations,
+    initial_networks: Vec<IpNetwork>,
+) -> SplitTunnelDestinationsHandle {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (addresses_tx, addresses_rx) = futures::channel::mpsc::unbounded();
+    let resolver = DestinationResolver {
+        command_tx,
+        event_tx,
+        destinations,
+        resolvers: vec![],
+        #[cfg(target_os = "linux")]
+        lookup: None,
+        resolved: HashMap::new(),
+        observed: HashMap::new(),
+        addresses_tx,
+        current_networks: initial_networks,
+    };
+    tokio::spawn(resolver.run(rx, addresses_rx));
+    SplitTunnelDestinationsHandle { tx }
+}
+
+struct DestinationResolver {
+    command_tx: Weak<UnboundedSender<TunnelCommand>>,
+    event_tx: DaemonEventSender<ExcludedDomainsUpdate>,
+    destinations: SplitTunnelDestinations,
+    /// Resolvers of the physical network.
+    resolvers: Vec<IpAddr>,
+    /// Resolver that sends queries to `resolvers` outside the tunnel.
+    #[cfg(target_os = "linux")]
+    lookup: Option<ExcludedResolver>,
+    /// Addresses of each listed domain from the last successful lookup. These are kept if a lookup
+    /// fails, e.g. because the tunnel is blocking traffic.
+    resolved: HashMap<String, Vec<IpAddr>>,
+    /// Addresses of excluded domains reported by the local DNS forwarder, and when they expire.
+    observed: HashMap<IpAddr, Instant>,
+    addresses_tx: ExcludedAddressesSender,
+    current_networks: Vec<IpNetwork>,
+}
+
+impl DestinationResolver {
+    async fn run(
+        mut self,
+        mut rx: mpsc::UnboundedReceiver<Command>,
+        mut addresses_rx: futures::channel::mpsc::UnboundedReceiver<(
+            Vec<IpAddr>,
+            Instant,
+            oneshot::Sender<()>,
+        )>,
+    ) {
+        self.set_resolvers(system_resolvers());
+        if !self.send_excluded_domains() {
+            return;
+        }
+        let mut next_lookup = self.resolve_domains().await;
+
+        loop {
+            let next_expiry = expire_observed(&mut self.observed);
+            if !self.update_networks().await {
+                break;
+            }
+
+            let delay = next_lookup.saturating_duration_since(Instant::now());
+            let expiry_delay = next_expiry
+                .map(|expiry| expiry.saturating_duration_since(Instant::now()))
+                .unwrap_or_default();
+            tokio::select! {
+                _ = talpid_time::sleep(delay), if !self.destinations.domains.is_empty() => {
+                    next_lookup = self.resolve_domains().await;
+                }
+                // Expired addresses are removed at the start of the loop
+                _ = talpid_time::sleep(expiry_delay), if next_expiry.is_some() => (),
+                Some((addresses, valid_until, done_tx)) = addresses_rx.next() => {
+                    observe(&mut self.observed, addresses, valid_until);
+                    if !self.update_networks().await {
+                        break;
+                    }
+                    let _ = done_tx.send(());
+                }
+                command = rx.recv() => match command {
+                    Some(Command::Update(destinations)) => {
+                        // Updates are sent on every settings change
+                        if destinations == self.destinations {
+                            continue;
+                        }
+                        let domains_changed = destinations.domains != self.destinations.domains;
+                        self.destinations = destinations;
+                        if domains_changed {
+                            self.observed.clear();
+                            if !self.send_excluded_domains() {
+                                break;
+                            }
+                            next_lookup = self.resolve_domains().await;
+                        }
+                    }
+                    Some(Command::TunnelDisconnected) => {
+                        if self.set_resolvers(system_resolvers()) {
+                            if !self.send_excluded_domains() {
+                                break;
+                            }
+                            next_lookup = self.resolve_domains().await;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// Use `resolvers` to look up excluded domains. Returns whether they changed.
+    fn set_resolvers(&mut self, resolvers: Vec<IpAddr>) -> bool {
+        if resolvers == self.resolvers {
+            return false;
+        }
+        log::debug!("Resolving excluded domains using {resolvers:?}");
+        #[cfg(target_os = "linux")]
+        {
+            self.lookup = (!resolvers.is_empty())
+                .then(|| create_excluded_resolver(&resolvers, mullvad_types::TUNNEL_FWMARK));
+        }
+        self.resolvers = resolvers;
+        true
+    }
+
+    /// Ask the daemon to configure the local DNS forwarder for the current domains and resolvers.
+    /// Returns `false` if the daemon is gone.
+    fn send_excluded_domains(&self) -> bool {
+        let excluded_domains = (!self.destinations.domains.is_empty()).then(|| ExcludedDomains {
+            domains: self.destinations.domains.clone(),
+            resolvers: self.resolvers.clone(),
+            addresses_tx: self.addresses_tx.clone(),
+        });
+        self.event_tx
+            .send(ExcludedDomainsUpdate {
+                domains: self.destinations.domains.clone(),
+                excluded_domains,
+            })
+            .is_ok()
+    }
+
+    /// Look up all listed domains, except for wildcard domains. Returns when they should be looked
+    /// up again.
+    #[cfg(target_os = "linux")]
+    async fn resolve_domains(&mut self) -> Instant {
+        let domains = &self.destinations.domains;
+        self.resolved.retain(|domain, _| domains.contains(domain));
+
+        let mut next_lookup = Instant::now() + MAX_RESOLVE_INTERVAL;
+        let Some(lookup) = &self.lookup else {
+            if !domains.is_empty() {
+                log::debug!("Not resolving excluded domains since no resolvers are known");
+            }
+            return next_lookup;
+        };
+
+        for domain in domains.iter().filter(|domain| !domain.starts_with("*.")) {
+            match lookup.lookup_ip(domain.as_str()).await {
+                Ok(addresses) => {
+                    next_lookup = next_lookup.min(addresses.valid_until());
+                    self.resolved
+                        .insert(domain.clone(), addresses.iter().collect());
+                }
+                Err(error) => {
+                    log::warn!("Failed to resolve excluded domain {domain}: {error}");
+                }
+            }
+        }
+
+        next_lookup.max(Instant::now() + MIN_RESOLVE_INTERVAL)
+    }
+
+    /// Queries can only be sent outside the tunnel on Linux. Elsewhere, addresses of excluded
+    /// domains are only reported by the local DNS forwarder.
+    #[cfg(not(target_os = "linux"))]
+    async fn resolve_domains(&mut self) -> Instant {
+        let domains = &self.destinations.domains;
+        self.resolved.retain(|domain, _| domains.contains(domain));
+        Instant::now() + MAX_RESOLVE_INTERVAL
+    }
+
+    /// Send all excluded networks to the tunnel state machine if they have changed, and wait for
+    /// them to be applied. Returns `false` if the tunnel state machine is gone.
+    async fn update_networks(&mut self) -> bool {
+        let domain_addresses = self
+            .resolved
+            .values()
+            .flatten()
+            .chain(self.observed.keys())
+            .copied();
+        let networks: Vec<IpNetwork> = self
+            .destinations
+            .networks
+            .iter()
+            .copied()
+            .chain(domain_addresses.map(IpNetwork::from))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        if networks == self.current_networks {
+            return true;
+        }
+        log::debug!("Excluded destinations: {networks:?}");
+        let Some(command_tx) = self.command_tx.upgrade() else {
+            return false;
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        if command_tx
+            .unbounded_send(TunnelCommand::ExcludedDestinations(
+                networks.clone(),
+                done_tx,
+            ))
+            .is_err()
+        {
+            return false;
+        }
+        drop(command_tx);
+        self.current_networks = networks;
+        let _ = done_rx.await;
+        true
+    }
+}
+
+/// Exclude `addresses` reported by the local DNS forwarder until `valid_until`, but for at least
+/// [MIN_OBSERVED_LIFETIME]. At most [MAX_OBSERVED_ADDRESSES] addresses are kept.
+fn observe(observed: &mut HashMap<IpAddr, Instant>, addresses: Vec<IpAddr>, valid_until: Instant) {
+    let expiry = valid_until.max(Instant::now() + MIN_OBSERVED_LIFETIME);
+    for address in addresses {
+        let address_expiry = observed.entry(address).or_insert(expiry);
+        *address_expiry = (*address_expiry).max(expiry);
+    }
+    while observed.len() > MAX_OBSERVED_ADDRESSES {
+        let Some(first_expiring) = observed
+            .iter()
+            .min_by_key(|(_, expiry)| **expiry)
+            .map(|(address, _)| *address)
+        else {
+            break;
+        };
+        observed.remove(&first_expiring);
+    }
+}
+
+/// Remove expired addresses from `observed`. Returns when the next address expires.
+fn expire_observed(observed: &mut HashMap<IpAddr, Instant>) -> Option<Instant> {
+    let now = Instant::now();
+    observed.retain(|_, expiry| *expiry > now);
+    observed.values().min().copied()
+}
+
+/// Return the resolvers of the physical network. Since the system DNS is overridden while the
+/// tunnel is up, this is only accurate while the tunnel is disconnected.
+#[cfg(target_os = "linux")]
+fn system_resolvers() -> Vec<IpAddr> {
+    // /etc/resolv.conf points at the stub resolver if systemd-resolved is used. Its upstream
+    // resolvers are listed here instead.
+    let config = match std::fs::read("/run/systemd/resolve/resolv.conf") {
+        Ok(contents) => hickory_resolver::system_conf::parse_resolv_conf(contents),
+        Err(_) => hickory_resolver::system_conf::read_system_conf(),
+    };
+
+    match config {
+        Ok((config, _options)) => config
+            .name_servers()
+            .iter()
+            .map(|server| server.socket_addr.ip())
+            // Local resolvers would forward queries through the tunnel
+            .filter(|address| !address.is_loopback())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        Err(error) => {
+            log::warn!("Failed to read system DNS resolvers: {error}");
+            vec![]
+        }
+    }
+}
+
+/// Queries for excluded domains are only sent outside the tunnel on Linux, so the resolvers of the
+/// physical network are not used elsewhere.
+#[cfg(not(target_os = "linux"))]
+fn system_resolvers() -> Vec<IpAddr> {
+    vec![]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// Observed addresses are kept for at least [MIN_OBSERVED_LIFETIME], and removed once expired.
+    #[test]
+    fn test_observed_addresses_expire() {
+        let kept = IpAddr::from(Ipv4Addr::new(1, 1, 1, 1));
+        let expired = IpAddr::from(Ipv4Addr::new(2, 2, 2, 2));
+
+        let mut observed = HashMap::new();
+        let before = Instant::now();
+        observe(&mut observed, vec![kept], before);
+        assert!(observed[&kept] >= before + MIN_OBSERVED_LIFETIME);
+
+        observed.insert(expired, Instant::now());
+        let next_expiry = expire_observed(&mut observed);
+        assert_eq!(observed.keys().collect::<Vec<_>>(), vec![&kept]);
+        assert_eq!(next_expiry, Some(observed[&kept]));
+    }
+
+    /// The addresses that expire first are removed once there are too many.
+    #[test]
+    fn test_observed_addresses_are_capped() {
+        let base = Instant::now() + 2 * MIN_OBSERVED_LIFETIME;
+        let mut observed = HashMap::new();
+        for i in 0..=MAX_OBSERVED_ADDRESSES {
+            let address = IpAddr::from(Ipv4Addr::from(i as u32));
+            observe(
+                &mut observed,
+                vec![address],
+                base + Duration::from_secs(i as u64 + 1),
+            );
+        }
+
+        assert_eq!(observed.len(), MAX_OBSERVED_ADDRESSES);
+        assert!(!observed.contains_key(&IpAddr::from(Ipv4Addr::from(0))));
+    }
+}
@@ -0,0 +1,59 @@
+//! Disconnects on trusted networks, and connects on all other networks.
+
+use crate::{settings, Daemon};
+use mullvad_types::{states::TargetState, trusted_network::TrustedNetworkSettings};
+
+impl Daemon {
+    pub async fn set_trusted_network_settings(
+        &mut self,
+        trusted_networks: TrustedNetworkSettings,
+    ) -> Result<(), settings::Error> {
+        let settings_changed = self
+            .settings
+            .try_update(move |settings| {
+                trusted_networks.validate()?;
+                settings.trusted_networks = trusted_networks;
+                Ok::<_, mullvad_types::trusted_network::Error>(())
+            })
+            .await?;
+        if settings_changed {
+            // Re-evaluate the current network with the new settings
+            self.network_trusted = None;
+            self.apply_trusted_networks().await;
+        }
+        Ok(())
+    }
+
+    /// Update the target state if the current network became trusted or untrusted.
+    ///
+    /// The target state is only changed when the network changes, so users can still connect or
+    /// disconnect manually until they join another network. While the network cannot be
+    /// identified, for example briefly while roaming, the last known state is kept.
+    pub(crate) async fn apply_trusted_networks(&mut self) {
+        let settings = &self.settings.trusted_networks;
+        if !settings.enabled {
+            self.network_trusted = None;
+            return;
+        }
+        let Some(trusted) = settings.is_trusted(&self.current_network) else {
+            return;
+        };
+        if Some(trusted) == self.network_trusted {
+            return;
+        }
+        self.network_trusted = Some(trusted);
+
+        if trusted {
+            #[cfg(not(target_os = "android"))]
+            if self.settings.block_when_disconnected {
+                log::info!("Not disconnecting on trusted network since lockdown mode is enabled");
+                return;
+            }
+            log::info!("Disconnecting because the current network is trusted");
+            self.set_target_state(TargetState::Unsecured).await;
+        } else {
+            log::info!("Connecting because the current network is not trusted");
+            self.set_target_state(TargetState::Secured).await;
+        }
+    }
+}
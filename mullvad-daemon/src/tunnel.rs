@@ -8,10 +8,17 @@ use std::{
 
 use tokio::sync::Mutex;
 
-use mullvad_relay_selector::{GetRelay, RelaySelector, RuntimeParameters, WireguardConfig};
+use mullvad_relay_selector::{
+    matcher::RelayRejection, GetRelay, RelaySelector, RuntimeParameters, WireguardConfig,
+};
 use mullvad_types::{
-    endpoint::MullvadWireguardEndpoint, location::GeoIpLocation, relay_list::Relay,
+    endpoint::MullvadWireguardEndpoint,
+    learned_obfuscation::{LearnedObfuscation, ObfuscationMethod},
+    location::GeoIpLocation,
+    relay_list::Relay,
     settings::TunnelOptions,
+    trusted_network::TrustedNetwork,
+    CustomTunnelEndpoint,
 };
 use std::sync::LazyLock;
 use talpid_core::tunnel_state_machine::TunnelParametersGenerator;
@@ -23,9 +30,15 @@ use talpid_types::net::{
 #[cfg(target_os = "android")]
 use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, TunnelParameters};
 
-use talpid_types::{tunnel::ParameterGenerationError, ErrorExt};
+use talpid_types::{
+    tunnel::{NoMatchingRelayReason, ParameterGenerationError},
+    ErrorExt,
+};
 
-use crate::device::{AccountManagerHandle, Error as DeviceError, PrivateAccountAndDevice};
+use crate::{
+    device::{AccountManagerHandle, Error as DeviceError, PrivateAccountAndDevice},
+    learned_obfuscation::{self, LearnedObfuscationStore},
+};
 
 /// The IP-addresses that the client uses when it connects to a server that supports the
 /// "Same IP" functionality. This means all clients have the same in-tunnel IP on these
@@ -47,6 +60,9 @@ pub enum Error {
     #[error("Failed to select a matching relay")]
     SelectRelay(#[from] mullvad_relay_selector::Error),
 
+    #[error("No relay matches the current constraints")]
+    NoMatchingRelay(Option<NoMatchingRelayReason>),
+
     #[error("Failed to resolve hostname for custom relay")]
     ResolveCustomHostname,
 
@@ -63,6 +79,15 @@ struct InnerParametersGenerator {
     account_manager: AccountManagerHandle,
 
     last_generated_relays: Option<LastSelectedRelays>,
+    /// Connection config of the last generated WireGuard tunnel parameters.
+    last_wireguard_config: Option<wireguard::ConnectionConfig>,
+
+    learned_obfuscation: LearnedObfuscationStore,
+    /// The network that the device is currently connected to, if it can be identified.
+    current_network: Option<TrustedNetwork>,
+    /// Obfuscation method of the last generated tunnel parameters. `None` unless the WireGuard
+    /// retry order was used with automatic obfuscation.
+    last_obfuscation_method: Option<ObfuscationMethod>,
 }
 
 impl ParametersGenerator {
@@ -71,6 +96,7 @@ impl ParametersGenerator {
         account_manager: AccountManagerHandle,
         relay_selector: RelaySelector,
         tunnel_options: TunnelOptions,
+        learned_obfuscation: LearnedObfuscationStore,
     ) -> Self {
         Self(Arc::new(Mutex::new(InnerParametersGenerator {
             tunnel_options,
@@ -79,6 +105,11 @@ impl ParametersGenerator {
             account_manager,
 
             last_generated_relays: None,
+            last_wireguard_config: None,
+
+            learned_obfuscation,
+            current_network: None,
+            last_obfuscation_method: None,
         })))
     }
 
@@ -103,6 +134,48 @@ impl ParametersGenerator {
         }
     }
 
+    /// Sets the network that the device is connected to. This determines which learned obfuscation
+    /// method is tried first.
+    pub async fn set_current_network(&self, network: Option<TrustedNetwork>) {
+        self.0.lock().await.current_network = network;
+    }
+
+    /// Registers that a tunnel was established using the last generated tunnel parameters, so that
+    /// the relay selector forgets any previous connection failures of the relays in use, and the
+    /// obfuscation method in use is remembered for the current network.
+    pub async fn report_connected(&self) {
+        let mut inner = self.0.lock().await;
+        if let Some(relays) = inner.last_generated_relays.as_ref() {
+            for hostname in relays.hostnames() {
+                inner.relay_selector.record_connection_success(hostname);
+            }
+        }
+        if let (Some(network), Some(method)) =
+            (inner.current_network.clone(), inner.last_obfuscation_method)
+        {
+            inner
+                .learned_obfuscation
+                .record_success(network, method)
+                .await;
+        }
+    }
+
+    /// Gets the obfuscation methods that have been learned to work, per network.
+    pub async fn learned_obfuscation(&self) -> Vec<LearnedObfuscation> {
+        self.0.lock().await.learned_obfuscation.entries()
+    }
+
+    /// Forgets all learned obfuscation methods.
+    pub async fn clear_learned_obfuscation(&self) -> Result<(), learned_obfuscation::Error> {
+        self.0.lock().await.learned_obfuscation.clear().await
+    }
+
+    /// Gets the connection config of the last generated tunnel parameters, if they were for a
+    /// WireGuard tunnel. This only describes the current tunnel while it is connected.
+    pub async fn last_wireguard_config(&self) -> Option<wireguard::ConnectionConfig> {
+        self.0.lock().await.last_wireguard_config.clone()
+    }
+
     /// Gets the location associated with the last generated tunnel parameters.
     pub async fn get_last_location(&self) -> Option<GeoIpLocation> {
         let inner = self.0.lock().await;
@@ -157,15 +230,39 @@ impl ParametersGenerator {
 }
 
 impl InnerParametersGenerator {
+    /// Avoid the relays used by the last generated tunnel parameters for a while, since a tunnel
+    /// could not be established through them.
+    fn record_connection_failure(&self) {
+        if let Some(relays) = self.last_generated_relays.as_ref() {
+            for hostname in relays.hostnames() {
+                self.relay_selector.record_connection_failure(hostname);
+            }
+        }
+    }
+
     async fn generate(
         &mut self,
         retry_attempt: u32,
         ipv6: bool,
     ) -> Result<TunnelParameters, Error> {
         let data = self.device().await?;
-        let selected_relay = self
-            .relay_selector
-            .get_relay(retry_attempt as usize, RuntimeParameters { ipv6 })?;
+        self.last_obfuscation_method = None;
+        let start = self
+            .current_network
+            .as_ref()
+            .and_then(|network| self.learned_obfuscation.get(network));
+        let (selected_relay, obfuscation_method) = match self.relay_selector.get_relay_starting_at(
+            retry_attempt as usize,
+            start,
+            RuntimeParameters { ipv6 },
+        ) {
+            Err(mullvad_relay_selector::Error::NoRelay) => {
+                let reason = self.no_matching_relay_reason(retry_attempt, ipv6);
+                return Err(Error::NoMatchingRelay(reason));
+            }
+            result => result?,
+        };
+        self.last_obfuscation_method = obfuscation_method;
 
         match selected_relay {
             #[cfg(not(target_os = "android"))]
@@ -219,17 +316,38 @@ impl InnerParametersGenerator {
             }
             GetRelay::Custom(custom_relay) => {
                 self.last_generated_relays = None;
-                custom_relay
-                     // TODO: generate proxy settings for custom tunnels
-                     .to_tunnel_parameters(self.tunnel_options.clone(), None)
-                     .map_err(|e| {
-                         log::error!("Failed to resolve hostname for custom tunnel config: {}", e);
-                         Error::ResolveCustomHostname
-                     })
+                self.create_custom_tunnel_parameters(custom_relay).await
             }
         }
     }
 
+    /// Find the constraint which eliminated the last matching relays, if any.
+    fn no_matching_relay_reason(
+        &self,
+        retry_attempt: u32,
+        ipv6: bool,
+    ) -> Option<NoMatchingRelayReason> {
+        let explanation = self
+            .relay_selector
+            .explain_relay_selection(retry_attempt as usize, RuntimeParameters { ipv6 })
+            .ok()??;
+        let reason = match explanation.eliminating_rejection()? {
+            (RelayRejection::Location, true) => NoMatchingRelayReason::EntryLocation,
+            (RelayRejection::Location, false) => NoMatchingRelayReason::Location,
+            (RelayRejection::TunnelType, _) => NoMatchingRelayReason::TunnelProtocol,
+            (RelayRejection::Inactive, _) => NoMatchingRelayReason::Inactive,
+            (RelayRejection::Excluded, _) => NoMatchingRelayReason::Excluded,
+            (RelayRejection::Ownership, _) => NoMatchingRelayReason::Ownership,
+            (RelayRejection::Providers, _) => NoMatchingRelayReason::Providers,
+            (RelayRejection::Daita, _) => NoMatchingRelayReason::Daita,
+            (RelayRejection::Obfuscation, _) => NoMatchingRelayReason::Obfuscation,
+            // These never eliminate every relay
+            (RelayRejection::NotIncludedInCountry | RelayRejection::Distance, _) => return None,
+        };
+        log::debug!("No relay matches the current constraints: {reason}");
+        Some(reason)
+    }
+
     #[cfg(not(target_os = "android"))]
     fn create_openvpn_tunnel_parameters(
         &self,
@@ -248,6 +366,27 @@ impl InnerParametersGenerator {
         .into()
     }
 
+    /// Create tunnel parameters for a custom relay, which may be a multihop WireGuard tunnel, and
+    /// which only negotiates quantum-resistant keys if its peers run the ephemeral peer service.
+    /// The host of the relay is resolved on a blocking thread, since this may take a while.
+    async fn create_custom_tunnel_parameters(
+        &self,
+        custom_relay: CustomTunnelEndpoint,
+    ) -> Result<TunnelParameters, Error> {
+        log::debug!("Using custom relay: {custom_relay}");
+        let tunnel_options = self.tunnel_options.clone();
+        tokio::task::spawn_blocking(move || {
+            // TODO: generate proxy settings for custom tunnels
+            custom_relay.to_tunnel_parameters(tunnel_options, None)
+        })
+        .await
+        .expect("Custom tunnel parameter generation panicked")
+        .map_err(|e| {
+            log::error!("Failed to resolve hostname for custom tunnel config: {}", e);
+            Error::ResolveCustomHostname
+        })
+    }
+
     fn create_wireguard_tunnel_parameters(
         &self,
         endpoint: MullvadWireguardEndpoint,
@@ -304,9 +443,17 @@ impl TunnelParametersGenerator for ParametersGenerator {
         let generator = self.0.clone();
         Box::pin(async move {
             let mut inner = generator.lock().await;
-            inner
-                .generate(retry_attempt, ipv6)
-                .await
+            let result = inner.generate(retry_attempt, ipv6).await;
+            // Keep the config of the previous tunnel if generation fails, since that is the
+            // tunnel that remains in use, if any
+            match &result {
+                Ok(TunnelParameters::Wireguard(params)) => {
+                    inner.last_wireguard_config = Some(params.connection.clone())
+                }
+                Ok(_) => inner.last_wireguard_config = None,
+                Err(_) => (),
+            }
+            result
                 .inspect_err(|error| {
                     log::error!(
                         "{}",
@@ -316,6 +463,13 @@ impl TunnelParametersGenerator for ParametersGenerator {
                 .map_err(ParameterGenerationError::from)
         })
     }
+
+    fn attempt_timed_out(&mut self) -> Pin<Box<dyn Future<Output = ()>>> {
+        let generator = self.0.clone();
+        Box::pin(async move {
+            generator.lock().await.record_connection_failure();
+        })
+    }
 }
 
 impl From<Error> for ParameterGenerationError {
@@ -327,8 +481,9 @@ impl From<Error> for ParameterGenerationError {
             Error::ResolveCustomHostname => {
                 ParameterGenerationError::CustomTunnelHostResultionError
             }
+            Error::NoMatchingRelay(reason) => ParameterGenerationError::NoMatchingRelay(reason),
             Error::NoAuthDetails | Error::SelectRelay(_) | Error::Device(_) => {
-                ParameterGenerationError::NoMatchingRelay
+                ParameterGenerationError::NoMatchingRelay(None)
             }
         }
     }
@@ -357,3 +512,28 @@ enum LastSelectedRelays {
         server_override: bool,
     },
 }
+
+impl LastSelectedRelays {
+    /// Returns the hostnames of all relays in the path to the internet, without duplicates.
+    /// Bridges are not included, since they are selected by proximity only.
+    fn hostnames(&self) -> Vec<&str> {
+        let relays = match self {
+            LastSelectedRelays::WireGuard {
+                wg_entry,
+                wg_exit,
+                obfuscator,
+                ..
+            } => vec![obfuscator.as_ref(), wg_entry.as_ref(), Some(wg_exit)],
+            #[cfg(not(target_os = "android"))]
+            LastSelectedRelays::OpenVpn { relay, .. } => vec![Some(relay)],
+        };
+        let mut hostnames: Vec<_> = relays
+            .into_iter()
+            .flatten()
+            .map(|relay| relay.hostname.as_str())
+            .collect();
+        hostnames.sort_unstable();
+        hostnames.dedup();
+        hostnames
+    }
+}
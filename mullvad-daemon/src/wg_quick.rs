@@ -0,0 +1,89 @@
+//! Importing and exporting WireGuard tunnels as `wg-quick` configuration files.
+
+use crate::{dns, Daemon, Error};
+use mullvad_types::{
+    relay_constraints::RelaySettings, settings::DnsState, states::TunnelState,
+    wg_quick::WgQuickConfig,
+};
+use std::net::{IpAddr, Ipv4Addr};
+use talpid_core::tunnel_state_machine::TunnelCommand;
+use tokio::sync::oneshot;
+
+impl Daemon {
+    /// Use the peer in a `wg-quick` configuration as a custom tunnel endpoint. If
+    /// `apply_dns_and_mtu` is set, the DNS servers and MTU of the configuration, if any, replace
+    /// the global settings, and remain in effect if another relay is selected later. Otherwise,
+    /// they are ignored.
+    pub async fn import_wireguard_config(
+        &mut self,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        apply_dns_and_mtu: bool,
+    ) -> Result<(), Error> {
+        let config: WgQuickConfig = config.parse().map_err(Error::WgQuickConfig)?;
+        let endpoint = config
+            .to_custom_tunnel_endpoint(ipv4_gateway)
+            .map_err(Error::WgQuickConfig)?;
+        if !apply_dns_and_mtu && (!config.dns.is_empty() || config.mtu.is_some()) {
+            log::info!("Ignoring DNS servers and MTU of imported WireGuard configuration");
+        }
+
+        let old_dns_options = self.settings.tunnel_options.dns_options.clone();
+        let changed = self
+            .settings
+            .update(move |settings| {
+                settings.set_relay_settings(RelaySettings::CustomTunnelEndpoint(endpoint));
+                if apply_dns_and_mtu && !config.dns.is_empty() {
+                    let dns_options = &mut settings.tunnel_options.dns_options;
+                    dns_options.state = DnsState::Custom;
+                    dns_options.custom_options.addresses = config.dns;
+                    dns_options.custom_options.encrypted_resolvers.clear();
+                }
+                if apply_dns_and_mtu && config.mtu.is_some() {
+                    settings.tunnel_options.wireguard.mtu = config.mtu;
+                }
+            })
+            .await
+            .map_err(Error::SettingsError)?;
+        if !changed {
+            return Ok(());
+        }
+
+        let dns_options = &self.settings.tunnel_options.dns_options;
+        if *dns_options != old_dns_options {
+            let (tx, _rx) = oneshot::channel();
+            self.send_tunnel_command(TunnelCommand::Dns(self.dns_config(), tx));
+        }
+        log::info!("Initiating tunnel restart because a WireGuard configuration was imported");
+        self.reconnect_tunnel();
+        Ok(())
+    }
+
+    /// Export the current WireGuard tunnel as a `wg-quick` configuration. The private key is
+    /// replaced by a placeholder, since any local user may call this.
+    pub async fn export_wireguard_config(&self) -> Result<String, Error> {
+        // The last generated parameters only describe the current tunnel while it is up
+        if !matches!(self.tunnel_state, TunnelState::Connected { .. }) {
+            return Err(Error::NoWireguardTunnel);
+        }
+        let connection = self
+            .parameters_generator
+            .last_wireguard_config()
+            .await
+            .ok_or(Error::NoWireguardTunnel)?;
+
+        let gateways: Vec<IpAddr> = std::iter::once(IpAddr::from(connection.ipv4_gateway))
+            .chain(connection.ipv6_gateway.map(IpAddr::from))
+            .collect();
+        let dns = dns::addresses_from_options(&self.settings.tunnel_options.dns_options)
+            .addresses(&gateways);
+
+        let config = WgQuickConfig::from_connection_config(
+            &connection,
+            dns,
+            self.settings.tunnel_options.wireguard.mtu,
+        )
+        .map_err(Error::WgQuickConfig)?;
+        Ok(config.to_string())
+    }
+}
@@ -14,7 +14,7 @@ use std::{
 };
 
 #[cfg(target_os = "linux")]
-use talpid_types::cgroup::{find_net_cls_mount, SPLIT_TUNNEL_CGROUP_NAME};
+use talpid_types::cgroup::find_split_tunnel_cgroup;
 
 #[cfg(target_os = "linux")]
 const PROGRAM_NAME: &str = "mullvad-exclude";
@@ -40,11 +40,11 @@ enum Error {
     #[error("An argument contains interior nul bytes")]
     ArgumentNul(#[source] NulError),
 
-    #[error("Failed to find net_cls controller")]
-    FindNetClsController(#[source] io::Error),
+    #[error("Failed to find split tunneling cgroup")]
+    FindCGroup(#[source] io::Error),
 
-    #[error("No net_cls controller")]
-    NoNetClsController,
+    #[error("No split tunneling cgroup. Is the daemon running?")]
+    NoCGroup,
 }
 
 fn main() {
@@ -85,12 +85,9 @@ fn run() -> Result<Infallible, Error> {
         .collect::<Result<Vec<CString>, NulError>>()
         .map_err(Error::ArgumentNul)?;
 
-    let cgroup_dir = find_net_cls_mount()
-        .map_err(Error::FindNetClsController)?
-        .ok_or(Error::NoNetClsController)?;
-
-    let procs_path = cgroup_dir
-        .join(SPLIT_TUNNEL_CGROUP_NAME)
+    let procs_path = find_split_tunnel_cgroup()
+        .map_err(Error::FindCGroup)?
+        .ok_or(Error::NoCGroup)?
         .join("cgroup.procs");
 
     let file = fs::OpenOptions::new()
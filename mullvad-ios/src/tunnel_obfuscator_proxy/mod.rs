@@ -1,43 +1,38 @@
 use ffi::TunnelObfuscatorProtocol;
-use std::{
-    io,
-    net::{Ipv4Addr, SocketAddr},
-};
+use std::{io, net::SocketAddr};
 use tokio::task::JoinHandle;
-use tunnel_obfuscation::{
-    create_obfuscator, shadowsocks, udp2tcp, Settings as ObfuscationSettings,
-};
+use tunnel_obfuscation::{create_obfuscator, shadowsocks, udp2tcp, Config as ObfuscationConfig};
 
 mod ffi;
 
 use crate::mullvad_ios_runtime;
 
 pub struct TunnelObfuscatorRuntime {
-    settings: ObfuscationSettings,
+    config: ObfuscationConfig,
 }
 
 impl TunnelObfuscatorRuntime {
     pub fn new(peer: SocketAddr, obfuscation_protocol: TunnelObfuscatorProtocol) -> Self {
-        let settings: ObfuscationSettings = match obfuscation_protocol {
-            TunnelObfuscatorProtocol::UdpOverTcp => {
-                ObfuscationSettings::Udp2Tcp(udp2tcp::Settings { peer })
-            }
-            TunnelObfuscatorProtocol::Shadowsocks => {
-                ObfuscationSettings::Shadowsocks(shadowsocks::Settings {
-                    shadowsocks_endpoint: peer,
-                    wireguard_endpoint: SocketAddr::from((Ipv4Addr::LOCALHOST, 51820)),
-                })
-            }
+        let protocol = match obfuscation_protocol {
+            TunnelObfuscatorProtocol::UdpOverTcp => udp2tcp::NAME,
+            TunnelObfuscatorProtocol::Shadowsocks => shadowsocks::NAME,
+        };
+        let config = ObfuscationConfig {
+            protocol: protocol.to_owned(),
+            endpoint: peer,
+            bind: None,
+            parameters: Default::default(),
+            mtu: None,
         };
 
-        Self { settings }
+        Self { config }
     }
 
     pub fn run(self) -> io::Result<(SocketAddr, TunnelObfuscatorHandle)> {
         let runtime = mullvad_ios_runtime().map_err(io::Error::other)?;
 
         let obfuscator = runtime.block_on(async move {
-            create_obfuscator(&self.settings)
+            create_obfuscator(&self.config)
                 .await
                 .map_err(io::Error::other)
         })?;
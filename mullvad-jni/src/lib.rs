@@ -179,6 +179,7 @@ fn spawn_daemon(
         cache_dir,
         android_context,
         endpoint,
+        metrics_address: None,
     };
 
     let running_daemon =
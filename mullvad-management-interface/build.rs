@@ -1,5 +1,9 @@
 fn main() {
-    tonic_build::compile_protos("proto/management_interface.proto").unwrap();
+    tonic_build::configure()
+        // Settings are much larger than any other event
+        .boxed(".mullvad_daemon.management_interface.DaemonEvent.event.settings")
+        .compile(&["proto/management_interface.proto"], &["proto"])
+        .unwrap();
 
     // Enable DAITA by default on desktop and android
     println!("cargo::rustc-check-cfg=cfg(daita)");
@@ -21,14 +21,20 @@ use mullvad_types::{
     custom_list::{CustomList, Id},
     device::{Device, DeviceId, DeviceState},
     features::FeatureIndicators,
+    learned_obfuscation::LearnedObfuscation,
+    profile::Profile,
     relay_constraints::{
-        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, ObfuscationSettings, RelayExclusion, RelayOverride,
+        RelaySelectionStrategy, RelaySettings,
     },
+    relay_health::RelayFailure,
     settings::DnsOptions,
+    trusted_network::{CurrentNetwork, TrustedNetworkSettings},
     wireguard::{PublicKey, QuantumResistantState, RotationInterval},
 };
 #[cfg(not(target_os = "android"))]
-use std::{path::Path, str::FromStr};
+use std::{net::Ipv4Addr, path::Path, str::FromStr};
+use talpid_types::firewall::{FirewallInspection, FirewallInspectionRequest};
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 #[cfg(not(target_os = "android"))]
@@ -45,7 +51,7 @@ pub struct MullvadProxyClient(crate::ManagementServiceClient);
 #[derive(Debug)]
 pub enum DaemonEvent {
     TunnelState(TunnelState),
-    Settings(Settings),
+    Settings(Box<Settings>),
     RelayList(RelayList),
     AppVersionInfo(AppVersionInfo),
     Device(DeviceEvent),
@@ -61,8 +67,8 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
             types::daemon_event::Event::TunnelState(state) => TunnelState::try_from(state)
                 .map(DaemonEvent::TunnelState)
                 .map_err(Error::InvalidResponse),
-            types::daemon_event::Event::Settings(settings) => Settings::try_from(settings)
-                .map(DaemonEvent::Settings)
+            types::daemon_event::Event::Settings(settings) => Settings::try_from(*settings)
+                .map(|settings| DaemonEvent::Settings(Box::new(settings)))
                 .map_err(Error::InvalidResponse),
             types::daemon_event::Event::RelayList(list) => RelayList::try_from(list)
                 .map(DaemonEvent::RelayList)
@@ -152,6 +158,43 @@ impl MullvadProxyClient {
         }))
     }
 
+    /// Listen for traffic statistics of the current tunnel.
+    pub async fn tunnel_stats_listen<'a>(
+        &mut self,
+    ) -> Result<impl Stream<Item = Result<talpid_types::tunnel::TunnelStats>> + 'a> {
+        let listener = self
+            .0
+            .tunnel_stats_listen(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+
+        Ok(listener.map(|item| {
+            talpid_types::tunnel::TunnelStats::try_from(item.map_err(Error::Rpc)?)
+                .map_err(Error::InvalidResponse)
+        }))
+    }
+
+    /// Return recorded tunnel sessions, oldest first. This includes the ongoing session, if any.
+    pub async fn get_connection_history(
+        &mut self,
+    ) -> Result<Vec<mullvad_types::connection_history::ConnectionHistoryEntry>> {
+        let history = self
+            .0
+            .get_connection_history(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        history
+            .entries
+            .into_iter()
+            .map(|entry| {
+                mullvad_types::connection_history::ConnectionHistoryEntry::try_from(entry)
+                    .map_err(Error::InvalidResponse)
+            })
+            .collect()
+    }
+
     /// DEPRECATED: Prefer to use `prepare_restart_v2`.
     pub async fn prepare_restart(&mut self) -> Result<()> {
         self.0.prepare_restart(()).await.map_err(Error::Rpc)?;
@@ -282,6 +325,34 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn import_wireguard_config(
+        &mut self,
+        config: String,
+        ipv4_gateway: Option<Ipv4Addr>,
+        apply_dns_and_mtu: bool,
+    ) -> Result<()> {
+        let request = types::WireguardConfigImport {
+            config,
+            ipv4_gateway: ipv4_gateway.map(|gateway| gateway.to_string()),
+            apply_dns_and_mtu,
+        };
+        self.0
+            .import_wireguard_config(request)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn export_wireguard_config(&mut self) -> Result<String> {
+        let config = self
+            .0
+            .export_wireguard_config(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(config)
+    }
+
     pub async fn set_bridge_settings(&mut self, settings: BridgeSettings) -> Result<()> {
         let settings = types::BridgeSettings::from(settings);
         self.0
@@ -297,6 +368,83 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_relay_selection_strategy(
+        &mut self,
+        strategy: RelaySelectionStrategy,
+    ) -> Result<()> {
+        let strategy = types::RelaySelectionStrategy::from(strategy);
+        self.0
+            .set_relay_selection_strategy(strategy)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn add_relay_exclusion(&mut self, exclusion: RelayExclusion) -> Result<()> {
+        let exclusion = types::RelayExclusion::from(exclusion);
+        self.0
+            .add_relay_exclusion(exclusion)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn remove_relay_exclusion(&mut self, exclusion: RelayExclusion) -> Result<()> {
+        let exclusion = types::RelayExclusion::from(exclusion);
+        self.0
+            .remove_relay_exclusion(exclusion)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn clear_relay_exclusions(&mut self) -> Result<()> {
+        self.0
+            .clear_relay_exclusions(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_relay_failures(&mut self) -> Result<Vec<RelayFailure>> {
+        let list = self
+            .0
+            .get_relay_failures(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        list.failures
+            .into_iter()
+            .map(|failure| RelayFailure::try_from(failure).map_err(Error::InvalidResponse))
+            .collect::<Result<_>>()
+    }
+
+    pub async fn clear_relay_failures(&mut self) -> Result<()> {
+        self.0.clear_relay_failures(()).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn get_learned_obfuscation(&mut self) -> Result<Vec<LearnedObfuscation>> {
+        let list = self
+            .0
+            .get_learned_obfuscation(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        list.entries
+            .into_iter()
+            .map(|learned| LearnedObfuscation::try_from(learned).map_err(Error::InvalidResponse))
+            .collect::<Result<_>>()
+    }
+
+    pub async fn reset_learned_obfuscation(&mut self) -> Result<()> {
+        self.0
+            .reset_learned_obfuscation(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_obfuscation_settings(&mut self, settings: ObfuscationSettings) -> Result<()> {
         let settings = types::ObfuscationSettings::from(&settings);
         self.0
@@ -326,6 +474,17 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_lan_access(
+        &mut self,
+        lan_access: &talpid_types::net::LanAccess,
+    ) -> Result<()> {
+        self.0
+            .set_lan_access(types::LanAccess::from(lan_access))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_show_beta_releases(&mut self, state: bool) -> Result<()> {
         self.0
             .set_show_beta_releases(state)
@@ -347,6 +506,28 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_trusted_network_settings(
+        &mut self,
+        settings: TrustedNetworkSettings,
+    ) -> Result<()> {
+        self.0
+            .set_trusted_network_settings(types::TrustedNetworkSettings::from(&settings))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Return the identifiers of the network that the device is connected to.
+    pub async fn get_current_network(&mut self) -> Result<CurrentNetwork> {
+        let network = self
+            .0
+            .get_current_network(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(CurrentNetwork::from(network))
+    }
+
     pub async fn set_openvpn_mssfix(&mut self, mssfix: Option<u16>) -> Result<()> {
         self.0
             .set_openvpn_mssfix(mssfix.map(u32::from).unwrap_or(0))
@@ -605,6 +786,26 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    /// Add a settings profile, or replace an existing profile with the same name.
+    pub async fn set_profile(&mut self, profile: Profile) -> Result<()> {
+        self.0
+            .set_profile(types::Profile::from(profile))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn delete_profile(&mut self, name: String) -> Result<()> {
+        self.0.delete_profile(name).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    /// Apply the settings stored in a profile.
+    pub async fn apply_profile(&mut self, name: String) -> Result<()> {
+        self.0.apply_profile(name).await.map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn add_access_method(
         &mut self,
         name: String,
@@ -699,6 +900,28 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_split_tunnel_mode(
+        &mut self,
+        mode: mullvad_types::settings::SplitTunnelMode,
+    ) -> Result<()> {
+        self.0
+            .set_split_tunnel_mode(types::SplitTunnelMode::from(mode))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_split_tunnel_destinations(
+        &mut self,
+        destinations: &mullvad_types::split_tunnel_destination::SplitTunnelDestinations,
+    ) -> Result<()> {
+        self.0
+            .set_split_tunnel_destinations(types::SplitTunnelDestinations::from(destinations))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn add_split_tunnel_app<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let path = path.as_ref().to_str().ok_or(Error::PathMustBeUtf8)?;
         self.0
@@ -768,6 +991,17 @@ impl MullvadProxyClient {
             .map(|response| response.into_inner())
             .map(FeatureIndicators::from)
     }
+
+    pub async fn inspect_firewall(
+        &mut self,
+        request: FirewallInspectionRequest,
+    ) -> Result<FirewallInspection> {
+        self.0
+            .inspect_firewall(types::FirewallInspectionRequest::from(request))
+            .await
+            .map_err(Error::Rpc)
+            .map(|response| FirewallInspection::from(response.into_inner()))
+    }
 }
 
 #[cfg(not(target_os = "android"))]
@@ -0,0 +1,100 @@
+use crate::types::{self, proto};
+use mullvad_types::connection_history::{ConnectionHistoryEntry, EndCause};
+use talpid_types::net::{ObfuscationType, TunnelType};
+
+use super::{
+    net::try_tunnel_type_from_i32,
+    relay_health::{to_timestamp, try_from_timestamp},
+    FromProtobufTypeError,
+};
+
+impl From<ConnectionHistoryEntry> for types::ConnectionHistoryEntry {
+    fn from(entry: ConnectionHistoryEntry) -> Self {
+        use proto::{
+            connection_history_entry::end_cause::Kind,
+            obfuscation_endpoint::ObfuscationType as Obfs,
+        };
+
+        types::ConnectionHistoryEntry {
+            start: Some(to_timestamp(entry.start)),
+            end: entry.end.map(to_timestamp),
+            tunnel_type: i32::from(match entry.tunnel_type {
+                TunnelType::Wireguard => proto::TunnelType::Wireguard,
+                TunnelType::OpenVpn => proto::TunnelType::Openvpn,
+            }),
+            relay: entry.relay,
+            entry_relay: entry.entry_relay,
+            obfuscation: entry.obfuscation.map(|obfuscation| {
+                i32::from(match obfuscation {
+                    ObfuscationType::Udp2Tcp => Obfs::Udp2tcp,
+                    ObfuscationType::Shadowsocks => Obfs::Shadowsocks,
+                    ObfuscationType::Quic => Obfs::Quic,
+                    ObfuscationType::Generic => Obfs::Generic,
+                })
+            }),
+            connected: entry.connected,
+            tx_bytes: entry.tx_bytes,
+            rx_bytes: entry.rx_bytes,
+            end_cause: entry.end_cause.map(|cause| {
+                let (kind, error) = match cause {
+                    EndCause::Disconnected => (Kind::Disconnected, String::new()),
+                    EndCause::Reconnected => (Kind::Reconnected, String::new()),
+                    EndCause::Error(error) => (Kind::Error, error),
+                };
+                proto::connection_history_entry::EndCause {
+                    kind: i32::from(kind),
+                    error,
+                }
+            }),
+        }
+    }
+}
+
+impl TryFrom<types::ConnectionHistoryEntry> for ConnectionHistoryEntry {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(entry: types::ConnectionHistoryEntry) -> Result<Self, FromProtobufTypeError> {
+        use proto::{
+            connection_history_entry::end_cause::Kind,
+            obfuscation_endpoint::ObfuscationType as Obfs,
+        };
+
+        let obfuscation = entry
+            .obfuscation
+            .map(|obfuscation| match Obfs::try_from(obfuscation) {
+                Ok(Obfs::Udp2tcp) => Ok(ObfuscationType::Udp2Tcp),
+                Ok(Obfs::Shadowsocks) => Ok(ObfuscationType::Shadowsocks),
+                Ok(Obfs::Quic) => Ok(ObfuscationType::Quic),
+                Ok(Obfs::Generic) => Ok(ObfuscationType::Generic),
+                Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                    "unknown obfuscation type",
+                )),
+            })
+            .transpose()?;
+        let end_cause = entry
+            .end_cause
+            .map(|cause| match Kind::try_from(cause.kind) {
+                Ok(Kind::Disconnected) => Ok(EndCause::Disconnected),
+                Ok(Kind::Reconnected) => Ok(EndCause::Reconnected),
+                Ok(Kind::Error) => Ok(EndCause::Error(cause.error)),
+                Err(_) => Err(FromProtobufTypeError::InvalidArgument("unknown end cause")),
+            })
+            .transpose()?;
+
+        Ok(ConnectionHistoryEntry {
+            start: try_from_timestamp(entry.start)?,
+            end: entry
+                .end
+                .map(|end| try_from_timestamp(Some(end)))
+                .transpose()?,
+            tunnel_type: try_tunnel_type_from_i32(entry.tunnel_type)?,
+            relay: entry.relay,
+            entry_relay: entry.entry_relay,
+            obfuscation,
+            connected: entry.connected,
+            tx_bytes: entry.tx_bytes,
+            rx_bytes: entry.rx_bytes,
+            end_cause,
+        })
+    }
+}
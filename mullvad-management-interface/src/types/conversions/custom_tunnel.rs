@@ -1,8 +1,13 @@
 use crate::types::{
-    conversions::{bytes_to_privkey, bytes_to_pubkey},
+    conversions::{
+        arg_from_str, bytes_to_privkey, bytes_to_pubkey, net::try_transport_protocol_from_i32,
+    },
     proto, FromProtobufTypeError,
 };
-use talpid_types::net::wireguard;
+use talpid_types::net::{
+    obfuscation::{ObfuscatorConfig, SHADOWSOCKS_OBFUSCATION_CIPHERS},
+    wireguard, Endpoint,
+};
 
 impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
     type Error = FromProtobufTypeError;
@@ -45,8 +50,11 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                 let peer = config.peer.ok_or(FromProtobufTypeError::InvalidArgument(
                     "missing peer config",
                 ))?;
-
-                let public_key = bytes_to_pubkey(&peer.public_key)?;
+                let peer = try_peer_config_from_proto(peer)?;
+                let exit_peer = config
+                    .exit_peer
+                    .map(try_peer_config_from_proto)
+                    .transpose()?;
 
                 let ipv4_gateway = config.ipv4_gateway.parse().map_err(|_err| {
                     FromProtobufTypeError::InvalidArgument("invalid IPv4 gateway")
@@ -60,10 +68,6 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                     })
                     .transpose()?;
 
-                let endpoint = peer.endpoint.parse().map_err(|_err| {
-                    FromProtobufTypeError::InvalidArgument("invalid peer address")
-                })?;
-
                 let mut tunnel_addresses = Vec::new();
                 for address in tunnel.addresses {
                     let address = address
@@ -72,29 +76,14 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                     tunnel_addresses.push(address);
                 }
 
-                let mut allowed_ips = Vec::new();
-                for address in peer.allowed_ips {
-                    let address = address
-                        .parse()
-                        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid address"))?;
-                    allowed_ips.push(address);
-                }
-
                 Ok(mullvad_types::ConnectionConfig::Wireguard(
                     wireguard::ConnectionConfig {
                         tunnel: wireguard::TunnelConfig {
                             private_key,
                             addresses: tunnel_addresses,
                         },
-                        peer: wireguard::PeerConfig {
-                            public_key,
-                            allowed_ips,
-                            endpoint,
-                            psk: None,
-                            #[cfg(daita)]
-                            constant_packet_size: false,
-                        },
-                        exit_peer: None,
+                        peer,
+                        exit_peer,
                         ipv4_gateway,
                         ipv6_gateway,
                         #[cfg(target_os = "linux")]
@@ -106,6 +95,34 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
     }
 }
 
+fn try_peer_config_from_proto(
+    peer: proto::connection_config::wireguard_config::PeerConfig,
+) -> Result<wireguard::PeerConfig, FromProtobufTypeError> {
+    let public_key = bytes_to_pubkey(&peer.public_key)?;
+
+    let endpoint = peer
+        .endpoint
+        .parse()
+        .map_err(|_err| FromProtobufTypeError::InvalidArgument("invalid peer address"))?;
+
+    let mut allowed_ips = Vec::new();
+    for address in peer.allowed_ips {
+        let address = address
+            .parse()
+            .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid address"))?;
+        allowed_ips.push(address);
+    }
+
+    Ok(wireguard::PeerConfig {
+        public_key,
+        allowed_ips,
+        endpoint,
+        psk: None,
+        #[cfg(daita)]
+        constant_packet_size: false,
+    })
+}
+
 impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
     fn from(config: mullvad_types::ConnectionConfig) -> Self {
         use proto::connection_config;
@@ -133,24 +150,189 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
                                 .map(|address| address.to_string())
                                 .collect(),
                         }),
-                        peer: Some(connection_config::wireguard_config::PeerConfig {
-                            public_key: config.peer.public_key.as_bytes().to_vec(),
-                            allowed_ips: config
-                                .peer
-                                .allowed_ips
-                                .iter()
-                                .map(|address| address.to_string())
-                                .collect(),
-                            endpoint: config.peer.endpoint.to_string(),
-                        }),
+                        peer: Some(connection_config::wireguard_config::PeerConfig::from(
+                            config.peer,
+                        )),
                         ipv4_gateway: config.ipv4_gateway.to_string(),
                         ipv6_gateway: config
                             .ipv6_gateway
                             .as_ref()
                             .map(|address| address.to_string()),
+                        exit_peer: config
+                            .exit_peer
+                            .map(connection_config::wireguard_config::PeerConfig::from),
                     })
                 }
             }),
         }
     }
 }
+
+impl From<wireguard::PeerConfig> for proto::connection_config::wireguard_config::PeerConfig {
+    fn from(peer: wireguard::PeerConfig) -> Self {
+        Self {
+            public_key: peer.public_key.as_bytes().to_vec(),
+            allowed_ips: peer
+                .allowed_ips
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+            endpoint: peer.endpoint.to_string(),
+        }
+    }
+}
+
+impl TryFrom<proto::ObfuscatorConfig> for ObfuscatorConfig {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(config: proto::ObfuscatorConfig) -> Result<ObfuscatorConfig, Self::Error> {
+        use proto::obfuscator_config::Config;
+
+        let config = config.config.ok_or(FromProtobufTypeError::InvalidArgument(
+            "missing obfuscator config",
+        ))?;
+        Ok(match config {
+            Config::Udp2tcp(config) => ObfuscatorConfig::Udp2Tcp {
+                endpoint: arg_from_str(&config.endpoint, "invalid obfuscator endpoint")?,
+            },
+            Config::Shadowsocks(config) => {
+                if let Some(cipher) = &config.cipher {
+                    if !SHADOWSOCKS_OBFUSCATION_CIPHERS.contains(&cipher.as_str()) {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "unsupported Shadowsocks cipher",
+                        ));
+                    }
+                }
+                ObfuscatorConfig::Shadowsocks {
+                    endpoint: arg_from_str(&config.endpoint, "invalid obfuscator endpoint")?,
+                    cipher: config.cipher,
+                    password: config.password,
+                    // Derived from the WireGuard peer when connecting
+                    target: None,
+                }
+            }
+            Config::Quic(config) => ObfuscatorConfig::Quic {
+                endpoint: arg_from_str(&config.endpoint, "invalid obfuscator endpoint")?,
+                hostname: config.hostname,
+                auth_token: config.auth_token,
+            },
+            Config::Generic(config) => ObfuscatorConfig::Generic {
+                protocol: config.protocol,
+                endpoint: Endpoint::from_socket_address(
+                    arg_from_str(&config.endpoint, "invalid obfuscator endpoint")?,
+                    try_transport_protocol_from_i32(config.transport)?,
+                ),
+                parameters: config.parameters.into_iter().collect(),
+            },
+        })
+    }
+}
+
+impl From<ObfuscatorConfig> for proto::ObfuscatorConfig {
+    fn from(config: ObfuscatorConfig) -> Self {
+        use proto::obfuscator_config::{self, Config};
+
+        let config = match config {
+            ObfuscatorConfig::Udp2Tcp { endpoint } => Config::Udp2tcp(obfuscator_config::Udp2Tcp {
+                endpoint: endpoint.to_string(),
+            }),
+            ObfuscatorConfig::Shadowsocks {
+                endpoint,
+                cipher,
+                password,
+                target: _,
+            } => Config::Shadowsocks(obfuscator_config::Shadowsocks {
+                endpoint: endpoint.to_string(),
+                cipher,
+                password,
+            }),
+            ObfuscatorConfig::Quic {
+                endpoint,
+                hostname,
+                auth_token,
+            } => Config::Quic(obfuscator_config::Quic {
+                endpoint: endpoint.to_string(),
+                hostname,
+                auth_token,
+            }),
+            ObfuscatorConfig::Generic {
+                protocol,
+                endpoint,
+                parameters,
+            } => Config::Generic(obfuscator_config::Generic {
+                protocol,
+                endpoint: endpoint.address.to_string(),
+                transport: i32::from(proto::TransportProtocol::from(endpoint.protocol)),
+                parameters: parameters.into_iter().collect(),
+            }),
+        };
+        Self {
+            config: Some(config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::TransportProtocol;
+
+    #[test]
+    fn test_obfuscator_config_round_trip() {
+        let configs = [
+            ObfuscatorConfig::Udp2Tcp {
+                endpoint: "192.0.2.1:443".parse().unwrap(),
+            },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: "192.0.2.1:443".parse().unwrap(),
+                cipher: None,
+                password: None,
+                target: None,
+            },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: "[2001:db8::1]:443".parse().unwrap(),
+                cipher: Some("chacha20-ietf-poly1305".to_owned()),
+                password: Some("secret".to_owned()),
+                target: None,
+            },
+            ObfuscatorConfig::Quic {
+                endpoint: "192.0.2.1:443".parse().unwrap(),
+                hostname: "example.com".to_owned(),
+                auth_token: "token".to_owned(),
+            },
+            ObfuscatorConfig::Generic {
+                protocol: "example".to_owned(),
+                endpoint: Endpoint::new(
+                    "192.0.2.1".parse::<std::net::IpAddr>().unwrap(),
+                    443,
+                    TransportProtocol::Tcp,
+                ),
+                parameters: [("key".to_owned(), "value".to_owned())].into(),
+            },
+        ];
+        for config in configs {
+            let proto_config = proto::ObfuscatorConfig::from(config.clone());
+            assert_eq!(ObfuscatorConfig::try_from(proto_config).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn test_shadowsocks_cipher() {
+        let shadowsocks = |cipher: &str| proto::ObfuscatorConfig {
+            config: Some(proto::obfuscator_config::Config::Shadowsocks(
+                proto::obfuscator_config::Shadowsocks {
+                    endpoint: "192.0.2.1:443".to_owned(),
+                    cipher: Some(cipher.to_owned()),
+                    password: None,
+                },
+            )),
+        };
+
+        for cipher in SHADOWSOCKS_OBFUSCATION_CIPHERS {
+            assert!(ObfuscatorConfig::try_from(shadowsocks(cipher)).is_ok());
+        }
+        for cipher in ["rc4-md5", "none", ""] {
+            assert!(ObfuscatorConfig::try_from(shadowsocks(cipher)).is_err());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::types::proto;
+use talpid_types::firewall::{FirewallInspection, FirewallInspectionRequest, FirewallPolicyKind};
+
+impl From<FirewallInspectionRequest> for proto::FirewallInspectionRequest {
+    fn from(request: FirewallInspectionRequest) -> Self {
+        use proto::firewall_inspection_request::Policy;
+
+        proto::FirewallInspectionRequest {
+            policy: i32::from(match request.policy {
+                FirewallPolicyKind::Current => Policy::Current,
+                FirewallPolicyKind::Blocked => Policy::Blocked,
+                FirewallPolicyKind::Connecting => Policy::Connecting,
+                FirewallPolicyKind::Connected => Policy::Connected,
+            }),
+            allow_lan: request.allow_lan,
+        }
+    }
+}
+
+impl TryFrom<proto::FirewallInspectionRequest> for FirewallInspectionRequest {
+    type Error = super::FromProtobufTypeError;
+
+    fn try_from(request: proto::FirewallInspectionRequest) -> Result<Self, Self::Error> {
+        use proto::firewall_inspection_request::Policy;
+
+        let policy = match Policy::try_from(request.policy) {
+            Ok(Policy::Current) => FirewallPolicyKind::Current,
+            Ok(Policy::Blocked) => FirewallPolicyKind::Blocked,
+            Ok(Policy::Connecting) => FirewallPolicyKind::Connecting,
+            Ok(Policy::Connected) => FirewallPolicyKind::Connected,
+            Err(_) => {
+                return Err(super::FromProtobufTypeError::InvalidArgument(
+                    "invalid firewall policy",
+                ))
+            }
+        };
+        Ok(FirewallInspectionRequest {
+            policy,
+            allow_lan: request.allow_lan,
+        })
+    }
+}
+
+impl From<FirewallInspection> for proto::FirewallInspection {
+    fn from(inspection: FirewallInspection) -> Self {
+        proto::FirewallInspection {
+            policy: inspection.policy,
+            expected_ruleset: inspection.expected_ruleset,
+            live_ruleset: inspection.live_ruleset,
+        }
+    }
+}
+
+impl From<proto::FirewallInspection> for FirewallInspection {
+    fn from(inspection: proto::FirewallInspection) -> Self {
+        FirewallInspection {
+            policy: inspection.policy,
+            expected_ruleset: inspection.expected_ruleset,
+            live_ruleset: inspection.live_ruleset,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::types::{self, proto};
+use mullvad_types::{
+    learned_obfuscation::{LearnedObfuscation, ObfuscationMethod},
+    trusted_network::TrustedNetwork,
+};
+use talpid_types::net::{IpVersion, ObfuscationType};
+
+use super::{
+    relay_health::{to_timestamp, try_from_timestamp},
+    FromProtobufTypeError,
+};
+
+impl From<ObfuscationMethod> for types::ObfuscationMethod {
+    fn from(method: ObfuscationMethod) -> Self {
+        use proto::obfuscation_endpoint::ObfuscationType as Obfs;
+
+        types::ObfuscationMethod {
+            obfuscation: method.obfuscation.map(|obfuscation| {
+                i32::from(match obfuscation {
+                    ObfuscationType::Udp2Tcp => Obfs::Udp2tcp,
+                    ObfuscationType::Shadowsocks => Obfs::Shadowsocks,
+                    ObfuscationType::Quic => Obfs::Quic,
+                    ObfuscationType::Generic => Obfs::Generic,
+                })
+            }),
+            port: method.port.map(u32::from),
+            ip_version: method
+                .ip_version
+                .map(|ip_version| i32::from(proto::IpVersion::from(ip_version))),
+        }
+    }
+}
+
+impl TryFrom<types::ObfuscationMethod> for ObfuscationMethod {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(method: types::ObfuscationMethod) -> Result<Self, FromProtobufTypeError> {
+        use proto::obfuscation_endpoint::ObfuscationType as Obfs;
+
+        let obfuscation = method
+            .obfuscation
+            .map(|obfuscation| match Obfs::try_from(obfuscation) {
+                Ok(Obfs::Udp2tcp) => Ok(ObfuscationType::Udp2Tcp),
+                Ok(Obfs::Shadowsocks) => Ok(ObfuscationType::Shadowsocks),
+                Ok(Obfs::Quic) => Ok(ObfuscationType::Quic),
+                Ok(Obfs::Generic) => Ok(ObfuscationType::Generic),
+                Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                    "unknown obfuscation type",
+                )),
+            })
+            .transpose()?;
+        let port = method
+            .port
+            .map(|port| {
+                u16::try_from(port)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))
+            })
+            .transpose()?;
+        let ip_version = method
+            .ip_version
+            .map(|ip_version| {
+                proto::IpVersion::try_from(ip_version)
+                    .map(IpVersion::from)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP version"))
+            })
+            .transpose()?;
+
+        Ok(ObfuscationMethod {
+            obfuscation,
+            port,
+            ip_version,
+        })
+    }
+}
+
+impl From<LearnedObfuscation> for types::LearnedObfuscation {
+    fn from(learned: LearnedObfuscation) -> Self {
+        types::LearnedObfuscation {
+            network: Some(types::TrustedNetwork::from(learned.network)),
+            method: Some(types::ObfuscationMethod::from(learned.method)),
+            last_success: Some(to_timestamp(learned.last_success)),
+        }
+    }
+}
+
+impl TryFrom<types::LearnedObfuscation> for LearnedObfuscation {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(learned: types::LearnedObfuscation) -> Result<Self, FromProtobufTypeError> {
+        let network = learned
+            .network
+            .ok_or(FromProtobufTypeError::InvalidArgument("missing network"))?;
+        let method = learned
+            .method
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing obfuscation method",
+            ))?;
+
+        Ok(LearnedObfuscation {
+            network: TrustedNetwork::try_from(network)?,
+            method: ObfuscationMethod::try_from(method)?,
+            last_success: try_from_timestamp(learned.last_success)?,
+        })
+    }
+}
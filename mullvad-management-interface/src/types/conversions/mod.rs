@@ -2,18 +2,23 @@ use std::str::FromStr;
 
 mod access_method;
 mod account;
+mod connection_history;
 mod custom_list;
 mod custom_tunnel;
 mod device;
 mod features;
+mod firewall;
+mod learned_obfuscation;
 mod location;
 mod net;
+mod profile;
 pub mod relay_constraints;
+mod relay_health;
 mod relay_list;
 mod settings;
-#[cfg(target_os = "windows")]
 mod split_tunnel;
 mod states;
+mod trusted_network;
 mod version;
 mod wireguard;
 
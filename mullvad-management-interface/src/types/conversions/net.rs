@@ -39,6 +39,12 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                         net::ObfuscationType::Shadowsocks => {
                             i32::from(proto::obfuscation_endpoint::ObfuscationType::Shadowsocks)
                         }
+                        net::ObfuscationType::Quic => {
+                            i32::from(proto::obfuscation_endpoint::ObfuscationType::Quic)
+                        }
+                        net::ObfuscationType::Generic => {
+                            i32::from(proto::obfuscation_endpoint::ObfuscationType::Generic)
+                        }
                     },
                 }
             }),
@@ -123,6 +129,12 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                                 Ok(proto::obfuscation_endpoint::ObfuscationType::Shadowsocks) => {
                                     talpid_net::ObfuscationType::Shadowsocks
                                 }
+                                Ok(proto::obfuscation_endpoint::ObfuscationType::Quic) => {
+                                    talpid_net::ObfuscationType::Quic
+                                }
+                                Ok(proto::obfuscation_endpoint::ObfuscationType::Generic) => {
+                                    talpid_net::ObfuscationType::Generic
+                                }
                                 Err(_) => {
                                     return Err(FromProtobufTypeError::InvalidArgument(
                                         "unknown obfuscation type",
@@ -206,6 +218,144 @@ pub fn try_transport_protocol_from_i32(
         .into())
 }
 
+impl From<&talpid_types::net::LanAccess> for proto::LanAccess {
+    fn from(lan_access: &talpid_types::net::LanAccess) -> Self {
+        proto::LanAccess {
+            networks: lan_access
+                .networks
+                .iter()
+                .map(|network| network.to_string())
+                .collect(),
+            inbound_ports: lan_access
+                .inbound_ports
+                .iter()
+                .map(|port| u32::from(*port))
+                .collect(),
+            allowed_networks: lan_access
+                .allowed_networks
+                .iter()
+                .map(|network| network.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::LanAccess> for talpid_types::net::LanAccess {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(lan_access: proto::LanAccess) -> Result<Self, Self::Error> {
+        let networks = lan_access
+            .networks
+            .iter()
+            .map(|network| {
+                try_network_from_str(network)
+                    .filter(talpid_types::net::LanAccess::is_lan_network)
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "invalid local network",
+                    ))
+            })
+            .collect::<Result<_, _>>()?;
+        let inbound_ports = lan_access
+            .inbound_ports
+            .into_iter()
+            .map(|port| {
+                u16::try_from(port)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid inbound port"))
+            })
+            .collect::<Result<_, _>>()?;
+        let allowed_networks = lan_access
+            .allowed_networks
+            .iter()
+            .map(|network| {
+                try_network_from_str(network)
+                    .filter(talpid_types::net::LanAccess::is_allowed_network)
+                    .ok_or(FromProtobufTypeError::InvalidArgument(
+                        "invalid allowed network",
+                    ))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            networks,
+            inbound_ports,
+            allowed_networks,
+        })
+    }
+}
+
+impl From<&talpid_types::net::dns::EncryptedDnsResolver> for proto::EncryptedDnsResolver {
+    fn from(resolver: &talpid_types::net::dns::EncryptedDnsResolver) -> Self {
+        use talpid_types::net::dns::EncryptedDnsProtocol;
+
+        let protocol = match resolver.protocol {
+            EncryptedDnsProtocol::Https => proto::encrypted_dns_resolver::Protocol::Https,
+            EncryptedDnsProtocol::Tls => proto::encrypted_dns_resolver::Protocol::Tls,
+        };
+        proto::EncryptedDnsResolver {
+            protocol: i32::from(protocol),
+            hostname: resolver.hostname.clone(),
+            addresses: resolver
+                .addresses
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect(),
+            port: resolver.port.map(u32::from),
+            spki_pins: resolver.spki_pins.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::EncryptedDnsResolver> for talpid_types::net::dns::EncryptedDnsResolver {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(resolver: proto::EncryptedDnsResolver) -> Result<Self, Self::Error> {
+        use talpid_types::net::dns::EncryptedDnsProtocol;
+
+        let protocol = match proto::encrypted_dns_resolver::Protocol::try_from(resolver.protocol) {
+            Ok(proto::encrypted_dns_resolver::Protocol::Https) => EncryptedDnsProtocol::Https,
+            Ok(proto::encrypted_dns_resolver::Protocol::Tls) => EncryptedDnsProtocol::Tls,
+            Err(_) => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid encrypted DNS protocol",
+                ))
+            }
+        };
+        let addresses = resolver
+            .addresses
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid IP address"))
+            })
+            .collect::<Result<_, _>>()?;
+        let port = resolver
+            .port
+            .map(|port| {
+                u16::try_from(port).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid encrypted DNS port")
+                })
+            })
+            .transpose()?;
+
+        let resolver = Self {
+            protocol,
+            hostname: resolver.hostname,
+            addresses,
+            port,
+            spki_pins: resolver.spki_pins,
+        };
+        resolver.validate().map_err(|_| {
+            FromProtobufTypeError::InvalidArgument("invalid encrypted DNS resolver")
+        })?;
+        Ok(resolver)
+    }
+}
+
+/// Parse a network in CIDR notation, clearing any host bits.
+fn try_network_from_str(network: &str) -> Option<ipnetwork::IpNetwork> {
+    let network: ipnetwork::IpNetwork = network.parse().ok()?;
+    ipnetwork::IpNetwork::new(network.network(), network.prefix()).ok()
+}
+
 mod proxy {
     use std::net::Ipv4Addr;
 
@@ -0,0 +1,154 @@
+use crate::types::{proto, FromProtobufTypeError};
+use chrono::{NaiveTime, Timelike, Weekday};
+use mullvad_types::profile::{Profile, ProfileContents, ProfileSettings, ProfileTrigger};
+
+impl From<&ProfileSettings> for proto::ProfileSettings {
+    fn from(settings: &ProfileSettings) -> Self {
+        Self {
+            profiles: settings
+                .profiles()
+                .iter()
+                .cloned()
+                .map(proto::Profile::from)
+                .collect(),
+            active: settings.active().map(str::to_owned),
+        }
+    }
+}
+
+impl TryFrom<proto::ProfileSettings> for ProfileSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::ProfileSettings) -> Result<Self, Self::Error> {
+        Ok(Self::from(
+            settings
+                .profiles
+                .into_iter()
+                .map(Profile::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        ))
+    }
+}
+
+impl From<Profile> for proto::Profile {
+    fn from(profile: Profile) -> Self {
+        Self {
+            name: profile.name,
+            contents: Some(proto::ProfileContents::from(profile.contents)),
+            triggers: profile
+                .triggers
+                .into_iter()
+                .map(proto::ProfileTrigger::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::Profile> for Profile {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(profile: proto::Profile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: profile.name,
+            contents: profile
+                .contents
+                .map(ProfileContents::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            triggers: profile
+                .triggers
+                .into_iter()
+                .map(ProfileTrigger::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl From<ProfileContents> for proto::ProfileContents {
+    fn from(contents: ProfileContents) -> Self {
+        Self {
+            relay_settings: contents.relay_settings.map(proto::RelaySettings::from),
+            dns_options: contents.dns_options.as_ref().map(proto::DnsOptions::from),
+            obfuscation_settings: contents
+                .obfuscation_settings
+                .map(proto::ObfuscationSettings::from),
+        }
+    }
+}
+
+impl TryFrom<proto::ProfileContents> for ProfileContents {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(contents: proto::ProfileContents) -> Result<Self, Self::Error> {
+        Ok(Self {
+            relay_settings: contents.relay_settings.map(TryFrom::try_from).transpose()?,
+            dns_options: contents.dns_options.map(TryFrom::try_from).transpose()?,
+            obfuscation_settings: contents
+                .obfuscation_settings
+                .map(TryFrom::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+impl From<ProfileTrigger> for proto::ProfileTrigger {
+    fn from(trigger: ProfileTrigger) -> Self {
+        use proto::profile_trigger;
+
+        let trigger = match trigger {
+            ProfileTrigger::Schedule { days, start, end } => {
+                profile_trigger::Trigger::Schedule(profile_trigger::Schedule {
+                    days: days.iter().map(Weekday::num_days_from_monday).collect(),
+                    start: start.num_seconds_from_midnight() / 60,
+                    end: end.num_seconds_from_midnight() / 60,
+                })
+            }
+            ProfileTrigger::Network { gateway_mac } => {
+                profile_trigger::Trigger::Network(profile_trigger::Network { gateway_mac })
+            }
+        };
+        Self {
+            trigger: Some(trigger),
+        }
+    }
+}
+
+impl TryFrom<proto::ProfileTrigger> for ProfileTrigger {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(trigger: proto::ProfileTrigger) -> Result<Self, Self::Error> {
+        use proto::profile_trigger;
+
+        match trigger.trigger {
+            Some(profile_trigger::Trigger::Schedule(schedule)) => Ok(ProfileTrigger::Schedule {
+                days: schedule
+                    .days
+                    .into_iter()
+                    .map(|day| {
+                        u8::try_from(day)
+                            .ok()
+                            .and_then(|day| Weekday::try_from(day).ok())
+                            .ok_or(FromProtobufTypeError::InvalidArgument("invalid weekday"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                start: minutes_to_time(schedule.start)?,
+                end: minutes_to_time(schedule.end)?,
+            }),
+            Some(profile_trigger::Trigger::Network(network)) => Ok(ProfileTrigger::Network {
+                gateway_mac: network.gateway_mac,
+            }),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing profile trigger",
+            )),
+        }
+    }
+}
+
+fn minutes_to_time(minutes: u32) -> Result<NaiveTime, FromProtobufTypeError> {
+    minutes
+        .checked_mul(60)
+        .and_then(|secs| NaiveTime::from_num_seconds_from_midnight_opt(secs, 0))
+        .ok_or(FromProtobufTypeError::InvalidArgument(
+            "invalid time of day",
+        ))
+}
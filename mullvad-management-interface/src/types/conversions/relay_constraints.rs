@@ -1,9 +1,19 @@
-use crate::types::{conversions::net::try_tunnel_type_from_i32, proto, FromProtobufTypeError};
+use crate::types::{
+    conversions::net::{try_transport_protocol_from_i32, try_tunnel_type_from_i32},
+    proto, FromProtobufTypeError,
+};
 use mullvad_types::{
-    constraints::Constraint, custom_list::Id, relay_constraints::GeographicLocationConstraint,
+    constraints::Constraint,
+    custom_list::Id,
+    location::Coordinates,
+    relay_constraints::{
+        GeographicLocationConstraint, NearestLocationConstraint, RelayExclusion, RelayExclusions,
+    },
 };
 use std::str::FromStr;
-use talpid_types::net::proxy::CustomProxy;
+use talpid_types::net::{
+    obfuscation::ObfuscatorConfig, proxy::CustomProxy, wireguard::PresharedKey,
+};
 
 impl TryFrom<&proto::WireguardConstraints>
     for mullvad_types::relay_constraints::WireguardConstraints
@@ -82,10 +92,20 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         "missing relay connection config",
                     ))?;
                 let config = mullvad_types::ConnectionConfig::try_from(config)?;
+                let psk = settings.psk.map(try_psk_from_proto).transpose()?;
+                let exit_psk = settings.exit_psk.map(try_psk_from_proto).transpose()?;
+                let obfuscation = settings
+                    .obfuscation
+                    .map(ObfuscatorConfig::try_from)
+                    .transpose()?;
                 Ok(mullvad_constraints::RelaySettings::CustomTunnelEndpoint(
                     CustomTunnelEndpoint {
                         host: settings.host,
                         config,
+                        psk,
+                        exit_psk,
+                        quantum_resistant: settings.quantum_resistant,
+                        obfuscation,
                     },
                 ))
             }
@@ -115,6 +135,11 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         FromProtobufTypeError::InvalidArgument("missing wireguard constraints"),
                     )?,
                 )?;
+                let exclusions = settings
+                    .exclusions
+                    .map(RelayExclusions::try_from)
+                    .transpose()?
+                    .unwrap_or_default();
 
                 Ok(mullvad_constraints::RelaySettings::Normal(
                     mullvad_constraints::RelayConstraints {
@@ -124,6 +149,7 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         tunnel_protocol,
                         wireguard_constraints,
                         openvpn_constraints,
+                        exclusions,
                     },
                 ))
             }
@@ -144,6 +170,22 @@ impl From<mullvad_types::relay_constraints::BridgeState> for proto::BridgeState
     }
 }
 
+impl From<mullvad_types::relay_constraints::RelaySelectionStrategy>
+    for proto::RelaySelectionStrategy
+{
+    fn from(strategy: mullvad_types::relay_constraints::RelaySelectionStrategy) -> Self {
+        use mullvad_types::relay_constraints::RelaySelectionStrategy;
+        Self {
+            strategy: i32::from(match strategy {
+                RelaySelectionStrategy::Random => proto::relay_selection_strategy::Strategy::Random,
+                RelaySelectionStrategy::Fastest => {
+                    proto::relay_selection_strategy::Strategy::Fastest
+                }
+            }),
+        }
+    }
+}
+
 impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::ObfuscationSettings {
     fn from(settings: &mullvad_types::relay_constraints::ObfuscationSettings) -> Self {
         use mullvad_types::relay_constraints::SelectedObfuscation;
@@ -156,11 +198,16 @@ impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::Obf
             SelectedObfuscation::Shadowsocks => {
                 proto::obfuscation_settings::SelectedObfuscation::Shadowsocks
             }
+            SelectedObfuscation::Quic => proto::obfuscation_settings::SelectedObfuscation::Quic,
+            SelectedObfuscation::Generic => {
+                proto::obfuscation_settings::SelectedObfuscation::Generic
+            }
         });
         Self {
             selected_obfuscation,
             udp2tcp: Some(proto::Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
             shadowsocks: Some(proto::ShadowsocksSettings::from(&settings.shadowsocks)),
+            generic: Some(proto::GenericObfuscationSettings::from(&settings.generic)),
         }
     }
 }
@@ -189,6 +236,19 @@ impl From<&mullvad_types::relay_constraints::ShadowsocksSettings> for proto::Sha
     }
 }
 
+impl From<&mullvad_types::relay_constraints::GenericObfuscationSettings>
+    for proto::GenericObfuscationSettings
+{
+    fn from(settings: &mullvad_types::relay_constraints::GenericObfuscationSettings) -> Self {
+        Self {
+            protocol: settings.protocol.clone(),
+            port: u32::from(settings.port),
+            transport: i32::from(proto::TransportProtocol::from(settings.transport)),
+            parameters: settings.parameters.clone().into_iter().collect(),
+        }
+    }
+}
+
 impl From<mullvad_types::relay_constraints::BridgeSettings> for proto::BridgeSettings {
     fn from(settings: mullvad_types::relay_constraints::BridgeSettings) -> Self {
         use proto::bridge_settings;
@@ -234,6 +294,10 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                 relay_settings::Endpoint::Custom(proto::CustomRelaySettings {
                     host: endpoint.host,
                     config: Some(proto::ConnectionConfig::from(endpoint.config)),
+                    psk: endpoint.psk.map(|psk| psk.as_bytes().to_vec()),
+                    exit_psk: endpoint.exit_psk.map(|psk| psk.as_bytes().to_vec()),
+                    quantum_resistant: endpoint.quantum_resistant,
+                    obfuscation: endpoint.obfuscation.map(proto::ObfuscatorConfig::from),
                 })
             }
             MullvadRelaySettings::Normal(constraints) => {
@@ -276,6 +340,8 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                             .option()
                             .map(proto::TransportPort::from),
                     }),
+
+                    exclusions: Some(proto::RelayExclusions::from(constraints.exclusions)),
                 })
             }
         };
@@ -309,6 +375,11 @@ impl From<mullvad_types::relay_constraints::LocationConstraint> for proto::Locat
                     list_id.to_string(),
                 )),
             },
+            LocationConstraint::Nearest(nearest) => Self {
+                r#type: Some(proto::location_constraint::Type::Nearest(
+                    proto::NearestLocationConstraint::from(nearest),
+                )),
+            },
         }
     }
 }
@@ -332,11 +403,121 @@ impl TryFrom<proto::LocationConstraint>
                 };
                 Ok(Constraint::Only(location))
             }
+            Some(proto::location_constraint::Type::Nearest(nearest)) => Ok(Constraint::Only(
+                LocationConstraint::Nearest(NearestLocationConstraint::try_from(nearest)?),
+            )),
             None => Ok(Constraint::Any),
         }
     }
 }
 
+impl From<NearestLocationConstraint> for proto::NearestLocationConstraint {
+    fn from(nearest: NearestLocationConstraint) -> Self {
+        Self {
+            origin: nearest
+                .origin
+                .map(|origin| proto::nearest_location_constraint::Coordinates {
+                    latitude: origin.latitude,
+                    longitude: origin.longitude,
+                }),
+            radius: nearest.radius,
+        }
+    }
+}
+
+impl TryFrom<proto::NearestLocationConstraint> for NearestLocationConstraint {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(nearest: proto::NearestLocationConstraint) -> Result<Self, Self::Error> {
+        let origin = nearest.origin.map(|origin| Coordinates {
+            latitude: origin.latitude,
+            longitude: origin.longitude,
+        });
+        if origin.is_some_and(|origin| !origin.is_valid()) {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "Invalid origin of nearest location constraint",
+            ));
+        }
+        Ok(Self {
+            origin,
+            radius: nearest.radius,
+        })
+    }
+}
+
+impl From<RelayExclusions> for proto::RelayExclusions {
+    fn from(exclusions: RelayExclusions) -> Self {
+        Self {
+            hostnames: exclusions.hostnames.into_iter().collect(),
+            custom_lists: exclusions
+                .custom_lists
+                .into_iter()
+                .map(|list_id| list_id.to_string())
+                .collect(),
+            providers: exclusions.providers.into_iter().collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::RelayExclusions> for RelayExclusions {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(exclusions: proto::RelayExclusions) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hostnames: exclusions.hostnames.into_iter().collect(),
+            custom_lists: exclusions
+                .custom_lists
+                .iter()
+                .map(|list_id| try_custom_list_id_from_proto(list_id))
+                .collect::<Result<_, _>>()?,
+            providers: exclusions.providers.into_iter().collect(),
+        })
+    }
+}
+
+impl From<RelayExclusion> for proto::RelayExclusion {
+    fn from(exclusion: RelayExclusion) -> Self {
+        use proto::relay_exclusion::Exclusion;
+        let exclusion = match exclusion {
+            RelayExclusion::Hostname(hostname) => Exclusion::Hostname(hostname),
+            RelayExclusion::CustomList(list_id) => Exclusion::CustomList(list_id.to_string()),
+            RelayExclusion::Provider(provider) => Exclusion::Provider(provider),
+        };
+        Self {
+            exclusion: Some(exclusion),
+        }
+    }
+}
+
+impl TryFrom<proto::RelayExclusion> for RelayExclusion {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(exclusion: proto::RelayExclusion) -> Result<Self, Self::Error> {
+        use proto::relay_exclusion::Exclusion;
+        match exclusion.exclusion {
+            Some(Exclusion::Hostname(hostname)) => Ok(RelayExclusion::Hostname(hostname)),
+            Some(Exclusion::CustomList(list_id)) => Ok(RelayExclusion::CustomList(
+                try_custom_list_id_from_proto(&list_id)?,
+            )),
+            Some(Exclusion::Provider(provider)) => Ok(RelayExclusion::Provider(provider)),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing relay exclusion",
+            )),
+        }
+    }
+}
+
+fn try_psk_from_proto(psk: Vec<u8>) -> Result<PresharedKey, FromProtobufTypeError> {
+    <[u8; 32]>::try_from(psk.as_slice())
+        .map(PresharedKey::from)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid preshared key"))
+}
+
+fn try_custom_list_id_from_proto(list_id: &str) -> Result<Id, FromProtobufTypeError> {
+    Id::from_str(list_id)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("Id could not be parsed to a uuid"))
+}
+
 impl From<GeographicLocationConstraint> for proto::GeographicLocationConstraint {
     fn from(location: mullvad_types::relay_constraints::GeographicLocationConstraint) -> Self {
         match location {
@@ -447,6 +628,8 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
                 Ok(IpcSelectedObfuscation::Off) => SelectedObfuscation::Off,
                 Ok(IpcSelectedObfuscation::Udp2tcp) => SelectedObfuscation::Udp2Tcp,
                 Ok(IpcSelectedObfuscation::Shadowsocks) => SelectedObfuscation::Shadowsocks,
+                Ok(IpcSelectedObfuscation::Quic) => SelectedObfuscation::Quic,
+                Ok(IpcSelectedObfuscation::Generic) => SelectedObfuscation::Generic,
                 Err(_) => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "invalid obfuscation settings",
@@ -475,10 +658,20 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
             }
         };
 
+        // Older clients do not know about generic obfuscation
+        let generic = settings
+            .generic
+            .map(|settings| {
+                mullvad_types::relay_constraints::GenericObfuscationSettings::try_from(&settings)
+            })
+            .transpose()?
+            .unwrap_or_default();
+
         Ok(Self {
             selected_obfuscation,
             udp2tcp,
             shadowsocks,
+            generic,
         })
     }
 }
@@ -507,6 +700,43 @@ impl TryFrom<&proto::ShadowsocksSettings>
     }
 }
 
+impl TryFrom<&proto::GenericObfuscationSettings>
+    for mullvad_types::relay_constraints::GenericObfuscationSettings
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: &proto::GenericObfuscationSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            protocol: settings.protocol.clone(),
+            port: u16::try_from(settings.port)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid port"))?,
+            transport: try_transport_protocol_from_i32(settings.transport)?,
+            parameters: settings.parameters.clone().into_iter().collect(),
+        })
+    }
+}
+
+impl TryFrom<proto::RelaySelectionStrategy>
+    for mullvad_types::relay_constraints::RelaySelectionStrategy
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(strategy: proto::RelaySelectionStrategy) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_constraints::RelaySelectionStrategy;
+        match proto::relay_selection_strategy::Strategy::try_from(strategy.strategy) {
+            Ok(proto::relay_selection_strategy::Strategy::Random) => {
+                Ok(RelaySelectionStrategy::Random)
+            }
+            Ok(proto::relay_selection_strategy::Strategy::Fastest) => {
+                Ok(RelaySelectionStrategy::Fastest)
+            }
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid relay selection strategy",
+            )),
+        }
+    }
+}
+
 impl TryFrom<proto::BridgeState> for mullvad_types::relay_constraints::BridgeState {
     type Error = FromProtobufTypeError;
 
@@ -631,3 +861,94 @@ fn convert_ownership_constraint(
         },
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nearest(latitude: f64, longitude: f64) -> proto::NearestLocationConstraint {
+        proto::NearestLocationConstraint {
+            origin: Some(proto::nearest_location_constraint::Coordinates {
+                latitude,
+                longitude,
+            }),
+            radius: NearestLocationConstraint::DEFAULT_RADIUS,
+        }
+    }
+
+    #[test]
+    fn test_nearest_location_constraint() {
+        let constraint = NearestLocationConstraint::try_from(nearest(59.33, -18.06)).unwrap();
+        assert_eq!(
+            proto::NearestLocationConstraint::from(constraint),
+            nearest(59.33, -18.06)
+        );
+        let device_location = proto::NearestLocationConstraint {
+            origin: None,
+            radius: 10,
+        };
+        assert_eq!(
+            NearestLocationConstraint::try_from(device_location).unwrap(),
+            NearestLocationConstraint::device_location(10)
+        );
+
+        for (latitude, longitude) in [
+            (f64::NAN, 0.0),
+            (0.0, f64::NAN),
+            (90.5, 0.0),
+            (0.0, -180.5),
+            (f64::INFINITY, 0.0),
+        ] {
+            assert!(
+                NearestLocationConstraint::try_from(nearest(latitude, longitude)).is_err(),
+                "{latitude}, {longitude} should be rejected"
+            );
+        }
+    }
+
+    /// Multihop peers, preshared keys, and quantum resistance of custom tunnels must survive a
+    /// round trip through the management interface.
+    #[test]
+    fn test_custom_tunnel_endpoint_round_trip() {
+        use mullvad_types::{relay_constraints::RelaySettings, CustomTunnelEndpoint};
+        use talpid_types::net::wireguard;
+
+        let peer = |endpoint: &str, allowed_ip: &str| wireguard::PeerConfig {
+            public_key: wireguard::PrivateKey::new_from_random().public_key(),
+            allowed_ips: vec![allowed_ip.parse().unwrap()],
+            endpoint: endpoint.parse().unwrap(),
+            psk: None,
+            #[cfg(daita)]
+            constant_packet_size: false,
+        };
+        let settings = RelaySettings::CustomTunnelEndpoint(CustomTunnelEndpoint {
+            host: "vpn.example.com".to_owned(),
+            config: mullvad_types::ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+                tunnel: wireguard::TunnelConfig {
+                    private_key: wireguard::PrivateKey::new_from_random(),
+                    addresses: vec!["10.64.0.2".parse().unwrap()],
+                },
+                peer: peer("192.0.2.1:51820", "10.0.0.2/32"),
+                exit_peer: Some(peer("10.0.0.2:51820", "0.0.0.0/0")),
+                ipv4_gateway: "10.64.0.1".parse().unwrap(),
+                ipv6_gateway: None,
+                #[cfg(target_os = "linux")]
+                fwmark: Some(mullvad_types::TUNNEL_FWMARK),
+            }),
+            psk: Some(PresharedKey::from([1; 32])),
+            exit_psk: Some(PresharedKey::from([2; 32])),
+            quantum_resistant: true,
+            obfuscation: None,
+        });
+
+        let proto_settings = proto::RelaySettings::from(settings.clone());
+        assert_eq!(RelaySettings::try_from(proto_settings).unwrap(), settings);
+
+        let mut proto_settings = proto::RelaySettings::from(settings);
+        if let Some(proto::relay_settings::Endpoint::Custom(custom)) = &mut proto_settings.endpoint
+        {
+            custom.exit_psk = Some(vec![0; 16]);
+        }
+        assert!(RelaySettings::try_from(proto_settings).is_err());
+    }
+}
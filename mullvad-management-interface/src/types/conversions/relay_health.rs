@@ -0,0 +1,44 @@
+use crate::types;
+use chrono::{DateTime, Utc};
+use mullvad_types::relay_health::RelayFailure;
+
+use super::FromProtobufTypeError;
+
+impl From<RelayFailure> for types::RelayFailure {
+    fn from(failure: RelayFailure) -> Self {
+        types::RelayFailure {
+            hostname: failure.hostname,
+            failures: failure.failures,
+            last_failure: Some(to_timestamp(failure.last_failure)),
+            penalized_until: Some(to_timestamp(failure.penalized_until)),
+        }
+    }
+}
+
+impl TryFrom<types::RelayFailure> for RelayFailure {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(failure: types::RelayFailure) -> Result<Self, FromProtobufTypeError> {
+        Ok(RelayFailure {
+            hostname: failure.hostname,
+            failures: failure.failures,
+            last_failure: try_from_timestamp(failure.last_failure)?,
+            penalized_until: try_from_timestamp(failure.penalized_until)?,
+        })
+    }
+}
+
+pub(super) fn to_timestamp(time: DateTime<Utc>) -> types::Timestamp {
+    types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: 0,
+    }
+}
+
+pub(super) fn try_from_timestamp(
+    timestamp: Option<types::Timestamp>,
+) -> Result<DateTime<Utc>, FromProtobufTypeError> {
+    let timestamp = timestamp.ok_or(FromProtobufTypeError::InvalidArgument("missing timestamp"))?;
+    DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32)
+        .ok_or(FromProtobufTypeError::InvalidArgument("invalid timestamp"))
+}
@@ -80,6 +80,16 @@ impl From<mullvad_types::relay_list::WireguardEndpointData> for proto::Wireguard
     }
 }
 
+impl From<mullvad_types::relay_list::QuicEndpointData> for proto::QuicEndpointData {
+    fn from(quic: mullvad_types::relay_list::QuicEndpointData) -> Self {
+        Self {
+            addr_in: quic.addr_in.iter().map(|addr| addr.to_string()).collect(),
+            domain: quic.domain,
+            token: quic.token,
+        }
+    }
+}
+
 impl From<RangeInclusive<u16>> for proto::PortRange {
     fn from(range: RangeInclusive<u16>) -> Self {
         proto::PortRange {
@@ -140,6 +150,7 @@ impl From<mullvad_types::relay_list::Relay> for proto::Relay {
                             .iter()
                             .map(|addr| addr.to_string())
                             .collect(),
+                        quic: data.quic.map(proto::QuicEndpointData::from),
                     },
                 )),
                 _ => None,
@@ -266,6 +277,10 @@ impl TryFrom<proto::Relay> for mullvad_types::relay_list::Relay {
                                 })
                             })
                             .collect::<Result<_, FromProtobufTypeError>>()?,
+                        quic: data
+                            .quic
+                            .map(mullvad_types::relay_list::QuicEndpointData::try_from)
+                            .transpose()?,
                     },
                 )
             }
@@ -373,6 +388,27 @@ impl TryFrom<proto::ShadowsocksEndpointData>
     }
 }
 
+impl TryFrom<proto::QuicEndpointData> for mullvad_types::relay_list::QuicEndpointData {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(quic: proto::QuicEndpointData) -> Result<Self, FromProtobufTypeError> {
+        let addr_in = quic
+            .addr_in
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid QUIC address"))
+            })
+            .collect::<Result<_, FromProtobufTypeError>>()?;
+
+        Ok(mullvad_types::relay_list::QuicEndpointData {
+            addr_in,
+            domain: quic.domain,
+            token: quic.token,
+        })
+    }
+}
+
 impl TryFrom<proto::WireguardEndpointData> for mullvad_types::relay_list::WireguardEndpointData {
     type Error = FromProtobufTypeError;
 
@@ -4,7 +4,6 @@ use talpid_types::ErrorExt;
 
 impl From<&mullvad_types::settings::Settings> for proto::Settings {
     fn from(settings: &mullvad_types::settings::Settings) -> Self {
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
         let split_tunnel = {
             let apps = settings
                 .split_tunnel
@@ -22,10 +21,12 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
             Some(proto::SplitTunnelSettings {
                 enable_exclusions: settings.split_tunnel.enable_exclusions,
                 apps,
+                mode: Some(proto::SplitTunnelMode::from(settings.split_tunnel.mode)),
+                destinations: Some(proto::SplitTunnelDestinations::from(
+                    &settings.split_tunnel.destinations,
+                )),
             })
         };
-        #[cfg(target_os = "linux")]
-        let split_tunnel = None;
 
         Self {
             relay_settings: Some(proto::RelaySettings::from(settings.get_relay_settings())),
@@ -33,7 +34,11 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 settings.bridge_settings.clone(),
             )),
             bridge_state: Some(proto::BridgeState::from(settings.bridge_state)),
+            relay_selection_strategy: Some(proto::RelaySelectionStrategy::from(
+                settings.relay_selection_strategy,
+            )),
             allow_lan: settings.allow_lan,
+            lan_access: Some(proto::LanAccess::from(&settings.lan_access)),
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: settings.block_when_disconnected,
             #[cfg(target_os = "android")]
@@ -57,6 +62,10 @@ impl From<&mullvad_types::settings::Settings> for proto::Settings {
                 .cloned()
                 .map(proto::RelayOverride::from)
                 .collect(),
+            profiles: Some(proto::ProfileSettings::from(&settings.profiles)),
+            trusted_networks: Some(proto::TrustedNetworkSettings::from(
+                &settings.trusted_networks,
+            )),
         }
     }
 }
@@ -85,7 +94,14 @@ impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
                     .iter()
                     .map(|addr| addr.to_string())
                     .collect(),
+                encrypted_resolvers: options
+                    .custom_options
+                    .encrypted_resolvers
+                    .iter()
+                    .map(proto::EncryptedDnsResolver::from)
+                    .collect(),
             }),
+            blocklists: Some(proto::DnsBlocklists::from(&options.blocklists)),
         }
     }
 }
@@ -138,6 +154,12 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 "missing bridge state",
             ))
             .and_then(|state| try_bridge_state_from_i32(state.state))?;
+        let relay_selection_strategy = settings
+            .relay_selection_strategy
+            .ok_or(FromProtobufTypeError::InvalidArgument(
+                "missing relay selection strategy",
+            ))
+            .and_then(mullvad_types::relay_constraints::RelaySelectionStrategy::try_from)?;
         let tunnel_options =
             settings
                 .tunnel_options
@@ -162,7 +184,21 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .ok_or(FromProtobufTypeError::InvalidArgument(
                     "missing api access methods settings",
                 ))?;
-        #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+        let profiles = settings
+            .profiles
+            .map(mullvad_types::profile::ProfileSettings::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        let trusted_networks = settings
+            .trusted_networks
+            .map(mullvad_types::trusted_network::TrustedNetworkSettings::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        let lan_access = settings
+            .lan_access
+            .map(talpid_types::net::LanAccess::try_from)
+            .transpose()?
+            .unwrap_or_default();
         let split_tunnel = settings
             .split_tunnel
             .ok_or(FromProtobufTypeError::InvalidArgument(
@@ -177,10 +213,13 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 bridge_settings,
             )?,
             bridge_state,
+            relay_selection_strategy,
             allow_lan: settings.allow_lan,
+            lan_access,
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: settings.block_when_disconnected,
             auto_connect: settings.auto_connect,
+            trusted_networks,
             tunnel_options: mullvad_types::settings::TunnelOptions::try_from(tunnel_options)?,
             relay_overrides: settings
                 .relay_overrides
@@ -188,8 +227,8 @@ impl TryFrom<proto::Settings> for mullvad_types::settings::Settings {
                 .map(mullvad_types::relay_constraints::RelayOverride::try_from)
                 .collect::<Result<Vec<_>, _>>()?,
             show_beta_releases: settings.show_beta_releases,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
-            split_tunnel: mullvad_types::settings::SplitTunnelSettings::from(split_tunnel),
+            profiles,
+            split_tunnel: mullvad_types::settings::SplitTunnelSettings::try_from(split_tunnel)?,
             obfuscation_settings: mullvad_types::relay_constraints::ObfuscationSettings::try_from(
                 obfuscation_settings,
             )?,
@@ -223,14 +262,28 @@ pub fn try_bridge_state_from_i32(
     }
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
-impl From<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
-    fn from(value: proto::SplitTunnelSettings) -> Self {
-        use mullvad_types::settings::{SplitApp, SplitTunnelSettings};
-        SplitTunnelSettings {
+impl TryFrom<proto::SplitTunnelSettings> for mullvad_types::settings::SplitTunnelSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(value: proto::SplitTunnelSettings) -> Result<Self, Self::Error> {
+        use mullvad_types::{
+            settings::{SplitApp, SplitTunnelMode, SplitTunnelSettings},
+            split_tunnel_destination::SplitTunnelDestinations,
+        };
+        Ok(SplitTunnelSettings {
             enable_exclusions: value.enable_exclusions,
             apps: value.apps.into_iter().map(SplitApp::from).collect(),
-        }
+            mode: value
+                .mode
+                .map(SplitTunnelMode::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            destinations: value
+                .destinations
+                .map(SplitTunnelDestinations::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
     }
 }
 
@@ -356,7 +409,70 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
                         })
                     })
                     .collect::<Result<Vec<_>, _>>()?,
+                encrypted_resolvers: custom_options
+                    .encrypted_resolvers
+                    .into_iter()
+                    .map(talpid_types::net::dns::EncryptedDnsResolver::try_from)
+                    .collect::<Result<Vec<_>, _>>()?,
             },
+            blocklists: options
+                .blocklists
+                .map(mullvad_types::settings::DnsBlocklists::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl From<&mullvad_types::settings::DnsBlocklists> for proto::DnsBlocklists {
+    fn from(blocklists: &mullvad_types::settings::DnsBlocklists) -> Self {
+        use mullvad_types::settings::DnsBlocklistSource;
+        use proto::dns_blocklist_source::Source;
+
+        let sources = blocklists
+            .sources
+            .iter()
+            .map(|source| proto::DnsBlocklistSource {
+                source: Some(match source {
+                    DnsBlocklistSource::File(path) => Source::File(path.display().to_string()),
+                    DnsBlocklistSource::Url(url) => Source::Url(url.clone()),
+                }),
+            })
+            .collect();
+        proto::DnsBlocklists {
+            sources,
+            allowed_domains: blocklists.allowed_domains.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::DnsBlocklists> for mullvad_types::settings::DnsBlocklists {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(blocklists: proto::DnsBlocklists) -> Result<Self, Self::Error> {
+        use mullvad_types::settings::DnsBlocklistSource;
+        use proto::dns_blocklist_source::Source;
+
+        let sources = blocklists
+            .sources
+            .into_iter()
+            .map(|source| {
+                let source = match source.source {
+                    Some(Source::File(path)) | Some(Source::Url(path)) => path,
+                    None => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "missing DNS blocklist source",
+                        ))
+                    }
+                };
+                source.parse::<DnsBlocklistSource>().map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid DNS blocklist source")
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            sources,
+            allowed_domains: blocklists.allowed_domains,
         })
     }
 }
@@ -1,7 +1,14 @@
+#[cfg(target_os = "windows")]
 use crate::types;
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::split_tunnel_destination::{SplitTunnelDestination, SplitTunnelDestinations};
+#[cfg(target_os = "windows")]
 use std::path::PathBuf;
+#[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
+use talpid_types::split_tunnel::SplitTunnelMode;
 
+#[cfg(target_os = "windows")]
 impl From<ExcludedProcess> for types::ExcludedProcess {
     fn from(value: ExcludedProcess) -> Self {
         types::ExcludedProcess {
@@ -12,6 +19,7 @@ impl From<ExcludedProcess> for types::ExcludedProcess {
     }
 }
 
+#[cfg(target_os = "windows")]
 impl From<types::ExcludedProcess> for ExcludedProcess {
     fn from(value: types::ExcludedProcess) -> Self {
         ExcludedProcess {
@@ -21,3 +29,70 @@ impl From<types::ExcludedProcess> for ExcludedProcess {
         }
     }
 }
+
+impl From<SplitTunnelMode> for proto::SplitTunnelMode {
+    fn from(mode: SplitTunnelMode) -> Self {
+        let mode = match mode {
+            SplitTunnelMode::Exclude => proto::split_tunnel_mode::Mode::Exclude,
+            SplitTunnelMode::Include => proto::split_tunnel_mode::Mode::Include,
+        };
+        proto::SplitTunnelMode {
+            mode: i32::from(mode),
+        }
+    }
+}
+
+impl TryFrom<proto::SplitTunnelMode> for SplitTunnelMode {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(mode: proto::SplitTunnelMode) -> Result<Self, Self::Error> {
+        match proto::split_tunnel_mode::Mode::try_from(mode.mode) {
+            Ok(proto::split_tunnel_mode::Mode::Exclude) => Ok(SplitTunnelMode::Exclude),
+            Ok(proto::split_tunnel_mode::Mode::Include) => Ok(SplitTunnelMode::Include),
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid split tunnel mode",
+            )),
+        }
+    }
+}
+
+impl From<&SplitTunnelDestinations> for proto::SplitTunnelDestinations {
+    fn from(destinations: &SplitTunnelDestinations) -> Self {
+        Self {
+            networks: destinations
+                .networks
+                .iter()
+                .map(|network| network.to_string())
+                .collect(),
+            domains: destinations.domains.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::SplitTunnelDestinations> for SplitTunnelDestinations {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(destinations: proto::SplitTunnelDestinations) -> Result<Self, Self::Error> {
+        let networks = destinations
+            .networks
+            .iter()
+            .map(|network| match network.parse() {
+                Ok(SplitTunnelDestination::Network(network)) => Ok(network),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid excluded network",
+                )),
+            })
+            .collect::<Result<_, _>>()?;
+        let domains = destinations
+            .domains
+            .iter()
+            .map(|domain| match domain.parse() {
+                Ok(SplitTunnelDestination::Domain(domain)) => Ok(domain),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid excluded domain",
+                )),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { networks, domains })
+    }
+}
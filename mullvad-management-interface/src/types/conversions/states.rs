@@ -178,7 +178,7 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                                 error_state.cause()
                             {
                                 match reason {
-                                talpid_tunnel::ParameterGenerationError::NoMatchingRelay => {
+                                talpid_tunnel::ParameterGenerationError::NoMatchingRelay(_) => {
                                     i32::from(GenerationError::NoMatchingRelay)
                                 }
                                 talpid_tunnel::ParameterGenerationError::NoMatchingBridgeRelay => {
@@ -211,6 +211,16 @@ impl From<mullvad_types::states::TunnelState> for proto::TunnelState {
                             }
                             _ => None,
                         },
+                        no_matching_relay_reason: match error_state.cause() {
+                            talpid_tunnel::ErrorStateCause::TunnelParameterError(
+                                talpid_tunnel::ParameterGenerationError::NoMatchingRelay(Some(
+                                    reason,
+                                )),
+                            ) => Some(i32::from(proto::error_state::NoMatchingRelayReason::from(
+                                *reason,
+                            ))),
+                            _ => None,
+                        },
                     }),
                 })
             }
@@ -254,6 +264,54 @@ impl From<proto::error_state::AuthFailedError> for mullvad_types::auth_failed::A
     }
 }
 
+impl From<talpid_types::tunnel::NoMatchingRelayReason>
+    for proto::error_state::NoMatchingRelayReason
+{
+    fn from(reason: talpid_types::tunnel::NoMatchingRelayReason) -> Self {
+        use proto::error_state::NoMatchingRelayReason as ProtoReason;
+        use talpid_types::tunnel::NoMatchingRelayReason;
+        match reason {
+            NoMatchingRelayReason::TunnelProtocol => ProtoReason::TunnelProtocol,
+            NoMatchingRelayReason::Inactive => ProtoReason::Inactive,
+            NoMatchingRelayReason::Excluded => ProtoReason::Excluded,
+            NoMatchingRelayReason::Location => ProtoReason::Location,
+            NoMatchingRelayReason::EntryLocation => ProtoReason::EntryLocation,
+            NoMatchingRelayReason::Ownership => ProtoReason::Ownership,
+            NoMatchingRelayReason::Providers => ProtoReason::Providers,
+            NoMatchingRelayReason::Daita => ProtoReason::Daita,
+            NoMatchingRelayReason::Obfuscation => ProtoReason::Obfuscation,
+        }
+    }
+}
+
+impl From<proto::error_state::NoMatchingRelayReason>
+    for talpid_types::tunnel::NoMatchingRelayReason
+{
+    fn from(reason: proto::error_state::NoMatchingRelayReason) -> Self {
+        use proto::error_state::NoMatchingRelayReason as ProtoReason;
+        use talpid_types::tunnel::NoMatchingRelayReason;
+        match reason {
+            ProtoReason::TunnelProtocol => NoMatchingRelayReason::TunnelProtocol,
+            ProtoReason::Inactive => NoMatchingRelayReason::Inactive,
+            ProtoReason::Excluded => NoMatchingRelayReason::Excluded,
+            ProtoReason::Location => NoMatchingRelayReason::Location,
+            ProtoReason::EntryLocation => NoMatchingRelayReason::EntryLocation,
+            ProtoReason::Ownership => NoMatchingRelayReason::Ownership,
+            ProtoReason::Providers => NoMatchingRelayReason::Providers,
+            ProtoReason::Daita => NoMatchingRelayReason::Daita,
+            ProtoReason::Obfuscation => NoMatchingRelayReason::Obfuscation,
+        }
+    }
+}
+
+fn try_no_matching_relay_reason_from_i32(
+    reason: i32,
+) -> Result<talpid_types::tunnel::NoMatchingRelayReason, FromProtobufTypeError> {
+    proto::error_state::NoMatchingRelayReason::try_from(reason)
+        .map(talpid_types::tunnel::NoMatchingRelayReason::from)
+        .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid no matching relay reason"))
+}
+
 impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
     type Error = FromProtobufTypeError;
 
@@ -338,6 +396,7 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         parameter_error,
                         policy_error,
                         create_tunnel_error,
+                        no_matching_relay_reason,
                         ..
                     }),
             })) => {
@@ -384,7 +443,9 @@ impl TryFrom<proto::TunnelState> for mullvad_types::states::TunnelState {
                         let parameter_error = match proto::error_state::GenerationError::try_from(parameter_error) {
                             Ok(proto::error_state::GenerationError::CustomTunnelHostResolutionError) => talpid_tunnel::ParameterGenerationError::CustomTunnelHostResultionError,
                             Ok(proto::error_state::GenerationError::NoMatchingBridgeRelay) => talpid_tunnel::ParameterGenerationError::NoMatchingBridgeRelay,
-                            Ok(proto::error_state::GenerationError::NoMatchingRelay) => talpid_tunnel::ParameterGenerationError::NoMatchingRelay,
+                            Ok(proto::error_state::GenerationError::NoMatchingRelay) => talpid_tunnel::ParameterGenerationError::NoMatchingRelay(
+                                no_matching_relay_reason.map(try_no_matching_relay_reason_from_i32).transpose()?,
+                            ),
                             Ok(proto::error_state::GenerationError::NoWireguardKey) => talpid_tunnel::ParameterGenerationError::NoWireguardKey,
                             _ => return Err(FromProtobufTypeError::InvalidArgument(
                                 "invalid parameter error",
@@ -455,3 +516,41 @@ fn try_firewall_policy_error_from_i32(
         )),
     }
 }
+
+impl From<talpid_types::tunnel::TunnelStats> for proto::TunnelStats {
+    fn from(stats: talpid_types::tunnel::TunnelStats) -> Self {
+        let to_proto_duration = |duration: std::time::Duration| {
+            prost_types::Duration::try_from(duration).expect("Duration should fit in protobuf")
+        };
+        Self {
+            tx_bytes: stats.tx_bytes,
+            rx_bytes: stats.rx_bytes,
+            tx_rate: stats.tx_rate,
+            rx_rate: stats.rx_rate,
+            last_handshake_age: stats.last_handshake_age.map(to_proto_duration),
+            latency: stats.latency.map(to_proto_duration),
+        }
+    }
+}
+
+impl TryFrom<proto::TunnelStats> for talpid_types::tunnel::TunnelStats {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(stats: proto::TunnelStats) -> Result<Self, Self::Error> {
+        let from_proto_duration = |duration: prost_types::Duration| {
+            std::time::Duration::try_from(duration)
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))
+        };
+        Ok(Self {
+            tx_bytes: stats.tx_bytes,
+            rx_bytes: stats.rx_bytes,
+            tx_rate: stats.tx_rate,
+            rx_rate: stats.rx_rate,
+            last_handshake_age: stats
+                .last_handshake_age
+                .map(from_proto_duration)
+                .transpose()?,
+            latency: stats.latency.map(from_proto_duration).transpose()?,
+        })
+    }
+}
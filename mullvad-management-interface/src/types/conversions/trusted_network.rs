@@ -0,0 +1,79 @@
+use crate::types::{proto, FromProtobufTypeError};
+use mullvad_types::trusted_network::{CurrentNetwork, TrustedNetwork, TrustedNetworkSettings};
+
+impl From<&TrustedNetworkSettings> for proto::TrustedNetworkSettings {
+    fn from(settings: &TrustedNetworkSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            networks: settings
+                .networks
+                .iter()
+                .cloned()
+                .map(proto::TrustedNetwork::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::TrustedNetworkSettings> for TrustedNetworkSettings {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: proto::TrustedNetworkSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            enabled: settings.enabled,
+            networks: settings
+                .networks
+                .into_iter()
+                .map(TrustedNetwork::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl From<TrustedNetwork> for proto::TrustedNetwork {
+    fn from(network: TrustedNetwork) -> Self {
+        use proto::trusted_network::Network;
+
+        let network = match network {
+            TrustedNetwork::GatewayMac(mac) => Network::GatewayMac(mac),
+            TrustedNetwork::Ssid(ssid) => Network::Ssid(ssid),
+        };
+        Self {
+            network: Some(network),
+        }
+    }
+}
+
+impl TryFrom<proto::TrustedNetwork> for TrustedNetwork {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(network: proto::TrustedNetwork) -> Result<Self, Self::Error> {
+        use proto::trusted_network::Network;
+
+        match network.network {
+            Some(Network::GatewayMac(mac)) => Ok(TrustedNetwork::GatewayMac(mac)),
+            Some(Network::Ssid(ssid)) => Ok(TrustedNetwork::Ssid(ssid)),
+            None => Err(FromProtobufTypeError::InvalidArgument(
+                "missing trusted network",
+            )),
+        }
+    }
+}
+
+impl From<CurrentNetwork> for proto::CurrentNetwork {
+    fn from(network: CurrentNetwork) -> Self {
+        Self {
+            gateway_mac: network.gateway_mac,
+            ssid: network.ssid,
+        }
+    }
+}
+
+impl From<proto::CurrentNetwork> for CurrentNetwork {
+    fn from(network: proto::CurrentNetwork) -> Self {
+        Self {
+            gateway_mac: network.gateway_mac,
+            ssid: network.ssid,
+        }
+    }
+}
@@ -9,8 +9,8 @@ mod relay_selector;
 // Re-exports
 pub use error::Error;
 pub use relay_selector::{
-    detailer, matcher, matcher::filter_matching_relay_list, query, relays::WireguardConfig,
-    AdditionalRelayConstraints, AdditionalWireguardConstraints, GetRelay, RelaySelector,
-    RuntimeParameters, SelectedBridge, SelectedObfuscator, SelectorConfig, OPENVPN_RETRY_ORDER,
-    WIREGUARD_RETRY_ORDER,
+    detailer, health, latency, matcher, matcher::filter_matching_relay_list, query,
+    relays::WireguardConfig, AdditionalRelayConstraints, AdditionalWireguardConstraints, GetRelay,
+    RelaySelectionExplanation, RelaySelector, RuntimeParameters, SelectedBridge,
+    SelectedObfuscator, SelectorConfig, OPENVPN_RETRY_ORDER, WIREGUARD_RETRY_ORDER,
 };
@@ -0,0 +1,210 @@
+//! This module keeps track of relays that recently failed to connect, so that the relay selector
+//! can avoid picking them again for a while.
+//!
+//! ## Overview
+//!
+//! The relay selector does not know whether a connection attempt succeeded. Instead, failures and
+//! successes are reported through [`RelaySelector::record_connection_failure`] and
+//! [`RelaySelector::record_connection_success`]. Each consecutive failure doubles the time during
+//! which a relay is avoided, up to [`MAX_FAILURE_COOLDOWN`], and a successful connection forgets
+//! all failures of a relay.
+//!
+//! Avoided relays are given a weight of 0, which means that they are only picked if every relay
+//! matching the constraints is avoided. A failing relay can therefore never cause relay selection
+//! to fail by itself.
+//!
+//! [`RelaySelector::record_connection_failure`]: crate::RelaySelector::record_connection_failure
+//! [`RelaySelector::record_connection_success`]: crate::RelaySelector::record_connection_success
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use mullvad_types::{
+    relay_health::RelayFailure,
+    relay_list::{RelayEndpointData, RelayList},
+};
+
+/// How long a relay is avoided after failing to connect once.
+pub const FAILURE_COOLDOWN: Duration = Duration::from_secs(2 * 60);
+/// The longest time a relay is avoided, no matter how many times it has failed to connect. Failures
+/// that are older than this are forgotten.
+pub const MAX_FAILURE_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+/// Recent connection failures, keyed by relay hostname.
+#[derive(Debug, Clone, Default)]
+pub struct RelayHealth {
+    failures: HashMap<String, Failures>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    last_failure_time: SystemTime,
+}
+
+impl Failures {
+    /// How long the relay is avoided after its last failure.
+    fn cooldown(&self) -> Duration {
+        let exponent = self.count.saturating_sub(1).min(u32::BITS - 1);
+        FAILURE_COOLDOWN
+            .saturating_mul(1 << exponent)
+            .min(MAX_FAILURE_COOLDOWN)
+    }
+
+    fn is_penalized(&self) -> bool {
+        self.last_failure.elapsed() < self.cooldown()
+    }
+
+    fn is_expired(&self) -> bool {
+        self.last_failure.elapsed() >= MAX_FAILURE_COOLDOWN
+    }
+}
+
+impl RelayHealth {
+    /// Register a failed connection attempt to `hostname`.
+    pub fn record_failure(&mut self, hostname: &str) {
+        self.record_failure_at(hostname, Instant::now(), SystemTime::now());
+    }
+
+    fn record_failure_at(&mut self, hostname: &str, at: Instant, at_time: SystemTime) {
+        self.prune();
+        let count = self
+            .failures
+            .get(hostname)
+            .map(|failures| failures.count.saturating_add(1))
+            .unwrap_or(1);
+        self.failures.insert(
+            hostname.to_owned(),
+            Failures {
+                count,
+                last_failure: at,
+                last_failure_time: at_time,
+            },
+        );
+    }
+
+    /// Register a successful connection to `hostname`, forgetting all of its failures.
+    pub fn record_success(&mut self, hostname: &str) {
+        self.failures.remove(hostname);
+    }
+
+    /// Returns whether `hostname` should currently be avoided.
+    pub fn is_penalized(&self, hostname: &str) -> bool {
+        self.failures
+            .get(hostname)
+            .is_some_and(|failures| failures.is_penalized())
+    }
+
+    /// Remove all failures that are too old to matter.
+    pub fn prune(&mut self) {
+        self.failures.retain(|_, failures| !failures.is_expired());
+    }
+
+    /// Forget all failures.
+    pub fn clear(&mut self) {
+        self.failures.clear();
+    }
+
+    /// Returns all relays that have failed to connect recently.
+    pub fn failures(&self) -> Vec<RelayFailure> {
+        let mut failures: Vec<_> = self
+            .failures
+            .iter()
+            .filter(|(_, failures)| !failures.is_expired())
+            .map(|(hostname, failures)| RelayFailure {
+                hostname: hostname.clone(),
+                failures: failures.count,
+                last_failure: DateTime::<Utc>::from(failures.last_failure_time),
+                penalized_until: DateTime::<Utc>::from(
+                    failures.last_failure_time + failures.cooldown(),
+                ),
+            })
+            .collect();
+        failures.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+        failures
+    }
+
+    /// Set the weight of every VPN relay in `relay_list` that should currently be avoided to 0.
+    ///
+    /// Bridges are left untouched, since they are selected based on their distance to the relay.
+    pub fn apply_penalties(&self, relay_list: &mut RelayList) {
+        if self.failures.is_empty() {
+            return;
+        }
+        let relays = relay_list
+            .countries
+            .iter_mut()
+            .flat_map(|country| country.cities.iter_mut())
+            .flat_map(|city| city.relays.iter_mut())
+            .filter(|relay| !matches!(relay.endpoint_data, RelayEndpointData::Bridge));
+        for relay in relays {
+            if self.is_penalized(&relay.hostname) {
+                relay.weight = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_grows_with_consecutive_failures() {
+        let mut health = RelayHealth::default();
+        health.record_failure("se-got-wg-001");
+        health.record_failure("se-got-wg-001");
+        health.record_failure("se-got-wg-001");
+
+        let failures = health.failures["se-got-wg-001"];
+        assert_eq!(failures.count, 3);
+        assert_eq!(failures.cooldown(), FAILURE_COOLDOWN * 4);
+        assert!(health.is_penalized("se-got-wg-001"));
+
+        for _ in 0..100 {
+            health.record_failure("se-got-wg-001");
+        }
+        assert_eq!(
+            health.failures["se-got-wg-001"].cooldown(),
+            MAX_FAILURE_COOLDOWN
+        );
+
+        health.record_success("se-got-wg-001");
+        assert!(!health.is_penalized("se-got-wg-001"));
+    }
+
+    #[test]
+    fn test_old_failures_are_forgotten() {
+        let long_ago = MAX_FAILURE_COOLDOWN + Duration::from_secs(1);
+        let now = Instant::now();
+        // `Instant` may not be able to represent a point in time this far back
+        let (Some(failed_long_ago), Some(failed_recently)) =
+            (now.checked_sub(long_ago), now.checked_sub(FAILURE_COOLDOWN))
+        else {
+            return;
+        };
+        let mut health = RelayHealth::default();
+        health.record_failure_at(
+            "se-got-wg-001",
+            failed_long_ago,
+            SystemTime::now() - long_ago,
+        );
+        health.record_failure_at(
+            "se-got-wg-002",
+            failed_recently,
+            SystemTime::now() - FAILURE_COOLDOWN,
+        );
+
+        assert!(!health.is_penalized("se-got-wg-001"));
+        assert!(!health.is_penalized("se-got-wg-002"));
+        assert_eq!(health.failures().len(), 1);
+
+        // A failure after the old ones have expired counts as the first one
+        health.record_failure("se-got-wg-001");
+        assert_eq!(health.failures["se-got-wg-001"].count, 1);
+    }
+}
@@ -8,14 +8,16 @@ use std::{
 use mullvad_types::{
     constraints::Constraint,
     endpoint::MullvadWireguardEndpoint,
-    relay_constraints::{ShadowsocksSettings, Udp2TcpObfuscationSettings},
-    relay_list::Relay,
+    relay_constraints::{
+        GenericObfuscationSettings, ShadowsocksSettings, Udp2TcpObfuscationSettings,
+    },
+    relay_list::{Relay, RelayEndpointData},
 };
 use rand::{
     seq::{IteratorRandom, SliceRandom},
     thread_rng, Rng,
 };
-use talpid_types::net::obfuscation::ObfuscatorConfig;
+use talpid_types::net::{obfuscation::ObfuscatorConfig, Endpoint};
 
 use crate::SelectedObfuscator;
 
@@ -23,10 +25,17 @@ use crate::SelectedObfuscator;
 /// For relays that have no additional IPs, only ports provided by the relay list are available.
 const SHADOWSOCKS_EXTRA_PORT_RANGES: &[RangeInclusive<u16>] = &[1..=u16::MAX];
 
+/// Port that the QUIC proxy on WireGuard relays listens on.
+const QUIC_PORT: u16 = 443;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Found no valid port matching the selected settings")]
     NoMatchingPort,
+    #[error("The relay does not support QUIC obfuscation")]
+    NoQuicProxy,
+    #[error("No protocol or port is set for generic obfuscation")]
+    MissingGenericObfuscation,
 }
 
 /// Picks a relay at random from `relays`, but don't pick `exclude`.
@@ -122,9 +131,7 @@ pub fn get_shadowsocks_obfuscator(
 ) -> Result<SelectedObfuscator, Error> {
     let port = settings.port;
     let extra_addrs = match &relay.endpoint_data {
-        mullvad_types::relay_list::RelayEndpointData::Wireguard(wg) => {
-            &wg.shadowsocks_extra_addr_in
-        }
+        RelayEndpointData::Wireguard(wg) => &wg.shadowsocks_extra_addr_in,
         _ => panic!("expected wireguard relay"),
     };
 
@@ -136,11 +143,68 @@ pub fn get_shadowsocks_obfuscator(
     )?;
 
     Ok(SelectedObfuscator {
-        config: ObfuscatorConfig::Shadowsocks { endpoint },
+        config: ObfuscatorConfig::Shadowsocks {
+            endpoint,
+            cipher: None,
+            password: None,
+            target: None,
+        },
         relay,
     })
 }
 
+pub fn get_quic_obfuscator(
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> Result<SelectedObfuscator, Error> {
+    let quic = match &relay.endpoint_data {
+        RelayEndpointData::Wireguard(wg) => wg.quic.as_ref().ok_or(Error::NoQuicProxy)?,
+        _ => panic!("expected wireguard relay"),
+    };
+
+    // Use an address of the same family as the WireGuard endpoint. If there is none, e.g. because
+    // the relay's address has been overridden, the proxy is reached at the WireGuard endpoint.
+    let wg_in_addr = endpoint.peer.endpoint.ip();
+    let in_ip = quic
+        .addr_in
+        .iter()
+        .filter(|addr| addr.is_ipv4() == wg_in_addr.is_ipv4())
+        .choose(&mut rand::thread_rng())
+        .copied()
+        .unwrap_or(wg_in_addr);
+
+    let config = ObfuscatorConfig::Quic {
+        endpoint: SocketAddr::new(in_ip, QUIC_PORT),
+        hostname: quic.domain.clone(),
+        auth_token: quic.token.clone(),
+    };
+
+    Ok(SelectedObfuscator { config, relay })
+}
+
+/// Return a config for the protocol in `settings`. Its server is assumed to listen on the same
+/// address as the WireGuard server, since the relay list does not describe generic protocols.
+pub fn get_generic_obfuscator(
+    settings: &GenericObfuscationSettings,
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> Result<SelectedObfuscator, Error> {
+    if settings.protocol.is_empty() || settings.port == 0 {
+        return Err(Error::MissingGenericObfuscation);
+    }
+    let config = ObfuscatorConfig::Generic {
+        protocol: settings.protocol.clone(),
+        endpoint: Endpoint::new(
+            endpoint.peer.endpoint.ip(),
+            settings.port,
+            settings.transport,
+        ),
+        parameters: settings.parameters.clone(),
+    };
+
+    Ok(SelectedObfuscator { config, relay })
+}
+
 /// Return an obfuscation config for the wireguard server at `wg_in_addr` or one of `extra_in_addrs`
 /// (unless empty). `wg_in_addr_port_ranges` contains all valid ports for `wg_in_addr`, and
 /// `SHADOWSOCKS_EXTRA_PORT_RANGES` contains valid ports for `extra_in_addrs`.
@@ -0,0 +1,166 @@
+//! This module keeps track of round-trip times to relays, which are used to bias relay selection
+//! towards fast relays when the [fastest][RelaySelectionStrategy::Fastest] relay selection
+//! strategy is in use.
+//!
+//! ## Overview
+//!
+//! The relay selector does not measure latencies by itself. Instead, a [`LatencyProber`] is
+//! passed to [`RelaySelector::update_latencies`], and the resulting measurements are stored in a
+//! [`LatencyCache`] until they expire. When selecting a relay, the weight of each relay is
+//! replaced by a weight derived from its measured latency. Since this only affects the weights,
+//! every constraint is still honored.
+//!
+//! [`RelaySelector::update_latencies`]: crate::RelaySelector::update_latencies
+//! [RelaySelectionStrategy::Fastest]: mullvad_types::relay_constraints::RelaySelectionStrategy::Fastest
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use mullvad_types::relay_list::{Relay, RelayEndpointData, RelayList};
+
+/// How long a latency measurement is considered to be accurate.
+pub const LATENCY_EXPIRY: Duration = Duration::from_secs(15 * 60);
+
+/// The weight given to a relay with a round-trip time of 1 ms or less. The weight of a relay is
+/// inversely proportional to the square of its round-trip time, which makes relays that are
+/// slightly slower than the fastest one still likely to be picked, while relays that are much
+/// slower are all but ignored.
+const MAX_LATENCY_WEIGHT: u64 = 1_000_000;
+
+/// Something which is able to measure the round-trip time to relays.
+pub trait LatencyProber {
+    /// Measure the round-trip time to each relay in `relays`. Relays which could not be reached
+    /// should be left out of the result.
+    fn probe(&self, relays: &[Relay]) -> Vec<(String, Duration)>;
+}
+
+/// Recent round-trip time measurements, keyed by relay hostname.
+#[derive(Debug, Clone)]
+pub struct LatencyCache {
+    expiry: Duration,
+    measurements: HashMap<String, Measurement>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Measurement {
+    latency: Duration,
+    measured_at: Instant,
+}
+
+impl LatencyCache {
+    pub fn new(expiry: Duration) -> Self {
+        LatencyCache {
+            expiry,
+            measurements: HashMap::new(),
+        }
+    }
+
+    /// Store a new measurement for `hostname`, replacing any previous one.
+    pub fn insert(&mut self, hostname: String, latency: Duration) {
+        self.insert_at(hostname, latency, Instant::now());
+    }
+
+    fn insert_at(&mut self, hostname: String, latency: Duration, measured_at: Instant) {
+        self.measurements.insert(
+            hostname,
+            Measurement {
+                latency,
+                measured_at,
+            },
+        );
+    }
+
+    /// Return the latency to `hostname`, unless it has not been measured or the measurement has
+    /// expired.
+    pub fn get(&self, hostname: &str) -> Option<Duration> {
+        self.measurements
+            .get(hostname)
+            .filter(|measurement| measurement.measured_at.elapsed() < self.expiry)
+            .map(|measurement| measurement.latency)
+    }
+
+    /// Remove all expired measurements.
+    pub fn prune(&mut self) {
+        let expiry = self.expiry;
+        self.measurements
+            .retain(|_, measurement| measurement.measured_at.elapsed() < expiry);
+    }
+
+    /// Remove all measurements.
+    pub fn clear(&mut self) {
+        self.measurements.clear();
+    }
+
+    /// Replace the weight of every VPN relay in `relay_list` with a weight derived from its
+    /// latency. Relays without a valid measurement get a weight of 0, which means that they will
+    /// only be picked if none of the matching relays have been measured.
+    ///
+    /// Bridges are left untouched, since they are selected based on their distance to the relay.
+    pub fn apply_weights(&self, relay_list: &mut RelayList) {
+        let relays = relay_list
+            .countries
+            .iter_mut()
+            .flat_map(|country| country.cities.iter_mut())
+            .flat_map(|city| city.relays.iter_mut())
+            .filter(|relay| !matches!(relay.endpoint_data, RelayEndpointData::Bridge));
+        for relay in relays {
+            relay.weight = match self.get(&relay.hostname) {
+                // Respect relays that have been disabled through their weight
+                Some(_) if relay.weight == 0 => 0,
+                Some(latency) => latency_weight(latency),
+                None => 0,
+            };
+        }
+    }
+}
+
+impl Default for LatencyCache {
+    fn default() -> Self {
+        Self::new(LATENCY_EXPIRY)
+    }
+}
+
+/// Map a round-trip time to a relay weight.
+fn latency_weight(latency: Duration) -> u64 {
+    let millis = u64::try_from(latency.as_millis())
+        .unwrap_or(u64::MAX)
+        .max(1);
+    (MAX_LATENCY_WEIGHT / millis.saturating_mul(millis)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_latency_is_ignored() {
+        // `Instant` may not be able to represent a point in time this far back
+        let Some(measured_at) = Instant::now().checked_sub(Duration::from_secs(120)) else {
+            return;
+        };
+        let mut cache = LatencyCache::new(Duration::from_secs(60));
+        cache.insert("se-got-wg-001".to_owned(), Duration::from_millis(10));
+        cache.insert_at(
+            "se-got-wg-002".to_owned(),
+            Duration::from_millis(10),
+            measured_at,
+        );
+
+        assert_eq!(cache.get("se-got-wg-001"), Some(Duration::from_millis(10)));
+        assert_eq!(cache.get("se-got-wg-002"), None);
+
+        cache.prune();
+        assert_eq!(cache.measurements.len(), 1);
+    }
+
+    #[test]
+    fn test_latency_weight() {
+        assert_eq!(latency_weight(Duration::ZERO), MAX_LATENCY_WEIGHT);
+        assert!(
+            latency_weight(Duration::from_millis(10)) > latency_weight(Duration::from_millis(20))
+        );
+        assert_eq!(latency_weight(Duration::from_secs(3600)), 1);
+    }
+}
@@ -5,8 +5,8 @@ use mullvad_types::{
     constraints::{Constraint, Match},
     custom_list::CustomListsSettings,
     relay_constraints::{
-        GeographicLocationConstraint, InternalBridgeConstraints, LocationConstraint, Ownership,
-        Providers, ShadowsocksSettings,
+        GeographicLocationConstraint, InternalBridgeConstraints, LocationConstraint,
+        NearestLocationConstraint, Ownership, Providers, RelayExclusions, ShadowsocksSettings,
     },
     relay_list::{Relay, RelayEndpointData, RelayList, WireguardRelayEndpointData},
 };
@@ -21,31 +21,18 @@ pub fn filter_matching_relay_list(
     relay_list: &RelayList,
     custom_lists: &CustomListsSettings,
 ) -> Vec<Relay> {
-    let relays = relay_list.relays();
-
     let locations = ResolvedLocationConstraint::from_constraint(query.location(), custom_lists);
-    let shortlist = relays
-            // Filter on tunnel type
-            .filter(|relay| filter_tunnel_type(&query.tunnel_protocol(), relay))
-            // Filter on active relays
-            .filter(|relay| filter_on_active(relay))
-            // Filter by location
-            .filter(|relay| filter_on_location(&locations, relay))
-            // Filter by ownership
-            .filter(|relay| filter_on_ownership(&query.ownership(), relay))
-            // Filter by providers
-            .filter(|relay| filter_on_providers(query.providers(), relay))
-            // Filter by DAITA support
-            .filter(|relay| filter_on_daita(&query.wireguard_constraints().daita, relay))
-            // Filter by obfuscation support
-            .filter(|relay| filter_on_obfuscation(query.wireguard_constraints(), relay_list, relay));
+    let exclusions = ResolvedRelayExclusions::from_exclusions(query.exclusions(), custom_lists);
+    let shortlist = relay_list
+        .relays()
+        .filter(|relay| check_relay(query, relay_list, &locations, &exclusions, relay).is_ok());
 
     // The last filtering to be done is on the `include_in_country` attribute found on each
     // relay. When the location constraint is based on country, a relay which has
     // `include_in_country` set to true should always be prioritized over relays which has this
     // flag set to false. We should only consider relays with `include_in_country` set to false
     // if there are no other candidates left.
-    match &locations {
+    let matches = match &locations {
         Constraint::Any => shortlist.cloned().collect(),
         Constraint::Only(locations) => {
             let mut included = HashSet::new();
@@ -63,9 +50,163 @@ pub fn filter_matching_relay_list(
                 included.into_iter().cloned().collect()
             }
         }
+    };
+
+    // Distances can only be compared once all other filters have been applied.
+    filter_on_distance(query.location(), matches)
+}
+
+/// Returns the first constraint in `query` that `relay` does not satisfy, checking them in the same
+/// order as [`filter_matching_relay_list`]. This does not consider constraints that depend on the
+/// other matching relays, i.e. `include_in_country` and [nearest][`NearestLocationConstraint`]
+/// location constraints.
+fn check_relay(
+    query: &RelayQuery,
+    relay_list: &RelayList,
+    locations: &Constraint<ResolvedLocationConstraint<'_>>,
+    exclusions: &ResolvedRelayExclusions<'_>,
+    relay: &Relay,
+) -> Result<(), RelayRejection> {
+    // Each constraint is only checked if the relay satisfies all previous ones
+    check(
+        filter_tunnel_type(&query.tunnel_protocol(), relay),
+        RelayRejection::TunnelType,
+    )?;
+    check(filter_on_active(relay), RelayRejection::Inactive)?;
+    check(
+        filter_on_location(locations, relay),
+        RelayRejection::Location,
+    )?;
+    check(
+        filter_on_ownership(&query.ownership(), relay),
+        RelayRejection::Ownership,
+    )?;
+    check(
+        filter_on_providers(query.providers(), relay),
+        RelayRejection::Providers,
+    )?;
+    check(
+        filter_on_exclusions(exclusions, relay),
+        RelayRejection::Excluded,
+    )?;
+    check(
+        filter_on_daita(&query.wireguard_constraints().daita, relay),
+        RelayRejection::Daita,
+    )?;
+    check(
+        filter_on_obfuscation(query.wireguard_constraints(), relay_list, relay),
+        RelayRejection::Obfuscation,
+    )
+}
+
+fn check(matches: bool, rejection: RelayRejection) -> Result<(), RelayRejection> {
+    if matches {
+        Ok(())
+    } else {
+        Err(rejection)
     }
 }
 
+/// The reason why a relay was not considered by a query. The variants are ordered in the same
+/// order as the constraints are checked, which goes from broad constraints to specific ones. This
+/// makes the last constraint that rejected any relay a good explanation for why no relay matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RelayRejection {
+    /// The relay does not support the tunnel protocol.
+    TunnelType,
+    /// The relay is not active.
+    Inactive,
+    /// The relay is not in any of the selected locations.
+    Location,
+    /// The relay does not match the ownership constraint.
+    Ownership,
+    /// The relay is not hosted by any of the selected providers.
+    Providers,
+    /// The relay is excluded by its hostname, provider or a custom list.
+    Excluded,
+    /// The relay does not support DAITA.
+    Daita,
+    /// The relay does not support the selected obfuscation settings.
+    Obfuscation,
+    /// The relay is not included in its country, and other relays in the country matched.
+    NotIncludedInCountry,
+    /// The relay is farther away than the radius of the nearest location constraint, and closer
+    /// relays matched.
+    Distance,
+}
+
+impl std::fmt::Display for RelayRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            RelayRejection::TunnelType => "does not support the tunnel protocol",
+            RelayRejection::Inactive => "is not active",
+            RelayRejection::Excluded => "is excluded",
+            RelayRejection::Location => "is not in the selected location",
+            RelayRejection::Ownership => "does not match the ownership constraint",
+            RelayRejection::Providers => "is not hosted by a selected provider",
+            RelayRejection::Daita => "does not support DAITA",
+            RelayRejection::Obfuscation => "does not support the obfuscation settings",
+            RelayRejection::NotIncludedInCountry => "is not included in its country",
+            RelayRejection::Distance => "is outside the radius of the nearest location",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Whether a single relay matched a query, and if not, why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayExplanation {
+    pub hostname: String,
+    /// `None` if the relay matched the query.
+    pub rejection: Option<RelayRejection>,
+}
+
+/// Explain why each relay in `relay_list` did or did not match `query`. The relays which match are
+/// exactly those returned by [`filter_matching_relay_list`].
+pub fn explain_relay_list(
+    query: &RelayQuery,
+    relay_list: &RelayList,
+    custom_lists: &CustomListsSettings,
+) -> Vec<RelayExplanation> {
+    let locations = ResolvedLocationConstraint::from_constraint(query.location(), custom_lists);
+    let exclusions = ResolvedRelayExclusions::from_exclusions(query.exclusions(), custom_lists);
+    let matches: HashSet<String> = filter_matching_relay_list(query, relay_list, custom_lists)
+        .into_iter()
+        .map(|relay| relay.hostname)
+        .collect();
+    let uses_distance = matches!(
+        query.location(),
+        Constraint::Only(LocationConstraint::Nearest(_))
+    );
+
+    relay_list
+        .relays()
+        .map(|relay| {
+            let rejection = match check_relay(query, relay_list, &locations, &exclusions, relay) {
+                Err(rejection) => Some(rejection),
+                Ok(()) if matches.contains(&relay.hostname) => None,
+                Ok(()) if uses_distance => Some(RelayRejection::Distance),
+                Ok(()) => Some(RelayRejection::NotIncludedInCountry),
+            };
+            RelayExplanation {
+                hostname: relay.hostname.clone(),
+                rejection,
+            }
+        })
+        .collect()
+}
+
+/// If no relay in `explanations` matched, returns the constraint that eliminated the last
+/// candidates, i.e. the last constraint which any relay did not satisfy.
+pub fn eliminating_rejection(explanations: &[RelayExplanation]) -> Option<RelayRejection> {
+    explanations
+        .iter()
+        .map(|explanation| explanation.rejection)
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .max()
+}
+
 pub fn filter_matching_bridges<'a, R: Iterator<Item = &'a Relay> + Clone>(
     constraints: &InternalBridgeConstraints,
     relays: R,
@@ -73,11 +214,15 @@ pub fn filter_matching_bridges<'a, R: Iterator<Item = &'a Relay> + Clone>(
 ) -> Vec<Relay> {
     let locations =
         ResolvedLocationConstraint::from_constraint(&constraints.location, custom_lists);
-    relays
+    let exclusions =
+        ResolvedRelayExclusions::from_exclusions(&constraints.exclusions, custom_lists);
+    let bridges = relays
             // Filter on active relays
             .filter(|relay| filter_on_active(relay))
             // Filter on bridge type
             .filter(|relay| filter_bridge(relay))
+            // Filter out excluded bridges
+            .filter(|relay| filter_on_exclusions(&exclusions, relay))
             // Filter by location
             .filter(|relay| filter_on_location(&locations, relay))
             // Filter by ownership
@@ -85,7 +230,8 @@ pub fn filter_matching_bridges<'a, R: Iterator<Item = &'a Relay> + Clone>(
             // Filter by providers
             .filter(|relay| filter_on_providers(&constraints.providers, relay))
             .cloned()
-            .collect()
+            .collect();
+    filter_on_distance(&constraints.location, bridges)
 }
 
 // --- Define relay filters as simple functions / predicates ---
@@ -104,6 +250,42 @@ pub fn filter_on_location(
     filter.matches(relay)
 }
 
+/// Returns whether `relay` is not excluded by `exclusions`.
+pub fn filter_on_exclusions(exclusions: &ResolvedRelayExclusions<'_>, relay: &Relay) -> bool {
+    !exclusions.matches(relay)
+}
+
+/// Keep only the relays in `relays` that satisfy a [nearest][`NearestLocationConstraint`] location
+/// constraint. Unlike the other filters, this depends on the whole set of `relays`, since the
+/// closest relays are used if no relay is within the radius of the constraint.
+pub fn filter_on_distance(
+    location_constraint: &Constraint<LocationConstraint>,
+    relays: Vec<Relay>,
+) -> Vec<Relay> {
+    let Constraint::Only(LocationConstraint::Nearest(NearestLocationConstraint { origin, radius })) =
+        location_constraint
+    else {
+        return relays;
+    };
+    let Some(origin) = origin else {
+        log::warn!("Ignoring nearest location constraint since the device location is unknown");
+        return relays;
+    };
+
+    let Some(smallest_distance) = relays
+        .iter()
+        .map(|relay| relay.location.distance_from(*origin))
+        .reduce(f64::min)
+    else {
+        return relays;
+    };
+    let max_distance = smallest_distance.max(f64::from(*radius));
+    relays
+        .into_iter()
+        .filter(|relay| relay.location.distance_from(*origin) <= max_distance)
+        .collect()
+}
+
 /// Returns whether `relay` satisfy the ownership constraint posed by `filter`.
 pub fn filter_on_ownership(filter: &Constraint<Ownership>, relay: &Relay) -> bool {
     filter.matches(relay)
@@ -145,7 +327,10 @@ fn filter_on_obfuscation(
             )
         }
 
-        // If Shadowsocks is not a requirement, then there are no relay-specific constraints
+        // Only a subset of relays run a QUIC proxy
+        ObfuscationQuery::Quic => filter_on_quic(relay),
+
+        // Other obfuscation methods have no relay-specific constraints
         _ => true,
     }
 }
@@ -183,6 +368,14 @@ fn filter_on_shadowsocks(
     }
 }
 
+/// Returns whether `relay` supports QUIC obfuscation.
+const fn filter_on_quic(relay: &Relay) -> bool {
+    matches!(
+        relay.endpoint_data,
+        RelayEndpointData::Wireguard(WireguardRelayEndpointData { quic: Some(_), .. })
+    )
+}
+
 /// Returns whether the relay is an OpenVPN relay.
 pub const fn filter_openvpn(relay: &Relay) -> bool {
     matches!(relay.endpoint_data, RelayEndpointData::Openvpn)
@@ -243,6 +436,9 @@ impl<'a> ResolvedLocationConstraint<'a> {
                         log::warn!("Resolved non-existent custom list with id {list_id:?}");
                         ResolvedLocationConstraint(vec![])
                     }),
+                // The distance to each relay is compared by `filter_on_distance` instead, since it
+                // depends on the other matching relays.
+                LocationConstraint::Nearest(_) => return Constraint::Any,
             }),
         }
     }
@@ -262,3 +458,45 @@ impl Match<Relay> for ResolvedLocationConstraint<'_> {
         self.into_iter().any(|location| location.matches(relay))
     }
 }
+
+/// Wrapper around [`RelayExclusions`] where excluded custom lists have been resolved to their
+/// locations.
+#[derive(Debug, Clone)]
+pub struct ResolvedRelayExclusions<'a> {
+    exclusions: &'a RelayExclusions,
+    locations: ResolvedLocationConstraint<'a>,
+}
+
+impl<'a> ResolvedRelayExclusions<'a> {
+    pub fn from_exclusions(
+        exclusions: &'a RelayExclusions,
+        custom_lists: &'a CustomListsSettings,
+    ) -> ResolvedRelayExclusions<'a> {
+        let locations = exclusions
+            .custom_lists
+            .iter()
+            .flat_map(|list_id| {
+                let custom_list = custom_lists.iter().find(|list| list.id == *list_id);
+                if custom_list.is_none() {
+                    log::warn!("Resolved non-existent excluded custom list with id {list_id:?}");
+                }
+                custom_list
+                    .into_iter()
+                    .flat_map(|list| list.locations.iter())
+            })
+            .collect();
+        ResolvedRelayExclusions {
+            exclusions,
+            locations: ResolvedLocationConstraint(locations),
+        }
+    }
+}
+
+/// A relay matches if it is excluded.
+impl Match<Relay> for ResolvedRelayExclusions<'_> {
+    fn matches(&self, relay: &Relay) -> bool {
+        self.exclusions.hostnames.contains(&relay.hostname)
+            || self.exclusions.providers.contains(&relay.provider)
+            || self.locations.matches(relay)
+    }
+}
@@ -1,13 +1,20 @@
 //! The implementation of the relay selector.
 
 pub mod detailer;
+pub mod health;
 mod helpers;
+pub mod latency;
 pub mod matcher;
 mod parsed_relays;
 pub mod query;
 pub mod relays;
 
-use matcher::{filter_matching_bridges, filter_matching_relay_list};
+use health::RelayHealth;
+use latency::{LatencyCache, LatencyProber};
+use matcher::{
+    eliminating_rejection, explain_relay_list, filter_matching_bridges, filter_matching_relay_list,
+    RelayExplanation, RelayRejection,
+};
 use parsed_relays::ParsedRelays;
 use relays::{Multihop, Singlehop, WireguardConfig};
 
@@ -33,12 +40,15 @@ use mullvad_types::{
     constraints::Constraint,
     custom_list::CustomListsSettings,
     endpoint::MullvadWireguardEndpoint,
+    learned_obfuscation::ObfuscationMethod,
     location::{Coordinates, Location},
     relay_constraints::{
         BridgeSettings, BridgeState, InternalBridgeConstraints, ObfuscationSettings,
-        OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySettings, ResolvedBridgeSettings,
+        OpenVpnConstraints, RelayConstraints, RelayExclusions, RelayOverride,
+        RelaySelectionStrategy, RelaySettings, ResolvedBridgeSettings, SelectedObfuscation,
         WireguardConstraints,
     },
+    relay_health::RelayFailure,
     relay_list::{Relay, RelayEndpointData, RelayList},
     settings::Settings,
     wireguard::QuantumResistantState,
@@ -112,6 +122,14 @@ pub static OPENVPN_RETRY_ORDER: LazyLock<Vec<RelayQuery>> = LazyLock::new(|| {
 pub struct RelaySelector {
     config: Arc<Mutex<SelectorConfig>>,
     parsed_relays: Arc<Mutex<ParsedRelays>>,
+    latencies: Arc<Mutex<LatencyCache>>,
+    /// Relays which recently failed to connect, and should be avoided for a while.
+    health: Arc<Mutex<RelayHealth>>,
+    /// The last known location of the device, used to resolve
+    /// [nearest][`NearestLocationConstraint`] location constraints.
+    ///
+    /// [`NearestLocationConstraint`]: mullvad_types::relay_constraints::NearestLocationConstraint
+    device_location: Arc<Mutex<Option<Coordinates>>>,
 }
 
 #[derive(Clone)]
@@ -121,6 +139,7 @@ pub struct SelectorConfig {
     pub additional_constraints: AdditionalRelayConstraints,
     pub custom_lists: CustomListsSettings,
     pub relay_overrides: Vec<RelayOverride>,
+    pub relay_selection_strategy: RelaySelectionStrategy,
     // Wireguard specific data
     pub obfuscation_settings: ObfuscationSettings,
     // OpenVPN specific data
@@ -158,6 +177,26 @@ impl SelectorConfig {
             obfuscation_settings: settings.obfuscation_settings.clone(),
             custom_lists: settings.custom_lists.clone(),
             relay_overrides: settings.relay_overrides.clone(),
+            relay_selection_strategy: settings.relay_selection_strategy,
+        }
+    }
+}
+
+impl SelectorConfig {
+    /// Use `device_location` as the origin of all [nearest][`NearestLocationConstraint`] location
+    /// constraints that do not specify an explicit origin.
+    ///
+    /// [`NearestLocationConstraint`]: mullvad_types::relay_constraints::NearestLocationConstraint
+    fn set_device_location(&mut self, device_location: Coordinates) {
+        let mut locations = vec![&mut self.bridge_settings.normal.location];
+        if let RelaySettings::Normal(constraints) = &mut self.relay_settings {
+            locations.push(&mut constraints.location);
+            locations.push(&mut constraints.wireguard_constraints.entry_location);
+        }
+        for location in locations {
+            if let Constraint::Only(location) = location {
+                location.set_device_location(device_location);
+            }
         }
     }
 }
@@ -307,6 +346,30 @@ pub struct SelectedObfuscator {
     pub relay: Relay,
 }
 
+/// The return type of [`RelaySelector::explain_relay_selection`].
+#[derive(Clone, Debug)]
+pub struct RelaySelectionExplanation {
+    /// The query used to select relays, i.e. the user's constraints merged with the default
+    /// constraints of the retry attempt.
+    pub query: RelayQuery,
+    /// Why each relay was or was not considered as the exit relay.
+    pub exit: Vec<RelayExplanation>,
+    /// Why each relay was or was not considered as the entry relay, if multihop is used.
+    pub entry: Option<Vec<RelayExplanation>>,
+}
+
+impl RelaySelectionExplanation {
+    /// If no exit or entry relay matched, returns the constraint that eliminated the last
+    /// candidates, and whether it eliminated the entry relays.
+    pub fn eliminating_rejection(&self) -> Option<(RelayRejection, bool)> {
+        if let Some(rejection) = eliminating_rejection(&self.exit) {
+            return Some((rejection, false));
+        }
+        let rejection = eliminating_rejection(self.entry.as_deref()?)?;
+        Some((rejection, true))
+    }
+}
+
 impl Default for SelectorConfig {
     fn default() -> Self {
         let default_settings = Settings::default();
@@ -318,6 +381,7 @@ impl Default for SelectorConfig {
             bridge_state: default_settings.bridge_state,
             custom_lists: default_settings.custom_lists,
             relay_overrides: default_settings.relay_overrides,
+            relay_selection_strategy: default_settings.relay_selection_strategy,
         }
     }
 }
@@ -431,6 +495,7 @@ impl<'a> TryFrom<NormalSelectorConfig<'a>> for RelayQuery {
             value.user_preferences.tunnel_protocol,
             wireguard_constraints,
             openvpn_constraints,
+            value.user_preferences.exclusions.clone(),
         )
     }
 }
@@ -462,6 +527,9 @@ impl RelaySelector {
         RelaySelector {
             config: Arc::new(Mutex::new(config)),
             parsed_relays: Arc::new(Mutex::new(unsynchronized_parsed_relays)),
+            latencies: Arc::new(Mutex::new(LatencyCache::default())),
+            health: Arc::new(Mutex::new(RelayHealth::default())),
+            device_location: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -473,6 +541,9 @@ impl RelaySelector {
                 &config.relay_overrides,
             ))),
             config: Arc::new(Mutex::new(config)),
+            latencies: Arc::new(Mutex::new(LatencyCache::default())),
+            health: Arc::new(Mutex::new(RelayHealth::default())),
+            device_location: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -482,6 +553,26 @@ impl RelaySelector {
         *config_mutex = config;
     }
 
+    /// Set the location of the device, which is used by [nearest][`NearestLocationConstraint`]
+    /// location constraints that do not specify an explicit origin.
+    ///
+    /// [`NearestLocationConstraint`]: mullvad_types::relay_constraints::NearestLocationConstraint
+    pub fn set_device_location(&self, location: Option<Coordinates>) {
+        *self.device_location.lock().unwrap() = location;
+    }
+
+    /// Returns the current config, with the origin of any [nearest][`NearestLocationConstraint`]
+    /// location constraints resolved to the last known location of the device.
+    ///
+    /// [`NearestLocationConstraint`]: mullvad_types::relay_constraints::NearestLocationConstraint
+    fn resolved_config(&self) -> SelectorConfig {
+        let mut config = self.config.lock().unwrap().clone();
+        if let Some(device_location) = *self.device_location.lock().unwrap() {
+            config.set_device_location(device_location);
+        }
+        config
+    }
+
     pub fn set_relays(&self, relays: RelayList) {
         let mut parsed_relays = self.parsed_relays.lock().unwrap();
         parsed_relays.update(relays);
@@ -499,6 +590,122 @@ impl RelaySelector {
         parsed_relays.original_list().clone()
     }
 
+    /// Measure the round-trip time to the relays matching the current constraints using
+    /// `prober`, skipping relays which have been measured recently. This does nothing unless the
+    /// [fastest][RelaySelectionStrategy::Fastest] relay selection strategy is in use.
+    pub fn update_latencies(&self, prober: &impl LatencyProber) {
+        let candidates = {
+            let config = self.resolved_config();
+            if config.relay_selection_strategy != RelaySelectionStrategy::Fastest {
+                return;
+            }
+            let SpecializedSelectorConfig::Normal(normal_config) =
+                SpecializedSelectorConfig::from(&config)
+            else {
+                return;
+            };
+            let Ok(query) = RelayQuery::try_from(normal_config.clone()) else {
+                return;
+            };
+            let relay_list = self.parsed_relays.lock().unwrap().parsed_list().clone();
+            Self::latency_candidates(&query, &relay_list, normal_config.custom_lists)
+        };
+
+        let stale_relays = {
+            let latencies = self.latencies.lock().unwrap();
+            candidates
+                .into_iter()
+                .filter(|relay| latencies.get(&relay.hostname).is_none())
+                .collect_vec()
+        };
+        if stale_relays.is_empty() {
+            return;
+        }
+
+        log::debug!("Measuring latency to {} relays", stale_relays.len());
+        let measurements = prober.probe(&stale_relays);
+        log::debug!("Received {} latency measurements", measurements.len());
+
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies.prune();
+        for (hostname, latency) in measurements {
+            latencies.insert(hostname, latency);
+        }
+    }
+
+    /// Forget all latency measurements.
+    pub fn clear_latencies(&self) {
+        self.latencies.lock().unwrap().clear();
+    }
+
+    /// Register that connecting to the relay `hostname` failed. The relay will be avoided for a
+    /// while, see [`health`] for details.
+    pub fn record_connection_failure(&self, hostname: &str) {
+        log::debug!("Avoiding relay {hostname} after failing to connect");
+        self.health.lock().unwrap().record_failure(hostname);
+    }
+
+    /// Register that connecting to the relay `hostname` succeeded, which forgets all previous
+    /// failures.
+    pub fn record_connection_success(&self, hostname: &str) {
+        self.health.lock().unwrap().record_success(hostname);
+    }
+
+    /// Returns all relays that have recently failed to connect.
+    pub fn relay_failures(&self) -> Vec<RelayFailure> {
+        let mut health = self.health.lock().unwrap();
+        health.prune();
+        health.failures()
+    }
+
+    /// Forget all connection failures.
+    pub fn clear_relay_failures(&self) {
+        self.health.lock().unwrap().clear();
+    }
+
+    /// Returns the relays which the client may connect to directly given `query`, i.e. the relays
+    /// whose latency is relevant for the [fastest][RelaySelectionStrategy::Fastest] strategy.
+    fn latency_candidates(
+        query: &RelayQuery,
+        parsed_relays: &RelayList,
+        custom_lists: &CustomListsSettings,
+    ) -> Vec<Relay> {
+        let mut candidates = filter_matching_relay_list(query, parsed_relays, custom_lists);
+        if query.tunnel_protocol() == TunnelType::Wireguard && !query.singlehop() {
+            let mut entry_query = query.clone();
+            if entry_query
+                .set_location(query.wireguard_constraints().entry_location.clone())
+                .is_ok()
+            {
+                candidates.extend(filter_matching_relay_list(
+                    &entry_query,
+                    parsed_relays,
+                    custom_lists,
+                ));
+            }
+        }
+        candidates
+            .into_iter()
+            .unique_by(|relay| relay.hostname.clone())
+            .collect()
+    }
+
+    /// Returns the relay list to select relays from. If the
+    /// [fastest][RelaySelectionStrategy::Fastest] strategy is in use, relay weights are derived
+    /// from the measured latencies. Relays which recently failed to connect are given a weight of
+    /// 0.
+    fn relay_list(&self, strategy: RelaySelectionStrategy) -> RelayList {
+        let mut relay_list = self.parsed_relays.lock().unwrap().parsed_list().clone();
+        if strategy == RelaySelectionStrategy::Fastest {
+            self.latencies
+                .lock()
+                .unwrap()
+                .apply_weights(&mut relay_list);
+        }
+        self.health.lock().unwrap().apply_penalties(&mut relay_list);
+        relay_list
+    }
+
     pub fn etag(&self) -> Option<String> {
         self.parsed_relays.lock().unwrap().etag()
     }
@@ -511,8 +718,8 @@ impl RelaySelector {
     /// state.
     pub fn get_bridge_forced(&self) -> Option<Shadowsocks> {
         let parsed_relays = &self.parsed_relays.lock().unwrap().parsed_list().clone();
-        let config = self.config.lock().unwrap();
-        let specialized_config = SpecializedSelectorConfig::from(&*config);
+        let config = self.resolved_config();
+        let specialized_config = SpecializedSelectorConfig::from(&config);
 
         let near_location = match specialized_config {
             SpecializedSelectorConfig::Normal(config) => RelayQuery::try_from(config.clone())
@@ -523,6 +730,10 @@ impl RelaySelector {
             SpecializedSelectorConfig::Custom(_) => None,
         };
 
+        let exclusions = match &config.relay_settings {
+            RelaySettings::Normal(constraints) => constraints.exclusions.clone(),
+            RelaySettings::CustomTunnelEndpoint(_) => RelayExclusions::default(),
+        };
         let bridge_settings = &config.bridge_settings;
         let constraints = match bridge_settings.resolve() {
             Ok(ResolvedBridgeSettings::Normal(settings)) => InternalBridgeConstraints {
@@ -530,12 +741,14 @@ impl RelaySelector {
                 providers: settings.providers.clone(),
                 ownership: settings.ownership,
                 transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+                exclusions,
             },
             _ => InternalBridgeConstraints {
                 location: Constraint::Any,
                 providers: Constraint::Any,
                 ownership: Constraint::Any,
                 transport_protocol: Constraint::Only(TransportProtocol::Tcp),
+                exclusions,
             },
         };
 
@@ -547,15 +760,18 @@ impl RelaySelector {
     }
 
     /// Returns random relay and relay endpoint matching `query`.
-    pub fn get_relay_by_query(&self, query: RelayQuery) -> Result<GetRelay, Error> {
-        let config_guard = self.config.lock().unwrap();
-        let config = SpecializedSelectorConfig::from(&*config_guard);
+    pub fn get_relay_by_query(&self, mut query: RelayQuery) -> Result<GetRelay, Error> {
+        if let Some(device_location) = *self.device_location.lock().unwrap() {
+            query.set_device_location(device_location);
+        }
+        let selector_config = self.resolved_config();
+        let config = SpecializedSelectorConfig::from(&selector_config);
         match config {
             SpecializedSelectorConfig::Custom(custom_config) => {
                 Ok(GetRelay::Custom(custom_config.clone()))
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
-                let relay_list = &self.parsed_relays.lock().unwrap().parsed_list().clone();
+                let relay_list = &self.relay_list(selector_config.relay_selection_strategy);
                 Self::get_relay_inner(&query, relay_list, normal_config.custom_lists)
             }
         }
@@ -569,32 +785,133 @@ impl RelaySelector {
         retry_attempt: usize,
         runtime_params: RuntimeParameters,
     ) -> Result<GetRelay, Error> {
-        let config_guard = self.config.lock().unwrap();
-        let config = SpecializedSelectorConfig::from(&*config_guard);
+        self.get_relay_starting_at(retry_attempt, None, runtime_params)
+            .map(|(relay, _method)| relay)
+    }
+
+    /// Like [`RelaySelector::get_relay`], but [`WIREGUARD_RETRY_ORDER`] starts at the entry which
+    /// requires the obfuscation method `start`, such as a method which is known to work on the
+    /// current network. The order of the remaining entries is preserved.
+    ///
+    /// Also returns the obfuscation method required by the retry order entry that was used. This
+    /// is only done, and `start` is only considered, if WireGuard is used and obfuscation is set to
+    /// [`SelectedObfuscation::Auto`].
+    pub fn get_relay_starting_at(
+        &self,
+        retry_attempt: usize,
+        start: Option<ObfuscationMethod>,
+        runtime_params: RuntimeParameters,
+    ) -> Result<(GetRelay, Option<ObfuscationMethod>), Error> {
+        let selector_config = self.resolved_config();
+        let config = SpecializedSelectorConfig::from(&selector_config);
         match config {
             SpecializedSelectorConfig::Custom(custom_config) => {
-                Ok(GetRelay::Custom(custom_config.clone()))
+                Ok((GetRelay::Custom(custom_config.clone()), None))
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
                 let tunnel_protocol = normal_config.user_preferences.tunnel_protocol;
-                drop(config_guard);
 
                 match tunnel_protocol {
-                    TunnelType::Wireguard => self.get_relay_with_custom_params(
-                        retry_attempt,
-                        &WIREGUARD_RETRY_ORDER,
-                        runtime_params,
-                    ),
-                    TunnelType::OpenVpn => self.get_relay_with_custom_params(
-                        retry_attempt,
-                        &OPENVPN_RETRY_ORDER,
-                        runtime_params,
-                    ),
+                    TunnelType::Wireguard => {
+                        let auto_obfuscation =
+                            normal_config.obfuscation_settings.selected_obfuscation
+                                == SelectedObfuscation::Auto;
+                        let mut retry_order = WIREGUARD_RETRY_ORDER.clone();
+                        if let Some(start) = start.filter(|_| auto_obfuscation) {
+                            if let Some(position) = retry_order.iter().position(|query| {
+                                query.wireguard_constraints().obfuscation_method() == start
+                            }) {
+                                retry_order.rotate_left(position);
+                            }
+                        }
+
+                        let relay_list = self.relay_list(selector_config.relay_selection_strategy);
+                        let (index, query) = Self::pick_and_merge_query(
+                            retry_attempt,
+                            &retry_order,
+                            runtime_params,
+                            &normal_config,
+                            &relay_list,
+                        )?;
+                        let relay =
+                            Self::get_relay_inner(&query, &relay_list, normal_config.custom_lists)?;
+                        let method = auto_obfuscation.then(|| {
+                            retry_order[index]
+                                .wireguard_constraints()
+                                .obfuscation_method()
+                        });
+                        Ok((relay, method))
+                    }
+                    TunnelType::OpenVpn => self
+                        .get_relay_with_custom_params(
+                            retry_attempt,
+                            &OPENVPN_RETRY_ORDER,
+                            runtime_params,
+                        )
+                        .map(|relay| (relay, None)),
                 }
             }
         }
     }
 
+    /// Explain which relays [`RelaySelector::get_relay`] would consider for `retry_attempt`, and
+    /// why the other relays are not considered. If no relays match any of the retry attempts, the
+    /// user's constraints are explained instead. Bridges and obfuscators are not explained.
+    ///
+    /// Returns `None` if a custom tunnel endpoint is used.
+    pub fn explain_relay_selection(
+        &self,
+        retry_attempt: usize,
+        runtime_params: RuntimeParameters,
+    ) -> Result<Option<RelaySelectionExplanation>, Error> {
+        let selector_config = self.resolved_config();
+        let SpecializedSelectorConfig::Normal(normal_config) =
+            SpecializedSelectorConfig::from(&selector_config)
+        else {
+            return Ok(None);
+        };
+        let retry_order: &[RelayQuery] = match normal_config.user_preferences.tunnel_protocol {
+            TunnelType::Wireguard => &WIREGUARD_RETRY_ORDER,
+            TunnelType::OpenVpn => &OPENVPN_RETRY_ORDER,
+        };
+        let relay_list = self.relay_list(selector_config.relay_selection_strategy);
+        let query = match Self::pick_and_merge_query(
+            retry_attempt,
+            retry_order,
+            runtime_params,
+            &normal_config,
+            &relay_list,
+        ) {
+            Ok((_index, query)) => query,
+            Err(Error::NoRelay) => RelayQuery::try_from(normal_config.clone())?,
+            Err(error) => return Err(error),
+        };
+
+        let custom_lists = normal_config.custom_lists;
+        let explanation = if query.tunnel_protocol() == TunnelType::Wireguard && !query.singlehop()
+        {
+            // Mirror the queries used by `get_wireguard_multihop_config`
+            let mut entry_query = query.clone();
+            entry_query.set_location(query.wireguard_constraints().entry_location.clone())?;
+            let mut exit_query = query.clone();
+            let mut wg_constraints = exit_query.wireguard_constraints().clone();
+            wg_constraints.daita = Constraint::Only(false);
+            exit_query.set_wireguard_constraints(wg_constraints)?;
+            RelaySelectionExplanation {
+                exit: explain_relay_list(&exit_query, &relay_list, custom_lists),
+                entry: Some(explain_relay_list(&entry_query, &relay_list, custom_lists)),
+                query,
+            }
+        } else {
+            RelaySelectionExplanation {
+                exit: explain_relay_list(&query, &relay_list, custom_lists),
+                entry: None,
+                query,
+            }
+        };
+        Ok(Some(explanation))
+    }
+
     /// Returns a random relay and relay endpoint matching the current constraints defined by
     /// `retry_order` corresponding to `retry_attempt`.
     pub fn get_relay_with_custom_params(
@@ -603,8 +920,8 @@ impl RelaySelector {
         retry_order: &[RelayQuery],
         runtime_params: RuntimeParameters,
     ) -> Result<GetRelay, Error> {
-        let config_guard = self.config.lock().unwrap();
-        let config = SpecializedSelectorConfig::from(&*config_guard);
+        let selector_config = self.resolved_config();
+        let config = SpecializedSelectorConfig::from(&selector_config);
 
         // Short-circuit if a custom tunnel endpoint is to be used - don't have to involve the
         // relay selector further!
@@ -613,9 +930,9 @@ impl RelaySelector {
                 Ok(GetRelay::Custom(custom_config.clone()))
             }
             SpecializedSelectorConfig::Normal(normal_config) => {
-                let relay_list = self.parsed_relays.lock().unwrap().parsed_list().clone();
+                let relay_list = self.relay_list(selector_config.relay_selection_strategy);
                 // Merge user preferences with the relay selector's default preferences.
-                let query = Self::pick_and_merge_query(
+                let (_index, query) = Self::pick_and_merge_query(
                     retry_attempt,
                     retry_order,
                     runtime_params,
@@ -638,6 +955,9 @@ impl RelaySelector {
     /// queries which rely on IPv6 will not be considered if working IPv6 is not available at
     /// runtime.
     ///
+    /// Returns the merged query along with the index of the query in `retry_order` that it was
+    /// derived from.
+    ///
     /// Returns an error iff the intersection between the user's preferences and every default retry
     /// attempt-query yields queries with no matching relays. I.e., no retry attempt could ever
     /// resolve to a relay.
@@ -647,16 +967,17 @@ impl RelaySelector {
         runtime_params: RuntimeParameters,
         user_config: &NormalSelectorConfig<'_>,
         parsed_relays: &RelayList,
-    ) -> Result<RelayQuery, Error> {
+    ) -> Result<(usize, RelayQuery), Error> {
         let user_query = RelayQuery::try_from(user_config.clone())?;
         log::trace!("Merging user preferences {user_query:?} with default retry strategy");
         retry_order
             .iter()
+            .enumerate()
             // Remove candidate queries based on runtime parameters before trying to merge user
             // settings
-            .filter(|query| runtime_params.compatible(query))
-            .filter_map(|query| query.clone().intersection(user_query.clone()))
-            .filter(|query| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists).is_ok())
+            .filter(|(_, query)| runtime_params.compatible(query))
+            .filter_map(|(index, query)| Some((index, query.clone().intersection(user_query.clone())?)))
+            .filter(|(_, query)| Self::get_relay_inner(query, parsed_relays, user_config.custom_lists).is_ok())
             .cycle() // If the above filters remove all relays, cycle will also return an empty iterator
             .nth(retry_attempt)
             .ok_or(Error::NoRelay)
@@ -955,6 +1276,14 @@ impl RelaySelector {
 
                 Ok(Some(obfuscation))
             }
+            ObfuscationQuery::Quic => helpers::get_quic_obfuscator(obfuscator_relay, endpoint)
+                .map(Some)
+                .map_err(box_obfsucation_error),
+            ObfuscationQuery::Generic(settings) => {
+                helpers::get_generic_obfuscator(settings, obfuscator_relay, endpoint)
+                    .map(Some)
+                    .map_err(box_obfsucation_error)
+            }
         }
     }
 
@@ -1052,6 +1381,7 @@ impl RelaySelector {
                 TransportProtocol::Tcp => {
                     Self::get_bridge_for(
                         bridge_query,
+                        query.exclusions(),
                         &relay.location,
                         // FIXME: This is temporary while talpid-core only supports TCP proxies
                         TransportProtocol::Tcp,
@@ -1065,6 +1395,7 @@ impl RelaySelector {
 
     fn get_bridge_for(
         query: &BridgeQuery,
+        exclusions: &RelayExclusions,
         location: &Location,
         transport_protocol: TransportProtocol,
         parsed_relays: &RelayList,
@@ -1077,6 +1408,7 @@ impl RelaySelector {
                     providers: settings.providers.clone(),
                     ownership: settings.ownership,
                     transport_protocol: Constraint::Only(transport_protocol),
+                    exclusions: exclusions.clone(),
                 };
 
                 let (settings, relay) = Self::get_proxy_settings(
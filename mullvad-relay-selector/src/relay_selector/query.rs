@@ -31,16 +31,18 @@
 use crate::Error;
 use mullvad_types::{
     constraints::Constraint,
+    learned_obfuscation::ObfuscationMethod,
+    location::Coordinates,
     relay_constraints::{
-        BridgeConstraints, BridgeSettings, BridgeState, BridgeType, LocationConstraint,
-        ObfuscationSettings, OpenVpnConstraints, Ownership, Providers, RelayConstraints,
-        RelaySettings, SelectedObfuscation, ShadowsocksSettings, TransportPort,
-        Udp2TcpObfuscationSettings, WireguardConstraints,
+        BridgeConstraints, BridgeSettings, BridgeState, BridgeType, GenericObfuscationSettings,
+        LocationConstraint, ObfuscationSettings, OpenVpnConstraints, Ownership, Providers,
+        RelayConstraints, RelayExclusions, RelaySettings, SelectedObfuscation, ShadowsocksSettings,
+        TransportPort, Udp2TcpObfuscationSettings, WireguardConstraints,
     },
     wireguard::QuantumResistantState,
     Intersection,
 };
-use talpid_types::net::{proxy::CustomProxy, IpVersion, TunnelType};
+use talpid_types::net::{proxy::CustomProxy, IpVersion, ObfuscationType, TunnelType};
 
 /// Represents a query for a relay based on various constraints.
 ///
@@ -82,6 +84,7 @@ pub struct RelayQuery {
     tunnel_protocol: TunnelType,
     wireguard_constraints: WireguardRelayQuery,
     openvpn_constraints: OpenVpnRelayQuery,
+    exclusions: RelayExclusions,
 }
 
 impl RelayQuery {
@@ -93,6 +96,7 @@ impl RelayQuery {
         tunnel_protocol: TunnelType,
         wireguard_constraints: WireguardRelayQuery,
         openvpn_constraints: OpenVpnRelayQuery,
+        exclusions: RelayExclusions,
     ) -> Result<RelayQuery, Error> {
         let mut query = RelayQuery {
             location,
@@ -101,6 +105,7 @@ impl RelayQuery {
             tunnel_protocol,
             wireguard_constraints,
             openvpn_constraints,
+            exclusions,
         };
         query.validate()?;
         Ok(query)
@@ -131,6 +136,25 @@ impl RelayQuery {
         self.set_if_valid(|query| query.location = location)
     }
 
+    /// Use `device_location` as the origin of all [nearest][`NearestLocationConstraint`] location
+    /// constraints that do not specify an explicit origin.
+    ///
+    /// [`NearestLocationConstraint`]: mullvad_types::relay_constraints::NearestLocationConstraint
+    pub fn set_device_location(&mut self, device_location: Coordinates) {
+        let mut locations = vec![
+            &mut self.location,
+            &mut self.wireguard_constraints.entry_location,
+        ];
+        if let BridgeQuery::Normal(bridge) = &mut self.openvpn_constraints.bridge_settings {
+            locations.push(&mut bridge.location);
+        }
+        for location in locations {
+            if let Constraint::Only(location) = location {
+                location.set_device_location(device_location);
+            }
+        }
+    }
+
     pub fn providers(&self) -> &Constraint<Providers> {
         &self.providers
     }
@@ -139,6 +163,14 @@ impl RelayQuery {
         self.ownership
     }
 
+    pub fn exclusions(&self) -> &RelayExclusions {
+        &self.exclusions
+    }
+
+    pub fn set_exclusions(&mut self, exclusions: RelayExclusions) {
+        self.exclusions = exclusions;
+    }
+
     pub fn tunnel_protocol(&self) -> TunnelType {
         self.tunnel_protocol
     }
@@ -217,6 +249,7 @@ impl RelayQuery {
             tunnel_protocol: self.tunnel_protocol,
             wireguard_constraints: self.wireguard_constraints.into_constraints(),
             openvpn_constraints: self.openvpn_constraints.into_constraints(),
+            exclusions: self.exclusions,
         };
 
         (constraints, bridge_state, bridge_settings, obfuscation)
@@ -245,6 +278,7 @@ impl Default for RelayQuery {
             tunnel_protocol: TunnelType::default(),
             wireguard_constraints: WireguardRelayQuery::new(),
             openvpn_constraints: OpenVpnRelayQuery::new(),
+            exclusions: RelayExclusions::default(),
         }
     }
 }
@@ -284,6 +318,8 @@ pub enum ObfuscationQuery {
     Auto,
     Udp2tcp(Udp2TcpObfuscationSettings),
     Shadowsocks(ShadowsocksSettings),
+    Quic,
+    Generic(GenericObfuscationSettings),
 }
 
 impl ObfuscationQuery {
@@ -307,6 +343,15 @@ impl ObfuscationQuery {
                 shadowsocks: settings,
                 ..Default::default()
             },
+            ObfuscationQuery::Quic => ObfuscationSettings {
+                selected_obfuscation: SelectedObfuscation::Quic,
+                ..Default::default()
+            },
+            ObfuscationQuery::Generic(settings) => ObfuscationSettings {
+                selected_obfuscation: SelectedObfuscation::Generic,
+                generic: settings,
+                ..Default::default()
+            },
         }
     }
 }
@@ -324,6 +369,8 @@ impl From<ObfuscationSettings> for ObfuscationQuery {
             SelectedObfuscation::Shadowsocks => {
                 ObfuscationQuery::Shadowsocks(obfuscation.shadowsocks)
             }
+            SelectedObfuscation::Quic => ObfuscationQuery::Quic,
+            SelectedObfuscation::Generic => ObfuscationQuery::Generic(obfuscation.generic),
         }
     }
 }
@@ -339,6 +386,10 @@ impl Intersection for ObfuscationQuery {
             (ObfuscationQuery::Shadowsocks(a), ObfuscationQuery::Shadowsocks(b)) => {
                 Some(ObfuscationQuery::Shadowsocks(a.intersection(b)?))
             }
+            (ObfuscationQuery::Quic, ObfuscationQuery::Quic) => Some(ObfuscationQuery::Quic),
+            (ObfuscationQuery::Generic(a), ObfuscationQuery::Generic(b)) if a == b => {
+                Some(ObfuscationQuery::Generic(a))
+            }
             _ => None,
         }
     }
@@ -348,6 +399,29 @@ impl WireguardRelayQuery {
     pub fn multihop(&self) -> bool {
         matches!(self.use_multihop, Constraint::Only(true))
     }
+
+    /// The obfuscation, port and IP version required by this query. The port is that of the
+    /// obfuscation endpoint if the query uses obfuscation, and that of the WireGuard endpoint
+    /// otherwise.
+    pub fn obfuscation_method(&self) -> ObfuscationMethod {
+        let (obfuscation, port) = match &self.obfuscation {
+            ObfuscationQuery::Off | ObfuscationQuery::Auto => (None, self.port),
+            ObfuscationQuery::Udp2tcp(settings) => (Some(ObfuscationType::Udp2Tcp), settings.port),
+            ObfuscationQuery::Shadowsocks(settings) => {
+                (Some(ObfuscationType::Shadowsocks), settings.port)
+            }
+            ObfuscationQuery::Quic => (Some(ObfuscationType::Quic), Constraint::Any),
+            ObfuscationQuery::Generic(settings) => (
+                Some(ObfuscationType::Generic),
+                Constraint::Only(settings.port),
+            ),
+        };
+        ObfuscationMethod {
+            obfuscation,
+            port: port.option(),
+            ip_version: self.ip_version.option(),
+        }
+    }
 }
 
 impl WireguardRelayQuery {
@@ -519,7 +593,7 @@ pub mod builder {
 
     // Re-exports
     pub use mullvad_types::relay_constraints::{
-        GeographicLocationConstraint, Ownership, Providers,
+        GeographicLocationConstraint, Ownership, Providers, RelayExclusion,
     };
     pub use talpid_types::net::{IpVersion, TransportProtocol};
 
@@ -540,6 +614,10 @@ pub mod builder {
     ///  in the final `RelayQuery` is `Constraint::Any`.
     pub struct Any;
 
+    /// QUIC obfuscation has no options which can be configured, so it is represented by a marker
+    /// type.
+    pub struct Quic;
+
     // This impl-block is quantified over all configurations, e.g. [`Any`],
     // [`WireguardRelayQuery`] & [`OpenVpnRelayQuery`]
     impl<VpnProtocol> RelayQueryBuilder<VpnProtocol> {
@@ -561,6 +639,12 @@ pub mod builder {
             self
         }
 
+        /// Never select a relay matching `exclusion`.
+        pub fn exclude(mut self, exclusion: RelayExclusion) -> Self {
+            self.query.exclusions.insert(exclusion);
+            self
+        }
+
         /// Assemble the final [`RelayQuery`] that has been configured
         /// through `self`.
         pub fn build(mut self) -> RelayQuery {
@@ -791,6 +875,23 @@ pub mod builder {
                 protocol,
             }
         }
+
+        /// Enable QUIC obfuscation.
+        pub fn quic(
+            mut self,
+        ) -> RelayQueryBuilder<Wireguard<Multihop, Quic, Daita, QuantumResistant>> {
+            let protocol = Wireguard {
+                multihop: self.protocol.multihop,
+                obfuscation: Quic,
+                daita: self.protocol.daita,
+                quantum_resistant: self.protocol.quantum_resistant,
+            };
+            self.query.wireguard_constraints.obfuscation = ObfuscationQuery::Quic;
+            RelayQueryBuilder {
+                query: self.query,
+                protocol,
+            }
+        }
     }
 
     impl<Multihop, Daita, QuantumResistant>
@@ -1046,6 +1147,7 @@ mod test {
                 shadowsocks: ShadowsocksSettings {
                     port: port2,
                 },
+                ..Default::default()
             });
             assert_eq!(query, ObfuscationQuery::Auto);
         }
@@ -2,8 +2,9 @@
 
 use std::{
     collections::HashSet,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    sync::LazyLock,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{LazyLock, Mutex},
+    time::Duration,
 };
 use talpid_types::net::{
     obfuscation::ObfuscatorConfig,
@@ -14,22 +15,27 @@ use talpid_types::net::{
 };
 
 use mullvad_relay_selector::{
+    latency::LatencyProber,
+    matcher::{eliminating_rejection, explain_relay_list, RelayRejection},
     query::{builder::RelayQueryBuilder, BridgeQuery, ObfuscationQuery, OpenVpnRelayQuery},
     Error, GetRelay, RelaySelector, RuntimeParameters, SelectedObfuscator, SelectorConfig,
     WireguardConfig, OPENVPN_RETRY_ORDER, WIREGUARD_RETRY_ORDER,
 };
 use mullvad_types::{
     constraints::Constraint,
+    custom_list::{CustomList, CustomListsSettings},
     endpoint::MullvadEndpoint,
-    location::Location,
+    location::{Coordinates, Location},
     relay_constraints::{
-        BridgeConstraints, BridgeState, GeographicLocationConstraint, Ownership, Providers,
-        RelayConstraints, RelayOverride, RelaySettings, TransportPort,
+        BridgeConstraints, BridgeState, GenericObfuscationSettings, GeographicLocationConstraint,
+        LocationConstraint, NearestLocationConstraint, ObfuscationSettings, Ownership, Providers,
+        RelayConstraints, RelayExclusion, RelayOverride, RelaySelectionStrategy, RelaySettings,
+        SelectedObfuscation, TransportPort,
     },
     relay_list::{
-        BridgeEndpointData, OpenVpnEndpoint, OpenVpnEndpointData, Relay, RelayEndpointData,
-        RelayList, RelayListCity, RelayListCountry, ShadowsocksEndpointData, WireguardEndpointData,
-        WireguardRelayEndpointData,
+        BridgeEndpointData, OpenVpnEndpoint, OpenVpnEndpointData, QuicEndpointData, Relay,
+        RelayEndpointData, RelayList, RelayListCity, RelayListCountry, ShadowsocksEndpointData,
+        WireguardEndpointData, WireguardRelayEndpointData,
     },
 };
 
@@ -71,6 +77,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: true,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: None,
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -92,6 +99,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: false,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: None,
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -113,6 +121,7 @@ static RELAYS: LazyLock<RelayList> = LazyLock::new(|| RelayList {
                         .unwrap(),
                         daita: true,
                         shadowsocks_extra_addr_in: vec![],
+                        quic: None,
                     }),
                     location: DUMMY_LOCATION.clone(),
                 },
@@ -239,6 +248,7 @@ static SHADOWSOCKS_RELAY: LazyLock<Relay> = LazyLock::new(|| Relay {
         public_key: PublicKey::from_base64("eaNHNoGO88LjV/wDBa7CUUwUzPq/fO2UwcGLy56hKy4=").unwrap(),
         daita: false,
         shadowsocks_extra_addr_in: SHADOWSOCKS_RELAY_EXTRA_ADDRS.to_vec(),
+        quic: None,
     }),
     location: DUMMY_LOCATION.clone(),
 });
@@ -424,8 +434,10 @@ fn test_wireguard_retry_order() {
                 assert!(match &query.wireguard_constraints().obfuscation {
                     ObfuscationQuery::Auto => true,
                     ObfuscationQuery::Off => obfuscator.is_none(),
-                    ObfuscationQuery::Udp2tcp(_) | ObfuscationQuery::Shadowsocks(_) =>
-                        obfuscator.is_some(),
+                    ObfuscationQuery::Udp2tcp(_)
+                    | ObfuscationQuery::Shadowsocks(_)
+                    | ObfuscationQuery::Quic
+                    | ObfuscationQuery::Generic(_) => obfuscator.is_some(),
                 });
             }
             _ => unreachable!(),
@@ -433,6 +445,52 @@ fn test_wireguard_retry_order() {
     }
 }
 
+/// Test that [`WIREGUARD_RETRY_ORDER`] can start at a given obfuscation method, and that the method
+/// used by each retry attempt is reported, but only if obfuscation is set to auto.
+#[test]
+fn test_wireguard_retry_order_starting_at() {
+    const START: usize = 4;
+    let mut relay_selector = default_relay_selector();
+    let start = WIREGUARD_RETRY_ORDER[START]
+        .wireguard_constraints()
+        .obfuscation_method();
+
+    let expected_order = WIREGUARD_RETRY_ORDER
+        .iter()
+        .cycle()
+        .skip(START)
+        .take(WIREGUARD_RETRY_ORDER.len());
+    for (retry_attempt, query) in expected_order.enumerate() {
+        let (_relay, method) = relay_selector
+            .get_relay_starting_at(retry_attempt, Some(start), RuntimeParameters { ipv6: true })
+            .unwrap_or_else(|_| panic!("Retry attempt {retry_attempt} did not yield any relay"));
+        assert_eq!(
+            method,
+            Some(query.wireguard_constraints().obfuscation_method()),
+            "Retry attempt {retry_attempt} used an unexpected obfuscation method"
+        );
+    }
+
+    relay_selector.set_config(SelectorConfig {
+        obfuscation_settings: ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::Off,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let (relay, method) = relay_selector
+        .get_relay_starting_at(0, Some(start), RuntimeParameters { ipv6: true })
+        .unwrap();
+    assert_eq!(method, None);
+    assert!(matches!(
+        relay,
+        GetRelay::Wireguard {
+            obfuscator: None,
+            ..
+        }
+    ));
+}
+
 /// Test whether the relay selector seems to respect the order as defined by [`OPENVPN_RETRY_ORDER`].
 #[test]
 fn test_openvpn_retry_order() {
@@ -586,6 +644,7 @@ fn test_wireguard_entry() {
                             .unwrap(),
                             daita: false,
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                         }),
                         location: DUMMY_LOCATION.clone(),
                     },
@@ -607,6 +666,7 @@ fn test_wireguard_entry() {
                             .unwrap(),
                             daita: false,
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                         }),
                         location: DUMMY_LOCATION.clone(),
                     },
@@ -870,6 +930,70 @@ fn test_selecting_wireguard_over_shadowsocks() {
     }
 }
 
+/// Test that generic obfuscation connects to the configured port and transport on the address of
+/// the WireGuard relay, and that it is rejected when no protocol is set.
+#[test]
+fn test_selecting_wireguard_over_generic_obfuscation() {
+    let generic = GenericObfuscationSettings {
+        protocol: "udp2tcp".to_owned(),
+        port: 8443,
+        transport: Tcp,
+        parameters: [("mark".to_owned(), "1".to_owned())].into(),
+    };
+    let mut relay_selector = RelaySelector::from_list(
+        SelectorConfig {
+            obfuscation_settings: ObfuscationSettings {
+                selected_obfuscation: SelectedObfuscation::Generic,
+                generic: generic.clone(),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RELAYS.clone(),
+    );
+
+    let relay = relay_selector
+        .get_relay(0, RuntimeParameters { ipv6: false })
+        .unwrap();
+    match relay {
+        GetRelay::Wireguard {
+            endpoint,
+            obfuscator:
+                Some(SelectedObfuscator {
+                    config:
+                        ObfuscatorConfig::Generic {
+                            protocol,
+                            endpoint: obfuscator_endpoint,
+                            parameters,
+                        },
+                    ..
+                }),
+            ..
+        } => {
+            assert_eq!(protocol, generic.protocol);
+            assert_eq!(parameters, generic.parameters);
+            assert_eq!(
+                obfuscator_endpoint,
+                Endpoint::new(endpoint.peer.endpoint.ip(), 8443, Tcp)
+            );
+        }
+        wrong_relay => panic!(
+            "Relay selector should have picked a Wireguard relay with generic obfuscation, instead chose {wrong_relay:?}"
+        ),
+    }
+
+    relay_selector.set_config(SelectorConfig {
+        obfuscation_settings: ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::Generic,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    relay_selector
+        .get_relay(0, RuntimeParameters { ipv6: false })
+        .expect_err("Generic obfuscation without a protocol should be rejected");
+}
+
 /// Test whether extra Shadowsocks IPs are selected when available
 #[test]
 fn test_selecting_wireguard_over_shadowsocks_extra_ips() {
@@ -885,7 +1009,7 @@ fn test_selecting_wireguard_over_shadowsocks_extra_ips() {
     let relay = relay_selector.get_relay_by_query(query).unwrap();
     match relay {
         GetRelay::Wireguard {
-            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint }, .. }),
+            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint, .. }, .. }),
             inner: WireguardConfig::Singlehop { exit },
             ..
         } => {
@@ -929,7 +1053,7 @@ fn test_selecting_wireguard_ignore_extra_ips_override_v4() {
     let relay = relay_selector.get_relay_by_query(query_v4).unwrap();
     match relay {
         GetRelay::Wireguard {
-            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint }, .. }),
+            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint, .. }, .. }),
             inner: WireguardConfig::Singlehop { exit },
             ..
         } => {
@@ -973,7 +1097,7 @@ fn test_selecting_wireguard_ignore_extra_ips_override_v6() {
     let relay = relay_selector.get_relay_by_query(query_v6).unwrap();
     match relay {
         GetRelay::Wireguard {
-            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint }, .. }),
+            obfuscator: Some(SelectedObfuscator { config: ObfuscatorConfig::Shadowsocks { endpoint, .. }, .. }),
             inner: WireguardConfig::Singlehop { exit },
             ..
         } => {
@@ -987,6 +1111,62 @@ fn test_selecting_wireguard_ignore_extra_ips_override_v6() {
     }
 }
 
+/// Test that only relays running a QUIC proxy are selected when QUIC obfuscation is used
+#[test]
+fn test_selecting_wireguard_over_quic() {
+    const QUIC_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(185, 213, 154, 69));
+    const QUIC_HOSTNAME: &str = "se9-wireguard.example.net";
+    const QUIC_TOKEN: &str = "test";
+
+    let mut relay_list = RELAYS.clone();
+    let quic_relay = &mut relay_list.countries[0].cities[0].relays[0];
+    let RelayEndpointData::Wireguard(ref mut wg_data) = quic_relay.endpoint_data else {
+        panic!("expected WireGuard relay");
+    };
+    wg_data.quic = Some(QuicEndpointData {
+        addr_in: vec![QUIC_ADDR],
+        domain: QUIC_HOSTNAME.to_string(),
+        token: QUIC_TOKEN.to_string(),
+    });
+    let quic_hostname = quic_relay.hostname.clone();
+
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), relay_list);
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .ip_version(IpVersion::V4)
+        .quic()
+        .build();
+
+    for _ in 0..100 {
+        let relay = relay_selector.get_relay_by_query(query.clone()).unwrap();
+        match relay {
+            GetRelay::Wireguard {
+                obfuscator:
+                    Some(SelectedObfuscator {
+                        config:
+                            ObfuscatorConfig::Quic {
+                                endpoint,
+                                hostname,
+                                auth_token,
+                            },
+                        ..
+                    }),
+                inner: WireguardConfig::Singlehop { exit },
+                ..
+            } => {
+                assert_eq!(exit.hostname, quic_hostname);
+                assert_eq!(endpoint, SocketAddr::new(QUIC_ADDR, 443));
+                assert_eq!(hostname, QUIC_HOSTNAME);
+                assert_eq!(auth_token, QUIC_TOKEN);
+            }
+            wrong_relay => panic!(
+                "Relay selector should have picked a Wireguard relay with QUIC, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+}
+
 /// Construct a query for a Wireguard configuration where UDP2TCP obfuscation is selected and
 /// multihop is explicitly turned off. Assert that the relay selector always return an obfuscator
 /// configuration.
@@ -1247,6 +1427,7 @@ fn test_include_in_country() {
                             )
                             .unwrap(),
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                             daita: false,
                         }),
                         location: DUMMY_LOCATION.clone(),
@@ -1268,6 +1449,7 @@ fn test_include_in_country() {
                             )
                             .unwrap(),
                             shadowsocks_extra_addr_in: vec![],
+                            quic: None,
                             daita: false,
                         }),
                         location: DUMMY_LOCATION.clone(),
@@ -1671,3 +1853,343 @@ fn valid_user_setting_should_yield_relay() {
         }
     }
 }
+
+/// A [`LatencyProber`] which reports fixed latencies and keeps track of which relays it has been
+/// asked to probe.
+struct MockProber {
+    latencies: Vec<(&'static str, Duration)>,
+    probed: Mutex<Vec<String>>,
+}
+
+impl MockProber {
+    fn new(latencies: Vec<(&'static str, Duration)>) -> Self {
+        MockProber {
+            latencies,
+            probed: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl LatencyProber for MockProber {
+    fn probe(&self, relays: &[Relay]) -> Vec<(String, Duration)> {
+        let mut probed = self.probed.lock().unwrap();
+        probed.extend(relays.iter().map(|relay| relay.hostname.clone()));
+        self.latencies
+            .iter()
+            .filter(|(hostname, _)| relays.iter().any(|relay| relay.hostname == *hostname))
+            .map(|(hostname, latency)| (hostname.to_string(), *latency))
+            .collect()
+    }
+}
+
+/// Verify that the fastest relay selection strategy prefers the relay with the lowest latency,
+/// and that relays are only probed when needed.
+#[test]
+fn test_fastest_relay_selection_strategy() {
+    let config = SelectorConfig {
+        relay_selection_strategy: RelaySelectionStrategy::Fastest,
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+    let prober = MockProber::new(vec![
+        ("se9-wireguard", Duration::from_millis(5)),
+        ("se10-wireguard", Duration::from_secs(1)),
+    ]);
+
+    relay_selector.update_latencies(&prober);
+    let probed = prober.probed.lock().unwrap().clone();
+    assert!(probed.contains(&"se9-wireguard".to_string()));
+    assert!(
+        !probed.contains(&"se-got-br-001".to_string()),
+        "Bridges should not be probed"
+    );
+
+    // Fresh measurements should not be probed again, but unreachable relays should
+    relay_selector.update_latencies(&prober);
+    let probed_again = prober.probed.lock().unwrap()[probed.len()..].to_vec();
+    assert!(!probed_again.contains(&"se9-wireguard".to_string()));
+    assert!(!probed_again.contains(&"se10-wireguard".to_string()));
+    let unreachable: Vec<_> = probed
+        .iter()
+        .filter(|hostname| !["se9-wireguard", "se10-wireguard"].contains(&hostname.as_str()))
+        .collect();
+    assert!(!unreachable.is_empty());
+    assert!(unreachable
+        .iter()
+        .all(|hostname| probed_again.contains(hostname)));
+
+    const ATTEMPTS: usize = 100;
+    let fastest_count = (0..ATTEMPTS)
+        .map(|_| {
+            unwrap_relay(
+                relay_selector
+                    .get_relay(0, RuntimeParameters::default())
+                    .unwrap(),
+            )
+        })
+        .filter(|relay| relay.hostname == "se9-wireguard")
+        .count();
+    assert!(
+        fastest_count > ATTEMPTS * 9 / 10,
+        "Expected the fastest relay to be picked most of the time, was picked {fastest_count} times"
+    );
+
+    // Constraints must still be honored
+    let location = GeographicLocationConstraint::hostname("se", "got", "se10-wireguard");
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .location(location)
+        .build();
+    let relay = unwrap_relay(relay_selector.get_relay_by_query(query).unwrap());
+    assert_eq!(relay.hostname, "se10-wireguard");
+}
+
+/// Latencies should not be measured unless the fastest relay selection strategy is in use.
+#[test]
+fn test_random_relay_selection_strategy_does_not_probe() {
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+    let prober = MockProber::new(vec![]);
+    relay_selector.update_latencies(&prober);
+    assert!(prober.probed.lock().unwrap().is_empty());
+}
+
+/// Verify that the nearest location constraint selects relays based on their distance from the
+/// origin, or from the location of the device if no origin is given.
+#[test]
+fn test_nearest_location_constraint() {
+    const OSLO: Coordinates = Coordinates {
+        latitude: 59.91,
+        longitude: 10.75,
+    };
+    const GOTHENBURG: Coordinates = Coordinates {
+        latitude: 57.71,
+        longitude: 11.97,
+    };
+
+    // Add a WireGuard relay in Oslo, roughly 300 km from the relays in Gothenburg
+    let mut relay_list = RELAYS.clone();
+    let mut oslo_relay = relay_list.countries[0].cities[0].relays[0].clone();
+    oslo_relay.hostname = "no-osl-wg-001".to_string();
+    relay_list.countries.push(RelayListCountry {
+        name: "Norway".to_string(),
+        code: "no".to_string(),
+        cities: vec![RelayListCity {
+            name: "Oslo".to_string(),
+            code: "osl".to_string(),
+            latitude: OSLO.latitude,
+            longitude: OSLO.longitude,
+            relays: vec![oslo_relay],
+        }],
+    });
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), relay_list);
+
+    let nearest = |origin, radius| {
+        RelayQueryBuilder::new()
+            .wireguard()
+            .location(LocationConstraint::Nearest(NearestLocationConstraint {
+                origin,
+                radius,
+            }))
+            .build()
+    };
+
+    // Only the closest relays should be picked if there are none within the radius
+    for _ in 0..10 {
+        let relay = relay_selector
+            .get_relay_by_query(nearest(Some(OSLO), 0))
+            .map(unwrap_relay)
+            .unwrap();
+        assert_eq!(relay.hostname, "no-osl-wg-001");
+    }
+
+    // All relays within the radius should be considered
+    let hostnames: HashSet<_> = (0..100)
+        .map(|_| {
+            relay_selector
+                .get_relay_by_query(nearest(Some(OSLO), 1000))
+                .map(unwrap_relay)
+                .unwrap()
+                .hostname
+        })
+        .collect();
+    assert!(
+        hostnames.len() > 1,
+        "Expected relays in both cities to be picked"
+    );
+
+    // The location of the device should be used if no origin is given
+    relay_selector.set_device_location(Some(GOTHENBURG));
+    for _ in 0..10 {
+        let relay = relay_selector
+            .get_relay_by_query(nearest(None, 0))
+            .map(unwrap_relay)
+            .unwrap();
+        assert_eq!(relay.location.country_code, "se");
+    }
+}
+
+/// Verify that excluded hostnames, providers and custom lists are never selected, whether as exit
+/// relay or as bridge.
+#[test]
+fn test_relay_exclusions() {
+    let mut excluded_list = CustomList::new("excluded".to_string()).unwrap();
+    excluded_list
+        .locations
+        .insert(GeographicLocationConstraint::hostname(
+            "se",
+            "got",
+            "se11-wireguard",
+        ));
+    let excluded_list_id = excluded_list.id;
+    let config = SelectorConfig {
+        custom_lists: CustomListsSettings::from(vec![excluded_list]),
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+
+    // Only se11-wireguard is neither hosted by provider0 nor excluded by its hostname
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .exclude(RelayExclusion::Provider("provider0".to_string()))
+        .exclude(RelayExclusion::Hostname("se10-wireguard".to_string()))
+        .build();
+    for _ in 0..10 {
+        let relay = relay_selector
+            .get_relay_by_query(query.clone())
+            .map(unwrap_relay)
+            .unwrap();
+        assert_eq!(relay.hostname, "se11-wireguard");
+    }
+
+    // Excluding the custom list leaves no WireGuard relays to choose from
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .exclude(RelayExclusion::Provider("provider0".to_string()))
+        .exclude(RelayExclusion::Hostname("se10-wireguard".to_string()))
+        .exclude(RelayExclusion::CustomList(excluded_list_id))
+        .build();
+    relay_selector
+        .get_relay_by_query(query)
+        .expect_err("Expected all WireGuard relays to be excluded");
+
+    // Exclusions also apply to bridges
+    let query = RelayQueryBuilder::new().openvpn().bridge().build();
+    relay_selector
+        .get_relay_by_query(query)
+        .expect("Expected a relay and bridge to be selected");
+    let query = RelayQueryBuilder::new()
+        .openvpn()
+        .bridge()
+        .exclude(RelayExclusion::Hostname("se-got-br-001".to_string()))
+        .build();
+    relay_selector
+        .get_relay_by_query(query)
+        .expect_err("Expected the only bridge to be excluded");
+}
+
+/// Verify that relays which recently failed to connect are avoided, unless no other relay matches
+/// the constraints.
+#[test]
+fn test_failed_relays_are_avoided() {
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+    let query = RelayQueryBuilder::new().wireguard().build();
+
+    relay_selector.record_connection_failure("se9-wireguard");
+    relay_selector.record_connection_failure("se10-wireguard");
+    relay_selector.record_connection_failure("se1337-wireguard");
+    for _ in 0..10 {
+        let relay = relay_selector
+            .get_relay_by_query(query.clone())
+            .map(unwrap_relay)
+            .unwrap();
+        assert_eq!(relay.hostname, "se11-wireguard");
+    }
+
+    // A failed relay is still selected if every matching relay has failed
+    relay_selector.record_connection_failure("se11-wireguard");
+    relay_selector
+        .get_relay_by_query(query)
+        .expect("Expected a failed relay to be selected as a last resort");
+
+    relay_selector.record_connection_success("se9-wireguard");
+    let failures = relay_selector.relay_failures();
+    assert_eq!(
+        failures
+            .iter()
+            .map(|failure| failure.hostname.as_str())
+            .collect::<Vec<_>>(),
+        vec!["se10-wireguard", "se11-wireguard", "se1337-wireguard"]
+    );
+    assert!(failures
+        .iter()
+        .all(|failure| failure.failures == 1 && failure.penalized_until > failure.last_failure));
+
+    relay_selector.clear_relay_failures();
+    assert!(relay_selector.relay_failures().is_empty());
+}
+
+/// Verify that the explanation of a query agrees with the relays that are selected, and that each
+/// rejected relay is attributed to the first constraint it does not satisfy.
+#[test]
+fn test_explain_relay_list() {
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .location(GeographicLocationConstraint::city("se", "got"))
+        .exclude(RelayExclusion::Hostname("se10-wireguard".to_string()))
+        .build();
+    let explanation = explain_relay_list(&query, &RELAYS, &CustomListsSettings::default());
+    let rejection = |hostname: &str| {
+        explanation
+            .iter()
+            .find(|explanation| explanation.hostname == hostname)
+            .unwrap()
+            .rejection
+    };
+
+    assert_eq!(rejection("se9-wireguard"), None);
+    assert_eq!(rejection("se10-wireguard"), Some(RelayRejection::Excluded));
+    assert_eq!(rejection("se-got-001"), Some(RelayRejection::TunnelType));
+    assert_eq!(rejection("se-got-br-001"), Some(RelayRejection::TunnelType));
+
+    let relay_selector = RelaySelector::from_list(SelectorConfig::default(), RELAYS.clone());
+    let relay = unwrap_relay(relay_selector.get_relay_by_query(query).unwrap());
+    assert_eq!(rejection(&relay.hostname), None);
+
+    let explanation = relay_selector
+        .explain_relay_selection(0, RuntimeParameters::default())
+        .unwrap()
+        .expect("Expected relays to be explained");
+    assert!(explanation.entry.is_none());
+    assert!(explanation
+        .exit
+        .iter()
+        .any(|explanation| explanation.rejection.is_none()));
+    assert_eq!(explanation.eliminating_rejection(), None);
+}
+
+/// Verify that the constraint which eliminated the last matching relays is reported.
+#[test]
+fn test_eliminating_rejection() {
+    // The relays in Gothenburg that are hosted by provider0 are all excluded
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .location(GeographicLocationConstraint::city("se", "got"))
+        .providers(Providers::new(["provider0".to_string()]).unwrap())
+        .exclude(RelayExclusion::Provider("provider0".to_string()))
+        .build();
+    let explanation = explain_relay_list(&query, &RELAYS, &CustomListsSettings::default());
+    assert_eq!(
+        eliminating_rejection(&explanation),
+        Some(RelayRejection::Excluded)
+    );
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .providers(Providers::new(["nonexistent".to_string()]).unwrap())
+        .build();
+    let explanation = explain_relay_list(&query, &RELAYS, &CustomListsSettings::default());
+    assert_eq!(
+        eliminating_rejection(&explanation),
+        Some(RelayRejection::Providers)
+    );
+}
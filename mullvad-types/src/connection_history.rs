@@ -0,0 +1,39 @@
+//! History of tunnel sessions.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use talpid_types::net::{ObfuscationType, TunnelType};
+
+/// A tunnel session, from when the daemon started connecting until the tunnel went down. Account
+/// numbers and IP addresses are never recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionHistoryEntry {
+    pub start: DateTime<Utc>,
+    /// `None` if the session is ongoing.
+    pub end: Option<DateTime<Utc>>,
+    pub tunnel_type: TunnelType,
+    /// Hostname of the exit relay.
+    pub relay: Option<String>,
+    /// Hostname of the entry relay or bridge, if one was used.
+    pub entry_relay: Option<String>,
+    pub obfuscation: Option<ObfuscationType>,
+    /// Whether the tunnel was ever connected during the session.
+    pub connected: bool,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    /// `None` if the session is ongoing.
+    pub end_cause: Option<EndCause>,
+}
+
+/// Why a tunnel session ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndCause {
+    /// The tunnel was disconnected.
+    Disconnected,
+    /// The daemon started a new session, e.g. because a setting changed or the tunnel went down
+    /// after having connected. Failed attempts to connect are retried within the same session.
+    Reconnected,
+    /// The tunnel entered the error state. Contains the name of the cause, e.g. `is_offline`.
+    Error(String),
+}
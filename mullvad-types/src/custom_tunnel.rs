@@ -4,7 +4,10 @@ use std::{
     fmt, io,
     net::{IpAddr, SocketAddr, ToSocketAddrs},
 };
-use talpid_types::net::{openvpn, proxy::CustomProxy, wireguard, Endpoint, TunnelParameters};
+use talpid_types::net::{
+    obfuscation::ObfuscatorConfig, openvpn, proxy::CustomProxy, wireguard, Endpoint,
+    ObfuscationEndpoint, TunnelParameters,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,11 +22,36 @@ pub enum Error {
 pub struct CustomTunnelEndpoint {
     pub host: String,
     pub config: ConnectionConfig,
+    /// Preshared key of the first WireGuard peer. Unlike the ephemeral PSKs of
+    /// quantum-resistant tunnels, this key is provided by the user and is therefore persisted.
+    /// Quantum-resistant tunnels replace it with a negotiated key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub psk: Option<wireguard::PresharedKey>,
+    /// Preshared key of the exit peer of a multihop tunnel. Like `psk`, it is replaced with a
+    /// negotiated key in quantum-resistant tunnels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_psk: Option<wireguard::PresharedKey>,
+    /// Whether the WireGuard peers run the ephemeral peer service, which is required for
+    /// negotiating quantum-resistant keys. For multihop tunnels, the exit peer must be reachable
+    /// at the same `ipv4_gateway` as the entry peer.
+    #[serde(default)]
+    pub quantum_resistant: bool,
+    /// Obfuscation used to reach the first WireGuard peer, which must be running the server side
+    /// of the obfuscation protocol. Ignored for OpenVPN tunnels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscation: Option<ObfuscatorConfig>,
 }
 
 impl CustomTunnelEndpoint {
     pub fn new(host: String, config: ConnectionConfig) -> Self {
-        Self { host, config }
+        Self {
+            host,
+            config,
+            psk: None,
+            exit_psk: None,
+            quantum_resistant: false,
+            obfuscation: None,
+        }
     }
 
     pub fn endpoint(&self) -> Endpoint {
@@ -52,17 +80,30 @@ impl CustomTunnelEndpoint {
                 fwmark: crate::TUNNEL_FWMARK,
             }
             .into(),
-            ConnectionConfig::Wireguard(connection) => {
+            ConnectionConfig::Wireguard(mut connection) => {
+                connection.peer.psk = self.psk.clone();
+                if let Some(exit_peer) = &mut connection.exit_peer {
+                    exit_peer.psk = self.exit_psk.clone();
+                }
                 let mut options = tunnel_options.wireguard.into_talpid_tunnel_options();
-                if options.quantum_resistant {
+                if options.quantum_resistant && !self.quantum_resistant {
                     options.quantum_resistant = false;
-                    log::info!("Ignoring quantum resistant option for custom tunnel");
+                    log::info!(
+                        "Ignoring quantum resistant option for custom tunnel without ephemeral peer support"
+                    );
                 }
+                let obfuscation = self.obfuscation.clone().map(|mut obfuscation| {
+                    // Unlike on Mullvad relays, the WireGuard server may not be on the same host
+                    if let ObfuscatorConfig::Shadowsocks { target, .. } = &mut obfuscation {
+                        *target = Some(connection.peer.endpoint);
+                    }
+                    obfuscation
+                });
                 wireguard::TunnelParameters {
                     connection,
                     options,
                     generic_options: tunnel_options.generic,
-                    obfuscation: None,
+                    obfuscation,
                 }
                 .into()
             }
@@ -81,13 +122,29 @@ impl fmt::Display for CustomTunnelEndpoint {
                 config.endpoint.address.port(),
                 config.endpoint.protocol
             ),
-            ConnectionConfig::Wireguard(connection) => write!(
-                f,
-                "WireGuard relay - {}:{} with public key {}",
-                self.host,
-                connection.peer.endpoint.port(),
-                connection.peer.public_key
-            ),
+            ConnectionConfig::Wireguard(connection) => {
+                write!(
+                    f,
+                    "WireGuard relay - {}:{} with public key {}",
+                    self.host,
+                    connection.peer.endpoint.port(),
+                    connection.peer.public_key
+                )?;
+                if let Some(exit_peer) = &connection.exit_peer {
+                    write!(
+                        f,
+                        " via exit {} with public key {}",
+                        exit_peer.endpoint, exit_peer.public_key
+                    )?;
+                }
+                if let Some(obfuscation) = &self.obfuscation {
+                    write!(f, " over {}", ObfuscationEndpoint::from(obfuscation))?;
+                }
+                if self.quantum_resistant {
+                    write!(f, " (quantum resistant)")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -132,3 +189,131 @@ impl ConnectionConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wireguard::QuantumResistantState;
+
+    fn wireguard_endpoint() -> CustomTunnelEndpoint {
+        let private_key = wireguard::PrivateKey::new_from_random();
+        let public_key = private_key.public_key();
+        CustomTunnelEndpoint::new(
+            "192.0.2.1".to_owned(),
+            ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+                tunnel: wireguard::TunnelConfig {
+                    private_key,
+                    addresses: vec!["10.64.0.2".parse().unwrap()],
+                },
+                peer: wireguard::PeerConfig {
+                    public_key,
+                    allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                    endpoint: "0.0.0.0:51820".parse().unwrap(),
+                    psk: None,
+                    #[cfg(daita)]
+                    constant_packet_size: false,
+                },
+                exit_peer: None,
+                ipv4_gateway: "10.64.0.1".parse().unwrap(),
+                ipv6_gateway: None,
+                #[cfg(target_os = "linux")]
+                fwmark: None,
+            }),
+        )
+    }
+
+    fn wireguard_parameters(
+        endpoint: &CustomTunnelEndpoint,
+        tunnel_options: TunnelOptions,
+    ) -> wireguard::TunnelParameters {
+        match endpoint.to_tunnel_parameters(tunnel_options, None).unwrap() {
+            TunnelParameters::Wireguard(parameters) => parameters,
+            TunnelParameters::OpenVpn(_) => panic!("expected WireGuard parameters"),
+        }
+    }
+
+    /// Shadowsocks servers must forward traffic to the resolved WireGuard endpoint
+    #[test]
+    fn test_shadowsocks_target() {
+        let mut endpoint = wireguard_endpoint();
+        endpoint.obfuscation = Some(ObfuscatorConfig::Shadowsocks {
+            endpoint: "192.0.2.2:443".parse().unwrap(),
+            cipher: Some("chacha20-ietf-poly1305".to_owned()),
+            password: Some("secret".to_owned()),
+            target: None,
+        });
+
+        let parameters = wireguard_parameters(&endpoint, TunnelOptions::default());
+        assert_eq!(
+            parameters.obfuscation,
+            Some(ObfuscatorConfig::Shadowsocks {
+                endpoint: "192.0.2.2:443".parse().unwrap(),
+                cipher: Some("chacha20-ietf-poly1305".to_owned()),
+                password: Some("secret".to_owned()),
+                target: Some("192.0.2.1:51820".parse().unwrap()),
+            })
+        );
+        assert_eq!(
+            parameters.get_next_hop_endpoint().address,
+            "192.0.2.2:443".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_multihop_quantum_resistant() {
+        let mut endpoint = wireguard_endpoint();
+        let exit_key = wireguard::PrivateKey::new_from_random().public_key();
+        if let ConnectionConfig::Wireguard(connection) = &mut endpoint.config {
+            connection.exit_peer = Some(wireguard::PeerConfig {
+                public_key: exit_key.clone(),
+                allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                endpoint: "10.0.0.2:51820".parse().unwrap(),
+                psk: None,
+                #[cfg(daita)]
+                constant_packet_size: false,
+            });
+        }
+        endpoint.psk = Some(wireguard::PresharedKey::from([1; 32]));
+        endpoint.exit_psk = Some(wireguard::PresharedKey::from([2; 32]));
+
+        let mut tunnel_options = TunnelOptions::default();
+        tunnel_options.wireguard.quantum_resistant = QuantumResistantState::On;
+
+        // Quantum resistance requires the ephemeral peer service
+        assert!(
+            !wireguard_parameters(&endpoint, tunnel_options.clone())
+                .options
+                .quantum_resistant
+        );
+
+        endpoint.quantum_resistant = true;
+        let parameters = wireguard_parameters(&endpoint, tunnel_options);
+        assert!(parameters.options.quantum_resistant);
+        assert_eq!(
+            parameters.get_next_hop_endpoint().address,
+            "192.0.2.1:51820".parse().unwrap()
+        );
+
+        let connection = parameters.connection;
+        assert_eq!(connection.peer.endpoint, "192.0.2.1:51820".parse().unwrap());
+        assert_eq!(connection.peer.psk, endpoint.psk);
+        let exit_peer = connection.exit_peer.unwrap();
+        assert_eq!(exit_peer.public_key, exit_key);
+        assert_eq!(exit_peer.endpoint, "10.0.0.2:51820".parse().unwrap());
+        assert_eq!(exit_peer.psk, endpoint.exit_psk);
+    }
+
+    #[test]
+    fn test_udp2tcp_obfuscation() {
+        let mut endpoint = wireguard_endpoint();
+        let obfuscation = ObfuscatorConfig::Udp2Tcp {
+            endpoint: "192.0.2.2:443".parse().unwrap(),
+        };
+        endpoint.obfuscation = Some(obfuscation.clone());
+
+        assert_eq!(
+            wireguard_parameters(&endpoint, TunnelOptions::default()).obfuscation,
+            Some(obfuscation)
+        );
+    }
+}
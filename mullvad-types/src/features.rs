@@ -80,6 +80,7 @@ pub enum FeatureIndicator {
     CustomMtu,
     CustomMssFix,
     Daita,
+    Quic,
 }
 
 impl FeatureIndicator {
@@ -99,6 +100,7 @@ impl FeatureIndicator {
             FeatureIndicator::CustomMtu => "Custom MTU",
             FeatureIndicator::CustomMssFix => "Custom MSS",
             FeatureIndicator::Daita => "DAITA",
+            FeatureIndicator::Quic => "QUIC",
         }
     }
 }
@@ -122,10 +124,7 @@ pub fn compute_feature_indicators(
     endpoint: &TunnelEndpoint,
     server_ip_override: bool,
 ) -> FeatureIndicators {
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     let split_tunneling = settings.split_tunnel.enable_exclusions;
-    #[cfg(not(any(windows, target_os = "android", target_os = "macos")))]
-    let split_tunneling = false;
 
     #[cfg(not(target_os = "android"))]
     let lockdown_mode = settings.block_when_disconnected;
@@ -170,6 +169,11 @@ pub fn compute_feature_indicators(
                 .as_ref()
                 .filter(|obfuscation| obfuscation.obfuscation_type == ObfuscationType::Shadowsocks)
                 .is_some();
+            let quic = endpoint
+                .obfuscation
+                .as_ref()
+                .filter(|obfuscation| obfuscation.obfuscation_type == ObfuscationType::Quic)
+                .is_some();
 
             let mtu = settings.tunnel_options.wireguard.mtu.is_some();
 
@@ -186,6 +190,7 @@ pub fn compute_feature_indicators(
                 (multihop, FeatureIndicator::Multihop),
                 (udp_tcp, FeatureIndicator::Udp2Tcp),
                 (shadowsocks, FeatureIndicator::Shadowsocks),
+                (quic, FeatureIndicator::Quic),
                 (mtu, FeatureIndicator::CustomMtu),
                 (daita, FeatureIndicator::Daita),
             ]
@@ -356,6 +361,13 @@ mod tests {
             compute_feature_indicators(&settings, &endpoint, false),
             expected_indicators
         );
+        endpoint.obfuscation.as_mut().unwrap().obfuscation_type = ObfuscationType::Quic;
+        expected_indicators.0.remove(&FeatureIndicator::Shadowsocks);
+        expected_indicators.0.insert(FeatureIndicator::Quic);
+        assert_eq!(
+            compute_feature_indicators(&settings, &endpoint, false),
+            expected_indicators
+        );
 
         settings.tunnel_options.wireguard.mtu = Some(1300);
         expected_indicators.0.insert(FeatureIndicator::CustomMtu);
@@ -437,6 +449,7 @@ mod tests {
             FeatureIndicator::CustomMtu => {}
             FeatureIndicator::CustomMssFix => {}
             FeatureIndicator::Daita => {}
+            FeatureIndicator::Quic => {}
         }
     }
 }
@@ -0,0 +1,42 @@
+//! Obfuscation methods which have been learned to work on specific networks.
+
+use crate::trusted_network::TrustedNetwork;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use talpid_types::net::{IpVersion, ObfuscationType};
+
+/// An entry in the WireGuard retry order, identified by the obfuscation, port and IP version that
+/// it requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObfuscationMethod {
+    /// `None` if the tunnel is not obfuscated.
+    pub obfuscation: Option<ObfuscationType>,
+    /// Port of the WireGuard or obfuscation endpoint, if a specific port is required.
+    pub port: Option<u16>,
+    pub ip_version: Option<IpVersion>,
+}
+
+/// The obfuscation method which was used the last time a tunnel was established on a network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LearnedObfuscation {
+    pub network: TrustedNetwork,
+    pub method: ObfuscationMethod,
+    pub last_success: DateTime<Utc>,
+}
+
+impl fmt::Display for ObfuscationMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.obfuscation {
+            Some(obfuscation) => write!(f, "{obfuscation}")?,
+            None => "no obfuscation".fmt(f)?,
+        }
+        if let Some(port) = self.port {
+            write!(f, ", port {port}")?;
+        }
+        if let Some(ip_version) = self.ip_version {
+            write!(f, ", {ip_version}")?;
+        }
+        Ok(())
+    }
+}
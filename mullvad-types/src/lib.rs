@@ -1,17 +1,24 @@
 pub mod access_method;
 pub mod account;
 pub mod auth_failed;
+pub mod connection_history;
 pub mod constraints;
 pub mod custom_list;
 pub mod device;
 pub mod endpoint;
 pub mod features;
+pub mod learned_obfuscation;
 pub mod location;
+pub mod profile;
 pub mod relay_constraints;
+pub mod relay_health;
 pub mod relay_list;
 pub mod settings;
+pub mod split_tunnel_destination;
 pub mod states;
+pub mod trusted_network;
 pub mod version;
+pub mod wg_quick;
 pub mod wireguard;
 
 mod custom_tunnel;
@@ -34,7 +34,7 @@ impl Location {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     pub latitude: f64,
     pub longitude: f64,
@@ -55,7 +55,22 @@ impl From<Location> for Coordinates {
     }
 }
 
+impl From<&GeoIpLocation> for Coordinates {
+    fn from(location: &GeoIpLocation) -> Self {
+        Self {
+            latitude: location.latitude,
+            longitude: location.longitude,
+        }
+    }
+}
+
 impl Coordinates {
+    /// Returns whether the latitude and longitude are within their valid ranges. This is never the
+    /// case if either of them is NaN.
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.latitude) && (-180.0..=180.0).contains(&self.longitude)
+    }
+
     /// Computes the approximate midpoint of a set of locations.
     ///
     /// This works by calculating the mean Cartesian coordinates, and converting them
@@ -0,0 +1,509 @@
+//! Named settings profiles which can be applied manually, or automatically by the daemon when
+//! one of their triggers matches.
+
+use crate::{
+    relay_constraints::{ObfuscationSettings, RelaySettings},
+    settings::{DnsOptions, Settings},
+    trusted_network::{mac_eq, CurrentNetwork},
+};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Profile name must not be empty")]
+    EmptyName,
+    #[error("Profile not found")]
+    ProfileNotFound,
+    #[error("Profile trigger \"{0}\" is not supported on this platform")]
+    UnsupportedTrigger(ProfileTrigger),
+}
+
+/// All saved profiles, along with the state needed to undo an automatically activated profile.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProfileSettings {
+    profiles: Vec<Profile>,
+    /// Name of the profile that was most recently activated by a trigger, if it is still
+    /// triggered.
+    active: Option<String>,
+    /// Settings that were replaced when `active` was activated. These are restored once the
+    /// profile is no longer triggered.
+    restore: Option<Box<ProfileContents>>,
+    /// Settings that were applied when `active` was activated. Settings that no longer have these
+    /// values have been changed since, and are not restored.
+    applied: Option<Box<ProfileContents>>,
+}
+
+/// A named set of partial settings.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub contents: ProfileContents,
+    /// Conditions under which the profile is applied automatically. If any trigger matches, the
+    /// profile is applied.
+    #[serde(default)]
+    pub triggers: Vec<ProfileTrigger>,
+}
+
+/// The settings stored in a profile. Settings that are `None` are left untouched when the profile
+/// is applied.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProfileContents {
+    pub relay_settings: Option<RelaySettings>,
+    pub dns_options: Option<DnsOptions>,
+    pub obfuscation_settings: Option<ObfuscationSettings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileTrigger {
+    /// Active between `start` and `end` (local time) on the given days. If `end` is earlier than
+    /// `start`, the interval extends past midnight into the following day. If `days` is empty,
+    /// the schedule applies to every day.
+    Schedule {
+        days: Vec<Weekday>,
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+    /// Active while the default gateway has the given MAC address. Separators and case are
+    /// ignored when comparing addresses.
+    Network { gateway_mac: String },
+}
+
+/// The conditions which triggers are evaluated against.
+#[derive(Debug, Clone)]
+pub struct TriggerContext {
+    /// The current local time.
+    pub time: NaiveDateTime,
+    /// MAC address of the current default gateway, if known.
+    pub gateway_mac: Option<String>,
+}
+
+impl From<Vec<Profile>> for ProfileSettings {
+    fn from(profiles: Vec<Profile>) -> Self {
+        Self {
+            profiles,
+            ..Default::default()
+        }
+    }
+}
+
+impl ProfileSettings {
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// Return the profile that is currently activated by a trigger, if any.
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Add a profile, or replace an existing profile with the same name.
+    pub fn set(&mut self, profile: Profile) -> Result<(), Error> {
+        if profile.name.trim().is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if let Some(trigger) = profile.triggers.iter().find(|t| !t.is_supported()) {
+            return Err(Error::UnsupportedTrigger(trigger.clone()));
+        }
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+        Ok(())
+    }
+
+    /// Return the first profile with a trigger matching `context`.
+    pub fn triggered(&self, context: &TriggerContext) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.triggers.iter().any(|t| t.is_active(context)))
+    }
+
+    /// Return whether a different profile than the active one is triggered by `context`, in
+    /// which case [`evaluate_triggers`] will change the settings.
+    pub fn trigger_changed(&self, context: &TriggerContext) -> bool {
+        self.triggered(context).map(|profile| profile.name.as_str()) != self.active()
+    }
+}
+
+impl ProfileContents {
+    /// Capture the current values of the selected settings.
+    pub fn capture(settings: &Settings, relay: bool, dns: bool, obfuscation: bool) -> Self {
+        ProfileContents {
+            relay_settings: relay.then(|| settings.relay_settings.clone()),
+            dns_options: dns.then(|| settings.tunnel_options.dns_options.clone()),
+            obfuscation_settings: obfuscation.then(|| settings.obfuscation_settings.clone()),
+        }
+    }
+
+    /// Capture the current values of the settings that applying `self` would overwrite.
+    fn capture_overwritten(&self, settings: &Settings) -> Self {
+        Self::capture(
+            settings,
+            self.relay_settings.is_some(),
+            self.dns_options.is_some(),
+            self.obfuscation_settings.is_some(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relay_settings.is_none()
+            && self.dns_options.is_none()
+            && self.obfuscation_settings.is_none()
+    }
+
+    /// Apply the settings in `self` that still have the values in `applied`, so that settings
+    /// which have been changed since `applied` was applied are kept.
+    fn restore(&self, applied: &ProfileContents, settings: &mut Settings) {
+        if let (Some(relay_settings), Some(applied)) =
+            (&self.relay_settings, &applied.relay_settings)
+        {
+            if settings.relay_settings == *applied {
+                settings.set_relay_settings(relay_settings.clone());
+            }
+        }
+        if let (Some(dns_options), Some(applied)) = (&self.dns_options, &applied.dns_options) {
+            if settings.tunnel_options.dns_options == *applied {
+                settings.tunnel_options.dns_options = dns_options.clone();
+            }
+        }
+        if let (Some(obfuscation_settings), Some(applied)) =
+            (&self.obfuscation_settings, &applied.obfuscation_settings)
+        {
+            if settings.obfuscation_settings == *applied {
+                settings.obfuscation_settings = obfuscation_settings.clone();
+            }
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(relay_settings) = &self.relay_settings {
+            settings.set_relay_settings(relay_settings.clone());
+        }
+        if let Some(dns_options) = &self.dns_options {
+            settings.tunnel_options.dns_options = dns_options.clone();
+        }
+        if let Some(obfuscation_settings) = &self.obfuscation_settings {
+            settings.obfuscation_settings = obfuscation_settings.clone();
+        }
+    }
+}
+
+impl ProfileTrigger {
+    /// Returns whether the trigger can ever be active on the current platform.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            ProfileTrigger::Schedule { .. } => true,
+            ProfileTrigger::Network { .. } => CurrentNetwork::HAS_GATEWAY_MAC,
+        }
+    }
+
+    pub fn is_active(&self, context: &TriggerContext) -> bool {
+        match self {
+            ProfileTrigger::Schedule { days, start, end } => {
+                let includes = |day: Weekday| days.is_empty() || days.contains(&day);
+                let time = context.time.time();
+                let day = context.time.weekday();
+                if start == end {
+                    includes(day)
+                } else if start < end {
+                    includes(day) && *start <= time && time < *end
+                } else {
+                    (includes(day) && *start <= time) || (includes(day.pred()) && time < *end)
+                }
+            }
+            ProfileTrigger::Network { gateway_mac } => context
+                .gateway_mac
+                .as_deref()
+                .is_some_and(|mac| mac_eq(mac, gateway_mac)),
+        }
+    }
+}
+
+impl fmt::Display for ProfileTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileTrigger::Schedule { days, start, end } => {
+                write!(
+                    f,
+                    "schedule {}-{}",
+                    start.format("%H:%M"),
+                    end.format("%H:%M")
+                )?;
+                if !days.is_empty() {
+                    let days: Vec<_> = days.iter().map(Weekday::to_string).collect();
+                    write!(f, " on {}", days.join(", "))?;
+                }
+                Ok(())
+            }
+            ProfileTrigger::Network { gateway_mac } => write!(f, "network {gateway_mac}"),
+        }
+    }
+}
+
+/// Apply the first profile triggered by `context`. If a previously triggered profile is no longer
+/// triggered, the settings it replaced are restored first.
+///
+/// Returns the name of the newly activated profile if the active profile changed.
+pub fn evaluate_triggers(settings: &mut Settings, context: &TriggerContext) -> Option<String> {
+    if !settings.profiles.trigger_changed(context) {
+        return None;
+    }
+    let triggered = settings
+        .profiles
+        .triggered(context)
+        .map(|profile| profile.name.clone());
+
+    deactivate(settings);
+
+    let profile = settings.profiles.get(triggered.as_deref()?)?.clone();
+    settings.profiles.restore = Some(Box::new(profile.contents.capture_overwritten(settings)));
+    settings.profiles.active = Some(profile.name.clone());
+    profile.contents.apply(settings);
+    settings.profiles.applied = Some(Box::new(profile.contents));
+
+    Some(profile.name)
+}
+
+/// Apply a profile by name. Unlike profiles applied by a trigger, the settings are not restored
+/// afterwards.
+pub fn apply_profile(settings: &mut Settings, name: &str) -> Result<(), Error> {
+    let profile = settings.profiles.get(name).ok_or(Error::ProfileNotFound)?;
+    let contents = profile.contents.clone();
+    contents.apply(settings);
+    // Keep the active profile so that it is not reapplied, but make the change permanent
+    settings.profiles.restore = None;
+    settings.profiles.applied = None;
+    Ok(())
+}
+
+/// Remove a profile by name. If it is currently activated by a trigger, the settings it replaced
+/// are restored.
+pub fn delete_profile(settings: &mut Settings, name: &str) -> Result<(), Error> {
+    let index = settings
+        .profiles
+        .profiles
+        .iter()
+        .position(|profile| profile.name == name)
+        .ok_or(Error::ProfileNotFound)?;
+    if settings.profiles.active.as_deref() == Some(name) {
+        deactivate(settings);
+    }
+    settings.profiles.profiles.remove(index);
+    Ok(())
+}
+
+/// Restore the settings replaced by the active profile, if any. Settings that have been changed
+/// since the profile was activated are kept.
+fn deactivate(settings: &mut Settings) {
+    if let Some(name) = settings.profiles.active.take() {
+        log::debug!("Deactivating profile \"{name}\"");
+    }
+    let applied = settings.profiles.applied.take();
+    if let (Some(restore), Some(applied)) = (settings.profiles.restore.take(), applied) {
+        restore.restore(&applied, settings);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{relay_constraints::SelectedObfuscation, settings::DnsState};
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, min: u32) -> TriggerContext {
+        // 2024-01-01 is a Monday
+        let time = NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap();
+        TriggerContext {
+            time,
+            gateway_mac: None,
+        }
+    }
+
+    fn hm(hour: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_schedule_trigger() {
+        let office_hours = ProfileTrigger::Schedule {
+            days: vec![Weekday::Mon, Weekday::Tue],
+            start: hm(9, 0),
+            end: hm(17, 0),
+        };
+        assert!(office_hours.is_active(&at(1, 9, 0)));
+        assert!(!office_hours.is_active(&at(1, 17, 0)));
+        assert!(!office_hours.is_active(&at(3, 12, 0)));
+
+        let overnight = ProfileTrigger::Schedule {
+            days: vec![Weekday::Fri],
+            start: hm(22, 0),
+            end: hm(6, 0),
+        };
+        assert!(overnight.is_active(&at(5, 23, 0)));
+        assert!(overnight.is_active(&at(6, 5, 59)));
+        assert!(!overnight.is_active(&at(5, 5, 0)));
+        assert!(!overnight.is_active(&at(6, 23, 0)));
+    }
+
+    #[test]
+    fn test_network_trigger() {
+        let trigger = ProfileTrigger::Network {
+            gateway_mac: "AA:BB:CC:00:11:22".to_owned(),
+        };
+        let mut context = at(1, 12, 0);
+        assert!(!trigger.is_active(&context));
+        context.gateway_mac = Some("aa:bb:cc:00:11:22".to_owned());
+        assert!(trigger.is_active(&context));
+        context.gateway_mac = Some("AABBCC001122".to_owned());
+        assert!(trigger.is_active(&context));
+        context.gateway_mac = Some("AABBCC001123".to_owned());
+        assert!(!trigger.is_active(&context));
+    }
+
+    #[test]
+    fn test_unsupported_trigger() {
+        let mut settings = ProfileSettings::default();
+        let profile = Profile {
+            name: "home".to_owned(),
+            contents: ProfileContents::default(),
+            triggers: vec![ProfileTrigger::Network {
+                gateway_mac: "AA:BB:CC:00:11:22".to_owned(),
+            }],
+        };
+
+        let result = settings.set(profile);
+        if CurrentNetwork::HAS_GATEWAY_MAC {
+            assert!(result.is_ok());
+        } else {
+            assert!(matches!(result, Err(Error::UnsupportedTrigger(_))));
+            assert!(settings.profiles().is_empty());
+        }
+    }
+
+    /// Activating a triggered profile must restore the replaced settings once it is no longer
+    /// triggered.
+    #[test]
+    fn test_evaluate_triggers_restores_settings() {
+        let mut settings = Settings::default();
+        let original_dns = settings.tunnel_options.dns_options.clone();
+
+        let mut dns_options = original_dns.clone();
+        dns_options.state = DnsState::Custom;
+        settings
+            .profiles
+            .set(Profile {
+                name: "work".to_owned(),
+                contents: ProfileContents {
+                    dns_options: Some(dns_options.clone()),
+                    ..Default::default()
+                },
+                triggers: vec![ProfileTrigger::Schedule {
+                    days: vec![],
+                    start: hm(9, 0),
+                    end: hm(17, 0),
+                }],
+            })
+            .unwrap();
+
+        assert_eq!(
+            evaluate_triggers(&mut settings, &at(1, 10, 0)).as_deref(),
+            Some("work")
+        );
+        assert_eq!(settings.tunnel_options.dns_options, dns_options);
+        assert_eq!(settings.profiles.active(), Some("work"));
+
+        // Still triggered, so nothing changes
+        assert_eq!(evaluate_triggers(&mut settings, &at(1, 11, 0)), None);
+
+        assert_eq!(evaluate_triggers(&mut settings, &at(1, 18, 0)), None);
+        assert_eq!(settings.tunnel_options.dns_options, original_dns);
+        assert_eq!(settings.profiles.active(), None);
+    }
+
+    /// Settings that are changed while a profile is active must not be reverted when it is
+    /// deactivated, but other settings replaced by the profile must be restored.
+    #[test]
+    fn test_manual_change_is_kept() {
+        let mut settings = Settings::default();
+        let original_dns = settings.tunnel_options.dns_options.clone();
+        let original_obfuscation = settings.obfuscation_settings.clone();
+
+        let mut dns_options = original_dns.clone();
+        dns_options.state = DnsState::Custom;
+        let mut obfuscation_settings = original_obfuscation.clone();
+        obfuscation_settings.selected_obfuscation = SelectedObfuscation::Udp2Tcp;
+        settings
+            .profiles
+            .set(Profile {
+                name: "work".to_owned(),
+                contents: ProfileContents {
+                    dns_options: Some(dns_options.clone()),
+                    obfuscation_settings: Some(obfuscation_settings),
+                    ..Default::default()
+                },
+                triggers: vec![ProfileTrigger::Schedule {
+                    days: vec![],
+                    start: hm(9, 0),
+                    end: hm(17, 0),
+                }],
+            })
+            .unwrap();
+
+        evaluate_triggers(&mut settings, &at(1, 10, 0));
+        let mut manual_dns = dns_options.clone();
+        manual_dns.default_options.block_ads = true;
+        settings.tunnel_options.dns_options = manual_dns.clone();
+
+        evaluate_triggers(&mut settings, &at(1, 18, 0));
+        assert_eq!(settings.profiles.active(), None);
+        assert_eq!(settings.tunnel_options.dns_options, manual_dns);
+        assert_eq!(settings.obfuscation_settings, original_obfuscation);
+    }
+
+    /// Manually applied profiles are permanent, even if the profile is also triggered.
+    #[test]
+    fn test_apply_profile_is_permanent() {
+        let mut settings = Settings::default();
+        let original_dns = settings.tunnel_options.dns_options.clone();
+
+        let mut dns_options = original_dns.clone();
+        dns_options.state = DnsState::Custom;
+        settings
+            .profiles
+            .set(Profile {
+                name: "work".to_owned(),
+                contents: ProfileContents {
+                    dns_options: Some(dns_options.clone()),
+                    ..Default::default()
+                },
+                triggers: vec![ProfileTrigger::Schedule {
+                    days: vec![],
+                    start: hm(9, 0),
+                    end: hm(17, 0),
+                }],
+            })
+            .unwrap();
+
+        evaluate_triggers(&mut settings, &at(1, 10, 0));
+        apply_profile(&mut settings, "work").unwrap();
+        evaluate_triggers(&mut settings, &at(1, 18, 0));
+        assert_eq!(settings.tunnel_options.dns_options, dns_options);
+
+        assert!(matches!(
+            apply_profile(&mut settings, "home"),
+            Err(Error::ProfileNotFound)
+        ));
+    }
+}
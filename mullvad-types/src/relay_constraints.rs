@@ -4,15 +4,15 @@
 use crate::{
     constraints::{Constraint, Match},
     custom_list::{CustomListsSettings, Id},
-    location::{CityCode, CountryCode, Hostname},
+    location::{CityCode, Coordinates, CountryCode, Hostname},
     relay_list::{Relay, RelayEndpointData},
     CustomTunnelEndpoint, Intersection,
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashSet},
     fmt,
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     str::FromStr,
 };
 use talpid_types::net::{proxy::CustomProxy, IpVersion, TransportProtocol, TunnelType};
@@ -89,6 +89,69 @@ impl fmt::Display for RelaySettingsFormatter<'_> {
 pub enum LocationConstraint {
     Location(GeographicLocationConstraint),
     CustomList { list_id: Id },
+    Nearest(NearestLocationConstraint),
+}
+
+impl LocationConstraint {
+    /// Use `device_location` as the origin if this is a [nearest][`NearestLocationConstraint`]
+    /// constraint without an explicit origin.
+    pub fn set_device_location(&mut self, device_location: Coordinates) {
+        if let LocationConstraint::Nearest(nearest) = self {
+            if nearest.origin.is_none() {
+                nearest.origin = Some(device_location);
+            }
+        }
+    }
+}
+
+/// Limits the set of [`crate::relay_list::Relay`]s to the ones closest to a geographic coordinate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearestLocationConstraint {
+    /// The coordinate to measure distances from. If this is `None`, the location of the device is
+    /// used, as last reported by am.i.mullvad.net while disconnected.
+    pub origin: Option<Coordinates>,
+    /// Relays within this distance of `origin`, in kilometers, are considered. If there are no such
+    /// relays, the relays closest to `origin` are considered instead.
+    pub radius: u32,
+}
+
+// Coordinates are compared by their bit patterns, so that the equality is reflexive even if they
+// are NaN.
+impl PartialEq for NearestLocationConstraint {
+    fn eq(&self, other: &Self) -> bool {
+        let bits = |origin: &Option<Coordinates>| {
+            origin.map(|origin| (origin.latitude.to_bits(), origin.longitude.to_bits()))
+        };
+        bits(&self.origin) == bits(&other.origin) && self.radius == other.radius
+    }
+}
+
+impl Eq for NearestLocationConstraint {}
+
+impl NearestLocationConstraint {
+    /// The radius used if none is specified, in kilometers.
+    pub const DEFAULT_RADIUS: u32 = 500;
+
+    /// Select relays close to the device's own location.
+    pub fn device_location(radius: u32) -> Self {
+        NearestLocationConstraint {
+            origin: None,
+            radius,
+        }
+    }
+}
+
+impl fmt::Display for NearestLocationConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.origin {
+            Some(Coordinates {
+                latitude,
+                longitude,
+            }) => write!(f, "nearest to {latitude:.4}, {longitude:.4}")?,
+            None => write!(f, "nearest to device location")?,
+        }
+        write!(f, " (within {} km)", self.radius)
+    }
 }
 
 pub struct LocationConstraintFormatter<'a> {
@@ -106,6 +169,7 @@ impl fmt::Display for LocationConstraintFormatter<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.constraint {
             LocationConstraint::Location(location) => write!(f, "{}", location),
+            LocationConstraint::Nearest(nearest) => write!(f, "{}", nearest),
             LocationConstraint::CustomList { list_id } => self
                 .custom_lists
                 .iter()
@@ -126,6 +190,7 @@ pub struct RelayConstraints {
     pub tunnel_protocol: TunnelType,
     pub wireguard_constraints: WireguardConstraints,
     pub openvpn_constraints: OpenVpnConstraints,
+    pub exclusions: RelayExclusions,
 }
 
 pub struct RelayConstraintsFormatter<'a> {
@@ -157,7 +222,15 @@ impl fmt::Display for RelayConstraintsFormatter<'_> {
                 })
         )?;
         writeln!(f, "Provider(s): {}", self.constraints.providers)?;
-        write!(f, "Ownership: {}", self.constraints.ownership)
+        writeln!(f, "Ownership: {}", self.constraints.ownership)?;
+        write!(
+            f,
+            "Excluded: {}",
+            RelayExclusionsFormatter {
+                exclusions: &self.constraints.exclusions,
+                custom_lists: self.custom_lists,
+            }
+        )
     }
 }
 
@@ -360,6 +433,98 @@ impl fmt::Display for Providers {
     }
 }
 
+/// Relays that a `RelaySelector` may never select, regardless of any other constraints. This
+/// applies to entry relays, exit relays and bridges alike.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RelayExclusions {
+    pub hostnames: BTreeSet<Hostname>,
+    pub custom_lists: BTreeSet<Id>,
+    pub providers: BTreeSet<Provider>,
+}
+
+/// A single entry in [`RelayExclusions`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RelayExclusion {
+    Hostname(Hostname),
+    CustomList(Id),
+    Provider(Provider),
+}
+
+impl RelayExclusions {
+    /// Returns whether no relays are excluded.
+    pub fn is_empty(&self) -> bool {
+        self.hostnames.is_empty() && self.custom_lists.is_empty() && self.providers.is_empty()
+    }
+
+    /// Add `exclusion`. Returns whether it was not already excluded.
+    pub fn insert(&mut self, exclusion: RelayExclusion) -> bool {
+        match exclusion {
+            RelayExclusion::Hostname(hostname) => self.hostnames.insert(hostname),
+            RelayExclusion::CustomList(list_id) => self.custom_lists.insert(list_id),
+            RelayExclusion::Provider(provider) => self.providers.insert(provider),
+        }
+    }
+
+    /// Remove `exclusion`. Returns whether it was excluded.
+    pub fn remove(&mut self, exclusion: &RelayExclusion) -> bool {
+        match exclusion {
+            RelayExclusion::Hostname(hostname) => self.hostnames.remove(hostname),
+            RelayExclusion::CustomList(list_id) => self.custom_lists.remove(list_id),
+            RelayExclusion::Provider(provider) => self.providers.remove(provider),
+        }
+    }
+}
+
+/// A relay matching both queries must not be excluded by either of them, so the intersection of
+/// two sets of exclusions is their union.
+impl Intersection for RelayExclusions {
+    fn intersection(mut self, other: Self) -> Option<Self> {
+        self.hostnames.extend(other.hostnames);
+        self.custom_lists.extend(other.custom_lists);
+        self.providers.extend(other.providers);
+        Some(self)
+    }
+}
+
+/// Returned when relay exclusions are changed while a custom tunnel endpoint is in use, since they
+/// only apply to relays from the relay list.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Relay exclusions are not used with a custom tunnel endpoint")]
+pub struct ExclusionsUnsupported;
+
+pub struct RelayExclusionsFormatter<'a> {
+    pub exclusions: &'a RelayExclusions,
+    pub custom_lists: &'a CustomListsSettings,
+}
+
+impl fmt::Display for RelayExclusionsFormatter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.exclusions.is_empty() {
+            return write!(f, "none");
+        }
+        let hostnames = self
+            .exclusions
+            .hostnames
+            .iter()
+            .map(|hostname| format!("hostname {hostname}"));
+        let custom_lists = self.exclusions.custom_lists.iter().map(|list_id| {
+            self.custom_lists
+                .iter()
+                .find(|list| &list.id == list_id)
+                .map(|list| format!("custom list {}", list.name))
+                .unwrap_or_else(|| "invalid custom list".to_owned())
+        });
+        let providers = self
+            .exclusions
+            .providers
+            .iter()
+            .map(|provider| format!("provider {provider}"));
+        let exclusions: Vec<_> = hostnames.chain(custom_lists).chain(providers).collect();
+        write!(f, "{}", exclusions.join(", "))
+    }
+}
+
 impl fmt::Display for GeographicLocationConstraint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
@@ -509,6 +674,9 @@ pub enum SelectedObfuscation {
     #[cfg_attr(feature = "clap", clap(name = "udp2tcp"))]
     Udp2Tcp,
     Shadowsocks,
+    Quic,
+    /// Use the protocol in [`GenericObfuscationSettings`].
+    Generic,
 }
 
 impl Intersection for SelectedObfuscation {
@@ -533,6 +701,8 @@ impl fmt::Display for SelectedObfuscation {
             SelectedObfuscation::Off => "off".fmt(f),
             SelectedObfuscation::Udp2Tcp => "udp2tcp".fmt(f),
             SelectedObfuscation::Shadowsocks => "shadowsocks".fmt(f),
+            SelectedObfuscation::Quic => "quic".fmt(f),
+            SelectedObfuscation::Generic => "generic".fmt(f),
         }
     }
 }
@@ -567,6 +737,52 @@ impl fmt::Display for ShadowsocksSettings {
     }
 }
 
+/// Obfuscation using a protocol that is identified by name, and which must be registered in
+/// `tunnel-obfuscation`. The obfuscation server is expected to listen on `port` on the WireGuard
+/// address of the relay.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(default)]
+pub struct GenericObfuscationSettings {
+    /// Name of the protocol
+    pub protocol: String,
+    pub port: u16,
+    /// Transport protocol used to reach the obfuscation server
+    pub transport: TransportProtocol,
+    /// Protocol-specific parameters
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl Default for GenericObfuscationSettings {
+    fn default() -> Self {
+        Self {
+            protocol: String::new(),
+            port: 0,
+            transport: TransportProtocol::Udp,
+            parameters: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for GenericObfuscationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.protocol.is_empty() {
+            return write!(f, "no protocol");
+        }
+        write!(
+            f,
+            "{} over {} port {}",
+            self.protocol, self.transport, self.port
+        )?;
+        // Values are not shown, since they may contain secrets
+        if !self.parameters.is_empty() {
+            let names: Vec<_> = self.parameters.keys().map(String::as_str).collect();
+            write!(f, ", parameters: {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 /// Contains obfuscation settings
 #[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -575,6 +791,7 @@ pub struct ObfuscationSettings {
     pub selected_obfuscation: SelectedObfuscation,
     pub udp2tcp: Udp2TcpObfuscationSettings,
     pub shadowsocks: ShadowsocksSettings,
+    pub generic: GenericObfuscationSettings,
 }
 
 /// Limits the set of bridge servers to use in `mullvad-daemon`.
@@ -643,12 +860,38 @@ impl fmt::Display for BridgeState {
     }
 }
 
+/// Strategy used to pick a relay among all relays matching the current constraints.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum RelaySelectionStrategy {
+    /// Pick a relay at random, weighted by the relay weights in the relay list.
+    #[default]
+    Random,
+    /// Prefer relays with a low measured round-trip time.
+    Fastest,
+}
+
+impl fmt::Display for RelaySelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RelaySelectionStrategy::Random => "random",
+                RelaySelectionStrategy::Fastest => "fastest",
+            }
+        )
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct InternalBridgeConstraints {
     pub location: Constraint<LocationConstraint>,
     pub providers: Constraint<Providers>,
     pub ownership: Constraint<Ownership>,
     pub transport_protocol: Constraint<TransportProtocol>,
+    pub exclusions: RelayExclusions,
 }
 
 /// Options to override for a particular relay to use instead of the ones specified in the relay
@@ -694,13 +937,17 @@ impl RelayOverride {
 
         // Additional IPs should be ignored when overrides are present
         if let RelayEndpointData::Wireguard(data) = &mut relay.endpoint_data {
-            data.shadowsocks_extra_addr_in.retain(|addr| {
+            let not_overridden = |addr: &IpAddr| {
                 let not_overridden_v4 = self.ipv4_addr_in.is_none() && addr.is_ipv4();
                 let not_overridden_v6 = self.ipv6_addr_in.is_none() && addr.is_ipv6();
 
                 // Keep address if it's not overridden
                 not_overridden_v4 || not_overridden_v6
-            });
+            };
+            data.shadowsocks_extra_addr_in.retain(not_overridden);
+            if let Some(quic) = &mut data.quic {
+                quic.addr_in.retain(not_overridden);
+            }
         }
     }
 }
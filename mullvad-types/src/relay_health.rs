@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A relay which recently failed to connect. The relay selector avoids such relays until
+/// `penalized_until` has passed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayFailure {
+    pub hostname: String,
+    /// Number of consecutive failed connection attempts
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    pub penalized_until: DateTime<Utc>,
+}
@@ -130,6 +130,7 @@ impl PartialEq for Relay {
     ///     #   .unwrap(),
     ///     #   daita: false,
     ///     #   shadowsocks_extra_addr_in: vec![],
+    ///     #   quic: None,
     ///     # }),
     ///     # location: mullvad_types::location::Location {
     ///     #   country: "Sweden".to_string(),
@@ -226,6 +227,20 @@ pub struct WireguardRelayEndpointData {
     /// Optional IP addresses used by Shadowsocks
     #[serde(default)]
     pub shadowsocks_extra_addr_in: Vec<IpAddr>,
+    /// Parameters used by QUIC obfuscation, if the relay supports it
+    #[serde(default)]
+    pub quic: Option<QuicEndpointData>,
+}
+
+/// Data needed to connect to the QUIC obfuscation proxy on a WireGuard relay.
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Debug)]
+pub struct QuicEndpointData {
+    /// IP addresses of the proxy
+    pub addr_in: Vec<IpAddr>,
+    /// Hostname of the proxy, which its certificate is valid for
+    pub domain: String,
+    /// Token used to authenticate with the proxy
+    pub token: String,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
@@ -1,5 +1,11 @@
 use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use std::{
+    fmt,
+    net::IpAddr,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+use talpid_types::net::dns::EncryptedDnsResolver;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -16,6 +22,8 @@ pub struct DnsOptions {
     pub state: DnsState,
     pub default_options: DefaultDnsOptions,
     pub custom_options: CustomDnsOptions,
+    /// Blocklists enforced by a local resolver, regardless of `state`. Not supported on Android.
+    pub blocklists: DnsBlocklists,
 }
 
 /// Default DNS config
@@ -34,6 +42,95 @@ pub struct DefaultDnsOptions {
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct CustomDnsOptions {
     pub addresses: Vec<IpAddr>,
+    /// DNS-over-HTTPS and DNS-over-TLS resolvers. When any are set, queries are sent to a local
+    /// resolver that forwards them to these through the tunnel, and `addresses` is ignored.
+    /// Not supported on Android.
+    #[serde(default)]
+    pub encrypted_resolvers: Vec<EncryptedDnsResolver>,
+}
+
+/// Custom DNS blocklists
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(default)]
+pub struct DnsBlocklists {
+    /// Files and URLs containing blocklists, either in hosts file format or with one domain per
+    /// line.
+    pub sources: Vec<DnsBlocklistSource>,
+    /// Domains that are never blocked. This also applies to their subdomains.
+    pub allowed_domains: Vec<String>,
+}
+
+impl DnsBlocklists {
+    /// Return whether any blocklists are used.
+    pub fn is_enabled(&self) -> bool {
+        !self.sources.is_empty()
+    }
+
+    /// Return an error if any blocklist file is not located in `blocklist_dir`.
+    pub fn check_files(&self, blocklist_dir: &Path) -> Result<(), BlocklistFileNotAllowed> {
+        for source in &self.sources {
+            if let DnsBlocklistSource::File(path) = source {
+                let is_contained = path.starts_with(blocklist_dir)
+                    && !path
+                        .components()
+                        .any(|component| component == Component::ParentDir);
+                if !is_contained {
+                    return Err(BlocklistFileNotAllowed(blocklist_dir.to_owned()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name of the directory in the settings directory that blocklist files must be located in. Any
+/// user may change the settings, so the daemon only reads files that privileged users have placed
+/// in this directory.
+pub const DNS_BLOCKLIST_DIR: &str = "dns-blocklists";
+
+/// Error returned when a blocklist file is located outside of [`DNS_BLOCKLIST_DIR`]
+#[derive(thiserror::Error, Debug)]
+#[error("Blocklist files must be located in {}", .0.display())]
+pub struct BlocklistFileNotAllowed(pub PathBuf);
+
+/// Location of a DNS blocklist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsBlocklistSource {
+    /// Absolute path to a local file in [`DNS_BLOCKLIST_DIR`]
+    File(PathBuf),
+    /// HTTP or HTTPS URL
+    Url(String),
+}
+
+/// Error returned when parsing a [`DnsBlocklistSource`]
+#[derive(thiserror::Error, Debug)]
+#[error("A blocklist must be an absolute path or an HTTP(S) URL")]
+pub struct InvalidDnsBlocklistSource;
+
+impl FromStr for DnsBlocklistSource {
+    type Err = InvalidDnsBlocklistSource;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if source.starts_with("https://") || source.starts_with("http://") {
+            return Ok(DnsBlocklistSource::Url(source.to_owned()));
+        }
+        let path = Path::new(source);
+        if path.is_absolute() {
+            Ok(DnsBlocklistSource::File(path.to_owned()))
+        } else {
+            Err(InvalidDnsBlocklistSource)
+        }
+    }
+}
+
+impl fmt::Display for DnsBlocklistSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsBlocklistSource::File(path) => write!(f, "{}", path.display()),
+            DnsBlocklistSource::Url(url) => f.write_str(url),
+        }
+    }
 }
 
 impl DefaultDnsOptions {
@@ -56,3 +153,31 @@ impl DefaultDnsOptions {
             || block_social_media
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_files() {
+        let dir = Path::new("/etc/mullvad-vpn/dns-blocklists");
+        let blocklists = |source: &str| DnsBlocklists {
+            sources: vec![source.parse().unwrap()],
+            allowed_domains: vec![],
+        };
+
+        assert!(blocklists("/etc/mullvad-vpn/dns-blocklists/ads.txt")
+            .check_files(dir)
+            .is_ok());
+        assert!(blocklists("https://example.com/etc/shadow")
+            .check_files(dir)
+            .is_ok());
+        assert!(blocklists("/etc/shadow").check_files(dir).is_err());
+        assert!(blocklists("/etc/mullvad-vpn/dns-blocklists-other/ads.txt")
+            .check_files(dir)
+            .is_err());
+        assert!(blocklists("/etc/mullvad-vpn/dns-blocklists/../../shadow")
+            .check_files(dir)
+            .is_err());
+    }
+}
@@ -2,17 +2,20 @@ use crate::{
     access_method,
     constraints::Constraint,
     custom_list::CustomListsSettings,
+    profile::ProfileSettings,
     relay_constraints::{
         BridgeSettings, BridgeState, GeographicLocationConstraint, LocationConstraint,
-        ObfuscationSettings, RelayConstraints, RelayOverride, RelaySettings,
-        RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
+        ObfuscationSettings, RelayConstraints, RelayOverride, RelaySelectionStrategy,
+        RelaySettings, RelaySettingsFormatter, SelectedObfuscation, WireguardConstraints,
     },
+    split_tunnel_destination::SplitTunnelDestinations,
+    trusted_network::TrustedNetworkSettings,
     wireguard,
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 use std::collections::HashSet;
-use talpid_types::net::{openvpn, GenericTunnelOptions};
+use talpid_types::net::{openvpn, GenericTunnelOptions, LanAccess};
+pub use talpid_types::split_tunnel::SplitTunnelMode;
 
 mod dns;
 
@@ -20,7 +23,7 @@ mod dns;
 /// latest version that exists in `SettingsVersion`.
 /// This should be bumped when a new version is introduced along with a migration
 /// being added to `mullvad-daemon`.
-pub const CURRENT_SETTINGS_VERSION: SettingsVersion = SettingsVersion::V11;
+pub const CURRENT_SETTINGS_VERSION: SettingsVersion = SettingsVersion::V12;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Clone, Copy)]
 #[repr(u32)]
@@ -35,6 +38,7 @@ pub enum SettingsVersion {
     V9 = 9,
     V10 = 10,
     V11 = 11,
+    V12 = 12,
 }
 
 impl<'de> Deserialize<'de> for SettingsVersion {
@@ -53,6 +57,7 @@ impl<'de> Deserialize<'de> for SettingsVersion {
             v if v == SettingsVersion::V9 as u32 => Ok(SettingsVersion::V9),
             v if v == SettingsVersion::V10 as u32 => Ok(SettingsVersion::V10),
             v if v == SettingsVersion::V11 as u32 => Ok(SettingsVersion::V11),
+            v if v == SettingsVersion::V12 as u32 => Ok(SettingsVersion::V12),
             v => Err(serde::de::Error::custom(format!(
                 "{v} is not a valid SettingsVersion"
             ))),
@@ -77,18 +82,25 @@ pub struct Settings {
     pub bridge_settings: BridgeSettings,
     pub obfuscation_settings: ObfuscationSettings,
     pub bridge_state: BridgeState,
+    /// How to pick a relay among the relays matching the relay constraints.
+    pub relay_selection_strategy: RelaySelectionStrategy,
     /// All of the custom relay lists
     pub custom_lists: CustomListsSettings,
     /// API access methods
     pub api_access_methods: access_method::Settings,
     /// If the daemon should allow communication with private (LAN) networks.
     pub allow_lan: bool,
+    /// Restricts which LAN networks and inbound ports are allowed when `allow_lan` is set, and
+    /// which other networks are always allowed. Not supported on Android.
+    pub lan_access: LanAccess,
     /// Extra level of kill switch. When this setting is on, the disconnected state will block
     /// the firewall to not allow any traffic in or out.
     #[cfg(not(target_os = "android"))]
     pub block_when_disconnected: bool,
     /// If the daemon should connect the VPN tunnel directly on start or not.
     pub auto_connect: bool,
+    /// Networks on which the tunnel is disconnected automatically.
+    pub trusted_networks: TrustedNetworkSettings,
     /// Options that should be applied to tunnels of a specific type regardless of where the relays
     /// might be located.
     pub tunnel_options: TunnelOptions,
@@ -96,24 +108,47 @@ pub struct Settings {
     pub relay_overrides: Vec<RelayOverride>,
     /// Whether to notify users of beta updates.
     pub show_beta_releases: bool,
+    /// Saved settings profiles
+    pub profiles: ProfileSettings,
     /// Split tunneling settings
-    #[cfg(any(windows, target_os = "android", target_os = "macos"))]
     pub split_tunnel: SplitTunnelSettings,
     /// Specifies settings schema version
     pub settings_version: SettingsVersion,
 }
 
-#[cfg(any(windows, target_os = "android", target_os = "macos"))]
 #[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 pub struct SplitTunnelSettings {
     /// Toggles split tunneling on or off
     pub enable_exclusions: bool,
     /// Set of applications to exclude from the tunnel.
     pub apps: HashSet<SplitApp>,
+    /// Whether the split tunneled applications bypass the tunnel or are the only ones using it.
+    /// Only the default mode, [`SplitTunnelMode::Exclude`], is supported outside of Linux.
+    #[serde(default)]
+    pub mode: SplitTunnelMode,
+    /// Destinations to exclude from the tunnel.
+    #[serde(default)]
+    pub destinations: SplitTunnelDestinations,
+}
+
+impl SplitTunnelSettings {
+    /// Return the split tunnel mode to enforce.
+    ///
+    /// In [`SplitTunnelMode::Include`], everything but the selected applications bypasses the
+    /// tunnel. It therefore only takes effect while split tunneling is enabled and applications
+    /// have been selected. Otherwise, [`SplitTunnelMode::Exclude`] is used.
+    pub fn effective_mode(&self) -> SplitTunnelMode {
+        match self.mode {
+            SplitTunnelMode::Include if self.enable_exclusions && !self.apps.is_empty() => {
+                SplitTunnelMode::Include
+            }
+            _ => SplitTunnelMode::Exclude,
+        }
+    }
 }
 
 /// An application whose traffic should be excluded from any active tunnel.
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(std::path::PathBuf);
 
@@ -122,7 +157,7 @@ pub struct SplitApp(std::path::PathBuf);
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SplitApp(String);
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl SplitApp {
     /// Convert the underlying path to a [`String`].
     /// This function will fail if the underlying path string is not valid UTF-8. See
@@ -158,14 +193,14 @@ impl SplitApp {
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl From<String> for SplitApp {
     fn from(value: String) -> Self {
         SplitApp::from(std::path::PathBuf::from(value))
     }
 }
 
-#[cfg(any(windows, target_os = "macos"))]
+#[cfg(not(target_os = "android"))]
 impl From<std::path::PathBuf> for SplitApp {
     fn from(value: std::path::PathBuf) -> Self {
         SplitApp(value)
@@ -200,16 +235,19 @@ impl Default for Settings {
                 ..Default::default()
             },
             bridge_state: BridgeState::Auto,
+            relay_selection_strategy: RelaySelectionStrategy::default(),
             custom_lists: CustomListsSettings::default(),
             api_access_methods: access_method::Settings::default(),
             allow_lan: false,
+            lan_access: LanAccess::default(),
             #[cfg(not(target_os = "android"))]
             block_when_disconnected: false,
             auto_connect: false,
+            trusted_networks: TrustedNetworkSettings::default(),
             tunnel_options: TunnelOptions::default(),
             relay_overrides: vec![],
             show_beta_releases: false,
-            #[cfg(any(windows, target_os = "android", target_os = "macos"))]
+            profiles: ProfileSettings::default(),
             split_tunnel: SplitTunnelSettings::default(),
             settings_version: CURRENT_SETTINGS_VERSION,
         }
@@ -276,7 +314,10 @@ pub struct TunnelOptions {
     pub dns_options: DnsOptions,
 }
 
-pub use dns::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
+pub use dns::{
+    BlocklistFileNotAllowed, CustomDnsOptions, DefaultDnsOptions, DnsBlocklistSource,
+    DnsBlocklists, DnsOptions, DnsState, InvalidDnsBlocklistSource, DNS_BLOCKLIST_DIR,
+};
 
 impl Default for TunnelOptions {
     fn default() -> Self {
@@ -0,0 +1,166 @@
+//! Destinations whose traffic should bypass the tunnel when split tunneling is enabled.
+
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// Maximum length of a domain name, excluding any trailing dot.
+const MAX_DOMAIN_LEN: usize = 253;
+/// Maximum length of a single label in a domain name.
+const MAX_LABEL_LEN: usize = 63;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SplitTunnelDestinations {
+    /// Networks that are reached outside the tunnel.
+    pub networks: Vec<IpNetwork>,
+    /// Domains whose addresses are reached outside the tunnel. A domain starting with `*.`
+    /// matches all of its subdomains.
+    pub domains: Vec<String>,
+}
+
+/// A destination that is reached outside the tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitTunnelDestination {
+    Network(IpNetwork),
+    Domain(String),
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseDestinationError {
+    #[error("Wildcards are only supported as the first label, as in *.example.com: {0}")]
+    Wildcard(String),
+    #[error("Not an IP network or domain name: {0}")]
+    Invalid(String),
+}
+
+impl SplitTunnelDestinations {
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty() && self.domains.is_empty()
+    }
+
+    /// Add a destination. Returns `false` if it was already excluded.
+    pub fn add(&mut self, destination: SplitTunnelDestination) -> bool {
+        match destination {
+            SplitTunnelDestination::Network(network) => {
+                if self.networks.contains(&network) {
+                    return false;
+                }
+                self.networks.push(network);
+            }
+            SplitTunnelDestination::Domain(domain) => {
+                if self.domains.contains(&domain) {
+                    return false;
+                }
+                self.domains.push(domain);
+            }
+        }
+        true
+    }
+
+    /// Remove a destination. Returns `false` if it was not excluded.
+    pub fn remove(&mut self, destination: &SplitTunnelDestination) -> bool {
+        match destination {
+            SplitTunnelDestination::Network(network) => {
+                let len = self.networks.len();
+                self.networks.retain(|excluded| excluded != network);
+                self.networks.len() != len
+            }
+            SplitTunnelDestination::Domain(domain) => {
+                let len = self.domains.len();
+                self.domains.retain(|excluded| excluded != domain);
+                self.domains.len() != len
+            }
+        }
+    }
+}
+
+impl FromStr for SplitTunnelDestination {
+    type Err = ParseDestinationError;
+
+    /// Parse an IP address, a network in CIDR notation or a domain name, which may start with
+    /// `*.` to match all subdomains. Domain names are normalized to lowercase without a trailing
+    /// dot.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(address) = s.parse::<IpAddr>() {
+            return Ok(SplitTunnelDestination::Network(IpNetwork::from(address)));
+        }
+        if let Ok(network) = s.parse::<IpNetwork>() {
+            return Ok(SplitTunnelDestination::Network(network));
+        }
+
+        let domain = s.strip_suffix('.').unwrap_or(s).to_ascii_lowercase();
+        let name = domain.strip_prefix("*.").unwrap_or(&domain);
+        if name.contains('*') {
+            return Err(ParseDestinationError::Wildcard(s.to_owned()));
+        }
+        if !is_valid_domain(name) {
+            return Err(ParseDestinationError::Invalid(s.to_owned()));
+        }
+        Ok(SplitTunnelDestination::Domain(domain))
+    }
+}
+
+impl fmt::Display for SplitTunnelDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitTunnelDestination::Network(network) => network.fmt(f),
+            SplitTunnelDestination::Domain(domain) => domain.fmt(f),
+        }
+    }
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    domain.len() <= MAX_DOMAIN_LEN
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_destination() {
+        assert_eq!(
+            "10.1.0.0/16".parse(),
+            Ok(SplitTunnelDestination::Network(
+                "10.1.0.0/16".parse().unwrap()
+            ))
+        );
+        assert_eq!(
+            "fd00::1".parse(),
+            Ok(SplitTunnelDestination::Network(
+                "fd00::1/128".parse().unwrap()
+            ))
+        );
+        assert_eq!(
+            "Intranet.Corp.Example.".parse(),
+            Ok(SplitTunnelDestination::Domain(
+                "intranet.corp.example".to_owned()
+            ))
+        );
+        assert_eq!(
+            "*.Corp.Example".parse(),
+            Ok(SplitTunnelDestination::Domain("*.corp.example".to_owned()))
+        );
+        assert_eq!(
+            "intranet.*.example".parse::<SplitTunnelDestination>(),
+            Err(ParseDestinationError::Wildcard(
+                "intranet.*.example".to_owned()
+            ))
+        );
+        assert!("*corp.example".parse::<SplitTunnelDestination>().is_err());
+        assert!("*.".parse::<SplitTunnelDestination>().is_err());
+        assert!("corp..example".parse::<SplitTunnelDestination>().is_err());
+        assert!("-corp.example".parse::<SplitTunnelDestination>().is_err());
+        assert!("".parse::<SplitTunnelDestination>().is_err());
+    }
+}
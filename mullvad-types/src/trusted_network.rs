@@ -0,0 +1,211 @@
+//! Networks on which the tunnel should be disconnected automatically.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Trusted networks are not supported on this platform")]
+    Unsupported,
+    #[error("Trusted networks cannot be identified by {0} on this platform")]
+    UnsupportedNetwork(&'static str),
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TrustedNetworkSettings {
+    /// Whether to disconnect on trusted networks and connect on all other networks.
+    pub enabled: bool,
+    pub networks: Vec<TrustedNetwork>,
+}
+
+/// A network identified by the MAC address of its default gateway, or by its Wi-Fi SSID.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustedNetwork {
+    /// Separators and case are ignored when comparing addresses.
+    GatewayMac(String),
+    Ssid(String),
+}
+
+/// Identifiers of the network that the device is currently connected to. Which identifiers are
+/// available depends on the platform: the gateway MAC address is known on desktop platforms, and
+/// the SSID only on Linux with NetworkManager.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CurrentNetwork {
+    pub gateway_mac: Option<String>,
+    pub ssid: Option<String>,
+}
+
+impl TrustedNetworkSettings {
+    /// Returns an error if the settings cannot take effect on the current platform, since the
+    /// current network would never match.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.enabled && !CurrentNetwork::HAS_GATEWAY_MAC && !CurrentNetwork::HAS_SSID {
+            return Err(Error::Unsupported);
+        }
+        match self.networks.iter().find(|network| !network.is_supported()) {
+            Some(TrustedNetwork::GatewayMac(_)) => Err(Error::UnsupportedNetwork("gateway MAC")),
+            Some(TrustedNetwork::Ssid(_)) => Err(Error::UnsupportedNetwork("SSID")),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether `network` is trusted, or `None` if the network cannot be identified.
+    pub fn is_trusted(&self, network: &CurrentNetwork) -> Option<bool> {
+        if network.is_unknown() {
+            return None;
+        }
+        Some(self.networks.iter().any(|trusted| trusted.matches(network)))
+    }
+
+    /// Add a network. Returns `false` if it was already trusted.
+    pub fn add(&mut self, network: TrustedNetwork) -> bool {
+        if self.networks.contains(&network) {
+            return false;
+        }
+        self.networks.push(network);
+        true
+    }
+
+    /// Remove a network. Returns `false` if it was not trusted.
+    pub fn remove(&mut self, network: &TrustedNetwork) -> bool {
+        let len = self.networks.len();
+        self.networks.retain(|trusted| trusted != network);
+        self.networks.len() != len
+    }
+}
+
+impl TrustedNetwork {
+    /// Returns whether networks of this kind can be identified on the current platform.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            TrustedNetwork::GatewayMac(_) => CurrentNetwork::HAS_GATEWAY_MAC,
+            TrustedNetwork::Ssid(_) => CurrentNetwork::HAS_SSID,
+        }
+    }
+
+    pub fn matches(&self, network: &CurrentNetwork) -> bool {
+        match self {
+            TrustedNetwork::GatewayMac(mac) => network
+                .gateway_mac
+                .as_deref()
+                .is_some_and(|current| mac_eq(current, mac)),
+            TrustedNetwork::Ssid(ssid) => network.ssid.as_ref() == Some(ssid),
+        }
+    }
+}
+
+impl fmt::Display for TrustedNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrustedNetwork::GatewayMac(mac) => write!(f, "gateway MAC {mac}"),
+            TrustedNetwork::Ssid(ssid) => write!(f, "SSID {ssid}"),
+        }
+    }
+}
+
+impl CurrentNetwork {
+    /// Whether the gateway MAC address is known on the current platform.
+    pub const HAS_GATEWAY_MAC: bool = cfg!(any(
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "windows"
+    ));
+    /// Whether the SSID is known on the current platform.
+    pub const HAS_SSID: bool = cfg!(target_os = "linux");
+
+    pub fn is_unknown(&self) -> bool {
+        self.gateway_mac.is_none() && self.ssid.is_none()
+    }
+
+    /// Returns an identifier of the network, preferring the gateway MAC address over the SSID.
+    /// Returns `None` if the network cannot be identified.
+    pub fn identity(&self) -> Option<TrustedNetwork> {
+        self.gateway_mac
+            .clone()
+            .map(TrustedNetwork::GatewayMac)
+            .or_else(|| self.ssid.clone().map(TrustedNetwork::Ssid))
+    }
+}
+
+/// Compare two MAC addresses, ignoring separators and case.
+pub fn mac_eq(a: &str, b: &str) -> bool {
+    let normalize = |mac: &str| -> String {
+        mac.chars()
+            .filter(char::is_ascii_hexdigit)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+    normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_trusted() {
+        let settings = TrustedNetworkSettings {
+            enabled: true,
+            networks: vec![
+                TrustedNetwork::GatewayMac("aa:bb:cc:00:11:22".to_owned()),
+                TrustedNetwork::Ssid("Office".to_owned()),
+            ],
+        };
+
+        assert_eq!(settings.is_trusted(&CurrentNetwork::default()), None);
+        assert_eq!(
+            settings.is_trusted(&CurrentNetwork {
+                gateway_mac: Some("AABBCC001122".to_owned()),
+                ssid: None,
+            }),
+            Some(true)
+        );
+        assert_eq!(
+            settings.is_trusted(&CurrentNetwork {
+                gateway_mac: None,
+                ssid: Some("Office".to_owned()),
+            }),
+            Some(true)
+        );
+        assert_eq!(
+            settings.is_trusted(&CurrentNetwork {
+                gateway_mac: None,
+                ssid: Some("Cafe".to_owned()),
+            }),
+            Some(false)
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_validate() {
+        let settings = TrustedNetworkSettings {
+            enabled: true,
+            networks: vec![
+                TrustedNetwork::Ssid("Office".to_owned()),
+                TrustedNetwork::GatewayMac("aa:bb:cc:00:11:22".to_owned()),
+            ],
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn test_validate() {
+        let mut settings = TrustedNetworkSettings {
+            enabled: true,
+            networks: vec![TrustedNetwork::GatewayMac("aa:bb:cc:00:11:22".to_owned())],
+        };
+        assert!(settings.validate().is_ok());
+
+        settings
+            .networks
+            .push(TrustedNetwork::Ssid("Office".to_owned()));
+        assert!(matches!(
+            settings.validate(),
+            Err(Error::UnsupportedNetwork(_))
+        ));
+    }
+}
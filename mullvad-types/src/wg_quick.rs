@@ -0,0 +1,494 @@
+//! Parsing and serialization of `wg-quick` configuration files.
+
+use crate::{custom_tunnel::ConnectionConfig, CustomTunnelEndpoint};
+use ipnetwork::IpNetwork;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+use talpid_types::net::wireguard;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Line {0}: Expected a section header or 'key = value'")]
+    Syntax(usize),
+
+    #[error("Line {0}: Unknown section '{1}'")]
+    UnknownSection(usize, String),
+
+    #[error("Line {0}: Unknown key '{1}'")]
+    UnknownKey(usize, String),
+
+    #[error("Line {0}: Invalid value for '{1}'")]
+    InvalidValue(usize, String),
+
+    #[error("Line {0}: DNS search domains are not supported: '{1}'")]
+    DnsSearchDomain(usize, String),
+
+    #[error("Missing required key '{0}'")]
+    MissingKey(&'static str),
+
+    #[error("Expected exactly one [Peer] section, found {0}")]
+    PeerCount(usize),
+
+    #[error("Unable to determine the IPv4 gateway of the tunnel")]
+    NoIpv4Gateway,
+
+    #[error("Multihop configurations cannot be expressed as a single wg-quick configuration")]
+    Multihop,
+}
+
+/// The subset of a `wg-quick` configuration that is relevant for connecting to a single peer.
+/// Options that only affect `wg-quick` itself, such as `PostUp` or `Table`, are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgQuickConfig {
+    /// Private key of the interface. This is always set for parsed configurations. If it is
+    /// `None`, a placeholder is written in its place, which must be replaced before the
+    /// configuration can be used.
+    pub private_key: Option<wireguard::PrivateKey>,
+    pub addresses: Vec<IpNetwork>,
+    pub dns: Vec<IpAddr>,
+    pub mtu: Option<u16>,
+    pub peer: WgQuickPeer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WgQuickPeer {
+    pub public_key: wireguard::PublicKey,
+    pub preshared_key: Option<wireguard::PresharedKey>,
+    pub allowed_ips: Vec<IpNetwork>,
+    /// Hostname or IP address of the peer.
+    pub host: String,
+    pub port: u16,
+}
+
+/// Written in place of a private key that is not included in a configuration.
+pub const PRIVATE_KEY_PLACEHOLDER: &str = "<replace with the private key of this device>";
+
+/// Keys that are understood by `wg` or `wg-quick`, but which have no effect here.
+const IGNORED_KEYS: &[&str] = &[
+    "listenport",
+    "fwmark",
+    "table",
+    "preup",
+    "postup",
+    "predown",
+    "postdown",
+    "saveconfig",
+    "persistentkeepalive",
+];
+
+#[derive(Default)]
+struct PeerBuilder {
+    public_key: Option<wireguard::PublicKey>,
+    preshared_key: Option<wireguard::PresharedKey>,
+    allowed_ips: Vec<IpNetwork>,
+    endpoint: Option<(String, u16)>,
+}
+
+enum Section {
+    None,
+    Interface,
+    Peer,
+}
+
+impl FromStr for WgQuickConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut section = Section::None;
+        let mut private_key = None;
+        let mut addresses = vec![];
+        let mut dns = vec![];
+        let mut mtu = None;
+        let mut peers: Vec<PeerBuilder> = vec![];
+
+        for (index, line) in s.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                section = match name.trim().to_ascii_lowercase().as_str() {
+                    "interface" => Section::Interface,
+                    "peer" => {
+                        peers.push(PeerBuilder::default());
+                        Section::Peer
+                    }
+                    _ => return Err(Error::UnknownSection(line_number, name.to_owned())),
+                };
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or(Error::Syntax(line_number))?;
+            let (key, value) = (key.trim(), value.trim());
+            let normalized_key = key.to_ascii_lowercase();
+            let invalid = || Error::InvalidValue(line_number, key.to_owned());
+
+            match (&section, normalized_key.as_str()) {
+                (Section::None, _) => return Err(Error::Syntax(line_number)),
+                (_, key) if IGNORED_KEYS.contains(&key) => (),
+                (Section::Interface, "privatekey") => {
+                    private_key =
+                        Some(wireguard::PrivateKey::from_base64(value).map_err(|_| invalid())?);
+                }
+                (Section::Interface, "address") => {
+                    addresses.extend(parse_list::<IpNetwork>(value).ok_or_else(invalid)?);
+                }
+                (Section::Interface, "dns") => {
+                    // Entries that are not IP addresses are search domains, which cannot be
+                    // configured
+                    for entry in split_list(value) {
+                        let server = entry
+                            .parse::<IpAddr>()
+                            .map_err(|_| Error::DnsSearchDomain(line_number, entry.to_owned()))?;
+                        dns.push(server);
+                    }
+                }
+                (Section::Interface, "mtu") => mtu = Some(value.parse().map_err(|_| invalid())?),
+                (Section::Peer, "publickey") => {
+                    let peer = peers.last_mut().expect("a peer section has been started");
+                    peer.public_key =
+                        Some(wireguard::PublicKey::from_base64(value).map_err(|_| invalid())?);
+                }
+                (Section::Peer, "presharedkey") => {
+                    let peer = peers.last_mut().expect("a peer section has been started");
+                    peer.preshared_key =
+                        Some(wireguard::PresharedKey::from_base64(value).map_err(|_| invalid())?);
+                }
+                (Section::Peer, "allowedips") => {
+                    let peer = peers.last_mut().expect("a peer section has been started");
+                    peer.allowed_ips
+                        .extend(parse_list::<IpNetwork>(value).ok_or_else(invalid)?);
+                }
+                (Section::Peer, "endpoint") => {
+                    let peer = peers.last_mut().expect("a peer section has been started");
+                    peer.endpoint = Some(parse_endpoint(value).ok_or_else(invalid)?);
+                }
+                _ => return Err(Error::UnknownKey(line_number, key.to_owned())),
+            }
+        }
+
+        if peers.len() != 1 {
+            return Err(Error::PeerCount(peers.len()));
+        }
+        let peer = peers.pop().unwrap();
+        let (host, port) = peer.endpoint.ok_or(Error::MissingKey("Endpoint"))?;
+
+        Ok(WgQuickConfig {
+            private_key: Some(private_key.ok_or(Error::MissingKey("PrivateKey"))?),
+            addresses,
+            dns,
+            mtu,
+            peer: WgQuickPeer {
+                public_key: peer.public_key.ok_or(Error::MissingKey("PublicKey"))?,
+                preshared_key: peer.preshared_key,
+                allowed_ips: peer.allowed_ips,
+                host,
+                port,
+            },
+        })
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+fn parse_list<T: FromStr>(value: &str) -> Option<Vec<T>> {
+    split_list(value).map(|entry| entry.parse().ok()).collect()
+}
+
+/// Parse `host:port`, where an IPv6 host is enclosed in brackets.
+fn parse_endpoint(value: &str) -> Option<(String, u16)> {
+    let (host, port) = value.rsplit_once(':')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_owned(), port.parse().ok()?))
+}
+
+impl fmt::Display for WgQuickConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: ToString>(items: &[T]) -> String {
+            items
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        writeln!(f, "[Interface]")?;
+        match &self.private_key {
+            Some(private_key) => writeln!(f, "PrivateKey = {}", private_key.to_base64())?,
+            None => writeln!(f, "PrivateKey = {PRIVATE_KEY_PLACEHOLDER}")?,
+        }
+        if !self.addresses.is_empty() {
+            writeln!(f, "Address = {}", join(&self.addresses))?;
+        }
+        if !self.dns.is_empty() {
+            writeln!(f, "DNS = {}", join(&self.dns))?;
+        }
+        if let Some(mtu) = self.mtu {
+            writeln!(f, "MTU = {mtu}")?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "[Peer]")?;
+        writeln!(f, "PublicKey = {}", self.peer.public_key.to_base64())?;
+        if let Some(psk) = &self.peer.preshared_key {
+            writeln!(f, "PresharedKey = {}", psk.to_base64())?;
+        }
+        writeln!(f, "AllowedIPs = {}", join(&self.peer.allowed_ips))?;
+        if self.peer.host.contains(':') {
+            writeln!(f, "Endpoint = [{}]:{}", self.peer.host, self.peer.port)
+        } else {
+            writeln!(f, "Endpoint = {}:{}", self.peer.host, self.peer.port)
+        }
+    }
+}
+
+impl WgQuickConfig {
+    /// Create a configuration from the connection config of a WireGuard tunnel. The private key
+    /// is not included, see [`WgQuickConfig::private_key`].
+    pub fn from_connection_config(
+        config: &wireguard::ConnectionConfig,
+        dns: Vec<IpAddr>,
+        mtu: Option<u16>,
+    ) -> Result<Self, Error> {
+        if config.exit_peer.is_some() {
+            return Err(Error::Multihop);
+        }
+        Ok(WgQuickConfig {
+            private_key: None,
+            addresses: config
+                .tunnel
+                .addresses
+                .iter()
+                .map(|address| IpNetwork::from(*address))
+                .collect(),
+            dns,
+            mtu,
+            peer: WgQuickPeer {
+                public_key: config.peer.public_key.clone(),
+                preshared_key: config.peer.psk.clone(),
+                allowed_ips: config.peer.allowed_ips.clone(),
+                host: config.peer.endpoint.ip().to_string(),
+                port: config.peer.endpoint.port(),
+            },
+        })
+    }
+
+    /// Convert the configuration to a custom tunnel endpoint.
+    ///
+    /// `wg-quick` configurations do not contain the in-tunnel address of the peer, which is
+    /// needed for connectivity checks. If `ipv4_gateway` is not given, it is guessed: a DNS server
+    /// that is routed through the tunnel is assumed to be the peer, and otherwise the first host
+    /// address of the interface's network is. The IPv6 gateway is always guessed this way. If the
+    /// guess is wrong, the tunnel is considered to be down, so the gateway should be given
+    /// explicitly whenever it is known.
+    pub fn to_custom_tunnel_endpoint(
+        &self,
+        ipv4_gateway: Option<Ipv4Addr>,
+    ) -> Result<CustomTunnelEndpoint, Error> {
+        let private_key = self
+            .private_key
+            .clone()
+            .ok_or(Error::MissingKey("PrivateKey"))?;
+        let ipv4_gateway = match ipv4_gateway {
+            Some(gateway) => gateway,
+            None => {
+                let gateway = self
+                    .guess_gateway(IpAddr::is_ipv4)
+                    .and_then(as_ipv4)
+                    .ok_or(Error::NoIpv4Gateway)?;
+                log::warn!("Assuming that the IPv4 gateway of the imported tunnel is {gateway}");
+                gateway
+            }
+        };
+        let ipv6_gateway = self.guess_gateway(IpAddr::is_ipv6).and_then(as_ipv6);
+
+        let config = wireguard::ConnectionConfig {
+            tunnel: wireguard::TunnelConfig {
+                private_key,
+                addresses: self.addresses.iter().map(IpNetwork::ip).collect(),
+            },
+            peer: wireguard::PeerConfig {
+                public_key: self.peer.public_key.clone(),
+                allowed_ips: self.peer.allowed_ips.clone(),
+                // The address is resolved from the host when connecting
+                endpoint: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), self.peer.port),
+                psk: None,
+                #[cfg(daita)]
+                constant_packet_size: false,
+            },
+            exit_peer: None,
+            ipv4_gateway,
+            ipv6_gateway,
+            #[cfg(target_os = "linux")]
+            fwmark: Some(crate::TUNNEL_FWMARK),
+        };
+
+        let mut endpoint =
+            CustomTunnelEndpoint::new(self.peer.host.clone(), ConnectionConfig::Wireguard(config));
+        endpoint.psk = self.peer.preshared_key.clone();
+        Ok(endpoint)
+    }
+
+    /// Guess the in-tunnel address of the peer. A DNS server inside the tunnel is usually the
+    /// peer itself. Otherwise, assume that the peer has the first address of the interface's
+    /// network.
+    fn guess_gateway(&self, family: fn(&IpAddr) -> bool) -> Option<IpAddr> {
+        let is_own_address = |ip: &IpAddr| self.addresses.iter().any(|net| net.ip() == *ip);
+        let routed_dns = self.dns.iter().find(|dns| {
+            family(dns)
+                && !is_own_address(dns)
+                && self.peer.allowed_ips.iter().any(|net| net.contains(**dns))
+        });
+        if let Some(dns) = routed_dns {
+            return Some(*dns);
+        }
+
+        self.addresses
+            .iter()
+            .filter(|net| family(&net.ip()))
+            .find_map(|net| {
+                let first_host = match net {
+                    IpNetwork::V4(net) if net.prefix() < 31 => {
+                        IpAddr::from(Ipv4Addr::from(u32::from(net.network()) + 1))
+                    }
+                    IpNetwork::V6(net) if net.prefix() < 127 => {
+                        IpAddr::from(Ipv6Addr::from(u128::from(net.network()) + 1))
+                    }
+                    _ => return None,
+                };
+                (!is_own_address(&first_host)).then_some(first_host)
+            })
+    }
+}
+
+fn as_ipv4(ip: IpAddr) -> Option<Ipv4Addr> {
+    match ip {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    }
+}
+
+fn as_ipv6(ip: IpAddr) -> Option<Ipv6Addr> {
+    match ip {
+        IpAddr::V4(_) => None,
+        IpAddr::V6(ip) => Some(ip),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# Exported from some provider
+[Interface]
+PrivateKey = mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI=
+Address = 10.8.0.2/24, fd00::2/64
+DNS = 10.8.0.1
+MTU = 1380
+PostUp = echo up
+
+[Peer]
+PublicKey = 7wJ9gsBBNYMZtotQtrvhtxk07gmGcUCuCDTBLZGyE3I=
+PresharedKey = cVLCyLbbcVcGJfGV7zzWthhTGcTMdZZzwTqYdjzmBvk=
+AllowedIPs = 0.0.0.0/0, ::/0
+Endpoint = vpn.example.com:51820
+PersistentKeepalive = 25
+"#;
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+        assert_eq!(config.addresses.len(), 2);
+        assert_eq!(config.dns, vec!["10.8.0.1".parse::<IpAddr>().unwrap()]);
+        assert_eq!(config.mtu, Some(1380));
+        assert_eq!(config.peer.host, "vpn.example.com");
+        assert_eq!(config.peer.port, 51820);
+        assert!(config.peer.preshared_key.is_some());
+
+        let reparsed: WgQuickConfig = config.to_string().parse().unwrap();
+        assert_eq!(config, reparsed);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            "[Interface]\nPrivateKey = mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI=\n"
+                .parse::<WgQuickConfig>(),
+            Err(Error::PeerCount(0))
+        ));
+        assert!(matches!(
+            "[Interface]\nFoo = bar\n".parse::<WgQuickConfig>(),
+            Err(Error::UnknownKey(2, _))
+        ));
+        assert!(matches!(
+            "[Interface]\nMTU = large\n".parse::<WgQuickConfig>(),
+            Err(Error::InvalidValue(2, _))
+        ));
+        assert!(matches!(
+            "[Interface]\nDNS = 10.8.0.1, example.com\n".parse::<WgQuickConfig>(),
+            Err(Error::DnsSearchDomain(2, domain)) if domain == "example.com"
+        ));
+    }
+
+    #[test]
+    fn test_export_omits_private_key() {
+        let mut config: WgQuickConfig = CONFIG.parse().unwrap();
+        config.private_key = None;
+        let exported = config.to_string();
+        assert!(exported.contains(&format!("PrivateKey = {PRIVATE_KEY_PLACEHOLDER}")));
+        assert!(!exported.contains("mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI="));
+        assert!(matches!(
+            exported.parse::<WgQuickConfig>(),
+            Err(Error::InvalidValue(_, key)) if key == "PrivateKey"
+        ));
+        assert!(matches!(
+            config.to_custom_tunnel_endpoint(None),
+            Err(Error::MissingKey("PrivateKey"))
+        ));
+    }
+
+    #[test]
+    fn test_to_custom_tunnel_endpoint() {
+        let config: WgQuickConfig = CONFIG.parse().unwrap();
+        let endpoint = config.to_custom_tunnel_endpoint(None).unwrap();
+        assert_eq!(endpoint.host, "vpn.example.com");
+        assert_eq!(endpoint.psk, config.peer.preshared_key);
+        let ConnectionConfig::Wireguard(connection) = endpoint.config else {
+            panic!("expected a WireGuard config");
+        };
+        assert_eq!(
+            connection.ipv4_gateway,
+            "10.8.0.1".parse::<Ipv4Addr>().unwrap()
+        );
+        assert_eq!(connection.ipv6_gateway, Some("fd00::1".parse().unwrap()));
+
+        // Without DNS servers or a network prefix, the gateway cannot be guessed
+        let mut config = config;
+        config.dns.clear();
+        config.addresses = vec!["10.8.0.2/32".parse().unwrap()];
+        assert!(matches!(
+            config.to_custom_tunnel_endpoint(None),
+            Err(Error::NoIpv4Gateway)
+        ));
+        assert!(config
+            .to_custom_tunnel_endpoint(Some("10.8.0.1".parse().unwrap()))
+            .is_ok());
+    }
+}
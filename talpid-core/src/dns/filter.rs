@@ -0,0 +1,147 @@
+//! Domain blocklists enforced by the local DNS forwarder.
+use std::{collections::HashSet, fmt, net::IpAddr};
+
+/// A set of blocked domains, and domains that are never blocked. Blocking or allowing a domain
+/// also applies to all of its subdomains. Allowed domains take precedence over blocked ones.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct DnsFilter {
+    blocked: HashSet<String>,
+    allowed: HashSet<String>,
+}
+
+impl DnsFilter {
+    /// Create a filter that blocks nothing and never blocks `allowed_domains`.
+    pub fn new<S: AsRef<str>>(allowed_domains: impl IntoIterator<Item = S>) -> Self {
+        let allowed = allowed_domains
+            .into_iter()
+            .filter_map(|domain| normalize_domain(domain.as_ref()))
+            .collect();
+        Self {
+            blocked: HashSet::new(),
+            allowed,
+        }
+    }
+
+    /// Block all domains in a hosts file or a list of domains, one per line. Lines in hosts files
+    /// start with an IP address, which is ignored. `#` starts a comment. Returns the number of
+    /// domains in the list.
+    pub fn add_list(&mut self, list: &str) -> usize {
+        let mut count = 0;
+        for line in list.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace().peekable();
+            if tokens
+                .peek()
+                .is_some_and(|token| token.parse::<IpAddr>().is_ok())
+            {
+                tokens.next();
+            }
+            for domain in tokens.filter_map(normalize_domain) {
+                self.blocked.insert(domain);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Return whether queries for `name` should be blocked.
+    pub fn is_blocked(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        let mut blocked = false;
+        for suffix in suffixes(&name) {
+            if self.allowed.contains(suffix) {
+                return false;
+            }
+            blocked |= self.blocked.contains(suffix);
+        }
+        blocked
+    }
+
+    /// Return the number of blocked domains.
+    pub fn len(&self) -> usize {
+        self.blocked.len()
+    }
+
+    /// Return whether no domains are blocked.
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty()
+    }
+}
+
+impl fmt::Debug for DnsFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Blocklists may contain hundreds of thousands of domains
+        f.debug_struct("DnsFilter")
+            .field("blocked", &self.blocked.len())
+            .field("allowed", &self.allowed.len())
+            .finish()
+    }
+}
+
+/// Return `name` and all its parent domains, e.g. `a.b.c`, `b.c`, and `c`.
+fn suffixes(name: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(name), |name| {
+        name.split_once('.').map(|(_, parent)| parent)
+    })
+}
+
+/// Return the domain in lowercase, or `None` if it is not a domain that should be filtered.
+/// Single-label names like `localhost` are never filtered.
+fn normalize_domain(domain: &str) -> Option<String> {
+    // Adblock-style rules, e.g. `||example.com^`
+    let domain = domain.strip_prefix("||").unwrap_or(domain);
+    let domain = domain.strip_suffix('^').unwrap_or(domain);
+    let domain = domain.trim_end_matches('.');
+
+    let is_valid = domain.contains('.')
+        && domain.parse::<IpAddr>().is_err()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+    is_valid.then(|| domain.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIST: &str = "\
+# Comment
+127.0.0.1 localhost
+0.0.0.0 ads.example.com tracker.example.net # trailing comment
+:: Telemetry.Example.org.
+blocked.test
+||adblock.example^
+not a domain!
+";
+
+    #[test]
+    fn test_add_list() {
+        let mut filter = DnsFilter::new(["allowed.ads.example.com"]);
+        assert_eq!(filter.add_list(LIST), 5);
+
+        assert!(filter.is_blocked("ads.example.com"));
+        assert!(filter.is_blocked("sub.ads.example.com."));
+        assert!(filter.is_blocked("TELEMETRY.example.org"));
+        assert!(filter.is_blocked("adblock.example"));
+        assert!(filter.is_blocked("blocked.test"));
+
+        assert!(!filter.is_blocked("localhost"));
+        assert!(!filter.is_blocked("example.com"));
+        assert!(!filter.is_blocked("notads.example.com"));
+    }
+
+    #[test]
+    fn test_allowed_domains() {
+        let mut filter = DnsFilter::new(["allowed.ads.example.com", "tracker.example.net"]);
+        filter.add_list(LIST);
+
+        assert!(!filter.is_blocked("allowed.ads.example.com"));
+        assert!(!filter.is_blocked("sub.allowed.ads.example.com"));
+        assert!(filter.is_blocked("other.ads.example.com"));
+        assert!(!filter.is_blocked("tracker.example.net"));
+    }
+}
@@ -0,0 +1,743 @@
+//! A local DNS resolver that forwards queries to plain or encrypted resolvers, and that can block
+//! queries for domains in a [DnsFilter].
+//!
+//! While connected, the system DNS is pointed at [LISTEN_ADDRESS] if forwarding is enabled in the
+//! [DnsConfig](super::DnsConfig). The forwarder is only running in the connected state. Its
+//! upstream queries are sent by the daemon, so the firewall does not restrict them, but they are
+//! sent through the tunnel since they follow the routes of the tunnel.
+//!
+//! The addresses in responses for [ExcludedDomains] are reported so that they can be excluded from
+//! the tunnel before the response reaches the application. On Linux, queries for these domains are
+//! instead sent to the resolvers of the physical network, from sockets marked with the firewall
+//! mark. Only these marked queries may reach the resolvers outside the tunnel.
+use futures::channel::{mpsc, oneshot};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+#[cfg(target_os = "linux")]
+use std::{future::Future, pin::Pin};
+
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    proto::{
+        op::{Header, MessageType, OpCode, ResponseCode},
+        rr::{RData, Record},
+    },
+    resolver::{
+        config::{
+            NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts,
+            TlsClientConfig,
+        },
+        error::ResolveErrorKind,
+        TokioAsyncResolver,
+    },
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+    ServerFuture,
+};
+#[cfg(target_os = "linux")]
+use hickory_server::{
+    proto::iocompat::AsyncIoTokioAsStd,
+    resolver::{
+        name_server::{GenericConnector, RuntimeProvider, TokioRuntimeProvider},
+        AsyncResolver,
+    },
+};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, ClientConfig, RootCertStore, ServerName,
+};
+use sha2::{Digest, Sha256};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer};
+
+use super::filter::DnsFilter;
+use talpid_types::net::dns::{EncryptedDnsError, EncryptedDnsProtocol, EncryptedDnsResolver};
+
+/// Address that the forwarder listens on.
+#[cfg(target_os = "macos")]
+pub const LISTEN_ADDRESS: Ipv4Addr = Ipv4Addr::LOCALHOST;
+/// Address that the forwarder listens on. This avoids conflicting with systemd-resolved, which
+/// listens on 127.0.0.53 and 127.0.0.54.
+#[cfg(not(target_os = "macos"))]
+pub const LISTEN_ADDRESS: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 35);
+
+const LISTEN_PORT: u16 = 53;
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to hold back a response for an excluded domain while its addresses are excluded.
+const EXCLUDE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Forwarder errors
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Invalid resolver configuration
+    #[error("Invalid encrypted DNS resolver")]
+    InvalidResolver(#[from] EncryptedDnsError),
+
+    /// Failed to bind UDP socket
+    #[error("Failed to bind UDP socket")]
+    UdpBind(#[source] io::Error),
+
+    /// Failed to bind TCP socket
+    #[error("Failed to bind TCP socket")]
+    TcpBind(#[source] io::Error),
+}
+
+/// Local forwarding of DNS queries, requested through [DnsConfig](super::DnsConfig).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForwarderConfig {
+    /// Encrypted resolvers to forward queries to. If empty, queries are forwarded to the
+    /// resolvers that would otherwise have been used.
+    pub encrypted_resolvers: Vec<EncryptedDnsResolver>,
+    /// Domains to block.
+    pub filter: Option<Arc<DnsFilter>>,
+    /// Domains whose addresses are reached outside the tunnel.
+    pub excluded_domains: Option<ExcludedDomains>,
+}
+
+impl ForwarderConfig {
+    /// Return whether queries should be sent to the local forwarder.
+    pub fn is_enabled(&self) -> bool {
+        !self.encrypted_resolvers.is_empty()
+            || self.filter.is_some()
+            || self.excluded_domains.is_some()
+    }
+}
+
+/// Receives the addresses in a response for an excluded domain, when their records expire, and a
+/// sender that should be used once the addresses have been excluded from the tunnel.
+pub type ExcludedAddressesSender =
+    mpsc::UnboundedSender<(Vec<IpAddr>, Instant, oneshot::Sender<()>)>;
+
+/// Domains whose addresses are reached outside the tunnel.
+#[derive(Clone)]
+pub struct ExcludedDomains {
+    /// Domain names. A name starting with `*.` matches all subdomains of the rest of the name.
+    pub domains: Vec<String>,
+    /// Resolvers of the physical network that queries for excluded domains are sent to, outside
+    /// the tunnel. Only used on Linux. If empty, the regular upstream resolvers are used.
+    pub resolvers: Vec<IpAddr>,
+    /// Where to report addresses of excluded domains.
+    pub addresses_tx: ExcludedAddressesSender,
+}
+
+impl ExcludedDomains {
+    /// Return whether `name` matches any of the excluded domains.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        self.domains
+            .iter()
+            .any(|domain| match domain.strip_prefix("*.") {
+                Some(parent) => name
+                    .strip_suffix(parent)
+                    .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+                None => name == *domain,
+            })
+    }
+}
+
+impl PartialEq for ExcludedDomains {
+    fn eq(&self, other: &Self) -> bool {
+        self.domains == other.domains
+            && self.resolvers == other.resolvers
+            && self.addresses_tx.same_receiver(&other.addresses_tx)
+    }
+}
+
+impl fmt::Debug for ExcludedDomains {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExcludedDomains")
+            .field("domains", &self.domains)
+            .field("resolvers", &self.resolvers)
+            .finish()
+    }
+}
+
+/// Resolvers that the forwarder sends queries to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Upstream {
+    /// Unencrypted resolvers listening on port 53.
+    Plain(Vec<IpAddr>),
+    /// DNS-over-HTTPS and DNS-over-TLS resolvers.
+    Encrypted(Vec<EncryptedDnsResolver>),
+}
+
+/// A DNS server that forwards queries to a set of resolvers, optionally blocking some domains.
+pub struct DnsForwarder {
+    config: Option<(Upstream, Option<Arc<DnsFilter>>, Option<ExcludedDomains>)>,
+    server: Option<ServerFuture<ForwardingHandler>>,
+    /// Mark of queries for excluded domains
+    #[cfg(target_os = "linux")]
+    fwmark: u32,
+}
+
+impl DnsForwarder {
+    /// Create a forwarder that is not forwarding anything.
+    pub fn new(#[cfg(target_os = "linux")] fwmark: u32) -> Self {
+        Self {
+            config: None,
+            server: None,
+            #[cfg(target_os = "linux")]
+            fwmark,
+        }
+    }
+
+    /// Start forwarding queries to `upstream`, blocking domains in `filter` and reporting the
+    /// addresses of `excluded_domains`. The server is restarted if it is already running with a
+    /// different configuration.
+    pub async fn start(
+        &mut self,
+        upstream: Upstream,
+        filter: Option<Arc<DnsFilter>>,
+        excluded_domains: Option<ExcludedDomains>,
+    ) -> Result<(), Error> {
+        let config = (upstream, filter, excluded_domains);
+        if self.server.is_some() && self.config.as_ref() == Some(&config) {
+            return Ok(());
+        }
+        self.stop().await;
+
+        let (upstream, filter, excluded_domains) = &config;
+        let resolver = match upstream {
+            Upstream::Plain(addresses) => create_plain_resolver(addresses),
+            Upstream::Encrypted(resolvers) => create_encrypted_resolver(resolvers)?,
+        };
+        let excluded = excluded_domains.clone().map(|domains| {
+            #[cfg(target_os = "linux")]
+            let resolver = (!domains.resolvers.is_empty())
+                .then(|| create_excluded_resolver(&domains.resolvers, self.fwmark));
+            #[cfg(not(target_os = "linux"))]
+            let resolver = None;
+            (domains, resolver)
+        });
+        let mut server = ServerFuture::new(ForwardingHandler {
+            resolver,
+            filter: filter.clone(),
+            excluded,
+        });
+
+        let listen_addr = SocketAddr::new(IpAddr::V4(LISTEN_ADDRESS), LISTEN_PORT);
+        let udp_socket = tokio::net::UdpSocket::bind(listen_addr)
+            .await
+            .map_err(Error::UdpBind)?;
+        let tcp_listener = tokio::net::TcpListener::bind(listen_addr)
+            .await
+            .map_err(Error::TcpBind)?;
+        server.register_socket(udp_socket);
+        server.register_listener(tcp_listener, TCP_TIMEOUT);
+
+        log::debug!(
+            "Forwarding DNS queries from {listen_addr} to {upstream:?}, filter: {:?}, excluded \
+             domains: {:?}",
+            filter,
+            excluded_domains
+        );
+        self.server = Some(server);
+        self.config = Some(config);
+
+        Ok(())
+    }
+
+    /// Stop the server, if it is running.
+    pub async fn stop(&mut self) {
+        self.config = None;
+        if let Some(mut server) = self.server.take() {
+            log::debug!("Stopping DNS forwarder");
+            if let Err(error) = server.shutdown_gracefully().await {
+                log::error!("DNS forwarder stopped unexpectedly: {error}");
+            }
+        }
+    }
+}
+
+fn create_plain_resolver(addresses: &[IpAddr]) -> TokioAsyncResolver {
+    // Make sure not to accidentally forward queries to ourselves
+    let addresses: Vec<_> = addresses
+        .iter()
+        .copied()
+        .filter(|addr| *addr != IpAddr::V4(LISTEN_ADDRESS))
+        .collect();
+    let servers = NameServerConfigGroup::from_ips_clear(&addresses, 53, true);
+    let config = ResolverConfig::from_parts(None, vec![], servers);
+
+    let mut options = ResolverOpts::default();
+    options.timeout = RESOLVER_TIMEOUT;
+
+    TokioAsyncResolver::tokio(config, options)
+}
+
+/// Resolver whose queries are sent outside the tunnel.
+#[cfg(target_os = "linux")]
+pub type ExcludedResolver = AsyncResolver<GenericConnector<MarkedRuntimeProvider>>;
+
+/// Create a resolver that sends queries to `addresses` from sockets marked with `fwmark`, so that
+/// they are routed outside the tunnel. The firewall only allows marked queries to the resolvers
+/// of excluded domains.
+#[cfg(target_os = "linux")]
+pub fn create_excluded_resolver(addresses: &[IpAddr], fwmark: u32) -> ExcludedResolver {
+    let servers = NameServerConfigGroup::from_ips_clear(addresses, 53, true);
+    let config = ResolverConfig::from_parts(None, vec![], servers);
+
+    let mut options = ResolverOpts::default();
+    options.timeout = RESOLVER_TIMEOUT;
+
+    let provider = MarkedRuntimeProvider {
+        inner: TokioRuntimeProvider::new(),
+        fwmark,
+    };
+    AsyncResolver::new(config, options, GenericConnector::new(provider))
+}
+
+/// A [RuntimeProvider] that marks all sockets with `fwmark`.
+#[cfg(target_os = "linux")]
+#[derive(Clone)]
+pub struct MarkedRuntimeProvider {
+    inner: TokioRuntimeProvider,
+    fwmark: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl RuntimeProvider for MarkedRuntimeProvider {
+    type Handle = <TokioRuntimeProvider as RuntimeProvider>::Handle;
+    type Timer = <TokioRuntimeProvider as RuntimeProvider>::Timer;
+    type Udp = tokio::net::UdpSocket;
+    type Tcp = AsyncIoTokioAsStd<tokio::net::TcpStream>;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.inner.create_handle()
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        let fwmark = self.fwmark;
+        Box::pin(async move {
+            let socket = match server_addr {
+                SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+            };
+            socket2::SockRef::from(&socket).set_mark(fwmark)?;
+            let stream = socket.connect(server_addr).await?;
+            Ok(AsyncIoTokioAsStd(stream))
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        _server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        let fwmark = self.fwmark;
+        Box::pin(async move {
+            let socket = tokio::net::UdpSocket::bind(local_addr).await?;
+            socket2::SockRef::from(&socket).set_mark(fwmark)?;
+            Ok(socket)
+        })
+    }
+}
+
+fn create_encrypted_resolver(
+    resolvers: &[EncryptedDnsResolver],
+) -> Result<TokioAsyncResolver, Error> {
+    let mut config = ResolverConfig::new();
+    for resolver in resolvers {
+        resolver.validate()?;
+
+        let tls_config = TlsClientConfig(Arc::new(client_config(resolver)?));
+        let protocol = match resolver.protocol {
+            EncryptedDnsProtocol::Https => Protocol::Https,
+            EncryptedDnsProtocol::Tls => Protocol::Tls,
+        };
+        for address in &resolver.addresses {
+            let mut server =
+                NameServerConfig::new(SocketAddr::new(*address, resolver.port()), protocol);
+            server.tls_dns_name = Some(resolver.hostname.clone());
+            server.tls_config = Some(tls_config.clone());
+            server.trust_negative_responses = true;
+            config.add_name_server(server);
+        }
+    }
+
+    let mut options = ResolverOpts::default();
+    options.timeout = RESOLVER_TIMEOUT;
+
+    Ok(TokioAsyncResolver::tokio(config, options))
+}
+
+/// Return a TLS config that accepts certificates valid for the resolver hostname, or if any SPKI
+/// pins are set, only certificate chains containing a pinned public key.
+fn client_config(resolver: &EncryptedDnsResolver) -> Result<ClientConfig, Error> {
+    let pins = resolver.decoded_spki_pins()?;
+    let verifier: Arc<dyn ServerCertVerifier> = if pins.is_empty() {
+        Arc::new(WebPkiVerifier::new(root_store(), None))
+    } else {
+        Arc::new(PinnedVerifier { pins })
+    };
+
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth())
+}
+
+fn root_store() -> RootCertStore {
+    let mut root_store = RootCertStore::empty();
+    root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    root_store
+}
+
+/// Accepts certificate chains in which the SubjectPublicKeyInfo of any certificate hashes to one
+/// of `pins`.
+///
+/// If the end-entity certificate is pinned, it is accepted as is. The handshake signature is still
+/// verified against it, so the server must own the pinned key. If only an intermediate or root
+/// certificate is pinned, the chain is verified with that certificate as the only trust anchor,
+/// including the server name and validity period, since anyone could present a copy of it.
+struct PinnedVerifier {
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let is_pinned = |certificate: &Certificate| {
+            let spki = subject_public_key_info(&certificate.0).ok_or_else(|| {
+                rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+            })?;
+            let digest: [u8; 32] = Sha256::digest(spki).into();
+            Ok::<_, rustls::Error>(self.pins.contains(&digest))
+        };
+
+        if is_pinned(end_entity)? {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        let mut trust_anchors = RootCertStore::empty();
+        for certificate in intermediates {
+            if is_pinned(certificate)? {
+                trust_anchors.add(certificate)?;
+            }
+        }
+        if trust_anchors.is_empty() {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+        WebPkiVerifier::new(trust_anchors, None).verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/// Return the DER encoded SubjectPublicKeyInfo of an X.509 certificate.
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+    Some(certificate.tbs_certificate.subject_pki.raw)
+}
+
+/// An implementation of [RequestHandler] that forwards queries to a [TokioAsyncResolver].
+struct ForwardingHandler {
+    resolver: TokioAsyncResolver,
+    filter: Option<Arc<DnsFilter>>,
+    /// Excluded domains, and a resolver outside the tunnel to use for them
+    #[cfg(target_os = "linux")]
+    excluded: Option<(ExcludedDomains, Option<ExcludedResolver>)>,
+    /// Excluded domains. These are resolved using `resolver`.
+    #[cfg(not(target_os = "linux"))]
+    excluded: Option<(ExcludedDomains, Option<TokioAsyncResolver>)>,
+}
+
+impl ForwardingHandler {
+    async fn lookup<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handler: R,
+    ) -> io::Result<ResponseInfo> {
+        let query = request.query().original();
+        let builder = MessageResponseBuilder::from_message_request(request);
+
+        if let Some(filter) = &self.filter {
+            if filter.is_blocked(&query.name().to_ascii()) {
+                log::trace!("Blocking DNS query for {}", query.name());
+                let response = builder.error_msg(request.header(), ResponseCode::NXDomain);
+                return response_handler.send_response(response).await;
+            }
+        }
+
+        let mut header = Header::response_from_request(request.header());
+        header.set_recursion_available(true);
+
+        let excluded = self
+            .excluded
+            .as_ref()
+            .filter(|(domains, _)| domains.matches(&query.name().to_ascii()));
+        let result = match excluded.and_then(|(_, resolver)| resolver.as_ref()) {
+            Some(resolver) => {
+                resolver
+                    .lookup(query.name().clone(), query.query_type())
+                    .await
+            }
+            None => {
+                self.resolver
+                    .lookup(query.name().clone(), query.query_type())
+                    .await
+            }
+        };
+
+        match result {
+            Ok(lookup) => {
+                if let Some((domains, _)) = excluded {
+                    let addresses: Vec<IpAddr> = lookup.iter().filter_map(RData::ip_addr).collect();
+                    if !addresses.is_empty() {
+                        exclude_addresses(domains, addresses, lookup.valid_until()).await;
+                    }
+                }
+                let records: Vec<Record> = lookup.record_iter().cloned().collect();
+                let response = builder.build(header, records.iter(), [], [], []);
+                response_handler.send_response(response).await
+            }
+            Err(error) => {
+                let response_code = match error.kind() {
+                    ResolveErrorKind::NoRecordsFound { response_code, .. } => *response_code,
+                    _ => {
+                        log::debug!("Failed to forward DNS query: {error}");
+                        ResponseCode::ServFail
+                    }
+                };
+                let response = builder.error_msg(request.header(), response_code);
+                response_handler.send_response(response).await
+            }
+        }
+    }
+}
+
+/// Report the addresses of an excluded domain, and wait until they have been excluded so that the
+/// first connection made after the lookup does not go through the tunnel.
+async fn exclude_addresses(
+    domains: &ExcludedDomains,
+    addresses: Vec<IpAddr>,
+    valid_until: Instant,
+) {
+    let (done_tx, done_rx) = oneshot::channel();
+    if domains
+        .addresses_tx
+        .unbounded_send((addresses, valid_until, done_tx))
+        .is_err()
+    {
+        return;
+    }
+    if tokio::time::timeout(EXCLUDE_TIMEOUT, done_rx)
+        .await
+        .is_err()
+    {
+        log::warn!("Timed out waiting for addresses of excluded domain to be excluded");
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for ForwardingHandler {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        if !request.src().ip().is_loopback() {
+            log::error!("Dropping a stray request from outside: {}", request.src());
+            return Header::new().into();
+        }
+        if request.message_type() != MessageType::Query || request.op_code() != OpCode::Query {
+            log::trace!("Dropping non-query request: {:?}", request);
+            return Header::new().into();
+        }
+
+        match self.lookup(request, response_handle).await {
+            Ok(info) => info,
+            Err(error) => {
+                log::error!("Failed to send DNS response: {error}");
+                Header::new().into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// ISRG Root X2, the ECDSA root of Let's Encrypt.
+    const ISRG_ROOT_X2: &str = "\
+-----BEGIN CERTIFICATE-----
+MIICGzCCAaGgAwIBAgIQQdKd0XLq7qeAwSxs6S+HUjAKBggqhkjOPQQDAzBPMQsw
+CQYDVQQGEwJVUzEpMCcGA1UEChMgSW50ZXJuZXQgU2VjdXJpdHkgUmVzZWFyY2gg
+R3JvdXAxFTATBgNVBAMTDElTUkcgUm9vdCBYMjAeFw0yMDA5MDQwMDAwMDBaFw00
+MDA5MTcxNjAwMDBaME8xCzAJBgNVBAYTAlVTMSkwJwYDVQQKEyBJbnRlcm5ldCBT
+ZWN1cml0eSBSZXNlYXJjaCBHcm91cDEVMBMGA1UEAxMMSVNSRyBSb290IFgyMHYw
+EAYHKoZIzj0CAQYFK4EEACIDYgAEzZvVn4CDCuwJSvMWSj5cz3es3mcFDR0HttwW
++1qLFNvicWDEukWVEYmO6gbf9yoWHKS5xcUy4APgHoIYOIvXRdgKam7mAHf7AlF9
+ItgKbppbd9/w+kHsOdx1ymgHDB/qo0IwQDAOBgNVHQ8BAf8EBAMCAQYwDwYDVR0T
+AQH/BAUwAwEB/zAdBgNVHQ4EFgQUfEKWrt5LSDv6kviejM9ti6lyN5UwCgYIKoZI
+zj0EAwMDaAAwZQIwe3lORlCEwkSHRhtFcP9Ymd70/aTSVaYgLXTWNLxBo1BfASdW
+tL4ndQavEi51mI38AjEAi/V3bNTIZargCyzuFJ0nN6T5U6VR5CmD1/iQMVtCnwr1
+/q4AaOeMSQ+2b1tbFfLn
+-----END CERTIFICATE-----";
+
+    /// Base64 encoded SHA-256 digest of the SubjectPublicKeyInfo of [ISRG_ROOT_X2].
+    const ISRG_ROOT_X2_PIN: &str = "diGVwiVYbubAI3RW4hB9xU8e/CH2GnkuvVFZE8zmgzI=";
+
+    /// A CA certificate, which is pinned in [test_pinned_intermediate].
+    const PINNED_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBnjCCAUWgAwIBAgIULYYD+nHGOwtWJqTfUCDog6mO07EwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRRXhhbXBsZSBQaW5uZWQgQ0EwIBcNMjYxMDE4MTQxODEzWhgP
+MjEyNjA5MjQxNDE4MTNaMBwxGjAYBgNVBAMMEUV4YW1wbGUgUGlubmVkIENBMFkw
+EwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEUSc2ad9tjeLx2ta8bBGxrqhqH3GRstWD
+wvf3okgTXObzKMS5LaJH3fc8Kwt6OCKrsrZmPRqCrL5tMzZ9i3jM/aNjMGEwHQYD
+VR0OBBYEFBzWX+0wPHCxT5cmCwia5+LR9i7+MB8GA1UdIwQYMBaAFBzWX+0wPHCx
+T5cmCwia5+LR9i7+MA8GA1UdEwEB/wQFMAMBAf8wDgYDVR0PAQH/BAQDAgEGMAoG
+CCqGSM49BAMCA0cAMEQCIDX+qi36CEQstkj2szloq9osy7VTl2hhMKR8Mb0CP3Jg
+AiAdsAFohLAZPZYt7rPKzMoUv3GdC+RFExd23Vd3YHBxpw==
+-----END CERTIFICATE-----";
+
+    /// A certificate for `dns.example.com` issued by [PINNED_CA].
+    const PINNED_CA_LEAF: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBzTCCAXOgAwIBAgIUUNgR+fxy6BL0cF4ysQiVmnfpFGEwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRRXhhbXBsZSBQaW5uZWQgQ0EwIBcNMjYxMDE4MTQxODEzWhgP
+MjEyNjA5MjQxNDE4MTNaMBoxGDAWBgNVBAMMD2Rucy5leGFtcGxlLmNvbTBZMBMG
+ByqGSM49AgEGCCqGSM49AwEHA0IABKOHJ23qtQoE1QKNd1JzLLbNHKuyKO230P+v
+kByh1HFkmF3kDq5njkwUVKLsYf5B4K1t9LGbW3zhTRMRHJ0IsyOjgZIwgY8wDAYD
+VR0TAQH/BAIwADAOBgNVHQ8BAf8EBAMCB4AwEwYDVR0lBAwwCgYIKwYBBQUHAwEw
+GgYDVR0RBBMwEYIPZG5zLmV4YW1wbGUuY29tMB0GA1UdDgQWBBTN1HfwzL9k1gA4
+7ivLWavgHMqToDAfBgNVHSMEGDAWgBQc1l/tMDxwsU+XJgsImufi0fYu/jAKBggq
+hkjOPQQDAgNIADBFAiEAuwAFOz6GyYWB343kZIA2qG1bbvGM/gPJ/d7vlgrC/4YC
+IC6JdT+9dXJQ7H1xOwh2vGceElNJJrg+2OqP8uJT0nd4
+-----END CERTIFICATE-----";
+
+    /// A self-signed certificate for `dns.example.com`.
+    const FORGED_LEAF: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBujCCAWCgAwIBAgIUOXYI/T/WZPDpMMXnHHdcCph5OV0wCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPZG5zLmV4YW1wbGUuY29tMCAXDTI2MTAxODE0MTgxM1oYDzIx
+MjYwOTI0MTQxODEzWjAaMRgwFgYDVQQDDA9kbnMuZXhhbXBsZS5jb20wWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAASNZB2NV5UJjZSNrAFOwwMP9EQp4cex0Etn8W2+
+z1/Je6s0v1nvFnN3VHg2VMCPPoBKs1cSwjC3Okw8ZIRgCKwfo4GBMH8wHQYDVR0O
+BBYEFHr8wmZDpZ3GGtLhaJbPaffIhqBlMB8GA1UdIwQYMBaAFHr8wmZDpZ3GGtLh
+aJbPaffIhqBlMAwGA1UdEwEB/wQCMAAwEwYDVR0lBAwwCgYIKwYBBQUHAwEwGgYD
+VR0RBBMwEYIPZG5zLmV4YW1wbGUuY29tMAoGCCqGSM49BAMCA0gAMEUCIBNUIO/R
+uq4hzi9oypbvhsLlPvJK+Tvjcj9Jp0s/4eccAiEA3+3gFLTjFzYGVPEBi1XE9x12
+TSMPTyyOo46u9nhdAuE=
+-----END CERTIFICATE-----";
+
+    fn pin() -> [u8; 32] {
+        let mut resolver = EncryptedDnsResolver::from_url("tls://1.1.1.1").unwrap();
+        resolver.spki_pins = vec![ISRG_ROOT_X2_PIN.to_owned()];
+        resolver.decoded_spki_pins().unwrap()[0]
+    }
+
+    fn certificate_der() -> Vec<u8> {
+        pem_to_der(ISRG_ROOT_X2)
+    }
+
+    fn pem_to_der(pem: &str) -> Vec<u8> {
+        let (_, pem) = x509_parser::pem::parse_x509_pem(pem.as_bytes()).unwrap();
+        pem.contents
+    }
+
+    #[test]
+    fn test_excluded_domains() {
+        let (addresses_tx, _addresses_rx) = mpsc::unbounded();
+        let domains = ExcludedDomains {
+            domains: vec![
+                "intranet.corp.example".to_owned(),
+                "*.lab.example".to_owned(),
+            ],
+            resolvers: vec![],
+            addresses_tx,
+        };
+
+        assert!(domains.matches("intranet.corp.example"));
+        assert!(domains.matches("Intranet.Corp.Example."));
+        assert!(!domains.matches("www.intranet.corp.example"));
+        assert!(!domains.matches("corp.example"));
+
+        assert!(domains.matches("build.lab.example"));
+        assert!(domains.matches("a.build.lab.example."));
+        assert!(!domains.matches("lab.example"));
+        assert!(!domains.matches("otherlab.example"));
+    }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        let certificate = certificate_der();
+        let spki = subject_public_key_info(&certificate).unwrap();
+        assert_eq!(<[u8; 32]>::from(Sha256::digest(spki)), pin());
+
+        assert!(subject_public_key_info(&certificate[..certificate.len() / 2]).is_none());
+        assert!(subject_public_key_info(&[]).is_none());
+    }
+
+    #[test]
+    fn test_pinned_verifier() {
+        let certificate = Certificate(certificate_der());
+        let server_name = ServerName::try_from("example.com").unwrap();
+        let verify = |pin: [u8; 32]| {
+            PinnedVerifier { pins: vec![pin] }.verify_server_cert(
+                &certificate,
+                &[],
+                &server_name,
+                &mut std::iter::empty(),
+                &[],
+                SystemTime::now(),
+            )
+        };
+
+        assert!(verify(pin()).is_ok());
+        assert!(verify([0; 32]).is_err());
+    }
+
+    /// A pinned CA must only be accepted if it has issued the end-entity certificate for the
+    /// server name, since anyone can present a copy of it.
+    #[test]
+    fn test_pinned_intermediate() {
+        let ca = Certificate(pem_to_der(PINNED_CA));
+        let leaf = Certificate(pem_to_der(PINNED_CA_LEAF));
+        let forged_leaf = Certificate(pem_to_der(FORGED_LEAF));
+        let ca_pin: [u8; 32] = Sha256::digest(subject_public_key_info(&ca.0).unwrap()).into();
+        let verifier = PinnedVerifier { pins: vec![ca_pin] };
+        let verify = |end_entity: &Certificate, server_name: &str| {
+            verifier.verify_server_cert(
+                end_entity,
+                std::slice::from_ref(&ca),
+                &ServerName::try_from(server_name).unwrap(),
+                &mut std::iter::empty(),
+                &[],
+                SystemTime::now(),
+            )
+        };
+
+        assert!(verify(&leaf, "dns.example.com").is_ok());
+        assert!(verify(&leaf, "other.example.com").is_err());
+        assert!(verify(&forged_leaf, "dns.example.com").is_err());
+    }
+}
@@ -25,16 +25,28 @@ mod imp;
 
 pub use self::imp::Error;
 
+/// Domain blocklists
+#[cfg(not(target_os = "android"))]
+pub mod filter;
+/// A local resolver that forwards queries to other resolvers
+#[cfg(not(target_os = "android"))]
+pub mod forwarder;
+
 /// DNS configuration
 #[derive(Debug, Clone, PartialEq)]
 pub struct DnsConfig {
     config: InnerDnsConfig,
+    /// How the local forwarder should handle queries, if at all
+    #[cfg(not(target_os = "android"))]
+    forwarder: forwarder::ForwarderConfig,
 }
 
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
             config: InnerDnsConfig::Default,
+            #[cfg(not(target_os = "android"))]
+            forwarder: forwarder::ForwarderConfig::default(),
         }
     }
 }
@@ -47,6 +59,40 @@ impl DnsConfig {
                 tunnel_config: tunnel_config.to_owned(),
                 non_tunnel_config: non_tunnel_config.to_owned(),
             },
+            #[cfg(not(target_os = "android"))]
+            forwarder: forwarder::ForwarderConfig::default(),
+        }
+    }
+
+    /// Send queries to a local forwarder while connected. Unless `forwarder` contains encrypted
+    /// resolvers, the forwarder sends queries to the resolvers that would otherwise be used.
+    #[cfg(not(target_os = "android"))]
+    pub fn with_forwarder(mut self, forwarder: forwarder::ForwarderConfig) -> Self {
+        self.forwarder = forwarder;
+        self
+    }
+
+    /// Return the configuration of the local forwarder.
+    #[cfg(not(target_os = "android"))]
+    pub fn forwarder(&self) -> &forwarder::ForwarderConfig {
+        &self.forwarder
+    }
+}
+
+impl DnsConfig {
+    /// Return all addresses used for DNS resolution. `default_tun_config` is returned unless the
+    /// addresses have been overridden.
+    pub fn addresses(&self, default_tun_config: &[IpAddr]) -> Vec<IpAddr> {
+        match &self.config {
+            InnerDnsConfig::Default => default_tun_config.to_owned(),
+            InnerDnsConfig::Override {
+                tunnel_config,
+                non_tunnel_config,
+            } => tunnel_config
+                .iter()
+                .chain(non_tunnel_config)
+                .copied()
+                .collect(),
         }
     }
 }
@@ -72,12 +118,20 @@ impl DnsConfig {
         default_tun_config: &[IpAddr],
         #[cfg(target_os = "macos")] port: u16,
     ) -> ResolvedDnsConfig {
+        #[cfg(not(target_os = "android"))]
+        if !self.forwarder.encrypted_resolvers.is_empty() {
+            // Encrypted resolvers are only reachable through the forwarder
+            return ResolvedDnsConfig::forwarder();
+        }
+
         match &self.config {
             InnerDnsConfig::Default => ResolvedDnsConfig {
                 tunnel_config: default_tun_config.to_owned(),
                 non_tunnel_config: vec![],
                 #[cfg(target_os = "macos")]
                 port,
+                #[cfg(not(target_os = "android"))]
+                forwarded: self.forwarder.is_enabled(),
             },
             InnerDnsConfig::Override {
                 tunnel_config,
@@ -87,6 +141,8 @@ impl DnsConfig {
                 non_tunnel_config: non_tunnel_config.to_owned(),
                 #[cfg(target_os = "macos")]
                 port,
+                #[cfg(not(target_os = "android"))]
+                forwarded: self.forwarder.is_enabled(),
             },
         }
     }
@@ -104,6 +160,10 @@ pub struct ResolvedDnsConfig {
     /// Port to use
     #[cfg(target_os = "macos")]
     port: u16,
+    /// Whether queries are sent to these resolvers by the local forwarder, rather than by the
+    /// system.
+    #[cfg(not(target_os = "android"))]
+    forwarded: bool,
 }
 
 impl fmt::Display for ResolvedDnsConfig {
@@ -117,6 +177,11 @@ impl fmt::Display for ResolvedDnsConfig {
         #[cfg(target_os = "macos")]
         write!(f, " Port: {}", self.port)?;
 
+        #[cfg(not(target_os = "android"))]
+        if self.forwarded {
+            f.write_str(" (forwarded)")?;
+        }
+
         Ok(())
     }
 }
@@ -133,6 +198,24 @@ impl ResolvedDnsConfig {
         f.write_str("}")
     }
 
+    /// Config that points the system at the local forwarder.
+    #[cfg(not(target_os = "android"))]
+    pub(crate) fn forwarder() -> Self {
+        ResolvedDnsConfig {
+            tunnel_config: vec![],
+            non_tunnel_config: vec![IpAddr::V4(forwarder::LISTEN_ADDRESS)],
+            #[cfg(target_os = "macos")]
+            port: 53,
+            forwarded: false,
+        }
+    }
+
+    /// Return whether queries are sent to these resolvers by the local forwarder.
+    #[cfg(not(target_os = "android"))]
+    pub fn is_forwarded(&self) -> bool {
+        self.forwarded
+    }
+
     /// Addresses to configure on the tunnel interface
     pub fn tunnel_config(&self) -> &[IpAddr] {
         &self.tunnel_config
@@ -6,15 +6,21 @@ use nftnl::{
     nft_expr, table, Batch, Chain, FinalizedBatch, ProtoFamily, Rule, Table,
 };
 use std::{
+    collections::HashSet,
     env,
-    ffi::CStr,
+    ffi::{CStr, CString},
     fs, io,
     net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
     sync::LazyLock,
+    thread,
 };
-use talpid_types::net::{
-    AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol, ALLOWED_LAN_MULTICAST_NETS,
-    ALLOWED_LAN_NETS,
+use talpid_types::{
+    net::{
+        AllowedEndpoint, AllowedTunnelTraffic, Endpoint, LanAccess, TransportProtocol,
+        ALLOWED_LAN_MULTICAST_NETS,
+    },
+    split_tunnel::SplitTunnelMode,
 };
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
@@ -52,6 +58,26 @@ pub enum Error {
     /// Unable to translate network interface name into index.
     #[error("Unable to translate network interface name \"{0}\" into index")]
     LookupIfaceIndexError(String, #[source] crate::linux::IfaceIndexLookupError),
+
+    /// A program needed to render rulesets is not installed.
+    #[error("Unable to find the \"{0}\" program")]
+    ProgramNotFound(&'static str),
+
+    /// Failed to execute a program needed to render rulesets.
+    #[error("Failed to execute \"{0}\"")]
+    RunProgram(&'static str, #[source] io::Error),
+
+    /// A program needed to render rulesets exited with an error.
+    #[error("\"{0}\" failed: {1}")]
+    ProgramFailed(&'static str, String),
+
+    /// Failed to create the network namespace in which rulesets are rendered.
+    #[error("Failed to create a network namespace for rendering the ruleset")]
+    CreateNamespace(#[source] nix::Error),
+
+    /// The thread rendering a ruleset panicked.
+    #[error("The thread rendering the ruleset panicked")]
+    RenderThreadPanicked,
 }
 
 /// TODO(linus): This crate is not supposed to be Mullvad-aware. So at some point this should be
@@ -64,6 +90,9 @@ const PREROUTING_CHAIN_NAME: &CStr = c"prerouting";
 const MANGLE_CHAIN_NAME: &CStr = c"mangle";
 const NAT_CHAIN_NAME: &CStr = c"nat";
 
+const NFT_PROGRAM: &str = "nft";
+const IP_PROGRAM: &str = "ip";
+
 /// Allows controlling whether firewall rules should have packet counters or not from an env
 /// variable. Useful for debugging the rules.
 static ADD_COUNTERS: LazyLock<bool> = LazyLock::new(|| {
@@ -98,25 +127,101 @@ enum End {
 /// The Linux implementation for the firewall and DNS.
 pub struct Firewall {
     fwmark: u32,
+    split_tunnel_mode: SplitTunnelMode,
+    policy: Option<FirewallPolicy>,
 }
 
 impl Firewall {
     pub fn from_args(args: FirewallArguments) -> Result<Self> {
-        Firewall::new(args.fwmark)
+        Ok(Firewall {
+            fwmark: args.fwmark,
+            split_tunnel_mode: args.split_tunnel_mode,
+            policy: None,
+        })
     }
 
     pub fn new(fwmark: u32) -> Result<Self> {
-        Ok(Firewall { fwmark })
+        Ok(Firewall {
+            fwmark,
+            split_tunnel_mode: SplitTunnelMode::default(),
+            policy: None,
+        })
+    }
+
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> bool {
+        let changed = self.split_tunnel_mode != mode;
+        self.split_tunnel_mode = mode;
+        changed
     }
 
     pub fn apply_policy(&mut self, policy: FirewallPolicy) -> Result<()> {
         let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
-        let batch = PolicyBatch::new(&table).finalize(&policy, self.fwmark)?;
+        let batch =
+            PolicyBatch::new(&table).finalize(&policy, self.fwmark, self.split_tunnel_mode)?;
         Self::send_and_process(&batch)?;
         Self::apply_kernel_config(&policy);
+        self.policy = Some(policy);
         self.verify_tables(&[TABLE_NAME])
     }
 
+    pub fn policy(&self) -> Option<&FirewallPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Return a function that renders the ruleset that `policy` would install, in nft syntax,
+    /// without applying it. The function runs external programs, so it may block for some time.
+    ///
+    /// The ruleset is installed in a new network namespace and listed from there using `nft`.
+    /// The tunnel interface referenced by the policy, if any, is created in that namespace as a
+    /// dummy interface, since rules matching on it can only be built for existing interfaces.
+    pub fn policy_renderer(
+        &self,
+        policy: &FirewallPolicy,
+    ) -> impl FnOnce() -> Result<String> + Send + 'static {
+        let policy = policy.clone();
+        let fwmark = self.fwmark;
+        let split_tunnel_mode = self.split_tunnel_mode;
+        move || Self::render_policy(policy, fwmark, split_tunnel_mode)
+    }
+
+    fn render_policy(
+        policy: FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<String> {
+        let nft = find_program(NFT_PROGRAM)?;
+        let ip = find_program(IP_PROGRAM)?;
+
+        // Network namespaces are per thread, so unsharing one only affects the spawned thread and
+        // the programs it executes.
+        thread::spawn(move || {
+            nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)
+                .map_err(Error::CreateNamespace)?;
+            if let Some(tunnel) = policy.tunnel() {
+                run_program(
+                    IP_PROGRAM,
+                    duct::cmd!(&ip, "link", "add", &tunnel.interface, "type", "dummy"),
+                )?;
+            }
+
+            let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
+            let batch = PolicyBatch::new(&table).finalize(&policy, fwmark, split_tunnel_mode)?;
+            Self::send_and_process(&batch)?;
+            list_table(&nft)
+        })
+        .join()
+        .unwrap_or(Err(Error::RenderThreadPanicked))
+    }
+
+    /// Render the currently installed ruleset in nft syntax. Returns `None` if no ruleset is
+    /// installed.
+    pub fn render_active_ruleset() -> Result<Option<String>> {
+        if !Self::get_tables()?.contains(TABLE_NAME) {
+            return Ok(None);
+        }
+        list_table(&find_program(NFT_PROGRAM)?).map(Some)
+    }
+
     pub fn reset_policy(&mut self) -> Result<()> {
         let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
         let mut batch = Batch::new();
@@ -133,6 +238,7 @@ impl Firewall {
 
         log::debug!("Removing table and chain from netfilter");
         Self::send_and_process(&batch)?;
+        self.policy = None;
 
         Ok(())
     }
@@ -186,6 +292,20 @@ impl Firewall {
     }
 
     fn verify_tables(&self, expected_tables: &[&CStr]) -> Result<()> {
+        let table_set = Self::get_tables()?;
+        for expected_table in expected_tables {
+            if !table_set.contains(*expected_table) {
+                log::error!(
+                    "Expected '{}' netfilter table to be set, but it is not",
+                    expected_table.to_string_lossy()
+                );
+                return Err(Error::NetfilterTableNotSetError);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_tables() -> Result<HashSet<CString>> {
         let socket = mnl::Socket::new(mnl::Bus::Netfilter).map_err(Error::NetlinkOpenError)?;
         let portid = socket.portid();
         let seq = 0;
@@ -195,7 +315,7 @@ impl Firewall {
             .send(&get_tables_msg)
             .map_err(Error::NetlinkSendError)?;
 
-        let mut table_set = std::collections::HashSet::new();
+        let mut table_set = HashSet::new();
         let mut msg_buffer = vec![0; nftnl::nft_nlmsg_maxsize() as usize];
 
         while let Some(message) = Self::socket_recv(&socket, &mut msg_buffer)? {
@@ -209,17 +329,7 @@ impl Firewall {
                 mnl::CbResult::Ok => log::trace!("cb_run OK"),
             }
         }
-
-        for expected_table in expected_tables {
-            if !table_set.contains(*expected_table) {
-                log::error!(
-                    "Expected '{}' netfilter table to be set, but it is not",
-                    expected_table.to_string_lossy()
-                );
-                return Err(Error::NetfilterTableNotSetError);
-            }
-        }
-        Ok(())
+        Ok(table_set)
     }
 
     fn socket_recv<'a>(socket: &mnl::Socket, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>> {
@@ -301,9 +411,14 @@ impl<'a> PolicyBatch<'a> {
 
     /// Finalize the nftnl message batch by adding every firewall rule needed to satisfy the given
     /// policy.
-    pub fn finalize(mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<FinalizedBatch> {
+    pub fn finalize(
+        mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<FinalizedBatch> {
         self.add_loopback_rules()?;
-        self.add_split_tunneling_rules(policy, fwmark)?;
+        self.add_split_tunneling_rules(policy, fwmark, split_tunnel_mode)?;
         self.add_dhcp_client_rules();
         self.add_ndp_rules();
         self.add_policy_specific_rules(policy, fwmark)?;
@@ -311,7 +426,12 @@ impl<'a> PolicyBatch<'a> {
         Ok(self.batch.finalize())
     }
 
-    fn add_split_tunneling_rules(&mut self, policy: &FirewallPolicy, fwmark: u32) -> Result<()> {
+    fn add_split_tunneling_rules(
+        &mut self,
+        policy: &FirewallPolicy,
+        fwmark: u32,
+        split_tunnel_mode: SplitTunnelMode,
+    ) -> Result<()> {
         // Send select DNS requests in the tunnel
         if let FirewallPolicy::Connected {
             tunnel, dns_config, ..
@@ -335,24 +455,50 @@ impl<'a> PolicyBatch<'a> {
             }
         }
 
-        // Split tunneled processes have their PIDs added to a net_cls cgroup.
-        // This causes all packets sent by that process to be marked with the
-        // cgroups classid (`NET_CLS_CLASSID`). This rule checks incoming packets for that classid.
-        // If the packet has the classid set then the packet will have two new marks applied to it.
-        // The `split_tunnel::MARK` as a connection tracking mark and the `fwmark` as packet
-        // metadata.
-        let mut rule = Rule::new(&self.mangle_chain);
-        rule.add_expr(&nft_expr!(meta cgroup));
-        rule.add_expr(&nft_expr!(cmp == split_tunnel::NET_CLS_CLASSID));
-        // Loads `split_tunnel::MARK` into first nftnl register
-        rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
-        // Sets `split_tunnel::MARK` as connection tracker mark
-        rule.add_expr(&nft_expr!(ct mark set));
-        // Loads `fwmark` into first nftnl register
-        rule.add_expr(&nft_expr!(immediate data fwmark));
-        // Sets `fwmark` as metadata mark for packet
-        rule.add_expr(&nft_expr!(meta mark set));
-        self.batch.add(&rule, nftnl::MsgType::Add);
+        let Some(cgroup_match) = split_tunnel_cgroup_match(policy, split_tunnel_mode) else {
+            // Nothing may bypass the tunnel, so neither mark nor accept split tunneled traffic
+            return Ok(());
+        };
+        let cmp_op = match cgroup_match {
+            CgroupMatch::Member => expr::CmpOp::Eq,
+            CgroupMatch::NotMember => expr::CmpOp::Neq,
+        };
+
+        if let Some(cgroup_id) = split_tunnel::cgroup2_id() {
+            // On systems without the net_cls controller, split tunneled processes are instead
+            // added to a cgroup v2 group. Packets are matched on the cgroup of their socket.
+            // Without support in libnftnl, traffic from these processes cannot be identified
+            if *SOCKET_CGROUPV2_SUPPORTED {
+                let mut rule = Rule::new(&self.mangle_chain);
+                rule.add_expr(&SocketCgroupV2 {
+                    level: split_tunnel::CGROUP2_LEVEL,
+                });
+                rule.add_expr(&expr::Cmp::new(cmp_op, &cgroup_id.to_ne_bytes()[..]));
+                add_split_tunnel_marks(&mut rule, fwmark);
+                self.batch.add(&rule, nftnl::MsgType::Add);
+            }
+        } else {
+            // Split tunneled processes have their PIDs added to a net_cls cgroup.
+            // This causes all packets sent by that process to be marked with the
+            // cgroups classid (`NET_CLS_CLASSID`). This rule checks incoming packets for that
+            // classid. If the packet has the classid set then the packet will have two new marks
+            // applied to it. The `split_tunnel::MARK` as a connection tracking mark and the
+            // `fwmark` as packet metadata.
+            let mut rule = Rule::new(&self.mangle_chain);
+            rule.add_expr(&nft_expr!(meta cgroup));
+            rule.add_expr(&expr::Cmp::new(cmp_op, split_tunnel::NET_CLS_CLASSID));
+            add_split_tunnel_marks(&mut rule, fwmark);
+            self.batch.add(&rule, nftnl::MsgType::Add);
+        }
+
+        // Packets to excluded destinations are marked the same way as packets from excluded
+        // processes, regardless of the split tunnel mode.
+        for network in policy.excluded_destinations() {
+            let mut rule = Rule::new(&self.mangle_chain);
+            check_net(&mut rule, End::Dst, *network);
+            add_split_tunnel_marks(&mut rule, fwmark);
+            self.batch.add(&rule, nftnl::MsgType::Add);
+        }
 
         for chain in &[&self.in_chain, &self.out_chain, &self.forward_chain] {
             let mut rule = Rule::new(chain);
@@ -541,8 +687,10 @@ impl<'a> PolicyBatch<'a> {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                excluded_destinations: _,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
                 self.add_allow_endpoint_rules(allowed_endpoint);
@@ -565,7 +713,7 @@ impl<'a> PolicyBatch<'a> {
                             self.add_allow_in_tunnel_endpoint_rules(&tunnel.interface, endpoint2)?;
                         }
                     }
-                    if *allow_lan {
+                    if *allow_lan || !lan_access.allowed_networks.is_empty() {
                         self.add_block_cve_2019_14899(tunnel);
                     }
                 }
@@ -575,7 +723,10 @@ impl<'a> PolicyBatch<'a> {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 dns_config,
+                excluded_destinations: _,
+                excluded_resolvers,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
 
@@ -603,18 +754,20 @@ impl<'a> PolicyBatch<'a> {
                         *server,
                     )?;
                 }
+                self.add_allow_excluded_dns_rules(&tunnel.interface, excluded_resolvers, fwmark)?;
 
                 // Important to block DNS *before* we allow the tunnel and allow LAN. So DNS
                 // can't leak to the wrong IPs in the tunnel or on the LAN.
                 self.add_drop_dns_rule();
                 self.add_allow_tunnel_rules(&tunnel.interface)?;
-                if *allow_lan {
+                if *allow_lan || !lan_access.allowed_networks.is_empty() {
                     self.add_block_cve_2019_14899(tunnel);
                 }
                 *allow_lan
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                lan_access: _,
                 allowed_endpoint,
             } => {
                 if let Some(endpoint) = allowed_endpoint {
@@ -628,8 +781,9 @@ impl<'a> PolicyBatch<'a> {
         };
 
         if allow_lan {
-            self.add_allow_lan_rules();
+            self.add_allow_lan_rules(policy.lan_access());
         }
+        self.add_allowed_networks_rules(&policy.lan_access().allowed_networks);
 
         // Reject any remaining outgoing traffic
         for chain in &[&self.out_chain, &self.forward_chain] {
@@ -778,6 +932,48 @@ impl<'a> PolicyBatch<'a> {
         Ok(())
     }
 
+    /// Allows the daemon to query `resolvers` outside the tunnel for excluded domains. Only
+    /// queries marked with `fwmark` are allowed.
+    fn add_allow_excluded_dns_rules(
+        &mut self,
+        tunnel_interface: &str,
+        resolvers: &[IpAddr],
+        fwmark: u32,
+    ) -> Result<()> {
+        for resolver in resolvers {
+            for protocol in [TransportProtocol::Udp, TransportProtocol::Tcp] {
+                // Mark responses with fwmark so that they pass the reverse path filter
+                let mut prerouting_rule = Rule::new(&self.prerouting_chain);
+                check_not_iface(&mut prerouting_rule, Direction::In, tunnel_interface)?;
+                check_ip(&mut prerouting_rule, End::Src, *resolver);
+                check_port(&mut prerouting_rule, protocol, End::Src, 53);
+                prerouting_rule.add_expr(&nft_expr!(immediate data fwmark));
+                prerouting_rule.add_expr(&nft_expr!(meta mark set));
+                self.batch.add(&prerouting_rule, nftnl::MsgType::Add);
+
+                let mut in_rule = Rule::new(&self.in_chain);
+                check_not_iface(&mut in_rule, Direction::In, tunnel_interface)?;
+                check_ip(&mut in_rule, End::Src, *resolver);
+                check_port(&mut in_rule, protocol, End::Src, 53);
+                let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+                in_rule.add_expr(&nft_expr!(ct state));
+                in_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+                in_rule.add_expr(&nft_expr!(cmp != 0u32));
+                add_verdict(&mut in_rule, &Verdict::Accept);
+                self.batch.add(&in_rule, nftnl::MsgType::Add);
+
+                let mut out_rule = Rule::new(&self.out_chain);
+                check_ip(&mut out_rule, End::Dst, *resolver);
+                check_port(&mut out_rule, protocol, End::Dst, 53);
+                out_rule.add_expr(&nft_expr!(meta mark));
+                out_rule.add_expr(&nft_expr!(cmp == fwmark));
+                add_verdict(&mut out_rule, &Verdict::Accept);
+                self.batch.add(&out_rule, nftnl::MsgType::Add);
+            }
+        }
+        Ok(())
+    }
+
     /// Blocks all outgoing DNS (port 53) on both TCP and UDP
     fn add_drop_dns_rule(&mut self) {
         for chain in &[&self.out_chain, &self.forward_chain] {
@@ -857,11 +1053,11 @@ impl<'a> PolicyBatch<'a> {
         }
     }
 
-    fn add_allow_lan_rules(&mut self) {
+    fn add_allow_lan_rules(&mut self, lan_access: &LanAccess) {
         // Output and forward chains
         for chain in &[&self.out_chain, &self.forward_chain] {
             // LAN -> LAN
-            for net in &*ALLOWED_LAN_NETS {
+            for net in lan_access.lan_networks() {
                 let mut out_rule = Rule::new(chain);
                 check_net(&mut out_rule, End::Dst, *net);
                 add_verdict(&mut out_rule, &Verdict::Accept);
@@ -879,13 +1075,54 @@ impl<'a> PolicyBatch<'a> {
 
         // Input chain
         // LAN -> LAN
-        for net in &*ALLOWED_LAN_NETS {
+        for net in lan_access.lan_networks() {
+            if lan_access.inbound_ports.is_empty() {
+                let mut in_rule = Rule::new(&self.in_chain);
+                check_net(&mut in_rule, End::Src, *net);
+                add_verdict(&mut in_rule, &Verdict::Accept);
+                self.batch.add(&in_rule, nftnl::MsgType::Add);
+                continue;
+            }
+
+            // Only accept new connections to the allowed ports, but accept responses to any
+            // connection initiated by this host
+            let mut established_rule = Rule::new(&self.in_chain);
+            check_net(&mut established_rule, End::Src, *net);
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            established_rule.add_expr(&nft_expr!(ct state));
+            established_rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            established_rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(&mut established_rule, &Verdict::Accept);
+            self.batch.add(&established_rule, nftnl::MsgType::Add);
+
+            for port in &lan_access.inbound_ports {
+                for protocol in [TransportProtocol::Tcp, TransportProtocol::Udp] {
+                    let mut in_rule = Rule::new(&self.in_chain);
+                    check_net(&mut in_rule, End::Src, *net);
+                    check_port(&mut in_rule, protocol, End::Dst, *port);
+                    add_verdict(&mut in_rule, &Verdict::Accept);
+                    self.batch.add(&in_rule, nftnl::MsgType::Add);
+                }
+            }
+        }
+        self.add_dhcp_server_rules();
+    }
+
+    /// Allow all traffic to and from `networks`, regardless of whether LAN traffic is allowed.
+    fn add_allowed_networks_rules(&mut self, networks: &[IpNetwork]) {
+        for net in networks {
+            for chain in &[&self.out_chain, &self.forward_chain] {
+                let mut out_rule = Rule::new(chain);
+                check_net(&mut out_rule, End::Dst, *net);
+                add_verdict(&mut out_rule, &Verdict::Accept);
+                self.batch.add(&out_rule, nftnl::MsgType::Add);
+            }
+
             let mut in_rule = Rule::new(&self.in_chain);
             check_net(&mut in_rule, End::Src, *net);
             add_verdict(&mut in_rule, &Verdict::Accept);
             self.batch.add(&in_rule, nftnl::MsgType::Add);
         }
-        self.add_dhcp_server_rules();
     }
 
     fn add_dhcp_server_rules(&mut self) {
@@ -988,7 +1225,7 @@ fn check_net(rule: &mut Rule<'_>, end: End, net: impl Into<IpNetwork>) {
         IpNetwork::V4(_) => rule.add_expr(&nft_expr!(bitwise mask net.mask(), xor 0u32)),
         IpNetwork::V6(_) => rule.add_expr(&nft_expr!(bitwise mask net.mask(), xor &[0u16; 8][..])),
     };
-    rule.add_expr(&nft_expr!(cmp == net.ip()));
+    rule.add_expr(&nft_expr!(cmp == net.network()));
 }
 
 fn check_icmpv6(rule: &mut Rule<'_>, r#type: u8, code: u8) {
@@ -1071,6 +1308,116 @@ fn add_verdict(rule: &mut Rule<'_>, verdict: &expr::Verdict) {
     rule.add_expr(verdict);
 }
 
+/// Which packets to mark as split tunneled, based on whether they were sent from the split tunnel
+/// cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CgroupMatch {
+    /// Mark packets sent by processes in the cgroup.
+    Member,
+    /// Mark packets sent by processes outside the cgroup.
+    NotMember,
+}
+
+/// Return which packets bypass the tunnel under `policy`, or `None` if no packets may bypass it.
+///
+/// In the inverse mode, every process *outside* the cgroup bypasses the tunnel. That must never
+/// happen in the blocked state, or every process would be able to leak.
+fn split_tunnel_cgroup_match(
+    policy: &FirewallPolicy,
+    split_tunnel_mode: SplitTunnelMode,
+) -> Option<CgroupMatch> {
+    match (split_tunnel_mode, policy) {
+        (SplitTunnelMode::Exclude, _) => Some(CgroupMatch::Member),
+        (SplitTunnelMode::Include, FirewallPolicy::Blocked { .. }) => None,
+        (SplitTunnelMode::Include, _) => Some(CgroupMatch::NotMember),
+    }
+}
+
+/// Mark a packet, and its connection, as belonging to a split tunneled process.
+fn add_split_tunnel_marks(rule: &mut Rule<'_>, fwmark: u32) {
+    // Loads `split_tunnel::MARK` into first nftnl register
+    rule.add_expr(&nft_expr!(immediate data split_tunnel::MARK));
+    // Sets `split_tunnel::MARK` as connection tracker mark
+    rule.add_expr(&nft_expr!(ct mark set));
+    // Loads `fwmark` into first nftnl register
+    rule.add_expr(&nft_expr!(immediate data fwmark));
+    // Sets `fwmark` as metadata mark for packet
+    rule.add_expr(&nft_expr!(meta mark set));
+}
+
+/// Whether the linked libnftnl can build [`SocketCgroupV2`] expressions. The `level` attribute
+/// of the socket expression was added in libnftnl 1.2.0, which is newer than any version that the
+/// `nftnl` bindings can require, so support is detected at runtime.
+static SOCKET_CGROUPV2_SUPPORTED: LazyLock<bool> = LazyLock::new(|| {
+    let expr = SocketCgroupV2 { level: 0 }.build();
+    if let Some(expr) = expr {
+        // SAFETY: `expr` was allocated by libnftnl and is not used after this.
+        unsafe { nftnl::nftnl_sys::nftnl_expr_free(expr) };
+    } else {
+        log::error!(
+            "libnftnl does not support matching the cgroup of sockets. Split tunneled traffic \
+            will not bypass the tunnel"
+        );
+    }
+    expr.is_some()
+});
+
+/// Loads the ID of the cgroup v2 ancestor, at `level`, of the socket that sent a packet into the
+/// first nftnl register. This is equivalent to `socket cgroupv2 level <level>` in nft, which
+/// `nftnl` has no expression type for.
+struct SocketCgroupV2 {
+    level: u32,
+}
+
+impl SocketCgroupV2 {
+    /// Returns `None` if libnftnl does not know about any of the attributes of the expression.
+    fn build(&self) -> Option<*mut nftnl::nftnl_sys::nftnl_expr> {
+        use nftnl::nftnl_sys as sys;
+
+        // Attributes of the socket expression, from `libnftnl/expr.h`
+        const NFTNL_EXPR_SOCKET_KEY: u16 = 1;
+        const NFTNL_EXPR_SOCKET_DREG: u16 = 2;
+        const NFTNL_EXPR_SOCKET_LEVEL: u16 = 3;
+        // `NFT_SOCKET_CGROUPV2` from `linux/netfilter/nf_tables.h`
+        const NFT_SOCKET_CGROUPV2: u32 = 3;
+
+        // SAFETY: The expression is checked for null, and only u32 attributes are set. libnftnl
+        // ignores attributes that it does not know about, which is checked afterwards.
+        unsafe {
+            // This fails if libnftnl has no socket expression at all
+            let expr = sys::nftnl_expr_alloc(c"socket".as_ptr());
+            if expr.is_null() {
+                return None;
+            }
+            sys::nftnl_expr_set_u32(expr, NFTNL_EXPR_SOCKET_KEY, NFT_SOCKET_CGROUPV2);
+            sys::nftnl_expr_set_u32(expr, NFTNL_EXPR_SOCKET_DREG, libc::NFT_REG_1 as u32);
+            sys::nftnl_expr_set_u32(expr, NFTNL_EXPR_SOCKET_LEVEL, self.level);
+
+            let attributes = [
+                NFTNL_EXPR_SOCKET_KEY,
+                NFTNL_EXPR_SOCKET_DREG,
+                NFTNL_EXPR_SOCKET_LEVEL,
+            ];
+            if !attributes
+                .into_iter()
+                .all(|attribute| sys::nftnl_expr_is_set(expr, attribute))
+            {
+                sys::nftnl_expr_free(expr);
+                return None;
+            }
+            Some(expr)
+        }
+    }
+}
+
+impl expr::Expression for SocketCgroupV2 {
+    fn to_expr(&self, _rule: &Rule<'_>) -> *mut nftnl::nftnl_sys::nftnl_expr {
+        // Only used if `SOCKET_CGROUPV2_SUPPORTED` is true
+        self.build()
+            .expect("libnftnl does not support socket cgroupv2 expressions")
+    }
+}
+
 fn set_src_valid_mark_sysctl() -> io::Result<()> {
     fs::write(PROC_SYS_NET_IPV4_CONF_SRC_VALID_MARK, b"1")
 }
@@ -1105,3 +1452,255 @@ fn batch_deprecated_tables(batch: &mut Batch) {
         batch.add(table, nftnl::MsgType::Del);
     }
 }
+
+fn find_program(program: &'static str) -> Result<PathBuf> {
+    which::which(program).map_err(|_| Error::ProgramNotFound(program))
+}
+
+/// List our table in nft syntax. Counter values are omitted so that listings can be compared.
+fn list_table(nft: &Path) -> Result<String> {
+    run_program(
+        NFT_PROGRAM,
+        duct::cmd!(
+            nft,
+            "--stateless",
+            "list",
+            "table",
+            "inet",
+            TABLE_NAME.to_str().unwrap()
+        ),
+    )
+}
+
+fn run_program(program: &'static str, expression: duct::Expression) -> Result<String> {
+    let output = expression
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .map_err(|error| Error::RunProgram(program, error))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(Error::ProgramFailed(program, stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem::size_of;
+    use talpid_types::net::{AllowedClients, ALLOWED_LAN_NETS};
+
+    /// Netlink message type of rules added to a batch.
+    const NEWRULE_MSG_TYPE: u16 =
+        ((libc::NFNL_SUBSYS_NFTABLES << 8) | libc::NFT_MSG_NEWRULE) as u16;
+
+    fn connecting_policy() -> FirewallPolicy {
+        let endpoint = AllowedEndpoint {
+            endpoint: Endpoint::new(Ipv4Addr::new(1, 2, 3, 4), 51820, TransportProtocol::Udp),
+            clients: AllowedClients::Root,
+        };
+        FirewallPolicy::Connecting {
+            peer_endpoint: endpoint.clone(),
+            tunnel: None,
+            allow_lan: false,
+            lan_access: LanAccess::default(),
+            allowed_endpoint: endpoint,
+            allowed_tunnel_traffic: AllowedTunnelTraffic::None,
+            excluded_destinations: vec![],
+        }
+    }
+
+    fn blocked_policy() -> FirewallPolicy {
+        FirewallPolicy::Blocked {
+            allow_lan: false,
+            lan_access: LanAccess::default(),
+            allowed_endpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_exclude_mode_marks_cgroup_members() {
+        for policy in [connecting_policy(), blocked_policy()] {
+            assert_eq!(
+                split_tunnel_cgroup_match(&policy, SplitTunnelMode::Exclude),
+                Some(CgroupMatch::Member)
+            );
+        }
+    }
+
+    #[test]
+    fn test_include_mode_marks_non_members() {
+        assert_eq!(
+            split_tunnel_cgroup_match(&connecting_policy(), SplitTunnelMode::Include),
+            Some(CgroupMatch::NotMember)
+        );
+    }
+
+    #[test]
+    fn test_include_mode_never_bypasses_blocked_state() {
+        assert_eq!(
+            split_tunnel_cgroup_match(&blocked_policy(), SplitTunnelMode::Include),
+            None
+        );
+    }
+
+    /// Return the rules in `batch`, without their netlink headers, so that they can be compared
+    /// regardless of their sequence numbers.
+    fn rule_messages(batch: &FinalizedBatch) -> Vec<Vec<u8>> {
+        let header_len = size_of::<libc::nlmsghdr>();
+        let mut rules = vec![];
+        for mut buffer in batch {
+            while buffer.len() >= header_len {
+                let len = u32::from_ne_bytes(buffer[..4].try_into().unwrap()) as usize;
+                let msg_type = u16::from_ne_bytes(buffer[4..6].try_into().unwrap());
+                if msg_type == NEWRULE_MSG_TYPE {
+                    rules.push(buffer[header_len..len].to_vec());
+                }
+                buffer = &buffer[((len + 3) & !3).min(buffer.len())..];
+            }
+        }
+        rules
+    }
+
+    /// Return the rules added to `batch`, in order.
+    fn rules(batch: PolicyBatch<'_>) -> Vec<Vec<u8>> {
+        rule_messages(&batch.batch.finalize())
+    }
+
+    /// Return a rule in `chain` built by `build`, in the same format as [rules].
+    fn rule(chain: &Chain<'_>, build: impl FnOnce(&mut Rule<'_>)) -> Vec<u8> {
+        let mut batch = Batch::new();
+        let mut rule = Rule::new(chain);
+        build(&mut rule);
+        batch.add(&rule, nftnl::MsgType::Add);
+        rule_messages(&batch.finalize()).remove(0)
+    }
+
+    fn accept_net(chain: &Chain<'_>, end: End, net: IpNetwork) -> Vec<u8> {
+        rule(chain, |rule| {
+            check_net(rule, end, net);
+            add_verdict(rule, &Verdict::Accept);
+        })
+    }
+
+    fn lan_network() -> IpNetwork {
+        "192.168.1.0/24".parse().unwrap()
+    }
+
+    /// Only the configured local networks must be allowed, rather than all private networks.
+    #[test]
+    fn test_restricted_lan_networks() {
+        let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
+        let expected = PolicyBatch::new(&table);
+        let mut batch = PolicyBatch::new(&table);
+        batch.add_allow_lan_rules(&LanAccess {
+            networks: vec![lan_network()],
+            ..LanAccess::default()
+        });
+        let rules = rules(batch);
+
+        assert!(rules.contains(&accept_net(&expected.out_chain, End::Dst, lan_network())));
+        assert!(rules.contains(&accept_net(
+            &expected.forward_chain,
+            End::Dst,
+            lan_network()
+        )));
+        assert!(rules.contains(&accept_net(&expected.in_chain, End::Src, lan_network())));
+        for net in &*ALLOWED_LAN_NETS {
+            assert!(!rules.contains(&accept_net(&expected.out_chain, End::Dst, *net)));
+            assert!(!rules.contains(&accept_net(&expected.in_chain, End::Src, *net)));
+        }
+    }
+
+    /// With inbound ports set, only responses and connections to those ports may be accepted
+    /// from the local network, while outgoing traffic to it is still allowed.
+    #[test]
+    fn test_lan_inbound_ports() {
+        let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
+        let expected = PolicyBatch::new(&table);
+        let mut batch = PolicyBatch::new(&table);
+        batch.add_allow_lan_rules(&LanAccess {
+            networks: vec![lan_network()],
+            inbound_ports: vec![22],
+            ..LanAccess::default()
+        });
+        let rules = rules(batch);
+
+        assert!(rules.contains(&accept_net(&expected.out_chain, End::Dst, lan_network())));
+        assert!(!rules.contains(&accept_net(&expected.in_chain, End::Src, lan_network())));
+
+        let established = rule(&expected.in_chain, |rule| {
+            check_net(rule, End::Src, lan_network());
+            let allowed_states = nftnl::expr::ct::States::ESTABLISHED.bits();
+            rule.add_expr(&nft_expr!(ct state));
+            rule.add_expr(&nft_expr!(bitwise mask allowed_states, xor 0u32));
+            rule.add_expr(&nft_expr!(cmp != 0u32));
+            add_verdict(rule, &Verdict::Accept);
+        });
+        assert!(rules.contains(&established));
+
+        for protocol in [TransportProtocol::Tcp, TransportProtocol::Udp] {
+            let port_rule = rule(&expected.in_chain, |rule| {
+                check_net(rule, End::Src, lan_network());
+                check_port(rule, protocol, End::Dst, 22);
+                add_verdict(rule, &Verdict::Accept);
+            });
+            assert!(rules.contains(&port_rule));
+            let other_port_rule = rule(&expected.in_chain, |rule| {
+                check_net(rule, End::Src, lan_network());
+                check_port(rule, protocol, End::Dst, 23);
+                add_verdict(rule, &Verdict::Accept);
+            });
+            assert!(!rules.contains(&other_port_rule));
+        }
+    }
+
+    /// Allowed networks must be reachable in the blocked state, even without LAN access, but DNS
+    /// to them must still be dropped.
+    #[test]
+    fn test_allowed_networks_in_blocked_state() {
+        let allowed_network: IpNetwork = "203.0.113.0/24".parse().unwrap();
+        let policy = FirewallPolicy::Blocked {
+            allow_lan: false,
+            lan_access: LanAccess {
+                allowed_networks: vec![allowed_network],
+                ..LanAccess::default()
+            },
+            allowed_endpoint: None,
+        };
+        let table = Table::new(&TABLE_NAME, ProtoFamily::Inet);
+        let expected = PolicyBatch::new(&table);
+        let mut batch = PolicyBatch::new(&table);
+        batch.add_policy_specific_rules(&policy, 0).unwrap();
+        let rules = rules(batch);
+
+        let position = |rule: &Vec<u8>| rules.iter().position(|r| r == rule);
+        let allow_out = position(&accept_net(&expected.out_chain, End::Dst, allowed_network))
+            .expect("allowed network must be reachable");
+        assert!(rules.contains(&accept_net(
+            &expected.forward_chain,
+            End::Dst,
+            allowed_network
+        )));
+        assert!(rules.contains(&accept_net(&expected.in_chain, End::Src, allowed_network)));
+
+        let drop_dns = rule(&expected.out_chain, |rule| {
+            check_port(rule, TransportProtocol::Udp, End::Dst, 53);
+            add_verdict(
+                rule,
+                &Verdict::Reject(RejectionType::Icmp(IcmpCode::PortUnreach)),
+            );
+        });
+        let drop_dns = position(&drop_dns).expect("DNS must be dropped");
+        assert!(
+            drop_dns < allow_out,
+            "DNS must be dropped before allowed networks are accepted"
+        );
+
+        for net in &*ALLOWED_LAN_NETS {
+            assert!(!rules.contains(&accept_net(&expected.out_chain, End::Dst, *net)));
+        }
+    }
+}
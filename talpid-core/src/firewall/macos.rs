@@ -8,8 +8,8 @@ use ipnetwork::IpNetwork;
 use libc::{c_int, sysctlbyname};
 use pfctl::{DropAction, FilterRuleAction, Ip, RedirectRule, Uid};
 use talpid_types::net::{
-    AllowedEndpoint, AllowedTunnelTraffic, TransportProtocol, ALLOWED_LAN_MULTICAST_NETS,
-    ALLOWED_LAN_NETS,
+    AllowedEndpoint, AllowedTunnelTraffic, LanAccess, TransportProtocol,
+    ALLOWED_LAN_MULTICAST_NETS, ALLOWED_LAN_NETS,
 };
 
 use super::{FirewallArguments, FirewallPolicy};
@@ -133,7 +133,9 @@ impl Firewall {
         }
 
         if policy.allow_lan() {
-            let net_is_lan = ALLOWED_LAN_NETS
+            let net_is_lan = policy
+                .lan_access()
+                .lan_networks()
                 .iter()
                 .chain(ALLOWED_LAN_MULTICAST_NETS.iter())
                 .any(|net| net.contains(remote_address.ip()));
@@ -144,6 +146,16 @@ impl Firewall {
             }
         }
 
+        if policy
+            .lan_access()
+            .allowed_networks
+            .iter()
+            .any(|net| net.contains(remote_address.ip()))
+        {
+            // Traffic to these networks is always allowed
+            return Ok(false);
+        }
+
         if let Some(endpoint) = policy.allowed_endpoint() {
             // Keep states to the allowed endpoint.
             // Note that we're not taking into account allowed clients here, because it's highly
@@ -249,7 +261,10 @@ impl Firewall {
 
         let redirect_rules = if *crate::resolver::LOCAL_DNS_RESOLVER {
             match policy {
-                FirewallPolicy::Connected { dns_config, .. } if dns_config.is_loopback() => {
+                // Queries to the local forwarder must not be redirected
+                FirewallPolicy::Connected { dns_config, .. }
+                    if dns_config.is_loopback() || dns_config.is_forwarded() =>
+                {
                     vec![]
                 }
                 FirewallPolicy::Blocked {
@@ -326,6 +341,19 @@ impl Firewall {
             rules.push(rule);
         }
 
+        // no nat to excluded destinations and allowed networks
+        for net in policy
+            .excluded_destinations()
+            .iter()
+            .chain(&policy.lan_access().allowed_networks)
+        {
+            let rule = pfctl::NatRuleBuilder::default()
+                .action(pfctl::NatRuleAction::NoNat)
+                .to(pfctl::Ip::from(*net))
+                .build()?;
+            rules.push(rule);
+        }
+
         // no nat to [vpn ip]
         let no_nat_to_vpn_server = pfctl::NatRuleBuilder::default()
             .action(pfctl::NatRuleAction::NoNat)
@@ -367,8 +395,10 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                excluded_destinations,
                 redirect_interface,
                 dns_redirect_port: _,
             } => {
@@ -404,8 +434,10 @@ impl Firewall {
                 }
 
                 if *allow_lan {
-                    rules.append(&mut self.get_allow_lan_rules()?);
+                    rules.append(&mut self.get_allow_lan_rules(lan_access)?);
                 }
+                rules.append(&mut self.get_allow_networks_rules(&lan_access.allowed_networks)?);
+                rules.append(&mut self.get_allow_networks_rules(excluded_destinations)?);
 
                 Ok(rules)
             }
@@ -413,7 +445,9 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 dns_config,
+                excluded_destinations,
                 redirect_interface,
                 dns_redirect_port: _,
             } => {
@@ -437,8 +471,10 @@ impl Firewall {
                 rules.append(&mut self.get_block_dns_rules()?);
 
                 if *allow_lan {
-                    rules.append(&mut self.get_allow_lan_rules()?);
+                    rules.append(&mut self.get_allow_lan_rules(lan_access)?);
                 }
+                rules.append(&mut self.get_allow_networks_rules(&lan_access.allowed_networks)?);
+                rules.append(&mut self.get_allow_networks_rules(excluded_destinations)?);
 
                 if let Some(redirect_interface) = redirect_interface {
                     enable_forwarding();
@@ -460,6 +496,7 @@ impl Firewall {
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                lan_access,
                 allowed_endpoint,
                 ..
             } => {
@@ -468,11 +505,14 @@ impl Firewall {
                     rules.push(self.get_allowed_endpoint_rule(allowed_endpoint)?);
                 }
 
-                if *allow_lan {
+                if *allow_lan || !lan_access.allowed_networks.is_empty() {
                     // Important to block DNS before allow LAN (so DNS does not leak to the LAN)
                     rules.append(&mut self.get_block_dns_rules()?);
-                    rules.append(&mut self.get_allow_lan_rules()?);
                 }
+                if *allow_lan {
+                    rules.append(&mut self.get_allow_lan_rules(lan_access)?);
+                }
+                rules.append(&mut self.get_allow_networks_rules(&lan_access.allowed_networks)?);
 
                 Ok(rules)
             }
@@ -696,9 +736,10 @@ impl Firewall {
         Ok(vec![lo0_rule])
     }
 
-    fn get_allow_lan_rules(&self) -> Result<Vec<pfctl::FilterRule>> {
+    /// Allow all traffic to and from `networks`.
+    fn get_allow_networks_rules(&self, networks: &[IpNetwork]) -> Result<Vec<pfctl::FilterRule>> {
         let mut rules = vec![];
-        for net in &*ALLOWED_LAN_NETS {
+        for net in networks {
             let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
             rule_builder.quick(true);
             let allow_out = rule_builder
@@ -715,6 +756,45 @@ impl Firewall {
             rules.push(allow_out);
             rules.push(allow_in);
         }
+        Ok(rules)
+    }
+
+    fn get_allow_lan_rules(&self, lan_access: &LanAccess) -> Result<Vec<pfctl::FilterRule>> {
+        let mut rules = vec![];
+        for net in lan_access.lan_networks() {
+            let mut rule_builder = self.create_rule_builder(FilterRuleAction::Pass);
+            rule_builder.quick(true);
+            let allow_out = rule_builder
+                .direction(pfctl::Direction::Out)
+                .from(pfctl::Ip::Any)
+                .keep_state(pfctl::StatePolicy::Keep)
+                .to(pfctl::Ip::from(*net))
+                .build()?;
+            rules.push(allow_out);
+
+            if lan_access.inbound_ports.is_empty() {
+                let allow_in = rule_builder
+                    .direction(pfctl::Direction::In)
+                    .from(pfctl::Ip::from(*net))
+                    .to(pfctl::Ip::Any)
+                    .build()?;
+                rules.push(allow_in);
+                continue;
+            }
+
+            // Responses to outgoing connections are let through by the state of the rule above
+            for port in &lan_access.inbound_ports {
+                for proto in [pfctl::Proto::Tcp, pfctl::Proto::Udp] {
+                    let allow_in = rule_builder
+                        .direction(pfctl::Direction::In)
+                        .proto(proto)
+                        .from(pfctl::Ip::from(*net))
+                        .to(pfctl::Port::from(*port))
+                        .build()?;
+                    rules.push(allow_in);
+                }
+            }
+        }
         for multicast_net in &*ALLOWED_LAN_MULTICAST_NETS {
             let allow_multicast_out = self
                 .create_rule_builder(FilterRuleAction::Pass)
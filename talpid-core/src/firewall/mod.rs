@@ -6,7 +6,11 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::LazyLock,
 };
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::LanAccess;
 use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, ALLOWED_LAN_NETS};
+#[cfg(target_os = "linux")]
+use talpid_types::split_tunnel::SplitTunnelMode;
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -87,10 +91,16 @@ pub enum FirewallPolicy {
         tunnel: Option<crate::tunnel::TunnelMetadata>,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Which local and additional networks to allow.
+        #[cfg(not(target_os = "android"))]
+        lan_access: LanAccess,
         /// Host that should be reachable while connecting.
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+        /// Destination networks that may be reached outside the tunnel.
+        #[cfg(not(target_os = "android"))]
+        excluded_destinations: Vec<IpNetwork>,
         /// Interface to redirect (VPN tunnel) traffic to
         #[cfg(target_os = "macos")]
         redirect_interface: Option<String>,
@@ -108,9 +118,19 @@ pub enum FirewallPolicy {
         tunnel: crate::tunnel::TunnelMetadata,
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Which local and additional networks to allow.
+        #[cfg(not(target_os = "android"))]
+        lan_access: LanAccess,
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_config: ResolvedDnsConfig,
+        /// Destination networks that may be reached outside the tunnel.
+        #[cfg(not(target_os = "android"))]
+        excluded_destinations: Vec<IpNetwork>,
+        /// Resolvers outside the tunnel that may receive the daemon's queries for excluded
+        /// domains.
+        #[cfg(target_os = "linux")]
+        excluded_resolvers: Vec<IpAddr>,
         /// Interface to redirect (VPN tunnel) traffic to
         #[cfg(target_os = "macos")]
         redirect_interface: Option<String>,
@@ -124,6 +144,9 @@ pub enum FirewallPolicy {
     Blocked {
         /// Flag setting if communication with LAN networks should be possible.
         allow_lan: bool,
+        /// Which local and additional networks to allow.
+        #[cfg(not(target_os = "android"))]
+        lan_access: LanAccess,
         /// Host that should be reachable while in the blocked state.
         allowed_endpoint: Option<AllowedEndpoint>,
         /// Destination port for DNS traffic redirection. Traffic destined to `127.0.0.1:53` will
@@ -181,6 +204,22 @@ impl FirewallPolicy {
         }
     }
 
+    /// Return destination networks that may be reached outside the tunnel
+    #[cfg(not(target_os = "android"))]
+    pub fn excluded_destinations(&self) -> &[IpNetwork] {
+        match self {
+            FirewallPolicy::Connecting {
+                excluded_destinations,
+                ..
+            }
+            | FirewallPolicy::Connected {
+                excluded_destinations,
+                ..
+            } => excluded_destinations,
+            FirewallPolicy::Blocked { .. } => &[],
+        }
+    }
+
     /// Return whether LAN traffic is allowed
     pub fn allow_lan(&self) -> bool {
         match self {
@@ -189,6 +228,40 @@ impl FirewallPolicy {
             | FirewallPolicy::Blocked { allow_lan, .. } => *allow_lan,
         }
     }
+
+    /// Return which local and additional networks to allow
+    #[cfg(not(target_os = "android"))]
+    pub fn lan_access(&self) -> &LanAccess {
+        match self {
+            FirewallPolicy::Connecting { lan_access, .. }
+            | FirewallPolicy::Connected { lan_access, .. }
+            | FirewallPolicy::Blocked { lan_access, .. } => lan_access,
+        }
+    }
+
+    /// Remove allowed networks that would let traffic bypass the tunnel indiscriminately. These
+    /// are networks that are too broad, and networks that contain the relay.
+    #[cfg(not(target_os = "android"))]
+    fn restrict_allowed_networks(mut self) -> Self {
+        let peer = self.peer_endpoint().map(|peer| peer.endpoint.address.ip());
+        let lan_access = match &mut self {
+            FirewallPolicy::Connecting { lan_access, .. }
+            | FirewallPolicy::Connected { lan_access, .. }
+            | FirewallPolicy::Blocked { lan_access, .. } => lan_access,
+        };
+        lan_access.allowed_networks.retain(|network| {
+            if !LanAccess::is_allowed_network(network) {
+                log::warn!("Ignoring allowed network {network} since it is too broad");
+                return false;
+            }
+            if peer.is_some_and(|peer| network.contains(peer)) {
+                log::warn!("Ignoring allowed network {network} since it contains the relay");
+                return false;
+            }
+            true
+        });
+        self
+    }
 }
 
 impl fmt::Display for FirewallPolicy {
@@ -279,10 +352,16 @@ pub struct FirewallArguments {
     pub initial_state: InitialFirewallState,
     /// This argument is required for the blocked state to configure the firewall correctly.
     pub allow_lan: bool,
+    /// Which local and additional networks to allow in the blocked state.
+    #[cfg(not(target_os = "android"))]
+    pub lan_access: LanAccess,
     /// Specifies the firewall mark used to identify traffic that is allowed to be excluded from
     /// the tunnel and _leaked_ during blocked states.
     #[cfg(target_os = "linux")]
     pub fwmark: u32,
+    /// Whether split tunneled processes bypass the tunnel or are the only ones using it.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: SplitTunnelMode,
 }
 
 /// State to enter during firewall init.
@@ -314,10 +393,43 @@ impl Firewall {
     /// Applies and starts enforcing the given `FirewallPolicy` Makes sure it is being kept in place
     /// until this method is called again with another policy, or until `reset_policy` is called.
     pub fn apply_policy(&mut self, policy: FirewallPolicy) -> Result<(), Error> {
+        #[cfg(not(target_os = "android"))]
+        let policy = policy.restrict_allowed_networks();
         log::info!("Applying firewall policy: {}", policy);
         self.inner.apply_policy(policy)
     }
 
+    /// Set whether split tunneled processes bypass the tunnel or are the only ones using it. Takes
+    /// effect the next time a policy is applied. Returns whether the mode changed.
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> bool {
+        self.inner.set_split_tunnel_mode(mode)
+    }
+
+    /// Returns the policy that is currently enforced, if any.
+    #[cfg(target_os = "linux")]
+    pub fn policy(&self) -> Option<&FirewallPolicy> {
+        self.inner.policy()
+    }
+
+    /// Returns a function that renders the ruleset that the given `FirewallPolicy` would install,
+    /// in nft syntax, without applying it. The function runs external programs, so it should not
+    /// be called on the thread of the tunnel state machine.
+    #[cfg(target_os = "linux")]
+    pub fn policy_renderer(
+        &self,
+        policy: &FirewallPolicy,
+    ) -> impl FnOnce() -> Result<String, Error> + Send + 'static {
+        self.inner
+            .policy_renderer(&policy.clone().restrict_allowed_networks())
+    }
+
+    /// Renders the currently installed ruleset in nft syntax, or returns `None` if there is none.
+    #[cfg(target_os = "linux")]
+    pub fn render_active_ruleset() -> Result<Option<String>, Error> {
+        imp::Firewall::render_active_ruleset()
+    }
+
     /// Resets/removes any currently enforced `FirewallPolicy`. Returns the system to the same state
     /// it had before any policy was applied through this `Firewall` instance.
     pub fn reset_policy(&mut self) -> Result<(), Error> {
@@ -2,12 +2,13 @@
 
 use crate::{dns::ResolvedDnsConfig, tunnel::TunnelMetadata};
 
+use ipnetwork::IpNetwork;
 use std::{ffi::CStr, io, net::IpAddr, ptr, sync::LazyLock};
 
 use self::winfw::*;
 use super::{FirewallArguments, FirewallPolicy, InitialFirewallState};
 use talpid_types::{
-    net::{AllowedEndpoint, AllowedTunnelTraffic},
+    net::{AllowedEndpoint, AllowedTunnelTraffic, LanAccess},
     tunnel::FirewallPolicyError,
     ErrorExt,
 };
@@ -85,7 +86,7 @@ pub struct Firewall(());
 impl Firewall {
     pub fn from_args(args: FirewallArguments) -> Result<Self, Error> {
         if let InitialFirewallState::Blocked(allowed_endpoint) = args.initial_state {
-            Self::initialize_blocked(allowed_endpoint, args.allow_lan)
+            Self::initialize_blocked(allowed_endpoint, args.allow_lan, &args.lan_access)
         } else {
             Self::new()
         }
@@ -108,8 +109,10 @@ impl Firewall {
     fn initialize_blocked(
         allowed_endpoint: AllowedEndpoint,
         allow_lan: bool,
+        lan_access: &LanAccess,
     ) -> Result<Self, Error> {
-        let cfg = &WinFwSettings::new(allow_lan);
+        let settings = WinFwSettingsContainer::new(allow_lan, lan_access);
+        let cfg = &settings.as_settings();
         let allowed_endpoint = WinFwAllowedEndpointContainer::from(allowed_endpoint);
         unsafe {
             WinFw_InitializeBlocked(
@@ -142,10 +145,13 @@ impl Firewall {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                excluded_destinations,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
+                let settings = WinFwSettingsContainer::new(allow_lan, &lan_access);
+                let cfg = &settings.as_settings();
 
                 self.set_connecting_state(
                     &peer_endpoint,
@@ -153,22 +159,34 @@ impl Firewall {
                     &tunnel,
                     &WinFwAllowedEndpointContainer::from(allowed_endpoint).as_endpoint(),
                     &allowed_tunnel_traffic,
+                    &excluded_destinations,
                 )
             }
             FirewallPolicy::Connected {
                 peer_endpoint,
                 tunnel,
                 allow_lan,
+                lan_access,
                 dns_config,
+                excluded_destinations,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
-                self.set_connected_state(&peer_endpoint, cfg, &tunnel, &dns_config)
+                let settings = WinFwSettingsContainer::new(allow_lan, &lan_access);
+                let cfg = &settings.as_settings();
+                self.set_connected_state(
+                    &peer_endpoint,
+                    cfg,
+                    &tunnel,
+                    &dns_config,
+                    &excluded_destinations,
+                )
             }
             FirewallPolicy::Blocked {
                 allow_lan,
+                lan_access,
                 allowed_endpoint,
             } => {
-                let cfg = &WinFwSettings::new(allow_lan);
+                let settings = WinFwSettingsContainer::new(allow_lan, &lan_access);
+                let cfg = &settings.as_settings();
                 self.set_blocked_state(
                     cfg,
                     allowed_endpoint.map(WinFwAllowedEndpointContainer::from),
@@ -203,10 +221,11 @@ impl Firewall {
     fn set_connecting_state(
         &mut self,
         endpoint: &AllowedEndpoint,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         tunnel_metadata: &Option<TunnelMetadata>,
         allowed_endpoint: &WinFwAllowedEndpoint<'_>,
         allowed_tunnel_traffic: &AllowedTunnelTraffic,
+        excluded_destinations: &[IpNetwork],
     ) -> Result<(), Error> {
         log::trace!("Applying 'connecting' firewall policy");
         let ip_str = widestring_ip(endpoint.endpoint.address.ip());
@@ -283,6 +302,8 @@ impl Firewall {
                 .unwrap_or(ptr::null()),
         };
 
+        let excluded_destinations = WinFwIpNetworksContainer::from(excluded_destinations);
+
         let res = unsafe {
             WinFw_ApplyPolicyConnecting(
                 winfw_settings,
@@ -292,6 +313,8 @@ impl Firewall {
                 interface_wstr_ptr,
                 allowed_endpoint,
                 &allowed_tunnel_traffic,
+                excluded_destinations.as_slice().as_ptr(),
+                excluded_destinations.as_slice().len(),
             )
             .into_result()
             .map_err(Error::ApplyingConnectingPolicy)
@@ -312,9 +335,10 @@ impl Firewall {
     fn set_connected_state(
         &mut self,
         endpoint: &AllowedEndpoint,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         tunnel_metadata: &TunnelMetadata,
         dns_config: &ResolvedDnsConfig,
+        excluded_destinations: &[IpNetwork],
     ) -> Result<(), Error> {
         log::trace!("Applying 'connected' firewall policy");
         let ip_str = widestring_ip(endpoint.endpoint.address.ip());
@@ -360,6 +384,8 @@ impl Firewall {
             .map(|ip| ip.as_ptr())
             .collect();
 
+        let excluded_destinations = WinFwIpNetworksContainer::from(excluded_destinations);
+
         let result = unsafe {
             WinFw_ApplyPolicyConnected(
                 winfw_settings,
@@ -371,6 +397,8 @@ impl Firewall {
                 tunnel_dns_servers.len(),
                 non_tunnel_dns_servers.as_ptr(),
                 non_tunnel_dns_servers.len(),
+                excluded_destinations.as_slice().as_ptr(),
+                excluded_destinations.as_slice().len(),
             )
             .into_result()
             .map_err(Error::ApplyingConnectedPolicy)
@@ -384,7 +412,7 @@ impl Firewall {
 
     fn set_blocked_state(
         &mut self,
-        winfw_settings: &WinFwSettings,
+        winfw_settings: &WinFwSettings<'_>,
         allowed_endpoint: Option<WinFwAllowedEndpointContainer>,
     ) -> Result<(), Error> {
         log::trace!("Applying 'blocked' firewall policy");
@@ -556,7 +584,10 @@ fn with_wmi_if_enabled(f: impl FnOnce(&wmi::WMIConnection)) {
 
 #[allow(non_snake_case)]
 mod winfw {
-    use super::{widestring_ip, AllowedEndpoint, AllowedTunnelTraffic, Error, WideCString};
+    use super::{
+        widestring_ip, AllowedEndpoint, AllowedTunnelTraffic, Error, IpNetwork, LanAccess,
+        WideCString,
+    };
     use std::ffi::{c_char, c_void};
     use talpid_types::net::TransportProtocol;
 
@@ -618,6 +649,45 @@ mod winfw {
         _phantom: std::marker::PhantomData<&'a WinFwAllowedEndpointContainer>,
     }
 
+    pub struct WinFwIpNetworksContainer {
+        _ips: Box<[WideCString]>,
+        networks: Box<[WinFwIpNetwork]>,
+    }
+
+    impl From<&[IpNetwork]> for WinFwIpNetworksContainer {
+        fn from(networks: &[IpNetwork]) -> Self {
+            let ips = networks
+                .iter()
+                .map(|network| widestring_ip(network.network()))
+                .collect::<Box<_>>();
+            let networks = networks
+                .iter()
+                .zip(ips.iter())
+                .map(|(network, ip)| WinFwIpNetwork {
+                    ip: ip.as_ptr(),
+                    prefix: network.prefix(),
+                })
+                .collect::<Box<_>>();
+
+            WinFwIpNetworksContainer {
+                _ips: ips,
+                networks,
+            }
+        }
+    }
+
+    impl WinFwIpNetworksContainer {
+        pub fn as_slice(&self) -> &[WinFwIpNetwork] {
+            &self.networks
+        }
+    }
+
+    #[repr(C)]
+    pub struct WinFwIpNetwork {
+        ip: *const libc::wchar_t,
+        prefix: u8,
+    }
+
     #[repr(C)]
     pub struct WinFwAllowedTunnelTraffic {
         pub type_: WinFwAllowedTunnelTrafficType,
@@ -668,21 +738,54 @@ mod winfw {
         }
     }
 
-    #[repr(C)]
-    pub struct WinFwSettings {
-        permitDhcp: bool,
-        permitLan: bool,
+    /// Owns the memory that [`WinFwSettings`] points to.
+    pub struct WinFwSettingsContainer {
+        permit_lan: bool,
+        lan_networks: WinFwIpNetworksContainer,
+        lan_inbound_ports: Box<[u16]>,
+        allowed_networks: WinFwIpNetworksContainer,
     }
 
-    impl WinFwSettings {
-        pub fn new(permit_lan: bool) -> WinFwSettings {
+    impl WinFwSettingsContainer {
+        pub fn new(permit_lan: bool, lan_access: &LanAccess) -> Self {
+            WinFwSettingsContainer {
+                permit_lan,
+                lan_networks: WinFwIpNetworksContainer::from(&lan_access.networks[..]),
+                lan_inbound_ports: lan_access.inbound_ports.iter().copied().collect(),
+                allowed_networks: WinFwIpNetworksContainer::from(&lan_access.allowed_networks[..]),
+            }
+        }
+
+        pub fn as_settings(&self) -> WinFwSettings<'_> {
             WinFwSettings {
                 permitDhcp: true,
-                permitLan: permit_lan,
+                permitLan: self.permit_lan,
+                lanNetworks: self.lan_networks.as_slice().as_ptr(),
+                numLanNetworks: self.lan_networks.as_slice().len(),
+                lanInboundPorts: self.lan_inbound_ports.as_ptr(),
+                numLanInboundPorts: self.lan_inbound_ports.len(),
+                allowedNetworks: self.allowed_networks.as_slice().as_ptr(),
+                numAllowedNetworks: self.allowed_networks.as_slice().len(),
+
+                _phantom: std::marker::PhantomData,
             }
         }
     }
 
+    #[repr(C)]
+    pub struct WinFwSettings<'a> {
+        permitDhcp: bool,
+        permitLan: bool,
+        lanNetworks: *const WinFwIpNetwork,
+        numLanNetworks: usize,
+        lanInboundPorts: *const u16,
+        numLanInboundPorts: usize,
+        allowedNetworks: *const WinFwIpNetwork,
+        numAllowedNetworks: usize,
+
+        _phantom: std::marker::PhantomData<&'a WinFwSettingsContainer>,
+    }
+
     #[allow(dead_code)]
     #[repr(u32)]
     #[derive(Clone, Copy)]
@@ -733,7 +836,7 @@ mod winfw {
         #[link_name = "WinFw_InitializeBlocked"]
         pub fn WinFw_InitializeBlocked(
             timeout: libc::c_uint,
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             allowed_endpoint: *const WinFwAllowedEndpoint<'_>,
             sink: Option<LogSink>,
             sink_context: *const u8,
@@ -744,18 +847,20 @@ mod winfw {
 
         #[link_name = "WinFw_ApplyPolicyConnecting"]
         pub fn WinFw_ApplyPolicyConnecting(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             relay: &WinFwEndpoint,
             relayClient: *const *const libc::wchar_t,
             relayClientLen: usize,
             tunnelIfaceAlias: *const libc::wchar_t,
             allowedEndpoint: *const WinFwAllowedEndpoint<'_>,
             allowedTunnelTraffic: &WinFwAllowedTunnelTraffic,
+            excludedDestinations: *const WinFwIpNetwork,
+            numExcludedDestinations: usize,
         ) -> WinFwPolicyStatus;
 
         #[link_name = "WinFw_ApplyPolicyConnected"]
         pub fn WinFw_ApplyPolicyConnected(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             relay: &WinFwEndpoint,
             relayClient: *const *const libc::wchar_t,
             relayClientLen: usize,
@@ -764,11 +869,13 @@ mod winfw {
             numTunnelDnsServers: usize,
             nonTunnelDnsServers: *const *const libc::wchar_t,
             numNonTunnelDnsServers: usize,
+            excludedDestinations: *const WinFwIpNetwork,
+            numExcludedDestinations: usize,
         ) -> WinFwPolicyStatus;
 
         #[link_name = "WinFw_ApplyPolicyBlocked"]
         pub fn WinFw_ApplyPolicyBlocked(
-            settings: &WinFwSettings,
+            settings: &WinFwSettings<'_>,
             allowed_endpoint: *const WinFwAllowedEndpoint<'_>,
         ) -> WinFwPolicyStatus;
 
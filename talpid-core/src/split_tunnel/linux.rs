@@ -1,9 +1,20 @@
 use std::{
+    collections::{HashMap, HashSet},
     env, fs,
     io::{self, BufRead, BufReader, Write},
-    path::PathBuf,
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::fs::MetadataExt,
+    },
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use talpid_types::cgroup::{
+    find_cgroup2_mount, find_net_cls_mount, SPLIT_TUNNEL_CGROUP2_LEVEL, SPLIT_TUNNEL_CGROUP_NAME,
 };
-use talpid_types::cgroup::{find_net_cls_mount, SPLIT_TUNNEL_CGROUP_NAME};
 
 const DEFAULT_NET_CLS_DIR: &str = "/sys/fs/cgroup/net_cls";
 const NET_CLS_DIR_OVERRIDE_ENV_VAR: &str = "TALPID_NET_CLS_MOUNT_DIR";
@@ -14,6 +25,23 @@ pub const NET_CLS_CLASSID: u32 = 0x4d9f41;
 /// Value used to mark packets and associated connections.
 /// This should be an arbitrary but unique integer.
 pub const MARK: i32 = 0xf41;
+/// Depth of the cgroup v2 group containing excluded processes.
+pub const CGROUP2_LEVEL: u32 = SPLIT_TUNNEL_CGROUP2_LEVEL;
+
+/// How often to look for new processes started from excluded applications, if the kernel cannot
+/// notify us of started processes.
+const APP_SCAN_INTERVAL: Duration = Duration::from_secs(2);
+/// How often to look for processes of excluded applications that were missed despite
+/// notifications of started processes.
+const APP_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+// Process connector constants, from `linux/connector.h` and `linux/cn_proc.h`
+const CN_IDX_PROC: u32 = 0x1;
+const CN_VAL_PROC: u32 = 0x1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x2;
+/// Size of `struct cn_msg`, which follows the netlink header of process connector messages.
+const CN_MSG_LEN: usize = 20;
 
 /// Errors related to split tunneling.
 #[derive(thiserror::Error, Debug)]
@@ -47,28 +75,64 @@ pub enum Error {
     ListMounts(#[source] io::Error),
 }
 
+/// Returns the ID of the cgroup v2 group containing split tunneled processes, if that is the
+/// hierarchy in use. Like [`PidManager::new`], this prefers the `net_cls` hierarchy if it is
+/// mounted.
+///
+/// This is the inode number of the cgroup directory, which is what nftables compares against.
+pub fn cgroup2_id() -> Option<u64> {
+    if find_net_cls_mount().ok().flatten().is_some() {
+        return None;
+    }
+    let cgroup2_path = find_cgroup2_mount().ok().flatten()?;
+    let metadata = fs::metadata(cgroup2_path.join(SPLIT_TUNNEL_CGROUP_NAME)).ok()?;
+    Some(metadata.ino())
+}
+
+/// The cgroup hierarchy used to track excluded processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CGroupVersion {
+    /// Legacy `net_cls` controller. Packets are identified by a class ID.
+    V1,
+    /// Unified hierarchy. Packets are identified by the cgroup of their socket.
+    V2,
+}
+
 /// Manages PIDs in the Linux Cgroup excluded from the VPN tunnel.
+#[derive(Debug, Clone)]
 pub struct PidManager {
-    net_cls_path: PathBuf,
+    cgroup_path: PathBuf,
+    version: CGroupVersion,
+    /// The cgroup v2 groups that excluded processes were moved from. Processes are returned to
+    /// these when they are no longer excluded.
+    origins: Arc<Mutex<HashMap<i32, PathBuf>>>,
 }
 
 impl PidManager {
     /// Creates a new PID Cgroup manager.
     ///
-    /// Finds the corresponding Cgroup to use. Will mount a `net_cls` filesystem
-    /// if none exists.
+    /// Finds the corresponding Cgroup to use. Prefers an existing `net_cls` controller, then the
+    /// cgroup v2 hierarchy. Will mount a `net_cls` filesystem if neither exists.
     pub fn new() -> Result<PidManager, Error> {
+        let (cgroup_path, version) = Self::create_cgroup()?;
         let manager = PidManager {
-            net_cls_path: Self::create_cgroup()?,
+            cgroup_path,
+            version,
+            origins: Arc::default(),
         };
         manager.setup_exclusion_group()?;
+        log::debug!("Using cgroup {:?} for split tunneling", manager.version);
         Ok(manager)
     }
 
     /// Set up cgroup used to track PIDs for split tunneling.
-    fn create_cgroup() -> Result<PathBuf, Error> {
+    fn create_cgroup() -> Result<(PathBuf, CGroupVersion), Error> {
         if let Some(net_cls_path) = find_net_cls_mount().map_err(Error::ListMounts)? {
-            return Ok(net_cls_path);
+            return Ok((net_cls_path, CGroupVersion::V1));
+        }
+
+        if let Some(cgroup2_path) = find_cgroup2_mount().map_err(Error::ListMounts)? {
+            return Ok((cgroup2_path, CGroupVersion::V2));
         }
 
         let net_cls_dir = env::var(NET_CLS_DIR_OVERRIDE_ENV_VAR)
@@ -89,15 +153,19 @@ impl PidManager {
         )
         .map_err(Error::InitNetClsCGroup)?;
 
-        Ok(net_cls_dir)
+        Ok((net_cls_dir, CGroupVersion::V1))
     }
 
     fn setup_exclusion_group(&self) -> Result<(), Error> {
-        let exclusions_dir = self.net_cls_path.join(SPLIT_TUNNEL_CGROUP_NAME);
+        let exclusions_dir = self.cgroup_path.join(SPLIT_TUNNEL_CGROUP_NAME);
         if !exclusions_dir.exists() {
             fs::create_dir(exclusions_dir.clone()).map_err(Error::CreateCGroup)?;
         }
 
+        if self.version == CGroupVersion::V2 {
+            return Ok(());
+        }
+
         let classid_path = exclusions_dir.join("net_cls.classid");
         fs::write(classid_path, NET_CLS_CLASSID.to_string().as_bytes())
             .map_err(Error::SetCGroupClassId)
@@ -105,38 +173,55 @@ impl PidManager {
 
     /// Add a PID to the Cgroup to have it excluded from the tunnel.
     pub fn add(&self, pid: i32) -> Result<(), Error> {
-        let exclusions_path = self
-            .net_cls_path
-            .join(SPLIT_TUNNEL_CGROUP_NAME)
-            .join("cgroup.procs");
+        let exclusions_dir = self.cgroup_path.join(SPLIT_TUNNEL_CGROUP_NAME);
+
+        let origin = match self.version {
+            CGroupVersion::V1 => None,
+            CGroupVersion::V2 => self
+                .process_cgroup2(pid)
+                .map_err(Error::AddCGroupPid)?
+                .filter(|origin| *origin != exclusions_dir),
+        };
 
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(exclusions_path)
-            .map_err(Error::AddCGroupPid)?;
+        write_pid(&exclusions_dir, pid).map_err(Error::AddCGroupPid)?;
 
-        file.write_all(pid.to_string().as_bytes())
-            .map_err(Error::AddCGroupPid)
+        if let Some(origin) = origin {
+            let mut origins = self.origins.lock().unwrap();
+            origins.retain(|pid, _| Path::new("/proc").join(pid.to_string()).exists());
+            origins.insert(pid, origin);
+        }
+        Ok(())
     }
 
     /// Remove a PID from the Cgroup to have it included in the tunnel.
     pub fn remove(&self, pid: i32) -> Result<(), Error> {
-        // FIXME: We remove PIDs from our cgroup here by adding
-        //        them to the parent cgroup. This seems wrong.
-        let mut file = self
-            .open_parent_cgroup_handle()
-            .map_err(Error::RemoveCGroupPid)?;
-
-        file.write_all(pid.to_string().as_bytes())
-            .map_err(Error::RemoveCGroupPid)
+        match self.version {
+            // The net_cls hierarchy is only used to classify packets, so processes are simply
+            // moved to its root
+            CGroupVersion::V1 => write_pid(&self.cgroup_path, pid).map_err(Error::RemoveCGroupPid),
+            // The unified hierarchy is managed by the init system, which must find processes in
+            // the groups it created
+            CGroupVersion::V2 => {
+                let origin = self.origins.lock().unwrap().remove(&pid);
+                if let Some(origin) = origin {
+                    match write_pid(&origin, pid) {
+                        Ok(()) => return Ok(()),
+                        Err(error) => log::debug!(
+                            "Failed to return process {pid} to {}: {error}",
+                            origin.display()
+                        ),
+                    }
+                }
+                let cgroup = self.ancestor_cgroup2(pid).map_err(Error::RemoveCGroupPid)?;
+                write_pid(&cgroup, pid).map_err(Error::RemoveCGroupPid)
+            }
+        }
     }
 
     /// Return a list of all PIDs currently in the Cgroup excluded from the tunnel.
     pub fn list(&self) -> Result<Vec<i32>, Error> {
         let exclusions_path = self
-            .net_cls_path
+            .cgroup_path
             .join(SPLIT_TUNNEL_CGROUP_NAME)
             .join("cgroup.procs");
 
@@ -156,24 +241,435 @@ impl PidManager {
 
     /// Removes all PIDs from the Cgroup.
     pub fn clear(&self) -> Result<(), Error> {
-        let pids = self.list()?;
+        for pid in self.list()? {
+            self.remove(pid)?;
+        }
+        Ok(())
+    }
+
+    /// Return the cgroup v2 group of a process, or `None` if the process does not exist.
+    fn process_cgroup2(&self, pid: i32) -> io::Result<Option<PathBuf>> {
+        let contents = match fs::read_to_string(format!("/proc/{pid}/cgroup")) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let cgroup = parse_cgroup2_path(&contents).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Process has no cgroup v2 group")
+        })?;
+        Ok(Some(self.cgroup_path.join(cgroup)))
+    }
 
-        let mut file = self
-            .open_parent_cgroup_handle()
-            .map_err(Error::RemoveCGroupPid)?;
-        for pid in pids {
-            file.write_all(pid.to_string().as_bytes())
-                .map_err(Error::RemoveCGroupPid)?;
+    /// Return the cgroup v2 group of the closest ancestor of a process that is not excluded.
+    /// This is used for processes that were not excluded by us, e.g. by `mullvad-exclude`.
+    fn ancestor_cgroup2(&self, pid: i32) -> io::Result<PathBuf> {
+        let exclusions_dir = self.cgroup_path.join(SPLIT_TUNNEL_CGROUP_NAME);
+        let mut pid = pid;
+        loop {
+            pid = parent_pid(pid)?;
+            if pid == 0 {
+                return Ok(self.cgroup_path.clone());
+            }
+            match self.process_cgroup2(pid)? {
+                Some(cgroup) if cgroup != exclusions_dir => return Ok(cgroup),
+                Some(_) => (),
+                None => return Err(io::Error::from(io::ErrorKind::NotFound)),
+            }
         }
+    }
+}
 
-        Ok(())
+/// Move a process into the cgroup at `cgroup_dir`.
+fn write_pid(cgroup_dir: &Path, pid: i32) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(cgroup_dir.join("cgroup.procs"))?;
+    file.write_all(pid.to_string().as_bytes())
+}
+
+/// Return the path of the cgroup v2 group, relative to the root of the hierarchy, listed in the
+/// contents of `/proc/<pid>/cgroup`.
+fn parse_cgroup2_path(contents: &str) -> Option<&Path> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| Path::new(path.trim_start_matches('/')))
+}
+
+/// Return the PID of the parent of a process.
+fn parent_pid(pid: i32) -> io::Result<i32> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status"))?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("PPid:"))
+        .and_then(|ppid| ppid.trim().parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing parent PID"))
+}
+
+/// Keeps processes started from a set of executables in the cgroup excluded from the tunnel.
+///
+/// The kernel notifies us whenever a process starts running a new executable, so that newly
+/// started instances of an excluded application are moved into the cgroup immediately. If these
+/// notifications are unavailable, running processes are checked periodically instead. Child
+/// processes inherit the cgroup of their parent and are thus excluded as well.
+pub struct ExcludedApps {
+    tx: mpsc::Sender<AppsMessage>,
+}
+
+enum AppsMessage {
+    SetPaths(HashSet<PathBuf>),
+    /// A process started running a new executable.
+    Exec(i32),
+    /// Notifications of started processes may have been lost.
+    Rescan,
+    /// Notifications of started processes are no longer available.
+    ExecEventsFailed,
+    Stop,
+}
+
+impl ExcludedApps {
+    /// Start monitoring processes. `paths` is the initial set of excluded executables.
+    pub fn spawn(pid_manager: PidManager, paths: HashSet<PathBuf>) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        let mut scan_interval = match ExecEvents::subscribe() {
+            Ok(events) => {
+                let tx = tx.clone();
+                thread::spawn(move || events.forward(tx));
+                APP_RESCAN_INTERVAL
+            }
+            Err(error) => {
+                log::warn!("Failed to subscribe to process events: {error}");
+                APP_SCAN_INTERVAL
+            }
+        };
+
+        thread::spawn(move || {
+            let mut paths = canonicalize_paths(paths);
+            pid_manager.exclude_app_processes(&paths);
+            loop {
+                match rx.recv_timeout(scan_interval) {
+                    Ok(AppsMessage::SetPaths(new_paths)) => {
+                        let new_paths = canonicalize_paths(new_paths);
+                        let removed_paths = paths.difference(&new_paths).cloned().collect();
+                        pid_manager.include_app_processes(&removed_paths);
+                        paths = new_paths;
+                        pid_manager.exclude_app_processes(&paths);
+                    }
+                    Ok(AppsMessage::Exec(pid)) => {
+                        if is_app_process(pid, &paths) {
+                            if let Err(error) = pid_manager.add(pid) {
+                                log::warn!("Failed to exclude process {pid}: {error}");
+                            }
+                        }
+                    }
+                    Ok(AppsMessage::ExecEventsFailed) => {
+                        scan_interval = APP_SCAN_INTERVAL;
+                        pid_manager.exclude_app_processes(&paths);
+                    }
+                    Ok(AppsMessage::Rescan) | Err(mpsc::RecvTimeoutError::Timeout) => {
+                        pid_manager.exclude_app_processes(&paths);
+                    }
+                    Ok(AppsMessage::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+        ExcludedApps { tx }
+    }
+
+    /// Replace the set of excluded executables. Processes of applications that are no longer
+    /// excluded are moved back into the tunnel.
+    pub fn set_paths(&self, paths: HashSet<PathBuf>) {
+        let _ = self.tx.send(AppsMessage::SetPaths(paths));
+    }
+}
+
+impl Drop for ExcludedApps {
+    fn drop(&mut self) {
+        // The thread forwarding process events also holds a sender, so the monitoring thread
+        // must be stopped explicitly
+        let _ = self.tx.send(AppsMessage::Stop);
+    }
+}
+
+/// Subscription to the exec events of the kernel process connector.
+struct ExecEvents {
+    socket: OwnedFd,
+}
+
+impl ExecEvents {
+    fn subscribe() -> io::Result<Self> {
+        // SAFETY: This is a plain socket call. The returned descriptor is checked below.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a valid descriptor that nothing else owns.
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `sockaddr_nl` is valid when zeroed.
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = CN_IDX_PROC;
+        // SAFETY: `address` is a valid `sockaddr_nl` of the given size.
+        let result = unsafe {
+            libc::bind(
+                socket.as_raw_fd(),
+                (&address as *const libc::sockaddr_nl).cast(),
+                mem::size_of_val(&address) as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let message = listen_message();
+        // SAFETY: `message` is valid for reads of its length.
+        let result = unsafe {
+            libc::send(
+                socket.as_raw_fd(),
+                message.as_ptr().cast(),
+                message.len(),
+                0,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(ExecEvents { socket })
     }
 
-    fn open_parent_cgroup_handle(&self) -> io::Result<fs::File> {
-        fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(self.net_cls_path.join("cgroup.procs"))
+    /// Send the PID of every process that starts running a new executable to `tx`, until the
+    /// receiver is dropped.
+    fn forward(self, tx: mpsc::Sender<AppsMessage>) {
+        let mut buffer = [0u8; 1024];
+        loop {
+            // SAFETY: `buffer` is valid for writes of its length.
+            let len = unsafe {
+                libc::recv(
+                    self.socket.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                    0,
+                )
+            };
+            let message = if len >= 0 {
+                match parse_exec_event(&buffer[..len as usize]) {
+                    Some(pid) => AppsMessage::Exec(pid),
+                    None => continue,
+                }
+            } else {
+                let error = io::Error::last_os_error();
+                match error.raw_os_error() {
+                    Some(libc::EINTR) => continue,
+                    // Events were dropped since they were not read quickly enough
+                    Some(libc::ENOBUFS) => AppsMessage::Rescan,
+                    _ => {
+                        log::error!("Failed to receive process events: {error}");
+                        let _ = tx.send(AppsMessage::ExecEventsFailed);
+                        return;
+                    }
+                }
+            };
+            if tx.send(message).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Return a message that subscribes to process connector events.
+fn listen_message() -> Vec<u8> {
+    let payload = PROC_CN_MCAST_LISTEN.to_ne_bytes();
+    let len = mem::size_of::<libc::nlmsghdr>() + CN_MSG_LEN + payload.len();
+
+    let mut message = Vec::with_capacity(len);
+    // struct nlmsghdr: length, type, flags, sequence number, port ID
+    message.extend((len as u32).to_ne_bytes());
+    message.extend((libc::NLMSG_DONE as u16).to_ne_bytes());
+    message.extend(0u16.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    // struct cn_msg: index, value, sequence number, ack, payload length, flags
+    message.extend(CN_IDX_PROC.to_ne_bytes());
+    message.extend(CN_VAL_PROC.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    message.extend((payload.len() as u16).to_ne_bytes());
+    message.extend(0u16.to_ne_bytes());
+    message.extend(payload);
+    message
+}
+
+/// Return the PID of the process in a process connector message, if it is an exec event.
+fn parse_exec_event(message: &[u8]) -> Option<i32> {
+    // struct proc_event: event type, CPU, timestamp, followed by struct exec_proc_event:
+    // thread ID, thread group ID
+    let event = message.get(mem::size_of::<libc::nlmsghdr>() + CN_MSG_LEN..)?;
+    let read_u32 = |offset: usize| {
+        let bytes = event.get(offset..offset + 4)?;
+        Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+    };
+    if read_u32(0)? != PROC_EVENT_EXEC {
+        return None;
+    }
+    // The thread group ID is the PID of the process
+    i32::try_from(read_u32(20)?).ok()
+}
+
+impl PidManager {
+    /// Add all processes running any of the given executables to the cgroup.
+    fn exclude_app_processes(&self, paths: &HashSet<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        let excluded = self.list().unwrap_or_default();
+        for pid in find_app_processes(paths) {
+            if excluded.contains(&pid) {
+                continue;
+            }
+            if let Err(error) = self.add(pid) {
+                log::warn!("Failed to exclude process {pid}: {error}");
+            }
+        }
+    }
+
+    /// Remove all processes running any of the given executables from the cgroup.
+    fn include_app_processes(&self, paths: &HashSet<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        let excluded = self.list().unwrap_or_default();
+        for pid in find_app_processes(paths) {
+            if !excluded.contains(&pid) {
+                continue;
+            }
+            if let Err(error) = self.remove(pid) {
+                log::warn!("Failed to stop excluding process {pid}: {error}");
+            }
+        }
+    }
+}
+
+/// Resolve symbolic links so that paths can be compared to `/proc/<pid>/exe`.
+fn canonicalize_paths(paths: HashSet<PathBuf>) -> HashSet<PathBuf> {
+    paths
+        .into_iter()
+        .map(|path| fs::canonicalize(&path).unwrap_or(path))
+        .collect()
+}
+
+/// Return the PIDs of all processes whose executable is in `paths`.
+fn find_app_processes(paths: &HashSet<PathBuf>) -> Vec<i32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            is_app_process(pid, paths).then_some(pid)
+        })
+        .collect()
+}
+
+/// Return whether the executable of a process is in `paths`.
+fn is_app_process(pid: i32, paths: &HashSet<PathBuf>) -> bool {
+    if paths.is_empty() {
+        return false;
+    }
+    fs::read_link(format!("/proc/{pid}/exe")).is_ok_and(|exe| is_excluded_exe(&exe, paths))
+}
+
+fn is_excluded_exe(exe: &Path, paths: &HashSet<PathBuf>) -> bool {
+    // The kernel appends " (deleted)" if the executable has been replaced, e.g. by an upgrade
+    let exe = exe
+        .to_str()
+        .and_then(|exe| exe.strip_suffix(" (deleted)"))
+        .map(Path::new)
+        .unwrap_or(exe);
+    paths.contains(exe)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded_exe() {
+        let paths = HashSet::from([PathBuf::from("/usr/bin/firefox")]);
+
+        assert!(is_excluded_exe(Path::new("/usr/bin/firefox"), &paths));
+        // The executable may have been replaced while the process is running
+        assert!(is_excluded_exe(
+            Path::new("/usr/bin/firefox (deleted)"),
+            &paths
+        ));
+        assert!(!is_excluded_exe(Path::new("/usr/bin/firefox-esr"), &paths));
+        assert!(!is_excluded_exe(Path::new("/usr/bin"), &paths));
+        assert!(!is_excluded_exe(
+            Path::new("/usr/bin/firefox"),
+            &HashSet::new()
+        ));
+    }
+
+    #[test]
+    fn test_find_app_processes() {
+        let pid = std::process::id() as i32;
+        let exe = fs::canonicalize(env::current_exe().unwrap()).unwrap();
+
+        let paths = canonicalize_paths(HashSet::from([exe]));
+        assert!(is_app_process(pid, &paths));
+        assert!(find_app_processes(&paths).contains(&pid));
+
+        let paths = HashSet::from([PathBuf::from("/nonexistent/app")]);
+        assert!(!is_app_process(pid, &paths));
+        assert!(!find_app_processes(&paths).contains(&pid));
+    }
+
+    #[test]
+    fn test_parse_exec_event() {
+        let event = |what: u32, tgid: u32| {
+            let mut message = vec![0u8; mem::size_of::<libc::nlmsghdr>() + CN_MSG_LEN];
+            message.extend(what.to_ne_bytes());
+            // CPU and timestamp
+            message.extend([0u8; 12]);
+            // Thread ID and thread group ID
+            message.extend(1234u32.to_ne_bytes());
+            message.extend(tgid.to_ne_bytes());
+            message
+        };
+
+        assert_eq!(parse_exec_event(&event(PROC_EVENT_EXEC, 1000)), Some(1000));
+        // Fork events
+        assert_eq!(parse_exec_event(&event(0x1, 1000)), None);
+
+        let mut truncated = event(PROC_EVENT_EXEC, 1000);
+        truncated.truncate(truncated.len() - 1);
+        assert_eq!(parse_exec_event(&truncated), None);
+    }
+
+    #[test]
+    fn test_parse_cgroup2_path() {
+        let contents = "0::/user.slice/user-1000.slice/session-2.scope\n";
+        assert_eq!(
+            parse_cgroup2_path(contents),
+            Some(Path::new("user.slice/user-1000.slice/session-2.scope"))
+        );
+
+        // Hybrid hierarchies also list cgroup v1 controllers
+        let contents = "12:net_cls,net_prio:/\n1:name=systemd:/init.scope\n0::/init.scope\n";
+        assert_eq!(parse_cgroup2_path(contents), Some(Path::new("init.scope")));
+
+        assert_eq!(parse_cgroup2_path("0::/\n"), Some(Path::new("")));
+        assert_eq!(parse_cgroup2_path("1:name=systemd:/\n"), None);
     }
 }
@@ -138,7 +138,13 @@ impl ConnectedState {
             tunnel: self.metadata.clone(),
             allow_lan: shared_values.allow_lan,
             #[cfg(not(target_os = "android"))]
+            lan_access: shared_values.lan_access.clone(),
+            #[cfg(not(target_os = "android"))]
             dns_config: Self::resolve_dns(&self.metadata, shared_values),
+            #[cfg(not(target_os = "android"))]
+            excluded_destinations: shared_values.excluded_destinations.clone(),
+            #[cfg(target_os = "linux")]
+            excluded_resolvers: shared_values.excluded_resolvers(),
             #[cfg(target_os = "macos")]
             redirect_interface,
             #[cfg(target_os = "macos")]
@@ -159,6 +165,11 @@ impl ConnectedState {
 
     fn set_dns(&self, shared_values: &mut SharedTunnelStateValues) -> Result<(), BoxedError> {
         let dns_config: ResolvedDnsConfig = Self::resolve_dns(&self.metadata, shared_values);
+        // Point the system at the local forwarder, if it is enabled
+        #[cfg(not(target_os = "android"))]
+        let dns_config = shared_values
+            .start_dns_forwarder(dns_config)
+            .map_err(BoxedError::new)?;
 
         #[cfg(not(target_os = "macos"))]
         shared_values
@@ -210,6 +221,11 @@ impl ConnectedState {
         shared_values
             .runtime
             .block_on(shared_values.filtering_resolver.disable_forward());
+
+        #[cfg(not(target_os = "android"))]
+        shared_values
+            .runtime
+            .block_on(shared_values.dns_forwarder.stop());
     }
 
     fn reset_routes(
@@ -280,6 +296,64 @@ impl ConnectedState {
                 consequence
             }
             #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::LanAccess(lan_access, complete_tx)) => {
+                let consequence = if shared_values.set_lan_access(lan_access) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::InspectFirewall(request, result_tx)) => {
+                shared_values.inspect_firewall(request, result_tx);
+                SameState(self)
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::ExcludedDestinations(destinations, complete_tx)) => {
+                let consequence = if shared_values.set_excluded_destinations(destinations) {
+                    #[cfg(any(target_os = "windows", target_os = "macos"))]
+                    shared_values.add_excluded_destination_routes();
+                    match self.set_firewall_policy(shared_values) {
+                        Ok(()) => SameState(self),
+                        Err(error) => self.disconnect(
+                            shared_values,
+                            AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                        ),
+                    }
+                } else {
+                    SameState(self)
+                };
+
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 shared_values.allowed_endpoint = endpoint;
                 let _ = tx.send(());
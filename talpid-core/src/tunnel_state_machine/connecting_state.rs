@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -10,7 +11,7 @@ use talpid_routing::RouteManagerHandle;
 use talpid_tunnel::tun_provider::TunProvider;
 use talpid_tunnel::{EventHook, TunnelArgs, TunnelEvent, TunnelMetadata};
 use talpid_types::net::{AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelParameters};
-use talpid_types::tunnel::{ErrorStateCause, FirewallPolicyError};
+use talpid_types::tunnel::{ErrorStateCause, FirewallPolicyError, TunnelStats};
 use talpid_types::ErrorExt;
 
 use super::connected_state::TunnelEventsReceiver;
@@ -45,6 +46,8 @@ pub struct ConnectingState {
     allowed_tunnel_traffic: AllowedTunnelTraffic,
     tunnel_close_event: TunnelCloseEvent,
     tunnel_close_tx: oneshot::Sender<()>,
+    /// Set if the tunnel monitor exited because the tunnel did not come up in time.
+    tunnel_timed_out: Arc<AtomicBool>,
     retry_attempt: u32,
 }
 
@@ -133,6 +136,9 @@ impl ConnectingState {
                         }
                     }
 
+                    #[cfg(any(target_os = "windows", target_os = "macos"))]
+                    shared_values.add_excluded_destination_routes();
+
                     let connecting_state = Self::start_tunnel(
                         shared_values.runtime.clone(),
                         tunnel_parameters,
@@ -140,6 +146,7 @@ impl ConnectingState {
                         &shared_values.resource_dir,
                         shared_values.tun_provider.clone(),
                         &shared_values.route_manager,
+                        shared_values.tunnel_stats_tx.clone(),
                         retry_attempt,
                     );
 
@@ -189,8 +196,12 @@ impl ConnectingState {
             peer_endpoint,
             tunnel: tunnel_metadata.clone(),
             allow_lan: shared_values.allow_lan,
+            #[cfg(not(target_os = "android"))]
+            lan_access: shared_values.lan_access.clone(),
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
+            #[cfg(not(target_os = "android"))]
+            excluded_destinations: shared_values.excluded_destinations.clone(),
             #[cfg(target_os = "macos")]
             redirect_interface,
             #[cfg(target_os = "macos")]
@@ -214,6 +225,7 @@ impl ConnectingState {
             })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn start_tunnel(
         runtime: tokio::runtime::Handle,
         parameters: TunnelParameters,
@@ -221,6 +233,7 @@ impl ConnectingState {
         resource_dir: &Path,
         tun_provider: Arc<Mutex<TunProvider>>,
         route_manager: &RouteManagerHandle,
+        stats_tx: tokio::sync::watch::Sender<Option<TunnelStats>>,
         retry_attempt: u32,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded();
@@ -232,6 +245,8 @@ impl ConnectingState {
 
         let (tunnel_close_tx, tunnel_close_rx) = oneshot::channel();
         let (tunnel_close_event_tx, tunnel_close_event_rx) = oneshot::channel();
+        let tunnel_timed_out = Arc::new(AtomicBool::new(false));
+        let monitor_timed_out = tunnel_timed_out.clone();
 
         let tunnel_parameters = parameters.clone();
 
@@ -246,11 +261,13 @@ impl ConnectingState {
                 tun_provider,
                 retry_attempt,
                 route_manager,
+                stats_tx,
             };
 
             let block_reason = match TunnelMonitor::start(&tunnel_parameters, &log_dir, args) {
                 Ok(monitor) => {
-                    let reason = Self::wait_for_tunnel_monitor(monitor, retry_attempt);
+                    let reason =
+                        Self::wait_for_tunnel_monitor(monitor, retry_attempt, &monitor_timed_out);
                     log::debug!("Tunnel monitor exited with block reason: {:?}", reason);
                     reason
                 }
@@ -289,6 +306,7 @@ impl ConnectingState {
             allowed_tunnel_traffic: INITIAL_ALLOWED_TUNNEL_TRAFFIC,
             tunnel_close_event: tunnel_close_event_rx.fuse(),
             tunnel_close_tx,
+            tunnel_timed_out,
             retry_attempt,
         }
     }
@@ -296,6 +314,7 @@ impl ConnectingState {
     fn wait_for_tunnel_monitor(
         tunnel_monitor: TunnelMonitor,
         retry_attempt: u32,
+        timed_out: &AtomicBool,
     ) -> Option<ErrorStateCause> {
         match tunnel_monitor.wait() {
             Ok(_) => None,
@@ -304,6 +323,7 @@ impl ConnectingState {
                     talpid_wireguard::Error::TimeoutError,
                 ) => {
                     log::debug!("WireGuard tunnel timed out");
+                    timed_out.store(true, Ordering::Release);
                     None
                 }
                 error @ tunnel::Error::WireguardTunnelMonitoringError(..)
@@ -401,6 +421,43 @@ impl ConnectingState {
                 consequence
             }
             #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::LanAccess(lan_access, complete_tx)) => {
+                let consequence = if shared_values.set_lan_access(lan_access) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let consequence = if shared_values.set_split_tunnel_mode(mode) {
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::InspectFirewall(request, result_tx)) => {
+                shared_values.inspect_firewall(request, result_tx);
+                SameState(self)
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::ExcludedDestinations(destinations, complete_tx)) => {
+                let consequence = if shared_values.set_excluded_destinations(destinations) {
+                    #[cfg(any(target_os = "windows", target_os = "macos"))]
+                    shared_values.add_excluded_destination_routes();
+                    self.reset_firewall(shared_values)
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
@@ -607,6 +664,13 @@ impl ConnectingState {
             "Tunnel closed. Reconnecting, attempt {}.",
             self.retry_attempt + 1
         );
+        if self.tunnel_timed_out.load(Ordering::Acquire) {
+            shared_values.runtime.block_on(
+                shared_values
+                    .tunnel_parameters_generator
+                    .attempt_timed_out(),
+            );
+        }
         Self::reset_routes(shared_values);
         EventConsequence::NewState(ConnectingState::enter(
             shared_values,
@@ -79,6 +79,7 @@ impl DisconnectedState {
         let result = if shared_values.block_when_disconnected {
             let policy = FirewallPolicy::Blocked {
                 allow_lan: shared_values.allow_lan,
+                lan_access: shared_values.lan_access.clone(),
                 allowed_endpoint: Some(shared_values.allowed_endpoint.clone()),
                 #[cfg(target_os = "macos")]
                 dns_redirect_port: shared_values.filtering_resolver.listening_port(),
@@ -174,6 +175,34 @@ impl TunnelState for DisconnectedState {
                 SameState(self)
             }
             #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::LanAccess(lan_access, complete_tx)) => {
+                if shared_values.set_lan_access(lan_access) {
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                if shared_values.set_split_tunnel_mode(mode) {
+                    Self::set_firewall_policy(shared_values, false);
+                }
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::InspectFirewall(request, result_tx)) => {
+                shared_values.inspect_firewall(request, result_tx);
+                SameState(self)
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::ExcludedDestinations(destinations, complete_tx)) => {
+                // Excluded destinations only affect the connecting and connected states.
+                let _ = shared_values.set_excluded_destinations(destinations);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 if shared_values.allowed_endpoint != endpoint {
                     shared_values.allowed_endpoint = endpoint;
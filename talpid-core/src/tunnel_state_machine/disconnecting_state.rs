@@ -42,6 +42,25 @@ impl DisconnectingState {
                 let _ = complete_tx.send(());
             }
             #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::LanAccess(lan_access, complete_tx)) => {
+                let _ = shared_values.set_lan_access(lan_access);
+                let _ = complete_tx.send(());
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::SplitTunnelMode(mode, complete_tx)) => {
+                let _ = shared_values.set_split_tunnel_mode(mode);
+                let _ = complete_tx.send(());
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::InspectFirewall(request, result_tx)) => {
+                shared_values.inspect_firewall(request, result_tx);
+            }
+            #[cfg(not(target_os = "android"))]
+            Some(TunnelCommand::ExcludedDestinations(destinations, complete_tx)) => {
+                let _ = shared_values.set_excluded_destinations(destinations);
+                let _ = complete_tx.send(());
+            }
+            #[cfg(not(target_os = "android"))]
             Some(TunnelCommand::AllowEndpoint(endpoint, tx)) => {
                 shared_values.allowed_endpoint = endpoint;
                 let _ = tx.send(());
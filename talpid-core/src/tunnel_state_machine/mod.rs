@@ -19,6 +19,8 @@ use crate::{
     mpsc::Sender,
     offline,
 };
+#[cfg(not(target_os = "android"))]
+use ipnetwork::IpNetwork;
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 use std::ffi::OsString;
 use talpid_routing::RouteManagerHandle;
@@ -26,7 +28,9 @@ use talpid_routing::RouteManagerHandle;
 use talpid_tunnel::TunnelMetadata;
 use talpid_tunnel::{tun_provider::TunProvider, TunnelEvent};
 use talpid_tunnel_config_client::classic_mceliece::spawn_keypair_generator;
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "android"))]
+use talpid_types::net::LanAccess;
+#[cfg(any(windows, target_os = "macos"))]
 use talpid_types::ErrorExt;
 
 use futures::{
@@ -43,18 +47,47 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(target_os = "linux")]
+use talpid_types::split_tunnel::SplitTunnelMode;
 #[cfg(target_os = "android")]
 use talpid_types::{android::AndroidContext, ErrorExt};
+#[cfg(target_os = "linux")]
+use talpid_types::{
+    firewall::{FirewallInspection, FirewallInspectionRequest, FirewallPolicyKind},
+    net::{AllowedClients, AllowedTunnelTraffic, Endpoint, TransportProtocol},
+};
 use talpid_types::{
     net::{AllowedEndpoint, Connectivity, TunnelParameters},
-    tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
+    tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition, TunnelStats},
 };
+use tokio::sync::watch;
 
 #[cfg(target_os = "android")]
 use crate::connectivity_listener::ConnectivityListener;
 
 const TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Relay address used when rendering firewall policies without a peer. Belongs to TEST-NET-1.
+#[cfg(target_os = "linux")]
+const EXAMPLE_PEER_ADDRESS: std::net::Ipv4Addr = std::net::Ipv4Addr::new(192, 0, 2, 1);
+
+/// Tunnel used when rendering firewall policies without a tunnel. Uses the addresses that
+/// WireGuard relays hand out.
+#[cfg(target_os = "linux")]
+fn example_tunnel_metadata() -> talpid_tunnel::TunnelMetadata {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    talpid_tunnel::TunnelMetadata {
+        interface: "wg0-mullvad".to_owned(),
+        ips: vec![
+            Ipv4Addr::new(10, 64, 0, 2).into(),
+            Ipv6Addr::new(0xfc00, 0xbbbb, 0xbbbb, 0xbb01, 0, 0, 0, 2).into(),
+        ],
+        ipv4_gateway: Ipv4Addr::new(10, 64, 0, 1),
+        ipv6_gateway: Some(Ipv6Addr::new(0xfc00, 0xbbbb, 0xbbbb, 0xbb01, 0, 0, 0, 1)),
+    }
+}
+
 /// Errors that can happen when setting up or using the state machine.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -93,6 +126,9 @@ pub enum Error {
 pub struct InitialTunnelState {
     /// Whether to allow LAN traffic when not in the (non-blocking) disconnected state.
     pub allow_lan: bool,
+    /// Which local and additional networks to allow outside the tunnel.
+    #[cfg(not(target_os = "android"))]
+    pub lan_access: LanAccess,
     /// Block traffic unless connected to the VPN.
     #[cfg(not(target_os = "android"))]
     pub block_when_disconnected: bool,
@@ -109,6 +145,12 @@ pub struct InitialTunnelState {
     /// Apps to exclude from the tunnel.
     #[cfg(target_os = "android")]
     pub exclude_paths: Vec<String>,
+    /// Whether split tunneled processes bypass the tunnel or are the only ones using it.
+    #[cfg(target_os = "linux")]
+    pub split_tunnel_mode: SplitTunnelMode,
+    /// Destination networks that should be reached outside the tunnel.
+    #[cfg(not(target_os = "android"))]
+    pub excluded_destinations: Vec<IpNetwork>,
 }
 
 /// Identifiers for various network resources that should be unique to a given instance of a tunnel
@@ -150,6 +192,8 @@ pub async fn spawn(
 
     let weak_command_tx = Arc::downgrade(&command_tx);
 
+    let (tunnel_stats_tx, tunnel_stats_rx) = watch::channel(None);
+
     let init_args = TunnelStateMachineInitArgs {
         settings: initial_settings,
         command_tx: weak_command_tx,
@@ -160,6 +204,7 @@ pub async fn spawn(
         resource_dir,
         commands_rx: command_rx,
         route_manager,
+        tunnel_stats_tx,
         #[cfg(target_os = "windows")]
         volume_update_rx,
         #[cfg(target_os = "android")]
@@ -186,6 +231,7 @@ pub async fn spawn(
     Ok(TunnelStateMachineHandle {
         command_tx,
         shutdown_rx,
+        tunnel_stats: tunnel_stats_rx,
         #[cfg(windows)]
         split_tunnel,
     })
@@ -195,6 +241,9 @@ pub async fn spawn(
 pub enum TunnelCommand {
     /// Enable or disable LAN access in the firewall.
     AllowLan(bool, oneshot::Sender<()>),
+    /// Set which local and additional networks the firewall allows.
+    #[cfg(not(target_os = "android"))]
+    LanAccess(LanAccess, oneshot::Sender<()>),
     /// Endpoint that should never be blocked. `()` is sent to the
     /// channel after attempting to set the firewall policy, regardless
     /// of whether it succeeded.
@@ -228,6 +277,18 @@ pub enum TunnelCommand {
         oneshot::Sender<Result<(), split_tunnel::Error>>,
         Vec<String>,
     ),
+    /// Set whether split tunneled processes bypass the tunnel or are the only ones using it.
+    #[cfg(target_os = "linux")]
+    SplitTunnelMode(SplitTunnelMode, oneshot::Sender<()>),
+    /// Set destination networks that should be reached outside the tunnel.
+    #[cfg(not(target_os = "android"))]
+    ExcludedDestinations(Vec<IpNetwork>, oneshot::Sender<()>),
+    /// Render a firewall policy without applying it, along with the installed ruleset.
+    #[cfg(target_os = "linux")]
+    InspectFirewall(
+        FirewallInspectionRequest,
+        oneshot::Sender<Result<FirewallInspection, crate::firewall::Error>>,
+    ),
 }
 
 type TunnelCommandReceiver = stream::Fuse<mpsc::UnboundedReceiver<TunnelCommand>>;
@@ -261,6 +322,7 @@ struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     resource_dir: PathBuf,
     commands_rx: mpsc::UnboundedReceiver<TunnelCommand>,
     route_manager: RouteManagerHandle,
+    tunnel_stats_tx: watch::Sender<Option<TunnelStats>>,
     #[cfg(target_os = "windows")]
     volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")]
@@ -310,8 +372,12 @@ impl TunnelStateMachine {
             #[cfg(target_os = "android")]
             initial_state: InitialFirewallState::None,
             allow_lan: args.settings.allow_lan,
+            #[cfg(not(target_os = "android"))]
+            lan_access: args.settings.lan_access.clone(),
             #[cfg(target_os = "linux")]
             fwmark: args.linux_ids.fwmark,
+            #[cfg(target_os = "linux")]
+            split_tunnel_mode: args.settings.split_tunnel_mode,
         };
 
         let firewall = Firewall::from_args(fw_args).map_err(Error::InitFirewallError)?;
@@ -380,10 +446,15 @@ impl TunnelStateMachine {
             firewall,
             dns_monitor,
             route_manager: args.route_manager,
+            tunnel_stats_tx: args.tunnel_stats_tx,
             _offline_monitor: offline_monitor,
             allow_lan: args.settings.allow_lan,
             #[cfg(not(target_os = "android"))]
+            lan_access: args.settings.lan_access,
+            #[cfg(not(target_os = "android"))]
             block_when_disconnected: args.settings.block_when_disconnected,
+            #[cfg(not(target_os = "android"))]
+            excluded_destinations: args.settings.excluded_destinations,
             connectivity,
             dns_config: args.settings.dns_config,
             allowed_endpoint: args.settings.allowed_endpoint,
@@ -395,6 +466,11 @@ impl TunnelStateMachine {
             connectivity_check_was_enabled: None,
             #[cfg(target_os = "macos")]
             filtering_resolver,
+            #[cfg(not(target_os = "android"))]
+            dns_forwarder: crate::dns::forwarder::DnsForwarder::new(
+                #[cfg(target_os = "linux")]
+                args.linux_ids.fwmark,
+            ),
         };
 
         tokio::task::spawn_blocking(move || {
@@ -454,6 +530,11 @@ pub trait TunnelParametersGenerator: Send + 'static {
         retry_attempt: u32,
         ipv6: bool,
     ) -> Pin<Box<dyn Future<Output = Result<TunnelParameters, ParameterGenerationError>>>>;
+
+    /// Called when the tunnel that was created using the most recently generated parameters is
+    /// closed because it did not become functional in time, i.e. because the handshake or the
+    /// connectivity check timed out.
+    fn attempt_timed_out(&mut self) -> Pin<Box<dyn Future<Output = ()>>>;
 }
 
 /// Values that are common to all tunnel states.
@@ -471,12 +552,20 @@ struct SharedTunnelStateValues {
     firewall: Firewall,
     dns_monitor: DnsMonitor,
     route_manager: RouteManagerHandle,
+    /// Sender for traffic statistics of the current tunnel.
+    tunnel_stats_tx: watch::Sender<Option<TunnelStats>>,
     _offline_monitor: offline::MonitorHandle,
     /// Should LAN access be allowed outside the tunnel.
     allow_lan: bool,
+    /// Which local and additional networks to allow outside the tunnel.
+    #[cfg(not(target_os = "android"))]
+    lan_access: LanAccess,
     /// Should network access be allowed when in the disconnected state.
     #[cfg(not(target_os = "android"))]
     block_when_disconnected: bool,
+    /// Destination networks that should be reached outside the tunnel.
+    #[cfg(not(target_os = "android"))]
+    excluded_destinations: Vec<IpNetwork>,
     /// True when the computer is known to be offline.
     connectivity: Connectivity,
     /// DNS configuration to use.
@@ -499,6 +588,10 @@ struct SharedTunnelStateValues {
     /// Filtering resolver handle
     #[cfg(target_os = "macos")]
     filtering_resolver: crate::resolver::ResolverHandle,
+
+    /// Local DNS forwarder, used in the connected state if enabled in `dns_config`
+    #[cfg(not(target_os = "android"))]
+    dns_forwarder: crate::dns::forwarder::DnsForwarder,
 }
 
 impl SharedTunnelStateValues {
@@ -560,6 +653,84 @@ impl SharedTunnelStateValues {
             .map_err(|error| ErrorStateCause::from(&error))
     }
 
+    /// Return whether the split tunnel mode changed
+    #[cfg(target_os = "linux")]
+    pub fn set_split_tunnel_mode(&mut self, mode: SplitTunnelMode) -> bool {
+        self.firewall.set_split_tunnel_mode(mode)
+    }
+
+    /// Set the destinations that are reached outside the tunnel. On Windows and macOS, routes for
+    /// destinations that are no longer excluded are removed.
+    #[cfg(not(target_os = "android"))]
+    pub fn set_excluded_destinations(&mut self, destinations: Vec<IpNetwork>) -> bool {
+        if self.excluded_destinations != destinations {
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            self.remove_excluded_destination_routes(&destinations);
+            self.excluded_destinations = destinations;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return the resolvers that the DNS forwarder sends queries for excluded domains to.
+    #[cfg(target_os = "linux")]
+    pub fn excluded_resolvers(&self) -> Vec<std::net::IpAddr> {
+        self.dns_config
+            .forwarder()
+            .excluded_domains
+            .as_ref()
+            .map(|domains| domains.resolvers.clone())
+            .unwrap_or_default()
+    }
+
+    /// Route excluded destinations via the default route rather than the tunnel. On Linux, this
+    /// is handled by the firewall marking packets so that they bypass the tunnel routing table.
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub fn add_excluded_destination_routes(&self) {
+        if self.excluded_destinations.is_empty() {
+            return;
+        }
+        let routes = self
+            .excluded_destinations
+            .iter()
+            .map(|network| {
+                talpid_routing::RequiredRoute::new(*network, talpid_routing::NetNode::DefaultNode)
+            })
+            .collect();
+        if let Err(error) = self.runtime.block_on(self.route_manager.add_routes(routes)) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to add routes for excluded destinations")
+            );
+        }
+    }
+
+    /// Remove the routes of currently excluded destinations that are not in `destinations`.
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn remove_excluded_destination_routes(&self, destinations: &[IpNetwork]) {
+        let routes: std::collections::HashSet<_> = self
+            .excluded_destinations
+            .iter()
+            .filter(|network| !destinations.contains(network))
+            .map(|network| {
+                talpid_routing::RequiredRoute::new(*network, talpid_routing::NetNode::DefaultNode)
+            })
+            .collect();
+        if routes.is_empty() {
+            return;
+        }
+        if let Err(error) = self
+            .runtime
+            .block_on(self.route_manager.remove_routes(routes))
+        {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to remove routes for excluded destinations")
+            );
+        }
+    }
+
     pub fn set_allow_lan(&mut self, allow_lan: bool) -> bool {
         if self.allow_lan != allow_lan {
             self.allow_lan = allow_lan;
@@ -569,6 +740,141 @@ impl SharedTunnelStateValues {
         }
     }
 
+    #[cfg(not(target_os = "android"))]
+    pub fn set_lan_access(&mut self, lan_access: LanAccess) -> bool {
+        if self.lan_access != lan_access {
+            self.lan_access = lan_access;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Render a firewall policy without applying it, along with the installed ruleset, and send
+    /// the result on `result_tx`. Connecting and connected policies use the current peer and
+    /// tunnel if there are any, and example values otherwise.
+    #[cfg(target_os = "linux")]
+    pub fn inspect_firewall(
+        &self,
+        request: FirewallInspectionRequest,
+        result_tx: oneshot::Sender<Result<FirewallInspection, crate::firewall::Error>>,
+    ) {
+        use crate::firewall::{Firewall, FirewallPolicy};
+
+        let current_policy = self.firewall.policy();
+        let allow_lan = request.allow_lan.unwrap_or(self.allow_lan);
+        let peer_endpoint = || {
+            current_policy
+                .and_then(FirewallPolicy::peer_endpoint)
+                .cloned()
+                .unwrap_or_else(|| AllowedEndpoint {
+                    endpoint: Endpoint::new(EXAMPLE_PEER_ADDRESS, 51820, TransportProtocol::Udp),
+                    clients: AllowedClients::Root,
+                })
+        };
+
+        let policy = match request.policy {
+            FirewallPolicyKind::Current => current_policy.cloned().map(|mut policy| {
+                if let Some(new_allow_lan) = request.allow_lan {
+                    match &mut policy {
+                        FirewallPolicy::Connecting { allow_lan, .. }
+                        | FirewallPolicy::Connected { allow_lan, .. }
+                        | FirewallPolicy::Blocked { allow_lan, .. } => *allow_lan = new_allow_lan,
+                    }
+                }
+                policy
+            }),
+            FirewallPolicyKind::Blocked => Some(FirewallPolicy::Blocked {
+                allow_lan,
+                lan_access: self.lan_access.clone(),
+                allowed_endpoint: Some(self.allowed_endpoint.clone()),
+            }),
+            FirewallPolicyKind::Connecting => {
+                let (tunnel, allowed_tunnel_traffic) = match current_policy {
+                    Some(policy @ FirewallPolicy::Connecting { .. }) => (
+                        policy.tunnel().cloned(),
+                        policy.allowed_tunnel_traffic().clone(),
+                    ),
+                    _ => (None, AllowedTunnelTraffic::None),
+                };
+                Some(FirewallPolicy::Connecting {
+                    peer_endpoint: peer_endpoint(),
+                    tunnel,
+                    allow_lan,
+                    lan_access: self.lan_access.clone(),
+                    allowed_endpoint: self.allowed_endpoint.clone(),
+                    allowed_tunnel_traffic,
+                    excluded_destinations: self.excluded_destinations.clone(),
+                })
+            }
+            FirewallPolicyKind::Connected => {
+                let tunnel = current_policy
+                    .and_then(FirewallPolicy::tunnel)
+                    .cloned()
+                    .unwrap_or_else(example_tunnel_metadata);
+                Some(FirewallPolicy::Connected {
+                    peer_endpoint: peer_endpoint(),
+                    dns_config: self.dns_config.resolve(&tunnel.gateways()),
+                    tunnel,
+                    allow_lan,
+                    lan_access: self.lan_access.clone(),
+                    excluded_destinations: self.excluded_destinations.clone(),
+                    excluded_resolvers: self.excluded_resolvers(),
+                })
+            }
+        };
+
+        let render_policy = policy
+            .as_ref()
+            .map(|policy| self.firewall.policy_renderer(policy));
+        let policy = policy.map(|policy| policy.to_string());
+
+        // Rendering runs external programs, so keep it off the state machine thread
+        self.runtime.spawn_blocking(move || {
+            let inspection = render_policy
+                .map(|render_policy| render_policy())
+                .transpose()
+                .and_then(|expected_ruleset| {
+                    Ok(FirewallInspection {
+                        policy,
+                        expected_ruleset,
+                        live_ruleset: Firewall::render_active_ruleset()?,
+                    })
+                });
+            let _ = result_tx.send(inspection);
+        });
+    }
+
+    /// Start the local DNS forwarder if it is enabled in the DNS config, and forward queries to
+    /// `dns_config` unless encrypted resolvers are used. Returns the DNS config that the system
+    /// should use.
+    #[cfg(not(target_os = "android"))]
+    pub fn start_dns_forwarder(
+        &mut self,
+        dns_config: crate::dns::ResolvedDnsConfig,
+    ) -> Result<crate::dns::ResolvedDnsConfig, crate::dns::forwarder::Error> {
+        use crate::dns::forwarder::Upstream;
+
+        let forwarder = self.dns_config.forwarder().clone();
+        if !forwarder.is_enabled() {
+            self.runtime.block_on(self.dns_forwarder.stop());
+            return Ok(dns_config);
+        }
+
+        let upstream = if forwarder.encrypted_resolvers.is_empty() {
+            Upstream::Plain(dns_config.addresses().collect())
+        } else {
+            Upstream::Encrypted(forwarder.encrypted_resolvers)
+        };
+        self.runtime.block_on(self.dns_forwarder.start(
+            upstream,
+            forwarder.filter,
+            forwarder.excluded_domains,
+        ))?;
+
+        Ok(crate::dns::ResolvedDnsConfig::forwarder())
+    }
+
     pub fn set_dns_config(&mut self, dns_config: DnsConfig) -> bool {
         if self.dns_config != dns_config {
             self.dns_config = dns_config;
@@ -702,6 +1008,7 @@ trait TunnelState: Send {
 pub struct TunnelStateMachineHandle {
     command_tx: Arc<mpsc::UnboundedSender<TunnelCommand>>,
     shutdown_rx: oneshot::Receiver<()>,
+    tunnel_stats: watch::Receiver<Option<TunnelStats>>,
     #[cfg(windows)]
     split_tunnel: split_tunnel::SplitTunnelHandle,
 }
@@ -723,6 +1030,12 @@ impl TunnelStateMachineHandle {
         &self.command_tx
     }
 
+    /// Returns a receiver of traffic statistics for the current tunnel. The value is `None` when
+    /// there is no tunnel, or if its statistics are not yet known.
+    pub fn tunnel_stats(&self) -> watch::Receiver<Option<TunnelStats>> {
+        self.tunnel_stats.clone()
+    }
+
     /// Returns split tunnel object handle.
     #[cfg(windows)]
     pub fn split_tunnel(&self) -> &split_tunnel::SplitTunnelHandle {
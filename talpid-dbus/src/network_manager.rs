@@ -43,6 +43,9 @@ const NM_SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
 const NM_SETTINGS_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
 const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
 const NM_CONNECTION_ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const NM_DEVICE_WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_ACCESS_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_WIRELESS_CONNECTION_TYPE: &str = "802-11-wireless";
 
 const NM_ADD_CONNECTION_VOLATILE: u32 = 0x2;
 
@@ -643,6 +646,45 @@ impl NetworkManager {
         Err(Error::DeviceNotFound)
     }
 
+    /// Returns the SSID of the Wi-Fi network used by the primary connection, if the primary
+    /// connection is a Wi-Fi connection.
+    pub fn get_primary_connection_ssid(&self) -> Result<Option<String>> {
+        let connection_path: dbus::Path<'static> = self
+            .as_manager()
+            .get(NM_MANAGER, "PrimaryConnection")
+            .map_err(Error::Dbus)?;
+        if &*connection_path == "/" {
+            return Ok(None);
+        }
+
+        let connection = self.as_path(&connection_path);
+        let connection_type: String = connection
+            .get(NM_CONNECTION_ACTIVE, "Type")
+            .map_err(Error::Dbus)?;
+        if connection_type != NM_WIRELESS_CONNECTION_TYPE {
+            return Ok(None);
+        }
+
+        let device_paths: Vec<dbus::Path<'static>> = connection
+            .get(NM_CONNECTION_ACTIVE, "Devices")
+            .map_err(Error::Dbus)?;
+        let device_path = device_paths.into_iter().next().ok_or(Error::NoDevice)?;
+
+        let access_point_path: dbus::Path<'static> = self
+            .as_path(&device_path)
+            .get(NM_DEVICE_WIRELESS, "ActiveAccessPoint")
+            .map_err(Error::Dbus)?;
+        if &*access_point_path == "/" {
+            return Ok(None);
+        }
+
+        let ssid: Vec<u8> = self
+            .as_path(&access_point_path)
+            .get(NM_ACCESS_POINT, "Ssid")
+            .map_err(Error::Dbus)?;
+        Ok(Some(String::from_utf8_lossy(&ssid).into_owned()))
+    }
+
     pub fn convert_address_to_dbus(address: &IpAddr) -> VariantMap {
         let mut map: VariantMap = HashMap::new();
         map.insert(
@@ -225,6 +225,11 @@ impl RouteManagerImpl {
                             log::debug!("Adding routes: {routes:?}");
                             let _ = tx.send(self.add_required_routes(routes).await);
                         }
+                        Some(RouteManagerCommand::RemoveRoutes(routes, tx)) => {
+                            log::debug!("Removing routes: {routes:?}");
+                            self.remove_required_routes(routes).await;
+                            let _ = tx.send(());
+                        }
                         Some(RouteManagerCommand::ClearRoutes) => {
                             if let Err(err) = self.cleanup_routes().await {
                                 log::error!("Failed to clean up rotues: {err}");
@@ -565,6 +570,26 @@ impl RouteManagerImpl {
         Ok(())
     }
 
+    /// Remove routes added by `add_required_routes`, except for default routes.
+    async fn remove_required_routes(&mut self, required_routes: HashSet<RequiredRoute>) {
+        let mut removed = HashSet::new();
+        for route in required_routes {
+            if route.node == NetNode::DefaultNode {
+                self.non_tunnel_routes.remove(&route.prefix);
+            }
+            if route.prefix.prefix() != 0 {
+                removed.insert(route.prefix);
+            }
+        }
+
+        self.remove_applied_routes(|route| {
+            route
+                .destination_ip()
+                .is_ok_and(|destination| removed.contains(&destination))
+        })
+        .await;
+    }
+
     /// Update/add routes that use the default non-tunnel interface. If some applied destination is
     /// a default route, this function replaces the non-tunnel default route with an ifscope route.
     async fn apply_non_tunnel_routes(&mut self) -> Result<()> {
@@ -118,6 +118,7 @@ pub(crate) enum RouteManagerCommand {
         HashSet<RequiredRoute>,
         oneshot::Sender<Result<(), PlatformError>>,
     ),
+    RemoveRoutes(HashSet<RequiredRoute>, oneshot::Sender<()>),
     ClearRoutes,
     Shutdown(oneshot::Sender<()>),
     RefreshRoutes,
@@ -213,6 +214,17 @@ impl RouteManagerHandle {
             .map_err(Error::PlatformError)
     }
 
+    /// Removes the given routes if they were applied in [`RouteManagerHandle::add_routes`].
+    /// Default routes are only removed by [`RouteManagerHandle::clear_routes`].
+    #[cfg(target_os = "macos")]
+    pub async fn remove_routes(&self, routes: HashSet<RequiredRoute>) -> Result<(), Error> {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.tx
+            .unbounded_send(RouteManagerCommand::RemoveRoutes(routes, result_tx))
+            .map_err(|_| Error::RouteManagerDown)?;
+        result_rx.await.map_err(|_| Error::ManagerChannelDown)
+    }
+
     /// Wait for routes to come up.
     ///
     /// This function is guaranteed to *not* wait for longer than 2 seconds.
@@ -59,6 +59,10 @@ pub enum Error {
     /// Contains the lower error
     #[error("Failed to add routes")]
     AddRoutesFailed(Box<Error>),
+    /// High level error caused by a failure to remove routes in the route manager.
+    /// Contains the lower error
+    #[error("Failed to remove routes")]
+    RemoveRoutesFailed(Box<Error>),
     /// Something went wrong when getting the mtu of the interface
     #[error("Could not get the mtu of the interface")]
     GetMtu,
@@ -105,6 +109,7 @@ pub struct RouteManagerHandle {
 
 pub enum RouteManagerCommand {
     AddRoutes(HashSet<RequiredRoute>, oneshot::Sender<Result<()>>),
+    RemoveRoutes(HashSet<RequiredRoute>, oneshot::Sender<Result<()>>),
     GetMtuForRoute(IpAddr, oneshot::Sender<Result<u16>>),
     ClearRoutes,
     RegisterDefaultRouteChangeCallback(Callback, oneshot::Sender<CallbackHandle>),
@@ -147,6 +152,15 @@ impl RouteManagerHandle {
         response_rx.await.map_err(|_| Error::RouteManagerDown)?
     }
 
+    /// Removes the given routes if they were applied in [`RouteManagerHandle::add_routes`].
+    pub async fn remove_routes(&self, routes: HashSet<RequiredRoute>) -> Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .unbounded_send(RouteManagerCommand::RemoveRoutes(routes, response_tx))
+            .map_err(|_| Error::RouteManagerDown)?;
+        response_rx.await.map_err(|_| Error::RouteManagerDown)?
+    }
+
     /// Retrieve MTU for the given destination/route.
     pub async fn get_mtu_for_route(&self, ip: IpAddr) -> Result<u16> {
         let (response_tx, response_rx) = oneshot::channel();
@@ -193,6 +207,21 @@ impl RouteManagerHandle {
                             .map_err(|e| Error::AddRoutesFailed(Box::new(e))),
                     );
                 }
+                RouteManagerCommand::RemoveRoutes(routes, tx) => {
+                    let routes: Vec<_> = routes
+                        .into_iter()
+                        .map(|route| Route {
+                            network: route.prefix,
+                            node: route.node,
+                        })
+                        .collect();
+
+                    let _ = tx.send(
+                        internal
+                            .remove_routes(&routes)
+                            .map_err(|e| Error::RemoveRoutesFailed(Box::new(e))),
+                    );
+                }
                 RouteManagerCommand::GetMtuForRoute(ip, tx) => {
                     let addr_family = if ip.is_ipv4() {
                         AddressFamily::Ipv4
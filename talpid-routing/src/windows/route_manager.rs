@@ -419,6 +419,30 @@ impl RouteManagerInternal {
         Ok(Some(luid))
     }
 
+    /// Delete the given routes if they were applied in [`RouteManagerInternal::add_routes`].
+    pub fn remove_routes(&mut self, removed_routes: &[Route]) -> Result<()> {
+        let mut routes = self.routes.lock().unwrap();
+        let mut result = Ok(());
+
+        routes.retain(|record| {
+            let is_removed = removed_routes.iter().any(|route| {
+                route.network == record.route.network && route.node == record.route.node
+            });
+            if !is_removed {
+                return true;
+            }
+            match Self::delete_from_routing_table(&record.registered_route) {
+                Ok(()) => false,
+                Err(error) => {
+                    result = Err(error);
+                    true
+                }
+            }
+        });
+
+        result
+    }
+
     pub fn delete_applied_routes(&mut self) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         // Delete all routes owned by us.
@@ -49,6 +49,8 @@ pub struct TunnelArgs<'a> {
     pub retry_attempt: u32,
     /// Route manager handle.
     pub route_manager: RouteManagerHandle,
+    /// Sender for traffic statistics of the tunnel while it is up.
+    pub stats_tx: tokio::sync::watch::Sender<Option<talpid_types::tunnel::TunnelStats>>,
 }
 
 #[derive(Clone)]
@@ -2,18 +2,53 @@ use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
 
 pub const SPLIT_TUNNEL_CGROUP_NAME: &str = "mullvad-exclusions";
 
+/// Depth of the split tunneling cgroup in the cgroup v2 hierarchy. It is created directly below
+/// the root of the unified hierarchy.
+pub const SPLIT_TUNNEL_CGROUP2_LEVEL: u32 = 1;
+
 /// Find the path of the cgroup v1 net_cls controller mount if it exists
 pub fn find_net_cls_mount() -> std::io::Result<Option<PathBuf>> {
     let mounts = fs::read("/proc/mounts")?;
     Ok(find_net_cls_mount_inner(&mounts))
 }
 
+/// Find the path of the cgroup v2 (unified hierarchy) mount if it exists
+pub fn find_cgroup2_mount() -> std::io::Result<Option<PathBuf>> {
+    let mounts = fs::read("/proc/mounts")?;
+    Ok(find_cgroup2_mount_inner(&mounts))
+}
+
+/// Find the split tunneling cgroup created by the daemon. The `net_cls` hierarchy is preferred
+/// over cgroup v2, matching the order in which the daemon picks a hierarchy.
+pub fn find_split_tunnel_cgroup() -> std::io::Result<Option<PathBuf>> {
+    for mount in [find_net_cls_mount()?, find_cgroup2_mount()?]
+        .into_iter()
+        .flatten()
+    {
+        let cgroup = mount.join(SPLIT_TUNNEL_CGROUP_NAME);
+        if cgroup.exists() {
+            return Ok(Some(cgroup));
+        }
+    }
+    Ok(None)
+}
+
 fn find_net_cls_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
     mounts
         .split(|byte| *byte == b'\n')
         .find_map(parse_mount_line)
 }
 
+fn find_cgroup2_mount_inner(mounts: &[u8]) -> Option<PathBuf> {
+    mounts.split(|byte| *byte == b'\n').find_map(|line| {
+        let mut parts = line.split(|byte| *byte == b' ');
+        let _device_type = parts.next()?;
+        let mount_path = parts.next()?;
+        let filesystem_type = parts.next()?;
+        (filesystem_type == b"cgroup2").then(|| PathBuf::from(OsStr::from_bytes(mount_path)))
+    })
+}
+
 fn parse_mount_line(line: &[u8]) -> Option<PathBuf> {
     // Each line contains multiple values separated by space.
     // `cgroup /sys/fs/cgroup/net_cls,net_prio cgroup
@@ -72,4 +107,17 @@ cgroup /nope
 
         assert_eq!(find_net_cls_mount_inner(input), None)
     }
+
+    #[test]
+    fn test_find_cgroup2_path() {
+        let input = br#"sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+cgroup2 /sys/fs/cgroup cgroup2 rw,nosuid,nodev,noexec,relatime,nsdelegate,memory_recursiveprot 0 0
+"#;
+
+        assert_eq!(
+            find_cgroup2_mount_inner(input),
+            Some(PathBuf::from("/sys/fs/cgroup"))
+        );
+        assert_eq!(find_net_cls_mount_inner(input), None);
+    }
 }
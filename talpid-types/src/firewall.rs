@@ -0,0 +1,34 @@
+/// Which firewall policy to render when inspecting the firewall.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FirewallPolicyKind {
+    /// The policy that is currently enforced.
+    #[default]
+    Current,
+    /// The policy of the blocked and error states.
+    Blocked,
+    /// The policy of the connecting state.
+    Connecting,
+    /// The policy of the connected state.
+    Connected,
+}
+
+/// Request to render a firewall policy without applying it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FirewallInspectionRequest {
+    /// The policy to render.
+    pub policy: FirewallPolicyKind,
+    /// Overrides whether the rendered policy allows LAN traffic.
+    pub allow_lan: Option<bool>,
+}
+
+/// A rendered firewall policy along with the ruleset that is currently installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallInspection {
+    /// Description of the rendered policy. `None` if the current policy was requested and no
+    /// policy is enforced.
+    pub policy: Option<String>,
+    /// The ruleset that the rendered policy installs.
+    pub expected_ruleset: Option<String>,
+    /// The ruleset that is currently installed.
+    pub live_ruleset: Option<String>,
+}
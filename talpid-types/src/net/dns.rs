@@ -0,0 +1,242 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::IpAddr};
+
+/// The only path that DNS-over-HTTPS queries can be sent to.
+pub const DNS_OVER_HTTPS_PATH: &str = "/dns-query";
+
+const DNS_OVER_HTTPS_PORT: u16 = 443;
+const DNS_OVER_TLS_PORT: u16 = 853;
+const SPKI_DIGEST_LEN: usize = 32;
+
+/// Protocol used to reach an [`EncryptedDnsResolver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptedDnsProtocol {
+    /// DNS over HTTPS (RFC 8484).
+    Https,
+    /// DNS over TLS (RFC 7858).
+    Tls,
+}
+
+/// A DNS-over-HTTPS or DNS-over-TLS resolver.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EncryptedDnsResolver {
+    pub protocol: EncryptedDnsProtocol,
+    /// Name of the resolver. Its certificate is verified against this name, unless the
+    /// certificate itself is pinned by `spki_pins`.
+    pub hostname: String,
+    /// Addresses of the resolver. These are required since resolving `hostname` would itself
+    /// require DNS.
+    pub addresses: Vec<IpAddr>,
+    /// Port of the resolver. The default port of the protocol is used if this is `None`.
+    pub port: Option<u16>,
+    /// Base64 encoded SHA-256 digests of accepted SubjectPublicKeyInfo structures. If any are
+    /// set, a certificate chain is only accepted if the public key of one of its certificates
+    /// matches one of them. If that is not the certificate of the resolver itself, the chain must
+    /// also be valid for `hostname`, with the pinned certificate as the trust anchor.
+    #[serde(default)]
+    pub spki_pins: Vec<String>,
+}
+
+/// Errors that can occur when parsing or validating an [`EncryptedDnsResolver`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum EncryptedDnsError {
+    #[error("The URL must start with \"https://\" or \"tls://\"")]
+    UnknownScheme,
+    #[error("The URL does not contain a host name")]
+    MissingHostname,
+    #[error("Invalid port: {0}")]
+    InvalidPort(String),
+    #[error("DNS-over-HTTPS resolvers must use the path \"{DNS_OVER_HTTPS_PATH}\"")]
+    UnsupportedPath,
+    #[error("DNS-over-TLS URLs cannot contain a path")]
+    UnexpectedPath,
+    #[error("No addresses are set for {0}")]
+    MissingAddresses(String),
+    #[error("Invalid SPKI pin \"{0}\": expected a base64 encoded SHA-256 digest")]
+    InvalidSpkiPin(String),
+}
+
+impl EncryptedDnsResolver {
+    /// Parse a resolver from a URL of the form `https://host[:port]/dns-query` or
+    /// `tls://host[:port]`. If the host is an IP address, it is also used as the address of the
+    /// resolver.
+    pub fn from_url(url: &str) -> Result<Self, EncryptedDnsError> {
+        let (protocol, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (EncryptedDnsProtocol::Https, rest)
+        } else if let Some(rest) = url.strip_prefix("tls://") {
+            (EncryptedDnsProtocol::Tls, rest)
+        } else {
+            return Err(EncryptedDnsError::UnknownScheme);
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        match protocol {
+            EncryptedDnsProtocol::Https if !path.is_empty() && path != DNS_OVER_HTTPS_PATH => {
+                return Err(EncryptedDnsError::UnsupportedPath);
+            }
+            EncryptedDnsProtocol::Tls if !path.is_empty() && path != "/" => {
+                return Err(EncryptedDnsError::UnexpectedPath);
+            }
+            _ => (),
+        }
+
+        let (hostname, port) = split_host_port(authority)?;
+        if hostname.is_empty() {
+            return Err(EncryptedDnsError::MissingHostname);
+        }
+        let addresses = hostname.parse::<IpAddr>().into_iter().collect();
+
+        Ok(EncryptedDnsResolver {
+            protocol,
+            hostname: hostname.to_owned(),
+            addresses,
+            port,
+            spki_pins: vec![],
+        })
+    }
+
+    /// Return the port of the resolver.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.protocol {
+            EncryptedDnsProtocol::Https => DNS_OVER_HTTPS_PORT,
+            EncryptedDnsProtocol::Tls => DNS_OVER_TLS_PORT,
+        })
+    }
+
+    /// Return the URL of the resolver, in the format accepted by [`Self::from_url`].
+    pub fn url(&self) -> String {
+        let host = match self.hostname.parse::<IpAddr>() {
+            Ok(IpAddr::V6(addr)) => format!("[{addr}]"),
+            _ => self.hostname.clone(),
+        };
+        let port = self.port.map(|port| format!(":{port}")).unwrap_or_default();
+        match self.protocol {
+            EncryptedDnsProtocol::Https => format!("https://{host}{port}{DNS_OVER_HTTPS_PATH}"),
+            EncryptedDnsProtocol::Tls => format!("tls://{host}{port}"),
+        }
+    }
+
+    /// Return the decoded SPKI pins.
+    pub fn decoded_spki_pins(&self) -> Result<Vec<[u8; SPKI_DIGEST_LEN]>, EncryptedDnsError> {
+        self.spki_pins
+            .iter()
+            .map(|pin| {
+                STANDARD
+                    .decode(pin)
+                    .ok()
+                    .and_then(|digest| <[u8; SPKI_DIGEST_LEN]>::try_from(digest).ok())
+                    .ok_or_else(|| EncryptedDnsError::InvalidSpkiPin(pin.clone()))
+            })
+            .collect()
+    }
+
+    /// Check that the resolver can be used.
+    pub fn validate(&self) -> Result<(), EncryptedDnsError> {
+        if self.hostname.is_empty() {
+            return Err(EncryptedDnsError::MissingHostname);
+        }
+        if self.addresses.is_empty() {
+            return Err(EncryptedDnsError::MissingAddresses(self.url()));
+        }
+        self.decoded_spki_pins().map(|_| ())
+    }
+}
+
+impl fmt::Display for EncryptedDnsResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url())?;
+        let addresses: Vec<_> = self.addresses.iter().map(IpAddr::to_string).collect();
+        write!(f, " ({})", addresses.join(", "))?;
+        if !self.spki_pins.is_empty() {
+            write!(f, ", pinned to {}", self.spki_pins.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn split_host_port(authority: &str) -> Result<(&str, Option<u16>), EncryptedDnsError> {
+    let parse_port = |port: &str| {
+        port.parse()
+            .map_err(|_| EncryptedDnsError::InvalidPort(port.to_owned()))
+    };
+
+    if let Some(rest) = authority.strip_prefix('[') {
+        // IPv6 literal
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or(EncryptedDnsError::MissingHostname)?;
+        return match rest.strip_prefix(':') {
+            Some(port) => Ok((host, Some(parse_port(port)?))),
+            None if rest.is_empty() => Ok((host, None)),
+            None => Err(EncryptedDnsError::InvalidPort(rest.to_owned())),
+        };
+    }
+    match authority.split_once(':') {
+        Some((host, port)) => Ok((host, Some(parse_port(port)?))),
+        None => Ok((authority, None)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_https_url() {
+        let resolver = EncryptedDnsResolver::from_url("https://dns.quad9.net/dns-query").unwrap();
+        assert_eq!(resolver.protocol, EncryptedDnsProtocol::Https);
+        assert_eq!(resolver.hostname, "dns.quad9.net");
+        assert!(resolver.addresses.is_empty());
+        assert_eq!(resolver.port(), 443);
+        assert_eq!(resolver.url(), "https://dns.quad9.net/dns-query");
+
+        assert_eq!(
+            EncryptedDnsResolver::from_url("https://dns.quad9.net/resolve"),
+            Err(EncryptedDnsError::UnsupportedPath)
+        );
+    }
+
+    #[test]
+    fn test_parse_tls_url() {
+        let resolver = EncryptedDnsResolver::from_url("tls://[2606:4700::1111]:8853").unwrap();
+        assert_eq!(resolver.protocol, EncryptedDnsProtocol::Tls);
+        assert_eq!(resolver.hostname, "2606:4700::1111");
+        assert_eq!(
+            resolver.addresses,
+            vec!["2606:4700::1111".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(resolver.port(), 8853);
+        assert_eq!(resolver.url(), "tls://[2606:4700::1111]:8853");
+
+        assert_eq!(
+            EncryptedDnsResolver::from_url("udp://1.1.1.1"),
+            Err(EncryptedDnsError::UnknownScheme)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut resolver = EncryptedDnsResolver::from_url("tls://one.one.one.one").unwrap();
+        assert!(matches!(
+            resolver.validate(),
+            Err(EncryptedDnsError::MissingAddresses(_))
+        ));
+
+        resolver.addresses.push("1.1.1.1".parse().unwrap());
+        resolver
+            .spki_pins
+            .push("GP8Knf7qBae+aIfythytMbYnL+yowaWVeD6MoLHkVRg=".to_owned());
+        assert_eq!(resolver.validate(), Ok(()));
+
+        resolver.spki_pins.push("c2hvcnQ=".to_owned());
+        assert_eq!(
+            resolver.validate(),
+            Err(EncryptedDnsError::InvalidSpkiPin("c2hvcnQ=".to_owned()))
+        );
+    }
+}
@@ -14,6 +14,7 @@ use std::{
 
 use self::proxy::{CustomProxy, Socks5Local};
 
+pub mod dns;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
@@ -52,6 +53,59 @@ pub static ALLOWED_LAN_MULTICAST_NETS: LazyLock<[IpNetwork; 8]> = LazyLock::new(
     ]
 });
 
+/// Restricts and extends the traffic that the firewall allows outside the tunnel.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanAccess {
+    /// Local networks to allow when "allow local network" is enabled. Each network must be
+    /// contained in one of [`ALLOWED_LAN_NETS`]. If empty, all of [`ALLOWED_LAN_NETS`] are
+    /// allowed.
+    pub networks: Vec<IpNetwork>,
+    /// Local ports that accept incoming TCP and UDP connections from the local network when
+    /// "allow local network" is enabled. If empty, incoming connections to any port are accepted.
+    pub inbound_ports: Vec<u16>,
+    /// Networks that traffic is always allowed to and from, regardless of whether "allow local
+    /// network" is enabled. Unlike `networks`, these do not have to be private.
+    ///
+    /// Traffic to these networks bypasses the tunnel and is allowed in every tunnel state,
+    /// including the blocked states and when "lockdown mode" is enabled. Each network must pass
+    /// [`LanAccess::is_allowed_network`].
+    pub allowed_networks: Vec<IpNetwork>,
+}
+
+/// The shortest prefix of an IPv4 network in [`LanAccess::allowed_networks`].
+pub const MIN_ALLOWED_NETWORK_PREFIX_V4: u8 = 8;
+/// The shortest prefix of an IPv6 network in [`LanAccess::allowed_networks`].
+pub const MIN_ALLOWED_NETWORK_PREFIX_V6: u8 = 16;
+
+impl LanAccess {
+    /// Return the local networks to allow when "allow local network" is enabled.
+    pub fn lan_networks(&self) -> &[IpNetwork] {
+        if self.networks.is_empty() {
+            &*ALLOWED_LAN_NETS
+        } else {
+            &self.networks
+        }
+    }
+
+    /// Return whether `network` may be used as an allowed network. Default routes and other
+    /// networks that are too broad are rejected, since they would disable the kill switch.
+    pub fn is_allowed_network(network: &IpNetwork) -> bool {
+        match network {
+            IpNetwork::V4(network) => network.prefix() >= MIN_ALLOWED_NETWORK_PREFIX_V4,
+            IpNetwork::V6(network) => network.prefix() >= MIN_ALLOWED_NETWORK_PREFIX_V6,
+        }
+    }
+
+    /// Return whether `network` may be used as a custom local network, i.e. whether it is
+    /// contained in one of [`ALLOWED_LAN_NETS`].
+    pub fn is_lan_network(network: &IpNetwork) -> bool {
+        ALLOWED_LAN_NETS.iter().any(|lan_net| {
+            lan_net.prefix() <= network.prefix() && lan_net.contains(network.network())
+        })
+    }
+}
+
 /// TunnelParameters are used to encapsulate all the data needed to start a tunnel. This is enum
 /// should be generated by implementations of the trait
 /// `talpid-core::tunnel_state_machine::TunnelParametersGenerator`
@@ -240,6 +294,9 @@ pub enum ObfuscationType {
     #[serde(rename = "udp2tcp")]
     Udp2Tcp,
     Shadowsocks,
+    Quic,
+    /// A protocol identified by name. See [ObfuscatorConfig::Generic].
+    Generic,
 }
 
 impl fmt::Display for ObfuscationType {
@@ -247,6 +304,8 @@ impl fmt::Display for ObfuscationType {
         match self {
             ObfuscationType::Udp2Tcp => "Udp2Tcp".fmt(f),
             ObfuscationType::Shadowsocks => "Shadowsocks".fmt(f),
+            ObfuscationType::Quic => "QUIC".fmt(f),
+            ObfuscationType::Generic => "Generic".fmt(f),
         }
     }
 }
@@ -268,13 +327,21 @@ impl From<&ObfuscatorConfig> for ObfuscationEndpoint {
                 },
                 ObfuscationType::Udp2Tcp,
             ),
-            ObfuscatorConfig::Shadowsocks { endpoint } => (
+            ObfuscatorConfig::Shadowsocks { endpoint, .. } => (
                 Endpoint {
                     address: *endpoint,
                     protocol: TransportProtocol::Udp,
                 },
                 ObfuscationType::Shadowsocks,
             ),
+            ObfuscatorConfig::Quic { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Udp,
+                },
+                ObfuscationType::Quic,
+            ),
+            ObfuscatorConfig::Generic { endpoint, .. } => (*endpoint, ObfuscationType::Generic),
         };
 
         ObfuscationEndpoint {
@@ -607,3 +674,24 @@ impl Connectivity {
         matches!(self, Connectivity::Status { ipv6: true, .. })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_allowed_network_prefix() {
+        for network in ["0.0.0.0/0", "128.0.0.0/1", "10.0.0.0/7", "::/0", "2000::/3"] {
+            assert!(
+                !LanAccess::is_allowed_network(&network.parse().unwrap()),
+                "{network} should be rejected"
+            );
+        }
+        for network in ["10.0.0.0/8", "192.0.2.1/32", "2001:db8::/32", "2001::/16"] {
+            assert!(
+                LanAccess::is_allowed_network(&network.parse().unwrap()),
+                "{network} should be accepted"
+            );
+        }
+    }
+}
@@ -1,12 +1,52 @@
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::{collections::BTreeMap, net::SocketAddr};
 
 use super::{Endpoint, TransportProtocol};
 
+/// Ciphers usable by Shadowsocks obfuscation. Unlike Shadowsocks proxies, only AEAD ciphers are
+/// supported.
+pub const SHADOWSOCKS_OBFUSCATION_CIPHERS: [&str; 3] =
+    ["aes-128-gcm", "aes-256-gcm", "chacha20-ietf-poly1305"];
+
 #[derive(Clone, Eq, PartialEq, Deserialize, Serialize, Debug)]
 pub enum ObfuscatorConfig {
-    Udp2Tcp { endpoint: SocketAddr },
-    Shadowsocks { endpoint: SocketAddr },
+    Udp2Tcp {
+        endpoint: SocketAddr,
+    },
+    Shadowsocks {
+        endpoint: SocketAddr,
+        /// Cipher used by the server. Mullvad relays are used with the default cipher.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cipher: Option<String>,
+        /// Password used by the server. Mullvad relays are used with the default password.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+        /// WireGuard endpoint that the server forwards traffic to. If `None`, the WireGuard
+        /// server is assumed to run on the same host as the Shadowsocks server, as on Mullvad
+        /// relays.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<SocketAddr>,
+    },
+    /// WireGuard traffic proxied over HTTP/3 using CONNECT-UDP
+    Quic {
+        /// UDP endpoint of the QUIC proxy
+        endpoint: SocketAddr,
+        /// Hostname of the proxy, used for SNI and certificate verification
+        hostname: String,
+        /// Token used to authenticate with the proxy
+        auth_token: String,
+    },
+    /// WireGuard traffic obfuscated using a protocol identified by name. The protocol must be
+    /// registered in `tunnel-obfuscation`, which also validates the parameters.
+    Generic {
+        /// Name of the protocol
+        protocol: String,
+        /// Endpoint of the obfuscation server
+        endpoint: Endpoint,
+        /// Protocol-specific parameters
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        parameters: BTreeMap<String, String>,
+    },
 }
 
 impl ObfuscatorConfig {
@@ -16,10 +56,12 @@ impl ObfuscatorConfig {
                 address: *endpoint,
                 protocol: TransportProtocol::Tcp,
             },
-            ObfuscatorConfig::Shadowsocks { endpoint } => Endpoint {
+            ObfuscatorConfig::Shadowsocks { endpoint, .. }
+            | ObfuscatorConfig::Quic { endpoint, .. } => Endpoint {
                 address: *endpoint,
                 protocol: TransportProtocol::Udp,
             },
+            ObfuscatorConfig::Generic { endpoint, .. } => *endpoint,
         }
     }
 }
@@ -1,6 +1,20 @@
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "windows")]
 use std::path::PathBuf;
 
+/// Determines how split tunneling treats the selected applications.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitTunnelMode {
+    /// The selected applications bypass the tunnel. All other traffic uses the tunnel.
+    #[default]
+    Exclude,
+    /// Only the selected applications use the tunnel. All other traffic bypasses it.
+    Include,
+}
+
 /// A process that is being excluded from the tunnel.
+#[cfg(target_os = "windows")]
 #[derive(Debug, Clone)]
 pub struct ExcludedProcess {
     /// Process identifier.
@@ -1,8 +1,8 @@
 use crate::net::TunnelEndpoint;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 #[cfg(target_os = "android")]
 use std::net::IpAddr;
+use std::{fmt, time::Duration};
 
 /// Event emitted from the states in `talpid_core::tunnel_state_machine` when the tunnel state
 /// machine enters a new state.
@@ -27,6 +27,24 @@ pub enum TunnelStateTransition {
     Error(ErrorState),
 }
 
+/// Traffic statistics of an active tunnel. For multihop tunnels, these describe the traffic
+/// exchanged with the exit peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TunnelStats {
+    /// Total number of bytes sent through the tunnel.
+    pub tx_bytes: u64,
+    /// Total number of bytes received through the tunnel.
+    pub rx_bytes: u64,
+    /// Bytes sent per second since the previous sample.
+    pub tx_rate: u64,
+    /// Bytes received per second since the previous sample.
+    pub rx_rate: u64,
+    /// Time elapsed since the last handshake with the peer, if one has occurred.
+    pub last_handshake_age: Option<Duration>,
+    /// Round-trip time of the most recently answered ping sent by the connectivity check.
+    pub latency: Option<Duration>,
+}
+
 /// Action that will be taken after disconnection is complete.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -121,9 +139,13 @@ impl ErrorStateCause {
 #[derive(thiserror::Error, Debug, Serialize, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ParameterGenerationError {
-    /// Failure to select a matching tunnel relay
-    #[error("Failure to select a matching tunnel relay")]
-    NoMatchingRelay,
+    /// Failure to select a matching tunnel relay, and the constraint that eliminated the last
+    /// candidates, if it is known
+    #[error(
+        "Failure to select a matching tunnel relay{}",
+        .0.map(|reason| format!(": {reason}")).unwrap_or_default()
+    )]
+    NoMatchingRelay(Option<NoMatchingRelayReason>),
     /// Failure to select a matching bridge relay
     #[error("Failure to select a matching bridge relay")]
     NoMatchingBridgeRelay,
@@ -135,6 +157,49 @@ pub enum ParameterGenerationError {
     CustomTunnelHostResultionError,
 }
 
+/// The relay constraint which eliminated the last relays matching all other constraints.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoMatchingRelayReason {
+    /// No relays support the selected tunnel protocol
+    TunnelProtocol,
+    /// None of the matching relays are active
+    Inactive,
+    /// All matching relays are excluded
+    Excluded,
+    /// No relays are in the selected location
+    Location,
+    /// No relays are in the selected multihop entry location
+    EntryLocation,
+    /// No relays match the ownership constraint
+    Ownership,
+    /// No relays are hosted by the selected providers
+    Providers,
+    /// No relays support DAITA
+    Daita,
+    /// No relays support the selected obfuscation settings
+    Obfuscation,
+}
+
+impl fmt::Display for NoMatchingRelayReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::TunnelProtocol => "no relays support the tunnel protocol",
+            Self::Inactive => "none of the matching relays are active",
+            Self::Excluded => "all matching relays are excluded",
+            Self::Location => "no relays are in the selected location",
+            Self::EntryLocation => "no relays are in the selected entry location",
+            Self::Ownership => "no relays in the location match the ownership constraint",
+            Self::Providers => "no relays in the location are hosted by the selected providers",
+            Self::Daita => "no relays matching the other constraints support DAITA",
+            Self::Obfuscation => {
+                "no relays matching the other constraints support the obfuscation settings"
+            }
+        };
+        f.write_str(description)
+    }
+}
+
 /// Application that prevents setting the firewall policy.
 #[cfg(windows)]
 #[derive(Debug, Serialize, Clone, Deserialize)]
@@ -1,18 +1,18 @@
-use std::net::Ipv4Addr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::broadcast;
-use tokio::time::Instant;
-
-use super::constants::*;
-use super::error::Error;
-use super::pinger;
-
-use crate::stats::StatsMap;
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::broadcast, time::Instant};
+
+use super::{constants::*, error::Error, pinger};
+
 #[cfg(target_os = "android")]
 use crate::Tunnel;
-use crate::{TunnelError, TunnelType};
+use crate::{stats::StatsMap, TunnelError, TunnelType};
 use pinger::Pinger;
 
 /// Verifies if a connection to a tunnel is working.
@@ -218,6 +218,18 @@ impl Check {
         }
     }
 
+    /// Traffic statistics from the most recent reading.
+    pub(crate) fn stats(&self) -> &StatsMap {
+        match &self.conn_state {
+            ConnState::Connecting { stats, .. } | ConnState::Connected { stats, .. } => stats,
+        }
+    }
+
+    /// Round-trip time of the most recently answered ping.
+    pub(crate) fn latency(&self) -> Option<Duration> {
+        self.ping_state.latency
+    }
+
     pub(crate) fn should_shut_down(&self) -> bool {
         self.cancel_receiver.closed()
     }
@@ -246,6 +258,9 @@ impl Check {
         timeout: Duration,
         tunnel_handle: &TunnelType,
     ) -> Result<bool, Error> {
+        if let Some(round_trip_time) = ping_state.pinger.round_trip_time() {
+            ping_state.latency = Some(round_trip_time);
+        }
         match Self::get_stats(tunnel_handle)
             .await
             .map_err(Error::ConfigReadError)?
@@ -307,6 +322,8 @@ impl Check {
 
 pub(super) struct PingState {
     initial_ping_timestamp: Option<Instant>,
+    /// Round-trip time of the most recently answered ping. This is kept across resets.
+    latency: Option<Duration>,
     num_pings_sent: u32,
     pinger: Box<dyn Pinger>,
 }
@@ -329,6 +346,7 @@ impl PingState {
     pub(super) fn new_with(pinger: Box<dyn Pinger>) -> Self {
         Self {
             initial_ping_timestamp: None,
+            latency: None,
             num_pings_sent: 0,
             pinger,
         }
@@ -477,6 +495,7 @@ impl ConnState {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Mutex;
     use tokio::sync::mpsc;
 
     use super::*;
@@ -517,6 +536,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(Instant::now(), stats);
@@ -542,6 +562,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(connect_time, stats);
@@ -566,6 +587,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 0,
+                last_handshake: None,
             },
         );
         conn_state.update(start, stats);
@@ -577,6 +599,7 @@ mod test {
             Stats {
                 rx_bytes: 1,
                 tx_bytes: 1,
+                last_handshake: None,
             },
         );
         conn_state.update(update_time, stats);
@@ -636,6 +659,29 @@ mod test {
         assert!(checker.check_connectivity(now, &tunnel).await.unwrap())
     }
 
+    #[tokio::test]
+    /// Verify that the latency is the round-trip time of the most recent echo reply, and that it
+    /// is kept until another reply is received.
+    async fn test_latency() {
+        let tunnel = MockTunnel::always_incrementing().boxed();
+        let round_trip_time = Arc::new(Mutex::new(Some(Duration::from_millis(20))));
+        let pinger = MockPinger::default().with_round_trip_time(round_trip_time.clone());
+        let now = Instant::now();
+        let (mut checker, _cancel_token) = mock_checker(now, Box::new(pinger));
+        assert_eq!(checker.latency(), None);
+
+        checker.check_connectivity(now, &tunnel).await.unwrap();
+        assert_eq!(checker.latency(), Some(Duration::from_millis(20)));
+
+        *round_trip_time.lock().unwrap() = None;
+        checker.check_connectivity(now, &tunnel).await.unwrap();
+        assert_eq!(checker.latency(), Some(Duration::from_millis(20)));
+
+        *round_trip_time.lock().unwrap() = Some(Duration::from_millis(35));
+        checker.check_connectivity(now, &tunnel).await.unwrap();
+        assert_eq!(checker.latency(), Some(Duration::from_millis(35)));
+    }
+
     #[tokio::test(start_paused = true)]
     /// Verify that the timeout for setting up a tunnel works as expected.
     async fn test_establish_timeout() {
@@ -658,6 +704,7 @@ mod test {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 MockTunnel::new(move || Ok(tunnel_stats.clone())).boxed()
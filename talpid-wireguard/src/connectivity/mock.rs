@@ -1,10 +1,15 @@
-use std::future::Future;
-use std::pin::Pin;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::time::Instant;
 
-use super::check::{CancelToken, ConnState, PingState};
-use super::pinger;
-use super::Check;
+use super::{
+    check::{CancelToken, ConnState, PingState},
+    pinger, Check,
+};
 
 use crate::{Config, Tunnel, TunnelError};
 use pinger::Pinger;
@@ -15,6 +20,15 @@ pub use crate::stats::{Stats, StatsMap};
 #[derive(Default)]
 pub(crate) struct MockPinger {
     on_send_ping: Option<Box<dyn FnMut() + Send + Sync>>,
+    round_trip_time: Option<Arc<Mutex<Option<Duration>>>>,
+}
+
+impl MockPinger {
+    /// Report the round-trip time in `round_trip_time` whenever it is asked for.
+    pub fn with_round_trip_time(mut self, round_trip_time: Arc<Mutex<Option<Duration>>>) -> Self {
+        self.round_trip_time = Some(round_trip_time);
+        self
+    }
 }
 
 pub(crate) struct MockTunnel {
@@ -35,6 +49,7 @@ pub fn connected_state(timestamp: Instant) -> ConnState {
         Stats {
             tx_bytes: 0,
             rx_bytes: 0,
+            last_handshake: None,
         },
     );
     ConnState::Connected {
@@ -65,6 +80,7 @@ impl MockTunnel {
             Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         let peers = std::sync::Mutex::new(map);
@@ -89,6 +105,7 @@ impl MockTunnel {
                     Stats {
                         tx_bytes: 0,
                         rx_bytes: 0,
+                        last_handshake: None,
                     },
                 );
                 Ok(map)
@@ -135,4 +152,8 @@ impl Pinger for MockPinger {
         }
         Ok(())
     }
+
+    fn round_trip_time(&mut self) -> Option<Duration> {
+        *self.round_trip_time.as_ref()?.lock().unwrap()
+    }
 }
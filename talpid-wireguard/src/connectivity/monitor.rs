@@ -3,7 +3,7 @@ use std::{sync::Weak, time::Duration};
 use tokio::sync::Mutex;
 use tokio::time::{Instant, MissedTickBehavior};
 
-use crate::TunnelType;
+use crate::{stats::StatsPublisher, TunnelType};
 
 use super::check::Check;
 use super::error::Error;
@@ -16,11 +16,21 @@ const SUSPEND_TIMEOUT: Duration = Duration::from_secs(6);
 
 pub struct Monitor {
     connectivity_check: Check,
+    stats_publisher: Option<StatsPublisher>,
 }
 
 impl Monitor {
     pub fn init(connectivity_check: Check) -> Self {
-        Self { connectivity_check }
+        Self {
+            connectivity_check,
+            stats_publisher: None,
+        }
+    }
+
+    /// Publish traffic statistics after every connectivity check.
+    pub fn with_stats_publisher(mut self, stats_publisher: StatsPublisher) -> Self {
+        self.stats_publisher = Some(stats_publisher);
+        self
     }
 
     pub async fn run(
@@ -65,9 +75,19 @@ impl Monitor {
             return Ok(false);
         };
 
-        self.connectivity_check
-            .check_connectivity(Instant::now(), tunnel)
-            .await
+        let now = Instant::now();
+        let connected = self
+            .connectivity_check
+            .check_connectivity(now, tunnel)
+            .await?;
+        if let Some(publisher) = &mut self.stats_publisher {
+            publisher.publish(
+                now,
+                self.connectivity_check.stats(),
+                self.connectivity_check.latency(),
+            );
+        }
+        Ok(connected)
     }
 }
 
@@ -128,6 +148,7 @@ mod test {
             Stats {
                 tx_bytes: 0,
                 rx_bytes: 0,
+                last_handshake: None,
             },
         );
         let tunnel_stats = std::sync::Mutex::new(map);
@@ -1,15 +1,20 @@
 use byteorder::{NetworkEndian, WriteBytesExt};
 use rand::Rng;
 use socket2::{Domain, Protocol, Socket, Type};
-use tokio::net::UdpSocket;
+use tokio::{net::UdpSocket, sync::mpsc, task::JoinHandle, time::Instant};
 
 use std::{
+    collections::VecDeque,
     io::{self, Write},
     net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
     time::Duration,
 };
 
 const SEND_RETRY_ATTEMPTS: u32 = 10;
+/// Maximum number of unanswered echo requests to keep track of.
+const MAX_PENDING_REQUESTS: usize = 16;
+const ICMP_ECHO_REPLY: u8 = 0x00;
 
 /// Pinger errors
 #[derive(thiserror::Error, Debug)]
@@ -52,10 +57,15 @@ pub enum Error {
 type Result<T> = std::result::Result<T, Error>;
 
 pub struct Pinger {
-    sock: UdpSocket,
+    sock: Arc<UdpSocket>,
     addr: SocketAddr,
     id: u16,
     seq: u16,
+    /// Sequence numbers and send times of echo requests that have not been answered yet.
+    pending_requests: VecDeque<(u16, Instant)>,
+    /// Sequence numbers and arrival times of echo replies.
+    replies_rx: mpsc::UnboundedReceiver<(u16, Instant)>,
+    reply_reader: JoinHandle<()>,
 }
 
 impl Pinger {
@@ -84,14 +94,27 @@ impl Pinger {
         #[cfg(target_os = "macos")]
         Self::set_device_index(&sock, &interface_name)?;
 
-        let sock =
-            UdpSocket::from_std(std::net::UdpSocket::from(sock)).map_err(Error::ConvertSocket)?;
+        // Raw sockets must be bound before they can receive anything on Windows
+        #[cfg(windows)]
+        sock.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0).into())
+            .map_err(Error::SocketOp)?;
+
+        let sock = Arc::new(
+            UdpSocket::from_std(std::net::UdpSocket::from(sock)).map_err(Error::ConvertSocket)?,
+        );
+
+        let id = rand::random();
+        let (replies_tx, replies_rx) = mpsc::unbounded_channel();
+        let reply_reader = tokio::spawn(read_replies(sock.clone(), addr, id, replies_tx));
 
         Ok(Self {
             sock,
             addr,
-            id: rand::random(),
+            id,
             seq: 0,
+            pending_requests: VecDeque::new(),
+            replies_rx,
+            reply_reader,
         })
     }
 
@@ -130,6 +153,67 @@ impl Pinger {
     }
 }
 
+impl Drop for Pinger {
+    fn drop(&mut self) {
+        self.reply_reader.abort();
+    }
+}
+
+/// Receive echo replies from `addr` to requests with the given ID, and send their sequence numbers
+/// and arrival times to `replies_tx`.
+async fn read_replies(
+    sock: Arc<UdpSocket>,
+    addr: SocketAddr,
+    id: u16,
+    replies_tx: mpsc::UnboundedSender<(u16, Instant)>,
+) {
+    let mut buffer = [0u8; 1024];
+    loop {
+        let (len, source) = match sock.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(error) => {
+                log::debug!("Stopped reading ICMP echo replies: {error}");
+                return;
+            }
+        };
+        let received = Instant::now();
+        if source.ip() != addr.ip() {
+            continue;
+        }
+        // On Android, the kernel replaces the ID of requests sent using datagram sockets
+        let id = if cfg!(target_os = "android") {
+            None
+        } else {
+            Some(id)
+        };
+        if let Some(seq) = parse_echo_reply(&buffer[..len], id) {
+            if replies_tx.send((seq, received)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Return the sequence number of `packet` if it is an ICMP echo reply with the ID `id`. Packets
+/// received on raw sockets start with an IPv4 header, which is skipped.
+fn parse_echo_reply(packet: &[u8], id: Option<u16>) -> Option<u16> {
+    let icmp = match packet.first() {
+        Some(first_byte) if first_byte >> 4 == 4 => {
+            let header_len = usize::from(first_byte & 0x0f) * 4;
+            packet.get(header_len..)?
+        }
+        _ => packet,
+    };
+    if icmp.len() < 8 || icmp[0] != ICMP_ECHO_REPLY {
+        return None;
+    }
+    let reply_id = u16::from_be_bytes([icmp[4], icmp[5]]);
+    if id.is_some_and(|id| id != reply_id) {
+        return None;
+    }
+    Some(u16::from_be_bytes([icmp[6], icmp[7]]))
+}
+
 #[cfg(windows)]
 fn should_retry_send(err: &io::Error) -> bool {
     // Winsock error for when there is no route
@@ -147,8 +231,32 @@ fn should_retry_send(_err: &io::Error) -> bool {
 impl super::Pinger for Pinger {
     async fn send_icmp(&mut self) -> Result<()> {
         let mut message = [0u8; 50];
+        let seq = self.seq;
         self.construct_icmpv4_packet(&mut message)?;
-        self.send_ping_request(&message, self.addr).await
+        self.send_ping_request(&message, self.addr).await?;
+
+        if self.pending_requests.len() >= MAX_PENDING_REQUESTS {
+            self.pending_requests.pop_front();
+        }
+        self.pending_requests.push_back((seq, Instant::now()));
+        Ok(())
+    }
+
+    fn round_trip_time(&mut self) -> Option<Duration> {
+        let mut round_trip_time = None;
+        while let Ok((seq, received)) = self.replies_rx.try_recv() {
+            let Some(index) = self
+                .pending_requests
+                .iter()
+                .position(|(request_seq, _)| *request_seq == seq)
+            else {
+                continue;
+            };
+            if let Some((_, sent)) = self.pending_requests.remove(index) {
+                round_trip_time = Some(received.saturating_duration_since(sent));
+            }
+        }
+        round_trip_time
     }
 }
 
@@ -258,6 +366,30 @@ mod test {
         assert_eq!(buffer, expected_packet);
     }
 
+    #[test]
+    fn test_parse_echo_reply() {
+        // Echo reply with ID 0x1dcd and sequence number 0x0001
+        let reply = [
+            0x00, 0x00, 0x00, 0x00, 0x1d, 0xcd, 0x00, 0x01, 0xb6, 0xe0, 0x87, 0x60,
+        ];
+        assert_eq!(parse_echo_reply(&reply, Some(0x1dcd)), Some(0x0001));
+        assert_eq!(parse_echo_reply(&reply, None), Some(0x0001));
+        assert_eq!(parse_echo_reply(&reply, Some(0x1dce)), None);
+
+        // The same reply received on a raw socket, preceded by a 20 byte IPv4 header
+        let mut packet = vec![0x45];
+        packet.extend_from_slice(&[0u8; 19]);
+        packet.extend_from_slice(&reply);
+        assert_eq!(parse_echo_reply(&packet, Some(0x1dcd)), Some(0x0001));
+
+        // Echo requests and truncated packets are ignored
+        let mut request = reply;
+        request[0] = 0x08;
+        assert_eq!(parse_echo_reply(&request, Some(0x1dcd)), None);
+        assert_eq!(parse_echo_reply(&reply[..7], Some(0x1dcd)), None);
+        assert_eq!(parse_echo_reply(&packet[..24], Some(0x1dcd)), None);
+    }
+
     #[test]
     fn test_icmpv4_packet_too_short() {
         assert!(!construct_icmpv4_packet_inner(
@@ -7,6 +7,10 @@ pub use icmp::Error;
 pub trait Pinger: Send {
     /// Sends an ICMP packet
     async fn send_icmp(&mut self) -> Result<(), Error>;
+    /// Returns the round-trip time of the most recent echo reply received since the last call.
+    fn round_trip_time(&mut self) -> Option<std::time::Duration> {
+        None
+    }
     /// Clears all resources used by the pinger.
     async fn reset(&mut self) {}
 }
@@ -237,6 +237,10 @@ impl WireguardMonitor {
         let moved_close_obfs_sender = close_obfs_sender.clone();
         let moved_obfuscator = monitor.obfuscator.clone();
         let detect_mtu = params.options.mtu.is_none();
+        let stats_publisher = stats::StatsPublisher::new(
+            *config.exit_peer().public_key.as_bytes(),
+            args.stats_tx.clone(),
+        );
         let tunnel_fut = async move {
             let tunnel = moved_tunnel;
             let close_obfs_sender: sync_mpsc::Sender<CloseMsg> = moved_close_obfs_sender;
@@ -359,6 +363,7 @@ impl WireguardMonitor {
             event_hook.on_event(TunnelEvent::Up(metadata)).await;
 
             if let Err(error) = connectivity::Monitor::init(connectivity_monitor)
+                .with_stats_publisher(stats_publisher)
                 .run(Arc::downgrade(&tunnel))
                 .await
             {
@@ -458,6 +463,10 @@ impl WireguardMonitor {
 
         let moved_close_obfs_sender = close_obfs_sender.clone();
         let moved_obfuscator = monitor.obfuscator.clone();
+        let stats_publisher = stats::StatsPublisher::new(
+            *config.exit_peer().public_key.as_bytes(),
+            args.stats_tx.clone(),
+        );
         let tunnel_fut = async move {
             let close_obfs_sender: sync_mpsc::Sender<CloseMsg> = moved_close_obfs_sender;
             let obfuscator = moved_obfuscator;
@@ -502,6 +511,7 @@ impl WireguardMonitor {
             event_hook.on_event(TunnelEvent::Up(metadata)).await;
 
             if let Err(error) = connectivity::Monitor::init(connectivity_check)
+                .with_stats_publisher(stats_publisher)
                 .run(Arc::downgrade(&tunnel))
                 .await
             {
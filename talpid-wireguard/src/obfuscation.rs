@@ -4,16 +4,16 @@ use super::{Error, Result};
 use crate::{config::Config, CloseMsg};
 #[cfg(target_os = "android")]
 use std::sync::{Arc, Mutex};
-use std::{
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
-    sync::mpsc as sync_mpsc,
-};
+use std::{collections::BTreeMap, net::SocketAddr, sync::mpsc as sync_mpsc};
 #[cfg(target_os = "android")]
 use talpid_tunnel::tun_provider::TunProvider;
-use talpid_types::{net::obfuscation::ObfuscatorConfig, ErrorExt};
+use talpid_types::{
+    net::{obfuscation::ObfuscatorConfig, TransportProtocol},
+    ErrorExt,
+};
 
 use tunnel_obfuscation::{
-    create_obfuscator, shadowsocks, udp2tcp, Settings as ObfuscationSettings,
+    create_obfuscator, registry::Transport, Config as ObfuscationConfig, Registry,
 };
 
 /// Begin running obfuscation machine, if configured. This function will patch `config`'s endpoint
@@ -27,13 +27,29 @@ pub async fn apply_obfuscation_config(
         return Ok(None);
     };
 
+    // The transport of a generic protocol is configured separately, so it may be wrong
+    if let ObfuscatorConfig::Generic {
+        protocol, endpoint, ..
+    } = obfuscator_config
+    {
+        Registry::builtin()
+            .check_transport(protocol, transport(endpoint.protocol))
+            .map_err(Error::ObfuscationError)?;
+    }
+
     let settings = settings_from_config(
         obfuscator_config,
+        config.mtu,
         #[cfg(target_os = "linux")]
         config.fwmark,
     );
 
-    log::trace!("Obfuscation settings: {settings:?}");
+    // Parameters are not logged, since they may contain secrets such as a Shadowsocks password
+    log::trace!(
+        "Obfuscation settings: {} via {}",
+        settings.protocol,
+        settings.endpoint
+    );
 
     let obfuscator = create_obfuscator(&settings)
         .await
@@ -76,29 +92,83 @@ fn patch_endpoint(config: &mut Config, endpoint: SocketAddr) {
 
 fn settings_from_config(
     config: &ObfuscatorConfig,
+    tunnel_mtu: u16,
     #[cfg(target_os = "linux")] fwmark: Option<u32>,
-) -> ObfuscationSettings {
+) -> ObfuscationConfig {
+    let endpoint = config.get_obfuscator_endpoint().address;
+    let (protocol, parameters) = protocol_parameters(config);
+    ObfuscationConfig {
+        protocol: protocol.to_owned(),
+        endpoint,
+        bind: None,
+        parameters,
+        mtu: Some(path_mtu(tunnel_mtu, endpoint)),
+        #[cfg(target_os = "linux")]
+        fwmark,
+    }
+}
+
+/// Return the name of the registered obfuscation protocol that implements `config`, and the
+/// parameters to pass to it.
+fn protocol_parameters(config: &ObfuscatorConfig) -> (&str, BTreeMap<String, String>) {
+    use tunnel_obfuscation::{quic, shadowsocks, udp2tcp};
+
     match config {
-        ObfuscatorConfig::Udp2Tcp { endpoint } => ObfuscationSettings::Udp2Tcp(udp2tcp::Settings {
-            peer: *endpoint,
-            #[cfg(target_os = "linux")]
-            fwmark,
-        }),
-        ObfuscatorConfig::Shadowsocks { endpoint } => {
-            ObfuscationSettings::Shadowsocks(shadowsocks::Settings {
-                shadowsocks_endpoint: *endpoint,
-                wireguard_endpoint: if endpoint.is_ipv4() {
-                    SocketAddr::from((Ipv4Addr::LOCALHOST, 51820))
-                } else {
-                    SocketAddr::from((Ipv6Addr::LOCALHOST, 51820))
-                },
-                #[cfg(target_os = "linux")]
-                fwmark,
-            })
+        ObfuscatorConfig::Udp2Tcp { .. } => (udp2tcp::NAME, BTreeMap::new()),
+        ObfuscatorConfig::Shadowsocks {
+            cipher,
+            password,
+            target,
+            ..
+        } => {
+            let parameters = [
+                (shadowsocks::CIPHER, cipher.clone()),
+                (shadowsocks::PASSWORD, password.clone()),
+                (shadowsocks::TARGET, target.map(|target| target.to_string())),
+            ]
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_owned(), value?)))
+            .collect();
+            (shadowsocks::NAME, parameters)
         }
+        ObfuscatorConfig::Quic {
+            hostname,
+            auth_token,
+            ..
+        } => (
+            quic::NAME,
+            BTreeMap::from([
+                (quic::HOSTNAME.to_owned(), hostname.clone()),
+                (quic::AUTH_TOKEN.to_owned(), auth_token.clone()),
+            ]),
+        ),
+        ObfuscatorConfig::Generic {
+            protocol,
+            parameters,
+            ..
+        } => (protocol, parameters.clone()),
     }
 }
 
+fn transport(protocol: TransportProtocol) -> Transport {
+    match protocol {
+        TransportProtocol::Udp => Transport::Udp,
+        TransportProtocol::Tcp => Transport::Tcp,
+    }
+}
+
+/// Return the MTU of the path to the obfuscation server. The tunnel MTU was derived from it by
+/// subtracting the WireGuard overhead, so this adds it back.
+fn path_mtu(tunnel_mtu: u16, endpoint: SocketAddr) -> u16 {
+    use talpid_tunnel::{IPV4_HEADER_SIZE, IPV6_HEADER_SIZE, WIREGUARD_HEADER_SIZE};
+
+    let ip_header_size = match endpoint.is_ipv4() {
+        true => IPV4_HEADER_SIZE,
+        false => IPV6_HEADER_SIZE,
+    };
+    tunnel_mtu.saturating_add(ip_header_size + WIREGUARD_HEADER_SIZE)
+}
+
 /// Route socket outside of the VPN on Android
 #[cfg(target_os = "android")]
 async fn bypass_vpn(
@@ -136,3 +206,56 @@ impl Drop for ObfuscatorHandle {
         self.obfuscation_task.abort();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::Endpoint;
+
+    /// Every obfuscator configuration must map onto a registered protocol which accepts its
+    /// parameters and uses the same transport as the endpoint that the firewall allows.
+    #[test]
+    fn test_registered_protocols() {
+        let endpoint = "192.0.2.1:443".parse().unwrap();
+        let configs = [
+            ObfuscatorConfig::Udp2Tcp { endpoint },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint,
+                cipher: None,
+                password: None,
+                target: None,
+            },
+            ObfuscatorConfig::Shadowsocks {
+                endpoint,
+                cipher: Some("aes-128-gcm".to_owned()),
+                password: Some("password".to_owned()),
+                target: Some("192.0.2.2:51820".parse().unwrap()),
+            },
+            ObfuscatorConfig::Quic {
+                endpoint,
+                hostname: "example.com".to_owned(),
+                auth_token: "token".to_owned(),
+            },
+            ObfuscatorConfig::Generic {
+                protocol: "udp2tcp".to_owned(),
+                endpoint: Endpoint::from_socket_address(endpoint, TransportProtocol::Tcp),
+                parameters: BTreeMap::new(),
+            },
+        ];
+
+        for config in configs {
+            let settings = settings_from_config(
+                &config,
+                1380,
+                #[cfg(target_os = "linux")]
+                None,
+            );
+            let protocol = Registry::builtin().validate(&settings).unwrap();
+            assert_eq!(
+                protocol.transport(),
+                transport(config.get_obfuscator_endpoint().protocol),
+                "{config:?}"
+            );
+        }
+    }
+}
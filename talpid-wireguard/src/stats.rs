@@ -1,9 +1,87 @@
+use std::time::{Duration, SystemTime};
+use talpid_types::tunnel::TunnelStats;
+use tokio::{sync::watch, time::Instant};
+
 /// Contains bytes sent and received through a tunnel
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Stats {
     pub tx_bytes: u64,
     pub rx_bytes: u64,
+    /// Time of the most recent handshake with the peer, if any.
+    pub last_handshake: Option<SystemTime>,
 }
 
 /// A map from peer pubkeys to peer stats.
 pub type StatsMap = std::collections::HashMap<[u8; 32], Stats>;
+
+/// Convert a handshake time, given in time since the Unix epoch, to a [`SystemTime`]. A zero time
+/// means that no handshake has occurred.
+pub fn handshake_time(secs: u64, nsecs: u32) -> Option<SystemTime> {
+    if secs == 0 && nsecs == 0 {
+        return None;
+    }
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nsecs))
+}
+
+/// Publishes [`TunnelStats`] for the exit peer of a tunnel, computed from consecutive readings of
+/// the tunnel's [`StatsMap`]. The published value is reset to `None` when this is dropped.
+pub struct StatsPublisher {
+    exit_peer: [u8; 32],
+    tx: watch::Sender<Option<TunnelStats>>,
+    previous: Option<(Instant, Stats)>,
+}
+
+impl StatsPublisher {
+    pub fn new(exit_peer: [u8; 32], tx: watch::Sender<Option<TunnelStats>>) -> Self {
+        Self {
+            exit_peer,
+            tx,
+            previous: None,
+        }
+    }
+
+    /// Publish the stats of the exit peer, if it is present in `stats`.
+    pub fn publish(&mut self, now: Instant, stats: &StatsMap, latency: Option<Duration>) {
+        let Some(current) = stats.get(&self.exit_peer).copied() else {
+            return;
+        };
+
+        let (tx_rate, rx_rate) = match self.previous {
+            Some((then, previous)) => {
+                let elapsed = now.saturating_duration_since(then).as_secs_f64();
+                let rate = |current: u64, previous: u64| {
+                    if elapsed > 0.0 {
+                        (current.saturating_sub(previous) as f64 / elapsed) as u64
+                    } else {
+                        0
+                    }
+                };
+                (
+                    rate(current.tx_bytes, previous.tx_bytes),
+                    rate(current.rx_bytes, previous.rx_bytes),
+                )
+            }
+            None => (0, 0),
+        };
+        self.previous = Some((now, current));
+
+        let last_handshake_age = current
+            .last_handshake
+            .and_then(|handshake| SystemTime::now().duration_since(handshake).ok());
+
+        self.tx.send_replace(Some(TunnelStats {
+            tx_bytes: current.tx_bytes,
+            rx_bytes: current.rx_bytes,
+            tx_rate,
+            rx_rate,
+            last_handshake_age,
+            latency,
+        }));
+    }
+}
+
+impl Drop for StatsPublisher {
+    fn drop(&mut self) {
+        self.tx.send_replace(None);
+    }
+}
@@ -658,6 +658,7 @@ impl Tunnel for WgGoTunnel {
 
 mod stats {
     use super::{Stats, StatsMap};
+    use crate::stats::handshake_time;
 
     #[derive(thiserror::Error, Debug, PartialEq)]
     pub enum Error {
@@ -675,6 +676,8 @@ mod stats {
             let mut peer = None;
             let mut tx_bytes = None;
             let mut rx_bytes = None;
+            let mut handshake_sec = 0;
+            let mut handshake_nsec = 0;
 
             // parts iterates over keys and values
             let parts = config.split('\n').filter_map(|line| {
@@ -693,6 +696,20 @@ mod stats {
                         peer = Some(buffer);
                         tx_bytes = None;
                         rx_bytes = None;
+                        handshake_sec = 0;
+                        handshake_nsec = 0;
+                    }
+                    "last_handshake_time_sec" => {
+                        handshake_sec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
+                    }
+                    "last_handshake_time_nsec" => {
+                        handshake_nsec = value
+                            .trim()
+                            .parse()
+                            .map_err(|err| Error::IntParse(value.to_string(), err))?;
                     }
                     "rx_bytes" => {
                         rx_bytes = Some(
@@ -722,6 +739,7 @@ mod stats {
                         Self {
                             tx_bytes: tx_bytes_val,
                             rx_bytes: rx_bytes_val,
+                            last_handshake: handshake_time(handshake_sec, handshake_nsec),
                         },
                     );
                     peer = None;
@@ -736,6 +754,7 @@ mod stats {
     #[cfg(test)]
     mod test {
         use super::super::stats::{Error, Stats};
+        use crate::stats::handshake_time;
 
         #[test]
         fn test_parsing() {
@@ -748,6 +767,10 @@ mod stats {
             assert_eq!(actual_keys, [pubkey]);
             assert_eq!(stats[&pubkey].rx_bytes, 2396);
             assert_eq!(stats[&pubkey].tx_bytes, 2740);
+            assert_eq!(
+                stats[&pubkey].last_handshake,
+                handshake_time(1578420649, 369416131)
+            );
         }
 
         #[test]
@@ -3,7 +3,7 @@
 use super::{
     config::Config,
     logging,
-    stats::{Stats, StatsMap},
+    stats::{handshake_time, Stats, StatsMap},
     Tunnel,
 };
 use bitflags::bitflags;
@@ -914,6 +914,17 @@ impl Drop for WgNtDll {
     }
 }
 
+/// Convert a handshake time given in 100-nanosecond intervals since 1601-01-01 to a `SystemTime`.
+fn filetime_to_handshake_time(filetime: u64) -> Option<std::time::SystemTime> {
+    /// Number of 100-nanosecond intervals between 1601-01-01 and the Unix epoch.
+    const UNIX_EPOCH_FILETIME: u64 = 116_444_736_000_000_000;
+    let since_epoch = filetime.checked_sub(UNIX_EPOCH_FILETIME)?;
+    handshake_time(
+        since_epoch / 10_000_000,
+        ((since_epoch % 10_000_000) * 100) as u32,
+    )
+}
+
 fn load_wg_nt_dll(resource_dir: &Path) -> Result<&'static WgNtDll> {
     WG_NT_DLL.get_or_try_init(|| WgNtDll::new(resource_dir).map_err(Error::LoadDll))
 }
@@ -1072,6 +1083,7 @@ impl Tunnel for WgNtTunnel {
                     Stats {
                         tx_bytes: peer.tx_bytes,
                         rx_bytes: peer.rx_bytes,
+                        last_handshake: filetime_to_handshake_time(peer.last_handshake),
                     },
                 );
             }
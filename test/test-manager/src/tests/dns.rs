@@ -84,8 +84,10 @@ pub async fn test_dns_leak_custom_public_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                ..Default::default()
             },
             state: settings::DnsState::Custom,
+            blocklists: settings::DnsBlocklists::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -116,8 +118,10 @@ pub async fn test_dns_leak_custom_private_ip(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![CONFIG_IP],
+                ..Default::default()
             },
             state: settings::DnsState::Custom,
+            blocklists: settings::DnsBlocklists::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -367,8 +371,10 @@ pub async fn test_dns_config_custom_private(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![IpAddr::V4(TEST_CONFIG.host_bridge_ip)],
+                ..Default::default()
             },
             state: settings::DnsState::Custom,
+            blocklists: settings::DnsBlocklists::default(),
         })
         .await
         .context("failed to configure DNS server")?;
@@ -401,8 +407,10 @@ pub async fn test_dns_config_custom_public(
             default_options: settings::DefaultDnsOptions::default(),
             custom_options: settings::CustomDnsOptions {
                 addresses: vec![custom_ip],
+                ..Default::default()
             },
             state: settings::DnsState::Custom,
+            blocklists: settings::DnsBlocklists::default(),
         })
         .await
         .context("failed to configure DNS server")?;
@@ -507,6 +515,7 @@ pub async fn test_content_blockers(
                 default_options: test_opts,
                 custom_options: settings::CustomDnsOptions::default(),
                 state: settings::DnsState::Default,
+                blocklists: settings::DnsBlocklists::default(),
             })
             .await
             .context("failed to configure DNS server")?;
@@ -677,6 +686,10 @@ async fn connect_local_wg_relay(mullvad_client: &mut MullvadProxyClient) -> Resu
             fwmark: None,
             ipv6_gateway: None,
         }),
+        psk: None,
+        exit_psk: None,
+        quantum_resistant: false,
+        obfuscation: None,
     };
     set_custom_endpoint(mullvad_client, custom_tunnel_endpoint)
         .await
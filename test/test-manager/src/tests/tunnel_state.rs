@@ -191,6 +191,10 @@ pub async fn test_connecting_state(
         CustomTunnelEndpoint {
             host: "1.3.3.7".to_owned(),
             config: mullvad_types::ConnectionConfig::Wireguard(unreachable_wireguard_tunnel()),
+            psk: None,
+            exit_psk: None,
+            quantum_resistant: false,
+            obfuscation: None,
         },
     )
     .await
@@ -1,9 +1,13 @@
 use async_trait::async_trait;
 use std::net::SocketAddr;
 
+pub mod quic;
+pub mod registry;
 pub mod shadowsocks;
 pub mod udp2tcp;
 
+pub use registry::{Config, Registry, ServerConfig};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -14,11 +18,50 @@ pub enum Error {
     #[error("Failed to run Udp2Tcp obfuscator")]
     RunUdp2TcpObfuscator(#[source] udp2tcp::Error),
 
+    #[error("Failed to run Udp2Tcp server")]
+    RunUdp2TcpServer(#[source] udp2tcp::Error),
+
     #[error("Failed to initialize Shadowsocks")]
     CreateShadowsocksObfuscator(#[source] shadowsocks::Error),
 
     #[error("Failed to run Shadowsocks")]
     RunShadowsocksObfuscator(#[source] shadowsocks::Error),
+
+    #[error("Failed to run Shadowsocks server")]
+    RunShadowsocksServer(#[source] shadowsocks::Error),
+
+    #[error("Failed to initialize QUIC obfuscator")]
+    CreateQuicObfuscator(#[source] quic::Error),
+
+    #[error("Failed to run QUIC obfuscator")]
+    RunQuicObfuscator(#[source] quic::Error),
+
+    #[error("Unknown obfuscation protocol: {0}")]
+    UnknownProtocol(String),
+
+    #[error("Obfuscation protocol has no server: {0}")]
+    ServerNotSupported(&'static str),
+
+    #[error("Unknown parameter for {protocol}: {name}")]
+    UnknownParameter { protocol: String, name: String },
+
+    #[error("Missing parameter for {protocol}: {name}")]
+    MissingParameter {
+        protocol: String,
+        name: &'static str,
+    },
+
+    #[error("Invalid value of parameter for {protocol}: {name}")]
+    InvalidParameter {
+        protocol: String,
+        name: &'static str,
+    },
+
+    #[error("Obfuscation protocol {protocol} does not use {transport}")]
+    UnexpectedTransport {
+        protocol: String,
+        transport: registry::Transport,
+    },
 }
 
 #[async_trait]
@@ -38,23 +81,9 @@ pub trait Obfuscator: Send {
     fn packet_overhead(&self) -> u16;
 }
 
-#[derive(Debug)]
-pub enum Settings {
-    Udp2Tcp(udp2tcp::Settings),
-    Shadowsocks(shadowsocks::Settings),
-}
-
-pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator>> {
-    match settings {
-        Settings::Udp2Tcp(s) => udp2tcp::Udp2Tcp::new(s)
-            .await
-            .map(box_obfuscator)
-            .map_err(Error::CreateUdp2TcpObfuscator),
-        Settings::Shadowsocks(s) => shadowsocks::Shadowsocks::new(s)
-            .await
-            .map(box_obfuscator)
-            .map_err(Error::CreateShadowsocksObfuscator),
-    }
+/// Create an obfuscator using one of the built-in protocols.
+pub async fn create_obfuscator(config: &Config) -> Result<Box<dyn Obfuscator>> {
+    Registry::builtin().create_obfuscator(config).await
 }
 
 fn box_obfuscator(obfs: impl Obfuscator + 'static) -> Box<dyn Obfuscator> {
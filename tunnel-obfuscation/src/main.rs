@@ -1,36 +1,157 @@
-use std::{env::args, net::SocketAddr};
-use tunnel_obfuscation::{create_obfuscator, udp2tcp, Obfuscator, Settings};
+//! Run an obfuscation client or server outside of the daemon. See [Opt].
 
-#[tokio::main]
-async fn main() {
-    if args().len() != 2 {
-        println!("Missing arguments");
-    }
+use clap::Parser;
+use std::{collections::BTreeMap, error::Error as _, net::SocketAddr, process};
+use tunnel_obfuscation::{Config, Registry, ServerConfig};
+
+/// Run WireGuard obfuscation protocols from the command line.
+///
+/// The client receives WireGuard traffic on a local UDP socket and sends it obfuscated to a
+/// server. The server receives obfuscated traffic and forwards it to a WireGuard server.
+#[derive(Parser)]
+pub enum Opt {
+    /// List the available protocols and their parameters
+    List,
+
+    /// Run the client side of a protocol
+    Client {
+        /// Name of the protocol
+        protocol: String,
+
+        /// Address of the obfuscation server
+        #[clap(long)]
+        peer: SocketAddr,
+
+        /// Local address to receive WireGuard traffic on. Defaults to a random port on the
+        /// loopback interface.
+        #[clap(long)]
+        bind: Option<SocketAddr>,
 
-    let obfuscator = instantiate_requested(&args().last().unwrap()).await;
+        /// Firewall mark to apply to the sockets connected to the peer
+        #[cfg(target_os = "linux")]
+        #[clap(long, value_parser = parse_fwmark)]
+        fwmark: Option<u32>,
 
-    println!("endpoint() returns {:?}", obfuscator.endpoint());
+        /// MTU of the path to the peer
+        #[clap(long)]
+        mtu: Option<u16>,
+
+        /// Protocol-specific parameter, as NAME=VALUE. May be given multiple times.
+        #[clap(short, long = "parameter", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+    },
+
+    /// Run the server side of a protocol
+    Server {
+        /// Name of the protocol
+        protocol: String,
+
+        /// Address to receive obfuscated traffic on
+        #[clap(long)]
+        listen: SocketAddr,
+
+        /// Address of the WireGuard server to forward traffic to
+        #[clap(long)]
+        forward: SocketAddr,
+
+        /// Protocol-specific parameter, as NAME=VALUE. May be given multiple times.
+        #[clap(short, long = "parameter", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+    },
+}
 
-    if let Err(err) = obfuscator.run().await {
-        println!("obfuscator.run() failed: {err:?}");
+#[tokio::main]
+async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    if let Err(error) = run(Opt::parse()).await {
+        eprint!("Error: {error}");
+        let mut source = error.source();
+        while let Some(error) = source {
+            eprint!("\nCaused by: {error}");
+            source = error.source();
+        }
+        eprintln!();
+        process::exit(1);
     }
 }
 
-async fn instantiate_requested(obfuscator_type: &str) -> Box<dyn Obfuscator> {
-    match obfuscator_type {
-        "udp2tcp" => {
-            let settings = udp2tcp::Settings {
-                peer: SocketAddr::new("127.0.0.1".parse().unwrap(), 3030),
+async fn run(opt: Opt) -> tunnel_obfuscation::Result<()> {
+    let registry = Registry::builtin();
+
+    match opt {
+        Opt::List => {
+            for protocol in registry.protocols() {
+                println!("{} ({})", protocol.name(), protocol.transport());
+                for parameter in protocol.parameters() {
+                    let required = if parameter.required {
+                        " (required)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "    {}{required}: {}",
+                        parameter.name, parameter.description
+                    );
+                }
+            }
+            Ok(())
+        }
+        Opt::Client {
+            protocol,
+            peer,
+            bind,
+            #[cfg(target_os = "linux")]
+            fwmark,
+            mtu,
+            parameters,
+        } => {
+            let config = Config {
+                protocol,
+                endpoint: peer,
+                bind,
+                parameters: BTreeMap::from_iter(parameters),
+                mtu,
                 #[cfg(target_os = "linux")]
-                fwmark: Some(1337),
+                fwmark,
             };
-
-            create_obfuscator(&Settings::Udp2Tcp(settings))
-                .await
-                .expect("Creating obfuscator failed")
+            let obfuscator = registry.create_obfuscator(&config).await?;
+            println!(
+                "Listening for WireGuard traffic on {}",
+                obfuscator.endpoint()
+            );
+            obfuscator.run().await
         }
-        _ => {
-            unimplemented!()
+        Opt::Server {
+            protocol,
+            listen,
+            forward,
+            parameters,
+        } => {
+            let config = ServerConfig {
+                protocol,
+                listen,
+                forward,
+                parameters: BTreeMap::from_iter(parameters),
+            };
+            registry.run_server(&config).await
         }
     }
 }
+
+fn parse_parameter(parameter: &str) -> Result<(String, String), String> {
+    let (name, value) = parameter
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{parameter}'"))?;
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+/// Parse a firewall mark given in decimal, or in hexadecimal with a `0x` prefix.
+#[cfg(target_os = "linux")]
+fn parse_fwmark(fwmark: &str) -> Result<u32, String> {
+    let result = match fwmark.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => fwmark.parse(),
+    };
+    result.map_err(|error| error.to_string())
+}
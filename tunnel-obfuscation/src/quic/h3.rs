@@ -0,0 +1,571 @@
+//! The small subset of HTTP/3 (RFC 9114), QPACK (RFC 9204), and HTTP datagrams (RFC 9297) that is
+//! needed to proxy UDP over HTTP/3 (RFC 9298).
+//!
+//! Only the static QPACK table is used, and Huffman coding is never used when encoding. This is
+//! allowed since we never advertise a dynamic table capacity.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use quinn::{RecvStream, VarInt};
+use std::net::SocketAddr;
+
+/// ALPN protocol identifier for HTTP/3
+pub const ALPN: &[u8] = b"h3";
+
+/// Unidirectional stream type of the HTTP/3 control stream
+pub const STREAM_TYPE_CONTROL: u64 = 0x00;
+
+/// Frame types
+pub const FRAME_HEADERS: u64 = 0x01;
+pub const FRAME_SETTINGS: u64 = 0x04;
+
+/// Setting enabling extended CONNECT (RFC 9220)
+const SETTINGS_ENABLE_CONNECT_PROTOCOL: u64 = 0x08;
+/// Setting enabling HTTP datagrams (RFC 9297)
+const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+
+/// The largest frame that is accepted. Frames on the request stream only contain headers.
+const MAX_FRAME_SIZE: u64 = 16 * 1024;
+
+const PATH_PREFIX: &str = "/.well-known/masque/udp/";
+const CONNECT_UDP: &str = "connect-udp";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to read from stream
+    #[error("Failed to read from HTTP/3 stream")]
+    Read(#[from] quinn::ReadError),
+    /// Stream ended unexpectedly
+    #[error("HTTP/3 stream ended unexpectedly")]
+    UnexpectedEnd,
+    /// Frame exceeds the maximum size
+    #[error("HTTP/3 frame is too large")]
+    FrameTooLarge,
+    /// Field section could not be decoded
+    #[error("Failed to decode HTTP/3 headers")]
+    InvalidHeaders,
+}
+
+/// Return the opening bytes of the client's or server's control stream.
+pub fn control_stream() -> Bytes {
+    let mut settings = BytesMut::new();
+    for (id, value) in [
+        (SETTINGS_ENABLE_CONNECT_PROTOCOL, 1),
+        (SETTINGS_H3_DATAGRAM, 1),
+    ] {
+        put_varint(&mut settings, id);
+        put_varint(&mut settings, value);
+    }
+
+    let mut buf = BytesMut::new();
+    put_varint(&mut buf, STREAM_TYPE_CONTROL);
+    put_frame(&mut buf, FRAME_SETTINGS, &settings);
+    buf.freeze()
+}
+
+/// A CONNECT-UDP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectUdpRequest {
+    /// Hostname of the proxy
+    pub authority: String,
+    /// The address to proxy datagrams to
+    pub target: SocketAddr,
+    /// Bearer token used to authenticate with the proxy
+    pub token: Option<String>,
+}
+
+impl ConnectUdpRequest {
+    /// Encode the request as a HEADERS frame.
+    pub fn encode(&self) -> Bytes {
+        // Colons in IPv6 addresses must be percent-encoded in the path
+        let host = self.target.ip().to_string().replace(':', "%3A");
+        let path = format!("{PATH_PREFIX}{host}/{}/", self.target.port());
+
+        let mut fields = field_section_prefix();
+        put_indexed(&mut fields, STATIC_METHOD_CONNECT);
+        put_literal(&mut fields, ":protocol", CONNECT_UDP);
+        put_indexed(&mut fields, STATIC_SCHEME_HTTPS);
+        put_literal_with_name_ref(&mut fields, STATIC_AUTHORITY, &self.authority);
+        put_literal_with_name_ref(&mut fields, STATIC_PATH, &path);
+        if let Some(token) = &self.token {
+            put_literal_with_name_ref(
+                &mut fields,
+                STATIC_AUTHORIZATION,
+                &format!("Bearer {token}"),
+            );
+        }
+        put_literal(&mut fields, "capsule-protocol", "?1");
+
+        let mut buf = BytesMut::new();
+        put_frame(&mut buf, FRAME_HEADERS, &fields);
+        buf.freeze()
+    }
+
+    #[cfg(any(test, feature = "test-server"))]
+    /// Decode a request from the payload of a HEADERS frame. Returns `None` if the headers are not
+    /// a valid CONNECT-UDP request.
+    pub fn decode(mut fields: &[u8]) -> Option<Self> {
+        skip_field_section_prefix(&mut fields)?;
+
+        let (mut method, mut protocol, mut authority, mut path, mut token) =
+            (None, None, None, None, None);
+        while fields.has_remaining() {
+            let (name, value) = get_field_line(&mut fields)?;
+            match name.as_str() {
+                ":method" => method = Some(value),
+                ":protocol" => protocol = Some(value),
+                ":authority" => authority = Some(value),
+                ":path" => path = Some(value),
+                "authorization" => token = value.strip_prefix("Bearer ").map(str::to_owned),
+                _ => (),
+            }
+        }
+        if method? != "CONNECT" || protocol? != CONNECT_UDP {
+            return None;
+        }
+
+        let path = path?;
+        let (host, port) = path
+            .strip_prefix(PATH_PREFIX)?
+            .trim_end_matches('/')
+            .split_once('/')?;
+        let ip = host.replace("%3A", ":").replace("%3a", ":").parse().ok()?;
+        let target = SocketAddr::new(ip, port.parse().ok()?);
+
+        Some(Self {
+            authority: authority?,
+            target,
+            token,
+        })
+    }
+}
+
+#[cfg(any(test, feature = "test-server"))]
+/// Encode a response with the given status code as a HEADERS frame. Only status codes in the
+/// static table are supported.
+pub fn encode_response(status: u16) -> Bytes {
+    let index = STATIC_TABLE
+        .iter()
+        .position(|&(name, value)| name == ":status" && value == status.to_string())
+        .expect("status code must be in the static table");
+
+    let mut fields = field_section_prefix();
+    put_indexed(&mut fields, index as u64);
+
+    let mut buf = BytesMut::new();
+    put_frame(&mut buf, FRAME_HEADERS, &fields);
+    buf.freeze()
+}
+
+/// Decode the status code from the payload of a HEADERS frame.
+pub fn decode_status(mut fields: &[u8]) -> Option<u16> {
+    skip_field_section_prefix(&mut fields)?;
+    // Pseudo-header fields precede all other fields, and responses only have one
+    let (name, value) = get_field_line(&mut fields)?;
+    if name != ":status" {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Read the next frame from `stream`. `buf` holds data that has been read but not yet consumed.
+/// Returns `None` if the stream has ended.
+pub async fn read_frame(
+    stream: &mut RecvStream,
+    buf: &mut BytesMut,
+) -> Result<Option<(u64, Bytes)>, Error> {
+    loop {
+        let mut data = &buf[..];
+        if let (Some(frame_type), Some(length)) = (get_varint(&mut data), get_varint(&mut data)) {
+            if length > MAX_FRAME_SIZE {
+                return Err(Error::FrameTooLarge);
+            }
+            if data.len() as u64 >= length {
+                let header_len = buf.len() - data.len();
+                buf.advance(header_len);
+                return Ok(Some((frame_type, buf.split_to(length as usize).freeze())));
+            }
+        }
+
+        match stream.read_chunk(usize::MAX, true).await? {
+            Some(chunk) => buf.extend_from_slice(&chunk.bytes),
+            None if buf.is_empty() => return Ok(None),
+            None => return Err(Error::UnexpectedEnd),
+        }
+    }
+}
+
+/// Prefix an HTTP datagram payload with the quarter stream ID of the request stream and the
+/// context ID of UDP payloads, which is always zero.
+pub fn encode_datagram(quarter_stream_id: u64, payload: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(payload.len() + 9);
+    put_varint(&mut buf, quarter_stream_id);
+    put_varint(&mut buf, 0);
+    buf.put_slice(payload);
+    buf.freeze()
+}
+
+/// Return the UDP payload of an HTTP datagram if it belongs to the given request stream.
+pub fn decode_datagram(quarter_stream_id: u64, mut datagram: Bytes) -> Option<Bytes> {
+    let mut data = &datagram[..];
+    if get_varint(&mut data)? != quarter_stream_id || get_varint(&mut data)? != 0 {
+        return None;
+    }
+    let prefix_len = datagram.len() - data.len();
+    datagram.advance(prefix_len);
+    Some(datagram)
+}
+
+fn put_frame(buf: &mut BytesMut, frame_type: u64, payload: &[u8]) {
+    put_varint(buf, frame_type);
+    put_varint(buf, payload.len() as u64);
+    buf.put_slice(payload);
+}
+
+fn put_varint(buf: &mut BytesMut, value: u64) {
+    let value = VarInt::from_u64(value).expect("value must fit in a varint");
+    let value = value.into_inner();
+    match value {
+        0..=0x3f => buf.put_u8(value as u8),
+        0x40..=0x3fff => buf.put_u16(0x4000 | value as u16),
+        0x4000..=0x3fff_ffff => buf.put_u32(0x8000_0000 | value as u32),
+        _ => buf.put_u64(0xc000_0000_0000_0000 | value),
+    }
+}
+
+fn get_varint(buf: &mut &[u8]) -> Option<u64> {
+    let first = *buf.first()?;
+    let len = 1 << (first >> 6);
+    if buf.len() < len {
+        return None;
+    }
+    let mut value = u64::from(first & 0x3f);
+    for &byte in &buf[1..len] {
+        value = (value << 8) | u64::from(byte);
+    }
+    buf.advance(len);
+    Some(value)
+}
+
+// Indices into the QPACK static table (RFC 9204, appendix A)
+const STATIC_AUTHORITY: u64 = 0;
+const STATIC_PATH: u64 = 1;
+const STATIC_METHOD_CONNECT: u64 = 15;
+const STATIC_SCHEME_HTTPS: u64 = 23;
+const STATIC_AUTHORIZATION: u64 = 84;
+
+/// The QPACK static table.
+/// https://www.rfc-editor.org/rfc/rfc9204.html#appendix-A
+const STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains",
+    ),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains; preload",
+    ),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    (
+        "content-security-policy",
+        "script-src 'none'; object-src 'none'; base-uri 'none'",
+    ),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+fn static_entry(index: u64) -> Option<(&'static str, &'static str)> {
+    STATIC_TABLE.get(usize::try_from(index).ok()?).copied()
+}
+
+/// Required insert count and delta base. Both are zero since the dynamic table is never used.
+fn field_section_prefix() -> BytesMut {
+    BytesMut::from(&[0u8, 0u8][..])
+}
+
+fn skip_field_section_prefix(buf: &mut &[u8]) -> Option<()> {
+    let required_insert_count = get_prefixed_int(buf, 8)?.1;
+    get_prefixed_int(buf, 7)?;
+    (required_insert_count == 0).then_some(())
+}
+
+/// Indexed field line referring to the static table
+fn put_indexed(buf: &mut BytesMut, index: u64) {
+    put_prefixed_int(buf, 0b1100_0000, 6, index);
+}
+
+/// Literal field line with a name from the static table
+fn put_literal_with_name_ref(buf: &mut BytesMut, index: u64, value: &str) {
+    put_prefixed_int(buf, 0b0101_0000, 4, index);
+    put_string(buf, 0, 7, value);
+}
+
+/// Literal field line with a literal name
+fn put_literal(buf: &mut BytesMut, name: &str, value: &str) {
+    put_string(buf, 0b0010_0000, 3, name);
+    put_string(buf, 0, 7, value);
+}
+
+fn get_field_line(buf: &mut &[u8]) -> Option<(String, String)> {
+    let first = *buf.first()?;
+    if first & 0b1000_0000 != 0 {
+        // Indexed field line. Only the static table may be referenced.
+        let (flags, index) = get_prefixed_int(buf, 6)?;
+        if flags & 0b0100_0000 == 0 {
+            return None;
+        }
+        let (name, value) = static_entry(index)?;
+        Some((name.to_owned(), value.to_owned()))
+    } else if first & 0b0100_0000 != 0 {
+        // Literal field line with name reference
+        let (flags, index) = get_prefixed_int(buf, 4)?;
+        if flags & 0b0001_0000 == 0 {
+            return None;
+        }
+        let (name, _) = static_entry(index)?;
+        Some((name.to_owned(), get_string(buf, 7)?))
+    } else if first & 0b0010_0000 != 0 {
+        // Literal field line with literal name
+        let name = get_string(buf, 3)?;
+        Some((name, get_string(buf, 7)?))
+    } else {
+        // Post-base references to the dynamic table
+        None
+    }
+}
+
+/// Encode `value` as an integer with an `n`-bit prefix (RFC 7541, section 5.1), and set the
+/// remaining high bits of the first byte to `flags`.
+fn put_prefixed_int(buf: &mut BytesMut, flags: u8, n: u8, mut value: u64) {
+    let max = (1u64 << n) - 1;
+    if value < max {
+        buf.put_u8(flags | value as u8);
+        return;
+    }
+    buf.put_u8(flags | max as u8);
+    value -= max;
+    while value >= 0x80 {
+        buf.put_u8(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+/// Decode an integer with an `n`-bit prefix. Returns the high bits of the first byte and the
+/// integer.
+fn get_prefixed_int(buf: &mut &[u8], n: u8) -> Option<(u8, u64)> {
+    let first = buf.try_get_u8().ok()?;
+    let max = (1u64 << n) - 1;
+    let flags = first & !(max as u8);
+    let mut value = u64::from(first) & max;
+    if value < max {
+        return Some((flags, value));
+    }
+    for shift in (0..63).step_by(7) {
+        let byte = buf.try_get_u8().ok()?;
+        value = value.checked_add(u64::from(byte & 0x7f) << shift)?;
+        if byte & 0x80 == 0 {
+            return Some((flags, value));
+        }
+    }
+    None
+}
+
+/// Encode a string literal without Huffman coding. The length has an `n`-bit prefix, and is
+/// preceded by the Huffman flag.
+fn put_string(buf: &mut BytesMut, flags: u8, n: u8, value: &str) {
+    put_prefixed_int(buf, flags, n, value.len() as u64);
+    buf.put_slice(value.as_bytes());
+}
+
+/// Decode a string literal. Huffman-coded strings are not supported.
+fn get_string(buf: &mut &[u8], n: u8) -> Option<String> {
+    let (flags, len) = get_prefixed_int(buf, n)?;
+    let huffman_flag = 1 << n;
+    if flags & huffman_flag != 0 {
+        return None;
+    }
+    let len = usize::try_from(len).ok()?;
+    if buf.len() < len {
+        return None;
+    }
+    let value = String::from_utf8(buf[..len].to_vec()).ok()?;
+    buf.advance(len);
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_roundtrip() {
+        for target in ["192.0.2.1:51820", "[2001:db8::1]:51820"] {
+            let request = ConnectUdpRequest {
+                authority: "relay.example.com".to_owned(),
+                target: target.parse().unwrap(),
+                token: Some("secret".to_owned()),
+            };
+            let mut frame = &request.encode()[..];
+            assert_eq!(get_varint(&mut frame), Some(FRAME_HEADERS));
+            assert_eq!(get_varint(&mut frame), Some(frame.len() as u64));
+            assert_eq!(ConnectUdpRequest::decode(frame), Some(request));
+        }
+    }
+
+    #[test]
+    fn test_response_status() {
+        let frame = encode_response(200);
+        // Field section prefix, then the indexed field line `:status 200`
+        assert_eq!(&frame[..], &[0x01, 0x03, 0x00, 0x00, 0xd9]);
+        assert_eq!(decode_status(&frame[2..]), Some(200));
+
+        // Literal `:status 418` with a name reference to index 24
+        assert_eq!(
+            decode_status(&[0x00, 0x00, 0x5f, 0x09, 0x03, b'4', b'1', b'8']),
+            Some(418)
+        );
+    }
+
+    #[test]
+    fn test_static_table() {
+        // Indexed field line referring to the last entry, `x-frame-options sameorigin`
+        let mut fields = &[0xff, 0x23][..];
+        assert_eq!(
+            get_field_line(&mut fields),
+            Some(("x-frame-options".to_owned(), "sameorigin".to_owned()))
+        );
+        assert!(fields.is_empty());
+
+        // Literal `server` field with a name reference to index 92
+        let mut fields = &[0x5f, 0x4d, 0x02, b'h', b'3'][..];
+        assert_eq!(
+            get_field_line(&mut fields),
+            Some(("server".to_owned(), "h3".to_owned()))
+        );
+
+        assert_eq!(static_entry(99), None);
+    }
+
+    #[test]
+    fn test_varint() {
+        // Examples from RFC 9000, appendix A.1
+        for (encoded, value) in [
+            (&[0x25][..], 37),
+            (&[0x7b, 0xbd][..], 15293),
+            (&[0x9d, 0x7f, 0x3e, 0x7d][..], 494878333),
+            (
+                &[0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c][..],
+                151288809941952652,
+            ),
+        ] {
+            let mut buf = BytesMut::new();
+            put_varint(&mut buf, value);
+            assert_eq!(&buf[..], encoded);
+            assert_eq!(get_varint(&mut &buf[..]), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_datagram() {
+        let datagram = encode_datagram(1, b"payload");
+        assert_eq!(&datagram[..2], &[0x01, 0x00]);
+        assert_eq!(decode_datagram(2, datagram.clone()), None);
+        assert_eq!(
+            decode_datagram(1, datagram).as_deref(),
+            Some(&b"payload"[..])
+        );
+    }
+}
@@ -0,0 +1,642 @@
+//! QUIC obfuscation
+//!
+//! WireGuard packets are sent as HTTP datagrams over an HTTP/3 connection, using the CONNECT-UDP
+//! method defined by MASQUE (RFC 9298). To an observer, this looks like ordinary HTTP/3 traffic
+//! to the relay's hostname.
+//!
+//! Note: It is important not to connect to the QUIC endpoint right away. The remote socket must be
+//! protected in `VpnService` so that the socket is not routed through the tunnel.
+
+use super::{
+    box_obfuscator,
+    registry::{self, Parameter, Transport},
+    Obfuscator,
+};
+use async_trait::async_trait;
+use bytes::BytesMut;
+#[cfg(target_os = "linux")]
+use nix::sys::socket::{setsockopt, sockopt};
+use quinn::{
+    crypto::rustls::QuicClientConfig, ClientConfig, Connection, Endpoint, EndpointConfig,
+    TokioRuntime, TransportConfig,
+};
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use std::os::fd::AsRawFd;
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{net::UdpSocket, sync::oneshot};
+
+mod h3;
+#[cfg(any(test, feature = "test-server"))]
+pub mod server;
+
+/// How often to send keep-alive packets, so that the connection is not closed when idle
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind local UDP socket
+    #[error("Failed to bind UDP socket")]
+    BindUdp(#[source] io::Error),
+    /// Failed to bind remote UDP socket
+    #[error("Failed to bind remote UDP socket")]
+    BindRemoteUdp(#[source] io::Error),
+    /// Failed to set fwmark
+    #[cfg(target_os = "linux")]
+    #[error("Failed to set fwmark")]
+    SetFwmark(#[source] nix::Error),
+    /// Missing UDP listener address
+    #[error("Failed to retrieve UDP socket bind address")]
+    GetUdpLocalAddress(#[source] io::Error),
+    /// Failed to create QUIC endpoint
+    #[error("Failed to create QUIC endpoint")]
+    CreateEndpoint(#[source] io::Error),
+    /// Failed to create TLS configuration
+    #[error("Failed to create TLS configuration")]
+    TlsConfig(#[source] rustls::Error),
+    /// Invalid trusted certificate
+    #[error("Invalid trusted certificate")]
+    InvalidCertificate(#[source] rustls::Error),
+    /// Failed to wait for UDP client
+    #[error("Failed to wait for UDP client")]
+    WaitForUdpClient(#[source] io::Error),
+    /// Failed to connect to QUIC endpoint
+    #[error("Failed to connect to QUIC endpoint")]
+    Connect(#[source] quinn::ConnectError),
+    /// QUIC handshake failed
+    #[error("QUIC handshake failed")]
+    Handshake(#[source] quinn::ConnectionError),
+    /// Failed to open HTTP/3 stream
+    #[error("Failed to open HTTP/3 stream")]
+    OpenStream(#[source] quinn::ConnectionError),
+    /// Failed to send HTTP/3 request
+    #[error("Failed to send HTTP/3 request")]
+    SendRequest(#[source] quinn::WriteError),
+    /// Failed to receive HTTP/3 response
+    #[error("Failed to receive HTTP/3 response")]
+    ReceiveResponse(#[source] h3::Error),
+    /// The proxy refused the request
+    #[error("Proxy refused CONNECT-UDP request with status {0}")]
+    RequestRefused(u16),
+    /// The proxy sent an invalid response
+    #[error("Proxy sent an invalid response")]
+    InvalidResponse,
+    /// The proxy does not support datagrams
+    #[error("Proxy does not support QUIC datagrams")]
+    DatagramsUnsupported,
+    /// The connection was closed
+    #[error("QUIC connection closed")]
+    ConnectionClosed(#[source] quinn::ConnectionError),
+}
+
+#[derive(Debug)]
+pub struct Settings {
+    /// Remote QUIC endpoint
+    pub quic_endpoint: SocketAddr,
+    /// Remote WireGuard endpoint, as seen from the proxy
+    pub wireguard_endpoint: SocketAddr,
+    /// Local address to receive WireGuard traffic on
+    pub bind: SocketAddr,
+    /// Hostname of the proxy. This is used for SNI and to verify its certificate.
+    pub hostname: String,
+    /// Token used to authenticate with the proxy
+    pub auth_token: Option<String>,
+    /// MTU of the path to the proxy
+    pub mtu: Option<u16>,
+    /// DER-encoded certificates to trust in addition to the web PKI roots
+    pub trusted_certificates: Vec<Vec<u8>>,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+/// Name of the protocol in the [`registry`]
+pub const NAME: &str = "quic";
+/// Parameter setting the hostname of the proxy
+pub const HOSTNAME: &str = "hostname";
+/// Parameter setting the token used to authenticate with the proxy
+pub const AUTH_TOKEN: &str = "auth_token";
+
+/// HTTP/3 CONNECT-UDP, forwarding traffic to a WireGuard server on the same host as the proxy.
+pub struct QuicProtocol;
+
+#[async_trait]
+impl registry::Protocol for QuicProtocol {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                name: HOSTNAME,
+                description: "Hostname of the proxy, used for SNI and to verify its certificate",
+                required: true,
+            },
+            Parameter {
+                name: AUTH_TOKEN,
+                description: "Token used to authenticate with the proxy",
+                required: false,
+            },
+        ]
+    }
+
+    fn transport(&self) -> Transport {
+        Transport::Udp
+    }
+
+    fn packet_overhead(&self, _config: &registry::Config) -> u16 {
+        packet_overhead()
+    }
+
+    async fn create(&self, config: &registry::Config) -> crate::Result<Box<dyn Obfuscator>> {
+        let settings = Settings {
+            quic_endpoint: config.endpoint,
+            wireguard_endpoint: config.local_wireguard_endpoint(),
+            bind: config.local_bind_addr(),
+            hostname: config.required_parameter(HOSTNAME)?.to_owned(),
+            auth_token: config.parameter(AUTH_TOKEN).map(str::to_owned),
+            mtu: config.mtu,
+            trusted_certificates: vec![],
+            #[cfg(target_os = "linux")]
+            fwmark: config.fwmark,
+        };
+        Quic::new(&settings)
+            .await
+            .map(box_obfuscator)
+            .map_err(crate::Error::CreateQuicObfuscator)
+    }
+}
+
+pub struct Quic {
+    udp_client_addr: SocketAddr,
+    server: tokio::task::JoinHandle<Result<()>>,
+    // The receiver will implicitly shut down when this is dropped
+    _shutdown_tx: oneshot::Sender<()>,
+    #[cfg(target_os = "android")]
+    outbound_fd: i32,
+}
+
+impl Quic {
+    pub(crate) async fn new(settings: &Settings) -> Result<Self> {
+        let (local_udp_socket, udp_client_addr) = create_local_udp_socket(settings.bind).await?;
+
+        let remote_socket = create_remote_socket(
+            settings.quic_endpoint.is_ipv4(),
+            #[cfg(target_os = "linux")]
+            settings.fwmark,
+        )?;
+
+        #[cfg(target_os = "android")]
+        let outbound_fd = remote_socket.as_raw_fd();
+
+        let endpoint = Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            remote_socket,
+            Arc::new(TokioRuntime),
+        )
+        .map_err(Error::CreateEndpoint)?;
+
+        let client_config = client_config(settings)?;
+        let request = h3::ConnectUdpRequest {
+            authority: settings.hostname.clone(),
+            target: settings.wireguard_endpoint,
+            token: settings.auth_token.clone(),
+        };
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let server = tokio::spawn(run_forwarding(
+            endpoint,
+            client_config,
+            settings.quic_endpoint,
+            request,
+            local_udp_socket,
+            shutdown_rx,
+        ));
+
+        Ok(Quic {
+            udp_client_addr,
+            server,
+            _shutdown_tx: shutdown_tx,
+            #[cfg(target_os = "android")]
+            outbound_fd,
+        })
+    }
+}
+
+fn client_config(settings: &Settings) -> Result<ClientConfig> {
+    let mut roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    for certificate in &settings.trusted_certificates {
+        roots
+            .add(certificate.clone().into())
+            .map_err(Error::InvalidCertificate)?;
+    }
+
+    let mut tls_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .map_err(Error::TlsConfig)?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    tls_config.alpn_protocols = vec![h3::ALPN.to_vec()];
+
+    let quic_config = QuicClientConfig::try_from(tls_config)
+        .expect("TLS 1.3 and an initial cipher suite is used");
+    let mut client_config = ClientConfig::new(Arc::new(quic_config));
+
+    let mut transport = TransportConfig::default();
+    transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+    if let Some(mtu) = settings.mtu {
+        let header_len = ip_header_len(settings.quic_endpoint) + UDP_HEADER_LEN;
+        // Smaller values are not allowed by QUIC
+        transport.initial_mtu(mtu.saturating_sub(header_len).max(1200));
+    }
+    client_config.transport_config(Arc::new(transport));
+
+    Ok(client_config)
+}
+
+async fn run_forwarding(
+    endpoint: Endpoint,
+    client_config: ClientConfig,
+    quic_endpoint: SocketAddr,
+    request: h3::ConnectUdpRequest,
+    local_udp_socket: UdpSocket,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> Result<()> {
+    wait_for_local_udp_client(&local_udp_socket)
+        .await
+        .map_err(Error::WaitForUdpClient)?;
+
+    let connection = endpoint
+        .connect_with(client_config, quic_endpoint, &request.authority)
+        .map_err(Error::Connect)?
+        .await
+        .map_err(Error::Handshake)?;
+    log::trace!("Connected to QUIC endpoint {quic_endpoint}");
+
+    let quarter_stream_id = connect_udp(&connection, &request).await?;
+
+    let local_udp = Arc::new(local_udp_socket);
+
+    let mut client = tokio::spawn(handle_outgoing(
+        connection.clone(),
+        local_udp.clone(),
+        quarter_stream_id,
+    ));
+    let mut server = tokio::spawn(handle_incoming(
+        connection.clone(),
+        local_udp,
+        quarter_stream_id,
+    ));
+
+    let result = tokio::select! {
+        _ = shutdown_rx => {
+            log::trace!("Stopping QUIC obfuscation");
+            Ok(())
+        }
+        result = &mut server => result.unwrap_or(Ok(())),
+        result = &mut client => {
+            let result = result.unwrap_or(Ok(()));
+            if result.is_ok() {
+                log::trace!("Local UDP client closed");
+            }
+            result
+        }
+    };
+
+    client.abort();
+    server.abort();
+    connection.close(0u32.into(), b"");
+
+    result
+}
+
+/// Set up the HTTP/3 connection and send a CONNECT-UDP request. Returns the quarter stream ID
+/// which identifies the datagrams that belong to the request.
+async fn connect_udp(connection: &Connection, request: &h3::ConnectUdpRequest) -> Result<u64> {
+    if connection.max_datagram_size().is_none() {
+        return Err(Error::DatagramsUnsupported);
+    }
+
+    let mut control_stream = connection.open_uni().await.map_err(Error::OpenStream)?;
+    control_stream
+        .write_all(&h3::control_stream())
+        .await
+        .map_err(Error::SendRequest)?;
+
+    // The server's control and QPACK streams must be kept open for the lifetime of the
+    // connection, but their contents are not needed
+    let connection_handle = connection.clone();
+    tokio::spawn(async move {
+        // Keep our control stream open as well
+        let _control_stream = control_stream;
+        while let Ok(mut stream) = connection_handle.accept_uni().await {
+            tokio::spawn(async move {
+                while let Ok(Some(_)) = stream.read_chunk(usize::MAX, true).await {}
+            });
+        }
+    });
+
+    let (mut send, mut recv) = connection.open_bi().await.map_err(Error::OpenStream)?;
+    send.write_all(&request.encode())
+        .await
+        .map_err(Error::SendRequest)?;
+
+    let mut buf = BytesMut::new();
+    loop {
+        match h3::read_frame(&mut recv, &mut buf)
+            .await
+            .map_err(Error::ReceiveResponse)?
+        {
+            Some((h3::FRAME_HEADERS, headers)) => {
+                let status = h3::decode_status(&headers).ok_or(Error::InvalidResponse)?;
+                match status {
+                    // Informational responses precede the final response
+                    100..=199 => continue,
+                    200..=299 => break,
+                    status => return Err(Error::RequestRefused(status)),
+                }
+            }
+            // Unknown and reserved frame types must be ignored
+            Some(_) => continue,
+            None => return Err(Error::InvalidResponse),
+        }
+    }
+
+    // The tunnel is closed if the request stream is closed, so keep it open until the
+    // connection is closed. Capsules sent on the stream are ignored.
+    let stream_id = u64::from(send.id());
+    tokio::spawn(async move {
+        let _send = send;
+        while let Ok(Some(_)) = h3::read_frame(&mut recv, &mut buf).await {}
+    });
+
+    Ok(stream_id / 4)
+}
+
+async fn create_local_udp_socket(bind_addr: SocketAddr) -> Result<(UdpSocket, SocketAddr)> {
+    let local_udp_socket = UdpSocket::bind(bind_addr).await.map_err(Error::BindUdp)?;
+    let udp_client_addr = local_udp_socket
+        .local_addr()
+        .map_err(Error::GetUdpLocalAddress)?;
+
+    Ok((local_udp_socket, udp_client_addr))
+}
+
+fn create_remote_socket(
+    ipv4: bool,
+    #[cfg(target_os = "linux")] fwmark: Option<u32>,
+) -> Result<std::net::UdpSocket> {
+    let random_bind_addr = if ipv4 {
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)
+    } else {
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
+    };
+    let socket = std::net::UdpSocket::bind(random_bind_addr).map_err(Error::BindRemoteUdp)?;
+    #[cfg(target_os = "linux")]
+    if let Some(fwmark) = fwmark {
+        setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark).map_err(Error::SetFwmark)?;
+    }
+    Ok(socket)
+}
+
+/// Wait for a client to connect to `udp_listener` and connect the socket to that address
+async fn wait_for_local_udp_client(udp_listener: &UdpSocket) -> io::Result<()> {
+    log::trace!("Waiting for UDP socket client");
+    let client_addr = udp_listener.peek_sender().await?;
+
+    log::trace!("UDP connection from {client_addr}");
+    udp_listener.connect(client_addr).await
+}
+
+async fn handle_outgoing(
+    connection: Connection,
+    local_udp_read: Arc<UdpSocket>,
+    quarter_stream_id: u64,
+) -> Result<()> {
+    let mut rx_buffer = vec![0u8; u16::MAX as usize];
+
+    loop {
+        let read_n = match local_udp_read.recv(&mut rx_buffer).await {
+            Ok(read_n) => read_n,
+            Err(error) => {
+                log::error!("Failed to read from local UDP socket: {error}");
+                return Ok(());
+            }
+        };
+
+        let datagram = h3::encode_datagram(quarter_stream_id, &rx_buffer[..read_n]);
+        match connection.send_datagram(datagram) {
+            Ok(()) => (),
+            Err(quinn::SendDatagramError::ConnectionLost(error)) => {
+                return Err(Error::ConnectionClosed(error));
+            }
+            // The packet is dropped, like any other UDP packet that does not fit the path MTU
+            Err(error) => log::trace!("Failed to send QUIC datagram: {error}"),
+        }
+    }
+}
+
+async fn handle_incoming(
+    connection: Connection,
+    local_udp_write: Arc<UdpSocket>,
+    quarter_stream_id: u64,
+) -> Result<()> {
+    loop {
+        let datagram = connection
+            .read_datagram()
+            .await
+            .map_err(Error::ConnectionClosed)?;
+        let Some(payload) = h3::decode_datagram(quarter_stream_id, datagram) else {
+            log::trace!("Ignoring datagram for unknown request or context");
+            continue;
+        };
+
+        if let Err(error) = local_udp_write.send(&payload).await {
+            log::error!("Failed to write to local UDP socket: {error}");
+            if is_fatal_socket_io_error(&error) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Obfuscator for Quic {
+    fn endpoint(&self) -> SocketAddr {
+        self.udp_client_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        match self.server.await {
+            Ok(result) => result.map_err(crate::Error::RunQuicObfuscator),
+            Err(_err) if _err.is_cancelled() => Ok(()),
+            Err(_err) => panic!("server handle panicked"),
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    fn remote_socket_fd(&self) -> std::os::unix::io::RawFd {
+        self.outbound_fd
+    }
+
+    fn packet_overhead(&self) -> u16 {
+        packet_overhead()
+    }
+}
+
+fn packet_overhead() -> u16 {
+    // A 1-RTT QUIC packet with a single DATAGRAM frame looks like this:
+    // [flags][destination connection ID][packet number][frame type][length][payload][tag]
+    // https://www.rfc-editor.org/rfc/rfc9000.html#section-17.3.1
+    // https://www.rfc-editor.org/rfc/rfc9221.html#section-4
+    // The payload is prefixed by the quarter stream ID and context ID.
+    // https://www.rfc-editor.org/rfc/rfc9298.html#section-5
+    let flags = 1;
+    let max_connection_id_len = 20;
+    let max_packet_number_len = 4;
+    let frame_type = 1;
+    let max_length = 2;
+    let tag_len = 16;
+    let quarter_stream_id_len = 1;
+    let context_id_len = 1;
+
+    flags
+        + max_connection_id_len
+        + max_packet_number_len
+        + frame_type
+        + max_length
+        + tag_len
+        + quarter_stream_id_len
+        + context_id_len
+}
+
+const UDP_HEADER_LEN: u16 = 8;
+
+fn ip_header_len(addr: SocketAddr) -> u16 {
+    if addr.is_ipv4() {
+        20
+    } else {
+        40
+    }
+}
+
+fn is_fatal_socket_io_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::NotConnected
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Send packets through the obfuscator and a local proxy to a UDP echo server
+    #[tokio::test]
+    async fn test_quic_obfuscation() {
+        let echo_server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo_server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 2048];
+            while let Ok((n, addr)) = echo_server.recv_from(&mut buf).await {
+                let _ = echo_server.send_to(&buf[..n], addr).await;
+            }
+        });
+
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let proxy = server::Server::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            certificate.cert.der().to_vec(),
+            certificate.key_pair.serialize_der(),
+            Some("token".to_owned()),
+        )
+        .unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        let settings = Settings {
+            quic_endpoint: proxy_addr,
+            wireguard_endpoint: echo_addr,
+            bind: "127.0.0.1:0".parse().unwrap(),
+            hostname: "localhost".to_owned(),
+            auth_token: Some("token".to_owned()),
+            mtu: None,
+            trusted_certificates: vec![certificate.cert.der().to_vec()],
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        };
+        let obfuscator = Box::new(Quic::new(&settings).await.unwrap());
+        let local_endpoint = obfuscator.endpoint();
+        tokio::spawn(obfuscator.run());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(local_endpoint).await.unwrap();
+
+        let mut buf = [0u8; 2048];
+        for packet in [&b"first"[..], &[0xab; 1000][..]] {
+            client.send(packet).await.unwrap();
+            let n = tokio::time::timeout(Duration::from_secs(5), client.recv(&mut buf))
+                .await
+                .expect("timed out waiting for echo")
+                .unwrap();
+            assert_eq!(&buf[..n], packet);
+        }
+    }
+
+    /// The proxy must refuse requests with the wrong token
+    #[tokio::test]
+    async fn test_quic_obfuscation_invalid_token() {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let proxy = server::Server::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            certificate.cert.der().to_vec(),
+            certificate.key_pair.serialize_der(),
+            Some("token".to_owned()),
+        )
+        .unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        let settings = Settings {
+            quic_endpoint: proxy_addr,
+            wireguard_endpoint: "127.0.0.1:51820".parse().unwrap(),
+            bind: "127.0.0.1:0".parse().unwrap(),
+            hostname: "localhost".to_owned(),
+            auth_token: Some("wrong".to_owned()),
+            mtu: None,
+            trusted_certificates: vec![certificate.cert.der().to_vec()],
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        };
+        let obfuscator = Quic::new(&settings).await.unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client
+            .send_to(b"packet", obfuscator.endpoint())
+            .await
+            .unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), Box::new(obfuscator).run())
+            .await
+            .expect("timed out waiting for obfuscator");
+        assert!(matches!(
+            result,
+            Err(crate::Error::RunQuicObfuscator(Error::RequestRefused(403)))
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+//! A minimal CONNECT-UDP proxy, used to test QUIC obfuscation without a relay.
+
+use super::h3;
+use bytes::BytesMut;
+use quinn::{crypto::rustls::QuicServerConfig, Connection, Endpoint, ServerConfig};
+use std::{io, net::SocketAddr, sync::Arc};
+use tokio::net::UdpSocket;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Invalid certificate or private key
+    #[error("Invalid certificate or private key")]
+    InvalidCertificate(#[source] rustls::Error),
+    /// Failed to create QUIC endpoint
+    #[error("Failed to create QUIC endpoint")]
+    CreateEndpoint(#[source] io::Error),
+}
+
+/// A proxy which forwards datagrams from CONNECT-UDP requests to the requested target.
+pub struct Server {
+    endpoint: Endpoint,
+    auth_token: Option<String>,
+}
+
+impl Server {
+    /// Listen on `addr`, using a DER-encoded certificate and PKCS #8 private key. If `auth_token`
+    /// is set, requests without a matching bearer token are refused.
+    pub fn bind(
+        addr: SocketAddr,
+        certificate: Vec<u8>,
+        private_key: Vec<u8>,
+        auth_token: Option<String>,
+    ) -> Result<Self, Error> {
+        let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(Error::InvalidCertificate)?
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certificate.into()],
+            rustls::pki_types::PrivatePkcs8KeyDer::from(private_key).into(),
+        )
+        .map_err(Error::InvalidCertificate)?;
+        tls_config.alpn_protocols = vec![h3::ALPN.to_vec()];
+
+        let quic_config = QuicServerConfig::try_from(tls_config)
+            .expect("TLS 1.3 and an initial cipher suite is used");
+        let endpoint = Endpoint::server(ServerConfig::with_crypto(Arc::new(quic_config)), addr)
+            .map_err(Error::CreateEndpoint)?;
+
+        Ok(Self {
+            endpoint,
+            auth_token,
+        })
+    }
+
+    /// Return the address that the proxy is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.local_addr()
+    }
+
+    /// Accept connections until the endpoint is closed.
+    pub async fn run(self) {
+        while let Some(incoming) = self.endpoint.accept().await {
+            let auth_token = self.auth_token.clone();
+            tokio::spawn(async move {
+                match incoming.await {
+                    Ok(connection) => {
+                        if let Err(error) = handle_connection(connection, auth_token).await {
+                            log::debug!("CONNECT-UDP proxy connection failed: {error}");
+                        }
+                    }
+                    Err(error) => log::debug!("QUIC handshake failed: {error}"),
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(
+    connection: Connection,
+    auth_token: Option<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut control_stream = connection.open_uni().await?;
+    control_stream.write_all(&h3::control_stream()).await?;
+
+    let connection_handle = connection.clone();
+    tokio::spawn(async move {
+        while let Ok(mut stream) = connection_handle.accept_uni().await {
+            tokio::spawn(async move {
+                while let Ok(Some(_)) = stream.read_chunk(usize::MAX, true).await {}
+            });
+        }
+    });
+
+    let (mut send, mut recv) = connection.accept_bi().await?;
+    let mut buf = BytesMut::new();
+    let request = loop {
+        match h3::read_frame(&mut recv, &mut buf).await? {
+            Some((h3::FRAME_HEADERS, headers)) => break h3::ConnectUdpRequest::decode(&headers),
+            Some(_) => continue,
+            None => return Ok(()),
+        }
+    };
+
+    let Some(request) = request else {
+        send.write_all(&h3::encode_response(400)).await?;
+        send.finish()?;
+        return Ok(());
+    };
+    if auth_token.is_some() && request.token != auth_token {
+        send.write_all(&h3::encode_response(403)).await?;
+        send.finish()?;
+        return Ok(());
+    }
+
+    let bind_addr: SocketAddr = if request.target.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = Arc::new(UdpSocket::bind(bind_addr).await?);
+    socket.connect(request.target).await?;
+    send.write_all(&h3::encode_response(200)).await?;
+
+    let quarter_stream_id = u64::from(send.id()) / 4;
+    let outgoing_socket = socket.clone();
+    let outgoing_connection = connection.clone();
+    let outgoing = tokio::spawn(async move {
+        while let Ok(datagram) = outgoing_connection.read_datagram().await {
+            if let Some(payload) = h3::decode_datagram(quarter_stream_id, datagram) {
+                let _ = outgoing_socket.send(&payload).await;
+            }
+        }
+    });
+
+    let mut rx_buffer = vec![0u8; u16::MAX as usize];
+    let result = loop {
+        tokio::select! {
+            result = socket.recv(&mut rx_buffer) => {
+                let n = result?;
+                let datagram = h3::encode_datagram(quarter_stream_id, &rx_buffer[..n]);
+                let _ = connection.send_datagram(datagram);
+            }
+            error = connection.closed() => break error,
+        }
+    };
+    outgoing.abort();
+    log::debug!("CONNECT-UDP proxy connection closed: {result}");
+
+    Ok(())
+}
@@ -0,0 +1,334 @@
+//! Registry of obfuscation protocols
+//!
+//! Each protocol describes the parameters it accepts, the transport used to reach its remote
+//! endpoint and how much overhead it adds to each packet, and knows how to create an
+//! [`Obfuscator`] from a protocol-independent [`Config`]. This means that callers only need to
+//! know the name of a protocol and its parameters in order to use it.
+//!
+//! Protocols may also implement the server side, which receives obfuscated traffic and forwards it
+//! to a WireGuard server. See [`ServerConfig`].
+//!
+//! The rest of the app describes the built-in protocols using typed configurations, such as
+//! `ObfuscatorConfig` in `talpid-types`, which are mapped onto the names and parameters exported by
+//! each protocol module when an obfuscator is created. Any other registered protocol can be used
+//! through the generic variants of those configurations, which only carry the name of the
+//! protocol, the endpoint of its server and its parameters. Since the transport of the endpoint is
+//! then chosen by the user, it is checked using [`Registry::check_transport`].
+
+use crate::{Error, Obfuscator, Result};
+use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::LazyLock,
+};
+
+/// Registry containing all built-in protocols.
+static BUILTIN: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+/// A parameter accepted by an obfuscation protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}
+
+/// Transport protocol used to reach the remote endpoint of an obfuscator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Udp => "UDP".fmt(f),
+            Transport::Tcp => "TCP".fmt(f),
+        }
+    }
+}
+
+/// Protocol-independent obfuscator configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Name of a registered protocol
+    pub protocol: String,
+    /// Remote endpoint of the obfuscation server
+    pub endpoint: SocketAddr,
+    /// Local address to receive WireGuard traffic on. If `None`, a random port on the loopback
+    /// interface is used.
+    pub bind: Option<SocketAddr>,
+    /// Protocol-specific parameters
+    pub parameters: BTreeMap<String, String>,
+    /// MTU of the path to the remote endpoint, if known
+    pub mtu: Option<u16>,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+impl Config {
+    /// Return the value of the parameter `name`, if it is set.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get(name).map(String::as_str)
+    }
+
+    /// Return the value of the parameter `name`, or an error if it is not set.
+    pub fn required_parameter(&self, name: &'static str) -> Result<&str> {
+        self.parameter(name).ok_or_else(|| Error::MissingParameter {
+            protocol: self.protocol.clone(),
+            name,
+        })
+    }
+
+    /// Return the address to bind the local UDP socket to: [`Config::bind`] if set, and otherwise
+    /// a random port on the loopback interface of the same address family as the endpoint.
+    pub fn local_bind_addr(&self) -> SocketAddr {
+        self.bind.unwrap_or_else(|| {
+            if self.endpoint.is_ipv4() {
+                SocketAddr::from((Ipv4Addr::LOCALHOST, 0))
+            } else {
+                SocketAddr::from((Ipv6Addr::LOCALHOST, 0))
+            }
+        })
+    }
+
+    /// Return the address of the WireGuard endpoint as seen from the obfuscation server, for
+    /// protocols that forward traffic to a WireGuard server running on the same host.
+    pub fn local_wireguard_endpoint(&self) -> SocketAddr {
+        const WIREGUARD_PORT: u16 = 51820;
+        if self.endpoint.is_ipv4() {
+            SocketAddr::from((Ipv4Addr::LOCALHOST, WIREGUARD_PORT))
+        } else {
+            SocketAddr::from((Ipv6Addr::LOCALHOST, WIREGUARD_PORT))
+        }
+    }
+}
+
+/// Protocol-independent configuration of an obfuscation server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Name of a registered protocol
+    pub protocol: String,
+    /// Address to receive obfuscated traffic on
+    pub listen: SocketAddr,
+    /// Address of the WireGuard server to forward traffic to
+    pub forward: SocketAddr,
+    /// Protocol-specific parameters
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl ServerConfig {
+    /// Return the value of the parameter `name`, if it is set.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get(name).map(String::as_str)
+    }
+}
+
+/// An obfuscation protocol which can be added to a [`Registry`].
+#[async_trait]
+pub trait Protocol: Send + Sync {
+    /// Unique name of the protocol
+    fn name(&self) -> &'static str;
+
+    /// Parameters accepted by the protocol. Unknown parameters are rejected.
+    fn parameters(&self) -> &'static [Parameter];
+
+    /// Transport protocol used to reach [`Config::endpoint`].
+    fn transport(&self) -> Transport;
+
+    /// The overhead (in bytes) added to each packet when using `config`.
+    fn packet_overhead(&self, config: &Config) -> u16;
+
+    /// Create an obfuscator. `config` has already been checked against [`Protocol::parameters`].
+    async fn create(&self, config: &Config) -> Result<Box<dyn Obfuscator>>;
+
+    /// Run the server side of the protocol until it fails. `config` has already been checked
+    /// against [`Protocol::parameters`]. Protocols without a server return
+    /// [`Error::ServerNotSupported`].
+    async fn serve(&self, _config: &ServerConfig) -> Result<()> {
+        Err(Error::ServerNotSupported(self.name()))
+    }
+}
+
+/// A set of obfuscation protocols, identified by name.
+pub struct Registry {
+    protocols: BTreeMap<&'static str, Box<dyn Protocol>>,
+}
+
+impl Registry {
+    /// Create a registry without any protocols.
+    pub fn empty() -> Self {
+        Self {
+            protocols: BTreeMap::new(),
+        }
+    }
+
+    /// Return a registry containing all built-in protocols.
+    pub fn builtin() -> &'static Self {
+        &BUILTIN
+    }
+
+    /// Add `protocol` to the registry, replacing any protocol with the same name.
+    pub fn register(&mut self, protocol: impl Protocol + 'static) {
+        self.protocols.insert(protocol.name(), Box::new(protocol));
+    }
+
+    /// Return the protocol named `name`, if it is registered.
+    pub fn get(&self, name: &str) -> Option<&dyn Protocol> {
+        self.protocols.get(name).map(|protocol| protocol.as_ref())
+    }
+
+    /// Return all registered protocols, ordered by name.
+    pub fn protocols(&self) -> impl Iterator<Item = &dyn Protocol> {
+        self.protocols.values().map(|protocol| protocol.as_ref())
+    }
+
+    /// Return an error unless the protocol named `name` reaches its remote endpoint using
+    /// `transport`.
+    pub fn check_transport(&self, name: &str, transport: Transport) -> Result<()> {
+        let protocol = self
+            .get(name)
+            .ok_or_else(|| Error::UnknownProtocol(name.to_owned()))?;
+        if protocol.transport() != transport {
+            return Err(Error::UnexpectedTransport {
+                protocol: name.to_owned(),
+                transport,
+            });
+        }
+        Ok(())
+    }
+
+    /// Validate `config` and create an obfuscator for it.
+    pub async fn create_obfuscator(&self, config: &Config) -> Result<Box<dyn Obfuscator>> {
+        let protocol = self.validate(config)?;
+        protocol.create(config).await
+    }
+
+    /// Validate `config` and run the server side of its protocol until it fails.
+    pub async fn run_server(&self, config: &ServerConfig) -> Result<()> {
+        let protocol = self.validate_parameters(&config.protocol, &config.parameters)?;
+        protocol.serve(config).await
+    }
+
+    /// Return the protocol used by `config`, or an error if the protocol is not registered or
+    /// does not accept the given parameters.
+    pub fn validate(&self, config: &Config) -> Result<&dyn Protocol> {
+        self.validate_parameters(&config.protocol, &config.parameters)
+    }
+
+    fn validate_parameters(
+        &self,
+        protocol_name: &str,
+        values: &BTreeMap<String, String>,
+    ) -> Result<&dyn Protocol> {
+        let protocol = self
+            .get(protocol_name)
+            .ok_or_else(|| Error::UnknownProtocol(protocol_name.to_owned()))?;
+        let parameters = protocol.parameters();
+
+        if let Some(name) = values
+            .keys()
+            .find(|name| !parameters.iter().any(|parameter| parameter.name == *name))
+        {
+            return Err(Error::UnknownParameter {
+                protocol: protocol_name.to_owned(),
+                name: name.clone(),
+            });
+        }
+        if let Some(parameter) = parameters
+            .iter()
+            .find(|parameter| parameter.required && !values.contains_key(parameter.name))
+        {
+            return Err(Error::MissingParameter {
+                protocol: protocol_name.to_owned(),
+                name: parameter.name,
+            });
+        }
+
+        Ok(protocol)
+    }
+}
+
+impl Default for Registry {
+    /// Create a registry containing all built-in protocols.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(crate::udp2tcp::Udp2TcpProtocol);
+        registry.register(crate::shadowsocks::ShadowsocksProtocol);
+        registry.register(crate::quic::QuicProtocol);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(protocol: &str, parameters: &[(&str, &str)]) -> Config {
+        Config {
+            protocol: protocol.to_owned(),
+            endpoint: SocketAddr::from((Ipv4Addr::LOCALHOST, 443)),
+            bind: None,
+            parameters: parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            mtu: None,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        }
+    }
+
+    #[test]
+    fn test_builtin_protocols() {
+        let names: Vec<_> = Registry::builtin()
+            .protocols()
+            .map(|protocol| protocol.name())
+            .collect();
+        assert_eq!(names, ["quic", "shadowsocks", "udp2tcp"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let registry = Registry::builtin();
+
+        assert!(registry.validate(&config("udp2tcp", &[])).is_ok());
+        assert!(matches!(
+            registry.validate(&config("invalid", &[])),
+            Err(Error::UnknownProtocol(_))
+        ));
+        assert!(matches!(
+            registry.validate(&config("udp2tcp", &[("hostname", "example.com")])),
+            Err(Error::UnknownParameter { .. })
+        ));
+        assert!(matches!(
+            registry.validate(&config("quic", &[])),
+            Err(Error::MissingParameter {
+                name: "hostname",
+                ..
+            })
+        ));
+        assert!(registry
+            .validate(&config("quic", &[("hostname", "example.com")]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_check_transport() {
+        let registry = Registry::builtin();
+
+        assert!(registry.check_transport("udp2tcp", Transport::Tcp).is_ok());
+        assert!(registry.check_transport("quic", Transport::Udp).is_ok());
+        assert!(matches!(
+            registry.check_transport("udp2tcp", Transport::Udp),
+            Err(Error::UnexpectedTransport { .. })
+        ));
+        assert!(matches!(
+            registry.check_transport("invalid", Transport::Udp),
+            Err(Error::UnknownProtocol(_))
+        ));
+    }
+}
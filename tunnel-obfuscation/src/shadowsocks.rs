@@ -2,8 +2,18 @@
 //!
 //! Note: It is important not to connect to the shadowsocks endpoint right away. The remote socket
 //! must be protected in `VpnService` so that the socket is not routed through the tunnel.
+//!
+//! The server side only forwards traffic to a single WireGuard server, regardless of the target
+//! address requested by clients.
+//!
+//! Mullvad relays use a fixed cipher and password. Self-hosted servers may use any AEAD cipher and
+//! password, set using the `cipher` and `password` parameters.
 
-use super::Obfuscator;
+use super::{
+    box_obfuscator,
+    registry::{self, Parameter, Transport},
+    Obfuscator,
+};
 use async_trait::async_trait;
 #[cfg(target_os = "linux")]
 use nix::sys::socket::{setsockopt, sockopt};
@@ -19,12 +29,24 @@ use shadowsocks::{
 };
 #[cfg(any(target_os = "android", target_os = "linux"))]
 use std::os::fd::AsRawFd;
-use std::{io, net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::{net::UdpSocket, sync::oneshot};
 
+/// Cipher used unless the `cipher` parameter is set
 const SHADOWSOCKS_CIPHER: CipherKind = CipherKind::AES_256_GCM;
+/// Password used unless the `password` parameter is set
 const SHADOWSOCKS_PASSWORD: &str = "mullvad";
 
+/// How long the server keeps forwarding traffic for a client that it has not received any
+/// responses for from the WireGuard server.
+const SERVER_CLIENT_TIMEOUT: Duration = Duration::from_secs(180);
+
 type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -54,11 +76,122 @@ pub enum Error {
     /// Failed to receive remote socket descriptor
     #[error("Failed to receive remote socket descriptor")]
     ReceiveRemoteFd,
+    /// Failed to receive from Shadowsocks clients
+    #[error("Failed to receive from Shadowsocks clients")]
+    ReceiveShadowsocks(#[source] ProxySocketError),
+}
+
+/// Name of the protocol in the [`registry`]
+pub const NAME: &str = "shadowsocks";
+/// Parameter setting the cipher used by the server
+pub const CIPHER: &str = "cipher";
+/// Parameter setting the password used by the server
+pub const PASSWORD: &str = "password";
+/// Parameter setting the WireGuard endpoint that the server forwards traffic to
+pub const TARGET: &str = "target";
+
+/// Shadowsocks over UDP, forwarding traffic to a WireGuard server on the same host as the
+/// Shadowsocks server, or to the endpoint set by the `target` parameter.
+pub struct ShadowsocksProtocol;
+
+#[async_trait]
+impl registry::Protocol for ShadowsocksProtocol {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                name: CIPHER,
+                description: "AEAD cipher used by the server. Defaults to aes-256-gcm",
+                required: false,
+            },
+            Parameter {
+                name: PASSWORD,
+                description:
+                    "Password used by the server. Defaults to the password of Mullvad relays",
+                required: false,
+            },
+            Parameter {
+                name: TARGET,
+                description: "WireGuard endpoint that the server forwards traffic to. Defaults to \
+                    a WireGuard server on the same host as the Shadowsocks server",
+                required: false,
+            },
+        ]
+    }
+
+    fn transport(&self) -> Transport {
+        Transport::Udp
+    }
+
+    fn packet_overhead(&self, config: &registry::Config) -> u16 {
+        let cipher =
+            parse_cipher(&config.protocol, config.parameter(CIPHER)).unwrap_or(SHADOWSOCKS_CIPHER);
+        let target = parse_target(config).unwrap_or(config.local_wireguard_endpoint());
+        packet_overhead(cipher, target)
+    }
+
+    async fn create(&self, config: &registry::Config) -> crate::Result<Box<dyn Obfuscator>> {
+        let settings = Settings {
+            shadowsocks_endpoint: config.endpoint,
+            wireguard_endpoint: parse_target(config)?,
+            bind: config.local_bind_addr(),
+            cipher: parse_cipher(&config.protocol, config.parameter(CIPHER))?,
+            password: config
+                .parameter(PASSWORD)
+                .unwrap_or(SHADOWSOCKS_PASSWORD)
+                .to_owned(),
+            #[cfg(target_os = "linux")]
+            fwmark: config.fwmark,
+        };
+        Shadowsocks::new(&settings)
+            .await
+            .map(box_obfuscator)
+            .map_err(crate::Error::CreateShadowsocksObfuscator)
+    }
+
+    async fn serve(&self, config: &registry::ServerConfig) -> crate::Result<()> {
+        let cipher = parse_cipher(&config.protocol, config.parameter(CIPHER))?;
+        let password = config.parameter(PASSWORD).unwrap_or(SHADOWSOCKS_PASSWORD);
+        run_server(config.listen, config.forward, cipher, password)
+            .await
+            .map_err(crate::Error::RunShadowsocksServer)
+    }
+}
+
+/// Parse the `cipher` parameter, or return the cipher used by Mullvad relays if it is not set.
+/// Only AEAD ciphers are supported.
+fn parse_cipher(protocol: &str, cipher: Option<&str>) -> crate::Result<CipherKind> {
+    let Some(cipher) = cipher else {
+        return Ok(SHADOWSOCKS_CIPHER);
+    };
+    match cipher.parse::<CipherKind>() {
+        Ok(cipher) if cipher.is_aead() => Ok(cipher),
+        _ => Err(crate::Error::InvalidParameter {
+            protocol: protocol.to_owned(),
+            name: CIPHER,
+        }),
+    }
+}
+
+/// Parse the `target` parameter, or return the WireGuard endpoint of Mullvad relays if it is not
+/// set.
+fn parse_target(config: &registry::Config) -> crate::Result<SocketAddr> {
+    let Some(target) = config.parameter(TARGET) else {
+        return Ok(config.local_wireguard_endpoint());
+    };
+    target.parse().map_err(|_| crate::Error::InvalidParameter {
+        protocol: config.protocol.clone(),
+        name: TARGET,
+    })
 }
 
 pub struct Shadowsocks {
     udp_client_addr: SocketAddr,
     wireguard_endpoint: SocketAddr,
+    cipher: CipherKind,
     server: tokio::task::JoinHandle<Result<()>>,
     // The receiver will implicitly shut down when this is dropped
     _shutdown_tx: oneshot::Sender<()>,
@@ -72,14 +205,19 @@ pub struct Settings {
     pub shadowsocks_endpoint: SocketAddr,
     /// Remote WireGuard endpoint
     pub wireguard_endpoint: SocketAddr,
+    /// Local address to receive WireGuard traffic on
+    pub bind: SocketAddr,
+    /// Cipher used by the Shadowsocks server. Must be an AEAD cipher
+    pub cipher: CipherKind,
+    /// Password used by the Shadowsocks server
+    pub password: String,
     #[cfg(target_os = "linux")]
     pub fwmark: Option<u32>,
 }
 
 impl Shadowsocks {
     pub(crate) async fn new(settings: &Settings) -> Result<Self> {
-        let (local_udp_socket, udp_client_addr) =
-            create_local_udp_socket(settings.shadowsocks_endpoint.is_ipv4()).await?;
+        let (local_udp_socket, udp_client_addr) = create_local_udp_socket(settings.bind).await?;
 
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
@@ -93,8 +231,15 @@ impl Shadowsocks {
         #[cfg(target_os = "android")]
         let outbound_fd = remote_socket.as_raw_fd();
 
+        let ss_config = ServerConfig::new(
+            settings.shadowsocks_endpoint,
+            settings.password.clone(),
+            settings.cipher,
+        );
+
         let server = tokio::spawn(run_forwarding(
             settings.shadowsocks_endpoint,
+            ss_config,
             remote_socket,
             local_udp_socket,
             settings.wireguard_endpoint,
@@ -104,6 +249,7 @@ impl Shadowsocks {
         Ok(Shadowsocks {
             udp_client_addr,
             wireguard_endpoint: settings.wireguard_endpoint,
+            cipher: settings.cipher,
             server,
             _shutdown_tx: shutdown_tx,
             #[cfg(target_os = "android")]
@@ -114,6 +260,7 @@ impl Shadowsocks {
 
 async fn run_forwarding(
     shadowsocks_endpoint: SocketAddr,
+    ss_config: ServerConfig,
     remote_socket: UdpSocket,
     local_udp_socket: UdpSocket,
     wireguard_endpoint: SocketAddr,
@@ -123,7 +270,7 @@ async fn run_forwarding(
         .await
         .map_err(Error::WaitForUdpClient)?;
 
-    let shadowsocks = connect_shadowsocks(remote_socket, shadowsocks_endpoint);
+    let shadowsocks = connect_shadowsocks(remote_socket, &ss_config);
     let shadowsocks = Arc::new(shadowsocks);
 
     let local_udp = Arc::new(local_udp_socket);
@@ -157,14 +304,9 @@ async fn run_forwarding(
     Ok(())
 }
 
-fn connect_shadowsocks(remote_socket: UdpSocket, shadowsocks_endpoint: SocketAddr) -> ProxySocket {
+fn connect_shadowsocks(remote_socket: UdpSocket, ss_config: &ServerConfig) -> ProxySocket {
     let ss_context = Context::new_shared(ServerType::Local);
-    let ss_config: ServerConfig = ServerConfig::new(
-        shadowsocks_endpoint,
-        SHADOWSOCKS_PASSWORD,
-        SHADOWSOCKS_CIPHER,
-    );
-    ProxySocket::from_socket(UdpSocketType::Client, ss_context, &ss_config, remote_socket)
+    ProxySocket::from_socket(UdpSocketType::Client, ss_context, ss_config, remote_socket)
 }
 
 async fn create_shadowsocks_socket(
@@ -187,15 +329,8 @@ async fn create_shadowsocks_socket(
     Ok(socket)
 }
 
-async fn create_local_udp_socket(ipv4: bool) -> Result<(UdpSocket, SocketAddr)> {
-    let random_bind_addr = if ipv4 {
-        SocketAddr::new("127.0.0.1".parse().unwrap(), 0)
-    } else {
-        SocketAddr::new("::1".parse().unwrap(), 0)
-    };
-    let local_udp_socket = UdpSocket::bind(random_bind_addr)
-        .await
-        .map_err(Error::BindUdp)?;
+async fn create_local_udp_socket(bind_addr: SocketAddr) -> Result<(UdpSocket, SocketAddr)> {
+    let local_udp_socket = UdpSocket::bind(bind_addr).await.map_err(Error::BindUdp)?;
     let udp_client_addr = local_udp_socket
         .local_addr()
         .map_err(Error::GetUdpLocalAddress)?;
@@ -277,6 +412,137 @@ async fn handle_incoming(
     }
 }
 
+async fn run_server(
+    listen: SocketAddr,
+    forward: SocketAddr,
+    cipher: CipherKind,
+    password: &str,
+) -> Result<()> {
+    let socket = UdpSocket::bind(listen).await.map_err(Error::BindUdp)?;
+    log::info!("Running Shadowsocks server on {listen}, forwarding to {forward}");
+    serve_socket(socket, forward, cipher, password).await
+}
+
+/// Receive Shadowsocks datagrams on `socket` and forward their payloads to `forward`. Each client
+/// is given its own forwarding socket, so that responses can be sent back to the right client.
+async fn serve_socket(
+    socket: UdpSocket,
+    forward: SocketAddr,
+    cipher: CipherKind,
+    password: &str,
+) -> Result<()> {
+    let listen = socket.local_addr().map_err(Error::GetUdpLocalAddress)?;
+    let ss_context = Context::new_shared(ServerType::Server);
+    let ss_config = ServerConfig::new(listen, password, cipher);
+    let shadowsocks = Arc::new(ProxySocket::from_socket(
+        UdpSocketType::Server,
+        ss_context,
+        &ss_config,
+        socket,
+    ));
+    let clients: Arc<Mutex<HashMap<SocketAddr, Arc<UdpSocket>>>> = Default::default();
+
+    let mut rx_buffer = vec![0u8; u16::MAX as usize];
+
+    loop {
+        let (read_n, client_addr, target_addr, _ctrl) =
+            match shadowsocks.recv_from(&mut rx_buffer).await {
+                Ok(received) => received,
+                Err(error) if is_fatal_socket_error(&error) => {
+                    return Err(Error::ReceiveShadowsocks(error));
+                }
+                Err(error) => {
+                    log::trace!("Failed to read from Shadowsocks client: {error}");
+                    continue;
+                }
+            };
+
+        let existing_socket = clients.lock().unwrap().get(&client_addr).cloned();
+        let forward_socket = match existing_socket {
+            Some(forward_socket) => forward_socket,
+            None => {
+                log::debug!("New Shadowsocks client: {client_addr}");
+                let forward_socket = match create_forward_socket(forward).await {
+                    Ok(forward_socket) => Arc::new(forward_socket),
+                    Err(error) => {
+                        log::error!("Failed to create forwarding socket: {error}");
+                        continue;
+                    }
+                };
+                clients
+                    .lock()
+                    .unwrap()
+                    .insert(client_addr, forward_socket.clone());
+                tokio::spawn(handle_server_responses(
+                    shadowsocks.clone(),
+                    forward_socket.clone(),
+                    client_addr,
+                    target_addr,
+                    clients.clone(),
+                ));
+                forward_socket
+            }
+        };
+
+        if let Err(error) = forward_socket.send(&rx_buffer[0..read_n]).await {
+            log::trace!("Failed to forward datagram from {client_addr}: {error}");
+        }
+    }
+}
+
+async fn create_forward_socket(forward: SocketAddr) -> io::Result<UdpSocket> {
+    let random_bind_addr = if forward.is_ipv4() {
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)
+    } else {
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
+    };
+    let socket = UdpSocket::bind(random_bind_addr).await?;
+    socket.connect(forward).await?;
+    Ok(socket)
+}
+
+/// Send responses received on `forward_socket` to `client_addr`, until no responses have been
+/// received for [`SERVER_CLIENT_TIMEOUT`]. Responses appear to come from `target_addr`, the
+/// address that the client asked to reach.
+async fn handle_server_responses(
+    ss_write: Arc<ProxySocket>,
+    forward_socket: Arc<UdpSocket>,
+    client_addr: SocketAddr,
+    target_addr: Address,
+    clients: Arc<Mutex<HashMap<SocketAddr, Arc<UdpSocket>>>>,
+) {
+    let mut rx_buffer = vec![0u8; u16::MAX as usize];
+
+    loop {
+        let read_n =
+            match tokio::time::timeout(SERVER_CLIENT_TIMEOUT, forward_socket.recv(&mut rx_buffer))
+                .await
+            {
+                Ok(Ok(read_n)) => read_n,
+                Ok(Err(error)) => {
+                    log::trace!("Failed to read from forwarding socket: {error}");
+                    if is_fatal_socket_io_error(&error) {
+                        break;
+                    }
+                    continue;
+                }
+                Err(_elapsed) => {
+                    log::debug!("Shadowsocks client timed out: {client_addr}");
+                    break;
+                }
+            };
+
+        if let Err(error) = ss_write
+            .send_to(client_addr, &target_addr, &rx_buffer[0..read_n])
+            .await
+        {
+            log::trace!("Failed to write to Shadowsocks client {client_addr}: {error}");
+        }
+    }
+
+    clients.lock().unwrap().remove(&client_addr);
+}
+
 #[async_trait]
 impl Obfuscator for Shadowsocks {
     fn endpoint(&self) -> SocketAddr {
@@ -297,17 +563,20 @@ impl Obfuscator for Shadowsocks {
     }
 
     fn packet_overhead(&self) -> u16 {
-        // This math relies on the packet structure of Shadowsocks AEAD UDP packets.
-        // https://shadowsocks.org/doc/aead.html
-        // Those packets look like this: [salt][address][payload][tag]
-        debug_assert!(SHADOWSOCKS_CIPHER.is_aead());
+        packet_overhead(self.cipher, self.wireguard_endpoint)
+    }
+}
 
-        let overhead = SHADOWSOCKS_CIPHER.salt_len()
-            + Address::from(self.wireguard_endpoint).serialized_len()
-            + SHADOWSOCKS_CIPHER.tag_len();
+fn packet_overhead(cipher: CipherKind, wireguard_endpoint: SocketAddr) -> u16 {
+    // This math relies on the packet structure of Shadowsocks AEAD UDP packets.
+    // https://shadowsocks.org/doc/aead.html
+    // Those packets look like this: [salt][address][payload][tag]
+    debug_assert!(cipher.is_aead());
 
-        u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
-    }
+    let overhead =
+        cipher.salt_len() + Address::from(wireguard_endpoint).serialized_len() + cipher.tag_len();
+
+    u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
 }
 
 /// Return whether retrying is a lost cause
@@ -325,3 +594,73 @@ fn is_fatal_socket_io_error(error: &io::Error) -> bool {
             | io::ErrorKind::BrokenPipe
     )
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Send packets through the obfuscator and a local Shadowsocks server to a UDP echo server
+    #[tokio::test]
+    async fn test_shadowsocks_loopback() {
+        let echo_server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo_server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 2048];
+            while let Ok((n, addr)) = echo_server.recv_from(&mut buf).await {
+                let _ = echo_server.send_to(&buf[..n], addr).await;
+            }
+        });
+
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        tokio::spawn(serve_socket(
+            server_socket,
+            echo_addr,
+            CipherKind::CHACHA20_POLY1305,
+            "secret",
+        ));
+
+        let settings = Settings {
+            shadowsocks_endpoint: server_addr,
+            wireguard_endpoint: echo_addr,
+            bind: "127.0.0.1:0".parse().unwrap(),
+            cipher: CipherKind::CHACHA20_POLY1305,
+            password: "secret".to_owned(),
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        };
+        let obfuscator = Box::new(Shadowsocks::new(&settings).await.unwrap());
+        let local_endpoint = obfuscator.endpoint();
+        tokio::spawn(obfuscator.run());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(local_endpoint).await.unwrap();
+
+        let mut buf = [0u8; 2048];
+        for packet in [&b"first"[..], &[0xab; 1000][..]] {
+            client.send(packet).await.unwrap();
+            let n = tokio::time::timeout(Duration::from_secs(5), client.recv(&mut buf))
+                .await
+                .expect("timed out waiting for echo")
+                .unwrap();
+            assert_eq!(&buf[..n], packet);
+        }
+    }
+
+    #[test]
+    fn test_parse_cipher() {
+        assert_eq!(
+            parse_cipher("shadowsocks", None).unwrap(),
+            SHADOWSOCKS_CIPHER
+        );
+        assert_eq!(
+            parse_cipher("shadowsocks", Some("chacha20-ietf-poly1305")).unwrap(),
+            CipherKind::CHACHA20_POLY1305
+        );
+        assert!(matches!(
+            parse_cipher("shadowsocks", Some("rc4-md5")),
+            Err(crate::Error::InvalidParameter { name: CIPHER, .. })
+        ));
+        assert!(parse_cipher("shadowsocks", Some("invalid")).is_err());
+    }
+}
@@ -1,7 +1,12 @@
-use crate::Obfuscator;
+use crate::{
+    box_obfuscator,
+    registry::{self, Parameter, Transport},
+    Obfuscator,
+};
 use async_trait::async_trait;
 use std::net::SocketAddr;
 use udp_over_tcp::{
+    tcp2udp,
     udp2tcp::{self, Udp2Tcp as Udp2TcpImpl},
     TcpOptions,
 };
@@ -9,6 +14,8 @@ use udp_over_tcp::{
 #[derive(Debug)]
 pub struct Settings {
     pub peer: SocketAddr,
+    /// Local address to receive WireGuard traffic on
+    pub bind: SocketAddr,
     #[cfg(target_os = "linux")]
     pub fwmark: Option<u32>,
 }
@@ -28,6 +35,54 @@ pub enum Error {
     /// Failed to run obfuscator
     #[error("Failed to run obfuscator")]
     RunObfuscator(#[source] udp2tcp::Error),
+
+    /// Failed to run server
+    #[error("Failed to run server")]
+    RunServer(#[source] tcp2udp::Tcp2UdpError),
+}
+
+/// Name of the protocol in the [`registry`]
+pub const NAME: &str = "udp2tcp";
+
+/// Sends UDP datagrams over a TCP connection, using the protocol from the `udp-over-tcp` crate.
+pub struct Udp2TcpProtocol;
+
+#[async_trait]
+impl registry::Protocol for Udp2TcpProtocol {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[]
+    }
+
+    fn transport(&self) -> Transport {
+        Transport::Tcp
+    }
+
+    fn packet_overhead(&self, _config: &registry::Config) -> u16 {
+        packet_overhead()
+    }
+
+    async fn create(&self, config: &registry::Config) -> crate::Result<Box<dyn Obfuscator>> {
+        let settings = Settings {
+            peer: config.endpoint,
+            bind: config.local_bind_addr(),
+            #[cfg(target_os = "linux")]
+            fwmark: config.fwmark,
+        };
+        Udp2Tcp::new(&settings)
+            .await
+            .map(box_obfuscator)
+            .map_err(crate::Error::CreateUdp2TcpObfuscator)
+    }
+
+    async fn serve(&self, config: &registry::ServerConfig) -> crate::Result<()> {
+        run_server(config.listen, config.forward)
+            .await
+            .map_err(crate::Error::RunUdp2TcpServer)
+    }
 }
 
 pub struct Udp2Tcp {
@@ -37,14 +92,8 @@ pub struct Udp2Tcp {
 
 impl Udp2Tcp {
     pub(crate) async fn new(settings: &Settings) -> Result<Self> {
-        let listen_addr = if settings.peer.is_ipv4() {
-            SocketAddr::new("127.0.0.1".parse().unwrap(), 0)
-        } else {
-            SocketAddr::new("::1".parse().unwrap(), 0)
-        };
-
         let instance = Udp2TcpImpl::new(
-            listen_addr,
+            settings.bind,
             settings.peer,
             TcpOptions {
                 #[cfg(target_os = "linux")]
@@ -87,14 +136,37 @@ impl Obfuscator for Udp2Tcp {
     }
 
     fn packet_overhead(&self) -> u16 {
-        let max_tcp_header_len = 60; // https://datatracker.ietf.org/doc/html/rfc9293#section-3.1-6.22.1
-        let udp_header_len = 8; // https://datatracker.ietf.org/doc/html/rfc768
+        packet_overhead()
+    }
+}
 
-        // TODO: Make `HEADER_LEN` constant public in udp-over-tcp lib and use it instead
-        let udp_over_tcp_header_len = size_of::<u16>();
+/// Accept udp2tcp connections on `listen`, and forward the datagrams received on them to
+/// `forward` over UDP.
+async fn run_server(listen: SocketAddr, forward: SocketAddr) -> Result<()> {
+    let options = tcp2udp::Options {
+        tcp_listen_addrs: vec![listen],
+        udp_forward_addr: forward,
+        udp_bind_ip: None,
+        tcp_options: TcpOptions {
+            nodelay: true,
+            ..TcpOptions::default()
+        },
+    };
+    log::info!("Running udp2tcp server on {listen}, forwarding to {forward}");
+    match tcp2udp::run(options).await {
+        Ok(never) => match never {},
+        Err(error) => Err(Error::RunServer(error)),
+    }
+}
 
-        let overhead = max_tcp_header_len - udp_header_len + udp_over_tcp_header_len;
+fn packet_overhead() -> u16 {
+    let max_tcp_header_len = 60; // https://datatracker.ietf.org/doc/html/rfc9293#section-3.1-6.22.1
+    let udp_header_len = 8; // https://datatracker.ietf.org/doc/html/rfc768
 
-        u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
-    }
+    // TODO: Make `HEADER_LEN` constant public in udp-over-tcp lib and use it instead
+    let udp_over_tcp_header_len = size_of::<u16>();
+
+    let overhead = max_tcp_header_len - udp_header_len + udp_over_tcp_header_len;
+
+    u16::try_from(overhead).expect("packet overhead is less than u16::MAX")
 }
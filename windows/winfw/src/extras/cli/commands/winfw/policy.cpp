@@ -13,7 +13,7 @@ namespace detail
 
 WinFwSettings CreateSettings(const std::wstring &dhcp, const std::wstring &lan)
 {
-	WinFwSettings s;
+	WinFwSettings s{};
 
 	s.permitDhcp = (0 == _wcsicmp(dhcp.c_str(), L"yes"));
 	s.permitLan = (0 == _wcsicmp(lan.c_str(), L"yes"));
@@ -15,6 +15,8 @@
 #include "rules/baseline/permitvpntunnelservice.h"
 #include "rules/baseline/permitdns.h"
 #include "rules/baseline/permitendpoint.h"
+#include "rules/baseline/permitallowednetworks.h"
+#include "rules/baseline/permitexcludeddestinations.h"
 #include "rules/dns/blockall.h"
 #include "rules/dns/permitloopback.h"
 #include "rules/dns/permittunnel.h"
@@ -52,6 +54,13 @@ void AppendSettingsRules
 	const WinFwSettings &settings
 )
 {
+	if ((nullptr == settings.lanNetworks && 0 != settings.numLanNetworks)
+		|| (nullptr == settings.lanInboundPorts && 0 != settings.numLanInboundPorts)
+		|| (nullptr == settings.allowedNetworks && 0 != settings.numAllowedNetworks))
+	{
+		THROW_ERROR("Invalid argument: settings");
+	}
+
 	if (settings.permitDhcp)
 	{
 		ruleset.emplace_back(std::make_unique<baseline::PermitDhcp>());
@@ -60,11 +69,21 @@ void AppendSettingsRules
 
 	if (settings.permitLan)
 	{
-		ruleset.emplace_back(std::make_unique<baseline::PermitLan>());
-		ruleset.emplace_back(std::make_unique<baseline::PermitLanService>());
+		const std::vector<WinFwIpNetwork> lanNetworks(settings.lanNetworks, settings.lanNetworks + settings.numLanNetworks);
+		const std::vector<uint16_t> inboundPorts(settings.lanInboundPorts, settings.lanInboundPorts + settings.numLanInboundPorts);
+
+		ruleset.emplace_back(std::make_unique<baseline::PermitLan>(lanNetworks));
+		ruleset.emplace_back(std::make_unique<baseline::PermitLanService>(lanNetworks, inboundPorts));
 		ruleset.emplace_back(baseline::PermitDhcpServer::WithExtent(baseline::PermitDhcpServer::Extent::IPv4Only));
 	}
 
+	if (0 != settings.numAllowedNetworks)
+	{
+		const std::vector<WinFwIpNetwork> allowedNetworks(settings.allowedNetworks, settings.allowedNetworks + settings.numAllowedNetworks);
+
+		ruleset.emplace_back(std::make_unique<baseline::PermitAllowedNetworks>(allowedNetworks));
+	}
+
 	//
 	// DNS management
 	//
@@ -123,6 +142,20 @@ void AppendAllowedEndpointRules
 	));
 }
 
+void AppendExcludedDestinationRules
+(
+	FwContext::Ruleset &ruleset,
+	const std::vector<WinFwIpNetwork> &excludedDestinations
+)
+{
+	if (excludedDestinations.empty())
+	{
+		return;
+	}
+
+	ruleset.emplace_back(std::make_unique<baseline::PermitExcludedDestinations>(excludedDestinations));
+}
+
 void AppendNetBlockedRules(FwContext::Ruleset &ruleset)
 {
 	ruleset.emplace_back(std::make_unique<baseline::BlockAll>());
@@ -188,7 +221,8 @@ bool FwContext::applyPolicyConnecting
 	const std::vector<std::wstring> &relayClients,
 	const std::optional<std::wstring> &tunnelInterfaceAlias,
 	const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
-	const WinFwAllowedTunnelTraffic &allowedTunnelTraffic
+	const WinFwAllowedTunnelTraffic &allowedTunnelTraffic,
+	const std::vector<WinFwIpNetwork> &excludedDestinations
 )
 {
 	Ruleset ruleset;
@@ -196,6 +230,7 @@ bool FwContext::applyPolicyConnecting
 	AppendNetBlockedRules(ruleset);
 	AppendSettingsRules(ruleset, settings);
 	AppendRelayRules(ruleset, relay, relayClients);
+	AppendExcludedDestinationRules(ruleset, excludedDestinations);
 
 	if (allowedEndpoint.has_value())
 	{
@@ -283,7 +318,8 @@ bool FwContext::applyPolicyConnected
 	const std::vector<std::wstring> &relayClient,
 	const std::wstring &tunnelInterfaceAlias,
 	const std::vector<wfp::IpAddress> &tunnelDnsServers,
-	const std::vector<wfp::IpAddress> &nonTunnelDnsServers
+	const std::vector<wfp::IpAddress> &nonTunnelDnsServers,
+	const std::vector<WinFwIpNetwork> &excludedDestinations
 )
 {
 	Ruleset ruleset;
@@ -291,6 +327,7 @@ bool FwContext::applyPolicyConnected
 	AppendNetBlockedRules(ruleset);
 	AppendSettingsRules(ruleset, settings);
 	AppendRelayRules(ruleset, relay, relayClient);
+	AppendExcludedDestinationRules(ruleset, excludedDestinations);
 
 	if (!tunnelDnsServers.empty())
 	{
@@ -31,7 +31,8 @@ public:
 		const std::vector<std::wstring> &relayClients,
 		const std::optional<std::wstring> &tunnelInterfaceAlias,
 		const std::optional<WinFwAllowedEndpoint> &allowedEndpoint,
-		const WinFwAllowedTunnelTraffic &allowedTunnelTraffic
+		const WinFwAllowedTunnelTraffic &allowedTunnelTraffic,
+		const std::vector<WinFwIpNetwork> &excludedDestinations
 	);
 
 	bool applyPolicyConnected
@@ -41,7 +42,8 @@ public:
 		const std::vector<std::wstring> &relayClients,
 		const std::wstring &tunnelInterfaceAlias,
 		const std::vector<wfp::IpAddress> &tunnelDnsServers,
-		const std::vector<wfp::IpAddress> &nonTunnelDnsServers
+		const std::vector<wfp::IpAddress> &nonTunnelDnsServers,
+		const std::vector<WinFwIpNetwork> &excludedDestinations
 	);
 
 	bool applyPolicyBlocked(
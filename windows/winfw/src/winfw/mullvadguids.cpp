@@ -118,6 +118,12 @@ MullvadGuids::DetailedIdentityRegistry MullvadGuids::DetailedRegistry(IdentityQu
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLan_Outbound_Multicast_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLanService_Inbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLanService_Inbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv4()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv6()));
+	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv6()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Outbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Inbound_Ipv4()));
 	registry.insert(std::make_pair(WfpObjectType::Filter, Filter_Baseline_PermitLoopback_Outbound_Ipv6()));
@@ -498,6 +504,90 @@ const GUID &MullvadGuids::Filter_Baseline_PermitLanService_Inbound_Ipv6()
 	return g;
 }
 
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0xaa8a21cb,
+		0x4b89,
+		0x44c2,
+		{ 0x9d, 0x6b, 0x1f, 0xb2, 0x84, 0xb2, 0x53, 0xe9 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xa0826e60,
+		0x7657,
+		0x449f,
+		{ 0xb5, 0xa2, 0xe2, 0xfc, 0xb5, 0xf7, 0x4c, 0xaa }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0x826aed2b,
+		0x2df0,
+		0x4f2c,
+		{ 0xa6, 0x8d, 0xd4, 0x84, 0x4c, 0x18, 0xeb, 0xfb }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv4()
+{
+	static const GUID g =
+	{
+		0xfef2885f,
+		0x8ca9,
+		0x4277,
+		{ 0xa6, 0x05, 0x88, 0x6a, 0x5e, 0xcc, 0xcd, 0x15 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xa65ff4c9,
+		0xb5a7,
+		0x4486,
+		{ 0x95, 0x3e, 0x7f, 0x14, 0xd5, 0x65, 0xb5, 0x72 }
+	};
+
+	return g;
+}
+
+//static
+const GUID &MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv6()
+{
+	static const GUID g =
+	{
+		0xdbf485bc,
+		0x5b1a,
+		0x40b3,
+		{ 0xa6, 0x03, 0xea, 0xa2, 0xcf, 0x01, 0xa3, 0x36 }
+	};
+
+	return g;
+}
+
 //static
 const GUID &MullvadGuids::Filter_Baseline_PermitLoopback_Outbound_Ipv4()
 {
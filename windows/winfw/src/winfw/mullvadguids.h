@@ -54,6 +54,14 @@ public:
 	static const GUID &Filter_Baseline_PermitLanService_Inbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLanService_Inbound_Ipv6();
 
+	static const GUID &Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv6();
+
+	static const GUID &Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv4();
+	static const GUID &Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv6();
+	static const GUID &Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv6();
+
 	static const GUID &Filter_Baseline_PermitLoopback_Outbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLoopback_Inbound_Ipv4();
 	static const GUID &Filter_Baseline_PermitLoopback_Outbound_Ipv6();
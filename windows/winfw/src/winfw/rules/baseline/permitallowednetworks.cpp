@@ -0,0 +1,131 @@
+#include "stdafx.h"
+#include "permitallowednetworks.h"
+#include <winfw/mullvadguids.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+PermitAllowedNetworks::PermitAllowedNetworks(const std::vector<WinFwIpNetwork> &networks)
+{
+	SplitNetworks(networks, m_networksIpv4, m_networksIpv6);
+}
+
+bool PermitAllowedNetworks::apply(IObjectInstaller &objectInstaller)
+{
+	//
+	// A filter without conditions matches everything, so filters are only added for address
+	// families that have allowed networks.
+	//
+
+	return (m_networksIpv4.empty() || applyIpv4(objectInstaller))
+		&& (m_networksIpv6.empty() || applyIpv6(objectInstaller));
+}
+
+bool PermitAllowedNetworks::applyIpv4(IObjectInstaller &objectInstaller) const
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound connections to allowed networks.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv4())
+		.name(L"Permit outbound connections to allowed networks (IPv4)")
+		.description(L"This filter is part of a rule that permits traffic to and from allowed networks")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		for (const auto &network : m_networksIpv4)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #2 Permit inbound connections from allowed networks.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv4())
+		.name(L"Permit inbound connections from allowed networks (IPv4)")
+		.layer(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
+
+	for (const auto &network : m_networksIpv4)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+bool PermitAllowedNetworks::applyIpv6(IObjectInstaller &objectInstaller) const
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound connections to allowed networks.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Outbound_Ipv6())
+		.name(L"Permit outbound connections to allowed networks (IPv6)")
+		.description(L"This filter is part of a rule that permits traffic to and from allowed networks")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	{
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+		for (const auto &network : m_networksIpv6)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	//
+	// #2 Permit inbound connections from allowed networks.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitAllowedNetworks_Inbound_Ipv6())
+		.name(L"Permit inbound connections from allowed networks (IPv6)")
+		.layer(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
+
+	for (const auto &network : m_networksIpv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
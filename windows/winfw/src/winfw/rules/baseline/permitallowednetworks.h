@@ -0,0 +1,29 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <winfw/rules/shared.h>
+#include <winfw/winfw.h>
+#include <vector>
+
+namespace rules::baseline
+{
+
+class PermitAllowedNetworks : public IFirewallRule
+{
+public:
+
+	PermitAllowedNetworks(const std::vector<WinFwIpNetwork> &networks);
+	~PermitAllowedNetworks() = default;
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	bool applyIpv4(IObjectInstaller &objectInstaller) const;
+	bool applyIpv6(IObjectInstaller &objectInstaller) const;
+
+	NetworkSet m_networksIpv4;
+	NetworkSet m_networksIpv6;
+};
+
+}
@@ -0,0 +1,85 @@
+#include "stdafx.h"
+#include "permitexcludeddestinations.h"
+#include <winfw/mullvadguids.h>
+#include <winfw/rules/shared.h>
+#include <libwfp/filterbuilder.h>
+#include <libwfp/conditionbuilder.h>
+#include <libwfp/ipaddress.h>
+#include <libwfp/conditions/conditionip.h>
+
+using namespace wfp::conditions;
+
+namespace rules::baseline
+{
+
+PermitExcludedDestinations::PermitExcludedDestinations(const std::vector<WinFwIpNetwork> &networks)
+{
+	SplitNetworks(networks, m_networksIpv4, m_networksIpv6);
+}
+
+bool PermitExcludedDestinations::apply(IObjectInstaller &objectInstaller)
+{
+	wfp::FilterBuilder filterBuilder;
+
+	//
+	// #1 Permit outbound connections to excluded destinations, IPv4.
+	//
+	// A filter without conditions matches everything, so filters are only added for address
+	// families that have excluded destinations.
+	//
+
+	if (false == m_networksIpv4.empty())
+	{
+		filterBuilder
+			.key(MullvadGuids::Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv4())
+			.name(L"Permit outbound connections to excluded destinations (IPv4)")
+			.description(L"This filter is part of a rule that permits traffic to destinations outside the tunnel")
+			.provider(MullvadGuids::Provider())
+			.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V4)
+			.sublayer(MullvadGuids::SublayerBaseline())
+			.weight(wfp::FilterBuilder::WeightClass::Medium)
+			.permit();
+
+		wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
+
+		for (const auto &network : m_networksIpv4)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (false == objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
+	}
+
+	if (m_networksIpv6.empty())
+	{
+		return true;
+	}
+
+	//
+	// #2 Permit outbound connections to excluded destinations, IPv6.
+	//
+
+	filterBuilder
+		.key(MullvadGuids::Filter_Baseline_PermitExcludedDestinations_Outbound_Ipv6())
+		.name(L"Permit outbound connections to excluded destinations (IPv6)")
+		.description(L"This filter is part of a rule that permits traffic to destinations outside the tunnel")
+		.provider(MullvadGuids::Provider())
+		.layer(FWPM_LAYER_ALE_AUTH_CONNECT_V6)
+		.sublayer(MullvadGuids::SublayerBaseline())
+		.weight(wfp::FilterBuilder::WeightClass::Medium)
+		.permit();
+
+	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
+
+	for (const auto &network : m_networksIpv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
+
+	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
+}
+
+}
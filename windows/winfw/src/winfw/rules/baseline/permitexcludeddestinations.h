@@ -0,0 +1,26 @@
+#pragma once
+
+#include <winfw/rules/ifirewallrule.h>
+#include <winfw/rules/shared.h>
+#include <winfw/winfw.h>
+#include <vector>
+
+namespace rules::baseline
+{
+
+class PermitExcludedDestinations : public IFirewallRule
+{
+public:
+
+	PermitExcludedDestinations(const std::vector<WinFwIpNetwork> &networks);
+	~PermitExcludedDestinations() = default;
+
+	bool apply(IObjectInstaller &objectInstaller) override;
+
+private:
+
+	NetworkSet m_networksIpv4;
+	NetworkSet m_networksIpv6;
+};
+
+}
@@ -12,6 +12,29 @@ using namespace wfp::conditions;
 namespace rules::baseline
 {
 
+PermitLan::PermitLan(const std::vector<WinFwIpNetwork> &networks)
+{
+	if (false == networks.empty())
+	{
+		SplitNetworks(networks, m_networksIpv4, m_networksIpv6);
+		return;
+	}
+
+	m_networksIpv4 =
+	{
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 10, 0, 0, 0 }), 8),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 172, 16, 0, 0 }), 12),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 192, 168, 0, 0 }), 16),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 169, 254, 0, 0 }), 16),
+	};
+
+	m_networksIpv6 =
+	{
+		wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFE80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 10),
+		wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFC00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 7),
+	};
+}
+
 bool PermitLan::apply(IObjectInstaller &objectInstaller)
 {
 	return applyIpv4(objectInstaller) && applyIpv6(objectInstaller);
@@ -37,14 +60,22 @@ bool PermitLan::applyIpv4(IObjectInstaller &objectInstaller) const
 
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V4);
 
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 10, 0, 0, 0 }), 8)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 172, 16, 0, 0 }), 12)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 192, 168, 0, 0 }), 16)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 169, 254, 0, 0 }), 16)));
+	//
+	// A filter without conditions matches everything, so the filter is skipped if only IPv6
+	// networks are permitted.
+	//
 
-	if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+	if (false == m_networksIpv4.empty())
 	{
-		return false;
+		for (const auto &network : m_networksIpv4)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
 	}
 
 	//
@@ -89,15 +120,17 @@ bool PermitLan::applyIpv6(IObjectInstaller &objectInstaller) const
 
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_CONNECT_V6);
 
-	const wfp::IpNetwork linkLocal(wfp::IpAddress::Literal6({ 0xFE80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 10);
-	const wfp::IpNetwork uniqueLocal(wfp::IpAddress::Literal6({ 0xFC00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 7);
-
-	conditionBuilder.add_condition(ConditionIp::Remote(linkLocal));
-	conditionBuilder.add_condition(ConditionIp::Remote(uniqueLocal));
-
-	if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+	if (false == m_networksIpv6.empty())
 	{
-		return false;
+		for (const auto &network : m_networksIpv6)
+		{
+			conditionBuilder.add_condition(ConditionIp::Remote(network));
+		}
+
+		if (!objectInstaller.addFilter(filterBuilder, conditionBuilder))
+		{
+			return false;
+		}
 	}
 
 	//
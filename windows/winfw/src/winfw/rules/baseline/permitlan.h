@@ -1,6 +1,9 @@
 #pragma once
 
 #include <winfw/rules/ifirewallrule.h>
+#include <winfw/rules/shared.h>
+#include <winfw/winfw.h>
+#include <vector>
 
 namespace rules::baseline
 {
@@ -9,7 +12,8 @@ class PermitLan : public IFirewallRule
 {
 public:
 
-	PermitLan() = default;
+	// Permits the given private networks, or all private address ranges if `networks` is empty.
+	PermitLan(const std::vector<WinFwIpNetwork> &networks);
 	~PermitLan() = default;
 	
 	bool apply(IObjectInstaller &objectInstaller) override;
@@ -18,6 +22,9 @@ private:
 
 	bool applyIpv4(IObjectInstaller &objectInstaller) const;
 	bool applyIpv6(IObjectInstaller &objectInstaller) const;
+
+	NetworkSet m_networksIpv4;
+	NetworkSet m_networksIpv6;
 };
 
 }
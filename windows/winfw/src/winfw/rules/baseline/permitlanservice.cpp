@@ -6,12 +6,37 @@
 #include <libwfp/ipaddress.h>
 #include <libwfp/ipnetwork.h>
 #include <libwfp/conditions/conditionip.h>
+#include <libwfp/conditions/conditionport.h>
 
 using namespace wfp::conditions;
 
 namespace rules::baseline
 {
 
+PermitLanService::PermitLanService(const std::vector<WinFwIpNetwork> &networks, const std::vector<uint16_t> &ports)
+	: m_ports(ports)
+{
+	if (false == networks.empty())
+	{
+		SplitNetworks(networks, m_networksIpv4, m_networksIpv6);
+		return;
+	}
+
+	m_networksIpv4 =
+	{
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 10, 0, 0, 0 }), 8),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 172, 16, 0, 0 }), 12),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 192, 168, 0, 0 }), 16),
+		wfp::IpNetwork(wfp::IpAddress::Literal({ 169, 254, 0, 0 }), 16),
+	};
+
+	m_networksIpv6 =
+	{
+		wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFE80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 10),
+		wfp::IpNetwork(wfp::IpAddress::Literal6({ 0xFC00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 7),
+	};
+}
+
 bool PermitLanService::apply(IObjectInstaller &objectInstaller)
 {
 	return applyIpv4(objectInstaller) && applyIpv6(objectInstaller);
@@ -37,10 +62,25 @@ bool PermitLanService::applyIpv4(IObjectInstaller &objectInstaller) const
 
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4);
 
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 10, 0, 0, 0 }), 8)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 172, 16, 0, 0 }), 12)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 192, 168, 0, 0 }), 16)));
-	conditionBuilder.add_condition(ConditionIp::Remote(wfp::IpNetwork(wfp::IpAddress::Literal({ 169, 254, 0, 0 }), 16)));
+	//
+	// A filter without conditions matches everything, so the filter is skipped if only IPv6
+	// networks are permitted.
+	//
+
+	if (m_networksIpv4.empty())
+	{
+		return true;
+	}
+
+	for (const auto &network : m_networksIpv4)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
+
+	for (const auto port : m_ports)
+	{
+		conditionBuilder.add_condition(ConditionPort::Local(port));
+	}
 
 	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
 }
@@ -65,11 +105,20 @@ bool PermitLanService::applyIpv6(IObjectInstaller &objectInstaller) const
 
 	wfp::ConditionBuilder conditionBuilder(FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6);
 
-	const wfp::IpNetwork linkLocal(wfp::IpAddress::Literal6{ 0xFE80, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }, 10);
-	const wfp::IpNetwork uniqueLocal(wfp::IpAddress::Literal6({ 0xFC00, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0 }), 7);
+	if (m_networksIpv6.empty())
+	{
+		return true;
+	}
+
+	for (const auto &network : m_networksIpv6)
+	{
+		conditionBuilder.add_condition(ConditionIp::Remote(network));
+	}
 
-	conditionBuilder.add_condition(ConditionIp::Remote(linkLocal));
-	conditionBuilder.add_condition(ConditionIp::Remote(uniqueLocal));
+	for (const auto port : m_ports)
+	{
+		conditionBuilder.add_condition(ConditionPort::Local(port));
+	}
 
 	return objectInstaller.addFilter(filterBuilder, conditionBuilder);
 }
@@ -1,6 +1,10 @@
 #pragma once
 
 #include <winfw/rules/ifirewallrule.h>
+#include <winfw/rules/shared.h>
+#include <winfw/winfw.h>
+#include <cstdint>
+#include <vector>
 
 namespace rules::baseline
 {
@@ -9,7 +13,10 @@ class PermitLanService : public IFirewallRule
 {
 public:
 
-	PermitLanService() = default;
+	// Permits inbound connections from the given private networks, or from all private address
+	// ranges if `networks` is empty. If `ports` is not empty, only connections to those local ports
+	// are permitted.
+	PermitLanService(const std::vector<WinFwIpNetwork> &networks, const std::vector<uint16_t> &ports);
 	~PermitLanService() = default;
 	
 	bool apply(IObjectInstaller &objectInstaller) override;
@@ -18,6 +25,10 @@ private:
 
 	bool applyIpv4(IObjectInstaller &objectInstaller) const;
 	bool applyIpv6(IObjectInstaller &objectInstaller) const;
+
+	NetworkSet m_networksIpv4;
+	NetworkSet m_networksIpv6;
+	std::vector<uint16_t> m_ports;
 };
 
 }
@@ -39,6 +39,35 @@ void SplitAddresses(const IpSet &in, IpSet &outIpv4, IpSet &outIpv6)
 	}
 }
 
+void SplitNetworks(const std::vector<WinFwIpNetwork> &in, NetworkSet &outIpv4, NetworkSet &outIpv6)
+{
+	outIpv4.clear();
+	outIpv6.clear();
+
+	for (const auto &network : in)
+	{
+		const wfp::IpAddress address(network.ip);
+
+		switch (address.type())
+		{
+			case wfp::IpAddress::Type::Ipv4:
+			{
+				outIpv4.emplace_back(address, network.prefix);
+				break;
+			}
+			case wfp::IpAddress::Type::Ipv6:
+			{
+				outIpv6.emplace_back(address, network.prefix);
+				break;
+			}
+			default:
+			{
+				THROW_ERROR("Missing case handler in switch clause");
+			}
+		}
+	}
+}
+
 std::unique_ptr<wfp::conditions::ConditionProtocol> CreateProtocolCondition(WinFwProtocol protocol)
 {
 	switch (protocol)
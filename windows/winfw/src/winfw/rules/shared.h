@@ -5,6 +5,7 @@
 #include <winfw/winfw.h>
 #include <libwfp/conditions/conditionprotocol.h>
 #include <libwfp/ipaddress.h>
+#include <libwfp/ipnetwork.h>
 
 namespace rules
 {
@@ -13,6 +14,10 @@ using IpSet = std::vector<wfp::IpAddress>;
 
 void SplitAddresses(const IpSet &in, IpSet &outIpv4, IpSet &outIpv6);
 
+using NetworkSet = std::vector<wfp::IpNetwork>;
+
+void SplitNetworks(const std::vector<WinFwIpNetwork> &in, NetworkSet &outIpv4, NetworkSet &outIpv6);
+
 std::unique_ptr<wfp::conditions::ConditionProtocol> CreateProtocolCondition(WinFwProtocol protocol);
 
 }
@@ -218,7 +218,9 @@ WinFw_ApplyPolicyConnecting(
 	size_t relayClientsLen,
 	const wchar_t *tunnelInterfaceAlias,
 	const WinFwAllowedEndpoint *allowedEndpoint,
-	const WinFwAllowedTunnelTraffic *allowedTunnelTraffic
+	const WinFwAllowedTunnelTraffic *allowedTunnelTraffic,
+	const WinFwIpNetwork *excludedDestinations,
+	size_t numExcludedDestinations
 )
 {
 	if (nullptr == g_fwContext)
@@ -243,6 +245,11 @@ WinFw_ApplyPolicyConnecting(
 			THROW_ERROR("Invalid argument: allowedTunnelTraffic");
 		}
 
+		if (nullptr == excludedDestinations && 0 != numExcludedDestinations)
+		{
+			THROW_ERROR("Invalid argument: excludedDestinations");
+		}
+
 		std::vector<std::wstring> relayClientWstrings;
 		relayClientWstrings.reserve(relayClientsLen);
 		for(int i = 0; i < relayClientsLen; i++) {
@@ -255,7 +262,8 @@ WinFw_ApplyPolicyConnecting(
 			relayClientWstrings,
 			tunnelInterfaceAlias != nullptr ? std::make_optional(tunnelInterfaceAlias) : std::nullopt,
 			MakeOptional(allowedEndpoint),
-			*allowedTunnelTraffic
+			*allowedTunnelTraffic,
+			std::vector<WinFwIpNetwork>(excludedDestinations, excludedDestinations + numExcludedDestinations)
 		) ? WINFW_POLICY_STATUS_SUCCESS : WINFW_POLICY_STATUS_GENERAL_FAILURE;
 	}
 	catch (common::error::WindowsException &err)
@@ -289,7 +297,9 @@ WinFw_ApplyPolicyConnected(
 	const wchar_t * const *tunnelDnsServers,
 	size_t numTunnelDnsServers,
 	const wchar_t * const *nonTunnelDnsServers,
-	size_t numNonTunnelDnsServers
+	size_t numNonTunnelDnsServers,
+	const WinFwIpNetwork *excludedDestinations,
+	size_t numExcludedDestinations
 )
 {
 	if (nullptr == g_fwContext)
@@ -324,6 +334,11 @@ WinFw_ApplyPolicyConnected(
 			THROW_ERROR("Invalid argument: nonTunnelDnsServers");
 		}
 
+		if (nullptr == excludedDestinations && 0 != numExcludedDestinations)
+		{
+			THROW_ERROR("Invalid argument: excludedDestinations");
+		}
+
 		std::vector<wfp::IpAddress> convertedTunnelDnsServers;
 		std::vector<wfp::IpAddress> convertedNonTunnelDnsServers;
 
@@ -375,7 +390,8 @@ WinFw_ApplyPolicyConnected(
 			relayClientWstrings,
 			tunnelInterfaceAlias,
 			convertedTunnelDnsServers,
-			convertedNonTunnelDnsServers
+			convertedNonTunnelDnsServers,
+			std::vector<WinFwIpNetwork>(excludedDestinations, excludedDestinations + numExcludedDestinations)
 		) ? WINFW_POLICY_STATUS_SUCCESS : WINFW_POLICY_STATUS_GENERAL_FAILURE;
 	}
 	catch (common::error::WindowsException &err)
@@ -19,13 +19,34 @@
 // Structures
 ///////////////////////////////////////////////////////////////////////////////
 
+typedef struct tag_WinFwIpNetwork
+{
+	const wchar_t *ip;
+	uint8_t prefix;
+}
+WinFwIpNetwork;
+
 typedef struct tag_WinFwSettings
 {
 	// Permit outbound DHCP requests and inbound DHCP responses on all interfaces.
 	bool permitDhcp;
 
-	// Permit all traffic to and from private address ranges.
+	// Permit traffic to and from private address ranges.
 	bool permitLan;
+
+	// Private networks to permit when `permitLan` is set.
+	// If empty, all private address ranges are permitted.
+	const WinFwIpNetwork *lanNetworks;
+	size_t numLanNetworks;
+
+	// Local ports that accept inbound connections from the LAN when `permitLan` is set.
+	// If empty, inbound connections to any port are permitted.
+	const uint16_t *lanInboundPorts;
+	size_t numLanInboundPorts;
+
+	// Networks to permit all traffic to and from, regardless of `permitLan`.
+	const WinFwIpNetwork *allowedNetworks;
+	size_t numAllowedNetworks;
 }
 WinFwSettings;
 
@@ -156,6 +177,7 @@ enum WINFW_POLICY_STATUS
 // - What is specified by settings
 // - Communication with the relay server
 // - Specified in-tunnel traffic, except DNS.
+// - Non-DNS traffic to any network in 'excludedDestinations'
 //
 extern "C"
 WINFW_LINKAGE
@@ -168,7 +190,9 @@ WinFw_ApplyPolicyConnecting(
 	size_t relayClientLen,
 	const wchar_t *tunnelInterfaceAlias,
 	const WinFwAllowedEndpoint *allowedEndpoint,
-	const WinFwAllowedTunnelTraffic *allowedTunnelTraffic
+	const WinFwAllowedTunnelTraffic *allowedTunnelTraffic,
+	const WinFwIpNetwork *excludedDestinations,
+	size_t numExcludedDestinations
 );
 
 //
@@ -180,6 +204,7 @@ WinFw_ApplyPolicyConnecting(
 // - Non-DNS traffic inside the VPN tunnel
 // - DNS requests inside the VPN tunnel to any server in 'tunnelDnsServers'
 // - DNS requests outside the VPN tunnel to any server in 'nonTunnelDnsServers'
+// - Non-DNS traffic to any network in 'excludedDestinations'
 //
 // Parameters:
 //
@@ -199,7 +224,9 @@ WinFw_ApplyPolicyConnected(
 	const wchar_t * const *tunnelDnsServers,
 	size_t numTunnelDnsServers,
 	const wchar_t * const *nonTunnelDnsServers,
-	size_t numNonTunnelDnsServers
+	size_t numNonTunnelDnsServers,
+	const WinFwIpNetwork *excludedDestinations,
+	size_t numExcludedDestinations
 );
 
 //